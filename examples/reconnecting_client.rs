@@ -0,0 +1,92 @@
+use lunatic::ap::{AbstractProcess, Config};
+use lunatic::{abstract_process, Mailbox};
+
+/// An open connection to a server.
+struct Session {
+    id: u32,
+    sent: Vec<String>,
+}
+
+/// A client whose state is either connected or waiting to reconnect.
+enum Conn {
+    Disconnected { attempts: u32 },
+    Connected(Session),
+}
+
+#[abstract_process]
+impl Conn {
+    #[init]
+    fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+        Ok(Conn::Disconnected { attempts: 0 })
+    }
+
+    #[terminate]
+    fn terminate(self) {
+        if let Conn::Connected(session) = self {
+            println!(
+                "Closing session {} after {} lines",
+                session.id,
+                session.sent.len()
+            );
+        }
+    }
+
+    #[handle_message]
+    fn connect(self) -> Self {
+        match self {
+            Conn::Disconnected { attempts } => Conn::Connected(Session {
+                id: attempts + 1,
+                sent: Vec::new(),
+            }),
+            connected => connected,
+        }
+    }
+
+    #[handle_message]
+    fn connection_lost(self) -> Self {
+        match self {
+            Conn::Connected(session) => Conn::Disconnected {
+                attempts: session.id,
+            },
+            disconnected => disconnected,
+        }
+    }
+
+    #[handle_request]
+    fn write_line(&mut self, line: String) -> Result<usize, String> {
+        match self {
+            Conn::Connected(session) => {
+                session.sent.push(line);
+                Ok(session.sent.len())
+            }
+            Conn::Disconnected { .. } => Err("not connected".to_owned()),
+        }
+    }
+
+    #[handle_request]
+    fn session_id(&self) -> Option<u32> {
+        match self {
+            Conn::Connected(session) => Some(session.id),
+            Conn::Disconnected { .. } => None,
+        }
+    }
+}
+
+#[lunatic::main]
+fn main(_: Mailbox<()>) {
+    let conn = Conn::link().start(()).unwrap();
+    assert!(conn.write_line("hello".to_owned()).is_err());
+
+    conn.connect();
+    assert_eq!(conn.session_id(), Some(1));
+    assert_eq!(conn.write_line("hello".to_owned()), Ok(1));
+
+    // Reconnecting starts a new session.
+    conn.connection_lost();
+    assert_eq!(conn.session_id(), None);
+    conn.connect();
+    assert_eq!(conn.session_id(), Some(2));
+    assert_eq!(conn.write_line("world".to_owned()), Ok(1));
+
+    conn.shutdown();
+}
@@ -0,0 +1,82 @@
+// Starts two processes that need a reference to each other, with the
+// two-phase start of a supervisor. Each of them gets the reference of the
+// other one in its `SiblingRefs`, after both were started, and they play a
+// few rounds of ping-pong.
+use std::time::Duration;
+
+use lunatic::ap::handlers::Message;
+use lunatic::ap::{AbstractProcess, Config, MessageHandler, ProcessRef, State};
+use lunatic::serializer::Bincode;
+use lunatic::supervisor::{ChildId, SiblingRefs, Siblings, Supervisor, SupervisorConfig};
+use lunatic::{sleep, Mailbox};
+use serde::{Deserialize, Serialize};
+
+struct Player {
+    name: String,
+    opponent_id: ChildId,
+    opponent: Option<ProcessRef<Player>>,
+}
+
+impl AbstractProcess for Player {
+    /// Names of the player and of its opponent.
+    type Arg = (String, String);
+    type State = Self;
+    type Serializer = Bincode;
+    type Handlers = (Message<SiblingRefs>, Message<Ball>);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, (name, opponent): (String, String)) -> Result<Self, ()> {
+        // The opponent could still be starting, its reference arrives with
+        // the `SiblingRefs`.
+        Ok(Player {
+            name,
+            opponent_id: ChildId::named(opponent),
+            opponent: None,
+        })
+    }
+}
+
+impl MessageHandler<SiblingRefs> for Player {
+    fn handle(mut state: State<Self>, siblings: SiblingRefs) {
+        state.opponent = siblings.get(&state.opponent_id);
+    }
+}
+
+/// The ball, with the number of hits left.
+#[derive(Serialize, Deserialize)]
+struct Ball(u32);
+
+impl MessageHandler<Ball> for Player {
+    fn handle(state: State<Self>, Ball(hits): Ball) {
+        println!("{} hits the ball, {hits} hits left", state.name);
+        if let (Some(opponent), true) = (state.opponent, hits > 0) {
+            opponent.send(Ball(hits - 1));
+        }
+    }
+}
+
+struct Table;
+
+impl Supervisor for Table {
+    type Arg = ();
+    type Children = (Player, Player);
+
+    fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+        config.set_args((
+            ("ping".to_owned(), "pong".to_owned()),
+            ("pong".to_owned(), "ping".to_owned()),
+        ));
+        config.set_ids((Some("ping".to_owned()), Some("pong".to_owned())));
+        config.set_siblings((Some(Siblings::new()), Some(Siblings::new())));
+    }
+}
+
+#[lunatic::main]
+fn main(_: Mailbox<()>) {
+    let table = Table::start(()).unwrap();
+    // Both players received their `SiblingRefs` before the supervisor
+    // returns their references.
+    let (ping, _) = table.children();
+    ping.send(Ball(6));
+    sleep(Duration::from_millis(100));
+}
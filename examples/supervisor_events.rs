@@ -0,0 +1,72 @@
+// Counts crashes and restarts of supervised children as metrics.
+//
+// Lunatic with metrics enabled is required (enabled by default), run it with
+// the --prometheus flag to collect them.
+use std::time::Duration;
+
+use lunatic::ap::{AbstractProcess, Config, MessageHandler, State};
+use lunatic::metrics::increment_counter;
+use lunatic::serializer::Bincode;
+use lunatic::supervisor::{Supervisor, SupervisorConfig, SupervisorEvent};
+use lunatic::{sleep, spawn_link, Mailbox};
+
+struct Worker;
+
+impl AbstractProcess for Worker {
+    type Arg = ();
+    type State = Self;
+    type Serializer = Bincode;
+    type Handlers = (lunatic::ap::handlers::Message<Crash>,);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+        Ok(Worker)
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Crash;
+
+impl MessageHandler<Crash> for Worker {
+    fn handle(_: State<Self>, _: Crash) {
+        panic!("worker crashed");
+    }
+}
+
+struct Sup;
+
+impl Supervisor for Sup {
+    type Arg = ();
+    type Children = (Worker,);
+
+    fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+        config.set_args(((),));
+    }
+}
+
+#[lunatic::main]
+fn main(_: Mailbox<()>) {
+    let sup = Sup::start(()).unwrap();
+
+    // Listen to the events of the supervisor and count them.
+    let listener = spawn_link!(|mailbox: Mailbox<SupervisorEvent>| loop {
+        match mailbox.receive() {
+            SupervisorEvent::ChildCrashed { child, .. } => {
+                println!("{} crashed", child.type_name);
+                increment_counter("lunatic::supervisor_example::crashes");
+            }
+            SupervisorEvent::ChildRestarted { child, .. } => {
+                println!("restarted, {} restarts so far", child.restarts);
+                increment_counter("lunatic::supervisor_example::restarts");
+            }
+            _ => {}
+        }
+    });
+    sup.subscribe_events(listener);
+
+    for _ in 0..3 {
+        let (worker,) = sup.children();
+        worker.send(Crash);
+        sleep(Duration::from_millis(100));
+    }
+}
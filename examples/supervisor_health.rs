@@ -0,0 +1,102 @@
+// Serves the health of a supervision tree over TCP, e.g. for the liveness
+// probe of a Kubernetes pod:
+//
+//     livenessProbe:
+//       httpGet:
+//         path: /health
+//         port: 8080
+//
+// Each request is answered with `200 OK` if all processes of the tree are
+// healthy and with `503 Service Unavailable` otherwise, with the report as
+// body.
+use std::io::{BufRead, BufReader, Write};
+
+use lunatic::ap::{AbstractProcess, Config, ProcessRef};
+use lunatic::serializer::Bincode;
+use lunatic::supervisor::{HealthReport, Supervisor, SupervisorConfig};
+use lunatic::{net, Mailbox, Process};
+
+struct Worker;
+
+impl AbstractProcess for Worker {
+    type Arg = ();
+    type State = Self;
+    type Serializer = Bincode;
+    type Handlers = ();
+    type StartupError = ();
+
+    fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+        Ok(Worker)
+    }
+}
+
+struct Workers;
+
+impl Supervisor for Workers {
+    type Arg = ();
+    type Children = (Worker, Worker);
+
+    fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+        config.set_args(((), ()));
+    }
+}
+
+struct App;
+
+impl Supervisor for App {
+    type Arg = ();
+    type Children = (Worker, Workers);
+
+    fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+        config.set_args(((), ()));
+    }
+}
+
+#[lunatic::main]
+fn main(_: Mailbox<()>) {
+    let app = App::start(()).unwrap();
+
+    let listener = net::TcpListener::bind("127.0.0.1:8080").unwrap();
+    println!("Health on http://{}/health", listener.local_addr().unwrap());
+    while let Ok((tcp_stream, _peer)) = listener.accept() {
+        Process::spawn((tcp_stream, app), handle);
+    }
+}
+
+fn handle((mut tcp_stream, app): (net::TcpStream, ProcessRef<App>), _: Mailbox<()>) {
+    // Only the request line is needed, every path answers with the health.
+    let mut request_line = String::new();
+    if BufReader::new(tcp_stream.clone())
+        .read_line(&mut request_line)
+        .is_err()
+    {
+        return;
+    }
+    let report = app.health();
+    let status = if report.is_healthy() {
+        "200 OK"
+    } else {
+        "503 Service Unavailable"
+    };
+    let mut body = String::new();
+    write_report(&mut body, &report, 0);
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    tcp_stream.write_all(response.as_bytes()).ok();
+}
+
+/// Writes one line per process of the tree, indented by its depth.
+fn write_report(body: &mut String, report: &HealthReport, depth: usize) {
+    let name = report.name.as_deref().unwrap_or(&report.type_name);
+    body.push_str(&format!(
+        "{:indent$}{name}: {:?}\n",
+        "",
+        report.health,
+        indent = depth * 2
+    ));
+    for child in &report.children {
+        write_report(body, child, depth + 1);
+    }
+}
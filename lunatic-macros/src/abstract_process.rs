@@ -2,7 +2,7 @@ use std::iter::repeat;
 
 use convert_case::{Case, Casing};
 use proc_macro2::TokenStream;
-use quote::{format_ident, quote};
+use quote::{format_ident, quote, quote_spanned};
 use syn::parse::{Parse, ParseStream};
 use syn::spanned::Spanned;
 use syn::{FnArg, PathArguments, Token, Type};
@@ -12,24 +12,50 @@ pub struct AbstractProcess {
     args: Args,
     /// Original impl item.
     item_impl: syn::ItemImpl,
-    /// Arg type in abstract process implementation.
-    arg_ty: syn::Type,
-    /// `init` method.
-    init: syn::ImplItemMethod,
+    /// Arg type in abstract process implementation, `None` for extensions.
+    arg_ty: Option<syn::Type>,
+    /// `init` method, `None` for extensions.
+    init: Option<syn::ImplItemMethod>,
+    /// Warm up method, its result is passed to `init`.
+    warm_up: Option<syn::ImplItemMethod>,
     /// Terminate method.
     terminate: Option<syn::ImplItemMethod>,
+    /// Time the terminate method has to finish, set with
+    /// `#[terminate(timeout = "5s")]`.
+    terminate_timeout_ms: Option<u64>,
     /// Handle link died method.
     handle_link_death: Option<syn::ImplItemMethod>,
+    /// Snapshot method, used to migrate the process.
+    snapshot: Option<syn::ImplItemMethod>,
     /// Message handler methods.
     message_handlers: Vec<syn::ImplItemMethod>,
     /// Request handler methods.
     request_handlers: Vec<syn::ImplItemMethod>,
-    /// Deferred request handler methods.
+    /// Deferred request handler methods, including the ones marked with
+    /// `#[handle_responder_request]`.
     deferred_request_handlers: Vec<syn::ImplItemMethod>,
+    /// Deferred request handlers taking a `Responder`.
+    responder_handlers: Vec<syn::Ident>,
+    /// Request handler methods marked with `#[continue_with]`.
+    continued_request_handlers: Vec<ContinuedHandler>,
+    /// Message handler methods marked with `#[output]`.
+    output_handlers: Vec<syn::Ident>,
+    /// Arguments of handler attributes, e.g. `#[handle_request(name = "...")]`,
+    /// indexed by the method name.
+    handler_args: Vec<(syn::Ident, HandlerArgs)>,
+    /// Handlers removed with `#[removed_handler(...)]`, keeping their ids.
+    removed_handlers: Vec<RemovedHandlerArgs>,
     /// Name of trait wrapping messages
     message_trait_name: syn::Ident,
     /// Name of trait wrapping requests
     request_trait_name: syn::Ident,
+    /// Name of the mock process reference, if enabled with `mock = true`.
+    mock_name: Option<syn::Ident>,
+    /// Module holding the client-facing items, set with `client_mod`.
+    client_mod: Option<syn::Ident>,
+    /// Type the impl block is for, re-exported from the client module. `None`
+    /// if it's not a single identifier.
+    client_marker: Option<syn::Ident>,
 }
 
 impl AbstractProcess {
@@ -54,13 +80,35 @@ impl AbstractProcess {
                 ))
             }
         };
+        let handler_args = parse_handler_args(&item_impl)?;
+        // The attributes are removed from the methods below.
+        let responder_handlers: Vec<syn::Ident> = item_impl
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                syn::ImplItem::Method(method) => Some(method),
+                _ => None,
+            })
+            .filter(|method| {
+                method
+                    .attrs
+                    .iter()
+                    .any(|attr| attr.path.is_ident("handle_responder_request"))
+            })
+            .map(|method| method.sig.ident.clone())
+            .collect();
+        let removed_handlers = take_removed_handlers(&mut item_impl)?;
         let (
             init,
+            warm_up,
             terminate,
             handle_link_death,
+            snapshot,
             message_handlers,
             request_handlers,
             deferred_request_handlers,
+            continue_with,
+            output_handlers,
         ) = item_impl
             .items
             .clone()
@@ -84,139 +132,500 @@ impl AbstractProcess {
                                 .map(|item_attr| (i, item_attr))
                         })?;
                 // We found an attribute, we should remove it from the original item_impl
-                impl_item_method.attrs.remove(j);
+                let attr = impl_item_method.attrs.remove(j);
                 if let syn::ImplItem::Method(impl_item_method) = item_impl.items.get_mut(i).unwrap()
                 {
                     impl_item_method.attrs.remove(j);
                 }
 
-                Some((item_attr, impl_item_method))
+                // `#[continue_with(method)]` and `#[output]` are only modifiers, remove them too.
+                let original = item_impl.items.get_mut(i).unwrap();
+                let continue_with =
+                    take_modifier(&mut impl_item_method, original, "continue_with")
+                        .map(|attr| attr.parse_args::<syn::Ident>());
+                let output = take_modifier(&mut impl_item_method, original, "output").is_some();
+
+                Some((item_attr, attr, impl_item_method, continue_with, output))
             })
             .fold(
-                Ok((None, None, None, Vec::new(), Vec::new(), Vec::new())),
-                |acc, (item_attr, impl_item_method)| {
+                Ok((None, None, None, None, None, Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new())),
+                |acc, (item_attr, attr, impl_item_method, continue_with, output)| {
                     let (
                         mut init,
+                        mut warm_up,
                         mut terminate,
                         mut handle_link_death,
+                        mut snapshot,
                         mut message_handlers,
                         mut request_handlers,
                         mut deferred_request_handlers,
+                        mut continue_with_handlers,
+                        mut output_handlers,
                     ) = acc?;
 
-                    match item_attr {
-                        ItemAttr::Init => {
-                            if init.is_some() {
+                    let continue_with = continue_with.transpose()?;
+                    if continue_with.is_some() && !matches!(item_attr, ItemAttr::HandleRequest) {
+                        return Err(syn::Error::new(
+                            impl_item_method.sig.ident.span(),
+                            "continue_with can only be used on request handlers",
+                        ));
+                    }
+                    if takes_self_by_value(&impl_item_method.sig) {
+                        match item_attr {
+                            ItemAttr::Init | ItemAttr::Terminate => {}
+                            ItemAttr::HandleMessage => {
+                                if output
+                                    || matches!(impl_item_method.sig.output, syn::ReturnType::Default)
+                                {
+                                    return Err(syn::Error::new(
+                                        impl_item_method.sig.ident.span(),
+                                        "message handlers taking `self` by value need to return the new state",
+                                    ));
+                                }
+                            }
+                            _ => {
                                 return Err(syn::Error::new(
                                     impl_item_method.sig.ident.span(),
-                                    "init method already defined",
+                                    "only message handlers can take `self` by value",
                                 ));
                             }
+                        }
+                    }
+                    if output {
+                        if !matches!(item_attr, ItemAttr::HandleMessage) {
+                            return Err(syn::Error::new(
+                                impl_item_method.sig.ident.span(),
+                                "output can only be used on message handlers",
+                            ));
+                        }
+                        output_handlers.push(impl_item_method.sig.ident.clone());
+                    }
+
+                    match item_attr {
+                        ItemAttr::Init => {
+                            if let Some(previous) = &init {
+                                return Err(already_defined("init", previous, &impl_item_method));
+                            }
 
                             init = Some(impl_item_method);
                         }
+                        ItemAttr::WarmUp => {
+                            if let Some(previous) = &warm_up {
+                                return Err(already_defined("warm_up", previous, &impl_item_method));
+                            }
+
+                            check_warm_up(&impl_item_method)?;
+                            warm_up = Some(impl_item_method);
+                        }
                         ItemAttr::Terminate => {
-                            if terminate.is_some() {
-                                return Err(syn::Error::new(
-                                    impl_item_method.sig.ident.span(),
-                                    "terminate method already defined",
-                                ));
+                            if let Some((previous, _)) = &terminate {
+                                return Err(already_defined("terminate", previous, &impl_item_method));
                             }
 
-                            terminate = Some(impl_item_method);
+                            let timeout_ms = parse_terminate_args(&attr)?;
+                            terminate = Some((impl_item_method, timeout_ms));
                         }
                         ItemAttr::HandleLinkTrapped => {
-                            if handle_link_death.is_some() {
-                                return Err(syn::Error::new(
-                                    impl_item_method.sig.ident.span(),
-                                    "handle_link_death method already defined",
-                                ));
+                            if let Some(previous) = &handle_link_death {
+                                return Err(already_defined("handle_link_death", previous, &impl_item_method));
                             }
 
+                            check_handle_link_death(&impl_item_method)?;
                             handle_link_death = Some(impl_item_method);
                         }
+                        ItemAttr::Snapshot => {
+                            if let Some(previous) = &snapshot {
+                                return Err(already_defined("snapshot", previous, &impl_item_method));
+                            }
+
+                            snapshot = Some(impl_item_method);
+                        }
                         ItemAttr::HandleMessage => {
                             message_handlers.push(impl_item_method);
                         }
-                        ItemAttr::HandleRequest => {
-                            request_handlers.push(impl_item_method);
-                        }
+                        ItemAttr::HandleRequest => match continue_with {
+                            Some(continuation) => {
+                                continue_with_handlers.push((impl_item_method, continuation));
+                            }
+                            None => {
+                                if let syn::ReturnType::Default = impl_item_method.sig.output {
+                                    return Err(syn::Error::new(
+                                        impl_item_method.sig.ident.span(),
+                                        "request handlers need to return a response, \
+                                         add a return type or use `#[handle_message]`",
+                                    ));
+                                }
+                                request_handlers.push(impl_item_method);
+                            }
+                        },
                         ItemAttr::HandleDeferredRequest => {
                             deferred_request_handlers.push(impl_item_method);
                         }
+                        ItemAttr::HandleResponderRequest => {
+                            check_responder_request(&impl_item_method)?;
+                            deferred_request_handlers.push(impl_item_method);
+                        }
                     }
 
                     Ok((
                         init,
+                        warm_up,
                         terminate,
                         handle_link_death,
+                        snapshot,
                         message_handlers,
                         request_handlers,
                         deferred_request_handlers,
+                        continue_with_handlers,
+                        output_handlers,
                     ))
                 },
             )?;
+        let (terminate, terminate_timeout_ms) = match terminate {
+            Some((terminate, timeout_ms)) => (Some(terminate), timeout_ms),
+            None => (None, None),
+        };
 
-        let init =
-            init.ok_or_else(|| syn::Error::new(item_impl.self_ty.span(), "missing init method"))?;
-        let arg_ty = match init
-            .sig
-            .inputs
-            .last()
-            .ok_or_else(|| syn::Error::new(init.sig.span(), "init must take 2 arguments"))?
-        {
-            syn::FnArg::Receiver(_) => {
-                return Err(syn::Error::new(init.sig.span(), "init cannot take `&self`"))
+        // Look up the methods continuing the yielding request handlers.
+        let continued_request_handlers = continue_with
+            .into_iter()
+            .map(|(handler, continuation_ident)| {
+                let continuation = item_impl
+                    .items
+                    .iter()
+                    .find_map(|item| match item {
+                        syn::ImplItem::Method(method) if method.sig.ident == continuation_ident => {
+                            Some(method.clone())
+                        }
+                        _ => None,
+                    })
+                    .ok_or_else(|| {
+                        syn::Error::new(
+                            continuation_ident.span(),
+                            format!("continuation method `{continuation_ident}` not found"),
+                        )
+                    })?;
+                if filter_typed_args(continuation.sig.inputs.iter())
+                    .next()
+                    .is_none()
+                {
+                    return Err(syn::Error::new(
+                        continuation.sig.span(),
+                        "continuation method must take the pending state as first argument",
+                    ));
+                }
+                Ok(ContinuedHandler {
+                    handler,
+                    continuation,
+                })
+            })
+            .collect::<syn::Result<Vec<_>>>()?;
+
+        if let Some(extend) = &args.extend {
+            check_extension(
+                &args,
+                &item_impl,
+                [&init, &warm_up, &terminate, &handle_link_death, &snapshot],
+            )?;
+            extend.parse::<syn::Ident>()?;
+        }
+        if args.export_schema() && !args.extensions.is_empty() {
+            return Err(syn::Error::new(
+                args.export_schema.span(),
+                "`export_schema` can't describe the handlers of extensions",
+            ));
+        }
+        let arg_ty = match (&init, &args.extend) {
+            (None, Some(_)) => None,
+            (None, None) => {
+                return Err(syn::Error::new(
+                    item_impl.self_ty.span(),
+                    "missing `#[init]` method, add \
+                     `#[init] fn init(config: Config<Self>, arg: Arg) -> Result<Self, Error>`",
+                ))
             }
-            syn::FnArg::Typed(typed_arg) => *typed_arg.ty.clone(),
+            // With a `#[warm_up]` method, its result follows the argument.
+            (Some(init), _) => match init
+                .sig
+                .inputs
+                .iter()
+                .rev()
+                .nth(warm_up.is_some() as usize)
+                .ok_or_else(|| match warm_up {
+                    Some(_) => syn::Error::new(init.sig.span(), "init must take 3 arguments"),
+                    None => syn::Error::new(init.sig.span(), "init must take 2 arguments"),
+                })?
+            {
+                syn::FnArg::Receiver(_) => {
+                    return Err(syn::Error::new(init.sig.span(), "init cannot take `&self`"))
+                }
+                syn::FnArg::Typed(typed_arg) => Some(*typed_arg.ty.clone()),
+            },
         };
 
+        let trait_prefix = args
+            .trait_name
+            .as_ref()
+            .map(|trait_name| format_ident!("{}", trait_name.value()))
+            .unwrap_or_else(|| self_ident.clone());
         let message_trait_name = args
             .message_trait_name
             .as_ref()
             .map(|message_trait_name| format_ident!("{}", message_trait_name.value()))
-            .unwrap_or_else(|| format_ident!("{}Messages", self_ident));
+            .unwrap_or_else(|| format_ident!("{}Messages", trait_prefix));
         let request_trait_name = args
             .request_trait_name
             .as_ref()
             .map(|request_trait_name| format_ident!("{}", request_trait_name.value()))
-            .unwrap_or_else(|| format_ident!("{}Requests", self_ident));
+            .unwrap_or_else(|| format_ident!("{}Requests", trait_prefix));
+        let mock_name = args
+            .mock
+            .as_ref()
+            .filter(|mock| mock.value)
+            .map(|_| format_ident!("Mock{}Ref", self_ident));
+        let client_mod = args.client_mod.as_ref().map(parse_client_mod).transpose()?;
+        let client_marker = match &*item_impl.self_ty {
+            syn::Type::Path(ty_path) if ty_path.qself.is_none() => {
+                ty_path.path.get_ident().cloned()
+            }
+            _ => None,
+        };
 
-        Ok(AbstractProcess {
+        let ap = AbstractProcess {
             args,
             item_impl,
             arg_ty,
             init,
+            warm_up,
             terminate,
+            terminate_timeout_ms,
             handle_link_death,
+            snapshot,
             message_handlers,
             request_handlers,
             deferred_request_handlers,
+            responder_handlers,
+            continued_request_handlers,
+            output_handlers,
+            handler_args,
+            removed_handlers,
             message_trait_name,
             request_trait_name,
-        })
+            mock_name,
+            client_mod,
+            client_marker,
+        };
+        ap.check_wrapper_collisions()?;
+        ap.check_defaults()?;
+        ap.check_mock()?;
+        ap.check_companion_collisions()?;
+        ap.check_client_mod()?;
+        ap.check_replies()?;
+        ap.check_ids()?;
+        ap.check_priorities()?;
+        Ok(ap)
+    }
+
+    /// Returns an error pointing at both handlers if two of them would
+    /// generate a wrapper type with the same name.
+    fn check_wrapper_collisions(&self) -> syn::Result<()> {
+        let methods = self
+            .message_handlers
+            .iter()
+            .chain(&self.request_handlers)
+            .chain(&self.deferred_request_handlers)
+            .chain(self.continued_request_handlers.iter().flat_map(
+                |ContinuedHandler {
+                     handler,
+                     continuation,
+                 }| [handler, continuation],
+            ));
+        let mut generated: Vec<(syn::Ident, &syn::Ident)> = Vec::new();
+        for method in methods {
+            let method = &method.sig.ident;
+            let wrapper = self.handler_wrapper_ident(method);
+            if let Some((previous, previous_method)) =
+                generated.iter().find(|(previous, _)| previous == &wrapper)
+            {
+                let mut error = syn::Error::new(
+                    wrapper.span(),
+                    format!("message type `{wrapper}` is generated for both `{previous_method}` and `{method}`"),
+                );
+                error.combine(syn::Error::new(
+                    previous.span(),
+                    format!("`{previous}` is also generated for `{previous_method}` here"),
+                ));
+                return Err(error);
+            }
+            generated.push((wrapper, method));
+        }
+        Ok(())
     }
 
     /// Expands macro.
     pub fn expand(&self) -> TokenStream {
         let handler_wrappers = self.expand_handler_wrappers();
         let original_impl = self.expand_original_impl();
-        let impl_abstract_process = self.expand_impl_abstract_process();
+        let impl_abstract_process = match &self.args.extend {
+            Some(extend) => self.expand_handler_group(extend),
+            None => self.expand_impl_abstract_process(),
+        };
         let message_handler_impls = self.expand_message_handler_impls();
         let request_handler_impls = self.expand_request_handler_impls();
         let deferred_request_handler_impls = self.expand_deferred_request_handler_impls();
+        let continued_request_handler_impls = self.expand_continued_request_handler_impls();
+        let protocol_checks = self.expand_protocol_checks();
         let handler_trait = self.expand_handler_trait();
         let impl_handler_trait = self.expand_impl_handler_trait();
+        let mock = self.expand_mock();
+        let schema = self.args.export_schema().then(|| self.expand_schema());
 
-        quote! {
+        let client = quote! {
             #handler_wrappers
+            #handler_trait
+            #impl_handler_trait
+            #mock
+        };
+        let client = match &self.client_mod {
+            Some(client_mod) => self.expand_client_mod(client_mod, client),
+            None => client,
+        };
+
+        quote! {
+            #client
             #original_impl
+            #schema
             #impl_abstract_process
             #message_handler_impls
             #request_handler_impls
             #deferred_request_handler_impls
-            #handler_trait
-            #impl_handler_trait
+            #continued_request_handler_impls
+            #protocol_checks
+        }
+    }
+
+    /// Wraps the client-facing items in the module set with `client_mod`.
+    ///
+    /// ```ignore
+    /// mod counter_client {
+    ///     use super::*;
+    ///     pub(super) use super::Counter;
+    ///     // Message types, traits and their implementations for `ProcessRef`.
+    /// }
+    /// use self::counter_client::*;
+    /// ```
+    fn expand_client_mod(&self, client_mod: &syn::Ident, client: TokenStream) -> TokenStream {
+        let mod_vis = self.base_visibility();
+        let vis = self.client_visibility();
+        let marker = self
+            .client_marker
+            .as_ref()
+            .map(|marker| quote! { #vis use super::#marker; });
+        let doc = match &self.client_marker {
+            Some(marker) => format!(" Client of [`{marker}`], generated by `#[abstract_process]`."),
+            None => " Client generated by `#[abstract_process]`.".to_string(),
+        };
+        quote! {
+            #[doc = #doc]
+            #mod_vis mod #client_mod {
+                #[allow(unused_imports)]
+                use super::*;
+                #marker
+                #client
+            }
+            #[allow(unused_imports)]
+            #mod_vis use self::#client_mod::*;
+        }
+    }
+
+    /// Returns the visibility of the fields of message types. Inside of the
+    /// `client_mod` module and the module holding the message types they need
+    /// to be visible to the handler implementations.
+    fn field_visibility(&self) -> Option<TokenStream> {
+        match (&self.client_mod, self.messages_mod()) {
+            (None, None) => None,
+            (Some(_), None) | (None, Some(_)) => Some(quote! { pub(super) }),
+            (Some(_), Some(_)) => Some(quote! { pub(in super::super) }),
+        }
+    }
+
+    /// Returns the visibility set with `visibility = ...`, or the one of the
+    /// traits if the items have separate visibilities.
+    ///
+    /// It's used by the items that don't have their own setting, e.g. the
+    /// `client_mod` module.
+    fn base_visibility(&self) -> Option<&syn::Visibility> {
+        match &self.args.item_visibility {
+            Some(items) => items.traits.as_ref(),
+            None => self.args.visibility.as_ref(),
+        }
+    }
+
+    /// Returns the visibility of the client-facing items, the traits and,
+    /// unless they have their own visibility, the message types and the mock.
+    fn client_visibility(&self) -> TokenStream {
+        self.raise_visibility(self.base_visibility())
+    }
+
+    /// Returns the visibility of the mock.
+    fn mock_visibility(&self) -> TokenStream {
+        match &self.args.item_visibility {
+            Some(items) => self.raise_visibility(items.mock.as_ref()),
+            None => self.client_visibility(),
+        }
+    }
+
+    /// Returns the visibility of the message type declarations. Inside of the
+    /// module holding the message types they are public and the re-export
+    /// limits their visibility.
+    fn wrapper_visibility(&self) -> TokenStream {
+        match self.messages_mod() {
+            Some(_) => quote! { pub },
+            None => self.client_visibility(),
+        }
+    }
+
+    /// Returns the visibility of the items used in the `Handlers` tuple of the
+    /// process, e.g. aliases of handlers with `#[cfg]` attributes.
+    fn alias_visibility(&self) -> TokenStream {
+        match self.messages_mod() {
+            Some(_) => quote! { pub },
+            None => {
+                let vis = &self.args.visibility;
+                quote! { #vis }
+            }
+        }
+    }
+
+    /// Returns the module holding the message types if the items have separate
+    /// visibilities, e.g. `__counter_messages`.
+    ///
+    /// The message types are declared public inside of the private module, so
+    /// that the public `Handlers` type of the process can name them, and the
+    /// re-export sets their visibility.
+    fn messages_mod(&self) -> Option<syn::Ident> {
+        self.args.item_visibility.as_ref().map(|_| {
+            let name = self.message_trait_name.to_string().to_case(Case::Snake);
+            format_ident!("__{}", name)
+        })
+    }
+
+    /// Returns `vis` inside of the `client_mod` module, raised by one level so
+    /// that the items are visible to the same modules as without
+    /// `client_mod`.
+    fn raise_visibility(&self, vis: Option<&syn::Visibility>) -> TokenStream {
+        if self.client_mod.is_none() {
+            return quote! { #vis };
+        }
+        match vis {
+            None | Some(syn::Visibility::Inherited) => quote! { pub(super) },
+            Some(syn::Visibility::Restricted(restricted)) if restricted.path.is_ident("self") => {
+                quote! { pub(super) }
+            }
+            Some(syn::Visibility::Restricted(restricted)) if restricted.path.is_ident("super") => {
+                quote! { pub(in super::super) }
+            }
+            Some(vis) => quote! { #vis },
         }
     }
 
@@ -233,14 +642,87 @@ impl AbstractProcess {
             .chain(self.request_handlers.iter())
             .map(|impl_item_method| self.expand_handler_wrapper(impl_item_method, false));
 
-        // Exclude last element that is a `DeferredResponse`
+        // Exclude last element that is a `DeferredResponse` or `Resume`
         let dr_wrappers = self
             .deferred_request_handlers
             .iter()
+            .chain(
+                self.continued_request_handlers
+                    .iter()
+                    .map(|continued| &continued.handler),
+            )
             .map(|impl_item_method| self.expand_handler_wrapper(impl_item_method, true));
-        quote! {
+        let continuation_wrappers = self
+            .continued_request_handlers
+            .iter()
+            .map(|continued| self.expand_continuation_wrapper(continued));
+        let wrappers = quote! {
             #( #wrappers )*
             #( #dr_wrappers )*
+            #( #continuation_wrappers )*
+        };
+        let Some(messages_mod) = self.messages_mod() else {
+            return wrappers;
+        };
+        let msgs = self
+            .args
+            .item_visibility
+            .as_ref()
+            .and_then(|items| items.msgs.as_ref());
+        let vis = self.raise_visibility(msgs);
+        let (_, handler_aliases) = self.expand_type_handlers();
+        quote! {
+            #[doc(hidden)]
+            mod #messages_mod {
+                #[allow(unused_imports)]
+                use super::*;
+                #wrappers
+                #handler_aliases
+            }
+            #[allow(unused_imports)]
+            #vis use self::#messages_mod::*;
+        }
+    }
+
+    /// Expands the wrapper struct of the reply delivered to a continuation
+    /// method.
+    ///
+    /// The first field is the id of the pending request, followed by all
+    /// arguments except the pending state. It needs to serialize the same way
+    /// as the `(u64, Reply)` tuple sent by `Resume`.
+    ///
+    /// ```ignore
+    /// __MsgWrapFinish(u64, Reply);
+    /// ```
+    fn expand_continuation_wrapper(&self, continued: &ContinuedHandler) -> TokenStream {
+        let impl_item_method = &continued.continuation;
+        let cfg_attrs = continued.cfg_attrs();
+        let vis = self.wrapper_visibility();
+        let ident = self.handler_wrapper_ident(&impl_item_method.sig.ident);
+        let fields = filter_typed_args(impl_item_method.sig.inputs.iter())
+            .skip(1)
+            .map(|field| &*field.ty);
+        let phantom_field = if !self.item_impl.generics.params.is_empty() {
+            let phantom_type = self.phantom_type();
+            Some(quote! { #[serde(skip)] #phantom_type, })
+        } else {
+            None
+        };
+
+        let doc_hidden = self.wrapper_doc_hidden(&impl_item_method.sig.ident);
+        let decl_generics = self.decl_generics();
+
+        let field_vis = self.field_visibility();
+
+        quote! {
+            #( #cfg_attrs )*
+            #doc_hidden
+            #[derive(serde::Serialize, serde::Deserialize)]
+            #vis struct #ident #decl_generics (
+                #field_vis u64,
+                #( #field_vis #fields, )*
+                #phantom_field
+            );
         }
     }
 
@@ -249,15 +731,18 @@ impl AbstractProcess {
     /// ```ignore
     /// __MsgWrap(Param1, Param2);
     /// ```
+    ///
+    /// The wrapper is serialized as a tuple with the `MethodId` of the handler
+    /// in front of the parameters, so that a message can't be decoded by a
+    /// different handler taking the same parameters.
     fn expand_handler_wrapper(
         &self,
         impl_item_method: &syn::ImplItemMethod,
         exclude_last: bool,
     ) -> TokenStream {
-        let vis = &self.args.visibility;
-        let ident = Self::handler_wrapper_ident(&impl_item_method.sig.ident);
+        let vis = self.wrapper_visibility();
+        let ident = self.handler_wrapper_ident(&impl_item_method.sig.ident);
         let (_, ty_generics, _) = &self.item_impl.generics.split_for_impl();
-        let phantom_generics = &self.item_impl.generics.params;
         let inputs = match exclude_last {
             true => {
                 // Exclude last element.
@@ -267,20 +752,192 @@ impl AbstractProcess {
             }
             false => impl_item_method.sig.inputs.clone().into_iter().collect(),
         };
-        let fields = filter_typed_args(inputs.iter()).map(|field| &*field.ty);
-        let phantom_field = if !self.item_impl.generics.params.is_empty() {
-            Some(quote! { std::marker::PhantomData <(#phantom_generics)>, })
+        let fields: Vec<_> = filter_typed_args(inputs.iter())
+            .map(|field| owned_type(&field.ty))
+            .collect();
+        let spans: Vec<_> = filter_typed_args(inputs.iter())
+            .map(|field| field.ty.span())
+            .collect();
+        let (phantom_field, phantom_value) = if !self.item_impl.generics.params.is_empty() {
+            let phantom_type = self.phantom_type();
+            (
+                Some(quote! { #phantom_type, }),
+                Some(quote! { std::marker::PhantomData, }),
+            )
         } else {
-            None
+            (None, None)
         };
+        let offset = usize::from(phantom_field.is_some());
+        let indexes = (offset..fields.len() + offset).map(proc_macro2::Literal::usize_unsuffixed);
+        let names: Vec<_> = (0..fields.len())
+            .map(|i| format_ident!("field{}", i))
+            .collect();
+        let method_id = proc_macro2::Literal::u32_suffixed(method_id(&ident.to_string()));
 
-        quote! {
-            #[derive(serde::Serialize, serde::Deserialize)]
-            #vis struct #ident #ty_generics (
+        let mut ser_generics = self.item_impl.generics.clone();
+        ser_generics
+            .make_where_clause()
+            .predicates
+            .extend(fields.iter().map(|ty| -> syn::WherePredicate {
+                syn::parse_quote! { #ty: serde::Serialize }
+            }));
+        let (ser_impl_generics, _, ser_where_clause) = ser_generics.split_for_impl();
+        let mut de_generics = self.item_impl.generics.clone();
+        de_generics.params.insert(0, syn::parse_quote! { '__de });
+        de_generics
+            .make_where_clause()
+            .predicates
+            .extend(fields.iter().map(|ty| -> syn::WherePredicate {
+                syn::parse_quote! { #ty: serde::de::DeserializeOwned }
+            }));
+        let (de_impl_generics, _, de_where_clause) = de_generics.split_for_impl();
+
+        let doc_hidden = self.wrapper_doc_hidden(&impl_item_method.sig.ident);
+        let decl_generics = self.decl_generics();
+        let attrs = forwarded_attrs(&impl_item_method.attrs);
+        let impl_attrs = forwarded_impl_attrs(&impl_item_method.attrs);
+        let field_vis = self.field_visibility();
+        let wrapper = quote! {
+            #doc_hidden
+            #( #attrs )*
+            #vis struct #ident #decl_generics (
                 #phantom_field
-                #( #fields ),*
+                #( #field_vis #fields ),*
             );
+        };
+        if self.item_impl.generics.params.is_empty() {
+            let serde_impls = expand_wrapper_serde(
+                &ident,
+                &fields,
+                &spans,
+                method_id,
+                &impl_attrs,
+                self.args.telemetry(),
+            );
+            return quote! {
+                #wrapper
+                #serde_impls
+            };
+        }
+
+        // The trace headers follow the arguments.
+        let (trace_value, trace_name, trace_field, receive_trace) = if self.args.telemetry() {
+            (
+                Some(quote! { &lunatic::ap::TraceHeaders::current(), }),
+                Some(quote! { trace, }),
+                Some(quote! { lunatic::ap::TraceHeaders, }),
+                Some(quote! { trace.receive(); }),
+            )
+        } else {
+            (None, None, None, None)
+        };
+
+        quote! {
+            #wrapper
+
+            #( #impl_attrs )*
+            #[allow(deprecated)]
+            impl #ser_impl_generics serde::Serialize for #ident #ty_generics #ser_where_clause {
+                fn serialize<__S: serde::Serializer>(&self, serializer: __S) -> Result<__S::Ok, __S::Error> {
+                    serde::Serialize::serialize(
+                        &(lunatic::ap::handlers::MethodId::<#method_id>, #( &self.#indexes, )* #trace_value),
+                        serializer,
+                    )
+                }
+            }
+
+            #( #impl_attrs )*
+            #[allow(deprecated)]
+            impl #de_impl_generics serde::Deserialize<'__de> for #ident #ty_generics #de_where_clause {
+                fn deserialize<__D: serde::Deserializer<'__de>>(deserializer: __D) -> Result<Self, __D::Error> {
+                    let (_, #( #names, )* #trace_name): (lunatic::ap::handlers::MethodId<#method_id>, #( #fields, )* #trace_field) =
+                        serde::Deserialize::deserialize(deserializer)?;
+                    #receive_trace
+                    Ok(#ident(#phantom_value #( #names ),*))
+                }
+            }
+        }
+    }
+
+    /// Returns the expressions passing the fields of a wrapper to the handler
+    /// with the signature `sig`, e.g. `message.0, &message.1`.
+    ///
+    /// Borrowed arguments are borrowed from the owned field.
+    fn wrapper_fields(
+        &self,
+        sig: &syn::Signature,
+        wrapper: TokenStream,
+        exclude_last: bool,
+    ) -> Vec<TokenStream> {
+        let offset = usize::from(!self.item_impl.generics.params.is_empty());
+        let mut args: Vec<_> = filter_typed_args(sig.inputs.iter()).collect();
+        if exclude_last {
+            args.pop();
+        }
+        args.iter()
+            .enumerate()
+            .map(|(i, arg)| {
+                let i = proc_macro2::Literal::usize_unsuffixed(i + offset);
+                match is_borrowed(&arg.ty) {
+                    true => quote! { &#wrapper. #i },
+                    false => quote! { #wrapper. #i },
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the `PhantomData` type that marks wrapper structs as using all
+    /// generic parameters of the impl block.
+    ///
+    /// Only the names of the parameters are used, so that bounds written inside
+    /// of the angle brackets (`impl<T: Serialize>`) don't end up in a type
+    /// position.
+    fn phantom_type(&self) -> TokenStream {
+        let params = self
+            .item_impl
+            .generics
+            .params
+            .iter()
+            .filter_map(|param| match param {
+                syn::GenericParam::Type(ty) => {
+                    let ident = &ty.ident;
+                    Some(quote! { #ident })
+                }
+                syn::GenericParam::Lifetime(lifetime) => {
+                    let lifetime = &lifetime.lifetime;
+                    Some(quote! { &#lifetime () })
+                }
+                syn::GenericParam::Const(_) => None,
+            });
+        quote! { std::marker::PhantomData<( #( #params, )* )> }
+    }
+
+    /// Returns the generic parameters of the impl block for declaring
+    /// generated types and traits, e.g. `<'a, T, const N: usize>`.
+    ///
+    /// Bounds are left out like in [`Self::phantom_type`], but const
+    /// parameters need their type.
+    fn decl_generics(&self) -> TokenStream {
+        let params = &self.item_impl.generics.params;
+        if params.is_empty() {
+            return TokenStream::new();
         }
+        let params = params.iter().map(|param| match param {
+            syn::GenericParam::Type(ty) => {
+                let ident = &ty.ident;
+                quote! { #ident }
+            }
+            syn::GenericParam::Lifetime(lifetime) => {
+                let lifetime = &lifetime.lifetime;
+                quote! { #lifetime }
+            }
+            syn::GenericParam::Const(param) => {
+                let ident = &param.ident;
+                let ty = &param.ty;
+                quote! { const #ident: #ty }
+            }
+        });
+        quote! { <#( #params ),*> }
     }
 
     /// Expands the original implementation written.
@@ -312,104 +969,419 @@ impl AbstractProcess {
         } = &self.item_impl;
 
         let (impl_generics, _ty_generics, where_clause) = generics.split_for_impl();
-        let arg_ty = &self.arg_ty;
+        let arg_ty = self.arg_ty();
         let serializer = match &self.args.serializer {
             Some(serializer) => quote!(#serializer),
             None => quote!(lunatic::serializer::Bincode),
         };
-        let handlers = self.expand_type_handlers();
+        let (handlers, handler_aliases) = self.expand_type_handlers();
+        let handler_aliases = self.messages_mod().is_none().then_some(handler_aliases);
+        let extensions = &self.args.extensions;
 
         let (init_impl, startup_error) = self.expand_init_impl();
         let terminate_impl = self.expand_terminate_impl();
         let handle_link_death_impl = self.expand_handle_link_death_impl();
+        let snapshot_impl = self.expand_snapshot_impl();
+        let restart_on_panic_impl = self.expand_restart_on_panic_impl();
+        let instrument_impl = self.expand_instrument_impl();
 
         quote! {
+            #handler_aliases
+
+            #[allow(deprecated)]
             impl #impl_generics lunatic::ap::AbstractProcess for #self_ty #where_clause {
                 type State = #self_ty;
                 type Arg = #arg_ty;
                 type Serializer = #serializer;
-                type Handlers = (#handlers);
+                type Handlers = (#handlers #( lunatic::ap::handlers::Extension<#extensions>, )*);
                 type StartupError = #startup_error;
 
                 #init_impl
                 #terminate_impl
                 #handle_link_death_impl
+                #snapshot_impl
+                #restart_on_panic_impl
+                #instrument_impl
             }
         }
     }
 
-    /// Collects all wrapper types and adds them to the `AP::Handlers` tuple.
-    fn expand_type_handlers(&self) -> TokenStream {
-        let message_wrappers = self.message_handlers.iter().map(|impl_item_method| {
-            let ident = Self::handler_wrapper_ident(&impl_item_method.sig.ident);
-            let (_, generics, _) = &self.item_impl.generics.split_for_impl();
-            quote! { lunatic::ap::handlers::Message<#ident #generics>, }
-        });
-        let request_wrappers = self.request_handlers.iter().map(|impl_item_method| {
-            let ident = Self::handler_wrapper_ident(&impl_item_method.sig.ident);
-            let (_, generics, _) = &self.item_impl.generics.split_for_impl();
-            quote! { lunatic::ap::handlers::Request<#ident #generics>, }
-        });
-        let deferred_request_wrappers =
-            self.deferred_request_handlers
-                .iter()
-                .map(|impl_item_method| {
-                    let ident = Self::handler_wrapper_ident(&impl_item_method.sig.ident);
-                    let (_, generics, _) = &self.item_impl.generics.split_for_impl();
-                    quote! { lunatic::ap::handlers::DeferredRequest<#ident #generics>, }
-                });
+    /// Expands the `SCHEMA` constant describing the messages of the process,
+    /// enabled with `export_schema`.
+    ///
+    /// ```ignore
+    /// impl Counter {
+    ///     pub const SCHEMA: &'static str = r#"{"process":"Counter",...}"#;
+    /// }
+    /// ```
+    fn expand_schema(&self) -> TokenStream {
+        let syn::ItemImpl {
+            generics, self_ty, ..
+        } = &self.item_impl;
+        let (impl_generics, _, where_clause) = generics.split_for_impl();
+        let vis = self.base_visibility();
+        let serializer = match &self.args.serializer {
+            Some(serializer) => type_string(quote!(#serializer)),
+            None => "lunatic::serializer::Bincode".to_owned(),
+        };
+        let handlers: Vec<_> = self
+            .handler_positions()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, slot)| Some((i + 1, slot?)))
+            // Continuations are only sent by the process to itself.
+            .filter(|(_, slot)| !matches!(slot.kind, HandlerKind::Continuation))
+            .map(|(id, slot)| self.handler_schema(id, &slot))
+            .collect();
+        // Messages of processes with `telemetry` end with the trace headers.
+        let telemetry = match &self.args.telemetry {
+            Some(telemetry) => format!(r#","telemetry":{}"#, json_string(&telemetry.value())),
+            None => String::new(),
+        };
+        let schema = format!(
+            r#"{{"process":{},"serializer":{}{telemetry},"handlers":[{}]}}"#,
+            json_string(&type_string(quote!(#self_ty))),
+            json_string(&serializer),
+            handlers.join(",")
+        );
+        let doc = format!(
+            " JSON description of the messages handled by `{}`, generated with \
+             `export_schema`.",
+            quote!(#self_ty)
+        );
 
-        message_wrappers
-            .chain(request_wrappers)
-            .chain(deferred_request_wrappers)
-            .collect()
+        quote! {
+            impl #impl_generics #self_ty #where_clause {
+                #[doc = #doc]
+                #vis const SCHEMA: &'static str = #schema;
+            }
+        }
     }
 
-    /// Expands the `init` method in the abstract process implementation.
-    fn expand_init_impl(&self) -> (TokenStream, TokenStream) {
-        let ident = &self.init.sig.ident;
-        let arg_ty = &self.arg_ty;
-
-        let init = quote! {
-            fn init(config: lunatic::ap::Config<Self>, arg: #arg_ty) -> Result<Self::State, Self::StartupError> {
-                Self::#ident(config, arg)
+    /// Returns the JSON object describing the handler in `slot`, e.g.
+    /// `{"name":"add","id":2,"direction":"request","method_id":...,
+    /// "args":[{"name":"by","type":"u32"}],"reply":"u32"}`.
+    fn handler_schema(&self, id: usize, slot: &HandlerSlot) -> String {
+        let method = slot.method;
+        let mut args: Vec<_> = filter_typed_arg_names(method.sig.inputs.iter()).collect();
+        let reply = match slot.kind {
+            HandlerKind::Message | HandlerKind::Continuation => None,
+            HandlerKind::Request => Some(self.handler_structure((method, false)).return_ty),
+            HandlerKind::StreamRequest => {
+                stream_item(&method.sig.output).map(|item| quote! { #item })
             }
-        };
-
-        // Extract startup error type from `Result<T, Error>`.
-        let startup_error = match &self.init.sig.output {
-            syn::ReturnType::Type(_, ret_type) => {
-                match ret_type.as_ref() {
-                    Type::Path(ret_type) => match ret_type.path.segments.last() {
-                        Some(ret_type_segments) => match &ret_type_segments.arguments {
-                            PathArguments::AngleBracketed(generics) => {
-                                // Error is the last argument in `Result<T, Error>`.
-                                let last = generics.args.last();
-                                quote! { #last }
-                            }
-                            _ => quote! { () },
-                        },
-                        _ => quote! { () },
-                    },
-                    _ => quote! { () },
-                }
+            HandlerKind::DeferredRequest | HandlerKind::ResponderRequest => {
+                args.pop();
+                Some(self.handler_structure((method, true)).return_ty)
+            }
+            HandlerKind::ContinuedRequest => {
+                args.pop();
+                Some(step_response_type(&method.sig.output))
             }
-            _ => quote! { () },
         };
-        (init, startup_error)
+        let args: Vec<_> = args
+            .into_iter()
+            .map(|(name, ty)| {
+                format!(
+                    r#"{{"name":{},"type":{}}}"#,
+                    json_string(&name.to_string()),
+                    json_string(&type_string(owned_type(ty)))
+                )
+            })
+            .collect();
+        let direction = match (slot.kind, &reply) {
+            (HandlerKind::StreamRequest, _) => "stream",
+            (_, Some(_)) => "request",
+            (_, None) => "cast",
+        };
+        let reply = match reply {
+            Some(reply) => json_string(&type_string(reply)),
+            None => "null".to_owned(),
+        };
+        let wrapper = self.handler_wrapper_ident(&method.sig.ident).to_string();
+        format!(
+            r#"{{"name":{},"id":{id},"direction":"{direction}","method_id":{},"args":[{}],"reply":{reply}}}"#,
+            json_string(&method.sig.ident.to_string()),
+            method_id(&wrapper),
+            args.join(",")
+        )
     }
 
-    /// Expands the `terminate` method in the abstract process implementation.
-    fn expand_terminate_impl(&self) -> TokenStream {
-        self.terminate
-            .as_ref()
-            .map(|terminate| {
-                let ident = &terminate.sig.ident;
-
+    /// Expands the marker type registering the handlers of an impl block
+    /// marked with `extend = "StoreWrites"`.
+    ///
+    /// ```ignore
+    /// pub struct StoreWrites;
+    ///
+    /// impl HandlerGroup<Store> for StoreWrites {
+    ///     type Handlers = (Message<__MsgWrapPut>, Request<__MsgWrapDelete>);
+    /// }
+    /// ```
+    fn expand_handler_group(&self, extend: &syn::LitStr) -> TokenStream {
+        let self_ty = &self.item_impl.self_ty;
+        let vis = self.base_visibility();
+        let marker = syn::Ident::new(&extend.value(), extend.span());
+        let (handlers, handler_aliases) = self.expand_type_handlers();
+        // With separate item visibilities the aliases are declared next to
+        // the message types.
+        let handler_aliases = self.messages_mod().is_none().then_some(handler_aliases);
+        let doc = format!(
+            " Handlers of an impl block extending `{}`, add `extensions = [{marker}]` \
+             to the `#[abstract_process]` of the main impl block.",
+            quote!(#self_ty)
+        );
+
+        quote! {
+            #handler_aliases
+
+            #[doc = #doc]
+            #vis struct #marker;
+
+            #[allow(deprecated)]
+            impl lunatic::ap::handlers::HandlerGroup<#self_ty> for #marker {
+                type Handlers = (#handlers);
+            }
+        }
+    }
+
+    /// Collects all wrapper types and adds them to the `AP::Handlers` tuple.
+    ///
+    /// Returns the types of the tuple and the aliases used by handlers with
+    /// `#[cfg]` attributes.
+    fn expand_type_handlers(&self) -> (TokenStream, TokenStream) {
+        let mut aliases = Vec::new();
+        let phantom_type = self.phantom_type();
+        let handlers: Vec<_> = self
+            .handler_positions()
+            .into_iter()
+            .zip(1..)
+            .map(|(slot, id)| match slot {
+                Some(slot) => self.handler_entry(
+                    slot.kind.handler_type(),
+                    &slot.method.sig.ident,
+                    &slot.cfgs,
+                    &mut aliases,
+                ),
+                None => match self
+                    .removed_handlers
+                    .iter()
+                    .find(|removed| removed.id == id)
+                {
+                    Some(removed) => {
+                        let (entry, marker) = removed.expand(&self.alias_visibility());
+                        aliases.push(marker);
+                        entry
+                    }
+                    // Unused id in front of an explicitly assigned one.
+                    None => quote! { lunatic::ap::handlers::Disabled<#phantom_type> },
+                },
+            })
+            .collect();
+
+        (quote! { #( #handlers, )* }, quote! { #( #aliases )* })
+    }
+
+    /// Returns the handlers in the order they are added to the `AP::Handlers`
+    /// tuple if no ids are assigned.
+    fn handler_slots(&self) -> Vec<HandlerSlot<'_>> {
+        let slot = HandlerSlot::new;
+        let mut slots: Vec<_> = self
+            .message_handlers
+            .iter()
+            .map(|method| slot(HandlerKind::Message, method))
+            .chain(self.request_handlers.iter().map(|method| {
+                match stream_item(&method.sig.output) {
+                    Some(_) => slot(HandlerKind::StreamRequest, method),
+                    None => slot(HandlerKind::Request, method),
+                }
+            }))
+            .chain(self.deferred_request_handlers.iter().map(|method| {
+                match self.is_responder(&method.sig.ident) {
+                    true => slot(HandlerKind::ResponderRequest, method),
+                    false => slot(HandlerKind::DeferredRequest, method),
+                }
+            }))
+            .collect();
+        for continued in &self.continued_request_handlers {
+            slots.push(slot(HandlerKind::ContinuedRequest, &continued.handler));
+            slots.push(HandlerSlot {
+                kind: HandlerKind::Continuation,
+                method: &continued.continuation,
+                cfgs: continued.cfg_attrs(),
+            });
+        }
+        slots
+    }
+
+    /// Returns the handlers ordered by their id, starting at 1.
+    ///
+    /// Handlers with an `id` argument are placed at that position, the others
+    /// take the lowest free ids in their order. `None` marks an id that isn't
+    /// taken by any handler, including the ids of removed handlers.
+    fn handler_positions(&self) -> Vec<Option<HandlerSlot<'_>>> {
+        let (explicit, assigned): (Vec<_>, Vec<_>) = self
+            .handler_slots()
+            .into_iter()
+            .partition(|slot| self.handler_id(&slot.method.sig.ident).is_some());
+        let mut positions: Vec<Option<HandlerSlot>> = Vec::new();
+        let removed: Vec<usize> = self
+            .removed_handlers
+            .iter()
+            .map(|removed| usize::from(removed.id) - 1)
+            .collect();
+        if let Some(last) = removed.iter().max() {
+            positions.resize_with(last + 1, || None);
+        }
+        for slot in explicit {
+            let index = usize::from(self.handler_id(&slot.method.sig.ident).unwrap()) - 1;
+            if positions.len() <= index {
+                positions.resize_with(index + 1, || None);
+            }
+            positions[index] = Some(slot);
+        }
+        for slot in assigned {
+            let free = positions
+                .iter()
+                .enumerate()
+                .position(|(index, slot)| slot.is_none() && !removed.contains(&index));
+            match free {
+                Some(index) => positions[index] = Some(slot),
+                None => positions.push(Some(slot)),
+            }
+        }
+        positions
+    }
+
+    /// Returns the entry of the handler method `ident` in the `AP::Handlers`
+    /// tuple, e.g. `Message<__MsgWrapFoo>`.
+    ///
+    /// A type can't be left out of a tuple with `#[cfg]`, so handlers with
+    /// `#[cfg]` attributes use an alias that resolves to `Disabled` if the
+    /// handler is compiled out.
+    fn handler_entry(
+        &self,
+        kind: TokenStream,
+        ident: &syn::Ident,
+        cfgs: &[&syn::Attribute],
+        aliases: &mut Vec<TokenStream>,
+    ) -> TokenStream {
+        let wrapper = self.handler_wrapper_ident(ident);
+        let (_, ty_generics, _) = self.item_impl.generics.split_for_impl();
+        let entry = quote! { #kind<#wrapper #ty_generics> };
+        let Some(predicate) = cfg_predicate(cfgs) else {
+            return entry;
+        };
+
+        let vis = self.alias_visibility();
+        let alias = format_ident!("__Handler{}", wrapper);
+        let decl_generics = self.decl_generics();
+        let phantom_type = self.phantom_type();
+        aliases.push(quote! {
+            #[cfg(#predicate)]
+            #[doc(hidden)]
+            #[allow(deprecated)]
+            #vis type #alias #decl_generics = #entry;
+            #[cfg(not(#predicate))]
+            #[doc(hidden)]
+            #vis type #alias #decl_generics = lunatic::ap::handlers::Disabled<#phantom_type>;
+        });
+        quote! { #alias #ty_generics }
+    }
+
+    /// Returns the `init` method. Only extensions don't have one, and they
+    /// don't implement `AbstractProcess`.
+    fn init(&self) -> &syn::ImplItemMethod {
+        self.init
+            .as_ref()
+            .expect("checked in `AbstractProcess::new`")
+    }
+
+    /// Returns the `init` argument type, see [`Self::init`].
+    fn arg_ty(&self) -> &syn::Type {
+        self.arg_ty
+            .as_ref()
+            .expect("checked in `AbstractProcess::new`")
+    }
+
+    /// Expands the `init` method in the abstract process implementation.
+    fn expand_init_impl(&self) -> (TokenStream, TokenStream) {
+        let init_method = self.init();
+        let ident = &init_method.sig.ident;
+        let arg_ty = self.arg_ty();
+
+        // A signature that doesn't match the associated types is reported at
+        // the method.
+        let init_fn = quote_spanned! {ident.span()=> Self::#ident };
+        let init = match &self.warm_up {
+            Some(warm_up) => {
+                let warm_up_ident = &warm_up.sig.ident;
+                let warm_up_fn = quote_spanned! {warm_up_ident.span()=> Self::#warm_up_ident };
+                let warm_up_ty = match &warm_up.sig.output {
+                    syn::ReturnType::Type(_, ty) => ty,
+                    syn::ReturnType::Default => unreachable!("checked in `check_warm_up`"),
+                };
+                quote! {
+                    fn init(config: lunatic::ap::Config<Self>, arg: #arg_ty) -> Result<Self::State, Self::StartupError> {
+                        let warm_up: fn() -> #warm_up_ty = #warm_up_fn;
+                        let init: fn(lunatic::ap::Config<Self>, #arg_ty, #warm_up_ty) -> Result<Self::State, Self::StartupError> =
+                            #init_fn;
+                        init(config, arg, warm_up())
+                    }
+                }
+            }
+            None => quote! {
+                fn init(config: lunatic::ap::Config<Self>, arg: #arg_ty) -> Result<Self::State, Self::StartupError> {
+                    let init: fn(lunatic::ap::Config<Self>, #arg_ty) -> Result<Self::State, Self::StartupError> =
+                        #init_fn;
+                    init(config, arg)
+                }
+            },
+        };
+
+        // Extract startup error type from `Result<T, Error>`.
+        let startup_error = match &init_method.sig.output {
+            syn::ReturnType::Type(_, ret_type) => {
+                match ret_type.as_ref() {
+                    Type::Path(ret_type) => match ret_type.path.segments.last() {
+                        Some(ret_type_segments) => match &ret_type_segments.arguments {
+                            PathArguments::AngleBracketed(generics) => {
+                                // Error is the last argument in `Result<T, Error>`.
+                                let last = generics.args.last();
+                                quote! { #last }
+                            }
+                            _ => quote! { () },
+                        },
+                        _ => quote! { () },
+                    },
+                    _ => quote! { () },
+                }
+            }
+            _ => quote! { () },
+        };
+        (init, startup_error)
+    }
+
+    /// Expands the `terminate` method in the abstract process implementation,
+    /// together with `terminate_timeout` if a timeout is set.
+    fn expand_terminate_impl(&self) -> TokenStream {
+        self.terminate
+            .as_ref()
+            .map(|terminate| {
+                let ident = &terminate.sig.ident;
+                let timeout = self.terminate_timeout_ms.map(|timeout_ms| {
+                    quote! {
+                        fn terminate_timeout() -> Option<std::time::Duration> {
+                            Some(std::time::Duration::from_millis(#timeout_ms))
+                        }
+                    }
+                });
+
                 quote! {
                     fn terminate(state: Self::State) {
                         state.#ident()
                     }
+
+                    #timeout
                 }
             })
             .unwrap_or_default()
@@ -422,16 +1394,96 @@ impl AbstractProcess {
             .as_ref()
             .map(|handle_link_death| {
                 let ident = &handle_link_death.sig.ident;
+                // Validated while parsing.
+                let arg_ty = &filter_typed_args(handle_link_death.sig.inputs.iter())
+                    .next()
+                    .unwrap()
+                    .ty;
+
+                // The method can take either the `TrapInfo` or only the `Tag`, a
+                // different type is reported at the argument.
+                let arg = quote_spanned! {arg_ty.span()=>
+                    <#arg_ty as lunatic::ap::LinkDeathArg>::from_trap_info(info)
+                };
+                quote! {
+                    fn handle_link_death(mut state: lunatic::ap::State<Self>, info: lunatic::ap::TrapInfo) {
+                        state.#ident(#arg);
+                    }
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    /// Expands the `snapshot` method in the abstract process implementation.
+    fn expand_snapshot_impl(&self) -> TokenStream {
+        self.snapshot
+            .as_ref()
+            .map(|snapshot| {
+                let ident = &snapshot.sig.ident;
+                let arg_ty = self.arg_ty();
+                let snapshot_fn = quote_spanned! {ident.span()=> Self::#ident };
 
                 quote! {
-                    fn handle_link_death(mut state: lunatic::ap::State<Self>, tag: lunatic::Tag) {
-                        state.#ident(tag);
+                    fn snapshot(state: &Self::State) -> Option<#arg_ty> {
+                        let snapshot: fn(&Self::State) -> #arg_ty = #snapshot_fn;
+                        Some(snapshot(state))
                     }
                 }
             })
             .unwrap_or_default()
     }
 
+    /// Expands `restart_on_panic` if `on_panic = "restart"` is set, restarting
+    /// the process with a clone of the `init` argument.
+    fn expand_restart_on_panic_impl(&self) -> TokenStream {
+        let Some(RestartArgs {
+            max_restarts,
+            delay_ms,
+        }) = &self.args.on_panic
+        else {
+            return TokenStream::new();
+        };
+        let arg_ty = self.arg_ty();
+        let max_restarts = match max_restarts {
+            Some(max) => quote! { Some(#max) },
+            None => quote! { None },
+        };
+        quote! {
+            fn restart_on_panic(arg: &#arg_ty) -> Option<(lunatic::ap::RestartPolicy, #arg_ty)> {
+                let policy = lunatic::ap::RestartPolicy {
+                    max_restarts: #max_restarts,
+                    delay: std::time::Duration::from_millis(#delay_ms),
+                };
+                Some((policy, std::clone::Clone::clone(arg)))
+            }
+        }
+    }
+
+    /// Expands the `instrument` method, enabled with `instrument`.
+    fn expand_instrument_impl(&self) -> TokenStream {
+        if !self.args.instrument() {
+            return TokenStream::new();
+        }
+        quote! {
+            fn instrument() -> bool {
+                true
+            }
+        }
+    }
+
+    /// Returns the statement running the handler `fn_ident` in a span, enabled
+    /// with `telemetry`.
+    fn handler_span(&self, fn_ident: &syn::Ident) -> TokenStream {
+        if !self.args.telemetry() {
+            return TokenStream::new();
+        }
+        let self_ty = &self.item_impl.self_ty;
+        let name = format!("{}::{fn_ident}", type_string(quote!(#self_ty)));
+        quote! {
+            let _span = lunatic::ap::__handler_span(#name);
+        }
+    }
+
     /// Expands the `MessageHandler` implementations for the message handler
     /// wrapper types.
     fn expand_message_handler_impls(&self) -> TokenStream {
@@ -441,29 +1493,97 @@ impl AbstractProcess {
                 sig,
                 ..
             } = message_handler;
+            let impl_attrs = forwarded_impl_attrs(attrs);
             let self_ty = &self.item_impl.self_ty;
-            let message_type = Self::handler_wrapper_ident(&sig.ident);
+            let message_type = self.handler_wrapper_ident(&sig.ident);
             let fn_ident = &sig.ident;
             let (impl_generics, ty_generics, where_clause) = self.item_impl.generics.split_for_impl();
-            let args = filter_typed_args(sig.inputs.iter());
-            let offset = usize::from(!self.item_impl.generics.params.is_empty());
-            let message_fields = (offset..args.count() + offset).map(|i| {
-                let i = proc_macro2::Literal::usize_unsuffixed(i);
-                quote! { message. #i }
-            });
+            let message_fields = self.wrapper_fields(sig, quote! { message }, false);
+            let span = self.handler_span(fn_ident);
+            let priority = self
+                .handler_priority(fn_ident)
+                .map(|priority| quote! { const PRIORITY: u8 = #priority; });
+
+            if takes_self_by_value(sig) {
+                // Move the state out, and the returned state back into the process.
+                return quote! {
+                    #( #impl_attrs )*
+                    #[allow(deprecated)]
+                    impl #impl_generics lunatic::ap::MessageHandler<#message_type #ty_generics> for #self_ty #where_clause {
+                        #priority
+                        fn handle(mut state: lunatic::ap::State<Self>, message: #message_type #ty_generics) {
+                            #span
+                            state.replace_with(|state| state.#fn_ident(#( #message_fields ),*))
+                        }
+                    }
+                };
+            }
+
+            if !self.output_handlers.contains(fn_ident) {
+                return quote! {
+                    #( #impl_attrs )*
+                    #[allow(deprecated)]
+                    impl #impl_generics lunatic::ap::MessageHandler<#message_type #ty_generics> for #self_ty #where_clause {
+                        #priority
+                        fn handle(mut state: lunatic::ap::State<Self>, message: #message_type #ty_generics) {
+                            #span
+                            state.#fn_ident(#( #message_fields ),*)
+                        }
+                    }
+                };
+            }
 
+            // Forward the returned value to connected pipes.
             quote! {
-                #( #attrs )*
+                #( #impl_attrs )*
+                #[allow(deprecated)]
                 impl #impl_generics lunatic::ap::MessageHandler<#message_type #ty_generics> for #self_ty #where_clause {
+                    #priority
                     fn handle(mut state: lunatic::ap::State<Self>, message: #message_type #ty_generics) {
-                        state.#fn_ident(#( #message_fields ),*)
+                        #span
+                        let output = state.#fn_ident(#( #message_fields ),*);
+                        state.emit(output);
                     }
                 }
             }
         });
 
+        // Mark each returned type of an `#[output]` handler as an output of the process.
+        // A type is an output as long as one of the handlers returning it is enabled.
+        let mut output_types: Vec<(TokenStream, Vec<Option<TokenStream>>)> = Vec::new();
+        for message_handler in &self.message_handlers {
+            if !self.output_handlers.contains(&message_handler.sig.ident) {
+                continue;
+            }
+            if let syn::ReturnType::Type(_, ty) = &message_handler.sig.output {
+                let ty = quote! { #ty };
+                let predicate = cfg_predicate(&cfg_attrs(&message_handler.attrs));
+                match output_types
+                    .iter_mut()
+                    .find(|(output, _)| output.to_string() == ty.to_string())
+                {
+                    Some((_, predicates)) => predicates.push(predicate),
+                    None => output_types.push((ty, vec![predicate])),
+                }
+            }
+        }
+        let self_ty = &self.item_impl.self_ty;
+        let (impl_generics, _, where_clause) = self.item_impl.generics.split_for_impl();
+        let output_impls = output_types.iter().map(|(output, predicates)| {
+            let cfg = predicates
+                .iter()
+                .cloned()
+                .collect::<Option<Vec<_>>>()
+                .map(|predicates| quote! { #[cfg(any( #( #predicates ),* ))] });
+            quote! {
+                #cfg
+                impl #impl_generics lunatic::ap::Output<#output> for #self_ty #where_clause {}
+            }
+        });
+
         quote! {
             #( #message_handler_impls )*
+            #( #output_impls )*
         }
     }
 
@@ -476,8 +1596,9 @@ impl AbstractProcess {
                 sig,
                 ..
             } = request_handler;
+            let impl_attrs = forwarded_impl_attrs(attrs);
             let self_ty = &self.item_impl.self_ty;
-            let request_type = Self::handler_wrapper_ident(&sig.ident);
+            let request_type = self.handler_wrapper_ident(&sig.ident);
             let response_type = match &sig.output {
                 syn::ReturnType::Type(_, ty) => quote! { #ty },
                 syn::ReturnType::Default => {
@@ -486,20 +1607,37 @@ impl AbstractProcess {
             };
             let fn_ident = &sig.ident;
             let (impl_generics, ty_generics, where_clause) = self.item_impl.generics.split_for_impl();
-            let args = filter_typed_args(sig.inputs.iter());
-            let offset = usize::from(!self.item_impl.generics.params.is_empty());
-            let request_fields = (offset..args.count() + offset).map(|i| {
-                let i = proc_macro2::Literal::usize_unsuffixed(i);
-                quote! { request. #i }
-            });
+            let request_fields = self.wrapper_fields(sig, quote! { request }, false);
+            let span = self.handler_span(fn_ident);
+            let reply = quote! { state.#fn_ident(#( #request_fields ),*) };
+            if let Some(item) = stream_item(&sig.output) {
+                return quote! {
+                    #( #impl_attrs )*
+                    #[allow(deprecated)]
+                    impl #impl_generics lunatic::ap::StreamRequestHandler<#request_type #ty_generics> for #self_ty #where_clause {
+                        type Item = #item;
+
+                        fn handle(mut state: lunatic::ap::State<Self>, request: #request_type #ty_generics) -> lunatic::ap::ResponseStream<Self::Item> {
+                            #span
+                            #reply
+                        }
+                    }
+                };
+            }
+            let (response_type, reply) = match self.map_reply(fn_ident) {
+                Some(map_reply) => (quote! { #map_reply }, convert_reply(&sig.output, reply)),
+                None => (response_type, reply),
+            };
 
             quote! {
-                #( #attrs )*
+                #( #impl_attrs )*
+                #[allow(deprecated)]
                 impl #impl_generics lunatic::ap::RequestHandler<#request_type #ty_generics> for #self_ty #where_clause {
                     type Response = #response_type;
 
                     fn handle(mut state: lunatic::ap::State<Self>, request: #request_type #ty_generics) -> Self::Response {
-                        state.#fn_ident(#( #request_fields ),*)
+                        #span
+                        #reply
                     }
                 }
             }
@@ -511,7 +1649,8 @@ impl AbstractProcess {
     }
 
     /// Expands the `DeferredRequestHandler` implementations for the deferred
-    /// request handler wrapper types.
+    /// request handler wrapper types, and the `ResponderRequestHandler`
+    /// implementations for the ones marked with `#[handle_responder_request]`.
     fn expand_deferred_request_handler_impls(&self) -> TokenStream {
         let request_handler_impls = self.deferred_request_handlers.iter().map(|request_handler| {
             let syn::ImplItemMethod {
@@ -519,37 +1658,39 @@ impl AbstractProcess {
                 sig,
                 ..
             } = request_handler;
+            let impl_attrs = forwarded_impl_attrs(attrs);
             let self_ty = &self.item_impl.self_ty;
-            let request_type = Self::handler_wrapper_ident(&sig.ident);
-            // Get the first generic of the last argument `DeferredRequest<THIS, _>`.
-            let response_type = match &sig.inputs.last() {
-                Some(FnArg::Typed(path)) => match &*path.ty {
-                        Type::Path(path) => {
-                            let last = path.path.segments.last().unwrap();
-                            match &last.arguments {
-                                PathArguments::AngleBracketed(generics) => {
-                                    let response_type = generics.args.first().unwrap();
-                                    quote!{ #response_type }
-                                },
-                                _ => quote!{()},
-                            }
-                        },
-                        _ => quote!{()},
-                },
-                _ => quote!{()},
-            };
+            let request_type = self.handler_wrapper_ident(&sig.ident);
+            // The first generic of the last argument `DeferredResponse<THIS, _>`.
+            let response_type = deferred_response_type(&sig.inputs);
             let fn_ident = &sig.ident;
             let (impl_generics, ty_generics, where_clause) = self.item_impl.generics.split_for_impl();
-            let args = filter_typed_args(sig.inputs.iter());
-            let offset = usize::from(!self.item_impl.generics.params.is_empty());
             // Exclude last argument
-            let request_fields = (offset..args.count() - 1 + offset).map(|i| {
-                let i = proc_macro2::Literal::usize_unsuffixed(i);
-                quote! { request. #i }
-            });
+            let request_fields = self.wrapper_fields(sig, quote! { request }, true);
+            let span = self.handler_span(fn_ident);
+
+            if self.is_responder(fn_ident) {
+                return quote! {
+                    #( #impl_attrs )*
+                    #[allow(deprecated)]
+                    impl #impl_generics lunatic::ap::ResponderRequestHandler<#request_type #ty_generics> for #self_ty #where_clause {
+                        type Response = #response_type;
+
+                        fn handle(
+                            mut state: lunatic::ap::State<Self>,
+                            request: #request_type #ty_generics,
+                            responder: &mut lunatic::ap::Responder<Self::Response, Self>,
+                        ) -> Option<Self::Response> {
+                            #span
+                            state.#fn_ident(#( #request_fields, )* responder)
+                        }
+                    }
+                };
+            }
 
             quote! {
-                #( #attrs )*
+                #( #impl_attrs )*
+                #[allow(deprecated)]
                 impl #impl_generics lunatic::ap::DeferredRequestHandler<#request_type #ty_generics> for #self_ty #where_clause {
                     type Response = #response_type;
 
@@ -557,6 +1698,7 @@ impl AbstractProcess {
                         mut state: lunatic::ap::State<Self>,
                         request: #request_type #ty_generics,
                         deferred_response: lunatic::ap::DeferredResponse<Self::Response, Self>) {
+                            #span
                             state.#fn_ident(#( #request_fields, )* deferred_response);
                     }
                 }
@@ -568,10 +1710,175 @@ impl AbstractProcess {
         }
     }
 
+    /// Expands the `DeferredRequestHandler` implementations for request handlers
+    /// marked with `#[continue_with]`, and the `MessageHandler`
+    /// implementations resuming them.
+    fn expand_continued_request_handler_impls(&self) -> TokenStream {
+        let impls = self.continued_request_handlers.iter().map(|continued| {
+            let ContinuedHandler { handler, continuation } = continued;
+            let impl_attrs = forwarded_impl_attrs(&handler.attrs);
+            let continuation_cfg_attrs = continued.cfg_attrs();
+            let self_ty = &self.item_impl.self_ty;
+            let request_type = self.handler_wrapper_ident(&handler.sig.ident);
+            let continuation_type = self.handler_wrapper_ident(&continuation.sig.ident);
+            let response_type = step_response_type(&handler.sig.output);
+            let pending_type = filter_typed_args(continuation.sig.inputs.iter())
+                .next()
+                .map(|arg| &*arg.ty);
+            let fn_ident = &handler.sig.ident;
+            let continuation_ident = &continuation.sig.ident;
+            let (impl_generics, ty_generics, where_clause) = self.item_impl.generics.split_for_impl();
+            // Exclude last argument
+            let request_fields = self.wrapper_fields(&handler.sig, quote! { request }, true);
+            let span = self.handler_span(fn_ident);
+            // Exclude the pending state, the id is the first field
+            let args = filter_typed_args(continuation.sig.inputs.iter());
+            let reply_fields = (1..args.count()).map(|i| {
+                let i = proc_macro2::Literal::usize_unsuffixed(i);
+                quote! { reply. #i }
+            });
+
+            quote! {
+                #( #impl_attrs )*
+                #[allow(deprecated)]
+                impl #impl_generics lunatic::ap::DeferredRequestHandler<#request_type #ty_generics> for #self_ty #where_clause {
+                    type Response = #response_type;
+
+                    fn handle(
+                        mut state: lunatic::ap::State<Self>,
+                        request: #request_type #ty_generics,
+                        deferred_response: lunatic::ap::DeferredResponse<Self::Response, Self>) {
+                            #span
+                            let resume = lunatic::ap::Resume::__new::<Self, #continuation_type #ty_generics>();
+                            let id = resume.__id();
+                            match state.#fn_ident(#( #request_fields, )* resume) {
+                                lunatic::ap::Step::Done(response) => deferred_response.send_response(response),
+                                lunatic::ap::Step::Pending(pending) => {
+                                    lunatic::ap::__store_pending(id, (pending, deferred_response))
+                                }
+                            }
+                    }
+                }
+
+                #( #continuation_cfg_attrs )*
+                #[allow(deprecated)]
+                impl #impl_generics lunatic::ap::MessageHandler<#continuation_type #ty_generics> for #self_ty #where_clause {
+                    fn handle(mut state: lunatic::ap::State<Self>, reply: #continuation_type #ty_generics) {
+                        let id = reply.0;
+                        // Replies to requests that are not pending anymore are ignored.
+                        let pending = lunatic::ap::__take_pending::<(
+                            #pending_type,
+                            lunatic::ap::DeferredResponse<#response_type, Self>,
+                        )>(id);
+                        if let Some((pending, deferred_response)) = pending {
+                            match state.#continuation_ident(pending #(, #reply_fields )*) {
+                                lunatic::ap::Step::Done(response) => deferred_response.send_response(response),
+                                lunatic::ap::Step::Pending(pending) => {
+                                    lunatic::ap::__store_pending(id, (pending, deferred_response))
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        });
+
+        quote! {
+            #( #impls )*
+        }
+    }
+
+    /// Expands compile-time checks of handler signatures that the generated
+    /// implementations don't verify on their own.
+    ///
+    /// The pending state passed from a request handler to its continuation is
+    /// stored without its type, so both need to agree on the `Step<Response,
+    /// Pending>` type. A mismatch would only show up as a lost request at
+    /// runtime.
+    ///
+    /// ```ignore
+    /// const _: () = {
+    ///     impl Cache {
+    ///         fn __check_continue_with_get() {
+    ///             let _: PhantomData<Step<u32, Key>> = PhantomData::<Step<u32, Key>>;
+    ///         }
+    ///     }
+    /// };
+    /// ```
+    fn expand_protocol_checks(&self) -> TokenStream {
+        let checks: Vec<_> = self
+            .continued_request_handlers
+            .iter()
+            .filter_map(|continued| {
+                let ContinuedHandler {
+                    handler,
+                    continuation,
+                } = continued;
+                let syn::ReturnType::Type(_, step) = &handler.sig.output else {
+                    return None;
+                };
+                let response = step_response_type(&handler.sig.output);
+                // Validated while parsing.
+                let pending = &filter_typed_args(continuation.sig.inputs.iter())
+                    .next()?
+                    .ty;
+                let pending_check = quote_spanned! {pending.span()=>
+                    let _: std::marker::PhantomData<#step> =
+                        std::marker::PhantomData::<lunatic::ap::Step<#response, #pending>>;
+                };
+                let output_check = match &continuation.sig.output {
+                    syn::ReturnType::Type(_, output) => quote_spanned! {output.span()=>
+                        let _: std::marker::PhantomData<#step> = std::marker::PhantomData::<#output>;
+                    },
+                    syn::ReturnType::Default => quote! {},
+                };
+                let cfg_attrs = continued.cfg_attrs();
+                let check = format_ident!("__check_continue_with_{}", handler.sig.ident);
+                Some(quote! {
+                    #( #cfg_attrs )*
+                    #[allow(dead_code, deprecated)]
+                    fn #check() {
+                        #pending_check
+                        #output_check
+                    }
+                })
+            })
+            .collect();
+        if checks.is_empty() {
+            return quote! {};
+        }
+
+        let syn::ItemImpl {
+            generics, self_ty, ..
+        } = &self.item_impl;
+        let (impl_generics, _, where_clause) = generics.split_for_impl();
+        quote! {
+            const _: () = {
+                impl #impl_generics #self_ty #where_clause {
+                    #( #checks )*
+                }
+            };
+        }
+    }
+
+    /// Returns the client side structure of request handlers marked with
+    /// `#[continue_with]`.
+    ///
+    /// They are called the same way as deferred requests, but the response type
+    /// is taken from the returned `Step<Response, _>`.
+    fn continued_handler_structures(&self) -> impl Iterator<Item = HandlerStructure<'_>> {
+        self.continued_request_handlers
+            .iter()
+            .map(move |continued| {
+                let mut structure = self.handler_structure((&continued.handler, true));
+                structure.return_ty = step_response_type(&continued.handler.sig.output);
+                structure
+            })
+    }
+
     /// Expands the new `Handler` trait.
     fn expand_handler_trait(&self) -> TokenStream {
         let Self {
-            args,
             item_impl,
             message_handlers,
             request_handlers,
@@ -580,83 +1887,111 @@ impl AbstractProcess {
             request_trait_name,
             ..
         } = self;
-        let vis = &args.visibility;
-        let (_impl_generics, ty_generics, where_clause) = item_impl.generics.split_for_impl();
+        let vis = self.client_visibility();
+        let (_, _, where_clause) = item_impl.generics.split_for_impl();
+        let decl_generics = self.decl_generics();
 
         let message_handler_defs = message_handlers
             .iter()
             .zip(repeat(false)) // is_deferred = false
-            .map(HandlerStructure::from_handler)
-            .map(|handler| {
+            .map(|handler| (&handler.0.sig.ident, self.handler_structure(handler)))
+            .map(|(method, handler)| {
+                let default_methods = self.expand_default_methods(&handler, method);
+                let companion = self.expand_companion_decl(&handler, method, true);
                 let HandlerStructure {
                     attrs,
+                    cfg_attrs,
                     ident,
                     generics,
+                    where_clause,
                     args,
                     ..
                 } = handler;
 
                 let return_ty_type = format_ident!("ReturnTy_{}", ident);
                 quote! {
+                    #( #cfg_attrs )*
                     #[allow(non_camel_case_types)]
                     type #return_ty_type;
                     #( #attrs )*
-                    fn #ident #generics (&self #(, #args )*) -> Self::#return_ty_type;
+                    fn #ident #generics (&self #(, #args )*) -> Self::#return_ty_type #where_clause;
+                    #default_methods
+                    #companion
                 }
             });
 
         let request_handler_defs = request_handlers
             .iter()
             .zip(repeat(false)) // is_deferred = false
-            .map(HandlerStructure::from_handler)
-            .map(|handler| {
+            .map(|handler| (&handler.0.sig.ident, self.handler_structure(handler)))
+            .map(|(method, handler)| {
+                let default_methods = self.expand_default_methods(&handler, method);
+                let companion = self.expand_companion_decl(&handler, method, false);
                 let HandlerStructure {
                     attrs,
+                    cfg_attrs,
                     ident,
                     generics,
+                    where_clause,
                     args,
                     ..
                 } = handler;
 
                 let return_ty_type = format_ident!("ReturnTy_{}", ident);
                 quote! {
+                    #( #cfg_attrs )*
                     #[allow(non_camel_case_types)]
                     type #return_ty_type;
                     #( #attrs )*
-                    fn #ident #generics (&self #(, #args )*) -> Self::#return_ty_type;
+                    fn #ident #generics (&self #(, #args )*) -> Self::#return_ty_type #where_clause;
+                    #default_methods
+                    #companion
                 }
             });
 
         let deferred_request_handler_defs = deferred_request_handlers
             .iter()
             .zip(repeat(true)) // is_deferred = true
-            .map(HandlerStructure::from_handler)
-            .map(|handler| {
+            .map(|handler| (&handler.0.sig.ident, self.handler_structure(handler)))
+            .chain(
+                self.continued_request_handlers
+                    .iter()
+                    .map(|continued| &continued.handler.sig.ident)
+                    .zip(self.continued_handler_structures()),
+            )
+            .map(|(method, mut handler)| {
+                // Remove last argument from input.
+                handler.args.pop();
+                let default_methods = self.expand_default_methods(&handler, method);
+                let companion = self.expand_companion_decl(&handler, method, false);
                 let HandlerStructure {
                     attrs,
+                    cfg_attrs,
                     ident,
                     generics,
-                    mut args,
+                    where_clause,
+                    args,
                     ..
                 } = handler;
 
-                // Remove last argument from input.
-                args.pop();
                 let return_ty_type = format_ident!("ReturnTy_{}", ident);
                 quote! {
+                    #( #cfg_attrs )*
                     #[allow(non_camel_case_types)]
                     type #return_ty_type;
                     #( #attrs )*
-                    fn #ident #generics (&self #(, #args )*) -> Self::#return_ty_type;
+                    fn #ident #generics (&self #(, #args )*) -> Self::#return_ty_type #where_clause;
+                    #default_methods
+                    #companion
                 }
             });
 
         quote! {
-            #vis trait #message_trait_name #ty_generics #where_clause {
+            #vis trait #message_trait_name #decl_generics #where_clause {
                 #( #message_handler_defs )*
             }
 
-            #vis trait #request_trait_name #ty_generics #where_clause {
+            #vis trait #request_trait_name #decl_generics #where_clause {
                 #( #request_handler_defs )*
                 #( #deferred_request_handler_defs )*
             }
@@ -676,220 +2011,1840 @@ impl AbstractProcess {
         } = self;
         let self_ty = &item_impl.self_ty;
         let (impl_generics, ty_generics, where_clause) = item_impl.generics.split_for_impl();
+        // Const parameters of the wrapper can't be inferred from the arguments.
+        let turbofish = ty_generics.as_turbofish();
         let arg_phantom = if !item_impl.generics.params.is_empty() {
             Some(quote! { std::marker::PhantomData, })
         } else {
             None
         };
+        let try_cast_method = |handler: &HandlerStructure, method: &syn::Ident| {
+            let signature = self.try_cast_signature(handler, method)?;
+            let HandlerStructure {
+                impl_attrs,
+                message_type,
+                message_args,
+                ..
+            } = handler;
+            Some(quote! {
+                #( #impl_attrs )*
+                #signature {
+                    let msg = #message_type #turbofish (#arg_phantom #( #message_args ),*);
+                    self.try_send(msg)
+                }
+            })
+        };
+        // Sends the request `req` with `call`, retrying it if the handler has
+        // `retry(...)` set. Each attempt sends a new request built from clones
+        // of the arguments.
+        let request_call = |handler: &HandlerStructure, method: &syn::Ident, call: TokenStream| {
+            let HandlerStructure {
+                message_type,
+                message_args,
+                cloned_args,
+                ..
+            } = handler;
+            match self
+                .handler_args(method)
+                .and_then(|args| args.retry.as_ref())
+            {
+                Some(RetryArgs { times, backoff_ms }) => quote! {
+                    lunatic::ap::__retry_request(
+                        #times,
+                        std::time::Duration::from_millis(#backoff_ms),
+                        || {
+                            let req = #message_type #turbofish (#arg_phantom #( #cloned_args ),*);
+                            #call
+                        },
+                    )
+                },
+                None => quote! {
+                    let req = #message_type #turbofish (#arg_phantom #( #message_args ),*);
+                    #call
+                },
+            }
+        };
+        let timeout_method =
+            |handler: &HandlerStructure, method: &syn::Ident, call: TokenStream| {
+                let (signature, timeout) = self.timeout_signature(handler, method)?;
+                let impl_attrs = &handler.impl_attrs;
+                // The timeout of a stream applies to each item.
+                let call = match self.stream_item(method) {
+                    Some(_) => quote! { Ok(#call(req, Some(#timeout))) },
+                    None => quote! { #call(req, Some(#timeout)) },
+                };
+                let body = request_call(handler, method, call);
+                Some(quote! {
+                    #( #impl_attrs )*
+                    #signature {
+                        #body
+                    }
+                })
+            };
 
         let message_handler_impls = message_handlers
             .iter()
             .zip(repeat(false)) // is_deferred = false
-            .map(HandlerStructure::from_handler)
-            .map(|handler| {
+            .map(|handler| (&handler.0.sig.ident, self.handler_structure(handler)))
+            .map(|(method, handler)| {
+                let companion = try_cast_method(&handler, method);
                 let HandlerStructure {
-                    attrs,
+                    impl_attrs,
+                    cfg_attrs,
                     ident,
                     generics,
+                    where_clause,
                     args,
                     message_type,
-                    handler_args,
+                    message_args,
                     ..
                 } = handler;
 
                 let return_ty_type = format_ident!("ReturnTy_{}", ident);
                 quote! {
+                    #( #cfg_attrs )*
                     type #return_ty_type = ();
-                    #( #attrs )*
-                    fn #ident #generics (&self #(, #args )*) {
-                        let msg = #message_type(#arg_phantom #( #handler_args ),*);
+                    #( #impl_attrs )*
+                    fn #ident #generics (&self #(, #args )*) #where_clause {
+                        let msg = #message_type #turbofish (#arg_phantom #( #message_args ),*);
                         self.send(msg);
                     }
+                    #companion
                 }
             });
 
         let message_delay_handler_impls = message_handlers
             .iter()
             .zip(repeat(false)) // is_deferred = false
-            .map(HandlerStructure::from_handler)
-            .map(|handler| {
+            .map(|handler| (&handler.0.sig.ident, self.handler_structure(handler)))
+            .map(|(method, handler)| {
+                let companion = try_cast_method(&handler, method);
                 let HandlerStructure {
-                    attrs,
+                    impl_attrs,
+                    cfg_attrs,
                     ident,
                     generics,
+                    where_clause,
                     args,
                     message_type,
-                    handler_args,
+                    message_args,
                     ..
                 } = handler;
 
                 let return_ty_type = format_ident!("ReturnTy_{}", ident);
                 quote! {
+                    #( #cfg_attrs )*
                     type #return_ty_type = lunatic::time::TimerRef;
-                    #( #attrs )*
-                    fn #ident #generics (&self #(, #args )*) -> lunatic::time::TimerRef {
-                        let msg = #message_type(#arg_phantom #( #handler_args ),*);
+                    #( #impl_attrs )*
+                    fn #ident #generics (&self #(, #args )*) -> lunatic::time::TimerRef #where_clause {
+                        let msg = #message_type #turbofish (#arg_phantom #( #message_args ),*);
                         self.send(msg)
                     }
+                    #companion
                 }
             });
 
         let request_handler_impls = request_handlers
             .iter()
             .zip(repeat(false)) // is_deferred = false
-            .map(HandlerStructure::from_handler)
-            .map(|handler| {
+            .map(|handler| (&handler.0.sig.ident, self.handler_structure(handler)))
+            .map(|(method, handler)| {
+                let (call, timeout_call) = match self.stream_item(method) {
+                    Some(_) => (quote! { request_stream }, quote! { self.request_stream_timeout }),
+                    None => (quote! { request }, quote! { self.request_timeout }),
+                };
+                let companion = timeout_method(&handler, method, timeout_call);
                 let HandlerStructure {
-                    attrs,
+                    impl_attrs,
+                    cfg_attrs,
                     ident,
                     generics,
+                    where_clause,
                     args,
                     return_ty,
                     message_type,
-                    handler_args,
+                    message_args,
+                    ..
                 } = handler;
 
                 let return_ty_type = format_ident!("ReturnTy_{}", ident);
                 quote! {
+                    #( #cfg_attrs )*
                     type #return_ty_type = #return_ty;
-                    #( #attrs )*
-                    fn #ident #generics (&self #(, #args )*) -> Self::#return_ty_type {
-                        let req = #message_type(#arg_phantom #( #handler_args ),*);
-                        self.request(req)
+                    #( #impl_attrs )*
+                    fn #ident #generics (&self #(, #args )*) -> Self::#return_ty_type #where_clause {
+                        let req = #message_type #turbofish (#arg_phantom #( #message_args ),*);
+                        self.#call(req)
                     }
+                    #companion
                 }
             });
 
         let request_timeout_handler_impls = request_handlers
             .iter()
             .zip(repeat(false)) // is_deferred = false
-            .map(HandlerStructure::from_handler)
-            .map(|handler| {
+            .map(|handler| (&handler.0.sig.ident, self.handler_structure(handler)))
+            .map(|(method, handler)| {
+                let is_stream = self.stream_item(method).is_some();
+                let call = match is_stream {
+                    true => quote! { self.process_ref().request_stream_timeout },
+                    false => quote! { self.process_ref().request_timeout },
+                };
+                let companion = timeout_method(&handler, method, call);
+                let call = match is_stream {
+                    true => quote! { self.request_stream(req) },
+                    false => quote! { self.request(req) },
+                };
+                let body = request_call(&handler, method, call);
                 let HandlerStructure {
-                    attrs,
+                    impl_attrs,
+                    cfg_attrs,
                     ident,
                     generics,
+                    where_clause,
                     args,
                     return_ty,
-                    message_type,
-                    handler_args,
+                    ..
                 } = handler;
 
+                // Streams report timeouts of each item in the iterator.
+                let return_ty = match is_stream {
+                    true => return_ty,
+                    false => quote! { Result<#return_ty, lunatic::time::Timeout> },
+                };
                 let return_ty_type = format_ident!("ReturnTy_{}", ident);
                 quote! {
-                    type #return_ty_type = Result<#return_ty, lunatic::time::Timeout>;
-                    #( #attrs )*
-                    fn #ident #generics (&self #(, #args )*) -> Self::#return_ty_type {
-                        let req = #message_type(#arg_phantom #( #handler_args ),*);
-                        self.request(req)
+                    #( #cfg_attrs )*
+                    type #return_ty_type = #return_ty;
+                    #( #impl_attrs )*
+                    fn #ident #generics (&self #(, #args )*) -> Self::#return_ty_type #where_clause {
+                        #body
                     }
+                    #companion
                 }
             });
 
         let deferred_request_handler_impls = deferred_request_handlers
             .iter()
             .zip(repeat(true)) // is_deferred = true
-            .map(HandlerStructure::from_handler)
-            .map(|handler| {
+            .map(|handler| (&handler.0.sig.ident, self.handler_structure(handler)))
+            .chain(
+                self.continued_request_handlers
+                    .iter()
+                    .map(|continued| &continued.handler.sig.ident)
+                    .zip(self.continued_handler_structures()),
+            )
+            .map(|(method, mut handler)| {
+                // Remove last argument from input.
+                handler.args.pop();
+                handler.message_args.pop();
+                handler.cloned_args.pop();
+                let (call, timeout_call) = match self.is_responder(method) {
+                    true => (quote! { responder_request }, quote! { self.responder_request_timeout }),
+                    false => (quote! { deferred_request }, quote! { self.deferred_request_timeout }),
+                };
+                let companion = timeout_method(&handler, method, timeout_call);
                 let HandlerStructure {
-                    attrs,
+                    impl_attrs,
+                    cfg_attrs,
                     ident,
                     generics,
-                    mut args,
+                    where_clause,
+                    args,
                     return_ty,
                     message_type,
-                    mut handler_args,
+                    message_args,
+                    ..
                 } = handler;
 
-                // Remove last argument from input.
-                args.pop();
-                handler_args.pop();
                 let return_ty_type = format_ident!("ReturnTy_{}", ident);
                 quote! {
+                    #( #cfg_attrs )*
                     type #return_ty_type = #return_ty;
-                    #( #attrs )*
-                    fn #ident #generics (&self #(, #args )*) -> Self::#return_ty_type {
-                        let req = #message_type(#arg_phantom #( #handler_args ),*);
-                        self.deferred_request(req)
+                    #( #impl_attrs )*
+                    fn #ident #generics (&self #(, #args )*) -> Self::#return_ty_type #where_clause {
+                        let req = #message_type #turbofish (#arg_phantom #( #message_args ),*);
+                        self.#call(req)
                     }
+                    #companion
                 }
             });
 
         let deferred_request_timeout_handler_impls = deferred_request_handlers
             .iter()
             .zip(repeat(true)) // is_deferred = true
-            .map(HandlerStructure::from_handler)
-            .map(|handler| {
+            .map(|handler| (&handler.0.sig.ident, self.handler_structure(handler)))
+            .chain(
+                self.continued_request_handlers
+                    .iter()
+                    .map(|continued| &continued.handler.sig.ident)
+                    .zip(self.continued_handler_structures()),
+            )
+            .map(|(method, mut handler)| {
+                // Remove last argument from input.
+                handler.args.pop();
+                handler.message_args.pop();
+                handler.cloned_args.pop();
+                let (call, timeout_call) = match self.is_responder(method) {
+                    true => (
+                        quote! { self.responder_request(req) },
+                        quote! { self.process_ref().responder_request_timeout },
+                    ),
+                    false => (
+                        quote! { self.deferred_request(req) },
+                        quote! { self.process_ref().deferred_request_timeout },
+                    ),
+                };
+                let companion = timeout_method(&handler, method, timeout_call);
+                let body = request_call(&handler, method, call);
                 let HandlerStructure {
-                    attrs,
+                    impl_attrs,
+                    cfg_attrs,
                     ident,
                     generics,
-                    mut args,
+                    where_clause,
+                    args,
                     return_ty,
-                    message_type,
-                    mut handler_args,
+                    ..
                 } = handler;
 
-                // Remove last argument from input.
-                args.pop();
-                handler_args.pop();
                 let return_ty_type = format_ident!("ReturnTy_{}", ident);
                 quote! {
+                    #( #cfg_attrs )*
                     type #return_ty_type = Result<#return_ty, lunatic::time::Timeout>;
-                    #( #attrs )*
-                    fn #ident #generics (&self #(, #args )*) -> Self::#return_ty_type {
-                        let req = #message_type(#arg_phantom #( #handler_args ),*);
-                        self.deferred_request(req)
+                    #( #impl_attrs )*
+                    fn #ident #generics (&self #(, #args )*) -> Self::#return_ty_type #where_clause {
+                        #body
+                    }
+                    #companion
+                }
+            });
+
+        quote! {
+            // Handlers and their message types can be deprecated.
+            #[allow(deprecated)]
+            impl #impl_generics #message_trait_name #ty_generics for lunatic::ap::ProcessRef<#self_ty> #where_clause {
+                #( #message_handler_impls )*
+            }
+
+            #[allow(deprecated)]
+            impl #impl_generics #request_trait_name #ty_generics for lunatic::ap::ProcessRef<#self_ty> #where_clause {
+                #( #request_handler_impls )*
+                #( #deferred_request_handler_impls )*
+            }
+
+            #[allow(deprecated)]
+            impl #impl_generics #message_trait_name #ty_generics for
+                    lunatic::time::WithDelay<lunatic::ap::ProcessRef<#self_ty>> #where_clause {
+                #( #message_delay_handler_impls )*
+            }
+
+            #[allow(deprecated)]
+            impl #impl_generics #request_trait_name #ty_generics for
+                    lunatic::time::WithTimeout<lunatic::ap::ProcessRef<#self_ty>> #where_clause {
+                #( #request_timeout_handler_impls )*
+                #( #deferred_request_timeout_handler_impls )*
+            }
+        }
+    }
+
+    /// Expands the mock of the process reference, e.g. `MockCounterRef`.
+    ///
+    /// It implements the handler traits by calling closures set with the
+    /// `expect_*` methods, and records the names of called handlers. Message
+    /// handlers without a closure do nothing, requests without one panic.
+    fn expand_mock(&self) -> TokenStream {
+        let Some(mock_name) = &self.mock_name else {
+            return TokenStream::new();
+        };
+        let Self {
+            item_impl,
+            message_trait_name,
+            request_trait_name,
+            ..
+        } = self;
+        let vis = self.mock_visibility();
+        let (impl_generics, ty_generics, where_clause) = item_impl.generics.split_for_impl();
+        let phantom_type = self.phantom_type();
+        let decl_generics = self.decl_generics();
+
+        let mock_method = |handler: &syn::ImplItemMethod,
+                           mut structure: HandlerStructure,
+                           is_message: bool,
+                           is_deferred: bool| {
+            let mut arg_tys: Vec<_> = filter_typed_arg_names(handler.sig.inputs.iter())
+                .map(|(_, ty)| ty)
+                .collect();
+            if is_deferred {
+                // Remove last argument from input.
+                arg_tys.pop();
+                structure.args.pop();
+                structure.handler_args.pop();
+            }
+            let method = &handler.sig.ident;
+            let call_ident = &structure.ident;
+            let call_args = &structure.handler_args;
+            let companion_attrs = &structure.impl_attrs;
+            let companion = if is_message {
+                self.try_cast_signature(&structure, method)
+                    .map(|signature| {
+                        quote! {
+                            #( #companion_attrs )*
+                            #signature {
+                                self.#call_ident(#( #call_args ),*);
+                                true
+                            }
+                        }
+                    })
+            } else {
+                self.timeout_signature(&structure, method)
+                    .map(|(signature, timeout)| {
+                        quote! {
+                            #( #companion_attrs )*
+                            #signature {
+                                let _ = #timeout;
+                                Ok(self.#call_ident(#( #call_args ),*))
+                            }
+                        }
+                    })
+            };
+            let HandlerStructure {
+                impl_attrs,
+                cfg_attrs,
+                ident,
+                where_clause,
+                args,
+                return_ty,
+                handler_args,
+                ..
+            } = structure;
+            let return_ty = if is_message {
+                quote! { () }
+            } else {
+                return_ty
+            };
+            let name = method.to_string();
+            let expect = format_ident!("expect_{}", method);
+            let expect_doc = format!(" Sets the closure handling calls of `{method}`.");
+
+            let field = quote! {
+                #( #cfg_attrs )*
+                #method: std::cell::RefCell<Option<Box<dyn FnMut(#( #arg_tys ),*) -> #return_ty>>>,
+            };
+            let field_init = quote! {
+                #( #cfg_attrs )*
+                #method: std::cell::RefCell::new(None),
+            };
+            let expect_method = quote! {
+                #( #cfg_attrs )*
+                #[doc = #expect_doc]
+                pub fn #expect(&mut self, f: impl FnMut(#( #arg_tys ),*) -> #return_ty + 'static) -> &mut Self {
+                    *self.#method.get_mut() = Some(Box::new(f));
+                    self
+                }
+            };
+            let call = if is_message {
+                quote! {
+                    if let Some(f) = self.#method.borrow_mut().as_mut() {
+                        f(#( #handler_args ),*)
+                    }
+                }
+            } else {
+                let unexpected = format!(
+                    "unexpected call to `{method}` on `{mock_name}`, set a closure with `{expect}`"
+                );
+                quote! {
+                    let mut f = self.#method.borrow_mut();
+                    let f = f.as_mut().expect(#unexpected);
+                    f(#( #handler_args ),*)
+                }
+            };
+            let return_ty_type = format_ident!("ReturnTy_{}", ident);
+            let trait_item = quote! {
+                #( #cfg_attrs )*
+                type #return_ty_type = #return_ty;
+                #( #impl_attrs )*
+                fn #ident(&self #(, #args )*) -> Self::#return_ty_type #where_clause {
+                    self.__calls.borrow_mut().push(#name);
+                    #call
+                }
+                #companion
+            };
+            (field, field_init, expect_method, trait_item)
+        };
+
+        let message_methods: Vec<_> = self
+            .message_handlers
+            .iter()
+            .map(|handler| {
+                let structure = self.handler_structure((handler, false));
+                mock_method(handler, structure, true, false)
+            })
+            .collect();
+        let request_methods: Vec<_> = self
+            .request_handlers
+            .iter()
+            .map(|handler| {
+                let structure = self.handler_structure((handler, false));
+                mock_method(handler, structure, false, false)
+            })
+            .chain(self.deferred_request_handlers.iter().map(|handler| {
+                let structure = self.handler_structure((handler, true));
+                mock_method(handler, structure, false, true)
+            }))
+            .chain(
+                self.continued_request_handlers
+                    .iter()
+                    .zip(self.continued_handler_structures())
+                    .map(|(continued, structure)| {
+                        mock_method(&continued.handler, structure, false, true)
+                    }),
+            )
+            .collect();
+        let fields = message_methods
+            .iter()
+            .chain(&request_methods)
+            .map(|method| &method.0);
+        let field_inits = message_methods
+            .iter()
+            .chain(&request_methods)
+            .map(|method| &method.1);
+        let expect_methods = message_methods
+            .iter()
+            .chain(&request_methods)
+            .map(|method| &method.2);
+        let message_items = message_methods.iter().map(|method| &method.3);
+        let request_items = request_methods.iter().map(|method| &method.3);
+
+        let doc = format!(
+            " Mock implementing `{message_trait_name}` and `{request_trait_name}` without a running process."
+        );
+        quote! {
+            #[doc = #doc]
+            #[allow(clippy::type_complexity)]
+            #vis struct #mock_name #decl_generics {
+                __calls: std::cell::RefCell<Vec<&'static str>>,
+                #( #fields )*
+                __phantom: #phantom_type,
+            }
+
+            #[allow(deprecated)]
+            impl #impl_generics #mock_name #ty_generics #where_clause {
+                /// Creates a mock without any closures set.
+                pub fn new() -> Self {
+                    Self {
+                        __calls: std::cell::RefCell::new(Vec::new()),
+                        #( #field_inits )*
+                        __phantom: std::marker::PhantomData,
+                    }
+                }
+
+                /// Returns the names of the called handlers, in the order of the calls.
+                pub fn calls(&self) -> Vec<&'static str> {
+                    self.__calls.borrow().clone()
+                }
+
+                #( #expect_methods )*
+            }
+
+            impl #impl_generics Default for #mock_name #ty_generics #where_clause {
+                fn default() -> Self {
+                    Self::new()
+                }
+            }
+
+            #[allow(deprecated)]
+            impl #impl_generics #message_trait_name #ty_generics for #mock_name #ty_generics #where_clause {
+                #( #message_items )*
+            }
+
+            #[allow(deprecated)]
+            impl #impl_generics #request_trait_name #ty_generics for #mock_name #ty_generics #where_clause {
+                #( #request_items )*
+            }
+        }
+    }
+
+    /// Returns the attribute arguments of the handler method `ident`.
+    fn handler_args(&self, ident: &syn::Ident) -> Option<&HandlerArgs> {
+        self.handler_args
+            .iter()
+            .find(|(method, _)| method == ident)
+            .map(|(_, args)| args)
+    }
+
+    /// Returns the name of the wrapper type of the handler method `ident`.
+    fn handler_wrapper_ident(&self, ident: &syn::Ident) -> syn::Ident {
+        self.handler_args(ident)
+            .and_then(|args| args.name.clone())
+            .unwrap_or_else(|| Self::default_handler_wrapper_ident(ident))
+    }
+
+    /// Create a wrapper name for the request and send
+    fn default_handler_wrapper_ident(ident: impl ToString) -> syn::Ident {
+        format_ident!("__MsgWrap{}", ident.to_string().to_case(Case::Pascal))
+    }
+
+    /// Returns the client side structure of the handler, using the custom
+    /// wrapper name if one is set.
+    fn handler_structure<'a>(
+        &self,
+        handler: (&'a syn::ImplItemMethod, bool),
+    ) -> HandlerStructure<'a> {
+        let ident = &handler.0.sig.ident;
+        let mut structure = HandlerStructure::from_handler(handler);
+        structure.message_type = self.handler_wrapper_ident(ident);
+        // Handlers with default arguments expose all arguments on the `_with`
+        // variant.
+        if self.has_defaults(ident) {
+            structure.ident = format_ident!("{}_with", ident);
+        }
+        if let Some(map_reply) = self.map_reply(ident) {
+            structure.return_ty = quote! { #map_reply };
+        }
+        // Callers receive the items of a stream with an iterator.
+        if let Some(item) = self.stream_item(ident) {
+            let self_ty = &self.item_impl.self_ty;
+            structure.return_ty = quote! {
+                lunatic::ap::ResponseIter<#item, <#self_ty as lunatic::ap::AbstractProcess>::Serializer>
+            };
+        }
+        structure
+    }
+
+    /// Returns the item type of the request handler method `ident`, if it
+    /// returns a `ResponseStream`.
+    /// Returns `true` if the handler `ident` is marked with
+    /// `#[handle_responder_request]`.
+    fn is_responder(&self, ident: &syn::Ident) -> bool {
+        self.responder_handlers.contains(ident)
+    }
+
+    fn stream_item(&self, ident: &syn::Ident) -> Option<&syn::Type> {
+        let handler = self
+            .request_handlers
+            .iter()
+            .find(|handler| &handler.sig.ident == ident)?;
+        stream_item(&handler.sig.output)
+    }
+
+    /// Returns the type set with `map_reply` on the handler method `ident`.
+    fn map_reply(&self, ident: &syn::Ident) -> Option<&syn::Type> {
+        self.handler_args(ident)?.map_reply.as_ref()
+    }
+
+    /// Returns the id assigned with `id = 7` to the handler method `ident`.
+    fn handler_id(&self, ident: &syn::Ident) -> Option<u8> {
+        let id = self.handler_args(ident)?.id.as_ref()?;
+        // Validated while parsing.
+        id.base10_parse().ok()
+    }
+
+    /// Checks that message priorities are only set with the `"priority"`
+    /// mailbox strategy, which is the only one taking them into account.
+    fn check_priorities(&self) -> syn::Result<()> {
+        let strategy = self
+            .args
+            .mailbox_strategy
+            .as_ref()
+            .map(|strategy| strategy.value());
+        if strategy.as_deref().map(str::trim) == Some("priority") {
+            return Ok(());
+        }
+        match self
+            .handler_args
+            .iter()
+            .find_map(|(_, args)| args.priority.as_ref())
+        {
+            Some(priority) => Err(syn::Error::new(
+                priority.span(),
+                "message priorities need `#[abstract_process(mailbox_strategy = \"priority\")]`",
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the priority of the messages to the handler method `ident`.
+    fn handler_priority(&self, ident: &syn::Ident) -> Option<&syn::LitInt> {
+        self.handler_args(ident)?.priority.as_ref()
+    }
+
+    /// Checks that no two handlers are assigned the same id, including the ids
+    /// kept by removed handlers, and that ids are only assigned in the main
+    /// impl block.
+    fn check_ids(&self) -> syn::Result<()> {
+        let mut assigned: Vec<(u8, &syn::LitInt)> = Vec::new();
+        for (_, args) in &self.handler_args {
+            let Some(id) = &args.id else {
+                continue;
+            };
+            if self.args.extend.is_some() {
+                return Err(syn::Error::new(
+                    id.span(),
+                    "ids can only be assigned in the main impl block, \
+                     the ids of extensions follow its handlers",
+                ));
+            }
+            let value = id.base10_parse()?;
+            if let Some((_, previous)) = assigned.iter().find(|(other, _)| *other == value) {
+                let mut error = syn::Error::new(id.span(), format!("id {value} is assigned twice"));
+                error.combine(syn::Error::new(previous.span(), "first assigned here"));
+                return Err(error);
+            }
+            assigned.push((value, id));
+        }
+        for removed in &self.removed_handlers {
+            if self.args.extend.is_some() {
+                return Err(syn::Error::new(
+                    removed.id_lit.span(),
+                    "handlers can only be removed from the main impl block",
+                ));
+            }
+            if let Some((_, previous)) = assigned.iter().find(|(other, _)| *other == removed.id) {
+                let mut error = syn::Error::new(
+                    removed.id_lit.span(),
+                    format!("id {} is kept by a removed handler", removed.id),
+                );
+                error.combine(syn::Error::new(previous.span(), "but assigned here"));
+                return Err(error);
+            }
+            assigned.push((removed.id, &removed.id_lit));
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if some arguments of the handler method `ident` have
+    /// default values.
+    fn has_defaults(&self, ident: &syn::Ident) -> bool {
+        self.handler_args(ident)
+            .is_some_and(|args| !args.defaults.is_empty())
+    }
+
+    /// Checks that only trailing client arguments have default values.
+    fn check_defaults(&self) -> syn::Result<()> {
+        let handlers = self
+            .message_handlers
+            .iter()
+            .chain(&self.request_handlers)
+            .map(|handler| (handler, false))
+            .chain(
+                self.deferred_request_handlers
+                    .iter()
+                    .chain(self.continued_request_handlers.iter().map(|c| &c.handler))
+                    .map(|handler| (handler, true)),
+            );
+        for (handler, is_deferred) in handlers {
+            let Some(args) = self.handler_args(&handler.sig.ident) else {
+                continue;
+            };
+            let mut names: Vec<_> = filter_typed_arg_names(handler.sig.inputs.iter())
+                .map(|(ident, _)| ident)
+                .collect();
+            if is_deferred {
+                names.pop();
+            }
+            let trailing = &names[names.len().saturating_sub(args.defaults.len())..];
+            for (param, _) in &args.defaults {
+                if !names.contains(param) {
+                    return Err(syn::Error::new(
+                        param.span(),
+                        format!("`{}` has no argument `{param}`", handler.sig.ident),
+                    ));
+                }
+                if !trailing.contains(param) {
+                    return Err(syn::Error::new(
+                        param.span(),
+                        "only trailing arguments can have default values",
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the replies of request handlers can be sent to the caller.
+    ///
+    /// Without this check an `impl Trait` or borrowed reply fails with trait
+    /// bound errors inside of the generated code.
+    fn check_replies(&self) -> syn::Result<()> {
+        let handlers = self.request_handlers.iter().map(|h| (h, false)).chain(
+            self.continued_request_handlers
+                .iter()
+                .map(|c| (&c.handler, true)),
+        );
+        for (handler, is_continued) in handlers {
+            let ident = &handler.sig.ident;
+            let syn::ReturnType::Type(_, ty) = &handler.sig.output else {
+                continue;
+            };
+            if !is_continued && stream_item(&handler.sig.output).is_some() {
+                let args = self.handler_args(ident);
+                if args.is_some_and(|args| args.map_reply.is_some() || args.retry.is_some()) {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        "map_reply and retry can't be used on handlers returning a `ResponseStream`",
+                    ));
+                }
+            }
+            if is_continued && self.map_reply(ident).is_some() {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "map_reply can't be used together with `#[continue_with]`",
+                ));
+            }
+            if !is_continued && self.map_reply(ident).is_some() {
+                continue;
+            }
+            let Some((unsendable, what)) = unsendable_type(ty) else {
+                continue;
+            };
+            let hint = match is_continued {
+                true => "return a concrete type",
+                false => {
+                    "return a concrete type, or convert the reply with e.g. \
+                     `#[handle_request(map_reply = Vec<Entry>)]`"
+                }
+            };
+            return Err(syn::Error::new_spanned(
+                unsendable,
+                format!(
+                    "the reply of `{ident}` is sent to the caller and can't contain {what}, {hint}"
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that the visibilities can be raised by one level for the items
+    /// inside of the `client_mod` module.
+    fn check_client_mod(&self) -> syn::Result<()> {
+        if self.client_mod.is_none() {
+            return Ok(());
+        }
+        let visibilities = match &self.args.item_visibility {
+            Some(items) => vec![&items.msgs, &items.traits, &items.mock],
+            None => vec![&self.args.visibility],
+        };
+        visibilities
+            .into_iter()
+            .flatten()
+            .try_for_each(Self::check_raised_visibility)
+    }
+
+    /// Checks that `vis` isn't relative to the current module, it can't be
+    /// raised by one level.
+    fn check_raised_visibility(vis: &syn::Visibility) -> syn::Result<()> {
+        match vis {
+            syn::Visibility::Restricted(restricted)
+                if restricted.path.segments.len() > 1
+                    && matches!(
+                        restricted.path.segments[0].ident.to_string().as_str(),
+                        "self" | "super"
+                    ) =>
+            {
+                Err(syn::Error::new(
+                    restricted.path.span(),
+                    "`client_mod` doesn't support visibilities relative to the current module, \
+                     use `pub(crate)` or `pub(in crate::...)`",
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that the handlers can be mocked if `mock = true` is set.
+    ///
+    /// Mocked handlers are stored as closures, which can't be generic.
+    fn check_mock(&self) -> syn::Result<()> {
+        if self.mock_name.is_none() {
+            return Ok(());
+        }
+        let generic_handler = self
+            .message_handlers
+            .iter()
+            .chain(&self.request_handlers)
+            .chain(&self.deferred_request_handlers)
+            .chain(self.continued_request_handlers.iter().map(|c| &c.handler))
+            .find(|handler| !handler.sig.generics.params.is_empty());
+        match generic_handler {
+            Some(handler) => Err(syn::Error::new(
+                handler.sig.generics.span(),
+                "generic handlers can't be mocked",
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Expands the methods that call the `_with` variant of handlers with
+    /// default arguments.
+    ///
+    /// ```ignore
+    /// fn get(&self, key: String) -> Self::ReturnTy_get_with {
+    ///     self.get_with(key, 10)
+    /// }
+    /// ```
+    fn expand_default_methods(
+        &self,
+        structure: &HandlerStructure,
+        method: &syn::Ident,
+    ) -> TokenStream {
+        let Some(args) = self.handler_args(method) else {
+            return TokenStream::new();
+        };
+        if args.defaults.is_empty() {
+            return TokenStream::new();
+        }
+        let HandlerStructure {
+            attrs,
+            ident,
+            generics,
+            where_clause,
+            ..
+        } = structure;
+        let return_ty_type = format_ident!("ReturnTy_{}", ident);
+        let leading = structure.args.len() - args.defaults.len();
+        let leading_args = &structure.args[..leading];
+        let leading_names = &structure.handler_args[..leading];
+        let defaults = structure.handler_args[leading..structure.args.len()]
+            .iter()
+            .map(|name| {
+                let (_, default) = args
+                    .defaults
+                    .iter()
+                    .find(|(param, _)| param == name)
+                    .unwrap();
+                default
+            });
+        quote! {
+            #( #attrs )*
+            #[allow(deprecated)]
+            fn #method #generics (&self #(, #leading_args )*) -> Self::#return_ty_type #where_clause {
+                self.#ident(#( #leading_names, )* #( #defaults ),*)
+            }
+        }
+    }
+
+    /// Returns `true` unless the `_timeout` variants of request handlers are
+    /// disabled with `timeouts = false`.
+    fn timeouts(&self) -> bool {
+        self.args
+            .timeouts
+            .as_ref()
+            .is_none_or(|timeouts| timeouts.value)
+    }
+
+    /// Returns `true` unless the `try_` variants of message handlers are
+    /// disabled with `try_cast = false`.
+    fn try_cast(&self) -> bool {
+        self.args
+            .try_cast
+            .as_ref()
+            .is_none_or(|try_cast| try_cast.value)
+    }
+
+    /// Returns the name of the client method generated next to the handler
+    /// method `ident`, `try_{ident}` for messages and `{ident}_timeout` for
+    /// requests.
+    fn companion_ident(&self, ident: &syn::Ident, is_message: bool) -> Option<syn::Ident> {
+        match is_message {
+            true if self.try_cast() => Some(format_ident!("try_{}", ident)),
+            false if self.timeouts() => Some(format_ident!("{}_timeout", ident)),
+            _ => None,
+        }
+    }
+
+    /// Expands the signature of the `try_` variant of a message handler.
+    ///
+    /// ```ignore
+    /// fn try_increment(&self, by: u32) -> bool
+    /// ```
+    fn try_cast_signature(
+        &self,
+        structure: &HandlerStructure,
+        method: &syn::Ident,
+    ) -> Option<TokenStream> {
+        let ident = self.companion_ident(method, true)?;
+        let HandlerStructure {
+            generics,
+            where_clause,
+            args,
+            ..
+        } = structure;
+        Some(quote! {
+            fn #ident #generics (&self #(, #args )*) -> bool #where_clause
+        })
+    }
+
+    /// Expands the signature of the `_timeout` variant of a request handler,
+    /// and returns it together with the name of the timeout argument.
+    ///
+    /// ```ignore
+    /// fn count_timeout(&self, timeout: Duration) -> Result<u32, RequestError>
+    /// ```
+    fn timeout_signature(
+        &self,
+        structure: &HandlerStructure,
+        method: &syn::Ident,
+    ) -> Option<(TokenStream, syn::Ident)> {
+        let ident = self.companion_ident(method, false)?;
+        let HandlerStructure {
+            generics,
+            where_clause,
+            args,
+            return_ty,
+            handler_args,
+            ..
+        } = structure;
+        let timeout = match handler_args.iter().any(|arg| arg == "timeout") {
+            true => format_ident!("__timeout"),
+            false => format_ident!("timeout"),
+        };
+        let signature = quote! {
+            fn #ident #generics (&self #(, #args )*, #timeout: std::time::Duration)
+                -> Result<#return_ty, lunatic::ap::RequestError> #where_clause
+        };
+        Some((signature, timeout))
+    }
+
+    /// Expands the declaration of the `try_` or `_timeout` variant of a
+    /// handler in the generated traits.
+    fn expand_companion_decl(
+        &self,
+        structure: &HandlerStructure,
+        method: &syn::Ident,
+        is_message: bool,
+    ) -> TokenStream {
+        let (signature, doc) = if is_message {
+            let Some(signature) = self.try_cast_signature(structure, method) else {
+                return TokenStream::new();
+            };
+            let doc = format!(
+                " Same as [`{method}`](Self::{method}), but doesn't send the message and \
+                 returns `false` if the process isn't alive."
+            );
+            (signature, doc)
+        } else {
+            let Some((signature, timeout)) = self.timeout_signature(structure, method) else {
+                return TokenStream::new();
+            };
+            let doc = format!(
+                " Same as [`{method}`](Self::{method}), but only waits for `{timeout}` on \
+                 the response."
+            );
+            (signature, doc)
+        };
+        // The documentation of the handler describes the original method.
+        let attrs = structure
+            .attrs
+            .iter()
+            .filter(|attr| !attr.path.is_ident("doc"));
+        quote! {
+            #( #attrs )*
+            #[doc = #doc]
+            #signature;
+        }
+    }
+
+    /// Returns an error if a `try_` or `_timeout` variant would have the same
+    /// name as a method of the impl block, a generated `_with` variant, or a
+    /// method of `ProcessRef` that would shadow it.
+    fn check_companion_collisions(&self) -> syn::Result<()> {
+        const PROCESS_REF_METHODS: &[&str] = &[
+            "try_send",
+            "request_timeout",
+            "deferred_request_timeout",
+            "responder_request_timeout",
+            "batch_request_timeout",
+            "shutdown_timeout",
+        ];
+        let methods: Vec<_> = self
+            .item_impl
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                syn::ImplItem::Method(method) => Some(&method.sig.ident),
+                _ => None,
+            })
+            .collect();
+        let defaults: Vec<_> = methods
+            .iter()
+            .filter(|method| self.has_defaults(method))
+            .map(|method| format_ident!("{}_with", method))
+            .collect();
+        let handlers = self
+            .message_handlers
+            .iter()
+            .map(|handler| (handler, true))
+            .chain(
+                self.request_handlers
+                    .iter()
+                    .chain(&self.deferred_request_handlers)
+                    .chain(self.continued_request_handlers.iter().map(|c| &c.handler))
+                    .map(|handler| (handler, false)),
+            );
+        for (handler, is_message) in handlers {
+            let method = &handler.sig.ident;
+            let Some(companion) = self.companion_ident(method, is_message) else {
+                continue;
+            };
+            let reason = if let Some(existing) = methods.iter().find(|m| **m == &companion) {
+                Some(format!(
+                    "`{existing}` is already defined in this impl block"
+                ))
+            } else if defaults.contains(&companion) {
+                Some(format!(
+                    "`{companion}` is also generated for default arguments"
+                ))
+            } else if PROCESS_REF_METHODS.contains(&companion.to_string().as_str()) {
+                Some(format!("`ProcessRef::{companion}` would shadow it"))
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                let opt_out = if is_message { "try_cast" } else { "timeouts" };
+                return Err(syn::Error::new(
+                    method.span(),
+                    format!(
+                        "`{companion}` is generated for `{method}`, but {reason}; \
+                         rename the handler or disable it with `{opt_out} = false`"
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns `#[doc(hidden)]` if the wrapper of the handler method `ident`
+    /// uses a generated name.
+    fn wrapper_doc_hidden(&self, ident: &syn::Ident) -> Option<TokenStream> {
+        match self.handler_args(ident) {
+            Some(HandlerArgs { name: Some(_), .. }) => None,
+            _ => Some(quote! { #[doc(hidden)] }),
+        }
+    }
+}
+
+/// Arguments of a handler attribute.
+#[derive(Default)]
+struct HandlerArgs {
+    /// Name of the generated wrapper type, `name = "FetchUser"`.
+    name: Option<syn::Ident>,
+    /// Default values of trailing arguments, `default(limit = 10)`.
+    defaults: Vec<(syn::Ident, syn::Expr)>,
+    /// Retries of timed out requests, `retry(times = 3, backoff = "100ms")`.
+    retry: Option<RetryArgs>,
+    /// Type the reply is converted into before it's sent,
+    /// `map_reply = Vec<Entry>`.
+    map_reply: Option<syn::Type>,
+    /// Explicitly assigned handler id, `id = 7`.
+    id: Option<syn::LitInt>,
+    /// Priority of the messages to a message handler, `priority = 2`.
+    priority: Option<syn::LitInt>,
+}
+
+/// Arguments of `retry(times = 3, backoff = "100ms")`.
+struct RetryArgs {
+    /// Number of retries after the first attempt.
+    times: u32,
+    /// Delay before the first retry in milliseconds, doubled after each retry.
+    backoff_ms: u64,
+}
+
+impl Parse for HandlerArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = HandlerArgs::default();
+        while !input.is_empty() {
+            let ident: syn::Ident = input.parse()?;
+            if ident == "name" {
+                if args.name.is_some() {
+                    return Err(syn::Error::new(ident.span(), "name already specified"));
+                }
+                let _: syn::Token![=] = input.parse()?;
+                let name: syn::LitStr = input.parse()?;
+                // Keep the span of the literal for errors about the name.
+                let parsed: syn::Ident = name.parse()?;
+                args.name = Some(syn::Ident::new(&parsed.to_string(), name.span()));
+            } else if ident == "default" {
+                let content;
+                syn::parenthesized!(content in input);
+                while !content.is_empty() {
+                    let param: syn::Ident = content.parse()?;
+                    if args.defaults.iter().any(|(other, _)| other == &param) {
+                        return Err(syn::Error::new(
+                            param.span(),
+                            "default value already specified",
+                        ));
+                    }
+                    let _: syn::Token![=] = content.parse()?;
+                    args.defaults.push((param, content.parse()?));
+                    let _: Option<Token![,]> = content.parse()?;
+                }
+            } else if ident == "retry" {
+                if args.retry.is_some() {
+                    return Err(syn::Error::new(ident.span(), "retry already specified"));
+                }
+                let content;
+                syn::parenthesized!(content in input);
+                args.retry = Some(parse_retry(&ident, &content)?);
+            } else if ident == "map_reply" {
+                if args.map_reply.is_some() {
+                    return Err(syn::Error::new(ident.span(), "map_reply already specified"));
+                }
+                let _: syn::Token![=] = input.parse()?;
+                args.map_reply = Some(input.parse()?);
+            } else if ident == "id" {
+                if args.id.is_some() {
+                    return Err(syn::Error::new(ident.span(), "id already specified"));
+                }
+                let _: syn::Token![=] = input.parse()?;
+                let id: syn::LitInt = input.parse()?;
+                match id.base10_parse::<u8>() {
+                    Ok(1..=MAX_HANDLERS) => args.id = Some(id),
+                    _ => {
+                        return Err(syn::Error::new(
+                            id.span(),
+                            format!("handler ids must be between 1 and {MAX_HANDLERS}"),
+                        ))
+                    }
+                }
+            } else if ident == "priority" {
+                if args.priority.is_some() {
+                    return Err(syn::Error::new(ident.span(), "priority already specified"));
+                }
+                let _: syn::Token![=] = input.parse()?;
+                let priority: syn::LitInt = input.parse()?;
+                match priority.base10_parse::<u8>() {
+                    Ok(1..=255) => args.priority = Some(priority),
+                    _ => {
+                        return Err(syn::Error::new(
+                            priority.span(),
+                            "priorities must be between 1 and 255",
+                        ))
+                    }
+                }
+            } else {
+                return Err(syn::Error::new(ident.span(), "unknown argument"));
+            }
+            let _: Option<Token![,]> = input.parse()?;
+        }
+        Ok(args)
+    }
+}
+
+/// Parses the content of `retry(times = 3, backoff = "100ms")`.
+fn parse_retry(retry: &syn::Ident, content: ParseStream) -> syn::Result<RetryArgs> {
+    let mut times = None;
+    let mut backoff_ms = 0;
+    while !content.is_empty() {
+        let option: syn::Ident = content.parse()?;
+        let _: syn::Token![=] = content.parse()?;
+        if option == "times" {
+            let value: syn::LitInt = content.parse()?;
+            times = Some(value.base10_parse()?);
+        } else if option == "backoff" {
+            let value: syn::LitStr = content.parse()?;
+            backoff_ms = parse_duration_ms(&value.value()).ok_or_else(|| {
+                syn::Error::new(
+                    value.span(),
+                    "`backoff` must be a duration in ms, s or m, e.g. \"100ms\"",
+                )
+            })?;
+        } else {
+            return Err(syn::Error::new(
+                option.span(),
+                "unknown option, expected `times` or `backoff`",
+            ));
+        }
+        let _: Option<Token![,]> = content.parse()?;
+    }
+    let times = times.ok_or_else(|| syn::Error::new(retry.span(), "missing `times`"))?;
+    Ok(RetryArgs { times, backoff_ms })
+}
+
+/// Parses the arguments of handler attributes, e.g.
+/// `#[handle_request(name = "FetchUser", default(limit = 10))]`.
+///
+/// Returns pairs of the method name and its arguments.
+fn parse_handler_args(item_impl: &syn::ItemImpl) -> syn::Result<Vec<(syn::Ident, HandlerArgs)>> {
+    let mut handler_args = Vec::new();
+    for item in &item_impl.items {
+        let syn::ImplItem::Method(method) = item else {
+            continue;
+        };
+        let handler_attrs = method.attrs.iter().filter(|attr| {
+            matches!(
+                attr.path
+                    .get_ident()
+                    .and_then(|ident| ItemAttr::from_str(&ident.to_string())),
+                Some(
+                    ItemAttr::HandleMessage
+                        | ItemAttr::HandleRequest
+                        | ItemAttr::HandleDeferredRequest
+                        | ItemAttr::HandleResponderRequest
+                )
+            )
+        });
+        for attr in handler_attrs {
+            if attr.tokens.is_empty() {
+                continue;
+            }
+            let args: HandlerArgs = attr.parse_args()?;
+            if args.retry.is_some() && attr.path.is_ident("handle_message") {
+                return Err(syn::Error::new(
+                    attr.span(),
+                    "retry can only be used on request handlers",
+                ));
+            }
+            if args.map_reply.is_some() && !attr.path.is_ident("handle_request") {
+                return Err(syn::Error::new(
+                    attr.span(),
+                    "map_reply can only be used on `#[handle_request]` handlers",
+                ));
+            }
+            if args.priority.is_some() && !attr.path.is_ident("handle_message") {
+                return Err(syn::Error::new(
+                    attr.span(),
+                    "priority can only be used on `#[handle_message]` handlers",
+                ));
+            }
+            handler_args.push((method.sig.ident.clone(), args));
+        }
+    }
+    Ok(handler_args)
+}
+
+/// Maximum number of entries in the `AP::Handlers` tuple.
+const MAX_HANDLERS: u8 = 16;
+
+/// Expands the serde implementations of a wrapper that isn't generic.
+///
+/// The wrapper is encoded the same way as the tuple `(MethodId, fields...)`,
+/// but each field is serialized on its own with the span of its argument. A
+/// type that doesn't implement `Serialize` or `Deserialize` is reported at the
+/// argument, instead of at the macro and every place the wrapper is used.
+fn expand_wrapper_serde(
+    ident: &syn::Ident,
+    fields: &[TokenStream],
+    spans: &[proc_macro2::Span],
+    method_id: proc_macro2::Literal,
+    impl_attrs: &[&syn::Attribute],
+    telemetry: bool,
+) -> TokenStream {
+    let len = fields.len() + 1 + usize::from(telemetry);
+    let serialize_fields = spans.iter().enumerate().map(|(i, span)| {
+        let mut index = proc_macro2::Literal::usize_unsuffixed(i);
+        index.set_span(*span);
+        quote_spanned! {*span=>
+            serde::ser::SerializeTuple::serialize_element(&mut tuple, &self.#index)?;
+        }
+    });
+    let names: Vec<_> = (0..fields.len())
+        .map(|i| format_ident!("field{}", i))
+        .collect();
+    let deserialize_fields =
+        names
+            .iter()
+            .zip(fields)
+            .zip(spans)
+            .enumerate()
+            .map(|(i, ((name, ty), span))| {
+                let next = quote_spanned! {*span=> seq.next_element::<#ty>() };
+                quote! {
+                    let #name = #next?
+                        .ok_or_else(|| serde::de::Error::invalid_length(#i + 1, &self))?;
+                }
+            });
+    let expecting = format!("the arguments of `{ident}`");
+    // The trace headers follow the arguments.
+    let (serialize_trace, deserialize_trace) = if telemetry {
+        let index = fields.len() + 1;
+        (
+            Some(quote! {
+                serde::ser::SerializeTuple::serialize_element(
+                    &mut tuple,
+                    &lunatic::ap::TraceHeaders::current(),
+                )?;
+            }),
+            Some(quote! {
+                seq.next_element::<lunatic::ap::TraceHeaders>()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(#index, &self))?
+                    .receive();
+            }),
+        )
+    } else {
+        (None, None)
+    };
+
+    quote! {
+        #( #impl_attrs )*
+        #[allow(deprecated)]
+        impl serde::Serialize for #ident {
+            fn serialize<__S: serde::Serializer>(&self, serializer: __S) -> Result<__S::Ok, __S::Error> {
+                let mut tuple = serializer.serialize_tuple(#len)?;
+                serde::ser::SerializeTuple::serialize_element(
+                    &mut tuple,
+                    &lunatic::ap::handlers::MethodId::<#method_id>,
+                )?;
+                #( #serialize_fields )*
+                #serialize_trace
+                serde::ser::SerializeTuple::end(tuple)
+            }
+        }
+
+        #( #impl_attrs )*
+        #[allow(deprecated)]
+        impl<'__de> serde::Deserialize<'__de> for #ident {
+            fn deserialize<__D: serde::Deserializer<'__de>>(deserializer: __D) -> Result<Self, __D::Error> {
+                struct __Visitor;
+
+                impl<'__de> serde::de::Visitor<'__de> for __Visitor {
+                    type Value = #ident;
+
+                    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        formatter.write_str(#expecting)
+                    }
+
+                    fn visit_seq<__A: serde::de::SeqAccess<'__de>>(self, mut seq: __A) -> Result<#ident, __A::Error> {
+                        let _: lunatic::ap::handlers::MethodId<#method_id> = seq
+                            .next_element()?
+                            .ok_or_else(|| serde::de::Error::invalid_length(0, &self))?;
+                        #( #deserialize_fields )*
+                        #deserialize_trace
+                        Ok(#ident(#( #names ),*))
                     }
                 }
-            });
 
-        quote! {
-            impl #impl_generics #message_trait_name #ty_generics for lunatic::ap::ProcessRef<#self_ty> #where_clause {
-                #( #message_handler_impls )*
+                deserializer.deserialize_tuple(#len, __Visitor)
             }
+        }
+    }
+}
 
-            impl #impl_generics #request_trait_name #ty_generics for lunatic::ap::ProcessRef<#self_ty> #where_clause {
-                #( #request_handler_impls )*
-                #( #deferred_request_handler_impls )*
+/// Arguments of `#[removed_handler(id = 4, name = "old_get")]` on the impl
+/// block, keeping the id of a removed handler so that messages from old
+/// clients don't reach the handler that would take it over.
+struct RemovedHandlerArgs {
+    id: u8,
+    id_lit: syn::LitInt,
+    name: syn::LitStr,
+    /// Argument types of a removed request handler, set with
+    /// `request(String, u32)`. `None` if it handled messages.
+    request: Option<Vec<syn::Type>>,
+}
+
+impl Parse for RemovedHandlerArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut id = None;
+        let mut name = None;
+        let mut request = None;
+        while !input.is_empty() {
+            let ident: syn::Ident = input.parse()?;
+            if ident == "id" {
+                let _: syn::Token![=] = input.parse()?;
+                let lit: syn::LitInt = input.parse()?;
+                match lit.base10_parse::<u8>() {
+                    Ok(value @ 1..=MAX_HANDLERS) => id = Some((value, lit)),
+                    _ => {
+                        return Err(syn::Error::new(
+                            lit.span(),
+                            format!("handler ids must be between 1 and {MAX_HANDLERS}"),
+                        ))
+                    }
+                }
+            } else if ident == "name" {
+                let _: syn::Token![=] = input.parse()?;
+                let lit: syn::LitStr = input.parse()?;
+                lit.parse::<syn::Ident>()?;
+                name = Some(lit);
+            } else if ident == "request" {
+                let content;
+                syn::parenthesized!(content in input);
+                let types = content.parse_terminated::<syn::Type, Token![,]>(syn::Type::parse)?;
+                request = Some(types.into_iter().collect());
+            } else {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "unknown argument, expected `id`, `name` or `request`",
+                ));
             }
+            let _: Option<Token![,]> = input.parse()?;
+        }
+        let (id, id_lit) = id.ok_or_else(|| input.error("missing `id`"))?;
+        let name = name.ok_or_else(|| input.error("missing `name`"))?;
+        Ok(RemovedHandlerArgs {
+            id,
+            id_lit,
+            name,
+            request,
+        })
+    }
+}
 
-            impl #impl_generics #message_trait_name #ty_generics for
-                    lunatic::time::WithDelay<lunatic::ap::ProcessRef<#self_ty>> #where_clause {
-                #( #message_delay_handler_impls )*
+impl RemovedHandlerArgs {
+    /// Returns the entry in the `AP::Handlers` tuple and the marker type it
+    /// refers to.
+    ///
+    /// ```ignore
+    /// pub struct __RemovedOldGet;
+    ///
+    /// impl RemovedHandler for __RemovedOldGet {
+    ///     const NAME: &'static str = "old_get";
+    ///     type Args = (u32, String);
+    /// }
+    /// ```
+    fn expand(&self, vis: &TokenStream) -> (TokenStream, TokenStream) {
+        let name = &self.name;
+        let marker = format_ident!("__Removed{}", name.value().to_case(Case::Pascal));
+        let (kind, args) = match &self.request {
+            Some(types) => (
+                quote! { lunatic::ap::handlers::RemovedRequest },
+                quote! { (u32, #( #types, )*) },
+            ),
+            None => (
+                quote! { lunatic::ap::handlers::RemovedMessage },
+                quote! { () },
+            ),
+        };
+        let marker_impl = quote! {
+            #[doc(hidden)]
+            #vis struct #marker;
+
+            impl lunatic::ap::handlers::RemovedHandler for #marker {
+                const NAME: &'static str = #name;
+                type Args = #args;
             }
+        };
+        (quote! { #kind<#marker> }, marker_impl)
+    }
+}
 
-            impl #impl_generics #request_trait_name #ty_generics for
-                    lunatic::time::WithTimeout<lunatic::ap::ProcessRef<#self_ty>> #where_clause {
-                #( #request_timeout_handler_impls )*
-                #( #deferred_request_timeout_handler_impls )*
+/// Removes the `#[removed_handler(...)]` attributes from the impl block and
+/// returns their arguments.
+fn take_removed_handlers(item_impl: &mut syn::ItemImpl) -> syn::Result<Vec<RemovedHandlerArgs>> {
+    let mut removed = Vec::new();
+    let mut error = None;
+    item_impl.attrs.retain(|attr| {
+        if !attr.path.is_ident("removed_handler") {
+            return true;
+        }
+        match attr.parse_args() {
+            Ok(args) => removed.push(args),
+            Err(err) => {
+                error.get_or_insert(err);
             }
         }
+        false
+    });
+    match error {
+        Some(err) => Err(err),
+        None => Ok(removed),
     }
+}
 
-    /// Create a wrapper name for the request and send
-    fn handler_wrapper_ident(ident: impl ToString) -> syn::Ident {
-        format_ident!("__MsgWrap{}", ident.to_string().to_case(Case::Pascal))
+/// Returns the id of the handler with the wrapper type `wrapper`, a 32 bit
+/// FNV-1a hash of its name.
+///
+/// It only depends on the name, so that it stays the same across builds.
+fn method_id(wrapper: &str) -> u32 {
+    wrapper.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Returns the source of a type without the spaces inserted by `quote`, e.g.
+/// `Vec<u32>` instead of `Vec < u32 >`.
+fn type_string(ty: TokenStream) -> String {
+    let source = ty.to_string();
+    let chars: Vec<char> = source.chars().collect();
+    let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+    chars
+        .iter()
+        .enumerate()
+        .filter(|&(i, c)| {
+            // Keep spaces separating words, like in `dyn Trait`.
+            *c != ' ' || (i > 0 && is_word(&chars[i - 1]) && chars.get(i + 1).is_some_and(is_word))
+        })
+        .map(|(_, c)| c)
+        .collect()
+}
+
+/// Quotes `value` as a JSON string.
+fn json_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Returns `true` if the method takes `self` by value, without a reference.
+fn takes_self_by_value(sig: &syn::Signature) -> bool {
+    matches!(sig.inputs.first(), Some(FnArg::Receiver(receiver)) if receiver.reference.is_none())
+}
+
+/// Returns the error for a second method marked with `#[attr]`.
+fn already_defined(
+    attr: &str,
+    previous: &syn::ImplItemMethod,
+    method: &syn::ImplItemMethod,
+) -> syn::Error {
+    syn::Error::new(
+        method.sig.ident.span(),
+        format!(
+            "`#[{attr}]` is already used on `{}`, merge the two methods",
+            previous.sig.ident
+        ),
+    )
+}
+
+/// Checks that a `#[handle_link_death]` method takes `self` by reference and
+/// one argument. The type of the argument is checked by the compiler.
+fn check_handle_link_death(method: &syn::ImplItemMethod) -> syn::Result<()> {
+    let takes_ref = matches!(
+        method.sig.inputs.first(),
+        Some(FnArg::Receiver(receiver)) if receiver.reference.is_some()
+    );
+    if !takes_ref || filter_typed_args(method.sig.inputs.iter()).count() != 1 {
+        return Err(syn::Error::new(
+            method.sig.ident.span(),
+            "`#[handle_link_death]` methods take `&mut self` and the `TrapInfo` or `Tag` of \
+             the link, e.g. `fn handle_link_death(&mut self, info: TrapInfo)`",
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that a `#[warm_up]` method takes no arguments and returns the value
+/// passed to `init`.
+fn check_warm_up(method: &syn::ImplItemMethod) -> syn::Result<()> {
+    if !method.sig.inputs.is_empty() || matches!(method.sig.output, syn::ReturnType::Default) {
+        return Err(syn::Error::new(
+            method.sig.ident.span(),
+            "`#[warm_up]` methods take no arguments and return the value passed to `init`, \
+             e.g. `fn warm_up() -> Buffers`",
+        ));
+    }
+    Ok(())
+}
+
+/// Removes the modifier attribute `name` from the method and from its copy
+/// inside of the original impl item.
+fn take_modifier(
+    impl_item_method: &mut syn::ImplItemMethod,
+    original: &mut syn::ImplItem,
+    name: &str,
+) -> Option<syn::Attribute> {
+    let k = impl_item_method
+        .attrs
+        .iter()
+        .position(|attr| attr.path.is_ident(name))?;
+    if let syn::ImplItem::Method(original) = original {
+        original.attrs.remove(k);
+    }
+    Some(impl_item_method.attrs.remove(k))
+}
+
+/// A request handler marked with `#[continue_with(continuation)]`.
+struct ContinuedHandler {
+    handler: syn::ImplItemMethod,
+    continuation: syn::ImplItemMethod,
+}
+
+impl ContinuedHandler {
+    /// Returns the `#[cfg]` attributes of both methods, code resuming the
+    /// handler is only generated if both exist.
+    fn cfg_attrs(&self) -> Vec<&syn::Attribute> {
+        let mut cfgs = cfg_attrs(&self.handler.attrs);
+        cfgs.extend(cfg_attrs(&self.continuation.attrs));
+        cfgs
+    }
+}
+
+/// Extracts `Response` from the `Step<Response, State>` return type.
+fn step_response_type(output: &syn::ReturnType) -> TokenStream {
+    match output {
+        syn::ReturnType::Type(_, ty) => match ty.as_ref() {
+            Type::Path(path) => match &path.path.segments.last().unwrap().arguments {
+                PathArguments::AngleBracketed(generics) => {
+                    let response_type = generics.args.first().unwrap();
+                    quote! { #response_type }
+                }
+                _ => quote! { () },
+            },
+            _ => quote! { () },
+        },
+        syn::ReturnType::Default => quote! { () },
+    }
+}
+
+/// Returns the first generic of the last argument of a deferred request
+/// handler, the `Response` of `DeferredResponse<Response, Self>` or of
+/// `&mut Responder<Response, Self>`.
+fn deferred_response_type(inputs: &syn::punctuated::Punctuated<FnArg, Token![,]>) -> TokenStream {
+    let Some(FnArg::Typed(arg)) = inputs.last() else {
+        return quote! {()};
+    };
+    let ty = match &*arg.ty {
+        Type::Reference(reference) => &*reference.elem,
+        ty => ty,
+    };
+    match ty {
+        Type::Path(path) => match &path.path.segments.last().unwrap().arguments {
+            PathArguments::AngleBracketed(generics) => {
+                let response_type = generics.args.first().unwrap();
+                quote! { #response_type }
+            }
+            _ => quote! {()},
+        },
+        _ => quote! {()},
+    }
+}
+
+/// Checks that a `#[handle_responder_request]` handler takes a `&mut
+/// Responder<Response, Self>` as last argument and returns an
+/// `Option<Response>`.
+fn check_responder_request(method: &syn::ImplItemMethod) -> syn::Result<()> {
+    let takes_responder = match method.sig.inputs.last() {
+        Some(FnArg::Typed(arg)) => match &*arg.ty {
+            Type::Reference(reference) if reference.mutability.is_some() => {
+                matches!(
+                    &*reference.elem,
+                    Type::Path(path) if path.path.segments.last().is_some_and(|last| last.ident == "Responder")
+                )
+            }
+            _ => false,
+        },
+        _ => false,
+    };
+    if !takes_responder {
+        return Err(syn::Error::new(
+            method.sig.span(),
+            "responder request handlers need to take \
+             `responder: &mut Responder<Response, Self>` as last argument",
+        ));
     }
+    if let syn::ReturnType::Default = method.sig.output {
+        return Err(syn::Error::new(
+            method.sig.ident.span(),
+            "responder request handlers need to return an `Option<Response>`, \
+             `None` if they already responded",
+        ));
+    }
+    Ok(())
 }
 
 #[derive(Default)]
 pub struct Args {
+    trait_name: Option<syn::LitStr>,
     message_trait_name: Option<syn::LitStr>,
     request_trait_name: Option<syn::LitStr>,
     visibility: Option<syn::Visibility>,
+    /// Visibilities of the generated items, set with
+    /// `visibility(msgs = pub(crate), traits = pub)`.
+    item_visibility: Option<ItemVisibility>,
     serializer: Option<syn::Type>,
+    mailbox_strategy: Option<syn::LitStr>,
+    mock: Option<syn::LitBool>,
+    timeouts: Option<syn::LitBool>,
+    try_cast: Option<syn::LitBool>,
+    on_panic: Option<RestartArgs>,
+    client_mod: Option<syn::LitStr>,
+    /// Name of the marker type of an impl block extending another one.
+    extend: Option<syn::LitStr>,
+    /// Marker types of the impl blocks extending this one.
+    extensions: Vec<syn::Path>,
+    /// Generates the `SCHEMA` constant, `export_schema` is the same as
+    /// `export_schema = true`.
+    export_schema: Option<syn::LitBool>,
+    /// Records handler calls in the metrics, `instrument` is the same as
+    /// `instrument = true`.
+    instrument: Option<syn::LitBool>,
+    /// Propagates the trace context with the messages, only
+    /// `telemetry = "opentelemetry"` is available.
+    telemetry: Option<syn::LitStr>,
+}
+
+/// Visibilities set with `visibility(msgs = ..., traits = ..., mock = ...)`.
+/// Items that aren't listed are private.
+#[derive(Default)]
+struct ItemVisibility {
+    /// Message types.
+    msgs: Option<syn::Visibility>,
+    /// Message and request traits implemented for `ProcessRef`.
+    traits: Option<syn::Visibility>,
+    /// Mock of the process reference.
+    mock: Option<syn::Visibility>,
+}
+
+/// Restart policy set with `on_panic = "restart(max = 3, delay = '1s')"`.
+struct RestartArgs {
+    max_restarts: Option<u32>,
+    delay_ms: u64,
 }
 
 impl Args {
+    fn export_schema(&self) -> bool {
+        self.export_schema
+            .as_ref()
+            .is_some_and(|export_schema| export_schema.value)
+    }
+
+    fn instrument(&self) -> bool {
+        self.instrument
+            .as_ref()
+            .is_some_and(|instrument| instrument.value)
+    }
+
+    fn telemetry(&self) -> bool {
+        self.telemetry.is_some()
+    }
+
     fn parse_arg(&mut self, input: ParseStream) -> syn::Result<()> {
         if input.is_empty() {
             return Ok(());
         }
 
         let ident: syn::Ident = input.parse()?;
+        if ident == "export_schema" {
+            if self.export_schema.is_some() {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "export_schema already specified",
+                ));
+            }
+
+            self.export_schema = Some(parse_flag(&ident, input)?);
+            return Ok(());
+        } else if ident == "instrument" {
+            if self.instrument.is_some() {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "instrument already specified",
+                ));
+            }
+
+            self.instrument = Some(parse_flag(&ident, input)?);
+            return Ok(());
+        } else if ident == "visibility" && input.peek(syn::token::Paren) {
+            if self.visibility.is_some() || self.item_visibility.is_some() {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "visibility already specified",
+                ));
+            }
+
+            let content;
+            syn::parenthesized!(content in input);
+            self.item_visibility = Some(parse_item_visibility(&content)?);
+            return Ok(());
+        }
         let _: syn::Token![=] = input.parse()?;
-        if ident == "message_trait_name" {
+        if ident == "trait_name" {
+            if self.trait_name.is_some() {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "trait name already specified",
+                ));
+            }
+
+            self.trait_name = Some(input.parse()?);
+        } else if ident == "message_trait_name" {
             if self.message_trait_name.is_some() {
                 return Err(syn::Error::new(
                     ident.span(),
@@ -908,7 +3863,7 @@ impl Args {
 
             self.request_trait_name = Some(input.parse()?);
         } else if ident == "visibility" {
-            if self.visibility.is_some() {
+            if self.visibility.is_some() || self.item_visibility.is_some() {
                 return Err(syn::Error::new(
                     ident.span(),
                     "visibility already specified",
@@ -925,6 +3880,84 @@ impl Args {
             }
 
             self.serializer = Some(input.parse()?);
+        } else if ident == "mailbox_strategy" {
+            if self.mailbox_strategy.is_some() {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "mailbox strategy already specified",
+                ));
+            }
+
+            let mailbox_strategy: syn::LitStr = input.parse()?;
+            check_mailbox_strategy(&mailbox_strategy)?;
+            self.mailbox_strategy = Some(mailbox_strategy);
+        } else if ident == "telemetry" {
+            if self.telemetry.is_some() {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "telemetry already specified",
+                ));
+            }
+
+            let telemetry: syn::LitStr = input.parse()?;
+            if telemetry.value() != "opentelemetry" {
+                return Err(syn::Error::new(
+                    telemetry.span(),
+                    "expected \"opentelemetry\"",
+                ));
+            }
+            self.telemetry = Some(telemetry);
+        } else if ident == "mock" {
+            if self.mock.is_some() {
+                return Err(syn::Error::new(ident.span(), "mock already specified"));
+            }
+
+            self.mock = Some(input.parse()?);
+        } else if ident == "timeouts" {
+            if self.timeouts.is_some() {
+                return Err(syn::Error::new(ident.span(), "timeouts already specified"));
+            }
+
+            self.timeouts = Some(input.parse()?);
+        } else if ident == "try_cast" {
+            if self.try_cast.is_some() {
+                return Err(syn::Error::new(ident.span(), "try_cast already specified"));
+            }
+
+            self.try_cast = Some(input.parse()?);
+        } else if ident == "on_panic" {
+            if self.on_panic.is_some() {
+                return Err(syn::Error::new(ident.span(), "on_panic already specified"));
+            }
+
+            self.on_panic = Some(parse_on_panic(&input.parse()?)?);
+        } else if ident == "client_mod" {
+            if self.client_mod.is_some() {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "client module already specified",
+                ));
+            }
+
+            self.client_mod = Some(input.parse()?);
+        } else if ident == "extend" {
+            if self.extend.is_some() {
+                return Err(syn::Error::new(ident.span(), "extend already specified"));
+            }
+
+            self.extend = Some(input.parse()?);
+        } else if ident == "extensions" {
+            if !self.extensions.is_empty() {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "extensions already specified",
+                ));
+            }
+
+            let content;
+            syn::bracketed!(content in input);
+            let extensions = content.parse_terminated::<_, Token![,]>(syn::Path::parse)?;
+            self.extensions = extensions.into_iter().collect();
         } else {
             return Err(syn::Error::new(ident.span(), "unknown argument"));
         }
@@ -933,6 +3966,206 @@ impl Args {
     }
 }
 
+/// Parses the value of a flag, which is `true` if it's given without one.
+fn parse_flag(flag: &syn::Ident, input: ParseStream) -> syn::Result<syn::LitBool> {
+    match input.parse::<Option<Token![=]>>()? {
+        Some(_) => input.parse(),
+        None => Ok(syn::LitBool::new(true, flag.span())),
+    }
+}
+
+/// Validates the `mailbox_strategy` argument.
+///
+/// The runtime hands out messages in the order they arrived and can only
+/// filter them by their tags. Messages with a priority are sent with a fixed
+/// tag per handler, so `"priority"` can take them out first, but the dispatch
+/// loop can't pick the latest message without taking all of them out of the
+/// mailbox. `"lifo"` is rejected instead of silently dispatching in arrival
+/// order.
+fn check_mailbox_strategy(mailbox_strategy: &syn::LitStr) -> syn::Result<()> {
+    match mailbox_strategy.value().trim() {
+        "fifo" | "priority" => Ok(()),
+        "lifo" => Err(syn::Error::new(
+            mailbox_strategy.span(),
+            "the lunatic runtime only delivers messages in the order they arrived, \
+             the \"lifo\" mailbox strategy is not available",
+        )),
+        _ => Err(syn::Error::new(
+            mailbox_strategy.span(),
+            "expected \"fifo\", \"lifo\" or \"priority\"",
+        )),
+    }
+}
+
+/// Parses the optional arguments of `#[terminate(timeout = "5s")]` into the
+/// timeout in milliseconds.
+fn parse_terminate_args(attr: &syn::Attribute) -> syn::Result<Option<u64>> {
+    if attr.tokens.is_empty() {
+        return Ok(None);
+    }
+    attr.parse_args_with(|input: ParseStream| {
+        let mut timeout_ms = None;
+        while !input.is_empty() {
+            let option: syn::Ident = input.parse()?;
+            if option != "timeout" {
+                return Err(syn::Error::new(
+                    option.span(),
+                    "unknown option, expected `timeout`",
+                ));
+            }
+            let _: Token![=] = input.parse()?;
+            let value: syn::LitStr = input.parse()?;
+            timeout_ms = Some(parse_duration_ms(&value.value()).ok_or_else(|| {
+                syn::Error::new(
+                    value.span(),
+                    "`timeout` must be a duration in ms, s or m, e.g. \"5s\"",
+                )
+            })?);
+            let _: Option<Token![,]> = input.parse()?;
+        }
+        Ok(timeout_ms)
+    })
+}
+
+/// Parses the content of `visibility(msgs = pub(crate), traits = pub)`.
+fn parse_item_visibility(content: ParseStream) -> syn::Result<ItemVisibility> {
+    let mut visibility = ItemVisibility::default();
+    while !content.is_empty() {
+        let item: syn::Ident = content.parse()?;
+        let slot = if item == "msgs" {
+            &mut visibility.msgs
+        } else if item == "traits" {
+            &mut visibility.traits
+        } else if item == "mock" {
+            &mut visibility.mock
+        } else {
+            return Err(syn::Error::new(
+                item.span(),
+                "unknown item, expected `msgs`, `traits` or `mock`",
+            ));
+        };
+        if slot.is_some() {
+            return Err(syn::Error::new(
+                item.span(),
+                format!("visibility of `{item}` already specified"),
+            ));
+        }
+        let _: Token![=] = content.parse()?;
+        *slot = Some(content.parse()?);
+        let _: Option<Token![,]> = content.parse()?;
+    }
+    Ok(visibility)
+}
+
+/// Parses the `on_panic` argument, `"restart"` optionally followed by the
+/// maximum number of restarts and the delay, e.g. `"restart(max = 3, delay =
+/// '1s')"`.
+fn parse_on_panic(on_panic: &syn::LitStr) -> syn::Result<RestartArgs> {
+    let error = |message: &str| syn::Error::new(on_panic.span(), message);
+    let value = on_panic.value();
+    let value = value.trim();
+    let options = match value.strip_prefix("restart") {
+        Some("") => "",
+        Some(rest) => rest
+            .trim()
+            .strip_prefix('(')
+            .and_then(|rest| rest.strip_suffix(')'))
+            .ok_or_else(|| error("expected \"restart\" or \"restart(max = 3, delay = '1s')\""))?,
+        None => return Err(error("only \"restart\" is supported")),
+    };
+    let mut args = RestartArgs {
+        max_restarts: None,
+        delay_ms: 0,
+    };
+    for option in options.split(',').map(str::trim).filter(|o| !o.is_empty()) {
+        let (key, value) = option
+            .split_once('=')
+            .ok_or_else(|| error("expected `key = value` options"))?;
+        let value = value.trim().trim_matches(|c| c == '\'' || c == '"');
+        match key.trim() {
+            "max" => {
+                args.max_restarts = Some(
+                    value
+                        .parse()
+                        .map_err(|_| error("`max` must be a number, e.g. max = 3"))?,
+                );
+            }
+            "delay" => {
+                args.delay_ms = parse_duration_ms(value).ok_or_else(|| {
+                    error("`delay` must be a duration in ms, s or m, e.g. delay = '500ms'")
+                })?;
+            }
+            _ => return Err(error("unknown option, expected `max` or `delay`")),
+        }
+    }
+    Ok(args)
+}
+
+/// Checks that an impl block marked with `extend` only contains handlers and
+/// no arguments that configure the process.
+fn check_extension(
+    args: &Args,
+    item_impl: &syn::ItemImpl,
+    lifecycle_methods: [&Option<syn::ImplItemMethod>; 5],
+) -> syn::Result<()> {
+    if let Some(method) = lifecycle_methods.iter().copied().flatten().next() {
+        return Err(syn::Error::new(
+            method.sig.ident.span(),
+            "impl blocks marked with `extend` can only contain handlers, \
+             lifecycle methods belong to the main impl block",
+        ));
+    }
+    if !item_impl.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            item_impl.generics.span(),
+            "generic impl blocks can't be extended",
+        ));
+    }
+    let process_arg = if args.serializer.is_some() {
+        Some("serializer")
+    } else if args.mailbox_strategy.is_some() {
+        Some("mailbox_strategy")
+    } else if args.on_panic.is_some() {
+        Some("on_panic")
+    } else if !args.extensions.is_empty() {
+        Some("extensions")
+    } else if args.export_schema() {
+        Some("export_schema")
+    } else if args.instrument() {
+        Some("instrument")
+    } else {
+        None
+    };
+    match process_arg {
+        Some(arg) => Err(syn::Error::new(
+            args.extend.span(),
+            format!("`{arg}` can't be used together with `extend`, set it on the main impl block"),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Parses the name of the module set with `client_mod`.
+fn parse_client_mod(client_mod: &syn::LitStr) -> syn::Result<syn::Ident> {
+    client_mod
+        .parse()
+        .map_err(|_| syn::Error::new(client_mod.span(), "expected a module name"))
+}
+
+/// Parses a duration like `500ms`, `2s` or `1m` into milliseconds.
+fn parse_duration_ms(duration: &str) -> Option<u64> {
+    let (number, factor) = if let Some(ms) = duration.strip_suffix("ms") {
+        (ms, 1)
+    } else if let Some(s) = duration.strip_suffix('s') {
+        (s, 1_000)
+    } else if let Some(m) = duration.strip_suffix('m') {
+        (m, 60_000)
+    } else {
+        return None;
+    };
+    number.trim().parse::<u64>().ok()?.checked_mul(factor)
+}
+
 impl Parse for Args {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let mut args = Args::default();
@@ -946,27 +4179,194 @@ impl Parse for Args {
 
 enum ItemAttr {
     Init,
+    WarmUp,
     Terminate,
     HandleLinkTrapped,
+    Snapshot,
     HandleMessage,
     HandleRequest,
     HandleDeferredRequest,
+    HandleResponderRequest,
 }
 
 impl ItemAttr {
     fn from_str(s: &str) -> Option<ItemAttr> {
         match s {
             "init" => Some(ItemAttr::Init),
+            "warm_up" => Some(ItemAttr::WarmUp),
             "terminate" => Some(ItemAttr::Terminate),
             "handle_link_death" => Some(ItemAttr::HandleLinkTrapped),
+            "snapshot" => Some(ItemAttr::Snapshot),
             "handle_message" => Some(ItemAttr::HandleMessage),
             "handle_request" => Some(ItemAttr::HandleRequest),
             "handle_deferred_request" => Some(ItemAttr::HandleDeferredRequest),
+            "handle_responder_request" => Some(ItemAttr::HandleResponderRequest),
             _ => None,
         }
     }
 }
 
+/// Returns the attributes of a handler method that are forwarded to its
+/// message type and client methods.
+fn forwarded_attrs(attrs: &[syn::Attribute]) -> Vec<&syn::Attribute> {
+    filter_attrs(attrs, &["doc", "cfg", "allow", "deprecated"])
+}
+
+/// Returns the attributes of a handler method that are forwarded to the
+/// implementations generated for it. Docs and deprecation notes have no effect
+/// on implementations.
+fn forwarded_impl_attrs(attrs: &[syn::Attribute]) -> Vec<&syn::Attribute> {
+    filter_attrs(attrs, &["cfg", "allow"])
+}
+
+/// Returns the `#[cfg]` attributes of a handler method.
+fn cfg_attrs(attrs: &[syn::Attribute]) -> Vec<&syn::Attribute> {
+    filter_attrs(attrs, &["cfg"])
+}
+
+fn filter_attrs<'a>(attrs: &'a [syn::Attribute], names: &[&str]) -> Vec<&'a syn::Attribute> {
+    attrs
+        .iter()
+        .filter(|attr| names.iter().any(|name| attr.path.is_ident(name)))
+        .collect()
+}
+
+/// Combines the predicates of `#[cfg]` attributes into `all(..)`, or returns
+/// `None` if there are none.
+fn cfg_predicate(cfgs: &[&syn::Attribute]) -> Option<TokenStream> {
+    if cfgs.is_empty() {
+        return None;
+    }
+    let predicates = cfgs.iter().map(|cfg| {
+        cfg.parse_args::<TokenStream>()
+            .unwrap_or_else(|err| err.into_compile_error())
+    });
+    Some(quote! { all( #( #predicates ),* ) })
+}
+
+/// Returns the first part of `ty` that can't be serialized as a reply, with a
+/// description of it. These are `impl Trait` types, trait objects and
+/// references.
+fn unsendable_type(ty: &syn::Type) -> Option<(&syn::Type, &'static str)> {
+    match ty {
+        Type::ImplTrait(_) => Some((ty, "an `impl Trait` type")),
+        Type::TraitObject(_) => Some((ty, "a trait object")),
+        Type::Reference(_) => Some((ty, "a reference")),
+        Type::Path(path) => path
+            .path
+            .segments
+            .iter()
+            .filter_map(|segment| match &segment.arguments {
+                PathArguments::AngleBracketed(generics) => Some(generics),
+                _ => None,
+            })
+            .flat_map(|generics| &generics.args)
+            .find_map(|arg| match arg {
+                syn::GenericArgument::Type(ty) => unsendable_type(ty),
+                _ => None,
+            }),
+        Type::Tuple(tuple) => tuple.elems.iter().find_map(unsendable_type),
+        Type::Array(array) => unsendable_type(&array.elem),
+        Type::Slice(slice) => unsendable_type(&slice.elem),
+        Type::Paren(paren) => unsendable_type(&paren.elem),
+        Type::Group(group) => unsendable_type(&group.elem),
+        _ => None,
+    }
+}
+
+/// Converts the `reply` of a handler returning `output` into the type set with
+/// `map_reply`.
+///
+/// Iterators, `impl Iterator`, `impl IntoIterator` or `Box<dyn Iterator>`, are
+/// collected, other types are converted with `Into`.
+fn convert_reply(output: &syn::ReturnType, reply: TokenStream) -> TokenStream {
+    let is_iterator = match output {
+        syn::ReturnType::Type(_, ty) => returns_iterator(ty),
+        syn::ReturnType::Default => false,
+    };
+    match is_iterator {
+        true => quote! { std::iter::FromIterator::from_iter(#reply) },
+        false => quote! { std::convert::Into::into(#reply) },
+    }
+}
+
+/// Returns the item type if a request handler returning `output` returns a
+/// `ResponseStream<T>`.
+fn stream_item(output: &syn::ReturnType) -> Option<&syn::Type> {
+    let syn::ReturnType::Type(_, ty) = output else {
+        return None;
+    };
+    let Type::Path(path) = ty.as_ref() else {
+        return None;
+    };
+    let last = path.path.segments.last()?;
+    if last.ident != "ResponseStream" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(generics) = &last.arguments else {
+        return None;
+    };
+    match generics.args.first() {
+        Some(syn::GenericArgument::Type(item)) => Some(item),
+        _ => None,
+    }
+}
+
+/// Returns `true` if `ty` is an `impl Trait` or `Box<dyn Trait>` of one of the
+/// iterator traits.
+fn returns_iterator(ty: &syn::Type) -> bool {
+    let bounds = match ty {
+        Type::ImplTrait(impl_trait) => &impl_trait.bounds,
+        Type::Path(path) => {
+            let Some(last) = path.path.segments.last().filter(|last| last.ident == "Box") else {
+                return false;
+            };
+            let PathArguments::AngleBracketed(generics) = &last.arguments else {
+                return false;
+            };
+            match generics.args.first() {
+                Some(syn::GenericArgument::Type(Type::TraitObject(object))) => &object.bounds,
+                _ => return false,
+            }
+        }
+        _ => return false,
+    };
+    bounds.iter().any(|bound| match bound {
+        syn::TypeParamBound::Trait(bound) => bound
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident.to_string().ends_with("Iterator")),
+        _ => false,
+    })
+}
+
+/// Returns `true` if a handler argument of type `ty` is borrowed, and stored
+/// as an owned value in the wrapper.
+fn is_borrowed(ty: &syn::Type) -> bool {
+    matches!(ty, Type::Reference(reference) if reference.mutability.is_none())
+}
+
+/// Returns the type of the wrapper field storing a handler argument of type
+/// `ty`.
+///
+/// Borrowed arguments are stored as owned values, `&str` as `String`, `&[T]`
+/// as `Vec<T>` and `&T` as `T`. It matches `ToOwned::Owned` of the borrowed
+/// type.
+fn owned_type(ty: &syn::Type) -> TokenStream {
+    match ty {
+        Type::Reference(reference) if is_borrowed(ty) => match &*reference.elem {
+            Type::Path(path) if path.path.is_ident("str") => quote! { String },
+            Type::Slice(slice) => {
+                let elem = &slice.elem;
+                quote! { Vec<#elem> }
+            }
+            elem => quote! { #elem },
+        },
+        ty => quote! { #ty },
+    }
+}
+
 fn filter_typed_args<'a>(
     args: impl Iterator<Item = &'a syn::FnArg>,
 ) -> impl Iterator<Item = &'a syn::PatType> {
@@ -987,14 +4387,78 @@ fn filter_typed_arg_names<'a>(
         })
 }
 
+/// Kind of a handler, deciding its entry in the `AP::Handlers` tuple.
+#[derive(Clone, Copy)]
+enum HandlerKind {
+    Message,
+    Request,
+    /// Request handler returning a `ResponseStream`.
+    StreamRequest,
+    DeferredRequest,
+    /// Deferred request handler taking a `Responder`.
+    ResponderRequest,
+    /// Request handler marked with `#[continue_with]`.
+    ContinuedRequest,
+    /// Method resuming a `ContinuedRequest`.
+    Continuation,
+}
+
+impl HandlerKind {
+    /// Returns the type wrapping the message type in the `AP::Handlers`
+    /// tuple.
+    fn handler_type(self) -> TokenStream {
+        match self {
+            HandlerKind::Message | HandlerKind::Continuation => {
+                quote! { lunatic::ap::handlers::Message }
+            }
+            HandlerKind::Request => quote! { lunatic::ap::handlers::Request },
+            HandlerKind::StreamRequest => quote! { lunatic::ap::handlers::StreamRequest },
+            HandlerKind::DeferredRequest | HandlerKind::ContinuedRequest => {
+                quote! { lunatic::ap::handlers::DeferredRequest }
+            }
+            HandlerKind::ResponderRequest => quote! { lunatic::ap::handlers::ResponderRequest },
+        }
+    }
+}
+
+/// Handler method taking an entry of the `AP::Handlers` tuple.
+struct HandlerSlot<'a> {
+    kind: HandlerKind,
+    method: &'a syn::ImplItemMethod,
+    /// `#[cfg]` attributes deciding if the handler is compiled.
+    cfgs: Vec<&'a syn::Attribute>,
+}
+
+impl<'a> HandlerSlot<'a> {
+    fn new(kind: HandlerKind, method: &'a syn::ImplItemMethod) -> Self {
+        HandlerSlot {
+            kind,
+            method,
+            cfgs: cfg_attrs(&method.attrs),
+        }
+    }
+}
+
 struct HandlerStructure<'a> {
-    attrs: &'a Vec<syn::Attribute>,
-    ident: &'a syn::Ident,
+    /// Attributes of client method declarations.
+    attrs: Vec<&'a syn::Attribute>,
+    /// Attributes of client method implementations.
+    impl_attrs: Vec<&'a syn::Attribute>,
+    /// `#[cfg]` attributes of the handler.
+    cfg_attrs: Vec<&'a syn::Attribute>,
+    ident: syn::Ident,
     generics: &'a syn::Generics,
+    where_clause: Option<&'a syn::WhereClause>,
     args: Vec<TokenStream>,
     return_ty: TokenStream,
     message_type: syn::Ident,
     handler_args: Vec<syn::Ident>,
+    /// Values of the wrapper fields, borrowed arguments are converted to
+    /// owned values.
+    message_args: Vec<TokenStream>,
+    /// Values of the wrapper fields for a request that is sent repeatedly,
+    /// owned arguments are cloned.
+    cloned_args: Vec<TokenStream>,
 }
 
 impl<'a> HandlerStructure<'a> {
@@ -1011,41 +4475,43 @@ impl<'a> HandlerStructure<'a> {
             .map(|(ident, ty)| quote! { #ident: #ty })
             .collect();
         let return_ty = if is_deferred {
-            match &inputs.last() {
-                Some(FnArg::Typed(path)) => match &*path.ty {
-                    Type::Path(path) => {
-                        let last = path.path.segments.last().unwrap();
-                        match &last.arguments {
-                            PathArguments::AngleBracketed(generics) => {
-                                let response_type = generics.args.first().unwrap();
-                                quote! { #response_type }
-                            }
-                            _ => quote! {()},
-                        }
-                    }
-                    _ => quote! {()},
-                },
-                _ => quote! {()},
-            }
+            deferred_response_type(inputs)
         } else {
             match output {
                 syn::ReturnType::Default => quote! {()},
                 syn::ReturnType::Type(_, ty) => quote! {#ty},
             }
         };
-        let message_type = AbstractProcess::handler_wrapper_ident(ident);
+        let message_type = AbstractProcess::default_handler_wrapper_ident(ident);
         let handler_args = filter_typed_arg_names(inputs.iter())
             .map(|(ident, _ty)| ident)
             .collect();
+        let message_args = filter_typed_arg_names(inputs.iter())
+            .map(|(ident, ty)| match is_borrowed(ty) {
+                true => quote! { std::borrow::ToOwned::to_owned(#ident) },
+                false => quote! { #ident },
+            })
+            .collect();
+        let cloned_args = filter_typed_arg_names(inputs.iter())
+            .map(|(ident, ty)| match is_borrowed(ty) {
+                true => quote! { std::borrow::ToOwned::to_owned(#ident) },
+                false => quote! { std::clone::Clone::clone(&#ident) },
+            })
+            .collect();
 
         HandlerStructure {
-            attrs,
-            ident,
+            attrs: forwarded_attrs(attrs),
+            impl_attrs: forwarded_impl_attrs(attrs),
+            cfg_attrs: cfg_attrs(attrs),
+            ident: ident.clone(),
             generics,
+            where_clause: generics.where_clause.as_ref(),
             args,
             return_ty,
             message_type,
             handler_args,
+            message_args,
+            cloned_args,
         }
     }
 }
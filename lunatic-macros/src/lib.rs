@@ -58,16 +58,199 @@ pub fn main(_args: TokenStream, item: TokenStream) -> TokenStream {
 /// - Use `#[handle_message]`, `#[handle_request]` and
 ///   `#[handle_deferred_request]` attributes to specify message and request
 ///   handlers.
+/// - A `#[handle_responder_request]` method takes a
+///   `&mut Responder<Response, Self>` as last argument and returns
+///   `Option<Response>`. It can respond early through the `Responder` and keep
+///   running, or return the response. Responding twice panics.
+/// - A `#[handle_message]` method can take `self` by value and return the new
+///   state, e.g. `fn toggle(self) -> Self`, to replace the state instead of
+///   mutating it. Together with an enum as the state this allows switching
+///   between variants, see `examples/reconnecting_client.rs`.
+/// - Add `#[continue_with(method)]` to a `#[handle_request]` method to let it
+///   yield. The handler takes a `Resume<Reply>` as last argument and returns a
+///   `Step<Response, Pending>`. If it returns `Step::Pending(state)`, the
+///   caller keeps waiting and `method(&mut self, state: Pending, reply: Reply)`
+///   is called once the reply is sent through the `Resume` handle. It returns
+///   the same `Step<Response, Pending>`, which is checked at compile time.
+/// - Add `#[output]` to a `#[handle_message]` method to emit its return value
+///   as an output of the process. Outputs are forwarded to other processes
+///   connected with `ProcessRef::pipe_to`.
+/// - `#[terminate(timeout = "5s")]` kills the process if the `#[terminate]`
+///   method and the registered cleanup actions don't finish in time. The
+///   `shutdown` call returns and a warning is logged.
+/// - The `#[handle_link_death]` method receives either the `TrapInfo` of the
+///   failed link, or only its `Tag`.
+/// - A `#[snapshot]` method, e.g. `fn snapshot(&self) -> Arg`, returns the
+///   `init` argument restoring the current state. It allows the process to be
+///   moved to another node with `lunatic::distributed::migrate`.
+/// - A `#[warm_up]` method, e.g. `fn warm_up() -> Buffers`, runs in the new
+///   process right before `init`, which receives its result as third
+///   argument: `fn init(config: Config<Self>, arg: Arg, buffers: Buffers)`.
+///   It keeps preparations that don't depend on the argument, like reading
+///   environment variables or allocating buffers, out of `init`. Without a
+///   `#[warm_up]` method `init` takes only 2 arguments.
 ///
 /// Specifying message types is unnecessary because the macro will create
 /// wrapper types for messages on all handlers. Handlers can take an arbitrary
 /// number of parameters and invoking them works the same as directly calling
 /// the method on the struct without spawning it as a process.
 ///
-/// A trait is generated and defaults to private and follows the name of your
-/// type with `Handler` added as a suffix. To rename or change the visibility of
-/// the generated trait, you can use the `trait_name` and `visbility` arguments
-/// with `#[abstract_process(trait_name = "MyHandler", visibility = pub)]`.
+/// Each message carries a hash of the name of its handler. Handlers taking the
+/// same parameters can't receive each other's messages, even if the sender was
+/// built with a different set of handlers. A mismatched message fails to decode
+/// instead of being dispatched to the wrong handler.
+///
+/// Handlers get consecutive ids in the order they are declared, starting at 1.
+/// An id can be assigned with `#[handle_request(id = 7)]`, so that it stays
+/// the same when other handlers are added or reordered. With
+/// `#[abstract_process(export_schema)]` a `SCHEMA` constant describes the
+/// messages as JSON for senders that aren't written in Rust: the `name`, `id`,
+/// `direction` (`cast` or `request`), `method_id` and the Rust types of the
+/// `args` and `reply` of each handler. The id is stored in bits 56 to 61 of
+/// the message tag. The arguments are serialized as a tuple, starting with the
+/// `method_id` as a `u32`, and requests send a pair of this tuple and the
+/// process the reply is sent to.
+///
+/// A removed handler can keep its id with `#[removed_handler(id = 4, name =
+/// "old_get")]` below `#[abstract_process]`, so that messages from clients
+/// built before the removal don't reach the handler that would take the id
+/// over. They are logged, counted in the `<process>::old_get::removed` metric
+/// and dropped. For a removed request handler, list its argument types with
+/// `request(String, u32)` and requests sent with a timeout are answered with
+/// `RequestError::HandlerRemoved`.
+///
+/// Handlers can take borrowed arguments, e.g. `fn put(&mut self, key: &str,
+/// value: &[u8])`. The message type stores owned values (`String`, `Vec<u8>`,
+/// or `T` for `&T`), and the client method converts the arguments with
+/// `ToOwned`.
+///
+/// Generic impl blocks are supported, with bounds either inside the angle
+/// brackets or in a `where` clause, and const parameters. The wrapper types
+/// carry all generic parameters, so each instantiation (`KvStore<String>`,
+/// `KvStore<u64>`) is a separate `AbstractProcess`.
+///
+/// Two traits are generated, which default to private and follow the name of
+/// your type with `Messages` and `Requests` added as a suffix. To rename or
+/// change the visibility of the generated traits, you can use the
+/// `trait_name` and `visibility` arguments with
+/// `#[abstract_process(trait_name = "MyHandler", visibility = pub)]`, which
+/// generates `MyHandlerMessages` and `MyHandlerRequests`. The full names can
+/// also be set with `message_trait_name` and `request_trait_name`.
+///
+/// The generated items can also have separate visibilities, e.g.
+/// `visibility(msgs = pub(crate), traits = pub, mock = pub(crate))`. `traits`
+/// covers the traits and their implementations for `ProcessRef`, items that
+/// are left out are private. The message types are then declared inside of a
+/// private module and re-exported with the `msgs` visibility, so that other
+/// crates can call the process through the traits without being able to
+/// construct the messages.
+///
+/// The generated message types are hidden from the docs. A handler can name
+/// its message type with `#[handle_request(name = "FetchUser")]`, which also
+/// avoids collisions between handlers of the same name in different impl
+/// blocks of the same module.
+///
+/// Doc comments and `#[cfg]`, `#[allow]` and `#[deprecated]` attributes on
+/// a handler are forwarded to its message type and client methods. No code is
+/// generated for a handler that is compiled out with `#[cfg]`.
+///
+/// Each request handler also gets a `_timeout` variant, e.g.
+/// `count_timeout(Duration)`, returning `Result<u32, RequestError>`. Each
+/// message handler gets a `try_` variant, e.g. `try_increment()`, that doesn't
+/// send the message and returns `false` if the process isn't alive anymore.
+/// They can be disabled with `timeouts = false` and `try_cast = false`. A
+/// handler whose variant would have the same name as another method is
+/// rejected at compile time.
+///
+/// Requests that are safe to repeat can be retried after a timeout with
+/// `#[handle_request(retry(times = 3, backoff = "100ms"))]`. Calls with a
+/// timeout, through `with_timeout` or the `_timeout` variant, then send the
+/// request again up to `times` times, waiting `backoff` before the first retry
+/// and doubling the delay after each one. The timeout applies to each attempt,
+/// and the error of the last attempt is returned. The arguments need to
+/// implement `Clone`, because each attempt sends a new request.
+///
+/// Replies are sent to the caller, so the return type of a request handler
+/// needs to be serializable. Handlers returning an `impl Trait` type, a trait
+/// object or a reference are rejected at compile time, unless the reply is
+/// converted with `#[handle_request(map_reply = Vec<Entry>)]`. The client
+/// method then returns `Vec<Entry>`. Iterators, `impl Iterator` and
+/// `Box<dyn Iterator>`, are collected into it, other types are converted with
+/// `Into`, e.g. `&str` with `map_reply = String`.
+///
+/// A request handler returning `ResponseStream<T>` sends its reply as a
+/// stream of items, each in its own message followed by an end of stream
+/// marker. The client method returns a `ResponseIter<T, _>`, an iterator of
+/// `Result<T, StreamError>` that receives the items lazily. With a timeout,
+/// through `with_timeout` or the `_timeout` variant, the timeout applies to
+/// each item. Streams can't be combined with `map_reply` or `retry`.
+///
+/// Trailing parameters can be given default values with
+/// `#[handle_request(default(limit = 10))]`. The client method then omits
+/// them, and a `_with` variant (`list_with`) takes all parameters.
+///
+/// With `#[abstract_process(client_mod = "counter_client")]` the message
+/// types, the traits, their implementations for `ProcessRef` and the mock are
+/// generated inside of a `counter_client` module, which also re-exports the
+/// type of the process. A thin API crate can re-export only this module, e.g.
+/// `pub use server::counter_client as counter;`, so that clients don't need the
+/// rest of the server in scope. The module and its items use the `visibility`
+/// of the traits, and the type of the process needs to be at least as visible.
+///
+/// The handlers of a process can be split across impl blocks, e.g. in
+/// different modules. Additional blocks are marked with
+/// `#[abstract_process(extend = "StoreWrites")]` and only contain handlers.
+/// They generate their own message types and traits, and a `StoreWrites`
+/// marker type registering their handlers, which the main block adds with
+/// `#[abstract_process(extensions = [writes::StoreWrites])]`. Lifecycle methods
+/// and arguments configuring the process, like `serializer`, belong to the main
+/// block. Blocks in the same module need different trait names.
+///
+/// Messages are serialized with `Bincode` by default. A different serializer
+/// can be selected with `#[abstract_process(serializer = Json)]`. It's used by
+/// all generated handlers and methods on `ProcessRef`, and because the
+/// serializer is part of the registered process name, a process can't be
+/// looked up with a reference type using a different serializer.
+///
+/// Messages are dispatched in the order they arrived, which can be spelled
+/// out with `#[abstract_process(mailbox_strategy = "fifo")]`. With
+/// `mailbox_strategy = "priority"`, message handlers can be given a priority
+/// from 1 to 255 with `#[handle_message(priority = 2)]`. Waiting messages with
+/// the highest priority are handled first, messages with the same priority and
+/// all other messages and requests in the order they arrived. The priority is
+/// part of the tag the generated methods send the message with, messages
+/// forwarded by pipes are handled without priority. The runtime doesn't offer
+/// a way to take the newest message out of the mailbox first, so `"lifo"` is
+/// rejected at compile time.
+///
+/// With `#[abstract_process(on_panic = "restart")]` the process restarts in
+/// place if a handler panics. The panic is logged, the state is dropped and
+/// `init` is called again with a clone of the original argument, which needs
+/// to implement `Clone`. References to the process stay valid. The number of
+/// restarts and the delay before each restart can be limited with
+/// `on_panic = "restart(max = 3, delay = '1s')"`; delays are given in `ms`,
+/// `s` or `m`. The process dies on the next panic after the last restart.
+///
+/// With `#[abstract_process(instrument)]` each handler call is recorded in the
+/// metrics of the runtime: the number of calls and panics, and a histogram of
+/// the duration in seconds, named after the process and handler type. Nothing
+/// is measured without it.
+///
+/// With `#[abstract_process(telemetry = "opentelemetry")]` the W3C
+/// `traceparent` and `tracestate` headers of the current OpenTelemetry span
+/// are sent with every message and request, after the arguments. Each handler
+/// runs in a child span of the sender's span, started with the global tracer
+/// and named after the process and handler, e.g. `Counter::increment`. This
+/// needs the `opentelemetry` feature of `lunatic`. Without the argument the
+/// messages don't carry any headers.
+///
+/// With `#[abstract_process(mock = true)]` a `Mock{Type}Ref` is generated
+/// for tests, e.g. `MockCounterRef`. It implements both traits without
+/// spawning a process. The response of each request is set with a closure,
+/// e.g. `mock.expect_count(|| 42)`, and `mock.calls()` returns the names of
+/// the called handlers. Code that should accept either the mock or a
+/// `ProcessRef` can be generic over the traits, e.g.
+/// `C: CounterRequests<ReturnTy_count = u32>`, or take a trait object.
 ///
 /// # Examples
 ///
@@ -0,0 +1,220 @@
+//! Generic processes for common patterns.
+//!
+//! A [`Router`] forwards messages to a set of workers, picked by a consistent
+//! hash of a routing key.
+//!
+//! An [`Aggregator`] folds incoming items per time window and sends the result
+//! to a downstream process at the end of each window.
+//!
+//! A [`Throttle`] forwards messages to a target process at most at a given
+//! [`Rate`], buffering the ones arriving faster.
+//!
+//! A [`Circuit`] breaker fails calls to a target process right away after
+//! too many consecutive failures, and lets a probe call through once a reset
+//! timeout passed.
+//!
+//! A [`Debounce`] forwards only the last message of each burst of messages
+//! with the same key to a target process, once the burst is over.
+//!
+//! A [`Cache`] holds values by key up to a capacity, evicting the least
+//! recently used ones, and can load missing values with a [`Loader`].
+//!
+//! A [`Pipeline`] chains processes running a [`Stage`] each, passing the
+//! output of one stage as input to the next.
+//!
+//! An [`EventSourcing`] process derives its state from the events produced by
+//! the commands it executes, and restores it from an [`EventStore`] after a
+//! restart.
+//!
+//! A [`Saga`] runs a sequence of steps, each in its own process, and undoes
+//! the completed ones with their compensating actions if a step fails.
+//!
+//! A [`StateMachine`] is an [`AbstractProcess`] that holds one value of a
+//! [`State`] type. Every incoming [`Event`] is passed to
+//! [`State::transition`], and if it returns [`Transition::Next`] the process
+//! moves to the new state, calling the [`on_exit`](State::on_exit) hook of
+//! the old state and the [`on_enter`](State::on_enter) hook of the new one.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use lunatic::actor::{State, StateMachine, Transition};
+//! use lunatic::AbstractProcess;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+//! enum Door {
+//!     Open,
+//!     Closed,
+//! }
+//!
+//! #[derive(Serialize, Deserialize)]
+//! enum Action {
+//!     Push,
+//!     Pull,
+//! }
+//!
+//! impl State<Action> for Door {
+//!     fn transition(&mut self, action: Action) -> Transition<Self> {
+//!         match (&self, action) {
+//!             (Door::Closed, Action::Push) => Transition::Next(Door::Open),
+//!             (Door::Open, Action::Pull) => Transition::Next(Door::Closed),
+//!             _ => Transition::Stay,
+//!         }
+//!     }
+//! }
+//!
+//! let door = StateMachine::<Door, Action>::start(Door::Closed).unwrap();
+//! door.trigger(Action::Push);
+//! assert_eq!(door.current_state(), Door::Open);
+//! ```
+
+mod aggregator;
+mod cache;
+mod circuit;
+mod debounce;
+mod event_sourcing;
+mod pipeline;
+mod router;
+mod saga;
+mod throttle;
+
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::ap::handlers::{Message, Request};
+use crate::ap::{self, AbstractProcess, Config, MessageHandler, ProcessRef, RequestHandler};
+use crate::serializer::Bincode;
+
+pub use self::aggregator::{
+    Aggregator, AggregatorArg, AggregatorRef, AggregatorState, Flush, Tick,
+};
+pub use self::cache::{
+    Cache, CacheConfig, CacheRef, CacheState, CacheStats, Get, GetCacheStats, Invalidate, Load,
+    Loader, Put,
+};
+pub use self::circuit::{
+    Acquire, Circuit, CircuitArg, CircuitBreaker, CircuitError, CircuitRef, CircuitState,
+    GetCircuitState, Outcome,
+};
+pub use self::debounce::{Debounce, DebounceArg, DebounceRef, DebounceState, Debounced, Elapsed};
+pub use self::event_sourcing::{
+    Append, CommandError, EventSourced, EventSourcing, EventSourcingConfig, EventSourcingRef,
+    EventSourcingState, EventStore, Execute, LoadStream, SaveSnapshot, StoredStream,
+};
+pub use self::pipeline::{
+    GetStageStatus, OnError, Pipeline, PipelineBuilder, Stage, StageArg, StageProcess, StageState,
+    StageStatus,
+};
+pub use self::router::{
+    AddWorker, RemoveWorker, Route, Router, RouterRef, RouterState, SpawnWorker, WorkerFor,
+};
+pub use self::saga::{Saga, SagaFailed, SagaResult, SagaStep, StepError};
+pub use self::throttle::{
+    Rate, Release, Throttle, ThrottleArg, ThrottleRef, ThrottleState, Throttled,
+};
+
+/// A state of a [`StateMachine`].
+pub trait State<E: Event>: Serialize + DeserializeOwned + Clone + 'static {
+    /// Handles `event` and decides if the machine should move to a new state.
+    fn transition(&mut self, event: E) -> Transition<Self>;
+
+    /// Called when the machine enters this state, including the initial
+    /// state.
+    fn on_enter(&mut self) {}
+
+    /// Called when the machine leaves this state, including when the process
+    /// shuts down.
+    fn on_exit(&mut self) {}
+}
+
+/// An event driving a [`StateMachine`].
+///
+/// Implemented for all types that can be sent between processes.
+pub trait Event: Serialize + DeserializeOwned + 'static {}
+
+impl<E> Event for E where E: Serialize + DeserializeOwned + 'static {}
+
+/// Result of [`State::transition`].
+pub enum Transition<S> {
+    /// Stay in the current state, without calling any hooks.
+    Stay,
+    /// Move to the new state.
+    Next(S),
+}
+
+/// A process driven by events of type `E` that moves between states of type
+/// `S`.
+///
+/// The argument passed to [`start`](AbstractProcess::start) is the initial
+/// state.
+pub struct StateMachine<S, E> {
+    phantom: PhantomData<(S, E)>,
+}
+
+impl<S, E> AbstractProcess for StateMachine<S, E>
+where
+    S: State<E>,
+    E: Event,
+{
+    type State = S;
+    type Serializer = Bincode;
+    type Arg = S;
+    type Handlers = (Message<E>, Request<GetState>);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, mut initial: S) -> Result<S, ()> {
+        initial.on_enter();
+        Ok(initial)
+    }
+
+    fn terminate(mut state: S) {
+        state.on_exit();
+    }
+}
+
+impl<S, E> MessageHandler<E> for StateMachine<S, E>
+where
+    S: State<E>,
+    E: Event,
+{
+    fn handle(mut state: ap::State<Self>, event: E) {
+        if let Transition::Next(next) = state.transition(event) {
+            state.on_exit();
+            *state = next;
+            state.on_enter();
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetState;
+impl<S, E> RequestHandler<GetState> for StateMachine<S, E>
+where
+    S: State<E>,
+    E: Event,
+{
+    type Response = S;
+
+    fn handle(state: ap::State<Self>, _: GetState) -> S {
+        state.clone()
+    }
+}
+
+impl<S, E> ProcessRef<StateMachine<S, E>>
+where
+    S: State<E>,
+    E: Event,
+{
+    /// Sends `event` to the state machine.
+    pub fn trigger(&self, event: E) {
+        self.send(event);
+    }
+
+    /// Returns a copy of the current state.
+    pub fn current_state(&self) -> S {
+        self.request(GetState)
+    }
+}
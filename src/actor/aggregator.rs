@@ -0,0 +1,241 @@
+use std::marker::PhantomData;
+use std::mem;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::ap::handlers::Message;
+use crate::ap::{self, AbstractProcess, Config, MessageHandler, ProcessRef};
+use crate::host;
+use crate::serializer::{Bincode, CanSerialize};
+
+/// A process folding incoming items of type `I` into a value of type `O` per
+/// time window, and sending the value to a downstream process at the end of
+/// each window.
+///
+/// A new window opens every `stride` and stays open for `window`, starting
+/// with a copy of the initial value. With a `stride` equal to the `window`
+/// the windows follow each other, with a shorter `stride` they overlap and
+/// each item is folded into all open windows. A value is sent at the end of
+/// every window, also if no items arrived during it.
+///
+/// The fold function can't capture any values, because it's sent to the new
+/// process as a function pointer. Windows that are still open when the
+/// process shuts down are dropped.
+pub struct Aggregator<I, O> {
+    phantom: PhantomData<(I, O)>,
+}
+
+/// Reference to an [`Aggregator`].
+pub type AggregatorRef<I, O> = ProcessRef<Aggregator<I, O>>;
+
+impl<I, O> Aggregator<I, O>
+where
+    I: Serialize + DeserializeOwned + Clone + 'static,
+    O: Serialize + DeserializeOwned + Clone + 'static,
+{
+    /// Starts an aggregator with windows that follow each other, sending
+    /// the folded values to `downstream`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is zero.
+    pub fn new<D>(
+        window: Duration,
+        fold: fn(O, I) -> O,
+        initial: O,
+        downstream: ProcessRef<D>,
+    ) -> AggregatorRef<I, O>
+    where
+        D: MessageHandler<O>,
+        D::Serializer: CanSerialize<O>,
+    {
+        Self::sliding(window, window, fold, initial, downstream)
+    }
+
+    /// Starts an aggregator opening a new window every `stride`, sending the
+    /// folded values to `downstream`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` or `stride` is zero.
+    pub fn sliding<D>(
+        window: Duration,
+        stride: Duration,
+        fold: fn(O, I) -> O,
+        initial: O,
+        downstream: ProcessRef<D>,
+    ) -> AggregatorRef<I, O>
+    where
+        D: MessageHandler<O>,
+        D::Serializer: CanSerialize<O>,
+    {
+        assert!(
+            !window.is_zero() && !stride.is_zero(),
+            "window and stride of an aggregator can't be zero"
+        );
+        let arg = AggregatorArg {
+            window,
+            stride,
+            fold: fold as usize,
+            initial,
+            downstream: downstream.id(),
+            send: send_downstream::<D, O> as fn(u64, O) as usize,
+        };
+        match Self::start(arg) {
+            Ok(aggregator) => aggregator,
+            Err(err) => panic!("Failed to start aggregator: {err:?}"),
+        }
+    }
+}
+
+/// Argument of an [`Aggregator`].
+#[derive(Serialize, Deserialize)]
+pub struct AggregatorArg<O> {
+    window: Duration,
+    stride: Duration,
+    /// Pointer to the fold function.
+    fold: usize,
+    initial: O,
+    /// Process id of the downstream process, on the same node.
+    downstream: u64,
+    /// Pointer to [`send_downstream`], instantiated for the type of the
+    /// downstream process.
+    send: usize,
+}
+
+fn send_downstream<D, O: 'static>(process_id: u64, value: O)
+where
+    D: MessageHandler<O>,
+    D::Serializer: CanSerialize<O>,
+{
+    let downstream = unsafe { ProcessRef::<D>::new(host::node_id(), process_id) };
+    downstream.send(value);
+}
+
+/// State of an [`Aggregator`].
+pub struct AggregatorState<I, O> {
+    window: Duration,
+    stride: Duration,
+    fold: fn(O, I) -> O,
+    initial: O,
+    downstream: u64,
+    send: fn(u64, O),
+    /// Open windows with their folded values, oldest first.
+    windows: Vec<(u64, O)>,
+    next_window: u64,
+}
+
+impl<I, O> AggregatorState<I, O>
+where
+    O: Clone,
+{
+    /// Opens a new window that is flushed after `window`.
+    fn open_window<AP>(&mut self, this: ProcessRef<AP>)
+    where
+        AP: AbstractProcess,
+        AP::Serializer: CanSerialize<Flush>,
+    {
+        let id = self.next_window;
+        self.next_window += 1;
+        self.windows.push((id, self.initial.clone()));
+        this.delayed_send(Flush(id), self.window);
+    }
+}
+
+impl<I, O> AbstractProcess for Aggregator<I, O>
+where
+    I: Serialize + DeserializeOwned + Clone + 'static,
+    O: Serialize + DeserializeOwned + Clone + 'static,
+{
+    type State = AggregatorState<I, O>;
+    type Serializer = Bincode;
+    type Arg = AggregatorArg<O>;
+    type Handlers = (Message<I>, Message<Tick>, Message<Flush>);
+    type StartupError = ();
+
+    fn init(config: Config<Self>, arg: AggregatorArg<O>) -> Result<Self::State, ()> {
+        // Safety: The pointers were created from functions with the same
+        // signatures in `Aggregator::sliding`.
+        let fold = unsafe { mem::transmute::<usize, fn(O, I) -> O>(arg.fold) };
+        let send = unsafe { mem::transmute::<usize, fn(u64, O)>(arg.send) };
+        let mut state = AggregatorState {
+            window: arg.window,
+            stride: arg.stride,
+            fold,
+            initial: arg.initial,
+            downstream: arg.downstream,
+            send,
+            windows: Vec::new(),
+            next_window: 0,
+        };
+        let this = config.self_ref();
+        state.open_window(this);
+        this.delayed_send(Tick, state.stride);
+        Ok(state)
+    }
+}
+
+impl<I, O> MessageHandler<I> for Aggregator<I, O>
+where
+    I: Serialize + DeserializeOwned + Clone + 'static,
+    O: Serialize + DeserializeOwned + Clone + 'static,
+{
+    fn handle(mut state: ap::State<Self>, item: I) {
+        let AggregatorState {
+            fold,
+            initial,
+            windows,
+            ..
+        } = &mut *state;
+        for (_, value) in windows.iter_mut() {
+            // `fold` takes the value, leave a copy of the initial value in its
+            // place in the meantime.
+            let folded = mem::replace(value, initial.clone());
+            *value = fold(folded, item.clone());
+        }
+    }
+}
+
+/// Opens the next window, sent by the aggregator to itself every `stride`.
+#[derive(Serialize, Deserialize)]
+pub struct Tick;
+impl<I, O> MessageHandler<Tick> for Aggregator<I, O>
+where
+    I: Serialize + DeserializeOwned + Clone + 'static,
+    O: Serialize + DeserializeOwned + Clone + 'static,
+{
+    fn handle(mut state: ap::State<Self>, _: Tick) {
+        let this = state.self_ref();
+        state.open_window(this);
+        this.delayed_send(Tick, state.stride);
+    }
+}
+
+/// Closes a window, sent by the aggregator to itself at its end.
+#[derive(Serialize, Deserialize)]
+pub struct Flush(u64);
+impl<I, O> MessageHandler<Flush> for Aggregator<I, O>
+where
+    I: Serialize + DeserializeOwned + Clone + 'static,
+    O: Serialize + DeserializeOwned + Clone + 'static,
+{
+    fn handle(mut state: ap::State<Self>, Flush(id): Flush) {
+        if let Some(index) = state.windows.iter().position(|(window, _)| *window == id) {
+            let (_, value) = state.windows.remove(index);
+            (state.send)(state.downstream, value);
+        }
+    }
+}
+
+impl<I, O> ProcessRef<Aggregator<I, O>>
+where
+    I: Serialize + DeserializeOwned + Clone + 'static,
+    O: Serialize + DeserializeOwned + Clone + 'static,
+{
+    /// Folds `item` into all open windows.
+    pub fn push(&self, item: I) {
+        self.send(item);
+    }
+}
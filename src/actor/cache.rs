@@ -0,0 +1,383 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::mem;
+use std::time::{Duration, Instant};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::ap::handlers::{Message, Request};
+use crate::ap::{self, AbstractProcess, Config, MessageHandler, ProcessRef, RequestHandler};
+use crate::serializer::Bincode;
+
+/// A process caching up to `capacity` values of type `V` by keys of type `K`.
+///
+/// Once the cache is full, putting a new key evicts the least recently used
+/// one. With a [`ttl`](CacheConfig::ttl) values also expire after the given
+/// time since they were put. If a [`Loader`] is set with
+/// [`on_miss`](CacheConfig::on_miss), a `get` of a missing key asks the
+/// loader for the value and caches it.
+///
+/// Keys and values are sent to the cache encoded with `Bincode`, the cache
+/// can be used from processes on other nodes.
+pub struct Cache<K, V> {
+    phantom: PhantomData<(K, V)>,
+}
+
+/// Reference to a [`Cache`].
+pub type CacheRef<K, V> = ProcessRef<Cache<K, V>>;
+
+impl<K, V> Cache<K, V>
+where
+    K: Serialize + DeserializeOwned + Hash + Eq + Clone + 'static,
+    V: Serialize + DeserializeOwned + Clone + 'static,
+{
+    /// Starts a cache holding up to `capacity` values, without expiry or
+    /// loader.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(capacity: usize) -> CacheRef<K, V> {
+        Self::with_config(CacheConfig::new(capacity))
+    }
+
+    /// Starts a cache configured by `config`.
+    pub fn with_config(config: CacheConfig<K, V>) -> CacheRef<K, V> {
+        match Self::start(config) {
+            Ok(cache) => cache,
+            Err(err) => panic!("Failed to start cache: {err:?}"),
+        }
+    }
+}
+
+/// Configuration of a [`Cache`], also its argument.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct CacheConfig<K, V>
+where
+    K: Serialize + DeserializeOwned + 'static,
+    V: Serialize + DeserializeOwned + 'static,
+{
+    capacity: usize,
+    ttl: Option<Duration>,
+    on_miss: Option<ProcessRef<Loader<K, V>>>,
+}
+
+impl<K, V> CacheConfig<K, V>
+where
+    K: Serialize + DeserializeOwned + 'static,
+    V: Serialize + DeserializeOwned + 'static,
+{
+    /// Creates the configuration of a cache holding up to `capacity`
+    /// values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity of a cache can't be zero");
+        CacheConfig {
+            capacity,
+            ttl: None,
+            on_miss: None,
+        }
+    }
+
+    /// Expires values `ttl` after they were put.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Loads missing values from `loader`.
+    pub fn on_miss(mut self, loader: ProcessRef<Loader<K, V>>) -> Self {
+        self.on_miss = Some(loader);
+        self
+    }
+}
+
+/// Counters of a [`Cache`], returned by [`ProcessRef::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    /// Gets that found a cached value.
+    pub hits: u64,
+    /// Gets that didn't find a cached value, also if the loader found it.
+    pub misses: u64,
+    /// Values removed to make room for new ones.
+    pub evictions: u64,
+    /// Values removed after their time to live.
+    pub expirations: u64,
+    /// Number of cached values.
+    pub size: usize,
+    pub capacity: usize,
+}
+
+/// State of a [`Cache`].
+pub struct CacheState<K, V>
+where
+    K: Serialize + DeserializeOwned + 'static,
+    V: Serialize + DeserializeOwned + 'static,
+{
+    capacity: usize,
+    ttl: Option<Duration>,
+    on_miss: Option<ProcessRef<Loader<K, V>>>,
+    entries: HashMap<K, Entry<V>>,
+    /// Keys by the time they were last used, the least recently used first.
+    recency: BTreeMap<u64, K>,
+    /// Incremented on each use of a key.
+    clock: u64,
+    stats: CacheStats,
+}
+
+struct Entry<V> {
+    value: V,
+    put: Instant,
+    used: u64,
+}
+
+impl<K, V> CacheState<K, V>
+where
+    K: Serialize + DeserializeOwned + Hash + Eq + Clone + 'static,
+    V: Serialize + DeserializeOwned + Clone + 'static,
+{
+    /// Returns the value of `key` and marks it as used.
+    fn get(&mut self, key: &K) -> Option<V> {
+        let entry = self.entries.get(key)?;
+        if self.ttl.is_some_and(|ttl| entry.put.elapsed() >= ttl) {
+            self.remove(key);
+            self.stats.expirations += 1;
+            return None;
+        }
+        self.clock += 1;
+        let entry = self.entries.get_mut(key).unwrap();
+        let used = mem::replace(&mut entry.used, self.clock);
+        let value = entry.value.clone();
+        self.recency.remove(&used);
+        self.recency.insert(self.clock, key.clone());
+        Some(value)
+    }
+
+    fn put(&mut self, key: K, value: V) {
+        self.remove(&key);
+        if self.entries.len() == self.capacity {
+            if let Some((_, oldest)) = self.recency.pop_first() {
+                self.entries.remove(&oldest);
+                self.stats.evictions += 1;
+            }
+        }
+        self.clock += 1;
+        self.recency.insert(self.clock, key.clone());
+        let entry = Entry {
+            value,
+            put: Instant::now(),
+            used: self.clock,
+        };
+        self.entries.insert(key, entry);
+    }
+
+    /// Returns `true` if `key` was cached.
+    fn remove(&mut self, key: &K) -> bool {
+        match self.entries.remove(key) {
+            Some(entry) => {
+                self.recency.remove(&entry.used);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl<K, V> AbstractProcess for Cache<K, V>
+where
+    K: Serialize + DeserializeOwned + Hash + Eq + Clone + 'static,
+    V: Serialize + DeserializeOwned + Clone + 'static,
+{
+    type State = CacheState<K, V>;
+    type Serializer = Bincode;
+    type Arg = CacheConfig<K, V>;
+    type Handlers = (
+        Request<Get<K>>,
+        Message<Put<K, V>>,
+        Request<Invalidate<K>>,
+        Request<GetCacheStats>,
+    );
+    type StartupError = ();
+
+    fn init(_: Config<Self>, config: CacheConfig<K, V>) -> Result<CacheState<K, V>, ()> {
+        Ok(CacheState {
+            capacity: config.capacity,
+            ttl: config.ttl,
+            on_miss: config.on_miss,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            clock: 0,
+            stats: CacheStats {
+                hits: 0,
+                misses: 0,
+                evictions: 0,
+                expirations: 0,
+                size: 0,
+                capacity: config.capacity,
+            },
+        })
+    }
+}
+
+impl<K, V> ProcessRef<Cache<K, V>>
+where
+    K: Serialize + DeserializeOwned + Hash + Eq + Clone + 'static,
+    V: Serialize + DeserializeOwned + Clone + 'static,
+{
+    /// Returns the value cached for `key`.
+    ///
+    /// If the value is missing and the cache has a [`Loader`], the cache waits
+    /// for the loader and caches the loaded value. Other requests to the cache
+    /// wait in the meantime.
+    pub fn get(&self, key: K) -> Option<V> {
+        self.request(Get(key))
+    }
+
+    /// Caches `value` for `key`, replacing the previous value.
+    ///
+    /// Like all messages from the same process, the value is put before the
+    /// following requests are handled.
+    pub fn put(&self, key: K, value: V) {
+        self.send(Put(key, value));
+    }
+
+    /// Removes the value cached for `key`.
+    ///
+    /// Returns `true` if a value was cached.
+    pub fn invalidate(&self, key: K) -> bool {
+        self.request(Invalidate(key))
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.request(GetCacheStats)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Get<K>(K);
+impl<K, V> RequestHandler<Get<K>> for Cache<K, V>
+where
+    K: Serialize + DeserializeOwned + Hash + Eq + Clone + 'static,
+    V: Serialize + DeserializeOwned + Clone + 'static,
+{
+    type Response = Option<V>;
+
+    fn handle(mut state: ap::State<Self>, Get(key): Get<K>) -> Option<V> {
+        if let Some(value) = state.get(&key) {
+            state.stats.hits += 1;
+            return Some(value);
+        }
+        state.stats.misses += 1;
+        let value = state.on_miss?.request(Load(key.clone()))?;
+        state.put(key, value.clone());
+        Some(value)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Put<K, V>(K, V);
+impl<K, V> MessageHandler<Put<K, V>> for Cache<K, V>
+where
+    K: Serialize + DeserializeOwned + Hash + Eq + Clone + 'static,
+    V: Serialize + DeserializeOwned + Clone + 'static,
+{
+    fn handle(mut state: ap::State<Self>, Put(key, value): Put<K, V>) {
+        state.put(key, value);
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Invalidate<K>(K);
+impl<K, V> RequestHandler<Invalidate<K>> for Cache<K, V>
+where
+    K: Serialize + DeserializeOwned + Hash + Eq + Clone + 'static,
+    V: Serialize + DeserializeOwned + Clone + 'static,
+{
+    type Response = bool;
+
+    fn handle(mut state: ap::State<Self>, Invalidate(key): Invalidate<K>) -> bool {
+        state.remove(&key)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetCacheStats;
+impl<K, V> RequestHandler<GetCacheStats> for Cache<K, V>
+where
+    K: Serialize + DeserializeOwned + Hash + Eq + Clone + 'static,
+    V: Serialize + DeserializeOwned + Clone + 'static,
+{
+    type Response = CacheStats;
+
+    fn handle(state: ap::State<Self>, _: GetCacheStats) -> CacheStats {
+        CacheStats {
+            size: state.entries.len(),
+            ..state.stats
+        }
+    }
+}
+
+/// A process loading the values of missing keys for a [`Cache`], see
+/// [`CacheConfig::on_miss`].
+///
+/// The load function can't capture any values, because it's sent to the new
+/// process as a function pointer.
+pub struct Loader<K, V> {
+    phantom: PhantomData<(K, V)>,
+}
+
+impl<K, V> Loader<K, V>
+where
+    K: Serialize + DeserializeOwned + 'static,
+    V: Serialize + DeserializeOwned + 'static,
+{
+    /// Starts a loader returning the value of a key with `load`, or `None` if
+    /// the key has no value.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(load: fn(K) -> Option<V>) -> ProcessRef<Loader<K, V>> {
+        match Self::start(load as usize) {
+            Ok(loader) => loader,
+            Err(err) => panic!("Failed to start loader: {err:?}"),
+        }
+    }
+}
+
+impl<K, V> AbstractProcess for Loader<K, V>
+where
+    K: Serialize + DeserializeOwned + 'static,
+    V: Serialize + DeserializeOwned + 'static,
+{
+    type State = fn(K) -> Option<V>;
+    type Serializer = Bincode;
+    /// Pointer to the load function.
+    type Arg = usize;
+    type Handlers = (Request<Load<K>>,);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, load: usize) -> Result<fn(K) -> Option<V>, ()> {
+        // Safety: The pointer was created from the same function type in
+        // `Loader::new`.
+        Ok(unsafe { mem::transmute::<usize, fn(K) -> Option<V>>(load) })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Load<K>(K);
+impl<K, V> RequestHandler<Load<K>> for Loader<K, V>
+where
+    K: Serialize + DeserializeOwned + 'static,
+    V: Serialize + DeserializeOwned + 'static,
+{
+    type Response = Option<V>;
+
+    fn handle(state: ap::State<Self>, Load(key): Load<K>) -> Option<V> {
+        (*state)(key)
+    }
+}
@@ -0,0 +1,330 @@
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::ap::handlers::{Message, Request};
+use crate::ap::messages::RequestMessage;
+use crate::ap::{
+    self, AbstractProcess, Config, MessageHandler, ProcessRef, RequestError, RequestHandler,
+};
+use crate::serializer::{Bincode, CanSerialize};
+
+/// A circuit breaker in front of a target process of type `T`.
+///
+/// Calls through the [`CircuitRef`] go straight to the target, the circuit
+/// process only keeps track of their outcome. After `threshold` consecutive
+/// failures, a dead target or a timed out request, the circuit opens and
+/// calls fail with [`CircuitError::Open`] without reaching the target. Once
+/// `reset_timeout` passed, the circuit is half-open and lets one probe call
+/// through. If it succeeds the circuit closes again, otherwise it stays open
+/// for another `reset_timeout`.
+///
+/// The state is shared by all copies of a `CircuitRef`, also in other
+/// processes. Each transition is logged.
+pub struct Circuit<T> {
+    phantom: PhantomData<T>,
+}
+
+impl<T> Circuit<T>
+where
+    T: AbstractProcess + 'static,
+{
+    /// Starts a closed circuit in front of `target`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `threshold` is zero.
+    pub fn wrap(target: ProcessRef<T>, threshold: u32, reset_timeout: Duration) -> CircuitRef<T> {
+        assert!(threshold > 0, "threshold of a circuit can't be zero");
+        let arg = CircuitArg {
+            target: std::any::type_name::<T>().to_owned(),
+            threshold,
+            reset_timeout,
+        };
+        match Self::start(arg) {
+            Ok(process) => CircuitRef { process, target },
+            Err(err) => panic!("Failed to start circuit: {err:?}"),
+        }
+    }
+}
+
+/// State of a [`Circuit`], returned by [`CircuitRef::state`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CircuitState {
+    /// Calls reach the target.
+    Closed,
+    /// Calls fail with [`CircuitError::Open`].
+    Open,
+    /// A probe call is allowed to reach the target.
+    HalfOpen,
+}
+
+/// Error of a call through a [`CircuitRef`].
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CircuitError {
+    /// The circuit is open, the target wasn't called.
+    #[error("circuit open")]
+    Open,
+    /// The call reached the target and failed.
+    #[error(transparent)]
+    Request(#[from] RequestError),
+}
+
+/// Reference to a [`Circuit`] and its target.
+///
+/// It has the same `send` and `request` interface as the [`ProcessRef`] of
+/// the target, returning a [`CircuitError`] if the call didn't go through.
+pub struct CircuitRef<T>
+where
+    T: AbstractProcess + 'static,
+{
+    process: ProcessRef<Circuit<T>>,
+    target: ProcessRef<T>,
+}
+
+impl<T: AbstractProcess + 'static> Clone for CircuitRef<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: AbstractProcess + 'static> Copy for CircuitRef<T> {}
+
+impl<T: AbstractProcess + 'static> Serialize for CircuitRef<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (self.process, self.target).serialize(serializer)
+    }
+}
+
+impl<'de, T: AbstractProcess + 'static> Deserialize<'de> for CircuitRef<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (process, target) = Deserialize::deserialize(deserializer)?;
+        Ok(CircuitRef { process, target })
+    }
+}
+
+impl<T> CircuitRef<T>
+where
+    T: AbstractProcess + 'static,
+{
+    /// Sends `message` to the target, if the circuit lets it through.
+    ///
+    /// Only a dead target counts as a failure, the message isn't
+    /// acknowledged.
+    pub fn send<M: 'static>(&self, message: M) -> Result<(), CircuitError>
+    where
+        T: MessageHandler<M>,
+        T::Serializer: CanSerialize<M>,
+    {
+        self.call(|target| match target.try_send(message) {
+            true => Ok(()),
+            false => Err(RequestError::ProcessDied),
+        })
+    }
+
+    /// Makes a request to the target, if the circuit lets it through.
+    ///
+    /// A dead target fails right away, but a target that dies while handling
+    /// the request is only noticed with a timeout, see
+    /// [`request_timeout`](Self::request_timeout).
+    pub fn request<R: 'static>(&self, request: R) -> Result<T::Response, CircuitError>
+    where
+        T: RequestHandler<R>,
+        T::Serializer: CanSerialize<R>,
+        T::Serializer: CanSerialize<T::Response>,
+        T::Serializer: CanSerialize<RequestMessage<R, T::Response, T::Serializer>>,
+    {
+        self.request_timeout(request, None)
+    }
+
+    /// Makes a request to the target with a timeout, if the circuit lets it
+    /// through.
+    ///
+    /// Timeouts and a dead target count as failures.
+    pub fn request_timeout<R: 'static>(
+        &self,
+        request: R,
+        timeout: Option<Duration>,
+    ) -> Result<T::Response, CircuitError>
+    where
+        T: RequestHandler<R>,
+        T::Serializer: CanSerialize<R>,
+        T::Serializer: CanSerialize<T::Response>,
+        T::Serializer: CanSerialize<RequestMessage<R, T::Response, T::Serializer>>,
+    {
+        self.call(|target| {
+            if !target.remote_inspect_alive() {
+                return Err(RequestError::ProcessDied);
+            }
+            target.request_timeout(request, timeout)
+        })
+    }
+
+    /// Returns the current state of the circuit.
+    pub fn state(&self) -> CircuitState {
+        self.process.request(GetCircuitState)
+    }
+
+    /// Returns the target of the circuit.
+    pub fn target(&self) -> ProcessRef<T> {
+        self.target
+    }
+
+    /// Returns the process of the circuit.
+    pub fn process(&self) -> ProcessRef<Circuit<T>> {
+        self.process
+    }
+
+    /// Shuts the circuit down, the target keeps running.
+    pub fn shutdown(&self) {
+        self.process.shutdown();
+    }
+
+    /// Runs `call` if the circuit lets it through and reports the outcome.
+    fn call<R>(
+        &self,
+        call: impl FnOnce(ProcessRef<T>) -> Result<R, RequestError>,
+    ) -> Result<R, CircuitError> {
+        if !self.process.request(Acquire) {
+            return Err(CircuitError::Open);
+        }
+        let result = call(self.target);
+        // A removed handler is still an answer of a running target.
+        let failed = matches!(
+            result,
+            Err(RequestError::TimedOut
+                | RequestError::DeadlineExceeded
+                | RequestError::HandlerPanicked
+                | RequestError::ProcessDied)
+        );
+        self.process.send(Outcome { failed });
+        Ok(result?)
+    }
+}
+
+/// Argument of a [`Circuit`].
+#[derive(Serialize, Deserialize)]
+pub struct CircuitArg {
+    /// Type name of the target, used in the log.
+    target: String,
+    threshold: u32,
+    reset_timeout: Duration,
+}
+
+/// State of a [`Circuit`] process.
+pub struct CircuitBreaker {
+    target: String,
+    threshold: u32,
+    reset_timeout: Duration,
+    state: CircuitState,
+    /// Consecutive failures while closed.
+    failures: u32,
+    /// When the circuit opened or the last probe was let through.
+    since: Instant,
+}
+
+impl CircuitBreaker {
+    fn transition(&mut self, state: CircuitState) {
+        crate::warning::emit(format!(
+            "Circuit of `{}` changed from {:?} to {:?}",
+            self.target, self.state, state
+        ));
+        self.state = state;
+        self.since = Instant::now();
+    }
+}
+
+impl<T> AbstractProcess for Circuit<T>
+where
+    T: AbstractProcess + 'static,
+{
+    type State = CircuitBreaker;
+    type Serializer = Bincode;
+    type Arg = CircuitArg;
+    type Handlers = (Request<Acquire>, Message<Outcome>, Request<GetCircuitState>);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, arg: CircuitArg) -> Result<CircuitBreaker, ()> {
+        Ok(CircuitBreaker {
+            target: arg.target,
+            threshold: arg.threshold,
+            reset_timeout: arg.reset_timeout,
+            state: CircuitState::Closed,
+            failures: 0,
+            since: Instant::now(),
+        })
+    }
+}
+
+/// Asks the circuit if a call can go through.
+#[derive(Serialize, Deserialize)]
+pub struct Acquire;
+impl<T> RequestHandler<Acquire> for Circuit<T>
+where
+    T: AbstractProcess + 'static,
+{
+    type Response = bool;
+
+    fn handle(mut state: ap::State<Self>, _: Acquire) -> bool {
+        match state.state {
+            CircuitState::Closed => true,
+            CircuitState::Open if state.since.elapsed() >= state.reset_timeout => {
+                state.transition(CircuitState::HalfOpen);
+                true
+            }
+            CircuitState::Open => false,
+            // A probe whose outcome never arrived, e.g. because the caller
+            // died, is replaced by a new one.
+            CircuitState::HalfOpen if state.since.elapsed() >= state.reset_timeout => {
+                state.since = Instant::now();
+                true
+            }
+            CircuitState::HalfOpen => false,
+        }
+    }
+}
+
+/// Reports the outcome of a call that went through.
+#[derive(Serialize, Deserialize)]
+pub struct Outcome {
+    failed: bool,
+}
+impl<T> MessageHandler<Outcome> for Circuit<T>
+where
+    T: AbstractProcess + 'static,
+{
+    fn handle(mut state: ap::State<Self>, Outcome { failed }: Outcome) {
+        match (state.state, failed) {
+            (CircuitState::Closed, false) => state.failures = 0,
+            (CircuitState::Closed, true) => {
+                state.failures += 1;
+                if state.failures >= state.threshold {
+                    state.transition(CircuitState::Open);
+                }
+            }
+            (CircuitState::HalfOpen, false) => {
+                state.failures = 0;
+                state.transition(CircuitState::Closed);
+            }
+            (CircuitState::HalfOpen, true) => state.transition(CircuitState::Open),
+            // Calls that went through before the circuit opened.
+            (CircuitState::Open, _) => {}
+        }
+    }
+}
+
+/// Returns the [`CircuitState`].
+#[derive(Serialize, Deserialize)]
+pub struct GetCircuitState;
+impl<T> RequestHandler<GetCircuitState> for Circuit<T>
+where
+    T: AbstractProcess + 'static,
+{
+    type Response = CircuitState;
+
+    fn handle(state: ap::State<Self>, _: GetCircuitState) -> CircuitState {
+        state.state
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::mem;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::ap::handlers::Message;
+use crate::ap::{self, AbstractProcess, Config, MessageHandler, ProcessRef};
+use crate::serializer::{Bincode, CanSerialize};
+
+/// A process forwarding messages of type `M` to a target process of type `T`,
+/// once no message with the same key arrived for a given delay.
+///
+/// Each message waits for the delay. If another message with the same key
+/// arrives in the meantime, the waiting one is dropped and the delay starts
+/// over for the new one, so only the last message of a burst is forwarded.
+/// Messages with different keys don't affect each other.
+///
+/// The key function can't capture any values, because it's sent to the new
+/// process as a function pointer. Messages still waiting when the process
+/// shuts down are dropped.
+pub struct Debounce<T, M> {
+    phantom: PhantomData<(T, M)>,
+}
+
+impl<T, M> Debounce<T, M>
+where
+    T: MessageHandler<M>,
+    T::Serializer: CanSerialize<M>,
+    M: Serialize + DeserializeOwned + 'static,
+{
+    /// Starts a debounce in front of `target`, grouping messages by the key
+    /// returned by `key`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `delay` is zero.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<K>(target: ProcessRef<T>, delay: Duration, key: fn(&M) -> K) -> DebounceRef<T, M>
+    where
+        K: Serialize,
+    {
+        assert!(!delay.is_zero(), "delay of a debounce can't be zero");
+        let arg = DebounceArg {
+            target,
+            delay,
+            key: key as usize,
+            encode_key: encode_key::<M, K> as fn(usize, &M) -> Vec<u8> as usize,
+        };
+        match Self::start(arg) {
+            Ok(process) => DebounceRef { process },
+            Err(err) => panic!("Failed to start debounce: {err:?}"),
+        }
+    }
+}
+
+/// Returns the key of `message`, encoded with `Bincode`.
+fn encode_key<M, K: Serialize>(key: usize, message: &M) -> Vec<u8> {
+    // Safety: The pointer was created from the same function type in
+    // `Debounce::new`.
+    let key = unsafe { mem::transmute::<usize, fn(&M) -> K>(key) };
+    bincode::serialize(&key(message)).unwrap()
+}
+
+/// Reference to a [`Debounce`].
+///
+/// It has the same `send` interface as the [`ProcessRef`] of the target, for
+/// messages of type `M`.
+pub struct DebounceRef<T, M>
+where
+    T: MessageHandler<M>,
+    T::Serializer: CanSerialize<M>,
+    M: Serialize + DeserializeOwned + 'static,
+{
+    process: ProcessRef<Debounce<T, M>>,
+}
+
+impl<T, M> Clone for DebounceRef<T, M>
+where
+    T: MessageHandler<M>,
+    T::Serializer: CanSerialize<M>,
+    M: Serialize + DeserializeOwned + 'static,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, M> Copy for DebounceRef<T, M>
+where
+    T: MessageHandler<M>,
+    T::Serializer: CanSerialize<M>,
+    M: Serialize + DeserializeOwned + 'static,
+{
+}
+
+impl<T, M> Serialize for DebounceRef<T, M>
+where
+    T: MessageHandler<M>,
+    T::Serializer: CanSerialize<M>,
+    M: Serialize + DeserializeOwned + 'static,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.process.serialize(serializer)
+    }
+}
+
+impl<'de, T, M> Deserialize<'de> for DebounceRef<T, M>
+where
+    T: MessageHandler<M>,
+    T::Serializer: CanSerialize<M>,
+    M: Serialize + DeserializeOwned + 'static,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let process = ProcessRef::deserialize(deserializer)?;
+        Ok(DebounceRef { process })
+    }
+}
+
+impl<T, M> DebounceRef<T, M>
+where
+    T: MessageHandler<M>,
+    T::Serializer: CanSerialize<M>,
+    M: Serialize + DeserializeOwned + 'static,
+{
+    /// Sends `message` to the target once no other message with the same key
+    /// arrived for the delay.
+    pub fn send(&self, message: M) {
+        self.process.send(Debounced(message));
+    }
+
+    /// Shuts the debounce down, waiting messages are dropped.
+    pub fn shutdown(&self) {
+        self.process.shutdown();
+    }
+
+    /// Returns the process of the debounce.
+    pub fn process(&self) -> ProcessRef<Debounce<T, M>> {
+        self.process
+    }
+}
+
+/// Argument of a [`Debounce`].
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct DebounceArg<T: AbstractProcess> {
+    target: ProcessRef<T>,
+    delay: Duration,
+    /// Pointer to the key function.
+    key: usize,
+    /// Pointer to [`encode_key`], instantiated for the key type.
+    encode_key: usize,
+}
+
+/// State of a [`Debounce`].
+pub struct DebounceState<T: AbstractProcess, M> {
+    target: ProcessRef<T>,
+    delay: Duration,
+    key: usize,
+    encode_key: fn(usize, &M) -> Vec<u8>,
+    /// The waiting message for each encoded key, with the number of the
+    /// [`Elapsed`] message that releases it.
+    waiting: HashMap<Vec<u8>, (M, u64)>,
+    next_timer: u64,
+}
+
+impl<T, M> AbstractProcess for Debounce<T, M>
+where
+    T: MessageHandler<M>,
+    T::Serializer: CanSerialize<M>,
+    M: Serialize + DeserializeOwned + 'static,
+{
+    type State = DebounceState<T, M>;
+    type Serializer = Bincode;
+    type Arg = DebounceArg<T>;
+    type Handlers = (Message<Debounced<M>>, Message<Elapsed>);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, arg: DebounceArg<T>) -> Result<DebounceState<T, M>, ()> {
+        Ok(DebounceState {
+            target: arg.target,
+            delay: arg.delay,
+            key: arg.key,
+            // Safety: The pointer was created from `encode_key` in
+            // `Debounce::new`.
+            encode_key: unsafe {
+                mem::transmute::<usize, fn(usize, &M) -> Vec<u8>>(arg.encode_key)
+            },
+            waiting: HashMap::new(),
+            next_timer: 0,
+        })
+    }
+}
+
+/// A message waiting to be forwarded to the target.
+#[derive(Serialize, Deserialize)]
+pub struct Debounced<M>(M);
+impl<T, M> MessageHandler<Debounced<M>> for Debounce<T, M>
+where
+    T: MessageHandler<M>,
+    T::Serializer: CanSerialize<M>,
+    M: Serialize + DeserializeOwned + 'static,
+{
+    fn handle(mut state: ap::State<Self>, Debounced(message): Debounced<M>) {
+        let key = (state.encode_key)(state.key, &message);
+        let timer = state.next_timer;
+        state.next_timer += 1;
+        // A message already waiting for the key is dropped, the timer that
+        // would have released it is ignored.
+        state.waiting.insert(key.clone(), (message, timer));
+        let delay = state.delay;
+        state.self_ref().delayed_send(Elapsed { key, timer }, delay);
+    }
+}
+
+/// Releases the message waiting for `key`, sent by the debounce to itself.
+#[derive(Serialize, Deserialize)]
+pub struct Elapsed {
+    key: Vec<u8>,
+    timer: u64,
+}
+impl<T, M> MessageHandler<Elapsed> for Debounce<T, M>
+where
+    T: MessageHandler<M>,
+    T::Serializer: CanSerialize<M>,
+    M: Serialize + DeserializeOwned + 'static,
+{
+    fn handle(mut state: ap::State<Self>, Elapsed { key, timer }: Elapsed) {
+        if state.waiting.get(&key).map(|(_, waiting)| *waiting) != Some(timer) {
+            return;
+        }
+        if let Some((message, _)) = state.waiting.remove(&key) {
+            state.target.send(message);
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{Event, GetState};
+use crate::ap::handlers::{Message, Request};
+use crate::ap::{self, AbstractProcess, Config, MessageHandler, ProcessRef, RequestHandler};
+use crate::serializer::Bincode;
+
+/// A state of an [`EventSourcing`] process, derived from events of type `E`.
+pub trait EventSourced<E: Event>: Serialize + DeserializeOwned + Clone + 'static {
+    /// A request to change the state.
+    type Command: Serialize + DeserializeOwned + 'static;
+
+    /// Validates `command` and returns the events it produces, without
+    /// applying them.
+    fn command(&self, command: Self::Command) -> Result<Vec<E>, CommandError>;
+
+    /// Updates the state with `event`.
+    ///
+    /// Events are applied both when a command produced them and when the
+    /// process replays its stream after a restart, so this shouldn't have any
+    /// side effects.
+    fn apply_event(&mut self, event: E);
+}
+
+/// A command rejected by [`EventSourced::command`].
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[error("command rejected: {reason}")]
+pub struct CommandError {
+    pub reason: String,
+}
+
+impl CommandError {
+    pub fn new(reason: impl Into<String>) -> Self {
+        CommandError {
+            reason: reason.into(),
+        }
+    }
+}
+
+/// A process holding a state of type `S` that only changes by applying events
+/// of type `E`.
+///
+/// Each command is validated by [`EventSourced::command`]. The events it
+/// produces are appended to a stream in an [`EventStore`] before they are
+/// applied, and a restarted process replays the stream to restore its state.
+/// With [`snapshot_every`](EventSourcingConfig::snapshot_every) the state is
+/// saved in the store every `n` events, so that only the events after the
+/// last snapshot are replayed.
+///
+/// Only one process should write to a stream at a time.
+pub struct EventSourcing<E, S> {
+    phantom: PhantomData<(E, S)>,
+}
+
+/// Reference to an [`EventSourcing`] process.
+pub type EventSourcingRef<E, S> = ProcessRef<EventSourcing<E, S>>;
+
+/// Configuration of an [`EventSourcing`] process, also its argument.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct EventSourcingConfig<S>
+where
+    S: Serialize + DeserializeOwned + 'static,
+{
+    initial: S,
+    store: ProcessRef<EventStore>,
+    stream: String,
+    snapshot_every: Option<u64>,
+}
+
+impl<S> EventSourcingConfig<S>
+where
+    S: Serialize + DeserializeOwned + 'static,
+{
+    /// Creates the configuration of a process keeping its events in the
+    /// stream `stream` of `store`, starting at `initial` if the stream is
+    /// empty.
+    pub fn new(initial: S, store: ProcessRef<EventStore>, stream: impl Into<String>) -> Self {
+        EventSourcingConfig {
+            initial,
+            store,
+            stream: stream.into(),
+            snapshot_every: None,
+        }
+    }
+
+    /// Saves a snapshot of the state every `events` events.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `events` is zero.
+    pub fn snapshot_every(mut self, events: u64) -> Self {
+        assert!(events > 0, "snapshot interval can't be zero");
+        self.snapshot_every = Some(events);
+        self
+    }
+}
+
+/// State of an [`EventSourcing`] process.
+pub struct EventSourcingState<S>
+where
+    S: Serialize + DeserializeOwned + 'static,
+{
+    state: S,
+    store: ProcessRef<EventStore>,
+    stream: String,
+    snapshot_every: Option<u64>,
+    /// Events applied since the last snapshot.
+    since_snapshot: u64,
+}
+
+impl<E, S> AbstractProcess for EventSourcing<E, S>
+where
+    E: Event + Clone,
+    S: EventSourced<E>,
+{
+    type State = EventSourcingState<S>;
+    type Serializer = Bincode;
+    type Arg = EventSourcingConfig<S>;
+    type Handlers = (Request<Execute<S::Command>>, Request<GetState>);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, config: EventSourcingConfig<S>) -> Result<EventSourcingState<S>, ()> {
+        let stored = config.store.load(config.stream.clone());
+        let mut state = match stored.snapshot {
+            Some(snapshot) => bincode::deserialize(&snapshot).map_err(|_| ())?,
+            None => config.initial,
+        };
+        let since_snapshot = stored.events.len() as u64;
+        for event in stored.events {
+            state.apply_event(bincode::deserialize(&event).map_err(|_| ())?);
+        }
+        Ok(EventSourcingState {
+            state,
+            store: config.store,
+            stream: config.stream,
+            snapshot_every: config.snapshot_every,
+            since_snapshot,
+        })
+    }
+}
+
+impl<E, S> ProcessRef<EventSourcing<E, S>>
+where
+    E: Event + Clone,
+    S: EventSourced<E>,
+{
+    /// Validates `command`, then stores and applies the events it produced.
+    ///
+    /// Returns the applied events. A rejected command doesn't change the
+    /// state.
+    pub fn execute(&self, command: S::Command) -> Result<Vec<E>, CommandError> {
+        self.request(Execute(command))
+    }
+
+    /// Returns a copy of the current state.
+    pub fn current_state(&self) -> S {
+        self.request(GetState)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Execute<C>(C);
+impl<E, S> RequestHandler<Execute<S::Command>> for EventSourcing<E, S>
+where
+    E: Event + Clone,
+    S: EventSourced<E>,
+{
+    type Response = Result<Vec<E>, CommandError>;
+
+    fn handle(
+        mut state: ap::State<Self>,
+        Execute(command): Execute<S::Command>,
+    ) -> Result<Vec<E>, CommandError> {
+        let events = state.state.command(command)?;
+        if events.is_empty() {
+            return Ok(events);
+        }
+        // The events are stored before they are applied, so that a crash
+        // while applying them doesn't lose them.
+        let encoded = events
+            .iter()
+            .map(|event| bincode::serialize(event).unwrap())
+            .collect();
+        state.store.request(Append {
+            stream: state.stream.clone(),
+            events: encoded,
+        });
+        for event in events.iter().cloned() {
+            state.state.apply_event(event);
+        }
+        state.since_snapshot += events.len() as u64;
+        if state
+            .snapshot_every
+            .is_some_and(|every| state.since_snapshot >= every)
+        {
+            state.store.send(SaveSnapshot {
+                stream: state.stream.clone(),
+                state: bincode::serialize(&state.state).unwrap(),
+            });
+            state.since_snapshot = 0;
+        }
+        Ok(events)
+    }
+}
+
+impl<E, S> RequestHandler<GetState> for EventSourcing<E, S>
+where
+    E: Event + Clone,
+    S: EventSourced<E>,
+{
+    type Response = S;
+
+    fn handle(state: ap::State<Self>, _: GetState) -> S {
+        state.state.clone()
+    }
+}
+
+/// A process keeping the event streams of [`EventSourcing`] processes by key.
+///
+/// Events and snapshots are kept encoded with `Bincode`, in the memory of the
+/// store. They outlive the event sourced processes, but not the store
+/// itself.
+pub struct EventStore;
+
+/// A stream of an [`EventStore`], returned by [`ProcessRef::load`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredStream {
+    /// The last snapshot of the state.
+    pub snapshot: Option<Vec<u8>>,
+    /// The events appended after the last snapshot.
+    pub events: Vec<Vec<u8>>,
+}
+
+impl EventStore {
+    /// Starts an empty store.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> ProcessRef<EventStore> {
+        match Self::start(()) {
+            Ok(store) => store,
+            Err(err) => panic!("Failed to start event store: {err:?}"),
+        }
+    }
+}
+
+impl AbstractProcess for EventStore {
+    type State = HashMap<String, StoredStream>;
+    type Serializer = Bincode;
+    type Arg = ();
+    type Handlers = (Request<Append>, Message<SaveSnapshot>, Request<LoadStream>);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, _: ()) -> Result<HashMap<String, StoredStream>, ()> {
+        Ok(HashMap::new())
+    }
+}
+
+impl ProcessRef<EventStore> {
+    /// Returns the last snapshot and the following events of `stream`.
+    pub fn load(&self, stream: impl Into<String>) -> StoredStream {
+        self.request(LoadStream(stream.into()))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Append {
+    stream: String,
+    events: Vec<Vec<u8>>,
+}
+impl RequestHandler<Append> for EventStore {
+    type Response = ();
+
+    fn handle(mut state: ap::State<Self>, Append { stream, events }: Append) {
+        state.entry(stream).or_default().events.extend(events);
+    }
+}
+
+/// Replaces the snapshot of a stream, including all events appended so far.
+#[derive(Serialize, Deserialize)]
+pub struct SaveSnapshot {
+    stream: String,
+    state: Vec<u8>,
+}
+impl MessageHandler<SaveSnapshot> for EventStore {
+    fn handle(
+        mut state: ap::State<Self>,
+        SaveSnapshot {
+            stream,
+            state: saved,
+        }: SaveSnapshot,
+    ) {
+        *state.entry(stream).or_default() = StoredStream {
+            snapshot: Some(saved),
+            events: Vec::new(),
+        };
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LoadStream(String);
+impl RequestHandler<LoadStream> for EventStore {
+    type Response = StoredStream;
+
+    fn handle(state: ap::State<Self>, LoadStream(stream): LoadStream) -> StoredStream {
+        state.get(&stream).cloned().unwrap_or_default()
+    }
+}
@@ -0,0 +1,283 @@
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::ap::handlers::{Message, Request};
+use crate::ap::{
+    self, AbstractProcess, Config, MessageHandler, Output, PipeHandle, ProcessRef, RequestHandler,
+};
+use crate::serializer::Bincode;
+
+/// A step of a [`Pipeline`], transforming each input into an output.
+pub trait Stage: Sized + 'static {
+    /// The argument the stage is started with.
+    type Arg: Serialize + DeserializeOwned;
+    /// Items received from the previous stage.
+    type Input: Serialize + DeserializeOwned + 'static;
+    /// Items passed on to the next stage.
+    type Output: Serialize + DeserializeOwned + 'static;
+    /// Error returned if an item can't be processed.
+    type Error: Debug;
+
+    /// Creates the stage inside of its process.
+    fn init(arg: Self::Arg) -> Self;
+
+    /// Processes one item.
+    fn process(&mut self, input: Self::Input) -> Result<Self::Output, Self::Error>;
+}
+
+/// What a stage does after [`Stage::process`] returns an error.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OnError {
+    /// The stage drops all following items, the pipeline stops.
+    #[default]
+    Stop,
+    /// Only the failed item is dropped.
+    Continue,
+}
+
+/// A linear chain of processes, each running one [`Stage`].
+///
+/// The output of each stage is the input of the next one, which is checked
+/// at compile time. `F` is the first stage and `L` the last one:
+///
+/// ```ignore
+/// let pipeline = Pipeline::start::<Parse>(())
+///     .then::<Validate>(rules)
+///     .then::<Store>(db)
+///     .on_error(OnError::Continue)
+///     .build();
+/// pipeline.send(line);
+/// ```
+///
+/// Outputs of the last stage are dropped, unless another process is
+/// connected to it with [`ProcessRef::pipe_to`]. The stages keep running when
+/// the pipeline is dropped, only the connections between them are torn down.
+pub struct Pipeline<F, L> {
+    /// All stages in order, `F` is the first and `L` the last one.
+    stages: Vec<Box<dyn AnyStage>>,
+    _pipes: Vec<PipeHandle>,
+    phantom: PhantomData<fn() -> (F, L)>,
+}
+
+/// Builder of a [`Pipeline`], created with [`Pipeline::start`].
+#[must_use = "no stage is started until the pipeline is built"]
+pub struct PipelineBuilder<F: Stage, L: Stage> {
+    start: Box<dyn FnOnce(OnError) -> Pipeline<F, L>>,
+    on_error: OnError,
+}
+
+impl Pipeline<(), ()> {
+    /// Starts building a pipeline with the stage `F`, started with `arg`.
+    pub fn start<F: Stage>(arg: F::Arg) -> PipelineBuilder<F, F> {
+        PipelineBuilder {
+            start: Box::new(move |on_error| {
+                let first = StageProcess::<F>::spawn(arg, on_error);
+                Pipeline {
+                    stages: vec![Box::new(first)],
+                    _pipes: Vec::new(),
+                    phantom: PhantomData,
+                }
+            }),
+            on_error: OnError::default(),
+        }
+    }
+}
+
+impl<F: Stage, L: Stage> PipelineBuilder<F, L> {
+    /// Appends the stage `S`, started with `arg`, receiving the outputs of
+    /// the current last stage.
+    pub fn then<S>(self, arg: S::Arg) -> PipelineBuilder<F, S>
+    where
+        S: Stage<Input = L::Output>,
+    {
+        let start = self.start;
+        PipelineBuilder {
+            start: Box::new(move |on_error| {
+                let mut pipeline = start(on_error);
+                let next = StageProcess::<S>::spawn(arg, on_error);
+                let pipe = pipeline.last().pipe_to::<_, L::Output>(next);
+                pipeline._pipes.push(pipe);
+                pipeline.stages.push(Box::new(next));
+                Pipeline {
+                    stages: pipeline.stages,
+                    _pipes: pipeline._pipes,
+                    phantom: PhantomData,
+                }
+            }),
+            on_error: self.on_error,
+        }
+    }
+
+    /// Sets what all stages do after an item fails, [`OnError::Stop`] by
+    /// default.
+    pub fn on_error(mut self, on_error: OnError) -> Self {
+        self.on_error = on_error;
+        self
+    }
+
+    /// Starts all stages, in order, and connects them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `init` function of a stage panics.
+    pub fn build(self) -> Pipeline<F, L> {
+        (self.start)(self.on_error)
+    }
+}
+
+impl<F: Stage, L: Stage> Pipeline<F, L> {
+    /// Sends `input` to the first stage.
+    pub fn send(&self, input: F::Input) {
+        self.first().send(input);
+    }
+
+    /// Returns the process running the first stage.
+    pub fn first(&self) -> ProcessRef<StageProcess<F>> {
+        let (node_id, id) = self.stages[0].process();
+        // Safety: The first stage was started as `StageProcess<F>`.
+        unsafe { ProcessRef::new(node_id, id) }
+    }
+
+    /// Returns the process running the last stage.
+    pub fn last(&self) -> ProcessRef<StageProcess<L>> {
+        let (node_id, id) = self.stages[self.stages.len() - 1].process();
+        // Safety: The last stage was started as `StageProcess<L>`.
+        unsafe { ProcessRef::new(node_id, id) }
+    }
+
+    /// Returns the error that stopped the pipeline, formatted with `Debug`.
+    ///
+    /// Returns `None` while all stages accept items. Items sent before the
+    /// call could still fail after it returned.
+    pub fn error(&self) -> Option<String> {
+        self.stages.iter().find_map(|stage| stage.status().error)
+    }
+
+    /// Returns the number of items that failed in any stage.
+    pub fn failures(&self) -> u64 {
+        self.stages
+            .iter()
+            .map(|stage| stage.status().failures)
+            .sum()
+    }
+
+    /// Shuts down all stages, starting with the first one.
+    pub fn shutdown(self) {
+        for stage in &self.stages {
+            stage.shutdown();
+        }
+    }
+}
+
+/// A stage of any type.
+trait AnyStage {
+    /// Returns the node and process id.
+    fn process(&self) -> (u64, u64);
+    fn status(&self) -> StageStatus;
+    fn shutdown(&self);
+}
+
+impl<S: Stage> AnyStage for ProcessRef<StageProcess<S>> {
+    fn process(&self) -> (u64, u64) {
+        (self.node_id(), self.id())
+    }
+
+    fn status(&self) -> StageStatus {
+        self.request(GetStageStatus)
+    }
+
+    fn shutdown(&self) {
+        ProcessRef::shutdown(self);
+    }
+}
+
+/// The process running a [`Stage`] of type `S`.
+pub struct StageProcess<S> {
+    phantom: PhantomData<S>,
+}
+
+impl<S: Stage> StageProcess<S> {
+    fn spawn(arg: S::Arg, on_error: OnError) -> ProcessRef<Self> {
+        match Self::start(StageArg { arg, on_error }) {
+            Ok(process) => process,
+            Err(err) => panic!(
+                "Failed to start pipeline stage `{}`: {err:?}",
+                std::any::type_name::<S>()
+            ),
+        }
+    }
+}
+
+/// Argument of a [`StageProcess`].
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct StageArg<S: Stage> {
+    arg: S::Arg,
+    on_error: OnError,
+}
+
+/// State of a [`StageProcess`].
+pub struct StageState<S> {
+    stage: S,
+    on_error: OnError,
+    status: StageStatus,
+}
+
+impl<S: Stage> AbstractProcess for StageProcess<S> {
+    type State = StageState<S>;
+    type Serializer = Bincode;
+    type Arg = StageArg<S>;
+    type Handlers = (Message<S::Input>, Request<GetStageStatus>);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, arg: StageArg<S>) -> Result<StageState<S>, ()> {
+        Ok(StageState {
+            stage: S::init(arg.arg),
+            on_error: arg.on_error,
+            status: StageStatus::default(),
+        })
+    }
+}
+
+impl<S: Stage> Output<S::Output> for StageProcess<S> {}
+
+impl<S: Stage> MessageHandler<S::Input> for StageProcess<S> {
+    fn handle(mut state: ap::State<Self>, input: S::Input) {
+        if state.status.error.is_some() {
+            return;
+        }
+        match state.stage.process(input) {
+            Ok(output) => state.emit(output),
+            Err(err) => {
+                state.status.failures += 1;
+                if state.on_error == OnError::Stop {
+                    state.status.error = Some(format!("{err:?}"));
+                }
+            }
+        }
+    }
+}
+
+/// Failures of a stage.
+#[derive(Default, Serialize, Deserialize)]
+pub struct StageStatus {
+    failures: u64,
+    /// Error that stopped the stage.
+    error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetStageStatus;
+impl<S: Stage> RequestHandler<GetStageStatus> for StageProcess<S> {
+    type Response = StageStatus;
+
+    fn handle(state: ap::State<Self>, _: GetStageStatus) -> StageStatus {
+        StageStatus {
+            failures: state.status.failures,
+            error: state.status.error.clone(),
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::ap::handlers::{Message, Request};
+use crate::ap::{
+    self, AbstractProcess, Config, MessageHandler, ProcessRef, RequestHandler, StartupError,
+    TrapInfo,
+};
+use crate::serializer::{Bincode, CanSerialize};
+use crate::Tag;
+
+/// A process forwarding messages of type `M` to workers of type `W`, picked
+/// by a consistent hash of a routing key.
+///
+/// Each worker is placed on a hash ring `replicas` times, the argument passed
+/// to [`start`](AbstractProcess::start). A message is forwarded to the worker
+/// following the hash of its routing key on the ring, so adding or removing a
+/// worker only moves the keys next to its positions. Messages with the same
+/// routing key reach the same worker, as long as the set of workers doesn't
+/// change.
+///
+/// The router is linked to its workers. A worker that dies is removed from the
+/// ring, and restarted if it was started with
+/// [`spawn_worker`](ProcessRef::spawn_worker).
+pub struct Router<W, M> {
+    phantom: PhantomData<(W, M)>,
+}
+
+/// Reference to a [`Router`].
+pub type RouterRef<W, M> = ProcessRef<Router<W, M>>;
+
+impl<W, M> Router<W, M>
+where
+    W: AbstractProcess + MessageHandler<M> + 'static,
+    W::Serializer: CanSerialize<M>,
+    W::Arg: Serialize + DeserializeOwned + Clone,
+    W::StartupError: Serialize + DeserializeOwned,
+    M: Serialize + DeserializeOwned + 'static,
+{
+    /// Starts a router placing each worker `replicas` times on the ring.
+    pub fn new(replicas: usize) -> RouterRef<W, M> {
+        match Self::start(replicas) {
+            Ok(router) => router,
+            Err(err) => panic!("Failed to start router: {err:?}"),
+        }
+    }
+}
+
+/// State of a [`Router`].
+pub struct RouterState<W: AbstractProcess> {
+    replicas: usize,
+    /// Positions on the ring, mapped to the key of the worker.
+    ring: BTreeMap<u64, u64>,
+    workers: HashMap<u64, Worker<W>>,
+}
+
+struct Worker<W: AbstractProcess> {
+    process: ProcessRef<W>,
+    /// Tag of the link to the worker.
+    tag: Tag,
+    /// Argument to restart the worker with, if it was started by the router.
+    arg: Option<W::Arg>,
+}
+
+impl<W: AbstractProcess> RouterState<W> {
+    fn insert(&mut self, key: u64, worker: Worker<W>) {
+        self.remove(key);
+        for replica in 0..self.replicas {
+            self.ring.insert(hash((key, replica)), key);
+        }
+        self.workers.insert(key, worker);
+    }
+
+    fn remove(&mut self, key: u64) -> Option<Worker<W>> {
+        let worker = self.workers.remove(&key)?;
+        self.ring.retain(|_, worker_key| *worker_key != key);
+        Some(worker)
+    }
+
+    /// Returns the worker following `routing_key` on the ring.
+    fn find(&self, routing_key: u64) -> Option<&Worker<W>> {
+        let (_, key) = self
+            .ring
+            .range(routing_key..)
+            .next()
+            .or_else(|| self.ring.iter().next())?;
+        self.workers.get(key)
+    }
+}
+
+impl<W, M> AbstractProcess for Router<W, M>
+where
+    W: AbstractProcess + MessageHandler<M> + 'static,
+    W::Serializer: CanSerialize<M>,
+    W::Arg: Serialize + DeserializeOwned + Clone,
+    W::StartupError: Serialize + DeserializeOwned,
+    M: Serialize + DeserializeOwned + 'static,
+{
+    type State = RouterState<W>;
+    type Serializer = Bincode;
+    type Arg = usize;
+    type Handlers = (
+        Message<AddWorker<W>>,
+        Message<RemoveWorker>,
+        Message<Route<M>>,
+        Request<SpawnWorker<W>>,
+        Request<WorkerFor>,
+    );
+    type StartupError = ();
+
+    fn init(config: Config<Self>, replicas: usize) -> Result<RouterState<W>, ()> {
+        // Dead workers are removed instead of killing the router.
+        config.die_if_link_dies(false);
+        Ok(RouterState {
+            replicas: replicas.max(1),
+            ring: BTreeMap::new(),
+            workers: HashMap::new(),
+        })
+    }
+
+    fn handle_link_death(mut state: ap::State<Self>, info: TrapInfo) {
+        let Some(key) = state
+            .workers
+            .iter()
+            .find(|(_, worker)| worker.tag == info.tag)
+            .map(|(key, _)| *key)
+        else {
+            return;
+        };
+        let worker = state.remove(key).unwrap();
+        if let Some(arg) = worker.arg {
+            if let Ok(worker) = start_worker(arg) {
+                state.insert(key, worker);
+            }
+        }
+    }
+}
+
+/// Starts a worker linked to the router.
+fn start_worker<W: AbstractProcess>(arg: W::Arg) -> Result<Worker<W>, StartupError<W>>
+where
+    W::Arg: Clone,
+{
+    let tag = Tag::new();
+    let process = W::link_with(tag).start(arg.clone())?;
+    Ok(Worker {
+        process,
+        tag,
+        arg: Some(arg),
+    })
+}
+
+/// Returns the position of `key` on the ring.
+fn hash(key: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct AddWorker<W: AbstractProcess>(u64, ProcessRef<W>);
+impl<W, M> MessageHandler<AddWorker<W>> for Router<W, M>
+where
+    W: AbstractProcess + MessageHandler<M> + 'static,
+    W::Serializer: CanSerialize<M>,
+    W::Arg: Serialize + DeserializeOwned + Clone,
+    W::StartupError: Serialize + DeserializeOwned,
+    M: Serialize + DeserializeOwned + 'static,
+{
+    fn handle(mut state: ap::State<Self>, AddWorker(key, process): AddWorker<W>) {
+        let tag = Tag::new();
+        process.link_with(tag);
+        let worker = Worker {
+            process,
+            tag,
+            arg: None,
+        };
+        state.insert(key, worker);
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RemoveWorker(u64);
+impl<W, M> MessageHandler<RemoveWorker> for Router<W, M>
+where
+    W: AbstractProcess + MessageHandler<M> + 'static,
+    W::Serializer: CanSerialize<M>,
+    W::Arg: Serialize + DeserializeOwned + Clone,
+    W::StartupError: Serialize + DeserializeOwned,
+    M: Serialize + DeserializeOwned + 'static,
+{
+    fn handle(mut state: ap::State<Self>, RemoveWorker(key): RemoveWorker) {
+        if let Some(worker) = state.remove(key) {
+            worker.process.unlink();
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Route<M>(u64, M);
+impl<W, M> MessageHandler<Route<M>> for Router<W, M>
+where
+    W: AbstractProcess + MessageHandler<M> + 'static,
+    W::Serializer: CanSerialize<M>,
+    W::Arg: Serialize + DeserializeOwned + Clone,
+    W::StartupError: Serialize + DeserializeOwned,
+    M: Serialize + DeserializeOwned + 'static,
+{
+    fn handle(state: ap::State<Self>, Route(routing_key, message): Route<M>) {
+        // Messages are dropped while there are no workers.
+        if let Some(worker) = state.find(routing_key) {
+            worker.process.send(message);
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "W::Arg: Serialize + DeserializeOwned")]
+pub struct SpawnWorker<W: AbstractProcess>(u64, W::Arg);
+impl<W, M> RequestHandler<SpawnWorker<W>> for Router<W, M>
+where
+    W: AbstractProcess + MessageHandler<M> + 'static,
+    W::Serializer: CanSerialize<M>,
+    W::Arg: Serialize + DeserializeOwned + Clone,
+    W::StartupError: Serialize + DeserializeOwned,
+    M: Serialize + DeserializeOwned + 'static,
+{
+    type Response = Result<ProcessRef<W>, StartupError<W>>;
+
+    fn handle(mut state: ap::State<Self>, SpawnWorker(key, arg): SpawnWorker<W>) -> Self::Response {
+        let worker = start_worker(arg)?;
+        let process = worker.process;
+        state.insert(key, worker);
+        Ok(process)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WorkerFor(u64);
+impl<W, M> RequestHandler<WorkerFor> for Router<W, M>
+where
+    W: AbstractProcess + MessageHandler<M> + 'static,
+    W::Serializer: CanSerialize<M>,
+    W::Arg: Serialize + DeserializeOwned + Clone,
+    W::StartupError: Serialize + DeserializeOwned,
+    M: Serialize + DeserializeOwned + 'static,
+{
+    type Response = Option<ProcessRef<W>>;
+
+    fn handle(state: ap::State<Self>, WorkerFor(routing_key): WorkerFor) -> Self::Response {
+        state.find(routing_key).map(|worker| worker.process)
+    }
+}
+
+impl<W, M> ProcessRef<Router<W, M>>
+where
+    W: AbstractProcess + MessageHandler<M> + 'static,
+    W::Serializer: CanSerialize<M>,
+    W::Arg: Serialize + DeserializeOwned + Clone,
+    W::StartupError: Serialize + DeserializeOwned,
+    M: Serialize + DeserializeOwned + 'static,
+{
+    /// Adds `worker` to the ring under `key`, replacing the worker that was
+    /// added with the same key.
+    ///
+    /// The worker is removed from the ring when it dies.
+    pub fn add_worker(&self, key: impl Hash, worker: ProcessRef<W>) {
+        self.send(AddWorker(hash(key), worker));
+    }
+
+    /// Starts a new worker with `arg` and adds it to the ring under `key`.
+    ///
+    /// The router restarts the worker with a copy of `arg` when it dies.
+    pub fn spawn_worker(
+        &self,
+        key: impl Hash,
+        arg: W::Arg,
+    ) -> Result<ProcessRef<W>, StartupError<W>> {
+        self.request(SpawnWorker(hash(key), arg))
+    }
+
+    /// Removes the worker under `key` from the ring, without stopping it.
+    pub fn remove_worker(&self, key: impl Hash) {
+        self.send(RemoveWorker(hash(key)));
+    }
+
+    /// Forwards `message` to the worker picked by `routing_key`.
+    ///
+    /// The message is dropped if the router doesn't have any workers.
+    pub fn route(&self, routing_key: impl Hash, message: M) {
+        self.send(Route(hash(routing_key), message));
+    }
+
+    /// Returns the worker messages with `routing_key` are forwarded to.
+    pub fn worker_for(&self, routing_key: impl Hash) -> Option<ProcessRef<W>> {
+        self.request(WorkerFor(hash(routing_key)))
+    }
+}
@@ -0,0 +1,177 @@
+use std::marker::PhantomData;
+use std::mem;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::distributed::{self, RemoteCall, RemoteCallable};
+
+/// A sequence of steps that either all succeed, or are undone by
+/// compensating actions.
+///
+/// Each [`SagaStep`] has a forward action, which can fail with an error of
+/// type `E`, and a compensating action that undoes it. [`execute`] runs the
+/// forward actions in order. If one of them fails, the compensations of the
+/// steps that succeeded before it run in reverse order:
+///
+/// ```ignore
+/// let saga = Saga::new(vec![
+///     SagaStep::new(reserve_stock, release_stock),
+///     SagaStep::new(charge_card, refund_card),
+///     SagaStep::new(ship_order, cancel_shipment),
+/// ]);
+/// match saga.execute(order) {
+///     Ok(order) => println!("order {} placed", order.id),
+///     Err(failed) => println!("step {} failed: {:?}", failed.step, failed.error),
+/// }
+/// ```
+///
+/// Every action runs in its own process, with its own copy of the state, so
+/// that a panicking action doesn't take down the caller. Actions can't
+/// change the state, side effects need to go through other processes. The
+/// actions can't capture any values, because they are sent to the new
+/// processes as function pointers.
+///
+/// [`execute`]: Self::execute
+pub struct Saga<S, E> {
+    steps: Vec<SagaStep<S, E>>,
+}
+
+/// A step of a [`Saga`].
+pub struct SagaStep<S, E> {
+    forward: fn(&S) -> Result<(), E>,
+    compensate: fn(&S),
+}
+
+impl<S, E> SagaStep<S, E> {
+    /// Creates a step running `forward`, undone by `compensate`.
+    pub fn new(forward: fn(&S) -> Result<(), E>, compensate: fn(&S)) -> Self {
+        SagaStep {
+            forward,
+            compensate,
+        }
+    }
+}
+
+/// Result of [`Saga::execute`], the state if all steps succeeded.
+pub type SagaResult<S, E> = Result<S, SagaFailed<E>>;
+
+/// A failed step of a [`Saga`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SagaFailed<E> {
+    /// Position of the step that failed.
+    pub step: usize,
+    pub error: StepError<E>,
+    /// `false` if the compensation of a step before it panicked. The other
+    /// compensations still ran.
+    pub compensated: bool,
+}
+
+/// Why a step of a [`Saga`] failed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StepError<E> {
+    /// The forward action returned an error.
+    Failed(E),
+    /// The forward action panicked, or its process couldn't be spawned.
+    Panicked,
+}
+
+impl<S, E> Saga<S, E>
+where
+    S: Serialize + DeserializeOwned + 'static,
+    E: Serialize + DeserializeOwned + 'static,
+{
+    pub fn new(steps: Vec<SagaStep<S, E>>) -> Self {
+        Saga { steps }
+    }
+
+    /// Runs the forward actions of all steps in order, until one fails.
+    ///
+    /// The state is returned if all steps succeeded. Otherwise the
+    /// compensations of the steps that succeeded run in reverse order, and
+    /// the failed step is returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `state` can't be serialized.
+    pub fn execute(&self, state: S) -> SagaResult<S, E> {
+        let encoded = bincode::serialize(&state).unwrap();
+        let node = distributed::node_id();
+        for (step, action) in self.steps.iter().enumerate() {
+            let forward = Forward {
+                forward: action.forward as usize,
+                state: encoded.clone(),
+                phantom: PhantomData::<fn() -> (S, E)>,
+            };
+            let error = match RemoteCall::call(node, forward) {
+                Ok(Ok(())) => continue,
+                Ok(Err(error)) => StepError::Failed(error),
+                Err(_) => StepError::Panicked,
+            };
+            let mut compensated = true;
+            for action in self.steps[..step].iter().rev() {
+                let compensate = Compensate {
+                    compensate: action.compensate as usize,
+                    state: encoded.clone(),
+                    phantom: PhantomData::<fn() -> S>,
+                };
+                compensated &= RemoteCall::call(node, compensate).is_ok();
+            }
+            return Err(SagaFailed {
+                step,
+                error,
+                compensated,
+            });
+        }
+        Ok(state)
+    }
+}
+
+/// Runs the forward action of a step.
+#[derive(Serialize, Deserialize)]
+struct Forward<P> {
+    /// Pointer to the forward action.
+    forward: usize,
+    /// The state, encoded with `Bincode`.
+    state: Vec<u8>,
+    phantom: PhantomData<P>,
+}
+
+impl<S, E> RemoteCallable for Forward<fn() -> (S, E)>
+where
+    S: Serialize + DeserializeOwned + 'static,
+    E: Serialize + DeserializeOwned + 'static,
+{
+    type Output = Result<(), E>;
+
+    fn execute(self) -> Result<(), E> {
+        // Safety: The pointer was created from the same function type in
+        // `Saga::execute`.
+        let forward = unsafe { mem::transmute::<usize, fn(&S) -> Result<(), E>>(self.forward) };
+        forward(&bincode::deserialize(&self.state).unwrap())
+    }
+}
+
+/// Runs the compensating action of a step.
+#[derive(Serialize, Deserialize)]
+struct Compensate<P> {
+    /// Pointer to the compensating action.
+    compensate: usize,
+    /// The state, encoded with `Bincode`.
+    state: Vec<u8>,
+    phantom: PhantomData<P>,
+}
+
+impl<S> RemoteCallable for Compensate<fn() -> S>
+where
+    S: Serialize + DeserializeOwned + 'static,
+{
+    type Output = ();
+
+    fn execute(self) {
+        // Safety: The pointer was created from the same function type in
+        // `Saga::execute`.
+        let compensate = unsafe { mem::transmute::<usize, fn(&S)>(self.compensate) };
+        compensate(&bincode::deserialize(&self.state).unwrap());
+    }
+}
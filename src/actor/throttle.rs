@@ -0,0 +1,269 @@
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::mem;
+use std::time::{Duration, Instant};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::ap::handlers::Message;
+use crate::ap::{self, AbstractProcess, Config, MessageHandler, ProcessRef};
+use crate::serializer::{Bincode, CanSerialize};
+
+/// Number of messages a throttle buffers by default.
+const DEFAULT_CAPACITY: usize = 1024;
+
+/// A process forwarding messages to a target process of type `T`, at most at
+/// the given [`Rate`].
+///
+/// Messages arriving faster are buffered and released one by one in arrival
+/// order. If the buffer is full, the oldest buffered message is dropped to
+/// make room for the new one.
+///
+/// Messages are sent to the throttle encoded with `Bincode`, the message type
+/// can be different for each [`send`](ThrottleRef::send) call.
+pub struct Throttle<T> {
+    phantom: PhantomData<T>,
+}
+
+impl<T> Throttle<T>
+where
+    T: AbstractProcess + 'static,
+{
+    /// Starts a throttle in front of `target`, buffering up to 1024 messages.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(target: ProcessRef<T>, rate: Rate) -> ThrottleRef<T> {
+        Self::with_capacity(target, rate, DEFAULT_CAPACITY)
+    }
+
+    /// Starts a throttle in front of `target`, buffering up to `capacity`
+    /// messages.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn with_capacity(target: ProcessRef<T>, rate: Rate, capacity: usize) -> ThrottleRef<T> {
+        assert!(capacity > 0, "capacity of a throttle can't be zero");
+        let arg = ThrottleArg {
+            target_node: target.node_id(),
+            target_id: target.id(),
+            interval: rate.interval(),
+            capacity,
+        };
+        match Self::start(arg) {
+            Ok(process) => ThrottleRef { process },
+            Err(err) => panic!("Failed to start throttle: {err:?}"),
+        }
+    }
+}
+
+/// Number of messages per second, as a fraction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Rate {
+    messages: u32,
+    seconds: u32,
+}
+
+impl Rate {
+    /// Creates a rate of `messages` per `seconds`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `messages` or `seconds` is zero.
+    pub fn new(messages: u32, seconds: u32) -> Self {
+        assert!(
+            messages > 0 && seconds > 0,
+            "messages and seconds of a rate can't be zero"
+        );
+        Rate { messages, seconds }
+    }
+
+    /// Creates a rate of `messages` per second.
+    pub fn per_second(messages: u32) -> Self {
+        Rate::new(messages, 1)
+    }
+
+    pub fn messages_per_second(&self) -> f64 {
+        self.messages as f64 / self.seconds as f64
+    }
+
+    /// Returns the time between two released messages.
+    fn interval(&self) -> Duration {
+        Duration::from_secs(self.seconds as u64) / self.messages
+    }
+}
+
+/// Reference to a [`Throttle`].
+///
+/// It has the same `send` interface as the [`ProcessRef`] of the target.
+pub struct ThrottleRef<T>
+where
+    T: AbstractProcess + 'static,
+{
+    process: ProcessRef<Throttle<T>>,
+}
+
+impl<T: AbstractProcess + 'static> Clone for ThrottleRef<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: AbstractProcess + 'static> Copy for ThrottleRef<T> {}
+
+impl<T: AbstractProcess + 'static> Serialize for ThrottleRef<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.process.serialize(serializer)
+    }
+}
+
+impl<'de, T: AbstractProcess + 'static> Deserialize<'de> for ThrottleRef<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let process = ProcessRef::deserialize(deserializer)?;
+        Ok(ThrottleRef { process })
+    }
+}
+
+impl<T> ThrottleRef<T>
+where
+    T: AbstractProcess + 'static,
+{
+    /// Sends `message` to the target once the rate allows it.
+    pub fn send<M>(&self, message: M)
+    where
+        T: MessageHandler<M>,
+        T::Serializer: CanSerialize<M>,
+        M: Serialize + DeserializeOwned + 'static,
+    {
+        self.process.send(Throttled {
+            send: send_target::<T, M> as fn(u64, u64, &[u8]) as usize,
+            message: bincode::serialize(&message).unwrap(),
+        });
+    }
+
+    /// Shuts the throttle down, buffered messages are dropped.
+    pub fn shutdown(&self) {
+        self.process.shutdown();
+    }
+
+    /// Returns the process of the throttle.
+    pub fn process(&self) -> ProcessRef<Throttle<T>> {
+        self.process
+    }
+}
+
+fn send_target<T, M>(node_id: u64, process_id: u64, message: &[u8])
+where
+    T: MessageHandler<M>,
+    T::Serializer: CanSerialize<M>,
+    M: DeserializeOwned + 'static,
+{
+    let message: M = bincode::deserialize(message).unwrap();
+    let target = unsafe { ProcessRef::<T>::new(node_id, process_id) };
+    target.send(message);
+}
+
+/// Argument of a [`Throttle`].
+#[derive(Serialize, Deserialize)]
+pub struct ThrottleArg {
+    target_node: u64,
+    target_id: u64,
+    interval: Duration,
+    capacity: usize,
+}
+
+/// State of a [`Throttle`].
+pub struct ThrottleState {
+    target_node: u64,
+    target_id: u64,
+    interval: Duration,
+    capacity: usize,
+    queue: VecDeque<Throttled>,
+    /// Earliest time the next message can be released.
+    next_release: Instant,
+    /// A [`Release`] message is on its way.
+    release_scheduled: bool,
+}
+
+impl ThrottleState {
+    fn release(&mut self, message: Throttled) {
+        // Safety: The pointer was created from `send_target` in
+        // `ThrottleRef::send`.
+        let send = unsafe { mem::transmute::<usize, fn(u64, u64, &[u8])>(message.send) };
+        send(self.target_node, self.target_id, &message.message);
+        self.next_release = Instant::now() + self.interval;
+    }
+}
+
+impl<T> AbstractProcess for Throttle<T>
+where
+    T: AbstractProcess + 'static,
+{
+    type State = ThrottleState;
+    type Serializer = Bincode;
+    type Arg = ThrottleArg;
+    type Handlers = (Message<Throttled>, Message<Release>);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, arg: ThrottleArg) -> Result<ThrottleState, ()> {
+        Ok(ThrottleState {
+            target_node: arg.target_node,
+            target_id: arg.target_id,
+            interval: arg.interval,
+            capacity: arg.capacity,
+            queue: VecDeque::new(),
+            next_release: Instant::now(),
+            release_scheduled: false,
+        })
+    }
+}
+
+/// A message waiting to be released to the target.
+#[derive(Serialize, Deserialize)]
+pub struct Throttled {
+    /// Pointer to [`send_target`], instantiated for the message type.
+    send: usize,
+    /// The message, encoded with `Bincode`.
+    message: Vec<u8>,
+}
+impl<T> MessageHandler<Throttled> for Throttle<T>
+where
+    T: AbstractProcess + 'static,
+{
+    fn handle(mut state: ap::State<Self>, message: Throttled) {
+        let now = Instant::now();
+        if !state.release_scheduled && now >= state.next_release {
+            state.release(message);
+            return;
+        }
+        if state.queue.len() == state.capacity {
+            state.queue.pop_front();
+        }
+        state.queue.push_back(message);
+        if !state.release_scheduled {
+            state.release_scheduled = true;
+            let delay = state.next_release.saturating_duration_since(now);
+            state.self_ref().delayed_send(Release, delay);
+        }
+    }
+}
+
+/// Releases the next buffered message, sent by the throttle to itself.
+#[derive(Serialize, Deserialize)]
+pub struct Release;
+impl<T> MessageHandler<Release> for Throttle<T>
+where
+    T: AbstractProcess + 'static,
+{
+    fn handle(mut state: ap::State<Self>, _: Release) {
+        state.release_scheduled = false;
+        if let Some(message) = state.queue.pop_front() {
+            state.release(message);
+        }
+        if !state.queue.is_empty() {
+            state.release_scheduled = true;
+            let interval = state.interval;
+            state.self_ref().delayed_send(Release, interval);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Sending a message to an abstract process on each node of a cluster.
+//!
+//! Each node can register one process per abstract process type as the
+//! receiver of broadcasts. The message is serialized once, the encoded bytes
+//! are copied into a new message for each node.
+
+use super::handlers::{Handlers, Message};
+use super::tag::AbstractProcessTag;
+use super::{AbstractProcess, ProcessRef};
+use crate::distributed::BroadcastError;
+use crate::function::process::{process_name, ProcessType};
+use crate::host;
+use crate::host::api::{message, registry};
+use crate::serializer::CanSerialize;
+
+/// Registry name of the broadcast receiver of type `T` on `node_id`.
+fn node_name<T: AbstractProcess>(node_id: u64) -> String {
+    process_name::<T, T::Serializer>(ProcessType::ProcessRef, &format!("broadcast/{node_id}"))
+}
+
+/// Registers `process` as the broadcast receiver of its node.
+pub(crate) fn register<T: AbstractProcess>(process: &ProcessRef<T>) {
+    let name = node_name::<T>(process.node_id());
+    unsafe { registry::put(name.as_ptr(), name.len(), process.node_id(), process.id()) };
+}
+
+/// Sends `message` to the broadcast receiver of type `T` on each node.
+pub(crate) fn broadcast<T, M>(node_ids: &[u64], message: M) -> Vec<Result<(), BroadcastError>>
+where
+    T: AbstractProcess,
+    T::Serializer: CanSerialize<M>,
+    M: 'static,
+{
+    let tag = AbstractProcessTag::message::<T>(T::Handlers::handler_id::<Message<M>>());
+    // Serialize once and read the encoded message back.
+    unsafe { message::create_data(tag.id(), 0) };
+    <T::Serializer as CanSerialize<M>>::encode(&message).unwrap();
+    let mut data = vec![0; unsafe { message::data_size() } as usize];
+    unsafe {
+        message::seek_data(0);
+        message::read_data(data.as_mut_ptr(), data.len());
+    }
+
+    node_ids
+        .iter()
+        .map(|&node_id| {
+            let name = node_name::<T>(node_id);
+            let (mut target_node, mut process_id) = (0, 0);
+            let result = unsafe {
+                registry::get(name.as_ptr(), name.len(), &mut target_node, &mut process_id)
+            };
+            if result != 0 {
+                return Err(BroadcastError::NotRegistered(node_id));
+            }
+            unsafe {
+                message::create_data(tag.id(), data.len() as u64);
+                message::write_data(data.as_ptr(), data.len());
+            }
+            // Messages to the local node skip the distributed layer.
+            host::send(target_node, process_id);
+            Ok(())
+        })
+        .collect()
+}
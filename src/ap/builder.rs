@@ -1,8 +1,8 @@
 use std::marker::PhantomData;
 
-use super::{lifecycles, AbstractProcess, ProcessRef, StartupError};
+use super::{lifecycles, AbstractProcess, ExitNotice, ProcessRef, StartupError};
 use crate::{function::process::{process_name, ProcessType}, MailboxError};
-use crate::{LunaticError, Mailbox, Process, ProcessConfig, ProcessName, Tag};
+use crate::{distributed, LunaticError, Mailbox, Process, ProcessConfig, ProcessName, Tag};
 
 trait IntoAbstractProcessBuilder<T> {}
 
@@ -18,6 +18,7 @@ pub struct AbstractProcessBuilder<'a, T: ?Sized> {
     link: Option<Tag>,
     config: Option<&'a ProcessConfig>,
     node: Option<u64>,
+    notice: Option<ExitNotice>,
     phantom: PhantomData<T>,
 }
 
@@ -30,6 +31,7 @@ where
             link: None,
             config: None,
             node: None,
+            notice: None,
             phantom: PhantomData,
         }
     }
@@ -40,6 +42,7 @@ where
             link: Some(Tag::new()),
             config: self.config,
             node: self.node,
+            notice: self.notice,
             phantom: PhantomData,
         }
     }
@@ -50,6 +53,7 @@ where
             link: Some(tag),
             config: self.config,
             node: self.node,
+            notice: self.notice,
             phantom: PhantomData,
         }
     }
@@ -60,6 +64,7 @@ where
             link: self.link,
             config: Some(config),
             node: self.node,
+            notice: self.notice,
             phantom: PhantomData,
         }
     }
@@ -70,6 +75,18 @@ where
             link: self.link,
             config: self.config,
             node: Some(node),
+            notice: self.notice,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Makes the process report its exit with the `notice`.
+    pub(crate) fn notify_exit(self, notice: ExitNotice) -> AbstractProcessBuilder<'a, T> {
+        AbstractProcessBuilder {
+            link: self.link,
+            config: self.config,
+            node: self.node,
+            notice: Some(notice),
             phantom: PhantomData,
         }
     }
@@ -84,7 +101,7 @@ where
     pub fn start(&self, arg: T::Arg) -> Result<ProcessRef<T>, StartupError<T>> {
         let init_tag = Tag::new();
         let this = unsafe { Process::<Result<(), StartupError<T>>, T::Serializer>::this() };
-        let entry_data = (this, init_tag, arg);
+        let entry_data = (this, init_tag, self.notice, arg);
         let process = match (self.link, &self.config, self.node) {
             (Some(_), _, Some(_node)) => {
                 unimplemented!("Linking across nodes is not supported yet");
@@ -133,12 +150,14 @@ where
     /// This call will block until the `init` function finishes. If the `init`
     /// function returns an error, it will be returned as
     /// `StartupError::Custom(error)`. If the `init` function panics during
-    /// execution, it will return [`StartupError::InitPanicked`].
+    /// execution, it will return [`StartupError::InitPanicked`]. If it doesn't
+    /// finish within `timeout`, the process is killed and
+    /// [`StartupError::TimedOut`] is returned.
     #[track_caller]
     pub fn start_timeout(&self, arg: T::Arg, timeout: std::time::Duration) -> Result<ProcessRef<T>, StartupError<T>> {
         let init_tag = Tag::new();
         let this = unsafe { Process::<Result<(), StartupError<T>>, T::Serializer>::this() };
-        let entry_data = (this, init_tag, arg);
+        let entry_data = (this, init_tag, self.notice, arg);
         let process = match (self.link, &self.config, self.node) {
             (Some(_), _, Some(_node)) => {
                 unimplemented!("Linking across nodes is not supported yet");
@@ -182,7 +201,10 @@ where
                 Err(err) => Err(err),
             },
             Err(err) => match err {
-                MailboxError::TimedOut => Err(StartupError::TimedOut),
+                MailboxError::TimedOut => {
+                    process.kill();
+                    Err(StartupError::TimedOut)
+                }
                 _ => unreachable!("tag_receive_timeout should panic in case of other errors"),
             },
         }
@@ -211,7 +233,7 @@ where
         let name = process_name::<T, T::Serializer>(ProcessType::ProcessRef, name);
         let init_tag = Tag::new();
         let this = unsafe { Process::<Result<(), StartupError<T>>, T::Serializer>::this() };
-        let entry_data = (this, init_tag, arg);
+        let entry_data = (this, init_tag, self.notice, arg);
         let process = match (self.link, &self.config, self.node) {
             (Some(_), _, Some(_node)) => {
                 unimplemented!("Linking across nodes is not supported yet");
@@ -276,4 +298,42 @@ where
             Err(err) => Err(err),
         }
     }
+
+    /// Starts one instance of the `AbstractProcess` on each node of the
+    /// cluster, including the local one, with the argument returned by
+    /// `arg_fn` for the node's id.
+    ///
+    /// The results are returned in the order of the node ids. Instances are
+    /// started one after another, each call blocks until the `init` function
+    /// of the instance finishes. A node set with [`on_node`](Self::on_node)
+    /// is ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the process is linked and the cluster has other nodes,
+    /// because linking across nodes isn't supported yet.
+    #[track_caller]
+    pub fn start_on_each_node<F>(&self, arg_fn: F) -> Vec<Result<ProcessRef<T>, StartupError<T>>>
+    where
+        F: Fn(u64) -> T::Arg,
+    {
+        let local = distributed::node_id();
+        let mut nodes = distributed::nodes();
+        nodes.push(local);
+        nodes.sort_unstable();
+        nodes.dedup();
+        nodes
+            .into_iter()
+            .map(|node| {
+                let builder = AbstractProcessBuilder::<T> {
+                    link: self.link,
+                    config: self.config,
+                    node: (node != local).then_some(node),
+                    notice: self.notice,
+                    phantom: PhantomData,
+                };
+                builder.start(arg_fn(node))
+            })
+            .collect()
+    }
 }
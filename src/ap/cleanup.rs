@@ -0,0 +1,29 @@
+//! Cleanup actions executed after an [`AbstractProcess`](super::AbstractProcess)
+//! terminates.
+
+use std::cell::RefCell;
+
+use crate::panic::catch_panic;
+
+type Action = Box<dyn FnOnce()>;
+
+crate::process_local! {
+    // Actions registered with `defer_on_terminate`, in registration order.
+    static ACTIONS: RefCell<Vec<Action>> = RefCell::new(Vec::new());
+}
+
+/// Registers `action` to be executed after `terminate` returns.
+pub(crate) fn defer<F: FnOnce() + 'static>(action: F) {
+    ACTIONS.with(|actions| actions.borrow_mut().push(Box::new(action)));
+}
+
+/// Executes all registered actions in reverse registration order.
+///
+/// A panicking action doesn't prevent the remaining ones from running.
+pub(crate) fn run() {
+    // Actions are popped one at a time, so that an action is allowed to
+    // register new ones.
+    while let Some(action) = ACTIONS.with(|actions| actions.borrow_mut().pop()) {
+        let _ = catch_panic(action);
+    }
+}
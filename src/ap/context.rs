@@ -0,0 +1,99 @@
+//! Deadline propagation for requests sent to an
+//! [`AbstractProcess`](super::AbstractProcess).
+
+use std::cell::Cell;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::host;
+
+crate::process_local! {
+    // Deadline of the request that is currently being handled, in milliseconds
+    // since the UNIX epoch.
+    static DEADLINE: Cell<Option<u64>> = Cell::new(None);
+}
+
+/// Information about the request that is currently being handled.
+///
+/// When a request is made with a timeout (e.g.
+/// [`ProcessRef::with_timeout`](super::ProcessRef::with_timeout)), the absolute
+/// deadline is sent together with the request. Inside the handler the
+/// remaining time can be read with [`Context::remaining_time`]. Any request
+/// made with a timeout from inside the handler will also inherit the deadline,
+/// if it's earlier than its own.
+///
+/// Requests whose deadline already passed when they are dequeued are dropped
+/// without running the handler, and the caller receives
+/// [`RequestError::DeadlineExceeded`](super::RequestError::DeadlineExceeded).
+pub struct Context;
+
+impl Context {
+    /// Returns the deadline of the request that is currently being handled.
+    ///
+    /// Returns `None` outside of request handlers or if the caller didn't
+    /// specify a timeout.
+    pub fn deadline() -> Option<SystemTime> {
+        DEADLINE
+            .with(Cell::get)
+            .map(|millis| UNIX_EPOCH + Duration::from_millis(millis))
+    }
+
+    /// Returns the time left until the caller stops waiting on the response.
+    ///
+    /// Returns `Some(Duration::ZERO)` if the deadline already passed and `None`
+    /// if the request doesn't have a deadline.
+    pub fn remaining_time() -> Option<Duration> {
+        DEADLINE
+            .with(Cell::get)
+            .map(|deadline| Duration::from_millis(deadline.saturating_sub(now())))
+    }
+
+    /// Returns `true` if the current request has a deadline that already
+    /// passed.
+    pub(crate) fn is_expired() -> bool {
+        DEADLINE.with(Cell::get).is_some_and(|deadline| deadline <= now())
+    }
+
+    /// Returns the deadline for an outgoing request with `timeout`, taking the
+    /// deadline of the current request into account.
+    pub(crate) fn outgoing_deadline(timeout: Option<Duration>) -> Option<u64> {
+        let own = timeout.map(|timeout| now().saturating_add(timeout.as_millis() as u64));
+        match (own, DEADLINE.with(Cell::get)) {
+            (Some(own), Some(inherited)) => Some(own.min(inherited)),
+            (own, inherited) => own.or(inherited),
+        }
+    }
+
+    /// Reads the deadline from the front of the current message and makes it
+    /// available to the handler.
+    pub(crate) fn enter(has_deadline: bool) {
+        let deadline = has_deadline.then(|| {
+            let mut deadline = [0u8; 8];
+            unsafe { host::api::message::read_data(deadline.as_mut_ptr(), deadline.len()) };
+            u64::from_le_bytes(deadline)
+        });
+        DEADLINE.with(|current| current.set(deadline));
+    }
+
+    /// Clears the deadline after the handler finished.
+    pub(crate) fn exit() {
+        DEADLINE.with(|current| current.set(None));
+    }
+}
+
+/// Writes `deadline` to the front of the message that is being created.
+pub(crate) fn write_deadline(deadline: u64) {
+    let deadline = deadline.to_le_bytes();
+    unsafe { host::api::message::write_data(deadline.as_ptr(), deadline.len()) };
+}
+
+/// Returns the remaining time until `deadline`.
+pub(crate) fn time_until(deadline: u64) -> Duration {
+    Duration::from_millis(deadline.saturating_sub(now()))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_millis() as u64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,103 @@
+//! Request handlers that yield in the middle of a request and continue when a
+//! reply arrives.
+//!
+//! Used by `#[handle_request]` methods marked with `#[continue_with(method)]`
+//! inside of the [`abstract_process`](crate::abstract_process) macro.
+
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::handlers::{Handlers, Message};
+use super::tag::AbstractProcessTag;
+use super::AbstractProcess;
+use crate::serializer::{Bincode, CanSerialize};
+use crate::Process;
+
+crate::process_local! {
+    // Requests waiting on a reply, indexed by the id of the `Resume` handle.
+    static PENDING: RefCell<HashMap<u64, Box<dyn Any>>> = RefCell::new(HashMap::new());
+    static NEXT_ID: Cell<u64> = Cell::new(0);
+}
+
+/// Result of a request handler that can yield.
+pub enum Step<Response, S> {
+    /// The request is finished, `Response` is sent to the caller.
+    Done(Response),
+    /// The request waits on a reply. The continuation method is called with
+    /// `S` once the reply arrives through the [`Resume`] handle.
+    Pending(S),
+}
+
+/// Handle used to deliver the awaited reply to a pending request.
+///
+/// It can be sent to another process that replies with
+/// [`send`](Resume::send). The handle can be stored inside the pending state
+/// and used again if the continuation yields another time.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Resume<Reply, S = Bincode> {
+    process: Process<(u64, Reply), S>,
+    handler: u8,
+    id: u64,
+}
+
+impl<Reply, S> Resume<Reply, S>
+where
+    S: CanSerialize<(u64, Reply)>,
+{
+    /// Creates a handle for a new pending request of the current process,
+    /// delivering replies to the message handler `M`.
+    #[doc(hidden)]
+    pub fn __new<AP, M>() -> Self
+    where
+        AP: AbstractProcess<Serializer = S>,
+        M: 'static,
+    {
+        let id = NEXT_ID.with(|next| next.replace(next.get() + 1));
+        Resume {
+            process: unsafe { Process::this() },
+            handler: AP::Handlers::handler_id::<Message<M>>(),
+            id,
+        }
+    }
+
+    /// Returns the id identifying the pending request.
+    #[doc(hidden)]
+    pub fn __id(&self) -> u64 {
+        self.id
+    }
+
+    /// Sends the reply to the process waiting on it.
+    pub fn send(&self, reply: Reply) {
+        let tag = AbstractProcessTag::from_u6(self.handler);
+        self.process.tag_send(tag, (self.id, reply));
+    }
+}
+
+impl<Reply, S> Clone for Resume<Reply, S> {
+    fn clone(&self) -> Self {
+        Resume {
+            process: self.process,
+            handler: self.handler,
+            id: self.id,
+        }
+    }
+}
+
+/// Stores the state of a pending request until the reply arrives.
+#[doc(hidden)]
+pub fn __store_pending<T: 'static>(id: u64, pending: T) {
+    PENDING.with(|requests| requests.borrow_mut().insert(id, Box::new(pending)));
+}
+
+/// Takes the state of the pending request `id`.
+///
+/// Returns `None` if the request is not pending anymore.
+#[doc(hidden)]
+pub fn __take_pending<T: 'static>(id: u64) -> Option<T> {
+    let pending = PENDING.with(|requests| requests.borrow_mut().remove(&id))?;
+    pending.downcast().ok().map(|pending| *pending)
+}
@@ -0,0 +1,125 @@
+//! Structured reports about panics inside of
+//! [`AbstractProcess`](super::AbstractProcess) handlers.
+
+use std::cell::{Cell, RefCell};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{host, LunaticError, Mailbox, Process, Tag};
+
+/// Name of the crate-provided process that logs crash reports.
+const DEFAULT_LOGGER: &str = "lunatic::crash_reports";
+
+crate::process_local! {
+    // Name of the process receiving crash reports, `None` if reporting is
+    // disabled.
+    static DESTINATION: RefCell<Option<String>> = RefCell::new(None);
+    // Message of the last panic, recorded by the panic hook.
+    static PANIC_MESSAGE: RefCell<Option<String>> = RefCell::new(None);
+    static HOOK_INSTALLED: Cell<bool> = Cell::new(false);
+}
+
+/// Information about a handler that panicked.
+///
+/// Crash reports are enabled with [`Config::report_crashes`] or
+/// [`Config::report_crashes_to`](super::Config::report_crashes_to).
+///
+/// [`Config::report_crashes`]: super::Config::report_crashes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashReport {
+    /// Id of the process that crashed.
+    pub process_id: u64,
+    /// Type name of the handler that panicked.
+    pub handler_name: String,
+    /// Message the handler panicked with.
+    pub panic_message: String,
+    /// Tag of the message that triggered the panic.
+    pub last_message_tag: i64,
+}
+
+impl fmt::Display for CrashReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "process {} crashed in `{}` while handling message with tag {}: {}",
+            self.process_id, self.handler_name, self.last_message_tag, self.panic_message
+        )
+    }
+}
+
+/// Sends crash reports of the current process to the process registered under
+/// `name`, or to the default logger if `name` is `None`.
+pub(crate) fn enable(name: Option<&str>) {
+    let name = name.unwrap_or(DEFAULT_LOGGER).to_owned();
+    DESTINATION.with(|destination| *destination.borrow_mut() = Some(name));
+    install_hook();
+}
+
+/// Installs a panic hook recording the message of the last panic.
+pub(crate) fn install_hook() {
+    if !HOOK_INSTALLED.with(|installed| installed.replace(true)) {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let message = if let Some(message) = info.payload().downcast_ref::<&str>() {
+                message.to_string()
+            } else if let Some(message) = info.payload().downcast_ref::<String>() {
+                message.clone()
+            } else {
+                info.to_string()
+            };
+            PANIC_MESSAGE.with(|last| *last.borrow_mut() = Some(message));
+            previous(info);
+        }));
+    }
+}
+
+/// Returns the message of the last panic, if the hook is installed.
+pub(crate) fn panic_message() -> Option<String> {
+    PANIC_MESSAGE.with(|last| last.borrow().clone())
+}
+
+/// Returns `true` if crash reports are enabled for the current process.
+pub(crate) fn is_enabled() -> bool {
+    DESTINATION.with(|destination| destination.borrow().is_some())
+}
+
+/// Sends a report about the panic that just happened in `handler_name`.
+///
+/// The report is dropped if the destination is the current process, so that a
+/// crashing logger doesn't report to itself.
+pub(crate) fn report(handler_name: &str, tag: Tag) {
+    let Some(name) = DESTINATION.with(|destination| destination.borrow().clone()) else {
+        return;
+    };
+    let destination = if name == DEFAULT_LOGGER {
+        match Process::name_spawn(DEFAULT_LOGGER, (), logger) {
+            Ok(logger) => Some(logger),
+            Err(LunaticError::NameAlreadyRegistered(node_id, id)) => {
+                Some(unsafe { Process::new(node_id, id) })
+            }
+            Err(_) => None,
+        }
+    } else {
+        Process::<CrashReport>::lookup(&name)
+    };
+    let process_id = host::process_id();
+    let destination = match destination {
+        Some(destination) if destination.id() != process_id => destination,
+        _ => return,
+    };
+    let panic_message = panic_message().unwrap_or_default();
+    destination.send(CrashReport {
+        process_id,
+        handler_name: handler_name.to_owned(),
+        panic_message,
+        last_message_tag: tag.id(),
+    });
+}
+
+/// Entry point of the default logger, printing all reports to stderr.
+fn logger(_: (), mailbox: Mailbox<CrashReport>) {
+    loop {
+        eprintln!("{}", mailbox.receive());
+    }
+}
@@ -3,17 +3,88 @@
 use std::any::{type_name, TypeId};
 use std::marker::PhantomData;
 
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
 use super::messages::RequestMessage;
-use super::{AbstractProcess, DeferredRequestHandler, MessageHandler, RequestHandler};
+use super::stream::StreamItem;
+use super::{
+    AbstractProcess, Context, DeferredRequestHandler, MessageHandler, RequestError, RequestHandler,
+    Responder, ResponderRequestHandler, StreamRequestHandler,
+};
 use crate::serializer::CanSerialize;
 use crate::Tag;
 
 pub struct Message<T>(PhantomData<T>);
 pub struct Request<T>(PhantomData<T>);
 pub struct DeferredRequest<T>(PhantomData<T>);
+pub struct ResponderRequest<T>(PhantomData<T>);
+pub struct StreamRequest<T>(PhantomData<T>);
+
+/// Identifies the handler a message generated by the
+/// [`abstract_process`](crate::abstract_process) macro is meant for.
+///
+/// Handlers are dispatched by their position inside of the `Handlers` tuple.
+/// If the sender was compiled with a different set of handlers, a message could
+/// reach another handler taking arguments of the same shape. `ID` is a hash of
+/// the handler name, it's serialized in front of the message arguments and
+/// decoding fails if it doesn't match the receiving handler.
+#[doc(hidden)]
+pub struct MethodId<const ID: u32>;
+
+impl<const ID: u32> Serialize for MethodId<ID> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(ID)
+    }
+}
+
+impl<'de, const ID: u32> Deserialize<'de> for MethodId<ID> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = u32::deserialize(deserializer)?;
+        if id != ID {
+            return Err(D::Error::custom(format!(
+                "message for handler {id:#010x} received by handler {ID:#010x}"
+            )));
+        }
+        Ok(MethodId)
+    }
+}
 
 pub trait Handler<AP: AbstractProcess> {
     fn handle(response_tag: Tag, state: &mut AP::State);
+
+    /// Number of handler ids taken by this entry of the `Handlers` tuple. Only
+    /// an [`Extension`] takes more than one.
+    #[doc(hidden)]
+    fn ids() -> u8 {
+        1
+    }
+
+    /// Returns the id of `H` relative to the first id of this entry.
+    #[doc(hidden)]
+    fn relative_id<H: 'static>() -> Option<u8>
+    where
+        Self: 'static,
+    {
+        (TypeId::of::<H>() == TypeId::of::<Self>()).then_some(0)
+    }
+
+    #[doc(hidden)]
+    fn name(_relative_id: u8) -> &'static str {
+        type_name::<Self>()
+    }
+
+    #[doc(hidden)]
+    fn handle_id(response_tag: Tag, _relative_id: u8, state: &mut AP::State) {
+        Self::handle(response_tag, state)
+    }
+
+    /// Returns the priority of messages to this handler, see
+    /// [`MessageHandler::PRIORITY`].
+    #[doc(hidden)]
+    fn priority(_relative_id: u8) -> u8 {
+        0
+    }
 }
 
 impl<AP, T> Handler<AP> for Message<T>
@@ -26,6 +97,10 @@ where
         let message = AP::Serializer::decode().unwrap();
         AP::handle(state, message);
     }
+
+    fn priority(_relative_id: u8) -> u8 {
+        AP::PRIORITY
+    }
 }
 
 impl<AP, T> Handler<AP> for Request<T>
@@ -39,8 +114,20 @@ where
         let state = super::State { state };
         let request: RequestMessage<T, AP::Response, AP::Serializer> =
             AP::Serializer::decode().unwrap();
-        let response = AP::handle(state, request.0);
-        request.1.send_response(response, response_tag);
+        if Context::deadline().is_none() {
+            request.1.track(response_tag, false);
+            let response = AP::handle(state, request.0);
+            request.1.send_response(response, response_tag);
+        } else if Context::is_expired() {
+            // Nobody is going to read the response, don't bother producing it.
+            request
+                .1
+                .send_result(Err(RequestError::DeadlineExceeded), response_tag);
+        } else {
+            request.1.track(response_tag, true);
+            let response = AP::handle(state, request.0);
+            request.1.send_result(Ok(response), response_tag);
+        }
     }
 }
 
@@ -55,20 +142,215 @@ where
         let state = super::State { state };
         let request: RequestMessage<T, AP::Response, AP::Serializer> =
             AP::Serializer::decode().unwrap();
+        let has_deadline = Context::deadline().is_some();
+        if has_deadline && Context::is_expired() {
+            request
+                .1
+                .send_result(Err(RequestError::DeadlineExceeded), response_tag);
+            return;
+        }
+        request.1.track(response_tag, has_deadline);
         AP::handle(
             state,
             request.0,
             super::DeferredResponse {
                 tag: response_tag,
                 return_address: request.1,
+                has_deadline,
             },
         );
     }
 }
 
+impl<AP, T> Handler<AP> for ResponderRequest<T>
+where
+    AP: ResponderRequestHandler<T>,
+    AP::Serializer: CanSerialize<T>,
+    AP::Serializer: CanSerialize<AP::Response>,
+    AP::Serializer: CanSerialize<RequestMessage<T, AP::Response, AP::Serializer>>,
+{
+    fn handle(response_tag: Tag, state: &mut <AP as AbstractProcess>::State) {
+        let state = super::State { state };
+        let request: RequestMessage<T, AP::Response, AP::Serializer> =
+            AP::Serializer::decode().unwrap();
+        let has_deadline = Context::deadline().is_some();
+        if has_deadline && Context::is_expired() {
+            request
+                .1
+                .send_result(Err(RequestError::DeadlineExceeded), response_tag);
+            return;
+        }
+        request.1.track(response_tag, has_deadline);
+        let mut responder = Responder::new(super::DeferredResponse {
+            tag: response_tag,
+            return_address: request.1,
+            has_deadline,
+        });
+        let response = AP::handle(state, request.0, &mut responder);
+        responder.finish(response);
+    }
+}
+
+impl<AP, T> Handler<AP> for StreamRequest<T>
+where
+    AP: StreamRequestHandler<T>,
+    AP::Serializer: CanSerialize<T>,
+    AP::Serializer: CanSerialize<StreamItem<AP::Item>>,
+    AP::Serializer: CanSerialize<RequestMessage<T, StreamItem<AP::Item>, AP::Serializer>>,
+{
+    fn handle(response_tag: Tag, state: &mut <AP as AbstractProcess>::State) {
+        let state = super::State { state };
+        let request: RequestMessage<T, StreamItem<AP::Item>, AP::Serializer> =
+            AP::Serializer::decode().unwrap();
+        // Streams are requested without a deadline.
+        let stream = AP::handle(state, request.0);
+        stream.send(request.1, response_tag);
+    }
+}
+
+/// Placeholder for a handler generated by the
+/// [`abstract_process`](crate::abstract_process) macro that is disabled with
+/// `#[cfg]`, or for an id that isn't taken because later handlers have
+/// explicitly assigned ids.
+///
+/// It keeps the positions of the other handlers stable, and never receives
+/// messages because no client method is generated for it.
+#[doc(hidden)]
+pub struct Disabled<T>(PhantomData<T>);
+
+impl<AP: AbstractProcess, T> Handler<AP> for Disabled<T> {
+    fn handle(_: Tag, _: &mut AP::State) {
+        unreachable!("message sent to a disabled or unused handler id")
+    }
+}
+
+/// Describes a handler that was removed from an abstract process, generated by
+/// `#[removed_handler(...)]` on the impl block.
+#[doc(hidden)]
+pub trait RemovedHandler {
+    /// Name of the removed handler.
+    const NAME: &'static str;
+    /// Arguments of the removed request handler, in front of the return
+    /// address. The method id is included as first element.
+    type Args;
+}
+
+/// Tombstone for the id of a removed message handler.
+///
+/// Messages still sent by old clients are logged, counted in the
+/// `<process>::<name>::removed` metric and dropped, instead of being decoded
+/// by a handler that took over the id.
+#[doc(hidden)]
+pub struct RemovedMessage<R>(PhantomData<R>);
+
+/// Tombstone for the id of a removed request handler.
+///
+/// Requests are dropped like messages, but if they were sent with a deadline
+/// the caller gets [`RequestError::HandlerRemoved`] back.
+#[doc(hidden)]
+pub struct RemovedRequest<R>(PhantomData<R>);
+
+fn report_removed<AP: AbstractProcess, R: RemovedHandler>() {
+    crate::warning::emit(format!(
+        "`{}` received a message for the removed handler `{}`, dropping it",
+        type_name::<AP>(),
+        R::NAME
+    ));
+    crate::metrics::increment_counter(&format!("{}::{}::removed", type_name::<AP>(), R::NAME));
+}
+
+impl<AP: AbstractProcess, R: RemovedHandler> Handler<AP> for RemovedMessage<R> {
+    fn handle(_: Tag, _: &mut AP::State) {
+        report_removed::<AP, R>();
+    }
+
+    fn name(_relative_id: u8) -> &'static str {
+        R::NAME
+    }
+}
+
+impl<AP, R> Handler<AP> for RemovedRequest<R>
+where
+    AP: AbstractProcess,
+    R: RemovedHandler,
+    AP::Serializer: CanSerialize<()>,
+    AP::Serializer: CanSerialize<RequestMessage<R::Args, (), AP::Serializer>>,
+{
+    fn handle(response_tag: Tag, _: &mut AP::State) {
+        report_removed::<AP, R>();
+        // Callers without a deadline expect the response of the old handler,
+        // there is nothing to send them.
+        if Context::deadline().is_none() {
+            return;
+        }
+        let decoded: Result<RequestMessage<R::Args, (), AP::Serializer>, _> =
+            AP::Serializer::decode();
+        if let Ok(request) = decoded {
+            request
+                .1
+                .send_result(Err(RequestError::HandlerRemoved), response_tag);
+        }
+    }
+
+    fn name(_relative_id: u8) -> &'static str {
+        R::NAME
+    }
+}
+
+/// Handlers of an impl block marked with `#[abstract_process(extend =
+/// "...")]`, taking a slot in the `Handlers` tuple of the process.
+///
+/// The handlers get consecutive ids, as if they were part of the tuple.
+pub struct Extension<G>(PhantomData<G>);
+
+/// Registers the handlers of an impl block extending an abstract process.
+///
+/// It's implemented by the marker type generated with
+/// `#[abstract_process(extend = "StoreWrites")]`, and the process adds
+/// `Extension<StoreWrites>` to its `Handlers` tuple.
+pub trait HandlerGroup<AP: AbstractProcess> {
+    type Handlers: Handlers<AP>;
+}
+
+impl<AP: AbstractProcess, G: HandlerGroup<AP>> Handler<AP> for Extension<G> {
+    fn handle(_: Tag, _: &mut AP::State) {
+        unreachable!("extensions are dispatched with `handle_id`")
+    }
+
+    fn ids() -> u8 {
+        G::Handlers::ids()
+    }
+
+    fn relative_id<H: 'static>() -> Option<u8> {
+        G::Handlers::find_handler::<H>().map(|id| id - 1)
+    }
+
+    fn name(relative_id: u8) -> &'static str {
+        G::Handlers::handler_name(relative_id + 1)
+    }
+
+    fn handle_id(response_tag: Tag, relative_id: u8, state: &mut AP::State) {
+        G::Handlers::handle(response_tag, relative_id + 1, state)
+    }
+
+    fn priority(relative_id: u8) -> u8 {
+        G::Handlers::priority(relative_id + 1)
+    }
+}
+
 pub trait Handlers<AP: AbstractProcess> {
     fn handler_id<Handler: 'static>() -> u8;
+    fn handler_name(id: u8) -> &'static str;
     fn handle(response_tag: Tag, id: u8, state: &mut AP::State);
+    /// Number of handler ids, extensions count all of their handlers.
+    #[doc(hidden)]
+    fn ids() -> u8;
+    /// Returns the id of `Handler`, or `None` if it's not in the tuple.
+    #[doc(hidden)]
+    fn find_handler<Handler: 'static>() -> Option<u8>;
+    /// Returns the priority of messages to the handler `id`, `0` for none.
+    #[doc(hidden)]
+    fn priority(id: u8) -> u8;
 }
 
 // Implement `Handlers` for tuple containing up to 16 handlers.
@@ -100,9 +382,9 @@ mod macros {
             {
                 #[track_caller]
                 fn handler_id<Handler: 'static>() -> u8 {
-                    match TypeId::of::<Handler>() {
-                        $(id if id == TypeId::of::<$args>() => $i,)*
-                        _ => panic!(
+                    match <Self as Handlers<AP>>::find_handler::<Handler>() {
+                        Some(id) => id,
+                        None => panic!(
                             "Called `send/request()` on type '{}' that doesn't match any handler defined in '<{} as AbstractProcess>::Handlers'",
                             type_name::<Handler>(),
                             type_name::<AP>()
@@ -110,19 +392,66 @@ mod macros {
                     }
                 }
 
-                #[allow(unused_variables)]
+                // Each entry takes `ids()` consecutive ids, starting at 1.
+                #[allow(unused_mut, unused_variables, unused_assignments)]
+                fn handler_name(id: u8) -> &'static str {
+                    let mut first = 1;
+                    $(
+                        if (first..first + <$args as Handler<AP>>::ids()).contains(&id) {
+                            return <$args as Handler<AP>>::name(id - first);
+                        }
+                        first += <$args as Handler<AP>>::ids();
+                    )*
+                    "unknown"
+                }
+
+                #[allow(unused_mut, unused_variables, unused_assignments)]
+                fn priority(id: u8) -> u8 {
+                    let mut first = 1;
+                    $(
+                        if (first..first + <$args as Handler<AP>>::ids()).contains(&id) {
+                            return <$args as Handler<AP>>::priority(id - first);
+                        }
+                        first += <$args as Handler<AP>>::ids();
+                    )*
+                    0
+                }
+
+                #[allow(unused_mut, unused_variables, unused_assignments)]
                 fn handle(response_tag: Tag, id: u8, state: &mut <AP as AbstractProcess>::State) {
-                    match id {
-                        // Handlers start with a value of 1. Zero indicates that this is a response from another
-                        // process where the call timed out, and we don't care about the result.
-                        0 => (),
-                        $($i => $args::handle(response_tag, state),)*
-                        _ => unreachable!(
-                            "AbstractProcess `{}` received message with unknown message ID: {}.",
-                            type_name::<AP>(),
-                            id
-                        ),
+                    // Handlers start with a value of 1. Zero indicates that this is a response from another
+                    // process where the call timed out, and we don't care about the result.
+                    if id == 0 {
+                        return;
                     }
+                    let mut first = 1;
+                    $(
+                        if (first..first + <$args as Handler<AP>>::ids()).contains(&id) {
+                            return <$args as Handler<AP>>::handle_id(response_tag, id - first, state);
+                        }
+                        first += <$args as Handler<AP>>::ids();
+                    )*
+                    unreachable!(
+                        "AbstractProcess `{}` received message with unknown message ID: {}.",
+                        type_name::<AP>(),
+                        id
+                    )
+                }
+
+                fn ids() -> u8 {
+                    0 $(+ <$args as Handler<AP>>::ids())*
+                }
+
+                #[allow(unused_mut, unused_variables, unused_assignments)]
+                fn find_handler<H: 'static>() -> Option<u8> {
+                    let mut first = 1;
+                    $(
+                        if let Some(id) = <$args as Handler<AP>>::relative_id::<H>() {
+                            return Some(first + id);
+                        }
+                        first += <$args as Handler<AP>>::ids();
+                    )*
+                    None
                 }
             }
         };
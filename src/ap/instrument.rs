@@ -0,0 +1,27 @@
+//! Recording the handler calls of an [`AbstractProcess`] in the metrics of
+//! the runtime.
+//!
+//! Metrics are named after the process and handler type, e.g.
+//! `counter::Counter::lunatic::ap::handlers::Request<...>::duration`. The
+//! runtime doesn't record when a message was sent, so the time it waited in
+//! the mailbox isn't measured.
+
+use std::time::Duration;
+
+use super::handlers::Handlers;
+use super::AbstractProcess;
+use crate::metrics;
+
+/// Records a call of the handler with `id` that took `duration`.
+pub(crate) fn record<AP: AbstractProcess>(id: u8, duration: Duration, panicked: bool) {
+    let prefix = format!(
+        "{}::{}",
+        std::any::type_name::<AP>(),
+        AP::Handlers::handler_name(id)
+    );
+    metrics::increment_counter(&format!("{prefix}::calls"));
+    metrics::histogram(&format!("{prefix}::duration"), duration.as_secs_f64());
+    if panicked {
+        metrics::increment_counter(&format!("{prefix}::panics"));
+    }
+}
@@ -2,12 +2,20 @@
 //! termination. This file contains the implementation of each lifecycle.
 
 use std::ptr::null;
+use std::time::{Duration, Instant};
 
 use super::handlers::Handlers;
-use super::messages::{ShutdownMessage, SHUTDOWN_HANDLER};
+use super::messages::{
+    ReturnAddress, ShutdownMessage, MIGRATE_HANDLER, PIPE_HANDLER, REPLACE_STATE_HANDLER,
+    SHUTDOWN_HANDLER,
+};
+use super::restart::{self, Restarts};
 use super::tag::AbstractProcessTag;
-use super::{AbstractProcess, Config, StartupError};
-use crate::mailbox::LINK_DIED;
+use super::{
+    cleanup, crash_report, instrument, migration, park, pipe, pipeline, replace_state,
+    AbstractProcess, Config, Context, ExitNotice, ExitReason, StartupError, TrapInfo,
+};
+use crate::mailbox::{LINK_DIED, PROCESS_DIED, TIMEOUT};
 use crate::panic::{catch_panic, Panicked};
 use crate::serializer::CanSerialize;
 use crate::{host, Mailbox, Process, Tag};
@@ -23,14 +31,22 @@ type ParentProcessRef<AP> =
 /// the `init` function.
 ///
 /// After the initialization finishes, it will spin in a loop waiting for
-/// commands, until the `Shutdown` command is received.
+/// commands, until the `Shutdown` command is received or the process is
+/// migrated to another node. If the parent asked for it, a shutdown or a panic
+/// is reported with the `notice`.
 pub(crate) fn entry<AP: AbstractProcess>(
-    (parent, init_tag, arg): (ParentProcessRef<AP>, Tag, AP::Arg),
+    (parent, init_tag, notice, arg): (ParentProcessRef<AP>, Tag, Option<ExitNotice>, AP::Arg),
     _: Mailbox<(), AP::Serializer>, // Can't be used for the `AbstractProcess` special case.
 ) where
     AP::Serializer: CanSerialize<()>,
     AP::Serializer: CanSerialize<ShutdownMessage<AP::Serializer>>,
 {
+    crate::process::enter::<AP>();
+    // The message of a panic is part of the reported exit.
+    if notice.is_some() {
+        crash_report::install_hook();
+    }
+    let mut restarts = Restarts::<AP>::new(&arg);
     // Catch errors during startup and notify parent. Panics will also be caught.
     let mut state = match startup::<AP>(arg) {
         Ok(state) => {
@@ -45,12 +61,15 @@ pub(crate) fn entry<AP: AbstractProcess>(
         }
     };
 
-    let shutdown_tag = loop_and_handle::<AP>(&mut state);
-    shutdown::<AP>(shutdown_tag, state);
+    // A migrated process exits without calling `terminate`, the state lives on
+    // in the replacement.
+    if let Some(shutdown_tag) = loop_and_handle::<AP>(&mut state, &mut restarts, notice) {
+        shutdown::<AP>(shutdown_tag, state, notice);
+    }
 }
 
 /// This code is executed during the [`AbstractProcess::start`] call.
-fn startup<AP: AbstractProcess>(arg: AP::Arg) -> Result<AP::State, StartupError<AP>> {
+pub(super) fn startup<AP: AbstractProcess>(arg: AP::Arg) -> Result<AP::State, StartupError<AP>> {
     let config = Config::new();
     match catch_panic(|| AP::init(config, arg)) {
         Ok(Ok(state)) => Ok(state),
@@ -61,13 +80,33 @@ fn startup<AP: AbstractProcess>(arg: AP::Arg) -> Result<AP::State, StartupError<
 
 /// Extracts the handler out of the tag for each incoming message, until
 /// shutdown message is received.
-fn loop_and_handle<AP: AbstractProcess>(state: &mut AP::State) -> Tag {
+///
+/// Returns `None` if the process was migrated to another node.
+fn loop_and_handle<AP: AbstractProcess>(
+    state: &mut AP::State,
+    restarts: &mut Option<Restarts<AP>>,
+    notice: Option<ExitNotice>,
+) -> Option<Tag> {
+    let priority_tags = priority_tags::<AP>();
     loop {
-        // Wait for next message & handle link died if result matches constant.
-        if unsafe { host::api::message::receive(null(), 0, u64::MAX) } == LINK_DIED {
+        // Take waiting messages with a priority out first, then wait for the
+        // next message & handle link died if result matches constant.
+        let message_type = priority_tags
+            .iter()
+            .map(|tags| unsafe { host::api::message::receive(tags.as_ptr(), tags.len(), 0) })
+            .find(|message_type| *message_type != TIMEOUT)
+            .unwrap_or_else(|| unsafe { host::api::message::receive(null(), 0, u64::MAX) });
+        if message_type == LINK_DIED {
             let tag = unsafe { host::api::message::get_tag() };
-            let tag = Tag::from(tag);
-            AP::handle_link_death(super::State { state }, tag);
+            let info = TrapInfo {
+                tag: Tag::from(tag),
+            };
+            AP::handle_link_death(super::State { state }, info);
+            continue;
+        }
+        if message_type == PROCESS_DIED {
+            let process_id = unsafe { host::api::message::get_process_id() };
+            AP::handle_process_death(super::State { state }, process_id);
             continue;
         }
 
@@ -78,16 +117,98 @@ fn loop_and_handle<AP: AbstractProcess>(state: &mut AP::State) -> Tag {
 
         // Check if `data` matches the shutdown message
         if data == SHUTDOWN_HANDLER {
-            break response_tag;
+            break Some(response_tag);
+        }
+        if data == MIGRATE_HANDLER {
+            if migration::handle::<AP>(response_tag, state) {
+                break None;
+            }
+            continue;
+        }
+        if data == PIPE_HANDLER {
+            pipe::handle_control();
+            continue;
+        }
+        // Responses to requests sent by this process and messages resuming a
+        // parked handler don't carry a handler id.
+        if data == 0 {
+            if !park::resume::<AP>(response_tag, state) {
+                pipeline::resume(response_tag);
+            }
+            continue;
         }
 
+        // Requests can carry a deadline in front of the message, it needs to be
+        // read before the handler decodes the rest.
+        Context::enter(AbstractProcessTag::has_deadline(tag));
+        if data == REPLACE_STATE_HANDLER {
+            replace_state::handle::<AP>(response_tag, state);
+            Context::exit();
+            continue;
+        }
+        let started = AP::instrument().then(Instant::now);
         // Use `data` to look up the right handler function
-        AP::Handlers::handle(response_tag, data, state);
+        if crash_report::is_enabled() || restarts.is_some() || started.is_some() || notice.is_some()
+        {
+            if catch_panic(|| AP::Handlers::handle(response_tag, data, state)).is_err() {
+                if let Some(started) = started {
+                    instrument::record::<AP>(data, started.elapsed(), true);
+                }
+                let handler_name = AP::Handlers::handler_name(data);
+                crash_report::report(handler_name, tag);
+                Context::exit();
+                let restarted = restarts
+                    .as_mut()
+                    .is_some_and(|restarts| restarts.restart(state, handler_name));
+                if !restarted {
+                    report_panic(notice);
+                    // Re-raise the trap without running the panic hook again.
+                    std::panic::resume_unwind(Box::new(Panicked));
+                }
+                continue;
+            }
+        } else {
+            AP::Handlers::handle(response_tag, data, state);
+        }
+        restart::untrack_request();
+        if let Some(started) = started {
+            instrument::record::<AP>(data, started.elapsed(), false);
+        }
+        Context::exit();
+    }
+}
+
+/// Returns the tags of messages with a priority, grouped by priority from the
+/// highest to the lowest.
+///
+/// It's empty unless the process uses the `"priority"` mailbox strategy.
+fn priority_tags<AP: AbstractProcess>() -> Vec<Vec<i64>> {
+    let mut levels: Vec<(u8, Vec<i64>)> = Vec::new();
+    for id in 1..=AP::Handlers::ids() {
+        let priority = AP::Handlers::priority(id);
+        if priority == 0 {
+            continue;
+        }
+        let tag = AbstractProcessTag::priority(id).id();
+        match levels.iter_mut().find(|(level, _)| *level == priority) {
+            Some((_, tags)) => tags.push(tag),
+            None => levels.push((priority, vec![tag])),
+        }
+    }
+    levels.sort_by(|(a, _), (b, _)| b.cmp(a));
+    levels.into_iter().map(|(_, tags)| tags).collect()
+}
+
+/// Reports the panic that just happened with the `notice`, if there is one.
+fn report_panic(notice: Option<ExitNotice>) {
+    if let Some(notice) = notice {
+        let message = crash_report::panic_message().unwrap_or_default();
+        notice.send(ExitReason::Panicked { message });
     }
 }
 
 /// Is executed if the [`AbstractProcess`] receives a `shutdown` command.
-fn shutdown<AP>(shutdown_tag: Tag, state: AP::State)
+fn shutdown<AP>(shutdown_tag: Tag, state: AP::State, notice: Option<ExitNotice>)
 where
     AP: AbstractProcess,
     AP::Serializer: CanSerialize<()>,
@@ -96,6 +217,70 @@ where
     // The shutdown message needs to deserialize before `terminate` is called.
     // After `terminate` we could have another message in the buffer.
     let shutdown_message: ShutdownMessage<AP::Serializer> = AP::Serializer::decode().unwrap();
-    AP::terminate(state);
+    let ack_tag = Tag::new();
+    let watchdog = AP::terminate_timeout().map(|timeout| {
+        // Safety: Only the acknowledgement with `ack_tag` is sent to it.
+        let this = unsafe { Process::<()>::this() };
+        let capture = (
+            this,
+            ack_tag,
+            timeout,
+            std::any::type_name::<AP>().to_string(),
+            shutdown_message.0.clone(),
+            shutdown_tag,
+        );
+        Process::<()>::spawn(capture, terminate_watchdog::<AP::Serializer>)
+    });
+    let terminated = catch_panic(|| AP::terminate(state));
+    // Registered cleanup actions run even if `terminate` panicked.
+    cleanup::run();
+    if let Some(watchdog) = watchdog {
+        // Once the watchdog acknowledged, it doesn't kill the process anymore
+        // and won't answer the `shutdown` call. If it timed out in the
+        // meantime, this process gets killed before the acknowledgement.
+        watchdog.send(());
+        // Safety: Only the acknowledgement is received with `ack_tag`.
+        unsafe { Mailbox::<()>::new() }.tag_receive(&[ack_tag]);
+    }
+    if terminated.is_err() {
+        report_panic(notice);
+        panic!("`terminate` of abstract process panicked");
+    }
     shutdown_message.0.send_response((), shutdown_tag);
+    if let Some(notice) = notice {
+        notice.send(ExitReason::Normal);
+    }
+}
+
+/// Kills the abstract process if `terminate` and the cleanup actions don't
+/// finish within the timeout, and answers the `shutdown` call in its place.
+///
+/// The abstract process tells the watchdog once the cleanup finished and waits
+/// for the acknowledgement before answering the call itself. The watchdog
+/// either acknowledges or kills the process, never both, so the call is
+/// answered exactly once and the process isn't killed while answering it.
+fn terminate_watchdog<S>(
+    (process, ack_tag, timeout, name, return_address, shutdown_tag): (
+        Process<()>,
+        Tag,
+        Duration,
+        String,
+        ReturnAddress<(), S>,
+        Tag,
+    ),
+    mailbox: Mailbox<()>,
+) where
+    S: CanSerialize<()>,
+{
+    match mailbox.receive_timeout(timeout) {
+        Ok(()) => process.tag_send(ack_tag, ()),
+        Err(_) => {
+            crate::warning::emit(format!(
+                "`terminate` of {name} didn't finish within {timeout:?}, \
+                 killing the process and skipping the remaining cleanup"
+            ));
+            process.kill();
+            return_address.send_response((), shutdown_tag);
+        }
+    }
 }
@@ -1,5 +1,18 @@
+use super::{restart, RequestError};
 use crate::serializer::CanSerialize;
-use crate::{Process, Tag};
+use crate::{host, Process, Tag};
+
+/// Status byte in front of responses to requests sent with a deadline.
+pub(crate) const RESPONSE_OK: u8 = 0;
+/// Status byte indicating that the request was dropped because the deadline
+/// passed. It's not followed by a response.
+pub(crate) const RESPONSE_DEADLINE_EXCEEDED: u8 = 1;
+/// Status byte indicating that the handler the request was meant for was
+/// removed from the process. It's not followed by a response.
+pub(crate) const RESPONSE_HANDLER_REMOVED: u8 = 2;
+/// Status byte indicating that the handler panicked in a process that restarts
+/// on panic. It's not followed by a response.
+pub(crate) const RESPONSE_HANDLER_PANICKED: u8 = 3;
 
 /// Contains information about the request sender, so that a response can be
 /// sent back to the correct process.
@@ -9,6 +22,14 @@ pub(crate) struct ReturnAddress<Response, Serializer> {
     process: Process<Response, Serializer>,
 }
 
+impl<Response, Serializer> Clone for ReturnAddress<Response, Serializer> {
+    fn clone(&self) -> Self {
+        ReturnAddress {
+            process: self.process,
+        }
+    }
+}
+
 impl<Response, Serializer> ReturnAddress<Response, Serializer>
 where
     Serializer: CanSerialize<Response>,
@@ -25,6 +46,43 @@ where
     pub(crate) fn send_response(self, response: Response, tag: Tag) {
         self.process.tag_send(tag, response);
     }
+
+    /// Sends a response to a request that was sent with a deadline.
+    ///
+    /// The caller expects a status byte in front of the serialized response.
+    pub(crate) fn send_result(self, result: Result<Response, RequestError>, tag: Tag) {
+        match result {
+            Ok(response) => {
+                unsafe { host::api::message::create_data(tag.id(), 0) };
+                let status = [RESPONSE_OK];
+                unsafe { host::api::message::write_data(status.as_ptr(), status.len()) };
+                Serializer::encode(&response).unwrap();
+                host::send(self.process.node_id(), self.process.id());
+            }
+            Err(err) => send_error(self.process.node_id(), self.process.id(), err, tag),
+        }
+    }
+
+    /// Remembers the caller of the request that is being handled, so that it
+    /// isn't left waiting if the handler panics and the process restarts.
+    pub(crate) fn track(&self, tag: Tag, has_deadline: bool) {
+        restart::track_request(self.process.node_id(), self.process.id(), tag, has_deadline);
+    }
+}
+
+/// Sends `err` to a caller that sent its request with a deadline.
+///
+/// Errors aren't followed by a response, so this doesn't need to know the
+/// response type.
+pub(crate) fn send_error(node_id: u64, process_id: u64, err: RequestError, tag: Tag) {
+    unsafe { host::api::message::create_data(tag.id(), 0) };
+    let status = match err {
+        RequestError::HandlerRemoved => [RESPONSE_HANDLER_REMOVED],
+        RequestError::HandlerPanicked => [RESPONSE_HANDLER_PANICKED],
+        _ => [RESPONSE_DEADLINE_EXCEEDED],
+    };
+    unsafe { host::api::message::write_data(status.as_ptr(), status.len()) };
+    host::send(node_id, process_id);
 }
 
 /// Value identifying the shutdown handler.
@@ -32,6 +90,16 @@ where
 /// All other handlers have a value from 0-16.
 pub(crate) const SHUTDOWN_HANDLER: u8 = 32;
 
+/// Value identifying control messages of pipes connected to the
+/// [`AbstractProcess`].
+pub(crate) const PIPE_HANDLER: u8 = 33;
+
+/// Value identifying migration requests sent to the [`AbstractProcess`].
+pub(crate) const MIGRATE_HANDLER: u8 = 34;
+
+/// Value identifying requests replacing the state of the [`AbstractProcess`].
+pub(crate) const REPLACE_STATE_HANDLER: u8 = 35;
+
 /// An incoming message indicating a shutdown for the [`AbstractProcess`].
 ///
 /// The message combined with the `SHUTDOWN_HANDLER` data inside the tag.
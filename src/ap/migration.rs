@@ -0,0 +1,168 @@
+//! Moving an abstract process to another node.
+//!
+//! The caller asks the process for a snapshot of its state and starts a
+//! replacement from it. Until the replacement is running the old process stops
+//! handling messages, they stay in its mailbox. Once the caller tells it where
+//! the replacement lives, the old process forwards its whole mailbox there and
+//! exits.
+
+use std::ptr::null;
+
+use serde::{Deserialize, Serialize};
+
+use super::messages::MIGRATE_HANDLER;
+use super::tag::AbstractProcessTag;
+use super::{AbstractProcess, ProcessRef};
+use crate::distributed::MigrationError;
+use crate::function::process::{process_name, ProcessType};
+use crate::mailbox::{LINK_DIED, TIMEOUT};
+use crate::serializer::{Bincode, CanSerialize};
+use crate::{host, Tag};
+
+/// Status byte in front of a snapshot.
+const SNAPSHOT_OK: u8 = 0;
+/// Status byte indicating that the process doesn't support snapshots. It's not
+/// followed by a snapshot.
+const SNAPSHOT_UNSUPPORTED: u8 = 1;
+
+/// Control messages of a migration.
+///
+/// They are always encoded with `Bincode`, independent of the serializer used
+/// by the abstract process.
+#[derive(Serialize, Deserialize)]
+enum MigrationMessage {
+    /// Requests a snapshot, sent to the process that is migrated.
+    Snapshot { node_id: u64, process_id: u64 },
+    /// The replacement is running, forward all messages to it.
+    Forward { node_id: u64, process_id: u64 },
+    /// The replacement failed to start, continue handling messages.
+    Resume,
+}
+
+/// Moves `process` to `target_node` and re-registers it under `name`.
+pub(crate) fn migrate<T: AbstractProcess>(
+    process: ProcessRef<T>,
+    target_node: u64,
+    name: Option<&str>,
+) -> Result<ProcessRef<T>, MigrationError<T>> {
+    process.assert_not_self();
+    let send_tag = AbstractProcessTag::from_u6(MIGRATE_HANDLER);
+    let (reply_tag, _) = AbstractProcessTag::extract_u6_data(send_tag);
+
+    unsafe { host::api::message::create_data(send_tag.id(), 0) };
+    <Bincode as CanSerialize<MigrationMessage>>::encode(&MigrationMessage::Snapshot {
+        node_id: host::node_id(),
+        process_id: host::process_id(),
+    })
+    .unwrap();
+    host::send_receive_skip_search(process.node_id(), process.id(), reply_tag.id(), u64::MAX);
+    let mut status = [0u8];
+    unsafe { host::api::message::read_data(status.as_mut_ptr(), status.len()) };
+    if status[0] != SNAPSHOT_OK {
+        return Err(MigrationError::SnapshotUnsupported);
+    }
+    let snapshot = <T::Serializer as CanSerialize<T::Arg>>::decode()
+        .map_err(|_| MigrationError::SnapshotUnsupported)?;
+
+    let replacement = match T::on_node(target_node).start(snapshot) {
+        Ok(replacement) => replacement,
+        Err(err) => {
+            send_control(process, reply_tag, MigrationMessage::Resume);
+            return Err(MigrationError::Startup(err));
+        }
+    };
+    // Register the replacement before the old process exits, so that lookups
+    // never fail during the migration.
+    if let Some(name) = name {
+        let name = process_name::<T, T::Serializer>(ProcessType::ProcessRef, name);
+        unsafe {
+            host::api::registry::put(
+                name.as_ptr(),
+                name.len(),
+                replacement.node_id(),
+                replacement.id(),
+            )
+        };
+    }
+    send_control(
+        process,
+        reply_tag,
+        MigrationMessage::Forward {
+            node_id: replacement.node_id(),
+            process_id: replacement.id(),
+        },
+    );
+    Ok(replacement)
+}
+
+fn send_control<T: AbstractProcess>(process: ProcessRef<T>, tag: Tag, message: MigrationMessage) {
+    unsafe { host::api::message::create_data(tag.id(), 0) };
+    <Bincode as CanSerialize<MigrationMessage>>::encode(&message).unwrap();
+    host::send(process.node_id(), process.id());
+}
+
+/// Handles a migration request received by the current process.
+///
+/// Returns `true` if the process was migrated and should exit without calling
+/// `terminate`.
+pub(crate) fn handle<AP: AbstractProcess>(reply_tag: Tag, state: &AP::State) -> bool {
+    let Ok(MigrationMessage::Snapshot {
+        node_id,
+        process_id,
+    }) = <Bincode as CanSerialize<MigrationMessage>>::decode()
+    else {
+        return false;
+    };
+
+    unsafe { host::api::message::create_data(reply_tag.id(), 0) };
+    let snapshot = AP::snapshot(state);
+    let status = match snapshot {
+        Some(_) => [SNAPSHOT_OK],
+        None => [SNAPSHOT_UNSUPPORTED],
+    };
+    unsafe { host::api::message::write_data(status.as_ptr(), status.len()) };
+    if let Some(snapshot) = &snapshot {
+        AP::Serializer::encode(snapshot).unwrap();
+    }
+    host::send(node_id, process_id);
+    if snapshot.is_none() {
+        return false;
+    }
+
+    // Wait until the replacement is running. Other messages stay buffered in
+    // the mailbox in the meantime.
+    let tags = [reply_tag.id()];
+    unsafe { host::api::message::receive(tags.as_ptr(), tags.len(), u64::MAX) };
+    match <Bincode as CanSerialize<MigrationMessage>>::decode() {
+        Ok(MigrationMessage::Forward {
+            node_id,
+            process_id,
+        }) => {
+            forward_mailbox(node_id, process_id);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Re-sends all messages waiting in the mailbox to the process `process_id`,
+/// keeping their tags.
+fn forward_mailbox(node_id: u64, process_id: u64) {
+    loop {
+        match unsafe { host::api::message::receive(null(), 0, 0) } {
+            TIMEOUT => break,
+            // Links are not carried over to the replacement.
+            LINK_DIED => continue,
+            _ => (),
+        }
+        let tag = unsafe { host::api::message::get_tag() };
+        let size = unsafe { host::api::message::data_size() } as usize;
+        let mut data = vec![0u8; size];
+        unsafe {
+            host::api::message::read_data(data.as_mut_ptr(), size);
+            host::api::message::create_data(tag, size as u64);
+            host::api::message::write_data(data.as_ptr(), size);
+        }
+        host::send(node_id, process_id);
+    }
+}
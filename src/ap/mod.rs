@@ -1,29 +1,70 @@
 //! Contains the [`AbstractProcess`] abstraction.
 
+mod broadcast;
 mod builder;
+mod cleanup;
+mod context;
+mod continuation;
+mod crash_report;
+mod instrument;
 mod lifecycles;
+mod migration;
+pub(crate) mod park;
+mod pending_call;
+mod pipe;
+mod pipeline;
+mod replace_state;
+mod restart;
+mod stream;
 mod tag;
+#[cfg(feature = "opentelemetry")]
+mod telemetry;
+mod trap;
 
 pub mod handlers;
 pub(crate) mod messages;
 
 use std::any::type_name;
 use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
 use std::mem;
 use std::ops::{Deref, DerefMut};
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
 
 use self::builder::AbstractProcessBuilder;
-use self::handlers::{DeferredRequest, Handlers, Message, Request};
-use self::messages::{RequestMessage, ReturnAddress, ShutdownMessage, SHUTDOWN_HANDLER};
+pub use self::context::Context;
+#[doc(hidden)]
+pub use self::continuation::{__store_pending, __take_pending};
+pub use self::continuation::{Resume, Step};
+pub use self::crash_report::CrashReport;
+pub(crate) use self::broadcast::{broadcast, register as register_broadcast};
+pub(crate) use self::migration::migrate;
+use self::handlers::{
+    DeferredRequest, Handlers, Message, Request, ResponderRequest, StreamRequest,
+};
+use self::messages::{
+    RequestMessage, ReturnAddress, ShutdownMessage, RESPONSE_HANDLER_PANICKED,
+    RESPONSE_HANDLER_REMOVED, RESPONSE_OK, SHUTDOWN_HANDLER,
+};
+pub use self::pending_call::{resolve_any, PendingCall};
+pub use self::pipe::{Output, PipeHandle};
+pub use self::pipeline::Pipeline;
+pub use self::restart::RestartPolicy;
+pub use self::stream::{ResponseIter, ResponseStream, StreamError, StreamItem};
 use self::tag::AbstractProcessTag;
+#[cfg(feature = "opentelemetry")]
+#[doc(hidden)]
+pub use self::telemetry::{TraceHeaders, __handler_span};
+pub use self::trap::{ExitNotice, ExitReason, LinkDeathArg, TrapInfo};
 use crate::function::process::{process_name, ProcessType};
-use crate::mailbox::{MailboxError, MessageSignal};
+use crate::mailbox::{MailboxError, MessageSignal, TIMEOUT};
 use crate::protocol::ProtocolCapture;
 use crate::serializer::CanSerialize;
 use crate::time::{Timeout, TimerRef, WithDelay, WithTimeout};
-use crate::{host, MailboxResult, Process, ProcessConfig, ProcessName, Tag};
+use crate::{host, Mailbox, MailboxResult, Process, ProcessConfig, ProcessName, Tag};
 
 /// Building block for processes that act as a server of a client-server
 /// relation.
@@ -91,6 +132,29 @@ use crate::{host, MailboxResult, Process, ProcessConfig, ProcessName, Tag};
 /// }
 /// ```
 ///
+/// A deferred request that depends on the responses of other processes can
+/// be answered with a [`Pipeline`] of requests, without blocking the process
+/// while it waits on them.
+///
+/// A [`ResponderRequestHandler`] can reply early with a [`Responder`] and
+/// continue working after the caller was unblocked. If the handler didn't
+/// respond, the returned value is sent as the response after it returns.
+///
+/// ```rust
+/// impl ResponderRequestHandler<Save> for Counter {
+///     type Response = bool;
+///     fn handle(
+///         state: State<Self>,
+///         save: Save,
+///         responder: &mut Responder<bool, Self>,
+///     ) -> Option<bool> {
+///         responder.respond(true);
+///         write_to_disk(save);
+///         None
+///     }
+/// }
+/// ```
+///
 /// _It is not enough just to define the handlers, they also need to be
 /// associated with the `AbstractProcess` using the [`Self::Handlers`] type:_
 ///
@@ -103,6 +167,11 @@ use crate::{host, MailboxResult, Process, ProcessConfig, ProcessName, Tag};
 /// An abstract process can be shut down using the [`ProcessRef::shutdown`]
 /// call. This function will block, until the [`Self::terminate`] function
 /// finishes.
+///
+/// Resources owned by the process (child processes, timers, ...) can be
+/// registered for cleanup with [`Config::defer_on_terminate`] or
+/// [`State::defer_on_terminate`]. The registered actions are executed in
+/// reverse registration order after `terminate` returns, even if it panics.
 pub trait AbstractProcess: Sized
 where
     // The serializer needs to be able to serialize types that are used
@@ -126,12 +195,14 @@ where
     Self::Serializer: CanSerialize<(
         Process<Result<(), StartupError<Self>>, Self::Serializer>,
         Tag,
+        Option<ExitNotice>,
         Self::Arg,
     )>,
     Self::Serializer: CanSerialize<
         ProtocolCapture<(
             Process<Result<(), StartupError<Self>>, Self::Serializer>,
             Tag,
+            Option<ExitNotice>,
             Self::Arg,
         )>,
     >,
@@ -182,8 +253,64 @@ where
     /// Called when a `shutdown` command is received.
     fn terminate(_state: Self::State) {}
 
+    /// Returns the time [`terminate`](Self::terminate) and the cleanup actions
+    /// registered with [`Config::defer_on_terminate`] have to finish.
+    ///
+    /// If they take longer, the process is killed and the `shutdown` call
+    /// returns, the remaining cleanup is skipped with a warning. Processes
+    /// returning `None`, the default, wait until the cleanup finishes.
+    fn terminate_timeout() -> Option<Duration> {
+        None
+    }
+
     /// This function will be called if another linked process dies.
-    fn handle_link_death(_state: State<Self>, _tag: Tag) {}
+    fn handle_link_death(_state: State<Self>, _info: TrapInfo) {}
+
+    /// This function will be called if a monitored process dies, also if it
+    /// finished normally.
+    ///
+    /// Processes are monitored with
+    /// [`host::api::process::monitor`](crate::host::api::process::monitor).
+    /// If a process is both linked and monitored, the link death is handled
+    /// first.
+    fn handle_process_death(_state: State<Self>, _process_id: u64) {}
+
+    /// Returns a snapshot of the state, used to move the process to another
+    /// node with [`migrate`](crate::distributed::migrate).
+    ///
+    /// The snapshot is passed as argument to [`init`](Self::init) of the
+    /// replacement process. Processes returning `None`, the default, can't be
+    /// migrated.
+    fn snapshot(_state: &Self::State) -> Option<Self::Arg> {
+        None
+    }
+
+    /// Returns the policy for restarting the process in place if a handler
+    /// panics, together with a copy of `arg`.
+    ///
+    /// It's called before each call to [`init`](Self::init). On restart, the
+    /// state is dropped without calling [`terminate`](Self::terminate) and
+    /// `init` is called again with the copy, the process and all references
+    /// to it stay the same. Processes returning `None`, the default, die if a
+    /// handler panics.
+    ///
+    /// If a request handler panics, the caller gets
+    /// [`RequestError::HandlerPanicked`] back. Callers that sent the request
+    /// without a timeout can't receive an error in place of the response, they
+    /// are killed instead of waiting forever.
+    fn restart_on_panic(_arg: &Self::Arg) -> Option<(RestartPolicy, Self::Arg)> {
+        None
+    }
+
+    /// Returns `true` if each handler call is recorded in the metrics of the
+    /// runtime, see [`metrics`](crate::metrics).
+    ///
+    /// For each handler, the number of calls and panics are counted and the
+    /// duration of each call is added to a histogram, in seconds. Processes
+    /// returning `false`, the default, don't measure anything.
+    fn instrument() -> bool {
+        false
+    }
 
     /// Starts a new `AbstractProcess` and returns a reference to it.
     ///
@@ -217,6 +344,18 @@ where
         AbstractProcessBuilder::<Self>::new().start_as(name, arg)
     }
 
+    /// Starts one instance on each node of the cluster, including the local
+    /// one, with the argument returned by `arg_fn` for the node's id.
+    ///
+    /// The results are returned in the order of the node ids.
+    #[track_caller]
+    fn start_on_each_node<F>(arg_fn: F) -> Vec<Result<ProcessRef<Self>, StartupError<Self>>>
+    where
+        F: Fn(u64) -> Self::Arg,
+    {
+        AbstractProcessBuilder::<Self>::new().start_on_each_node(arg_fn)
+    }
+
     /// Links the to be spawned process to the parent.
     fn link() -> AbstractProcessBuilder<'static, Self> {
         AbstractProcessBuilder::new().link()
@@ -243,10 +382,13 @@ where
 /// Available configuration options:
 /// - [`die_if_link_dies`](Config::die_if_link_dies) - Sets if link deaths
 ///   should be caught.
+/// - [`report_crashes`](Config::report_crashes) - Sends a [`CrashReport`] if
+///   a handler panics.
 ///
 /// The `Config` struct can also be used to acquire a self reference with
 /// [`self_ref`](Config::self_ref) to send messages to itself during the
-/// initialization process.
+/// initialization process, or to register cleanup actions with
+/// [`defer_on_terminate`](Config::defer_on_terminate).
 pub struct Config<AP: AbstractProcess> {
     phantom: PhantomData<AP>,
 }
@@ -270,17 +412,58 @@ impl<AP: AbstractProcess> Config<AP> {
         unsafe { host::api::process::die_when_link_dies(die as u32) };
     }
 
+    /// Sends a [`CrashReport`] to a crate-provided logger process if a handler
+    /// panics.
+    ///
+    /// The logger prints all reports to stderr. The process still dies after
+    /// the report is sent.
+    pub fn report_crashes(&self) {
+        crash_report::enable(None);
+    }
+
+    /// Sends a [`CrashReport`] to the process registered under `name` if a
+    /// handler panics.
+    ///
+    /// The receiving process needs to be a `Process<CrashReport>`. Reports are
+    /// dropped if no such process exists, or if it's the crashing process
+    /// itself.
+    pub fn report_crashes_to(&self, name: &str) {
+        crash_report::enable(Some(name));
+    }
+
     /// Get a reference to the running [`AbstractProcess`].
     pub fn self_ref(&self) -> ProcessRef<AP> {
         let process = unsafe { Process::this() };
         ProcessRef { process }
     }
+
+    /// Registers `action` to be executed after
+    /// [`terminate`](AbstractProcess::terminate) returns.
+    ///
+    /// Actions are executed in reverse registration order, even if `terminate`
+    /// panics. A panicking action doesn't prevent the remaining ones from
+    /// running.
+    ///
+    /// ```ignore
+    /// let child = Child::link().start(()).unwrap();
+    /// config.defer_on_terminate(move || child.shutdown());
+    /// ```
+    pub fn defer_on_terminate<F: FnOnce() + 'static>(&self, action: F) {
+        cleanup::defer(action);
+    }
 }
 
 pub trait MessageHandler<Message>: AbstractProcess
 where
     Self::Serializer: CanSerialize<Message>,
 {
+    /// Priority of the messages, `0` for none.
+    ///
+    /// Processes using the `"priority"` mailbox strategy handle waiting
+    /// messages with the highest priority first, and messages without a
+    /// priority in the order they arrived.
+    const PRIORITY: u8 = 0;
+
     fn handle(state: State<Self>, message: Message);
 }
 
@@ -308,6 +491,40 @@ where
     );
 }
 
+pub trait ResponderRequestHandler<Request>: AbstractProcess
+where
+    Self::Serializer: CanSerialize<Request>,
+    Self::Serializer: CanSerialize<Self::Response>,
+{
+    type Response;
+
+    /// Handles the request.
+    ///
+    /// Exactly one response needs to be sent, either with
+    /// [`Responder::respond`] or by returning `Some(response)`. Returning
+    /// `None` without responding or returning `Some` after responding will
+    /// panic.
+    fn handle(
+        state: State<Self>,
+        request: Request,
+        responder: &mut Responder<Self::Response, Self>,
+    ) -> Option<Self::Response>;
+}
+
+pub trait StreamRequestHandler<Request>: AbstractProcess
+where
+    Self::Serializer: CanSerialize<Request>,
+    Self::Serializer: CanSerialize<StreamItem<Self::Item>>,
+{
+    type Item;
+
+    /// Handles the request.
+    ///
+    /// The items of the returned stream are sent to the caller one by one,
+    /// see [`ResponseStream`].
+    fn handle(state: State<Self>, request: Request) -> ResponseStream<Self::Item>;
+}
+
 /// A reference to the state inside handlers.
 pub struct State<'a, AP: AbstractProcess> {
     state: &'a mut AP::State,
@@ -319,6 +536,47 @@ impl<'a, AP: AbstractProcess> State<'a, AP> {
         let process = unsafe { Process::this() };
         ProcessRef { process }
     }
+
+    /// Registers `action` to be executed after
+    /// [`terminate`](AbstractProcess::terminate) returns.
+    ///
+    /// See [`Config::defer_on_terminate`] for details.
+    pub fn defer_on_terminate<F: FnOnce() + 'static>(&self, action: F) {
+        cleanup::defer(action);
+    }
+
+    /// Replaces the state with the one returned by `f`, which takes the
+    /// current state by value.
+    ///
+    /// This allows handlers to consume the state, e.g. to transition between
+    /// variants of an enum. If `f` panics the process dies, the same as if any
+    /// other handler panics.
+    pub fn replace_with<F: FnOnce(AP::State) -> AP::State>(&mut self, f: F) {
+        // Aborts if `f` unwinds, so that the moved out state is never observed.
+        struct AbortOnUnwind;
+        impl Drop for AbortOnUnwind {
+            fn drop(&mut self) {
+                std::process::abort();
+            }
+        }
+
+        let guard = AbortOnUnwind;
+        unsafe {
+            let state = std::ptr::read(self.state);
+            std::ptr::write(self.state, f(state));
+        }
+        mem::forget(guard);
+    }
+
+    /// Forwards the output `message` to all processes connected with
+    /// [`ProcessRef::pipe_to`].
+    pub fn emit<M>(&self, message: M)
+    where
+        AP: Output<M>,
+        AP::Serializer: CanSerialize<M>,
+    {
+        pipe::emit::<M, AP::Serializer>(&message);
+    }
 }
 
 impl<'a, AP: AbstractProcess> Deref for State<'a, AP> {
@@ -340,6 +598,10 @@ impl<'a, AP: AbstractProcess> DerefMut for State<'a, AP> {
 pub struct DeferredResponse<Response, AP: AbstractProcess> {
     tag: Tag,
     return_address: ReturnAddress<Response, AP::Serializer>,
+    // The caller sent the request with a deadline and expects the response in
+    // the deadline envelope.
+    #[serde(default)]
+    has_deadline: bool,
 }
 
 impl<Response, AP: AbstractProcess> DeferredResponse<Response, AP>
@@ -347,7 +609,60 @@ where
     AP::Serializer: CanSerialize<Response>,
 {
     pub fn send_response(self, response: Response) {
-        self.return_address.send_response(response, self.tag);
+        if self.has_deadline {
+            self.return_address.send_result(Ok(response), self.tag);
+        } else {
+            self.return_address.send_response(response, self.tag);
+        }
+    }
+}
+
+/// Sends the response to a request handled by a [`ResponderRequestHandler`].
+///
+/// The response is sent as soon as [`respond`](Responder::respond) is called,
+/// the rest of the handler runs while the caller is already unblocked.
+pub struct Responder<Response, AP: AbstractProcess> {
+    deferred_response: Option<DeferredResponse<Response, AP>>,
+}
+
+impl<Response, AP: AbstractProcess> Responder<Response, AP>
+where
+    AP::Serializer: CanSerialize<Response>,
+{
+    pub(crate) fn new(deferred_response: DeferredResponse<Response, AP>) -> Self {
+        Self {
+            deferred_response: Some(deferred_response),
+        }
+    }
+
+    /// Sends the response to the caller.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the response was already sent.
+    #[track_caller]
+    pub fn respond(&mut self, response: Response) {
+        match self.deferred_response.take() {
+            Some(deferred_response) => deferred_response.send_response(response),
+            None => panic!("Request was already responded to"),
+        }
+    }
+
+    /// Returns `true` if the response was already sent.
+    pub fn has_responded(&self) -> bool {
+        self.deferred_response.is_none()
+    }
+
+    /// Sends the value returned from the handler, if it didn't respond yet.
+    pub(crate) fn finish(mut self, response: Option<Response>) {
+        match response {
+            Some(response) => self.respond(response),
+            None if !self.has_responded() => panic!(
+                "Request handler of `{}` returned without responding",
+                type_name::<AP>()
+            ),
+            None => (),
+        }
     }
 }
 
@@ -355,10 +670,16 @@ where
 ///
 /// `ProcessRef<T>` is different from a `Process` in the ability to handle
 /// messages of different types, as long as the traits
-/// `MessageHandler<Message>`, `RequestHandler<Request>` or
-/// `DeferredRequestHandler<Request>` are implemented for `T`.
-#[derive(serde::Serialize, serde::Deserialize)]
-#[serde(bound = "")]
+/// `MessageHandler<Message>`, `RequestHandler<Request>`,
+/// `DeferredRequestHandler<Request>` or `ResponderRequestHandler<Request>` are
+/// implemented for `T`.
+///
+/// Copies and clones of a `ProcessRef` refer to the same process.
+///
+/// A `ProcessRef` is serialized as its `(node_id, process_id)` pair, so it can
+/// be sent as part of a message. The receiving process can use it to send
+/// messages and requests to the referenced process directly, e.g. to reply to
+/// a return address.
 pub struct ProcessRef<T>
 where
     T: AbstractProcess,
@@ -390,6 +711,14 @@ where
         self.process.node_id()
     }
 
+    /// Returns the node ID and process ID.
+    ///
+    /// Process IDs are only unique on a node, the pair identifies the process
+    /// across all nodes.
+    pub fn global_id(&self) -> (u64, u64) {
+        (self.process.node_id(), self.process.id())
+    }
+
     /// Returns a process registered under `name` if it exists and the signature
     /// matches.
     pub fn lookup<N: ProcessName + ?Sized>(name: &N) -> Option<Self> {
@@ -413,7 +742,8 @@ where
 
     /// Returns `true` for processes on the local node that are running.
     ///
-    /// Panics if called on a remote process.
+    /// Panics if called on a remote process, use
+    /// [`remote_inspect_alive`](Self::remote_inspect_alive) instead.
     #[track_caller]
     pub fn is_alive(&self) -> bool {
         assert_eq!(
@@ -468,6 +798,7 @@ where
         T::Serializer: CanSerialize<ShutdownMessage<T::Serializer>>,
         T::Serializer: CanSerialize<()>,
     {
+        self.assert_not_self();
         let return_address = ReturnAddress::from_self();
         let message = ShutdownMessage(return_address);
         let send_tag = AbstractProcessTag::from_u6(SHUTDOWN_HANDLER);
@@ -484,6 +815,22 @@ where
         }
     }
 
+    /// Asks the [`AbstractProcess`] to shut down, without waiting for it.
+    ///
+    /// Unlike [`shutdown`](Self::shutdown) this can be used by the process on
+    /// itself, it exits once the current handler returned.
+    pub(crate) fn send_shutdown(&self)
+    where
+        T::Serializer: CanSerialize<ShutdownMessage<T::Serializer>>,
+    {
+        let message = ShutdownMessage(ReturnAddress::from_self());
+        let send_tag = AbstractProcessTag::from_u6(SHUTDOWN_HANDLER);
+        // Cast into the right type for sending.
+        let process: Process<ShutdownMessage<T::Serializer>, T::Serializer> =
+            unsafe { mem::transmute(self.process) };
+        process.tag_send(send_tag, message);
+    }
+
     /// Send message to the process.
     #[track_caller]
     pub fn send<M: 'static>(&self, message: M)
@@ -491,12 +838,52 @@ where
         T::Serializer: CanSerialize<M>,
     {
         let handler_id = T::Handlers::handler_id::<Message<M>>();
-        let tag = AbstractProcessTag::from_u6(handler_id);
+        let tag = AbstractProcessTag::message::<T>(handler_id);
         // Cast into the right type for sending.
         let process: Process<M, T::Serializer> = unsafe { std::mem::transmute(self.process) };
         process.tag_send(tag, message);
     }
 
+    /// Send message to the process if it's running.
+    ///
+    /// Returns `false` without sending the message if the process doesn't
+    /// exist anymore. The liveness of processes on other nodes can't be
+    /// checked, messages to them are always sent.
+    #[track_caller]
+    pub fn try_send<M: 'static>(&self, message: M) -> bool
+    where
+        T::Serializer: CanSerialize<M>,
+    {
+        if !self.may_be_alive() {
+            return false;
+        }
+        self.send(message);
+        true
+    }
+
+    /// Returns `false` if the process is on the local node and not running.
+    pub(crate) fn may_be_alive(&self) -> bool {
+        self.process.node_id() != host::node_id()
+            || unsafe { host::api::process::exists(self.process.id()) != 0 }
+    }
+
+    /// Returns `true` if the process is running, on any node.
+    ///
+    /// Processes on the local node are looked up in the process table, like
+    /// with [`is_alive`](Self::is_alive). For a process on another node the
+    /// lookup is executed on that node with a
+    /// [`RemoteCall`](crate::distributed::RemoteCall), which blocks until the
+    /// node answered. If the node is disconnected, or disconnects before it
+    /// answered, the process is reported as dead.
+    pub fn remote_inspect_alive(&self) -> bool {
+        let node_id = self.process.node_id();
+        if node_id == host::node_id() {
+            unsafe { host::api::process::exists(self.process.id()) != 0 }
+        } else {
+            crate::distributed::process_exists(node_id, self.process.id())
+        }
+    }
+
     /// Send message to the process after the specified duration has passed.
     #[track_caller]
     pub(crate) fn delayed_send<M: 'static>(&self, message: M, duration: Duration) -> TimerRef
@@ -504,13 +891,17 @@ where
         T::Serializer: CanSerialize<M>,
     {
         let handler_id = T::Handlers::handler_id::<Message<M>>();
-        let tag = AbstractProcessTag::from_u6(handler_id);
+        let tag = AbstractProcessTag::message::<T>(handler_id);
         // Cast into the right type for sending.
         let process: Process<M, T::Serializer> = unsafe { std::mem::transmute(self.process) };
         process.tag_send_after(tag, message, duration)
     }
 
     /// Make a request to the process.
+    ///
+    /// Panics if called from inside of the process itself, because waiting on
+    /// its own response would deadlock. The same applies to all other request
+    /// methods and to [`shutdown`](Self::shutdown).
     #[track_caller]
     pub fn request<R: 'static>(&self, request: R) -> T::Response
     where
@@ -519,40 +910,223 @@ where
         T::Serializer: CanSerialize<T::Response>,
         T::Serializer: CanSerialize<RequestMessage<R, T::Response, T::Serializer>>,
     {
-        self.request_timeout(request, None).unwrap()
+        self.send_request::<R, Request<R>, T::Response>(request, None, None)
+            .unwrap()
     }
 
     /// Make a request to the process.
-    //
+    ///
     /// If a timeout is specified the function will only block for the timeout
-    /// period before returning `Err(Timeout)`.
+    /// period before returning `Err(RequestError::TimedOut)`.
+    ///
+    /// The absolute deadline is sent together with the request. If called
+    /// from inside a handler that is processing a request with an earlier
+    /// deadline, the earlier deadline is used. If the deadline passes before
+    /// the process gets to the request, it's dropped and
+    /// `Err(RequestError::DeadlineExceeded)` is returned.
     #[track_caller]
-    pub(crate) fn request_timeout<R: 'static>(
+    pub fn request_timeout<R: 'static>(
         &self,
         request: R,
         timeout: Option<Duration>,
-    ) -> Result<T::Response, Timeout>
+    ) -> Result<T::Response, RequestError>
     where
         T: RequestHandler<R>,
         T::Serializer: CanSerialize<R>,
         T::Serializer: CanSerialize<T::Response>,
         T::Serializer: CanSerialize<RequestMessage<R, T::Response, T::Serializer>>,
     {
-        let return_address = ReturnAddress::from_self();
-        let message = RequestMessage(request, return_address);
+        let deadline = Context::outgoing_deadline(timeout);
+        self.send_request::<R, Request<R>, T::Response>(request, deadline, timeout)
+    }
+
+    /// Makes a request to the process, giving up on it after `deadline`.
+    ///
+    /// Returns `Ok(None)` if no response arrived in time, e.g. because the
+    /// process is busy. It's an expected outcome for callers that would
+    /// rather skip a request than wait. Like with
+    /// [`request_timeout`](Self::request_timeout), the deadline is sent with
+    /// the request and the process drops it once the deadline passes.
+    ///
+    /// Returns `Err(RequestError::ProcessDied)` if the process isn't running,
+    /// also if it died while handling the request.
+    #[track_caller]
+    pub fn try_request<R: 'static>(
+        &self,
+        request: R,
+        deadline: Duration,
+    ) -> Result<Option<T::Response>, RequestError>
+    where
+        T: RequestHandler<R>,
+        T::Serializer: CanSerialize<R>,
+        T::Serializer: CanSerialize<T::Response>,
+        T::Serializer: CanSerialize<RequestMessage<R, T::Response, T::Serializer>>,
+    {
+        if !self.remote_inspect_alive() {
+            return Err(RequestError::ProcessDied);
+        }
+        match self.request_timeout(request, Some(deadline)) {
+            Ok(response) => Ok(Some(response)),
+            Err(RequestError::TimedOut | RequestError::DeadlineExceeded) => {
+                if self.remote_inspect_alive() {
+                    Ok(None)
+                } else {
+                    Err(RequestError::ProcessDied)
+                }
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Sends all `requests` to the process at once and waits on the
+    /// responses.
+    ///
+    /// The process handles the requests one after another as usual, but the
+    /// caller doesn't wait on a round trip per request. Responses are returned
+    /// in the same order as the requests.
+    #[track_caller]
+    pub fn batch_request<R: 'static>(
+        &self,
+        requests: Vec<R>,
+    ) -> Vec<Result<T::Response, RequestError>>
+    where
+        T: RequestHandler<R>,
+        T::Serializer: CanSerialize<R>,
+        T::Serializer: CanSerialize<T::Response>,
+        T::Serializer: CanSerialize<RequestMessage<R, T::Response, T::Serializer>>,
+    {
+        self.batch_request_timeout(requests, None)
+    }
+
+    /// Sends all `requests` to the process at once and waits on the
+    /// responses.
+    ///
+    /// The timeout is shared by all requests. Responses that don't arrive
+    /// before it expires are returned as `Err(RequestError::TimedOut)`.
+    #[track_caller]
+    pub fn batch_request_timeout<R: 'static>(
+        &self,
+        requests: Vec<R>,
+        timeout: Option<Duration>,
+    ) -> Vec<Result<T::Response, RequestError>>
+    where
+        T: RequestHandler<R>,
+        T::Serializer: CanSerialize<R>,
+        T::Serializer: CanSerialize<T::Response>,
+        T::Serializer: CanSerialize<RequestMessage<R, T::Response, T::Serializer>>,
+    {
+        self.assert_not_self();
+        let handler_id = T::Handlers::handler_id::<Request<R>>();
+        // Cast into the right type for sending.
+        let process: Process<RequestMessage<R, T::Response, T::Serializer>, T::Serializer> =
+            unsafe { mem::transmute(self.process) };
+        // Each request gets a unique tag, used to match the response.
+        let receive_tags: Vec<Tag> = requests
+            .into_iter()
+            .map(|request| {
+                let send_tag = AbstractProcessTag::from_u6(handler_id);
+                let (receive_tag, _) = AbstractProcessTag::extract_u6_data(send_tag);
+                let message = RequestMessage(request, ReturnAddress::from_self());
+                process.tag_send(send_tag, message);
+                receive_tag
+            })
+            .collect();
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mailbox: Mailbox<T::Response, T::Serializer> = unsafe { Mailbox::new() };
+        receive_tags
+            .into_iter()
+            .map(|tag| {
+                let response = match deadline {
+                    Some(deadline) => {
+                        let remaining = deadline.saturating_duration_since(Instant::now());
+                        mailbox.tag_receive_timeout(&[tag], remaining)
+                    }
+                    None => Ok(mailbox.tag_receive(&[tag])),
+                };
+                match response {
+                    Ok(response) => Ok(response),
+                    Err(MailboxError::TimedOut) => Err(RequestError::TimedOut),
+                    Err(_) => panic!(
+                        "Could not deserialize message: {}",
+                        type_name::<T::Response>()
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    /// Sends `request` to the process without waiting on the response.
+    ///
+    /// The returned call is resolved with [`PendingCall::resolve`] or
+    /// [`resolve_any`], which return the response together with `context`.
+    /// This allows sending several requests first and matching the responses,
+    /// arriving in any order, to the calls they belong to.
+    #[track_caller]
+    pub fn call_with_context<C, R: 'static>(
+        &self,
+        context: C,
+        request: R,
+    ) -> PendingCall<C, T::Response, T::Serializer>
+    where
+        T: RequestHandler<R>,
+        T::Serializer: CanSerialize<R>,
+        T::Serializer: CanSerialize<T::Response>,
+        T::Serializer: CanSerialize<RequestMessage<R, T::Response, T::Serializer>>,
+    {
+        self.assert_not_self();
         let handler_id = T::Handlers::handler_id::<Request<R>>();
         let send_tag = AbstractProcessTag::from_u6(handler_id);
         let (receive_tag, _) = AbstractProcessTag::extract_u6_data(send_tag);
-        unsafe {
-            // Cast into the right type for sending.
-            let process: Process<RequestMessage<R, T::Response, T::Serializer>, T::Serializer> =
-                mem::transmute(self.process);
-            match process.tag_send_receive(send_tag, receive_tag, message, timeout) {
-                MailboxResult::Ok(MessageSignal::Message(message)) => Ok(message),
-                MailboxResult::Err(MailboxError::TimedOut) => Err(Timeout),
-                _ => unreachable!("send_receive should panic in case of other errors"),
-            }
-        }
+        // Cast into the right type for sending.
+        let process: Process<RequestMessage<R, T::Response, T::Serializer>, T::Serializer> =
+            unsafe { mem::transmute(self.process) };
+        process.tag_send(
+            send_tag,
+            RequestMessage(request, ReturnAddress::from_self()),
+        );
+        PendingCall::new(context, receive_tag)
+    }
+
+    /// Makes a request to a handler returning a [`ResponseStream`] and returns
+    /// an iterator receiving the items.
+    #[track_caller]
+    pub fn request_stream<R: 'static>(&self, request: R) -> ResponseIter<T::Item, T::Serializer>
+    where
+        T: StreamRequestHandler<R>,
+        T::Serializer: CanSerialize<R>,
+        T::Serializer: CanSerialize<StreamItem<T::Item>>,
+        T::Serializer: CanSerialize<RequestMessage<R, StreamItem<T::Item>, T::Serializer>>,
+    {
+        self.request_stream_timeout(request, None)
+    }
+
+    /// Makes a request to a handler returning a [`ResponseStream`] and returns
+    /// an iterator receiving the items.
+    ///
+    /// If a timeout is specified, the iterator only waits for the timeout
+    /// period on each item before returning `Err(StreamError::TimedOut)`.
+    #[track_caller]
+    pub fn request_stream_timeout<R: 'static>(
+        &self,
+        request: R,
+        timeout: Option<Duration>,
+    ) -> ResponseIter<T::Item, T::Serializer>
+    where
+        T: StreamRequestHandler<R>,
+        T::Serializer: CanSerialize<R>,
+        T::Serializer: CanSerialize<StreamItem<T::Item>>,
+        T::Serializer: CanSerialize<RequestMessage<R, StreamItem<T::Item>, T::Serializer>>,
+    {
+        self.assert_not_self();
+        let handler_id = T::Handlers::handler_id::<StreamRequest<R>>();
+        let send_tag = AbstractProcessTag::from_u6(handler_id);
+        let (receive_tag, _) = AbstractProcessTag::extract_u6_data(send_tag);
+        // Cast into the right type for sending.
+        let process: Process<_, T::Serializer> = unsafe { mem::transmute(self.process) };
+        let return_address = ReturnAddress::<StreamItem<T::Item>, T::Serializer>::from_self();
+        process.tag_send(send_tag, RequestMessage(request, return_address));
+        ResponseIter::new(receive_tag, timeout)
     }
 
     /// Make a deferred request to the process.
@@ -564,40 +1138,208 @@ where
         T::Serializer: CanSerialize<T::Response>,
         T::Serializer: CanSerialize<RequestMessage<R, T::Response, T::Serializer>>,
     {
-        self.deferred_request_timeout(request, None).unwrap()
+        self.send_request::<R, DeferredRequest<R>, T::Response>(request, None, None)
+            .unwrap()
     }
 
     /// Make a deferred request to the process.
-    //
-    /// If a timeout is specified the function will only block for the timeout
-    /// period before returning `Err(Timeout)`.
+    ///
+    /// Timeouts and deadlines behave the same as in
+    /// [`request_timeout`](Self::request_timeout).
     #[track_caller]
-    pub(crate) fn deferred_request_timeout<R: 'static>(
+    pub fn deferred_request_timeout<R: 'static>(
         &self,
         request: R,
         timeout: Option<Duration>,
-    ) -> Result<T::Response, Timeout>
+    ) -> Result<T::Response, RequestError>
     where
         T: DeferredRequestHandler<R>,
         T::Serializer: CanSerialize<R>,
         T::Serializer: CanSerialize<T::Response>,
         T::Serializer: CanSerialize<RequestMessage<R, T::Response, T::Serializer>>,
     {
+        let deadline = Context::outgoing_deadline(timeout);
+        self.send_request::<R, DeferredRequest<R>, T::Response>(request, deadline, timeout)
+    }
+
+    /// Make a request to a [`ResponderRequestHandler`].
+    ///
+    /// Returns as soon as the handler responds, even if it keeps running.
+    #[track_caller]
+    pub fn responder_request<R: 'static>(&self, request: R) -> T::Response
+    where
+        T: ResponderRequestHandler<R>,
+        T::Serializer: CanSerialize<R>,
+        T::Serializer: CanSerialize<T::Response>,
+        T::Serializer: CanSerialize<RequestMessage<R, T::Response, T::Serializer>>,
+    {
+        self.send_request::<R, ResponderRequest<R>, T::Response>(request, None, None)
+            .unwrap()
+    }
+
+    /// Make a request to a [`ResponderRequestHandler`].
+    ///
+    /// Timeouts and deadlines behave the same as in
+    /// [`request_timeout`](Self::request_timeout).
+    #[track_caller]
+    pub fn responder_request_timeout<R: 'static>(
+        &self,
+        request: R,
+        timeout: Option<Duration>,
+    ) -> Result<T::Response, RequestError>
+    where
+        T: ResponderRequestHandler<R>,
+        T::Serializer: CanSerialize<R>,
+        T::Serializer: CanSerialize<T::Response>,
+        T::Serializer: CanSerialize<RequestMessage<R, T::Response, T::Serializer>>,
+    {
+        let deadline = Context::outgoing_deadline(timeout);
+        self.send_request::<R, ResponderRequest<R>, T::Response>(request, deadline, timeout)
+    }
+
+    /// Panics if this is a reference to the current process.
+    ///
+    /// The process would block waiting on a response that only it can
+    /// produce, so requests to self are rejected instead of deadlocking.
+    #[track_caller]
+    fn assert_not_self(&self) {
+        if self.process.id() == host::process_id() && self.process.node_id() == host::node_id() {
+            panic!(
+                "request to self would deadlock: `{}` can't wait on itself",
+                type_name::<T>()
+            );
+        }
+    }
+
+    /// Sends a request to the handler `H` and waits on the response.
+    ///
+    /// Requests without a deadline use the original envelope, so that they can
+    /// be handled by processes that don't know about deadlines.
+    #[track_caller]
+    fn send_request<R: 'static, H: 'static, Response>(
+        &self,
+        request: R,
+        deadline: Option<u64>,
+        timeout: Option<Duration>,
+    ) -> Result<Response, RequestError>
+    where
+        T::Serializer: CanSerialize<Response>,
+        T::Serializer: CanSerialize<RequestMessage<R, Response, T::Serializer>>,
+    {
+        self.assert_not_self();
         let return_address = ReturnAddress::from_self();
         let message = RequestMessage(request, return_address);
-        let handler_id = T::Handlers::handler_id::<DeferredRequest<R>>();
-        let send_tag = AbstractProcessTag::from_u6(handler_id);
-        let (receive_tag, _) = AbstractProcessTag::extract_u6_data(send_tag);
-        unsafe {
-            // Cast into the right type for sending.
-            let process: Process<RequestMessage<R, T::Response, T::Serializer>, T::Serializer> =
-                mem::transmute(self.process);
-            match process.tag_send_receive(send_tag, receive_tag, message, timeout) {
-                MailboxResult::Ok(MessageSignal::Message(message)) => Ok(message),
-                MailboxResult::Err(MailboxError::TimedOut) => Err(Timeout),
-                _ => unreachable!("send_receive should panic in case of other errors"),
+        let handler_id = T::Handlers::handler_id::<H>();
+
+        let deadline = match deadline {
+            Some(deadline) => deadline,
+            None => {
+                let send_tag = AbstractProcessTag::from_u6(handler_id);
+                let (receive_tag, _) = AbstractProcessTag::extract_u6_data(send_tag);
+                return unsafe {
+                    // Cast into the right type for sending.
+                    let process: Process<RequestMessage<R, Response, T::Serializer>, T::Serializer> =
+                        mem::transmute(self.process);
+                    match process.tag_send_receive(send_tag, receive_tag, message, timeout) {
+                        MailboxResult::Ok(MessageSignal::Message(message)) => Ok(message),
+                        MailboxResult::Err(MailboxError::TimedOut) => Err(RequestError::TimedOut),
+                        _ => unreachable!("send_receive should panic in case of other errors"),
+                    }
+                };
             }
+        };
+
+        let remaining = context::time_until(deadline);
+        if remaining.is_zero() {
+            return Err(RequestError::DeadlineExceeded);
         }
+        let send_tag = AbstractProcessTag::from_u6_with_deadline(handler_id);
+        let (receive_tag, _) = AbstractProcessTag::extract_u6_data(send_tag);
+        unsafe { host::api::message::create_data(send_tag.id(), 0) };
+        context::write_deadline(deadline);
+        <T::Serializer as CanSerialize<RequestMessage<R, Response, T::Serializer>>>::encode(
+            &message,
+        )
+        .unwrap();
+        let result = host::send_receive_skip_search(
+            self.process.node_id(),
+            self.process.id(),
+            receive_tag.id(),
+            remaining.as_millis() as u64,
+        );
+        if result == TIMEOUT {
+            return Err(RequestError::TimedOut);
+        }
+        let mut status = [0u8];
+        unsafe { host::api::message::read_data(status.as_mut_ptr(), status.len()) };
+        match status[0] {
+            RESPONSE_OK => match <T::Serializer as CanSerialize<Response>>::decode() {
+                Ok(response) => Ok(response),
+                Err(_) => panic!("Could not deserialize message: {}", type_name::<Response>()),
+            },
+            RESPONSE_HANDLER_REMOVED => Err(RequestError::HandlerRemoved),
+            RESPONSE_HANDLER_PANICKED => Err(RequestError::HandlerPanicked),
+            _ => Err(RequestError::DeadlineExceeded),
+        }
+    }
+
+    /// Replaces the state of the process with the result of `f`, called with
+    /// `capture` and the current state.
+    ///
+    /// `f` runs inside of the process between two handlers, so no other
+    /// message observes an intermediate state. The function returns once the
+    /// state was replaced. Like with [`spawn`](crate::Process::spawn), `f`
+    /// can't capture its environment and values it needs are passed as
+    /// `capture`. The process needs to run the same module as the caller. If
+    /// `f` panics, the process dies.
+    ///
+    /// ```ignore
+    /// counter.replace_state(10, |by, count| count * by)?;
+    /// ```
+    #[track_caller]
+    pub fn replace_state<C>(
+        &self,
+        capture: C,
+        f: fn(C, T::State) -> T::State,
+    ) -> Result<(), RequestError>
+    where
+        C: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        replace_state::replace_state(*self, capture, f, None)
+    }
+
+    /// Replaces the state of the process with the result of `f`.
+    ///
+    /// Timeouts and deadlines behave the same as in
+    /// [`request_timeout`](Self::request_timeout).
+    #[track_caller]
+    pub fn replace_state_timeout<C>(
+        &self,
+        capture: C,
+        f: fn(C, T::State) -> T::State,
+        timeout: Option<Duration>,
+    ) -> Result<(), RequestError>
+    where
+        C: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        replace_state::replace_state(*self, capture, f, timeout)
+    }
+
+    /// Forwards the output `M` of this process to `target`.
+    ///
+    /// Each message emitted with [`State::emit`] is sent to the
+    /// [`MessageHandler<M>`] of `target`. Both processes need to use the same
+    /// serializer. The relay is torn down when the returned [`PipeHandle`] is
+    /// dropped.
+    pub fn pipe_to<B, M: 'static>(&self, target: ProcessRef<B>) -> PipeHandle
+    where
+        T: Output<M>,
+        B: AbstractProcess<Serializer = T::Serializer>,
+        B: MessageHandler<M>,
+        T::Serializer: CanSerialize<M>,
+    {
+        let handler = B::Handlers::handler_id::<Message<M>>();
+        PipeHandle::connect::<M, _>(self.process, target.process, handler)
     }
 
     /// Set a timeout on the next action performed on this process.
@@ -630,6 +1372,10 @@ where
     }
 }
 
+/// A clone refers to the same process as the original, no new process is
+/// spawned. It only copies the `(node_id, process_id)` pair, the runtime
+/// doesn't count references and neither keeps the process alive.
+/// `ProcessRef` is also `Copy`, so cloning is never needed.
 impl<T> Clone for ProcessRef<T>
 where
     T: AbstractProcess,
@@ -652,6 +1398,41 @@ where
 
 impl<T> Eq for ProcessRef<T> where T: AbstractProcess {}
 
+impl<T> Hash for ProcessRef<T>
+where
+    T: AbstractProcess,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.process.hash(state);
+    }
+}
+
+impl<T> serde::Serialize for ProcessRef<T>
+where
+    T: AbstractProcess,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serde::Serialize::serialize(&self.global_id(), serializer)
+    }
+}
+
+impl<'de, T> serde::Deserialize<'de> for ProcessRef<T>
+where
+    T: AbstractProcess,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (node_id, process_id) = <(u64, u64)>::deserialize(deserializer)?;
+        // Safety: The pair was serialized from a `ProcessRef<T>`.
+        Ok(unsafe { ProcessRef::new(node_id, process_id) })
+    }
+}
+
 /// Result of [`AbstractProcess::start`].
 #[derive(serde::Serialize, serde::Deserialize)]
 pub enum StartupError<AP: AbstractProcess> {
@@ -710,3 +1491,62 @@ where
 }
 
 impl<AP: AbstractProcess> Eq for StartupError<AP> where AP::StartupError: Eq {}
+
+/// Error result of [`ProcessRef::request_timeout`] and
+/// [`ProcessRef::deferred_request_timeout`].
+#[derive(Error, Debug, Clone, Copy, Hash, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum RequestError {
+    /// No response arrived before the timeout expired.
+    #[error("timed out")]
+    TimedOut,
+    /// The process dropped the request, because the deadline passed before it
+    /// got to it.
+    #[error("deadline exceeded")]
+    DeadlineExceeded,
+    /// The process dropped the request, because the handler was removed and
+    /// only a `#[removed_handler]` marker is left in its place.
+    ///
+    /// Only requests sent with a deadline get this error. The others can't be
+    /// answered without knowing the response type, and never get a response.
+    #[error("handler removed")]
+    HandlerRemoved,
+    /// The handler panicked in a process that restarts on panic, see
+    /// [`AbstractProcess::restart_on_panic`].
+    ///
+    /// Only requests sent with a deadline get this error. The callers of the
+    /// others are killed instead, they can't be answered without a response.
+    #[error("handler panicked")]
+    HandlerPanicked,
+    /// The process isn't running, only returned by
+    /// [`ProcessRef::try_request`].
+    ///
+    /// For a process on another node its node is asked, see
+    /// [`ProcessRef::remote_inspect_alive`].
+    #[error("process died")]
+    ProcessDied,
+}
+
+/// Calls `attempt` until it succeeds, at most `retries` more times after the
+/// first failure, and returns the last result.
+///
+/// Waits `backoff` before the first retry and doubles the delay after each
+/// one. Used by request handlers marked with `retry(...)` in
+/// [`abstract_process`](crate::abstract_process).
+#[doc(hidden)]
+pub fn __retry_request<T, E>(
+    retries: u32,
+    backoff: Duration,
+    mut attempt: impl FnMut() -> Result<T, E>,
+) -> Result<T, E> {
+    let mut delay = backoff;
+    for _ in 0..retries {
+        if let Ok(response) = attempt() {
+            return Ok(response);
+        }
+        if !delay.is_zero() {
+            crate::sleep(delay);
+        }
+        delay = delay.saturating_mul(2);
+    }
+    attempt()
+}
@@ -0,0 +1,95 @@
+//! Handlers that stop half-way and continue once a message with a specific
+//! tag arrives.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use super::{AbstractProcess, ProcessRef, State};
+use crate::serializer::CanSerialize;
+use crate::{Process, Tag};
+
+crate::process_local! {
+    // Continuations waiting on a message, indexed by the tag they wait on.
+    static PARKED: RefCell<HashMap<Tag, Continuation>> = RefCell::new(HashMap::new());
+}
+
+/// Continuation of a parked handler, called with a pointer to the state of
+/// the process.
+type Continuation = Box<dyn FnOnce(*mut ())>;
+
+/// Parks the rest of a handler until a message with `tag` arrives.
+///
+/// Wasm can't suspend a running function, the rest of the handler is passed
+/// as `continuation` instead. `park` returns right away and the handler
+/// should return too. The process keeps handling other messages, and once a
+/// message with `tag` is sent to it with [`unpark`], `continuation` is called
+/// with the state of the process and the message. Local variables the
+/// continuation needs can be moved into the closure:
+///
+/// ```ignore
+/// #[handle_message]
+/// fn upload(&mut self, file: File) {
+///     let tag = Tag::new();
+///     self.storage.reserve(file.len(), this_ref::<Self>(), tag);
+///     park::<Self, _, _>(tag, move |mut state, reserved: Reserved| {
+///         state.uploads.push((reserved, file));
+///     });
+/// }
+/// ```
+///
+/// The tag should be created with [`Tag::new`], so that it doesn't collide
+/// with the tags used by the process for other messages. Parking twice on the
+/// same tag replaces the first continuation. Messages with `tag` that arrive
+/// while nothing is parked on it are dropped.
+///
+/// # Panics
+///
+/// Panics if the current process isn't running an `AbstractProcess` of type
+/// `AP`.
+#[track_caller]
+pub fn park<AP, M, F>(tag: Tag, continuation: F)
+where
+    AP: AbstractProcess + 'static,
+    AP::Serializer: CanSerialize<M>,
+    M: 'static,
+    F: FnOnce(State<AP>, M) + 'static,
+{
+    // The state passed to the continuation is only valid for the process type
+    // running in this process.
+    crate::process::this_ref::<AP>();
+    let continuation: Continuation = Box::new(move |state| {
+        // Safety: `resume` is called by the dispatch loop of this process with
+        // its state, which was checked to be the state of `AP`.
+        let state = unsafe { &mut *(state as *mut AP::State) };
+        let message = <AP::Serializer as CanSerialize<M>>::decode().unwrap();
+        continuation(State { state }, message);
+    });
+    PARKED.with(|parked| parked.borrow_mut().insert(tag, continuation));
+}
+
+/// Sends `message` with `tag` to `process`, resuming the continuation parked
+/// on `tag` with [`park`].
+pub fn unpark<T, M>(process: ProcessRef<T>, tag: Tag, message: M)
+where
+    T: AbstractProcess,
+    T::Serializer: CanSerialize<M>,
+{
+    let process: Process<M, T::Serializer> =
+        unsafe { Process::new(process.node_id(), process.id()) };
+    process.tag_send(tag, message);
+}
+
+/// Runs the continuation parked on `tag`.
+///
+/// Returns `false` if nothing is parked on `tag`.
+pub(crate) fn resume<AP: AbstractProcess>(tag: Tag, state: &mut AP::State) -> bool {
+    // The continuation can park again, don't keep the map borrowed.
+    let continuation = PARKED.with(|parked| parked.borrow_mut().remove(&tag));
+    match continuation {
+        Some(continuation) => {
+            continuation(state as *mut AP::State as *mut ());
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,108 @@
+//! Requests sent without waiting on the response, resolved later together
+//! with a caller-provided context.
+
+use std::any::type_name;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use super::RequestError;
+use crate::mailbox::MailboxError;
+use crate::serializer::{Bincode, CanSerialize};
+use crate::{host, Mailbox, Tag};
+
+/// A request sent with [`ProcessRef::call_with_context`], waiting on the
+/// response.
+///
+/// Several calls can be in flight at once. Their responses may arrive in any
+/// order, the context returned together with each response identifies the
+/// call it belongs to:
+///
+/// ```ignore
+/// let mut calls: Vec<_> = shards
+///     .iter()
+///     .enumerate()
+///     .map(|(index, shard)| shard.call_with_context(index, Count))
+///     .collect();
+/// while let Some((index, count)) = resolve_any(&mut calls, None) {
+///     counts[index] = count;
+/// }
+/// ```
+///
+/// Dropping a call doesn't cancel the request. The response still arrives
+/// and stays in the mailbox.
+///
+/// [`ProcessRef::call_with_context`]: super::ProcessRef::call_with_context
+#[must_use = "the response is only received when the call is resolved"]
+pub struct PendingCall<C, R, S = Bincode> {
+    context: C,
+    tag: Tag,
+    phantom: PhantomData<(R, S)>,
+}
+
+impl<C, R, S> PendingCall<C, R, S>
+where
+    S: CanSerialize<R>,
+{
+    pub(crate) fn new(context: C, tag: Tag) -> Self {
+        PendingCall {
+            context,
+            tag,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the context of the call.
+    pub fn context(&self) -> &C {
+        &self.context
+    }
+
+    /// Blocks until the response arrives and returns it together with the
+    /// context.
+    ///
+    /// If a timeout is specified the function only blocks for the timeout
+    /// period before returning `Err(RequestError::TimedOut)`.
+    pub fn resolve(self, timeout: Option<Duration>) -> Result<(C, R), RequestError> {
+        let mailbox: Mailbox<R, S> = unsafe { Mailbox::new() };
+        let response = match timeout {
+            Some(timeout) => mailbox.tag_receive_timeout(&[self.tag], timeout),
+            None => Ok(mailbox.tag_receive(&[self.tag])),
+        };
+        match response {
+            Ok(response) => Ok((self.context, response)),
+            Err(MailboxError::TimedOut) => Err(RequestError::TimedOut),
+            Err(_) => panic!("Could not deserialize message: {}", type_name::<R>()),
+        }
+    }
+}
+
+/// Blocks until the response to any of the `calls` arrives, removes the call
+/// from `calls` and returns the response together with its context.
+///
+/// Returns `None` if `calls` is empty, or if no response arrived before the
+/// timeout expired. The calls are left untouched then.
+pub fn resolve_any<C, R, S>(
+    calls: &mut Vec<PendingCall<C, R, S>>,
+    timeout: Option<Duration>,
+) -> Option<(C, R)>
+where
+    S: CanSerialize<R>,
+{
+    if calls.is_empty() {
+        return None;
+    }
+    let tags: Vec<Tag> = calls.iter().map(|call| call.tag).collect();
+    let mailbox: Mailbox<R, S> = unsafe { Mailbox::new() };
+    let response = match timeout {
+        Some(timeout) => mailbox.tag_receive_timeout(&tags, timeout),
+        None => Ok(mailbox.tag_receive(&tags)),
+    };
+    let response = match response {
+        Ok(response) => response,
+        Err(MailboxError::TimedOut) => return None,
+        Err(_) => panic!("Could not deserialize message: {}", type_name::<R>()),
+    };
+    // The tag of the received message identifies the call.
+    let tag = Tag::from(unsafe { host::api::message::get_tag() });
+    let index = calls.iter().position(|call| call.tag == tag).unwrap();
+    Some((calls.swap_remove(index).context, response))
+}
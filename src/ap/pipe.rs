@@ -0,0 +1,120 @@
+//! Forwarding of output messages between abstract processes.
+
+use std::any::type_name;
+use std::cell::{Cell, RefCell};
+
+use serde::{Deserialize, Serialize};
+
+use super::messages::PIPE_HANDLER;
+use super::tag::AbstractProcessTag;
+use super::AbstractProcess;
+use crate::serializer::{Bincode, CanSerialize};
+use crate::{host, Process};
+
+crate::process_local! {
+    // Pipes connected to outputs of the current process.
+    static PIPES: RefCell<Vec<Pipe>> = RefCell::new(Vec::new());
+    static NEXT_ID: Cell<u64> = Cell::new(0);
+}
+
+/// Marks `M` as an output of the abstract process.
+///
+/// Output messages are emitted with [`State::emit`](super::State::emit) and
+/// forwarded to all processes connected with
+/// [`ProcessRef::pipe_to`](super::ProcessRef::pipe_to).
+pub trait Output<M>: AbstractProcess
+where
+    Self::Serializer: CanSerialize<M>,
+{
+}
+
+/// A relay forwarding output messages of one abstract process to another.
+///
+/// The relay is torn down when the handle is dropped.
+#[must_use = "the pipe is torn down when the handle is dropped"]
+pub struct PipeHandle {
+    source: Process<()>,
+    id: u64,
+}
+
+impl PipeHandle {
+    /// Connects the output `M` of `source` to the message handler with id
+    /// `handler` of `target`.
+    pub(crate) fn connect<M, S>(
+        source: Process<(), S>,
+        target: Process<(), S>,
+        handler: u8,
+    ) -> Self {
+        let source = unsafe { Process::new(source.node_id(), source.id()) };
+        // Ids only need to be unique per source, combine the caller id with a
+        // local counter.
+        let id = (host::process_id() << 32) ^ NEXT_ID.with(|next| next.replace(next.get() + 1));
+        send_control(
+            source,
+            PipeMessage::Connect(Pipe {
+                id,
+                output: type_name::<M>().to_owned(),
+                node_id: target.node_id(),
+                process_id: target.id(),
+                handler,
+            }),
+        );
+        PipeHandle { source, id }
+    }
+}
+
+impl Drop for PipeHandle {
+    fn drop(&mut self) {
+        send_control(self.source, PipeMessage::Disconnect(self.id));
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Pipe {
+    id: u64,
+    output: String,
+    node_id: u64,
+    process_id: u64,
+    handler: u8,
+}
+
+/// Control message sent to the source process of a pipe.
+///
+/// It's always encoded with `Bincode`, independent of the serializer used by
+/// the abstract process.
+#[derive(Serialize, Deserialize)]
+enum PipeMessage {
+    Connect(Pipe),
+    Disconnect(u64),
+}
+
+fn send_control(source: Process<()>, message: PipeMessage) {
+    let tag = AbstractProcessTag::from_u6(PIPE_HANDLER);
+    unsafe { host::api::message::create_data(tag.id(), 0) };
+    <Bincode as CanSerialize<PipeMessage>>::encode(&message).unwrap();
+    host::send(source.node_id(), source.id());
+}
+
+/// Handles a control message received by the current process.
+pub(crate) fn handle_control() {
+    match <Bincode as CanSerialize<PipeMessage>>::decode() {
+        Ok(PipeMessage::Connect(pipe)) => PIPES.with(|pipes| pipes.borrow_mut().push(pipe)),
+        Ok(PipeMessage::Disconnect(id)) => {
+            PIPES.with(|pipes| pipes.borrow_mut().retain(|pipe| pipe.id != id))
+        }
+        Err(_) => (),
+    }
+}
+
+/// Forwards `message` to all processes connected to the output `M`.
+pub(crate) fn emit<M, S: CanSerialize<M>>(message: &M) {
+    let output = type_name::<M>();
+    PIPES.with(|pipes| {
+        for pipe in pipes.borrow().iter().filter(|pipe| pipe.output == output) {
+            let tag = AbstractProcessTag::from_u6(pipe.handler);
+            unsafe { host::api::message::create_data(tag.id(), 0) };
+            S::encode(message).unwrap();
+            host::send(pipe.node_id, pipe.process_id);
+        }
+    });
+}
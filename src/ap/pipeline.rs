@@ -0,0 +1,142 @@
+//! Chains of requests to other processes, executed by the dispatch loop of an
+//! [`AbstractProcess`] without blocking it between steps.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem;
+
+use super::handlers::{Handlers, Request};
+use super::messages::{RequestMessage, ReturnAddress};
+use super::tag::AbstractProcessTag;
+use super::{AbstractProcess, DeferredResponse, ProcessRef, RequestHandler};
+use crate::serializer::CanSerialize;
+use crate::{Process, Tag};
+
+crate::process_local! {
+    // Steps waiting on a reply, indexed by the tag the reply is sent with.
+    static WAITING: RefCell<HashMap<Tag, Box<dyn FnOnce()>>> = RefCell::new(HashMap::new());
+}
+
+/// A sequence of requests to other processes, producing a value of type `T`.
+///
+/// Each request is sent without waiting on the reply. The process keeps
+/// handling other messages, and the next step runs once the reply arrives.
+/// This replaces the bookkeeping of deferred responses when a request handler
+/// needs the results of several sub-requests, one after another:
+///
+/// ```ignore
+/// impl DeferredRequestHandler<Query> for Service {
+///     type Response = usize;
+///
+///     fn handle(state: State<Self>, query: Query, dr: DeferredResponse<usize, Self>) {
+///         let cache = state.cache;
+///         Pipeline::request(state.db, query)
+///             .then(move |rows| Pipeline::request(cache, Put(rows)))
+///             .respond_with(|stored| stored.len())
+///             .respond_to(dr);
+///     }
+/// }
+/// ```
+///
+/// Nothing is sent until the pipeline is started with
+/// [`respond_to`](Pipeline::respond_to). Steps don't have access to the state
+/// of the process, values they need have to be moved into the closures. If a
+/// process never replies, the rest of the pipeline never runs and the caller
+/// keeps waiting on the response.
+pub struct Pipeline<T> {
+    start: Box<dyn FnOnce(Next<T>)>,
+}
+
+/// Rest of the pipeline, called with the value of the current step.
+type Next<T> = Box<dyn FnOnce(T)>;
+
+impl<T: 'static> Pipeline<T> {
+    /// Returns a pipeline that finishes with `value` without sending any
+    /// request.
+    pub fn ready(value: T) -> Self {
+        Pipeline {
+            start: Box::new(move |next| next(value)),
+        }
+    }
+
+    /// Returns a pipeline that sends `request` to `process` and finishes with
+    /// the response.
+    pub fn request<P, R: 'static>(process: ProcessRef<P>, request: R) -> Self
+    where
+        P: RequestHandler<R, Response = T> + 'static,
+        P::Serializer: CanSerialize<R>,
+        P::Serializer: CanSerialize<T>,
+        P::Serializer: CanSerialize<RequestMessage<R, T, P::Serializer>>,
+    {
+        Pipeline {
+            start: Box::new(move |next| {
+                process.assert_not_self();
+                let handler_id = P::Handlers::handler_id::<Request<R>>();
+                let send_tag = AbstractProcessTag::from_u6(handler_id);
+                let (receive_tag, _) = AbstractProcessTag::extract_u6_data(send_tag);
+                // Register the next step first, the reply can't arrive before
+                // the dispatch loop reads the next message anyway.
+                let resume = move || {
+                    let response = <P::Serializer as CanSerialize<T>>::decode().unwrap();
+                    next(response);
+                };
+                WAITING.with(|waiting| waiting.borrow_mut().insert(receive_tag, Box::new(resume)));
+                // Cast into the right type for sending.
+                let target: Process<RequestMessage<R, T, P::Serializer>, P::Serializer> =
+                    unsafe { mem::transmute(process.process) };
+                target.tag_send(
+                    send_tag,
+                    RequestMessage(request, ReturnAddress::from_self()),
+                );
+            }),
+        }
+    }
+
+    /// Continues with the pipeline returned by `f`, once this one finished.
+    pub fn then<U: 'static, F>(self, f: F) -> Pipeline<U>
+    where
+        F: FnOnce(T) -> Pipeline<U> + 'static,
+    {
+        Pipeline {
+            start: Box::new(move |next| {
+                (self.start)(Box::new(move |value| (f(value).start)(next)))
+            }),
+        }
+    }
+
+    /// Computes the response from the value of the previous step.
+    pub fn respond_with<U: 'static, F>(self, f: F) -> Pipeline<U>
+    where
+        F: FnOnce(T) -> U + 'static,
+    {
+        Pipeline {
+            start: Box::new(move |next| (self.start)(Box::new(move |value| next(f(value))))),
+        }
+    }
+
+    /// Starts the pipeline and sends the value it finishes with as response
+    /// to a deferred request.
+    ///
+    /// Returns as soon as the first request is sent.
+    pub fn respond_to<AP>(self, deferred_response: DeferredResponse<T, AP>)
+    where
+        AP: AbstractProcess + 'static,
+        AP::Serializer: CanSerialize<T>,
+    {
+        (self.start)(Box::new(move |value| {
+            deferred_response.send_response(value)
+        }));
+    }
+}
+
+/// Runs the step of a pipeline waiting on the reply with `tag`.
+///
+/// Replies nobody is waiting on are dropped, e.g. late responses to requests
+/// that timed out.
+pub(crate) fn resume(tag: Tag) {
+    // The step can start the next request, don't keep the map borrowed.
+    let step = WAITING.with(|waiting| waiting.borrow_mut().remove(&tag));
+    if let Some(step) = step {
+        step();
+    }
+}
@@ -0,0 +1,128 @@
+//! Replacing the state of an abstract process from another process.
+//!
+//! Closures can't be serialized, so the caller sends a pointer to a function
+//! taking the state by value together with its serialized capture. Like
+//! spawned processes, the receiving process needs to run the same module to
+//! resolve the function pointer.
+
+use std::mem;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::context::{self, Context};
+use super::messages::{RequestMessage, ReturnAddress, REPLACE_STATE_HANDLER, RESPONSE_OK};
+use super::tag::AbstractProcessTag;
+use super::{AbstractProcess, ProcessRef, RequestError};
+use crate::mailbox::TIMEOUT;
+use crate::serializer::{Bincode, CanSerialize};
+use crate::{host, Tag};
+
+/// Request to replace the state.
+///
+/// It's always encoded with `Bincode`, independent of the serializer used by
+/// the abstract process.
+#[derive(Serialize, Deserialize)]
+struct ReplaceState {
+    /// Pointer to [`apply`], instantiated for the type of the capture.
+    apply: usize,
+    /// Pointer to the function computing the new state.
+    f: usize,
+    /// Capture passed to `f`, encoded with `Bincode`.
+    capture: Vec<u8>,
+}
+
+type Apply<State> = fn(&mut State, usize, &[u8]);
+
+/// Sends `f` with its `capture` to `process` and waits until the state was
+/// replaced.
+pub(crate) fn replace_state<T, C>(
+    process: ProcessRef<T>,
+    capture: C,
+    f: fn(C, T::State) -> T::State,
+    timeout: Option<Duration>,
+) -> Result<(), RequestError>
+where
+    T: AbstractProcess,
+    C: Serialize + DeserializeOwned,
+{
+    process.assert_not_self();
+    let request = ReplaceState {
+        apply: apply::<T, C> as Apply<T::State> as usize,
+        f: f as usize,
+        capture: bincode::serialize(&capture).unwrap(),
+    };
+    let message = RequestMessage(request, ReturnAddress::<(), T::Serializer>::from_self());
+
+    let deadline = Context::outgoing_deadline(timeout);
+    let send_tag = match deadline {
+        Some(_) => AbstractProcessTag::from_u6_with_deadline(REPLACE_STATE_HANDLER),
+        None => AbstractProcessTag::from_u6(REPLACE_STATE_HANDLER),
+    };
+    let (receive_tag, _) = AbstractProcessTag::extract_u6_data(send_tag);
+    let wait = match deadline {
+        Some(deadline) => {
+            let remaining = context::time_until(deadline);
+            if remaining.is_zero() {
+                return Err(RequestError::DeadlineExceeded);
+            }
+            remaining.as_millis() as u64
+        }
+        None => u64::MAX,
+    };
+    unsafe { host::api::message::create_data(send_tag.id(), 0) };
+    if let Some(deadline) = deadline {
+        context::write_deadline(deadline);
+    }
+    <Bincode as CanSerialize<RequestMessage<ReplaceState, (), T::Serializer>>>::encode(&message)
+        .unwrap();
+    let result =
+        host::send_receive_skip_search(process.node_id(), process.id(), receive_tag.id(), wait);
+    if result == TIMEOUT {
+        return Err(RequestError::TimedOut);
+    }
+    // Without a deadline the response is an empty `()`.
+    if deadline.is_none() {
+        return Ok(());
+    }
+    let mut status = [0u8];
+    unsafe { host::api::message::read_data(status.as_mut_ptr(), status.len()) };
+    match status[0] {
+        RESPONSE_OK => Ok(()),
+        _ => Err(RequestError::DeadlineExceeded),
+    }
+}
+
+/// Handles a request to replace the state, sent with `REPLACE_STATE_HANDLER`.
+pub(crate) fn handle<AP: AbstractProcess>(response_tag: Tag, state: &mut AP::State) {
+    let request: RequestMessage<ReplaceState, (), AP::Serializer> =
+        <Bincode as CanSerialize<_>>::decode().unwrap();
+    let RequestMessage(replace, return_address) = request;
+    if Context::deadline().is_some() && Context::is_expired() {
+        return_address.send_result(Err(RequestError::DeadlineExceeded), response_tag);
+        return;
+    }
+    // Safety: The pointer was created by `replace_state` for the same type of
+    // abstract process, in the same module.
+    let apply: Apply<AP::State> = unsafe { mem::transmute(replace.apply) };
+    apply(state, replace.f, &replace.capture);
+    if Context::deadline().is_some() {
+        return_address.send_result(Ok(()), response_tag);
+    } else {
+        return_address.send_response((), response_tag);
+    }
+}
+
+/// Decodes the capture and replaces the state with the result of `f`.
+fn apply<AP: AbstractProcess, C: DeserializeOwned>(
+    state: &mut AP::State,
+    f: usize,
+    capture: &[u8],
+) {
+    // Safety: The pointer was created by `replace_state` from a function with
+    // this signature.
+    let f: fn(C, AP::State) -> AP::State = unsafe { mem::transmute(f) };
+    let capture: C = bincode::deserialize(capture).unwrap();
+    super::State::<AP> { state }.replace_with(|state| f(capture, state));
+}
@@ -0,0 +1,147 @@
+//! Restarting an [`AbstractProcess`] in place after a handler panicked.
+//!
+//! The process keeps running and its references stay valid. Only the state is
+//! replaced, by calling `init` again with a copy of the original argument.
+
+use std::cell::Cell;
+use std::mem;
+use std::time::Duration;
+
+use super::messages::send_error;
+use super::{cleanup, crash_report, lifecycles, AbstractProcess, RequestError};
+use crate::{host, Tag};
+
+crate::process_local! {
+    // Caller of the request that is currently being handled.
+    static CALLER: Cell<Option<Caller>> = Cell::new(None);
+}
+
+/// Caller waiting on the response of the current request.
+#[derive(Clone, Copy)]
+struct Caller {
+    node_id: u64,
+    process_id: u64,
+    tag: Tag,
+    has_deadline: bool,
+}
+
+/// Remembers the caller of the request that is about to be handled.
+pub(crate) fn track_request(node_id: u64, process_id: u64, tag: Tag, has_deadline: bool) {
+    let caller = Caller {
+        node_id,
+        process_id,
+        tag,
+        has_deadline,
+    };
+    CALLER.with(|current| current.set(Some(caller)));
+}
+
+/// Forgets the caller after the handler returned, it was answered by the
+/// handler.
+pub(crate) fn untrack_request() {
+    CALLER.with(|current| current.set(None));
+}
+
+/// Answers the caller of the request whose handler panicked.
+///
+/// Callers that sent the request with a deadline get
+/// [`RequestError::HandlerPanicked`]. The others expect the response and
+/// can't be sent an error, they are killed instead of blocking forever.
+fn answer_panicked_request() {
+    let Some(caller) = CALLER.with(Cell::take) else {
+        return;
+    };
+    if caller.has_deadline {
+        send_error(
+            caller.node_id,
+            caller.process_id,
+            RequestError::HandlerPanicked,
+            caller.tag,
+        );
+    } else if caller.node_id == host::node_id() {
+        unsafe { host::api::process::kill(caller.process_id) };
+    } else {
+        crate::distributed::kill_process(caller.node_id, caller.process_id);
+    }
+}
+
+/// Policy for restarting an [`AbstractProcess`] after a handler panicked,
+/// returned by [`AbstractProcess::restart_on_panic`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RestartPolicy {
+    /// Maximum number of restarts, `None` for no limit. If a handler panics
+    /// after the last restart, the process dies.
+    pub max_restarts: Option<u32>,
+    /// Delay before `init` is called again.
+    pub delay: Duration,
+}
+
+/// Restarts of the current process.
+pub(crate) struct Restarts<AP: AbstractProcess> {
+    policy: RestartPolicy,
+    /// Copy of the `init` argument, used for the next restart.
+    arg: AP::Arg,
+    count: u32,
+}
+
+impl<AP: AbstractProcess> Restarts<AP> {
+    /// Returns `None` if `AP` doesn't restart on panic.
+    pub(crate) fn new(arg: &AP::Arg) -> Option<Self> {
+        let (policy, arg) = AP::restart_on_panic(arg)?;
+        // The panic message is logged on restart.
+        crash_report::install_hook();
+        Some(Restarts {
+            policy,
+            arg,
+            count: 0,
+        })
+    }
+
+    /// Replaces `state` with a new one after `handler_name` panicked.
+    ///
+    /// Returns `false` if the maximum number of restarts is reached, the
+    /// process should die then. The old state is dropped without calling
+    /// `terminate`, but the actions registered with `defer_on_terminate` run.
+    /// If the handler was handling a request, its caller is answered first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `init` fails.
+    pub(crate) fn restart(&mut self, state: &mut AP::State, handler_name: &str) -> bool {
+        answer_panicked_request();
+        if self
+            .policy
+            .max_restarts
+            .is_some_and(|max| self.count >= max)
+        {
+            return false;
+        }
+        self.count += 1;
+        crate::warning::emit(format!(
+            "`{}` panicked in `{handler_name}`: {}, restarting ({}{})",
+            std::any::type_name::<AP>(),
+            crash_report::panic_message().unwrap_or_default(),
+            self.count,
+            self.policy
+                .max_restarts
+                .map(|max| format!("/{max}"))
+                .unwrap_or_default(),
+        ));
+        cleanup::run();
+        if !self.policy.delay.is_zero() {
+            crate::sleep(self.policy.delay);
+        }
+        // Keep a copy of the argument for the next restart.
+        let arg = match AP::restart_on_panic(&self.arg) {
+            Some((_, next)) => mem::replace(&mut self.arg, next),
+            None => return false,
+        };
+        match lifecycles::startup::<AP>(arg) {
+            Ok(new_state) => {
+                *state = new_state;
+                true
+            }
+            Err(err) => panic!("`init` failed while restarting: {err:?}"),
+        }
+    }
+}
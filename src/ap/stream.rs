@@ -0,0 +1,157 @@
+//! Requests answered with a stream of responses.
+
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::messages::ReturnAddress;
+use crate::mailbox::{MailboxError, MessageSignal};
+use crate::serializer::{Bincode, CanSerialize};
+use crate::{Mailbox, Tag};
+
+/// Response of a request handler sending multiple items to the caller.
+///
+/// The items are sent one by one after the handler returns, each in its own
+/// message, followed by an end of stream marker. On the caller side they are
+/// received lazily with a [`ResponseIter`].
+///
+/// The iterator can't borrow the state of the process. If it panics, the
+/// process dies and the caller doesn't receive the end of the stream.
+///
+/// ```ignore
+/// #[handle_request]
+/// fn tail(&self, lines: usize) -> ResponseStream<String> {
+///     let start = self.log.len().saturating_sub(lines);
+///     ResponseStream::new(self.log[start..].to_vec())
+/// }
+/// ```
+pub struct ResponseStream<T> {
+    items: Box<dyn Iterator<Item = T>>,
+}
+
+impl<T> ResponseStream<T> {
+    /// Creates a stream sending the items of `items`.
+    pub fn new<I>(items: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: 'static,
+    {
+        ResponseStream {
+            items: Box::new(items.into_iter()),
+        }
+    }
+
+    /// Sends all items to the caller, followed by the end of the stream.
+    pub(crate) fn send<S>(self, return_address: ReturnAddress<StreamItem<T>, S>, tag: Tag)
+    where
+        S: CanSerialize<StreamItem<T>>,
+    {
+        for item in self.items {
+            return_address
+                .clone()
+                .send_response(StreamItem::Item(item), tag);
+        }
+        return_address.send_response(StreamItem::EndOfStream, tag);
+    }
+}
+
+impl<T: 'static> FromIterator<T> for ResponseStream<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        ResponseStream::new(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+/// Message sent for each item of a [`ResponseStream`].
+#[doc(hidden)]
+#[derive(Serialize, Deserialize)]
+pub enum StreamItem<T> {
+    Item(T),
+    EndOfStream,
+}
+
+/// Error returned by a [`ResponseIter`] instead of the next item.
+///
+/// The iterator ends after the error.
+#[derive(Error, Debug)]
+pub enum StreamError {
+    /// The next item didn't arrive before the timeout expired.
+    #[error("timed out")]
+    TimedOut,
+    /// The next item couldn't be received.
+    #[error("receiving the next item failed: {0}")]
+    Receive(MailboxError),
+}
+
+/// Items of a [`ResponseStream`], received from the process that handled the
+/// request.
+///
+/// Each call to `next` blocks until the next item arrives. Items that are
+/// not received before the iterator is dropped stay in the mailbox.
+pub struct ResponseIter<T, S = Bincode> {
+    /// Tag of the item messages, `None` after the end of the stream.
+    tag: Option<Tag>,
+    /// Time to wait for each item.
+    timeout: Option<Duration>,
+    /// Items that were already received.
+    received: VecDeque<T>,
+    phantom: PhantomData<S>,
+}
+
+impl<T, S> ResponseIter<T, S> {
+    pub(crate) fn new(tag: Tag, timeout: Option<Duration>) -> Self {
+        ResponseIter {
+            tag: Some(tag),
+            timeout,
+            received: VecDeque::new(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T, S> Iterator for ResponseIter<T, S>
+where
+    S: CanSerialize<StreamItem<T>>,
+{
+    type Item = Result<T, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(item) = self.received.pop_front() {
+            return Some(Ok(item));
+        }
+        let tag = self.tag?;
+        let mailbox: Mailbox<StreamItem<T>, S> = unsafe { Mailbox::new() };
+        let result = mailbox
+            .receive_(&[tag], self.timeout)
+            .map(MessageSignal::unwrap_message);
+        match result {
+            Ok(StreamItem::Item(item)) => Some(Ok(item)),
+            Ok(StreamItem::EndOfStream) => {
+                self.tag = None;
+                None
+            }
+            Err(err) => {
+                self.tag = None;
+                match err {
+                    MailboxError::TimedOut => Some(Err(StreamError::TimedOut)),
+                    err => Some(Err(StreamError::Receive(err))),
+                }
+            }
+        }
+    }
+}
+
+/// Creates an iterator over items that were already received, e.g. to return
+/// it from a mock.
+impl<T, S> FromIterator<T> for ResponseIter<T, S> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        ResponseIter {
+            tag: None,
+            timeout: None,
+            received: iter.into_iter().collect(),
+            phantom: PhantomData,
+        }
+    }
+}
@@ -1,13 +1,28 @@
+use super::handlers::Handlers;
+use super::AbstractProcess;
 use crate::Tag;
 
 /// Unique tags that also hold additional `u6` data used to dispatch to the
 /// correct handler function.
 ///
 /// The reason only `u6` is used is that the first 2 bits are reserved for
-/// future use cases. `AbstractProcesses` can have at most 16 handler functions
-/// and this should be enough space to encode all of them.
+/// flags describing the message envelope. `AbstractProcesses` can have at most
+/// 16 handler functions and this should be enough space to encode all of them.
 pub(crate) struct AbstractProcessTag;
 
+/// Flag set on requests that carry a deadline in front of the serialized
+/// message.
+///
+/// Older clients never set this bit, so their messages keep the original
+/// envelope.
+const DEADLINE_FLAG: u8 = 0b0100_0000;
+
+/// Lower 56 bits of the tags of messages with a priority.
+///
+/// [`Tag::new`] wraps around before it reaches this value, so it doesn't
+/// collide with the tags of other messages.
+const PRIORITY_TAG: i64 = 0xFF_FFFF_FFFF_FFFF;
+
 impl AbstractProcessTag {
     /// Returns a [`Tag`] with `u6` data encoded into it.
     #[track_caller]
@@ -18,12 +33,44 @@ impl AbstractProcessTag {
         Tag::from(id)
     }
 
+    /// Returns a [`Tag`] with `u6` data encoded into it and the deadline flag
+    /// set.
+    #[track_caller]
+    pub(crate) fn from_u6_with_deadline(data: u8) -> Tag {
+        let tag = Self::from_u6(data);
+        Tag::from(((DEADLINE_FLAG as i64) << 56) | tag.id())
+    }
+
+    /// Returns the [`Tag`] of a message to the handler `data` of `AP`.
+    ///
+    /// Messages to handlers with a priority don't need a unique tag, they are
+    /// sent with a fixed tag per handler that the process can filter on.
+    #[track_caller]
+    pub(crate) fn message<AP: AbstractProcess>(data: u8) -> Tag {
+        if AP::Handlers::priority(data) == 0 {
+            Self::from_u6(data)
+        } else {
+            Self::priority(data)
+        }
+    }
+
+    /// Returns the fixed [`Tag`] of messages with a priority to the handler
+    /// `data`.
+    pub(crate) fn priority(data: u8) -> Tag {
+        Tag::from(((data as i64) << 56) | PRIORITY_TAG)
+    }
+
     /// Extracts `u6` data encoded into the [`Tag`].
     ///
     /// The returned `Tag` doesn't contain the data anymore.
     pub(crate) fn extract_u6_data(tag: Tag) -> (Tag, u8) {
-        let data = (tag.id() >> 56) as u8; // extract data
+        let data = (tag.id() >> 56) as u8 & 0b0011_1111; // extract data
         let tag = tag.id() & 0xFFFFFFFFFFFFFF; // remove data from first byte
         (Tag::from(tag), data)
     }
+
+    /// Returns `true` if the message was sent with a deadline.
+    pub(crate) fn has_deadline(tag: Tag) -> bool {
+        (tag.id() >> 56) as u8 & DEADLINE_FLAG != 0
+    }
 }
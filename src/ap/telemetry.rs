@@ -0,0 +1,97 @@
+//! Trace propagation for processes declared with
+//! `#[abstract_process(telemetry = "opentelemetry")]`.
+//!
+//! Each message carries the W3C `traceparent` and `tracestate` headers of the
+//! span that was current when it was sent, and its handler runs in a child
+//! span of it, started with the global tracer.
+
+use std::cell::RefCell;
+use std::str::FromStr;
+
+use opentelemetry::trace::{SpanContext, TraceContextExt, TraceState, Tracer};
+use opentelemetry::{global, Context, ContextGuard, SpanId, TraceFlags, TraceId};
+use serde::{Deserialize, Serialize};
+
+crate::process_local! {
+    // Headers of the message that is handled next.
+    static RECEIVED: RefCell<Option<TraceHeaders>> = RefCell::new(None);
+}
+
+/// The trace context sent with a message.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceHeaders {
+    pub traceparent: Option<String>,
+    pub tracestate: Option<String>,
+}
+
+impl TraceHeaders {
+    /// Returns the headers of the current span, or empty headers if there
+    /// isn't a valid one.
+    pub fn current() -> Self {
+        let context = Context::current();
+        let span = context.span();
+        let span_context = span.span_context();
+        if !span_context.is_valid() {
+            return TraceHeaders::default();
+        }
+        let traceparent = format!(
+            "00-{}-{}-{:02x}",
+            span_context.trace_id(),
+            span_context.span_id(),
+            span_context.trace_flags()
+        );
+        let tracestate = span_context.trace_state().header();
+        TraceHeaders {
+            traceparent: Some(traceparent),
+            tracestate: (!tracestate.is_empty()).then_some(tracestate),
+        }
+    }
+
+    /// Returns the remote span context of the headers, `None` if they are
+    /// missing or malformed.
+    pub fn span_context(&self) -> Option<SpanContext> {
+        let mut parts = self.traceparent.as_deref()?.split('-');
+        let (version, trace_id, span_id, flags) =
+            (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        if version != "00"
+            || parts.next().is_some()
+            || trace_id.len() != 32
+            || span_id.len() != 16
+            || flags.len() != 2
+        {
+            return None;
+        }
+        let trace_state = match &self.tracestate {
+            Some(tracestate) => TraceState::from_str(tracestate).ok()?,
+            None => TraceState::default(),
+        };
+        let span_context = SpanContext::new(
+            TraceId::from_hex(trace_id).ok()?,
+            SpanId::from_hex(span_id).ok()?,
+            TraceFlags::new(u8::from_str_radix(flags, 16).ok()?),
+            true,
+            trace_state,
+        );
+        span_context.is_valid().then_some(span_context)
+    }
+
+    /// Keeps the headers of a decoded message for the span of its handler.
+    pub fn receive(self) {
+        RECEIVED.with(|received| *received.borrow_mut() = Some(self));
+    }
+}
+
+/// Starts the span of the handler `name` as a child of the received headers,
+/// or as a new trace without them. The span stays current until the guard is
+/// dropped.
+pub fn __handler_span(name: &'static str) -> ContextGuard {
+    let parent = match RECEIVED
+        .with(|received| received.borrow_mut().take())
+        .and_then(|headers| headers.span_context())
+    {
+        Some(span_context) => Context::new().with_remote_span_context(span_context),
+        None => Context::new(),
+    };
+    let span = global::tracer("lunatic").start_with_context(name, &parent);
+    parent.with_span(span).attach()
+}
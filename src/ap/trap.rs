@@ -0,0 +1,112 @@
+//! Information about linked processes that died.
+
+use std::mem;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Tag;
+
+/// Passed to [`AbstractProcess::handle_link_death`] when a linked process
+/// fails.
+///
+/// The runtime only notifies links about failures and doesn't tell why the
+/// process failed, so the link is only identified by its tag.
+///
+/// [`AbstractProcess::handle_link_death`]: super::AbstractProcess::handle_link_death
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrapInfo {
+    /// Tag used when the link was created.
+    pub tag: Tag,
+}
+
+/// Reason a process exited.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExitReason {
+    /// The process finished without an error.
+    Normal,
+    /// A handler or `terminate` of the abstract process panicked.
+    Panicked {
+        /// Message the process panicked with.
+        message: String,
+    },
+    /// The process failed without reporting why, e.g. it was killed.
+    Unknown,
+}
+
+impl ExitReason {
+    /// Returns `true` if the process didn't finish normally.
+    ///
+    /// Supervisors only restart children that exited with a failure. An
+    /// [`Unknown`](ExitReason::Unknown) reason counts as a failure.
+    pub fn is_failure(&self) -> bool {
+        !matches!(self, ExitReason::Normal)
+    }
+}
+
+/// Sends the exit `reason` of a process to the process with the node and
+/// process id, naming the process by the tag.
+pub(crate) type ReportExitFn = fn(u64, u64, Tag, ExitReason);
+
+/// Tells an abstract process whom to report its exit to, see
+/// [`AbstractProcessBuilder::notify_exit`](super::AbstractProcessBuilder).
+///
+/// Links only report failures, so a process that exits normally sends this
+/// report itself right before it exits.
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ExitNotice {
+    /// Pointer to a [`ReportExitFn`].
+    report: usize,
+    node_id: u64,
+    process_id: u64,
+    tag: Tag,
+}
+
+impl ExitNotice {
+    pub(crate) fn new(report: ReportExitFn, node_id: u64, process_id: u64, tag: Tag) -> Self {
+        ExitNotice {
+            report: report as usize,
+            node_id,
+            process_id,
+            tag,
+        }
+    }
+
+    /// Reports the exit with `reason`.
+    pub(crate) fn send(self, reason: ExitReason) {
+        // Safety: The pointer was created from a `ReportExitFn` in `new`, and
+        // processes reporting their exit run the same module.
+        let report: ReportExitFn = unsafe { mem::transmute(self.report) };
+        report(self.node_id, self.process_id, self.tag, reason);
+    }
+}
+
+impl From<TrapInfo> for Tag {
+    fn from(info: TrapInfo) -> Self {
+        info.tag
+    }
+}
+
+/// Argument of a `#[handle_link_death]` method in an
+/// [`abstract_process`](crate::abstract_process).
+#[doc(hidden)]
+#[diagnostic::on_unimplemented(
+    message = "`#[handle_link_death]` methods can't take `{Self}`",
+    label = "expected `TrapInfo` or `Tag`",
+    note = "change the type of the argument to `lunatic::ap::TrapInfo` or `lunatic::Tag`"
+)]
+pub trait LinkDeathArg {
+    fn from_trap_info(info: TrapInfo) -> Self;
+}
+
+impl LinkDeathArg for TrapInfo {
+    fn from_trap_info(info: TrapInfo) -> Self {
+        info
+    }
+}
+
+impl LinkDeathArg for Tag {
+    fn from_trap_info(info: TrapInfo) -> Self {
+        info.tag
+    }
+}
@@ -0,0 +1,134 @@
+//! Utilities for process-level micro-benchmarks.
+//!
+//! Benchmark harnesses like Criterion only measure code inside of a single
+//! process. [`round_trip_latency`] measures the time it takes a request to
+//! reach another process and the response to come back.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use lunatic::bench::{round_trip_latency, Echo};
+//!
+//! let stats = round_trip_latency::<Echo>(10_000);
+//! println!("{stats}");
+//! ```
+//!
+//! Inside of a Criterion benchmark the [`Echo`] process can be used directly:
+//!
+//! ```ignore
+//! let echo = Echo::link().start(()).unwrap();
+//! c.bench_function("round trip", |b| b.iter(|| echo.request(Ping)));
+//! ```
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ap::handlers::Request;
+use crate::ap::{AbstractProcess, Config, RequestHandler, State};
+use crate::serializer::Bincode;
+
+/// Latency statistics of a benchmark, in nanoseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stats {
+    /// Average of all samples.
+    pub mean_ns: u64,
+    /// Median.
+    pub p50_ns: u64,
+    /// 99th percentile.
+    pub p99_ns: u64,
+    /// 99.9th percentile.
+    pub p999_ns: u64,
+}
+
+impl Stats {
+    /// Calculates the statistics of the measured `samples`.
+    ///
+    /// Returns all zeros if there are no samples.
+    pub fn from_samples(samples: &[Duration]) -> Self {
+        if samples.is_empty() {
+            return Stats {
+                mean_ns: 0,
+                p50_ns: 0,
+                p99_ns: 0,
+                p999_ns: 0,
+            };
+        }
+        let mut samples: Vec<u64> = samples
+            .iter()
+            .map(|sample| sample.as_nanos() as u64)
+            .collect();
+        samples.sort_unstable();
+        let percentile = |p: f64| samples[((samples.len() - 1) as f64 * p).round() as usize];
+        Stats {
+            mean_ns: samples.iter().sum::<u64>() / samples.len() as u64,
+            p50_ns: percentile(0.5),
+            p99_ns: percentile(0.99),
+            p999_ns: percentile(0.999),
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "mean: {}ns, p50: {}ns, p99: {}ns, p99.9: {}ns",
+            self.mean_ns, self.p50_ns, self.p99_ns, self.p999_ns
+        )
+    }
+}
+
+/// Request sent by [`round_trip_latency`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Ping;
+
+/// `AbstractProcess` that answers each [`Ping`] immediately.
+pub struct Echo;
+
+impl AbstractProcess for Echo {
+    type State = ();
+    type Serializer = Bincode;
+    type Arg = ();
+    type Handlers = (Request<Ping>,);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, _: ()) -> Result<(), ()> {
+        Ok(())
+    }
+}
+
+impl RequestHandler<Ping> for Echo {
+    type Response = ();
+
+    fn handle(_: State<Self>, _: Ping) {}
+}
+
+/// Measures the round trip latency of requests to the process `T`.
+///
+/// `T` needs to answer [`Ping`] requests with `()`.
+///
+/// A new `T` is started with its default argument, `iterations` [`Ping`]
+/// requests are sent to it from the current process and the time of each
+/// round trip is recorded. The process is shut down afterwards.
+///
+/// Use [`Echo`] to measure the overhead of message passing alone.
+pub fn round_trip_latency<T>(iterations: u64) -> Stats
+where
+    T: AbstractProcess<Serializer = Bincode> + RequestHandler<Ping, Response = ()>,
+    T::Arg: Default,
+{
+    let process = T::link()
+        .start(T::Arg::default())
+        .expect("benchmarked process failed to start");
+    let samples: Vec<Duration> = (0..iterations)
+        .map(|_| {
+            let start = Instant::now();
+            process.request(Ping);
+            start.elapsed()
+        })
+        .collect();
+    process.shutdown();
+    Stats::from_samples(&samples)
+}
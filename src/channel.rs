@@ -0,0 +1,147 @@
+//! Typed channels between processes.
+//!
+//! A [`oneshot`] channel carries a single value back to the process that
+//! created it, replacing the pattern of sending a [`Tag`] with a request and
+//! waiting for a message with that tag.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use lunatic::channel::oneshot;
+//! use lunatic::{spawn_link, Mailbox};
+//!
+//! #[lunatic::main]
+//! fn main(_: Mailbox<()>) {
+//!     let (sender, receiver) = oneshot::<u32>();
+//!     spawn_link!(|sender| sender.send(42));
+//!     assert_eq!(receiver.recv_timeout(Duration::from_secs(1)).unwrap(), 42);
+//! }
+//! ```
+
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::serializer::DecodeError;
+use crate::{host, Mailbox, MailboxError, MessageSignal, Process, Tag};
+
+/// Creates a channel for sending a single value to the current process.
+///
+/// The sender can be included in messages and passed to other processes. The
+/// receiver can only be used by the current process.
+pub fn oneshot<T>() -> (OneshotSender<T>, OneshotReceiver<T>)
+where
+    T: Serialize + DeserializeOwned,
+{
+    let tag = Tag::new();
+    // Safety: Only values of type `T` are sent with `tag`.
+    let process = unsafe { Process::this() };
+    (
+        OneshotSender { process, tag },
+        OneshotReceiver { process, tag },
+    )
+}
+
+/// Sending half of a [`oneshot`] channel.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct OneshotSender<T> {
+    process: Process<T>,
+    tag: Tag,
+}
+
+impl<T> OneshotSender<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Sends `value` to the receiver.
+    ///
+    /// The value is delivered even if the receiver stopped waiting for it
+    /// after a timeout, as long as its process is alive.
+    pub fn send(self, value: T) {
+        self.process.tag_send(self.tag, value);
+    }
+}
+
+/// Receiving half of a [`oneshot`] channel.
+pub struct OneshotReceiver<T> {
+    process: Process<T>,
+    tag: Tag,
+}
+
+impl<T> OneshotReceiver<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Waits for the value.
+    ///
+    /// Other messages stay in the mailbox. If the sender is dropped without
+    /// sending a value, this waits forever.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from a different process than the one that created
+    /// the channel.
+    #[track_caller]
+    pub fn recv(self) -> Result<T, RecvError> {
+        self.receive(None)
+    }
+
+    /// Waits for the value for the duration of `timeout`.
+    ///
+    /// Returns [`RecvError::TimedOut`] if no value arrived in time, waiting
+    /// can be continued with another call.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from a different process than the one that created
+    /// the channel.
+    #[track_caller]
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvError> {
+        self.receive(Some(timeout))
+    }
+
+    #[track_caller]
+    fn receive(&self, timeout: Option<Duration>) -> Result<T, RecvError> {
+        assert_eq!(
+            self.process.id(),
+            host::process_id(),
+            "a oneshot receiver can only be used by the process that created it"
+        );
+        // Safety: Only values of type `T` are received, because of the tag.
+        let mailbox = unsafe { Mailbox::<T>::new() };
+        mailbox
+            .receive_(&[self.tag], timeout)
+            .map(MessageSignal::unwrap_message)
+            .map_err(RecvError::from)
+    }
+}
+
+/// An error returned when receiving from a [`OneshotReceiver`].
+#[derive(Error, Debug)]
+pub enum RecvError {
+    /// No value arrived before the timeout.
+    #[error("timed out")]
+    TimedOut,
+    /// The value failed to be deserialized.
+    #[error("deserialization failed: {0}")]
+    DeserializationFailed(DecodeError),
+    /// The process was asked to exit with
+    /// [`Process::kill_gracefully`](crate::Process::kill_gracefully).
+    #[error("process killed")]
+    ProcessKilled,
+}
+
+impl From<MailboxError> for RecvError {
+    fn from(err: MailboxError) -> Self {
+        match err {
+            MailboxError::TimedOut => RecvError::TimedOut,
+            MailboxError::DeserializationFailed(err) => RecvError::DeserializationFailed(err),
+            MailboxError::ProcessKilled => RecvError::ProcessKilled,
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Framing of byte streams into messages.
+//!
+//! A [`Framed`] stream sends each frame prefixed with its length as a 4 byte
+//! big-endian integer, so that the receiver can read exactly one frame at a
+//! time. It's the base for implementing binary protocols on top of a
+//! [`TcpStream`](crate::net::TcpStream).
+//!
+//! # Example
+//!
+//! ```no_run
+//! use lunatic::codec::LengthDelimitedCodec;
+//! use lunatic::net::TcpStream;
+//!
+//! let stream = TcpStream::connect("127.0.0.1:8080").unwrap();
+//! let mut framed = LengthDelimitedCodec::new(stream);
+//! framed.write_frame(b"ping").unwrap();
+//! let pong = framed.read_frame().unwrap();
+//! ```
+
+use std::io::{self, ErrorKind, Read, Write};
+#[cfg(feature = "json_serializer")]
+use std::marker::PhantomData;
+
+#[cfg(feature = "json_serializer")]
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Default limit for the length of a frame, 8 MiB.
+const DEFAULT_MAX_FRAME_LENGTH: usize = 8 * 1024 * 1024;
+
+/// Codec framing a stream with a length prefix in front of each frame.
+pub struct LengthDelimitedCodec;
+
+impl LengthDelimitedCodec {
+    /// Wraps `stream` into a [`Framed`] stream.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<T: Read + Write>(stream: T) -> Framed<T> {
+        Framed {
+            inner: stream,
+            max_frame_length: DEFAULT_MAX_FRAME_LENGTH,
+        }
+    }
+}
+
+/// A stream reading and writing length-prefixed frames, created with
+/// [`LengthDelimitedCodec::new`].
+///
+/// Like the stream it wraps, it can be sent to another process.
+#[derive(Serialize, Deserialize)]
+pub struct Framed<T> {
+    inner: T,
+    max_frame_length: usize,
+}
+
+impl<T: Read + Write> Framed<T> {
+    /// Reads exactly one frame.
+    ///
+    /// Returns an error of kind [`UnexpectedEof`](ErrorKind::UnexpectedEof)
+    /// if the stream ends before the frame is complete, and of kind
+    /// [`InvalidData`](ErrorKind::InvalidData) if the frame is longer than
+    /// the maximum frame length. In both cases the stream can't be used to
+    /// read further frames.
+    pub fn read_frame(&mut self) -> io::Result<Vec<u8>> {
+        let mut length = [0; 4];
+        self.inner.read_exact(&mut length)?;
+        let length = u32::from_be_bytes(length) as usize;
+        if length > self.max_frame_length {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "frame of {length} bytes exceeds the maximum of {} bytes",
+                    self.max_frame_length
+                ),
+            ));
+        }
+        let mut frame = vec![0; length];
+        self.inner.read_exact(&mut frame)?;
+        Ok(frame)
+    }
+
+    /// Writes `bytes` as one frame.
+    ///
+    /// Returns an error of kind [`InvalidInput`](ErrorKind::InvalidInput) if
+    /// `bytes` is longer than the maximum frame length.
+    pub fn write_frame(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if bytes.len() > self.max_frame_length {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "frame of {} bytes exceeds the maximum of {} bytes",
+                    bytes.len(),
+                    self.max_frame_length
+                ),
+            ));
+        }
+        let mut buf = Vec::with_capacity(4 + bytes.len());
+        buf.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        buf.extend_from_slice(bytes);
+        self.inner.write_all(&buf)?;
+        self.inner.flush()
+    }
+}
+
+impl<T> Framed<T> {
+    /// Limits the length of frames, 8 MiB by default.
+    ///
+    /// Longer frames are rejected before their content is read, the limit
+    /// can't be raised above `u32::MAX`.
+    pub fn set_max_frame_length(&mut self, length: usize) {
+        self.max_frame_length = length.min(u32::MAX as usize);
+    }
+
+    pub fn max_frame_length(&self) -> usize {
+        self.max_frame_length
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying stream.
+    ///
+    /// Reading or writing directly on the stream corrupts the framing.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Returns the underlying stream.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+/// Codec sending each message of type `M` as a JSON encoded frame.
+#[cfg(feature = "json_serializer")]
+#[cfg_attr(docsrs, doc(cfg(feature = "json_serializer")))]
+#[derive(Serialize, Deserialize)]
+#[serde(bound(serialize = "T: Serialize", deserialize = "T: Deserialize<'de>"))]
+pub struct JsonCodec<M, T = crate::net::TcpStream> {
+    framed: Framed<T>,
+    phantom: PhantomData<M>,
+}
+
+#[cfg(feature = "json_serializer")]
+impl<M, T> JsonCodec<M, T>
+where
+    M: Serialize + DeserializeOwned,
+    T: Read + Write,
+{
+    /// Wraps `stream` into a length-prefixed stream of JSON messages.
+    pub fn new(stream: T) -> Self {
+        JsonCodec {
+            framed: LengthDelimitedCodec::new(stream),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Reads the next frame and deserializes it.
+    ///
+    /// A frame that isn't valid JSON for `M` results in an error of kind
+    /// [`InvalidData`](ErrorKind::InvalidData). The frame is consumed, so the
+    /// next message can still be read.
+    pub fn read(&mut self) -> io::Result<M> {
+        let frame = self.framed.read_frame()?;
+        serde_json::from_slice(&frame).map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
+    }
+
+    /// Serializes `message` and writes it as one frame.
+    pub fn write(&mut self, message: &M) -> io::Result<()> {
+        let frame = serde_json::to_vec(message)
+            .map_err(|err| io::Error::new(ErrorKind::InvalidInput, err))?;
+        self.framed.write_frame(&frame)
+    }
+
+    /// Returns the framed stream.
+    pub fn into_framed(self) -> Framed<T> {
+        self.framed
+    }
+}
+
+#[cfg(feature = "json_serializer")]
+impl<M, T> JsonCodec<M, T> {
+    /// Returns a mutable reference to the framed stream, e.g. to change the
+    /// maximum frame length.
+    pub fn framed_mut(&mut self) -> &mut Framed<T> {
+        &mut self.framed
+    }
+}
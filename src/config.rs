@@ -1,10 +1,10 @@
-use crate::{host, LunaticError};
+use crate::{host, LunaticError, WasmModule};
 
 /// Process configurations determine permissions of processes.
 ///
 /// The functions `spawn_config` & `spawn_link_config` can be used to create
 /// processes with a specific configuration.
-pub struct ProcessConfig(ProcessConfigType);
+pub struct ProcessConfig(ProcessConfigType, Option<WasmModule>);
 
 enum ProcessConfigType {
     /// ID of a configuration held by the host as a resource.
@@ -33,6 +33,7 @@ impl std::fmt::Debug for ProcessConfig {
                 .field("can_compile_modules", &self.can_compile_modules())
                 .field("can_create_configs", &self.can_create_configs())
                 .field("can_spawn_processes", &self.can_spawn_processes())
+                .field("has_wasm_module", &self.1.is_some())
                 .finish(),
             ProcessConfigType::Inherit => f.debug_struct("ProcessConfig::Inherit").finish(),
         }
@@ -47,12 +48,12 @@ impl ProcessConfig {
     pub fn new() -> Result<Self, LunaticError> {
         match unsafe { host::api::process::create_config() } {
             -1 => Err(LunaticError::PermissionDenied),
-            id => Ok(Self(ProcessConfigType::Config(id as u64))),
+            id => Ok(Self(ProcessConfigType::Config(id as u64), None)),
         }
     }
 
     pub(crate) fn inherit() -> Self {
-        Self(ProcessConfigType::Inherit)
+        Self(ProcessConfigType::Inherit, None)
     }
 
     /// Returns the id of the configuration resource or -1 in case it's an
@@ -125,6 +126,40 @@ impl ProcessConfig {
         (unsafe { host::api::process::config_can_spawn_processes(self.id() as u64) }) > 0
     }
 
+    /// Spawns processes from the WebAssembly module `bytes`, instead of the
+    /// module of the parent.
+    ///
+    /// The module is compiled once, when it's set. Its processes start in the
+    /// function exported with [`plugin_entry!`](crate::plugin_entry), which
+    /// declares the capture and mailbox types it expects. The function passed
+    /// to [`Process::spawn_config`](crate::Process::spawn_config) only names
+    /// these types, it doesn't run. The capture is sent to the process as the
+    /// first message, prefixed with a version byte for its encoding, so that
+    /// the module can reject an argument it doesn't understand.
+    ///
+    /// Only mailbox based processes can be spawned from another module, and
+    /// only on the local node. Together with a configuration that denies all
+    /// permissions, this can be used to run untrusted plugins in isolated
+    /// processes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes` aren't a valid WebAssembly module, or the current
+    /// process isn't allowed to compile modules.
+    #[track_caller]
+    pub fn set_wasm_module(&mut self, bytes: &[u8]) -> &mut Self {
+        match WasmModule::new(bytes) {
+            Ok(module) => self.1 = Some(module),
+            Err(err) => panic!("Failed to compile WASM module: {err:?}"),
+        }
+        self
+    }
+
+    /// Returns the module set with [`set_wasm_module`](Self::set_wasm_module).
+    pub(crate) fn wasm_module(&self) -> Option<&WasmModule> {
+        self.1.as_ref()
+    }
+
     /// Adds environment variable.
     pub fn add_environment_variable(&mut self, key: &str, value: &str) {
         unsafe {
@@ -1,9 +1,20 @@
+use std::fmt::{self, Debug};
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::ap::{AbstractProcess, ProcessRef, StartupError};
+use crate::function::process::IntoProcess;
 use crate::host::api::distributed::{
     copy_lookup_nodes_results, exec_lookup_nodes, get_nodes, module_id, nodes_count,
 };
 use crate::host::api::{self};
 use crate::module::{params_to_vec, Param};
-use crate::LunaticError;
+use crate::serializer::{Bincode, CanSerialize};
+use crate::{LunaticError, Mailbox, MessageSignal, Process, ProcessName, Tag};
 
 pub fn node_id() -> u64 {
     unsafe { api::distributed::node_id() }
@@ -71,3 +82,286 @@ pub fn spawn(node_id: u64, config_id: i64, entry: fn(i32), arg: i32) -> Result<u
         Err(LunaticError::Error(id))
     }
 }
+
+/// Error result of [`migrate`] and [`migrate_as`].
+pub enum MigrationError<T: AbstractProcess> {
+    /// The process doesn't implement
+    /// [`AbstractProcess::snapshot`]. It keeps running on the original node.
+    SnapshotUnsupported,
+    /// The replacement failed to start on the target node. The original process
+    /// keeps running with its state.
+    Startup(StartupError<T>),
+}
+
+impl<T: AbstractProcess> Debug for MigrationError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SnapshotUnsupported => write!(f, "SnapshotUnsupported"),
+            Self::Startup(err) => f.debug_tuple("Startup").field(err).finish(),
+        }
+    }
+}
+
+/// Moves the abstract process `process` to `target_node`.
+///
+/// The state of the process is captured with [`AbstractProcess::snapshot`]
+/// and a replacement is started on `target_node` with the snapshot as `init`
+/// argument. Messages that reach the original process during the migration
+/// are buffered and forwarded to the replacement. The original process exits
+/// afterwards without calling [`terminate`](AbstractProcess::terminate).
+///
+/// References to the original process stop working once it exits, messages
+/// sent to it afterwards are lost. Links are not carried over.
+///
+/// The registry can't be searched by process, use [`migrate_as`] to move the
+/// name of a registered process to the replacement.
+pub fn migrate<T: AbstractProcess>(
+    process: ProcessRef<T>,
+    target_node: u64,
+) -> Result<ProcessRef<T>, MigrationError<T>> {
+    crate::ap::migrate(process, target_node, None)
+}
+
+/// Moves the abstract process `process` to `target_node` and registers the
+/// replacement under `name`.
+///
+/// The name points to the replacement before the original process exits, so
+/// that lookups keep working during the migration. See [`migrate`] for
+/// details.
+pub fn migrate_as<T: AbstractProcess, N: ProcessName>(
+    process: ProcessRef<T>,
+    target_node: u64,
+    name: &N,
+) -> Result<ProcessRef<T>, MigrationError<T>> {
+    crate::ap::migrate(process, target_node, Some(name.process_name()))
+}
+
+/// Error result of [`broadcast_node`] and [`broadcast_cluster`] for a single
+/// node.
+#[derive(Error, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum BroadcastError {
+    /// No process was registered with [`register_broadcast`] on the node.
+    #[error("no broadcast receiver registered on node {0}")]
+    NotRegistered(u64),
+}
+
+/// Registers `process` as the receiver of broadcasts of type `T` on the node
+/// it's running on.
+///
+/// Each node has one receiver per abstract process type, registering another
+/// process on the same node replaces it.
+pub fn register_broadcast<T: AbstractProcess>(process: &ProcessRef<T>) {
+    crate::ap::register_broadcast(process)
+}
+
+/// Sends `message` to the receiver of type `T` on each node in `node_ids`.
+///
+/// The message is serialized only once. The receivers are registered with
+/// [`register_broadcast`], and the result for each node is returned in the
+/// order of `node_ids`. Messages to the local node are sent directly, without
+/// going through the distributed layer.
+///
+/// Like [`ProcessRef::send`] the message is sent without waiting for it to be
+/// delivered, a receiver that died after registering doesn't cause an error.
+/// Messages holding resources can't be broadcast, only the serialized bytes
+/// are copied for each node.
+///
+/// # Panics
+///
+/// This function will panic if the message can't be serialized.
+#[track_caller]
+pub fn broadcast_node<T, M>(node_ids: &[u64], message: M) -> Vec<Result<(), BroadcastError>>
+where
+    T: AbstractProcess,
+    T::Serializer: CanSerialize<M>,
+    M: 'static,
+{
+    crate::ap::broadcast::<T, M>(node_ids, message)
+}
+
+/// Sends `message` to the receiver of type `T` on each node of the cluster,
+/// including the local node.
+///
+/// The nodes are taken from [`nodes`], see [`broadcast_node`] for details.
+#[track_caller]
+pub fn broadcast_cluster<T, M>(message: M) -> Vec<Result<(), BroadcastError>>
+where
+    T: AbstractProcess,
+    T::Serializer: CanSerialize<M>,
+    M: 'static,
+{
+    let mut node_ids = nodes();
+    let local = node_id();
+    if !node_ids.contains(&local) {
+        node_ids.insert(0, local);
+    }
+    broadcast_node::<T, M>(&node_ids, message)
+}
+
+/// A function that can be executed on another node with [`RemoteCall`].
+///
+/// The implementing type holds the arguments, it's sent to the node encoded
+/// with `Bincode` and the output is sent back the same way.
+pub trait RemoteCallable: Serialize + DeserializeOwned + 'static {
+    type Output: Serialize + DeserializeOwned + 'static;
+
+    /// Executes the function, on the node the call was made to.
+    fn execute(self) -> Self::Output;
+}
+
+/// Error result of [`RemoteCall::call`] and [`RemoteCall::call_timeout`].
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RemoteCallError {
+    /// The process executing the call couldn't be spawned on the node.
+    #[error("failed to spawn the call on node {node_id}: {reason}")]
+    Spawn { node_id: u64, reason: String },
+    /// [`RemoteCallable::execute`] panicked.
+    #[error("call panicked")]
+    Panicked,
+    /// No result arrived before the timeout expired. The call is killed.
+    #[error("timed out")]
+    TimedOut,
+    /// The node disconnected before the result arrived.
+    #[error("node {0} disconnected")]
+    NodeDisconnected(u64),
+}
+
+/// How often a caller checks that the node of a call is still connected.
+const NODE_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Executes functions of type `T` on other nodes.
+///
+/// ```ignore
+/// #[derive(Serialize, Deserialize)]
+/// struct Checksum(Vec<u8>);
+///
+/// impl RemoteCallable for Checksum {
+///     type Output = u32;
+///
+///     fn execute(self) -> u32 {
+///         crc32(&self.0)
+///     }
+/// }
+///
+/// let checksum = RemoteCall::<Checksum>::call(node_id, Checksum(data))?;
+/// ```
+///
+/// Each call spawns a temporary process on the node, which executes the
+/// function in a linked process and sends the result back. A call to the
+/// local node is executed the same way, without going through the
+/// distributed layer.
+pub struct RemoteCall<T> {
+    phantom: PhantomData<T>,
+}
+
+impl<T: RemoteCallable> RemoteCall<T> {
+    /// Executes `arg` on the node `node_id` and waits for the result.
+    pub fn call(node_id: u64, arg: T) -> Result<T::Output, RemoteCallError> {
+        Self::call_timeout(node_id, arg, None)
+    }
+
+    /// Executes `arg` on the node `node_id`, waiting up to `timeout` for the
+    /// result.
+    ///
+    /// The call is killed once the timeout expires.
+    pub fn call_timeout(
+        node_id: u64,
+        arg: T,
+        timeout: Option<Duration>,
+    ) -> Result<T::Output, RemoteCallError> {
+        let tag = Tag::new();
+        // Safety: Results are only received with the tag of this call.
+        let caller = unsafe { Process::<CallResult<T>>::this() };
+        let node = (node_id != self::node_id()).then_some(node_id);
+        let runner = <Mailbox<T::Output> as IntoProcess<T::Output, Bincode>>::spawn(
+            (caller, tag, arg),
+            run_call::<T>,
+            None,
+            None,
+            None,
+            node,
+        )
+        .map_err(|err| RemoteCallError::Spawn {
+            node_id,
+            reason: err.to_string(),
+        })?;
+
+        // Safety: Only messages with the tag of this call are received.
+        let mailbox = unsafe { Mailbox::<CallResult<T>>::new() };
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let mut wait = NODE_CHECK_INTERVAL;
+            if let Some(deadline) = deadline {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    runner.kill();
+                    return Err(RemoteCallError::TimedOut);
+                }
+                wait = wait.min(remaining);
+            }
+            if let Ok(result) = mailbox.tag_receive_timeout(&[tag], wait) {
+                return result;
+            }
+            if node.is_some() && !nodes().contains(&node_id) {
+                return Err(RemoteCallError::NodeDisconnected(node_id));
+            }
+        }
+    }
+}
+
+type CallResult<T> = Result<<T as RemoteCallable>::Output, RemoteCallError>;
+
+/// Looks up a process in the process table of the node it's running on.
+#[derive(Serialize, Deserialize)]
+struct ProcessExists(u64);
+
+impl RemoteCallable for ProcessExists {
+    type Output = bool;
+
+    fn execute(self) -> bool {
+        unsafe { api::process::exists(self.0) != 0 }
+    }
+}
+
+/// Returns `true` if the process `process_id` is running on the node
+/// `node_id`, `false` also if the node couldn't be asked.
+pub(crate) fn process_exists(node_id: u64, process_id: u64) -> bool {
+    RemoteCall::call(node_id, ProcessExists(process_id)).unwrap_or(false)
+}
+
+/// Kills a process on the node it's running on.
+#[derive(Serialize, Deserialize)]
+struct KillProcess(u64);
+
+impl RemoteCallable for KillProcess {
+    type Output = ();
+
+    fn execute(self) {
+        unsafe { api::process::kill(self.0) };
+    }
+}
+
+/// Kills the process `process_id` on the node `node_id`, if the node can be
+/// reached.
+pub(crate) fn kill_process(node_id: u64, process_id: u64) {
+    let _ = RemoteCall::call(node_id, KillProcess(process_id));
+}
+
+/// Executes a call in a linked process and sends the result to `caller`.
+fn run_call<T: RemoteCallable>(
+    (caller, tag, arg): (Process<CallResult<T>>, Tag, T),
+    mailbox: Mailbox<T::Output>,
+) {
+    let runner = mailbox.this();
+    let mailbox = mailbox.catch_link_failure();
+    Process::spawn_link((runner, arg), execute_call::<T>);
+    let result = match mailbox.receive() {
+        MessageSignal::Message(output) => Ok(output),
+        MessageSignal::Signal(_) => Err(RemoteCallError::Panicked),
+    };
+    caller.tag_send(tag, result);
+}
+
+fn execute_call<T: RemoteCallable>((runner, arg): (Process<T::Output>, T), _: Mailbox<()>) {
+    runner.send(arg.execute());
+}
@@ -0,0 +1,88 @@
+//! Environment variables of the running process.
+//!
+//! The standard library reads the environment once, when the process starts,
+//! and depending on the host the variables added with
+//! [`ProcessConfig::add_environment_variable`](crate::ProcessConfig::add_environment_variable)
+//! may not be visible through [`std::env::var`]. The functions in this module
+//! query the host with the WASI calls on each read.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::host;
+
+crate::process_local! {
+    // Variables set with `set_var`, the host environment can't be modified.
+    static OVERRIDES: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
+}
+
+/// Error returned by [`var`].
+#[derive(Error, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum EnvError {
+    /// The variable isn't set.
+    #[error("environment variable not found")]
+    NotPresent,
+    /// The value of the variable isn't valid UTF-8.
+    #[error("environment variable was not valid unicode")]
+    NotUnicode,
+    /// The host failed to return the environment, with the WASI error code.
+    #[error("reading the environment failed with WASI error {0}")]
+    Host(u16),
+}
+
+/// Returns the value of the environment variable `key` of this process.
+///
+/// Values set with [`set_var`] take precedence over the environment the
+/// process was spawned with.
+pub fn var(key: &str) -> Result<String, EnvError> {
+    let overridden = OVERRIDES.with(|overrides| overrides.borrow().get(key).cloned());
+    if let Some(value) = overridden {
+        return Ok(value);
+    }
+    let environ = environ()?;
+    let value = environ
+        .split(|&byte| byte == 0)
+        .find_map(|entry| entry.strip_prefix(key.as_bytes())?.strip_prefix(b"="))
+        .ok_or(EnvError::NotPresent)?;
+    String::from_utf8(value.to_vec()).map_err(|_| EnvError::NotUnicode)
+}
+
+/// Sets the environment variable `key` of this process to `value`.
+///
+/// WASI doesn't allow changing the environment of a running process, so the
+/// value is only visible to [`var`] and [`std::env::var`] inside of this
+/// process. Processes spawned afterwards don't inherit it, use
+/// [`ProcessConfig::add_environment_variable`](crate::ProcessConfig::add_environment_variable)
+/// for them.
+///
+/// # Panics
+///
+/// Like [`std::env::set_var`], if `key` is empty or contains `=` or a NUL
+/// character, or if `value` contains a NUL character.
+pub fn set_var(key: &str, value: &str) {
+    std::env::set_var(key, value);
+    OVERRIDES.with(|overrides| {
+        overrides
+            .borrow_mut()
+            .insert(key.to_owned(), value.to_owned())
+    });
+}
+
+/// Returns the `KEY=value` entries of the host environment, each terminated
+/// by a NUL character.
+fn environ() -> Result<Vec<u8>, EnvError> {
+    let (mut count, mut buf_size) = (0, 0);
+    let errno = unsafe { host::api::wasi::environ_sizes_get(&mut count, &mut buf_size) };
+    if errno != 0 {
+        return Err(EnvError::Host(errno));
+    }
+    let mut pointers = vec![std::ptr::null_mut(); count];
+    let mut buf = vec![0; buf_size];
+    let errno = unsafe { host::api::wasi::environ_get(pointers.as_mut_ptr(), buf.as_mut_ptr()) };
+    if errno != 0 {
+        return Err(EnvError::Host(errno));
+    }
+    Ok(buf)
+}
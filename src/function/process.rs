@@ -8,7 +8,7 @@ use std::time::Duration;
 use serde::{Deserialize, Serialize};
 
 use crate::host::{self, node_id, process_id};
-use crate::mailbox::{MailboxError, MessageSignal, TIMEOUT};
+use crate::mailbox::{MailboxError, MessageSignal, KILL_SIGNAL, TIMEOUT};
 use crate::protocol::ProtocolCapture;
 use crate::serializer::{Bincode, CanSerialize};
 use crate::time::TimerRef;
@@ -392,6 +392,24 @@ impl<M, S> Process<M, S> {
         unsafe { host::api::process::kill(self.id) };
     }
 
+    /// Asks this process to exit, giving it a chance to clean up.
+    ///
+    /// Unlike [`kill`](Self::kill), which terminates the process immediately,
+    /// the next `receive` of the process returns
+    /// [`MailboxError::ProcessKilled`], also when it's waiting with
+    /// `tag_receive` for other tags. `receive` and
+    /// `tag_receive` panic on it, the process handles it with `try_receive` or
+    /// the `_timeout` variants. After cleaning up, it can exit or re-raise the
+    /// signal with `mailbox.this().kill()`.
+    ///
+    /// Only processes receiving through a [`Mailbox`](crate::Mailbox) can
+    /// handle the signal, abstract processes should be stopped with
+    /// `shutdown`.
+    pub fn kill_gracefully(&self) {
+        unsafe { host::api::message::create_data(KILL_SIGNAL, 0) };
+        host::send(self.node_id, self.id);
+    }
+
     /// Register process under a name.
     pub fn register<N: ProcessName>(&self, name: &N) {
         // Encode type information in name
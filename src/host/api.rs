@@ -15,7 +15,6 @@ pub mod message {
         pub fn create_data(tag: i64, capacity: u64);
         pub fn write_data(data: *const u8, data_len: usize) -> usize;
         pub fn read_data(data: *mut u8, data_len: usize) -> usize;
-        #[allow(dead_code)]
         pub fn seek_data(position: u64);
         pub fn get_tag() -> i64;
         pub fn get_process_id() -> u64;
@@ -296,6 +295,14 @@ pub mod wasi {
         pub fn config_add_command_line_argument(config_id: u64, key: *const u8, key_len: usize);
         pub fn config_preopen_dir(config_id: u64, key: *const u8, key_len: usize);
     }
+
+    // Environment of the running process, as set up by the host from the
+    // `ProcessConfig` it was spawned with. Calls return a WASI errno.
+    #[link(wasm_import_module = "wasi_snapshot_preview1")]
+    extern "C" {
+        pub fn environ_sizes_get(count: *mut usize, buf_size: *mut usize) -> u16;
+        pub fn environ_get(environ: *mut *mut u8, environ_buf: *mut u8) -> u16;
+    }
 }
 
 #[allow(clashing_extern_declarations)]
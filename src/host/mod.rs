@@ -27,10 +27,22 @@ pub(crate) fn spawn(
     arg: i32,
 ) -> Result<u64, LunaticError> {
     let entry = entry as usize as i32;
-    let params = params_to_vec(&[Param::I32(entry), Param::I32(arg)]);
+    let mut params = params_to_vec(&[Param::I32(entry), Param::I32(arg)]);
     let mut id = 0;
     let mut node_id = 0;
-    let func = concat!("_lunatic_spawn_by_index_", env!("CARGO_PKG_VERSION"));
+    let mut func = concat!("_lunatic_spawn_by_index_", env!("CARGO_PKG_VERSION"));
+    let mut module_id = WasmModule::inherit().id();
+    // Function pointers are only valid inside of the module they were created
+    // in, processes of other modules start in their declared entry function.
+    if let Some(module) = config.and_then(ProcessConfig::wasm_module) {
+        assert!(
+            node.is_none(),
+            "Processes can't be spawned from a WASM module on other nodes"
+        );
+        params.clear();
+        func = PLUGIN_ENTRY;
+        module_id = module.id();
+    }
     let link = match link {
         Some(tag) => tag.id(),
         None => 0,
@@ -54,7 +66,7 @@ pub(crate) fn spawn(
                 name.len(),
                 link,
                 config_id,
-                WasmModule::inherit().id(),
+                module_id,
                 func.as_ptr(),
                 func.len(),
                 params.as_ptr(),
@@ -66,7 +78,7 @@ pub(crate) fn spawn(
             api::process::spawn(
                 link,
                 config_id,
-                WasmModule::inherit().id(),
+                module_id,
                 func.as_ptr(),
                 func.len(),
                 params.as_ptr(),
@@ -85,6 +97,12 @@ pub(crate) fn spawn(
     }
 }
 
+/// Name of the function exported with [`plugin_entry!`](crate::plugin_entry).
+///
+/// It has no version attached, because the module and its parent can depend
+/// on different versions of this crate.
+pub(crate) const PLUGIN_ENTRY: &str = "_lunatic_plugin_entry";
+
 /// We attach the version to the exported function to avoid duplicate exports if
 /// multiple dependencies use different versions of this crate. See:
 /// https://github.com/lunatic-solutions/lunatic-rs/issues/71
@@ -0,0 +1,280 @@
+//! HTTP/1.1 client.
+//!
+//! Each request is performed by a short-lived task process linked to the
+//! caller. The task opens the connection, exchanges the request and response
+//! and sends the result back to the caller's mailbox.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use lunatic::http::{client, Method};
+//!
+//! let response = client::get("http://example.com/").unwrap();
+//! assert_eq!(response.status, 200);
+//!
+//! let response = client::RequestBuilder::new(Method::Put, "http://example.com/items/1")
+//!     .header("content-type", "application/json")
+//!     .body(r#"{"name":"lunatic"}"#)
+//!     .timeout(Duration::from_secs(5))
+//!     .send()
+//!     .unwrap();
+//! println!("{}", response.text());
+//! ```
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::wire::{self, MessageReader};
+use super::{HttpError, Method};
+use crate::net::{TcpStream, TlsStream};
+use crate::protocol::{Protocol, Send, TaskEnd};
+use crate::Process;
+
+/// Performs a `GET` request.
+pub fn get(url: &str) -> Result<Response, HttpError> {
+    RequestBuilder::new(Method::Get, url).send()
+}
+
+/// Performs a `POST` request with `body`.
+pub fn post<B: Into<Vec<u8>>>(url: &str, body: B) -> Result<Response, HttpError> {
+    RequestBuilder::new(Method::Post, url).body(body).send()
+}
+
+/// A response returned by the server.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Response {
+    /// Status code.
+    pub status: u16,
+    /// Response headers. Names are lowercased, repeated headers are joined
+    /// with `", "`.
+    pub headers: HashMap<String, String>,
+    /// Response body, with any chunked transfer encoding removed.
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// Returns the value of the header `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    /// Returns `true` if the status code is in the `2xx` range.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Returns the body as a string, replacing invalid UTF-8 sequences.
+    pub fn text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.body)
+    }
+}
+
+/// Builder for a single HTTP request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RequestBuilder {
+    method: Method,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    timeout: Option<Duration>,
+}
+
+impl RequestBuilder {
+    /// Creates a request for `url` without a body.
+    pub fn new(method: Method, url: &str) -> Self {
+        Self {
+            method,
+            url: url.to_owned(),
+            headers: Vec::new(),
+            body: Vec::new(),
+            timeout: None,
+        }
+    }
+
+    /// Adds a request header.
+    ///
+    /// `host`, `connection` and `content-length` are filled in automatically
+    /// if not set.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Sets the request body.
+    pub fn body<B: Into<Vec<u8>>>(mut self, body: B) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Sets a timeout for connecting and for each read and write on the
+    /// connection.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sends the request and waits for the response.
+    pub fn send(self) -> Result<Response, HttpError> {
+        let task = Process::spawn_link(
+            self,
+            |request: RequestBuilder,
+             protocol: Protocol<Send<Result<Response, HttpError>, TaskEnd>>| {
+                let _ = protocol.send(request.execute());
+            },
+        );
+        task.result()
+    }
+
+    fn execute(&self) -> Result<Response, HttpError> {
+        let url = Url::parse(&self.url)?;
+        if url.tls {
+            let mut stream = match self.timeout {
+                Some(timeout) => {
+                    TlsStream::connect_timeout(&url.host, timeout, url.port as u32, vec![])?
+                }
+                None => TlsStream::connect(&url.host, url.port as u32)?,
+            };
+            stream.set_read_timeout(self.timeout)?;
+            stream.set_write_timeout(self.timeout)?;
+            self.exchange(stream, &url)
+        } else {
+            let addr = format!("{}:{}", url.host, url.port);
+            let mut stream = match self.timeout {
+                Some(timeout) => TcpStream::connect_timeout(addr, timeout)?,
+                None => TcpStream::connect(addr)?,
+            };
+            stream.set_read_timeout(self.timeout)?;
+            stream.set_write_timeout(self.timeout)?;
+            self.exchange(stream, &url)
+        }
+    }
+
+    fn exchange<S: Read + Write>(&self, mut stream: S, url: &Url) -> Result<Response, HttpError> {
+        let mut headers = self.headers.clone();
+        let has_header = |headers: &[(String, String)], name: &str| {
+            headers.iter().any(|(n, _)| n.eq_ignore_ascii_case(name))
+        };
+        if !has_header(&headers, "host") {
+            headers.push(("Host".to_owned(), url.authority()));
+        }
+        if !has_header(&headers, "connection") {
+            headers.push(("Connection".to_owned(), "close".to_owned()));
+        }
+        let expects_body = matches!(self.method, Method::Post | Method::Put | Method::Patch);
+        if (expects_body || !self.body.is_empty()) && !has_header(&headers, "content-length") {
+            headers.push(("Content-Length".to_owned(), self.body.len().to_string()));
+        }
+
+        let start_line = format!("{} {} HTTP/1.1", self.method, url.path);
+        wire::write_message(&mut stream, &start_line, &headers, &self.body)?;
+
+        let mut reader = MessageReader::new(&mut stream);
+        loop {
+            let head = reader.read_head()?;
+            let status = parse_status(&head.start_line)?;
+            // Skip interim responses, like `100 Continue`
+            if (100..200).contains(&status) && status != 101 {
+                continue;
+            }
+            let body = if self.method == Method::Head || status == 204 || status == 304 {
+                Vec::new()
+            } else {
+                reader.read_body(&head.headers, true)?
+            };
+            return Ok(Response {
+                status,
+                headers: head.headers,
+                body,
+            });
+        }
+    }
+}
+
+fn parse_status(status_line: &str) -> Result<u16, HttpError> {
+    let mut parts = status_line.splitn(3, ' ');
+    match (parts.next(), parts.next()) {
+        (Some(version), Some(status)) if version.starts_with("HTTP/1.") => status
+            .parse()
+            .map_err(|_| HttpError::Malformed(format!("invalid status line `{status_line}`"))),
+        _ => Err(HttpError::Malformed(format!(
+            "invalid status line `{status_line}`"
+        ))),
+    }
+}
+
+#[derive(Debug)]
+struct Url {
+    tls: bool,
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Url {
+    fn parse(url: &str) -> Result<Self, HttpError> {
+        let invalid = || HttpError::InvalidUrl(url.to_owned());
+        let (scheme, rest) = url.split_once("://").ok_or_else(invalid)?;
+        let tls = match scheme.to_ascii_lowercase().as_str() {
+            "http" => false,
+            "https" => true,
+            _ => return Err(HttpError::UnsupportedScheme(scheme.to_owned())),
+        };
+        let (authority, path) = match rest.find(['/', '?', '#']) {
+            Some(index) => rest.split_at(index),
+            None => (rest, "/"),
+        };
+        let path = path.split('#').next().unwrap_or_default();
+        let path = match path {
+            "" => "/".to_owned(),
+            path if path.starts_with('?') => format!("/{path}"),
+            path => path.to_owned(),
+        };
+        // Ignore user info
+        let authority = authority.rsplit('@').next().unwrap_or_default();
+        let default_port = if tls { 443 } else { 80 };
+        let (host, port) = if authority.starts_with('[') {
+            // IPv6 literal, e.g. `[::1]:8080`
+            let end = authority.find(']').ok_or_else(invalid)?;
+            match &authority[end + 1..] {
+                "" => (&authority[..=end], default_port),
+                port => (
+                    &authority[..=end],
+                    port.strip_prefix(':')
+                        .and_then(|port| port.parse().ok())
+                        .ok_or_else(invalid)?,
+                ),
+            }
+        } else {
+            match authority.rsplit_once(':') {
+                Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+                None => (authority, default_port),
+            }
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Url {
+            tls,
+            host: host.to_owned(),
+            port,
+            path,
+        })
+    }
+
+    /// Value of the `host` header.
+    fn authority(&self) -> String {
+        if self.port == if self.tls { 443 } else { 80 } {
+            self.host.clone()
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! A small HTTP/1.1 client and server built on top of
+//! [`lunatic::net`](crate::net).
+//!
+//! Sockets in lunatic never block the underlying thread. A process waiting on
+//! the network is simply descheduled until data arrives, which makes it
+//! possible to speak HTTP without pulling in a blocking client library or
+//! handing the work off to the host.
+
+pub mod client;
+pub mod server;
+mod wire;
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// HTTP request method.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Patch,
+    Delete,
+    Options,
+}
+
+impl Method {
+    /// Returns the method as it appears on the wire.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Patch => "PATCH",
+            Method::Delete => "DELETE",
+            Method::Options => "OPTIONS",
+        }
+    }
+}
+
+impl FromStr for Method {
+    type Err = HttpError;
+
+    fn from_str(method: &str) -> Result<Self, Self::Err> {
+        match method {
+            "GET" => Ok(Method::Get),
+            "HEAD" => Ok(Method::Head),
+            "POST" => Ok(Method::Post),
+            "PUT" => Ok(Method::Put),
+            "PATCH" => Ok(Method::Patch),
+            "DELETE" => Ok(Method::Delete),
+            "OPTIONS" => Ok(Method::Options),
+            _ => Err(HttpError::Malformed(format!("unknown method `{method}`"))),
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An error returned by the HTTP client.
+///
+/// I/O errors are carried as strings so that the error can be sent between
+/// processes.
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HttpError {
+    /// The URL could not be parsed.
+    #[error("invalid url: {0}")]
+    InvalidUrl(String),
+    /// The URL scheme is neither `http` nor `https`.
+    #[error("unsupported scheme: {0}")]
+    UnsupportedScheme(String),
+    /// Connecting, reading or writing failed.
+    #[error("io error: {0}")]
+    Io(String),
+    /// The operation didn't finish inside of the configured timeout.
+    #[error("timed out")]
+    TimedOut,
+    /// The peer sent something that is not valid HTTP/1.1.
+    #[error("malformed message: {0}")]
+    Malformed(String),
+}
+
+impl From<std::io::Error> for HttpError {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::TimedOut => HttpError::TimedOut,
+            _ => HttpError::Io(err.to_string()),
+        }
+    }
+}
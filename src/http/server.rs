@@ -0,0 +1,257 @@
+//! HTTP/1.1 server.
+//!
+//! The server accepts connections inside of a dedicated process and spawns a
+//! new process for each incoming request. A failing handler can't affect other
+//! requests or the server itself.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use lunatic::http::server::{self, Request, Response};
+//!
+//! let server = server::listen("127.0.0.1:8080", |request: Request| {
+//!     Response::new(200).body(format!("Hello from {}", request.path))
+//! })
+//! .unwrap();
+//! println!("Listening on {}", server.local_addr());
+//! // ...
+//! server.stop();
+//! ```
+
+use std::collections::HashMap;
+use std::mem::MaybeUninit;
+use std::net::SocketAddr;
+
+use serde::{Deserialize, Serialize};
+
+use super::wire::{self, MessageReader};
+use super::{HttpError, Method};
+use crate::net::{TcpListener, TcpStream, ToSocketAddrs};
+use crate::panic::catch_panic;
+use crate::protocol::{Protocol, Send, TaskEnd};
+use crate::{Mailbox, Process};
+
+/// Starts accepting HTTP connections on `addr`.
+///
+/// Each request is handled by a new process running `handler`. Because the
+/// handler is executed in a different process, it can't capture any variables
+/// from the environment and needs to be a function or a non-capturing closure.
+///
+/// The server is linked to the caller. It will keep running until
+/// [`ServerHandle::stop`] is called or the caller dies.
+///
+/// # Panics
+///
+/// This function will panic if `handler` captures variables.
+#[track_caller]
+pub fn listen<A, F>(addr: A, handler: F) -> Result<ServerHandle, HttpError>
+where
+    A: ToSocketAddrs,
+    F: Fn(Request) -> Response + Clone + 'static,
+{
+    assert_eq!(
+        std::mem::size_of_val(&handler),
+        0,
+        "http handler can't capture variables"
+    );
+    let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+    let acceptor = Process::spawn_link(addrs, accept::<F>);
+    let process = unsafe { Process::new(acceptor.node_id(), acceptor.id()) };
+    let local_addr = acceptor.result()?;
+    Ok(ServerHandle {
+        process,
+        local_addr,
+    })
+}
+
+/// A reference to a running HTTP server.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServerHandle {
+    process: Process<()>,
+    local_addr: SocketAddr,
+}
+
+impl ServerHandle {
+    /// Returns the address the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stops accepting new connections.
+    ///
+    /// Requests that are already being handled will finish in their own
+    /// processes.
+    pub fn stop(self) {
+        self.process.unlink();
+        self.process.kill();
+    }
+}
+
+/// A request received by the server.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Request {
+    /// Request method.
+    pub method: Method,
+    /// Request target, including the query string.
+    pub path: String,
+    /// Request headers. Names are lowercased, repeated headers are joined
+    /// with `", "`.
+    pub headers: HashMap<String, String>,
+    /// Request body, with any chunked transfer encoding removed.
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Returns the value of the header `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+}
+
+/// A response returned by the request handler.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Response {
+    /// Status code.
+    pub status: u16,
+    /// Response headers.
+    ///
+    /// `content-length` and `connection` are filled in automatically if not
+    /// set.
+    pub headers: Vec<(String, String)>,
+    /// Response body.
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// Creates an empty response with `status`.
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Adds a response header.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Sets the response body.
+    pub fn body<B: Into<Vec<u8>>>(mut self, body: B) -> Self {
+        self.body = body.into();
+        self
+    }
+}
+
+/// Entry point of the process accepting connections.
+fn accept<F>(
+    addrs: Vec<SocketAddr>,
+    protocol: Protocol<Send<Result<SocketAddr, HttpError>, TaskEnd>>,
+) where
+    F: Fn(Request) -> Response + Clone + 'static,
+{
+    let listener = match TcpListener::bind(addrs.as_slice()) {
+        Ok(listener) => listener,
+        Err(err) => {
+            let _ = protocol.send(Err(err.into()));
+            return;
+        }
+    };
+    let local_addr = listener.local_addr().map_err(HttpError::from);
+    let failed = local_addr.is_err();
+    let _ = protocol.send(local_addr);
+    if failed {
+        return;
+    }
+    while let Ok((stream, _)) = listener.accept() {
+        Process::spawn(stream, handle::<F>);
+    }
+}
+
+/// Entry point of the process handling a single request.
+fn handle<F>(mut stream: TcpStream, _: Mailbox<()>)
+where
+    F: Fn(Request) -> Response + Clone + 'static,
+{
+    // `listen` guarantees that the handler doesn't capture anything, so it can
+    // be recreated inside of this process.
+    let handler: F = unsafe { MaybeUninit::zeroed().assume_init() };
+    let response = match read_request(&mut stream) {
+        Ok(request) => match catch_panic(|| handler(request)) {
+            Ok(response) => response,
+            Err(_) => Response::new(500),
+        },
+        Err(HttpError::Io(_)) | Err(HttpError::TimedOut) => return,
+        Err(_) => Response::new(400),
+    };
+    let _ = write_response(&mut stream, response);
+}
+
+fn read_request(stream: &mut TcpStream) -> Result<Request, HttpError> {
+    let mut reader = MessageReader::new(stream);
+    let head = reader.read_head()?;
+    let invalid = || HttpError::Malformed(format!("invalid request line `{}`", head.start_line));
+    let mut parts = head.start_line.split(' ');
+    let (method, path) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(path), Some(version), None) if version.starts_with("HTTP/1.") => {
+            (method.parse::<Method>()?, path.to_owned())
+        }
+        _ => return Err(invalid()),
+    };
+    let body = reader.read_body(&head.headers, false)?;
+    Ok(Request {
+        method,
+        path,
+        headers: head.headers,
+        body,
+    })
+}
+
+fn write_response(stream: &mut TcpStream, response: Response) -> Result<(), HttpError> {
+    let Response {
+        status,
+        mut headers,
+        body,
+    } = response;
+    let has_header = |headers: &[(String, String)], name: &str| {
+        headers.iter().any(|(n, _)| n.eq_ignore_ascii_case(name))
+    };
+    if !has_header(&headers, "content-length") {
+        headers.push(("Content-Length".to_owned(), body.len().to_string()));
+    }
+    if !has_header(&headers, "connection") {
+        headers.push(("Connection".to_owned(), "close".to_owned()));
+    }
+    let status_line = format!("HTTP/1.1 {} {}", status, reason_phrase(status));
+    wire::write_message(stream, &status_line, &headers, &body)
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        100 => "Continue",
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "",
+    }
+}
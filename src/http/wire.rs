@@ -0,0 +1,181 @@
+//! Reading and writing of HTTP/1.1 messages.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use super::HttpError;
+
+// Upper limit for the start line and headers, protects against peers that
+// never terminate the head.
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+/// Start line and headers of a message.
+///
+/// Header names are lowercased, repeated headers are joined with `", "`.
+pub(crate) struct Head {
+    pub(crate) start_line: String,
+    pub(crate) headers: HashMap<String, String>,
+}
+
+/// Buffers reads from a stream, so that bytes received after the head can be
+/// used as the beginning of the body.
+pub(crate) struct MessageReader<S> {
+    stream: S,
+    buffer: Vec<u8>,
+}
+
+impl<S: Read> MessageReader<S> {
+    pub(crate) fn new(stream: S) -> Self {
+        Self {
+            stream,
+            buffer: Vec::new(),
+        }
+    }
+
+    pub(crate) fn read_head(&mut self) -> Result<Head, HttpError> {
+        loop {
+            if let Some(end) = find(&self.buffer, b"\r\n\r\n") {
+                let head: Vec<u8> = self.buffer.drain(..end + 4).collect();
+                return parse_head(&head[..end]);
+            }
+            if self.buffer.len() > MAX_HEAD_SIZE {
+                return Err(HttpError::Malformed("head too large".to_owned()));
+            }
+            if self.fill()? == 0 {
+                return Err(HttpError::Malformed(
+                    "connection closed before end of head".to_owned(),
+                ));
+            }
+        }
+    }
+
+    /// Reads the body described by `headers`.
+    ///
+    /// If neither `transfer-encoding` nor `content-length` are present, the
+    /// body extends to the end of the stream when `until_eof` is set and is
+    /// empty otherwise.
+    pub(crate) fn read_body(
+        &mut self,
+        headers: &HashMap<String, String>,
+        until_eof: bool,
+    ) -> Result<Vec<u8>, HttpError> {
+        let chunked = headers
+            .get("transfer-encoding")
+            .is_some_and(|value| value.to_ascii_lowercase().contains("chunked"));
+        if chunked {
+            return self.read_chunked();
+        }
+        if let Some(length) = headers.get("content-length") {
+            let length = length
+                .trim()
+                .parse()
+                .map_err(|_| HttpError::Malformed(format!("invalid content-length `{length}`")))?;
+            return self.read_exact(length);
+        }
+        if until_eof {
+            while self.fill()? != 0 {}
+            return Ok(std::mem::take(&mut self.buffer));
+        }
+        Ok(Vec::new())
+    }
+
+    fn read_chunked(&mut self) -> Result<Vec<u8>, HttpError> {
+        let mut body = Vec::new();
+        loop {
+            let line = self.read_line()?;
+            let size = line.split(';').next().unwrap_or_default().trim();
+            let size = usize::from_str_radix(size, 16)
+                .map_err(|_| HttpError::Malformed(format!("invalid chunk size `{size}`")))?;
+            if size == 0 {
+                // Skip trailers
+                while !self.read_line()?.is_empty() {}
+                return Ok(body);
+            }
+            body.extend(self.read_exact(size)?);
+            self.read_line()?;
+        }
+    }
+
+    fn read_exact(&mut self, length: usize) -> Result<Vec<u8>, HttpError> {
+        while self.buffer.len() < length {
+            if self.fill()? == 0 {
+                return Err(HttpError::Malformed(
+                    "connection closed before end of body".to_owned(),
+                ));
+            }
+        }
+        Ok(self.buffer.drain(..length).collect())
+    }
+
+    fn read_line(&mut self) -> Result<String, HttpError> {
+        loop {
+            if let Some(end) = find(&self.buffer, b"\r\n") {
+                let line: Vec<u8> = self.buffer.drain(..end + 2).collect();
+                return String::from_utf8(line[..end].to_vec())
+                    .map_err(|_| HttpError::Malformed("line is not valid utf-8".to_owned()));
+            }
+            if self.fill()? == 0 {
+                return Err(HttpError::Malformed(
+                    "connection closed before end of line".to_owned(),
+                ));
+            }
+        }
+    }
+
+    fn fill(&mut self) -> Result<usize, HttpError> {
+        let mut chunk = [0u8; 4096];
+        let n = self.stream.read(&mut chunk)?;
+        self.buffer.extend_from_slice(&chunk[..n]);
+        Ok(n)
+    }
+}
+
+/// Writes a complete message. The caller is responsible for including a
+/// `content-length` header if the message has a body.
+pub(crate) fn write_message<W: Write>(
+    writer: &mut W,
+    start_line: &str,
+    headers: &[(String, String)],
+    body: &[u8],
+) -> Result<(), HttpError> {
+    let mut head = format!("{start_line}\r\n");
+    for (name, value) in headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str("\r\n");
+    writer.write_all(head.as_bytes())?;
+    writer.write_all(body)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn parse_head(head: &[u8]) -> Result<Head, HttpError> {
+    let head = std::str::from_utf8(head)
+        .map_err(|_| HttpError::Malformed("head is not valid utf-8".to_owned()))?;
+    let mut lines = head.split("\r\n");
+    let start_line = lines.next().unwrap_or_default().to_owned();
+    let mut headers: HashMap<String, String> = HashMap::new();
+    for line in lines {
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| HttpError::Malformed(format!("invalid header `{line}`")))?;
+        let value = value.trim();
+        headers
+            .entry(name.trim().to_ascii_lowercase())
+            .and_modify(|existing| {
+                existing.push_str(", ");
+                existing.push_str(value);
+            })
+            .or_insert_with(|| value.to_owned());
+    }
+    Ok(Head {
+        start_line,
+        headers,
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
@@ -0,0 +1,168 @@
+//! Reading from standard input.
+//!
+//! Reading from stdin only suspends the calling process, but it can't be
+//! interrupted. [`stdin_lines`] reads stdin in a separate process that sends
+//! each line back as a message. Waiting for the next line can then be combined
+//! with waiting for messages with [`StdinLines::next_event`].
+//!
+//! # Example
+//!
+//! ```no_run
+//! use lunatic::io::{stdin_lines, StdinEvent};
+//! use lunatic::Mailbox;
+//!
+//! #[lunatic::main]
+//! fn main(mailbox: Mailbox<String>) {
+//!     let mut lines = stdin_lines();
+//!     loop {
+//!         match lines.next_event(&mailbox).unwrap() {
+//!             StdinEvent::Line(line) => println!("read: {line}"),
+//!             StdinEvent::Message(message) => println!("received: {message}"),
+//!             StdinEvent::Eof => break,
+//!         }
+//!     }
+//! }
+//! ```
+
+use std::io::{self, BufRead};
+
+use serde::{Deserialize, Serialize};
+
+use crate::host::api::message;
+use crate::mailbox::{DATA_MESSAGE, LINK_DIED};
+use crate::serializer::{Bincode, CanSerialize};
+use crate::{Mailbox, Process, Tag};
+
+/// Returns an iterator over the lines of stdin.
+///
+/// Lines are read by a new process, which is killed when the iterator is
+/// dropped. Only one iterator should be used at a time, lines are split
+/// between concurrent readers.
+pub fn stdin_lines() -> StdinLines {
+    let tag = Tag::new();
+    // Safety: The reader only sends `ReaderMessage`s, tagged with `tag`.
+    let this = unsafe { Process::<ReaderMessage>::this() };
+    let reader = Process::spawn((this, tag), read_lines);
+    StdinLines {
+        reader,
+        tag,
+        eof: false,
+    }
+}
+
+/// Iterator over the lines of stdin, created with [`stdin_lines`].
+///
+/// Trailing newlines are removed from each line.
+pub struct StdinLines {
+    reader: Process<()>,
+    tag: Tag,
+    eof: bool,
+}
+
+/// Result of [`StdinLines::next_event`].
+#[derive(Debug)]
+pub enum StdinEvent<M> {
+    /// A line was read from stdin.
+    Line(String),
+    /// A message arrived in the mailbox.
+    Message(M),
+    /// Stdin was closed. It's only returned once, afterwards
+    /// [`next_event`](StdinLines::next_event) only waits for messages.
+    Eof,
+}
+
+impl StdinLines {
+    /// Waits for the next line of stdin or the next message in `mailbox`,
+    /// whichever arrives first.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the received message can't be deserialized
+    /// into `M` with serializer `S`.
+    #[track_caller]
+    pub fn next_event<M, S>(&mut self, mailbox: &Mailbox<M, S>) -> io::Result<StdinEvent<M>>
+    where
+        S: CanSerialize<M>,
+    {
+        if self.eof {
+            return Ok(StdinEvent::Message(mailbox.receive()));
+        }
+        loop {
+            let message_type = unsafe { message::receive([].as_ptr(), 0, u64::MAX) };
+            match message_type {
+                DATA_MESSAGE => (),
+                // Link deaths are only delivered to mailboxes catching them.
+                LINK_DIED => continue,
+                _ => panic!("unknown message type: {message_type}"),
+            }
+            if unsafe { message::get_tag() } != self.tag.id() {
+                return match S::decode() {
+                    Ok(message) => Ok(StdinEvent::Message(message)),
+                    Err(err) => panic!("Could not deserialize message: {err}"),
+                };
+            }
+            return match self.decode()? {
+                Some(line) => Ok(StdinEvent::Line(line)),
+                None => Ok(StdinEvent::Eof),
+            };
+        }
+    }
+
+    /// Decodes a message of the reader that was just received.
+    fn decode(&mut self) -> io::Result<Option<String>> {
+        let message = <Bincode as CanSerialize<ReaderMessage>>::decode()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        match message {
+            ReaderMessage::Line(line) => Ok(Some(line)),
+            ReaderMessage::Error(err) => {
+                self.eof = true;
+                Err(io::Error::other(err))
+            }
+            ReaderMessage::Eof => {
+                self.eof = true;
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Iterator for StdinLines {
+    type Item = io::Result<String>;
+
+    /// Waits for the next line of stdin. Other messages stay in the mailbox.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.eof {
+            return None;
+        }
+        let tags = [self.tag.id()];
+        unsafe { message::receive(tags.as_ptr(), tags.len(), u64::MAX) };
+        self.decode().transpose()
+    }
+}
+
+impl Drop for StdinLines {
+    fn drop(&mut self) {
+        if !self.eof {
+            self.reader.kill();
+        }
+    }
+}
+
+/// Message sent by the process reading stdin.
+#[derive(Serialize, Deserialize)]
+enum ReaderMessage {
+    Line(String),
+    /// Reading failed, the reader stops.
+    Error(String),
+    Eof,
+}
+
+fn read_lines((parent, tag): (Process<ReaderMessage>, Tag), _: Mailbox<()>) {
+    for line in io::stdin().lock().lines() {
+        match line {
+            Ok(line) => parent.tag_send(tag, ReaderMessage::Line(line)),
+            Err(err) => return parent.tag_send(tag, ReaderMessage::Error(err.to_string())),
+        }
+    }
+    parent.tag_send(tag, ReaderMessage::Eof);
+}
@@ -105,15 +105,25 @@ mod module;
 mod process_local;
 mod process_name;
 mod tag;
+mod warning;
 
+pub mod actor;
 pub mod ap;
+pub mod bench;
+pub mod channel;
+pub mod codec;
 pub mod distributed;
+pub mod env;
 pub mod function;
 pub mod host;
+pub mod http;
+pub mod io;
 pub mod metrics;
 pub mod net;
 pub mod panic;
+pub mod process;
 pub mod protocol;
+pub mod pubsub;
 pub mod serializer;
 pub mod supervisor;
 #[doc(hidden)]
@@ -134,6 +144,8 @@ pub use mailbox::{
     LinkDiedSignal, Mailbox, MailboxError, MailboxResult, MessageSignal, MessageSignalConvertError,
     ProcessDiedSignal, Signal,
 };
+#[doc(hidden)]
+pub use module::plugin_entry as __plugin_entry;
 pub use module::{Param, WasmModule};
 #[doc(hidden)]
 pub use process_local::statik::Key as __StaticProcessLocalInner;
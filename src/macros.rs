@@ -222,3 +222,38 @@ macro_rules! spawn_link {
         )
     };
 }
+
+/// Declares the entry function of a WebAssembly module that processes can be
+/// spawned from with
+/// [`ProcessConfig::set_wasm_module`](crate::ProcessConfig::set_wasm_module).
+///
+/// The function takes the capture and a mailbox, like the entry of
+/// [`Process::spawn`](crate::Process::spawn). Both types need to match the
+/// ones used by the parent, which can't be checked across modules.
+///
+/// # Example
+///
+/// ```
+/// // Plugin module
+/// lunatic::plugin_entry!(greet);
+///
+/// fn greet(name: String, mailbox: Mailbox<String>) {
+///     let message = mailbox.receive();
+///     println!("{message}, {name}!");
+/// }
+///
+/// // Host module
+/// let mut config = ProcessConfig::new().unwrap();
+/// config.set_wasm_module(&plugin_bytes);
+/// let plugin = Process::spawn_config(&config, "Plugin".to_owned(), |_, _: Mailbox<String>| {});
+/// plugin.send("Hello".to_owned());
+/// ```
+#[macro_export]
+macro_rules! plugin_entry {
+    ($entry:path) => {
+        #[export_name = "_lunatic_plugin_entry"]
+        extern "C" fn _lunatic_plugin_entry() {
+            lunatic::__plugin_entry($entry);
+        }
+    };
+}
@@ -7,6 +7,7 @@ use thiserror::Error;
 
 use crate::function::process::{IntoProcess, NoLink};
 use crate::host::api::message;
+use crate::module::send_plugin_arg;
 use crate::serializer::{Bincode, CanSerialize, DecodeError};
 use crate::{host, LunaticError, Process, ProcessConfig, Tag};
 
@@ -15,6 +16,9 @@ pub const LINK_DIED: u32 = 1;
 pub const PROCESS_DIED: u32 = 2;
 pub const TIMEOUT: u32 = 9027;
 
+/// Tag of the signal sent by [`Process::kill_gracefully`].
+pub(crate) const KILL_SIGNAL: i64 = 1;
+
 pub type MailboxResult<T, U = ()> = Result<MessageSignal<T, U>, MailboxError>;
 
 /// The mailbox of a [`Process`].
@@ -266,14 +270,25 @@ where
         unsafe { Process::new(host::node_id(), host::process_id()) }
     }
 
-    fn receive_(&self, tags: &[Tag], timeout: Option<Duration>) -> MailboxResult<M, Signal> {
-        let tags: Vec<i64> = tags.iter().map(|tag| tag.id()).collect();
+    pub(crate) fn receive_(
+        &self,
+        tags: &[Tag],
+        timeout: Option<Duration>,
+    ) -> MailboxResult<M, Signal> {
+        let mut tags: Vec<i64> = tags.iter().map(|tag| tag.id()).collect();
+        // The kill signal interrupts waiting for specific tags.
+        if !tags.is_empty() {
+            tags.push(KILL_SIGNAL);
+        }
         let timeout_ms = match timeout {
             Some(timeout) => timeout.as_millis() as u64,
             None => u64::MAX,
         };
         let message_type = unsafe { message::receive(tags.as_ptr(), tags.len(), timeout_ms) };
         match message_type {
+            DATA_MESSAGE if unsafe { message::get_tag() } == KILL_SIGNAL => {
+                Err(MailboxError::ProcessKilled)
+            }
             DATA_MESSAGE => match S::decode() {
                 Ok(msg) => Ok(MessageSignal::Message(msg)),
                 Err(err) => Err(MailboxError::DeserializationFailed(err)),
@@ -332,6 +347,9 @@ pub enum MailboxError {
     /// Receive message timed out.
     #[error("timed out")]
     TimedOut,
+    /// The process was asked to exit with [`Process::kill_gracefully`].
+    #[error("process killed")]
+    ProcessKilled,
 }
 
 /// A signal received when a link dies or monitored process dies.
@@ -389,6 +407,11 @@ impl MailboxError {
     pub fn is_deserialization_failed(&self) -> bool {
         matches!(self, MailboxError::DeserializationFailed(_))
     }
+
+    /// Returns true if the error is a [`MailboxError::ProcessKilled`].
+    pub fn is_process_killed(&self) -> bool {
+        matches!(self, MailboxError::ProcessKilled)
+    }
 }
 
 /// Error returned when converting a [`MessageSignal`].
@@ -469,9 +492,13 @@ where
             entry,
         ) {
             Ok(id) => {
-                // If the captured variable is of size 0, we don't need to send it to another
-                // process.
-                if std::mem::size_of::<C>() == 0 {
+                // Processes of other modules always expect the capture, with a
+                // version prefix. Otherwise, if the captured variable is of size 0, we
+                // don't need to send it to another process.
+                if config.and_then(ProcessConfig::wasm_module).is_some() {
+                    send_plugin_arg::<C, S>(node_id, id, &capture);
+                    Ok(unsafe { Process::new(node_id, id) })
+                } else if std::mem::size_of::<C>() == 0 {
                     Ok(unsafe { Process::new(node_id, id) })
                 } else {
                     let child = unsafe { Process::<C, S>::new(node_id, id) };
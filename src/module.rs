@@ -1,3 +1,4 @@
+use std::io::{Read, Write};
 use std::u128;
 
 use serde::{Deserialize, Serialize};
@@ -5,8 +6,16 @@ use serde::{Deserialize, Serialize};
 use crate::error::LunaticError;
 use crate::host::api::distributed::node_id;
 use crate::host::{self};
-use crate::serializer::CanSerialize;
-use crate::{Process, ProcessConfig, Tag};
+use crate::mailbox::DATA_MESSAGE;
+use crate::serializer::{CanSerialize, MessageRw};
+use crate::{Mailbox, Process, ProcessConfig, Tag};
+
+/// Version of the encoding of the capture sent to a process spawned from a
+/// module set with [`ProcessConfig::set_wasm_module`].
+///
+/// It's the first byte of the message, followed by the capture serialized
+/// with the serializer of the mailbox.
+const PLUGIN_ARG_VERSION: u8 = 1;
 
 /// A compiled instance of a WebAssembly module.
 ///
@@ -181,6 +190,48 @@ impl WasmModule {
     }
 }
 
+/// Sends `capture` to the process `id`, spawned from a module set with
+/// [`ProcessConfig::set_wasm_module`].
+pub(crate) fn send_plugin_arg<C, S>(node_id: u64, id: u64, capture: &C)
+where
+    S: CanSerialize<C>,
+{
+    unsafe { host::api::message::create_data(Tag::none().id(), 0) };
+    MessageRw {}.write_all(&[PLUGIN_ARG_VERSION]).unwrap();
+    S::encode(capture).unwrap();
+    host::send(node_id, id);
+}
+
+/// Receives the capture sent by the parent and calls `entry`, used by
+/// [`plugin_entry!`](crate::plugin_entry).
+///
+/// # Panics
+///
+/// Panics if the capture was encoded with a version this crate doesn't
+/// understand, or can't be deserialized into `C`.
+#[doc(hidden)]
+pub fn plugin_entry<C, M, S>(entry: fn(C, Mailbox<M, S>))
+where
+    S: CanSerialize<C> + CanSerialize<M>,
+{
+    let message_type = unsafe { host::api::message::receive([].as_ptr(), 0, u64::MAX) };
+    assert_eq!(
+        message_type, DATA_MESSAGE,
+        "Expected the capture as first message"
+    );
+    let mut version = [0];
+    MessageRw {}.read_exact(&mut version).unwrap();
+    assert_eq!(
+        version[0], PLUGIN_ARG_VERSION,
+        "Unsupported version of the capture encoding"
+    );
+    let capture = match S::decode() {
+        Ok(capture) => capture,
+        Err(err) => panic!("Failed to deserialize the capture: {err:?}"),
+    };
+    entry(capture, unsafe { Mailbox::new() });
+}
+
 pub enum Param {
     I32(i32),
     I64(i64),
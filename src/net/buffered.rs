@@ -0,0 +1,159 @@
+use std::io::{self, BufRead, IntoInnerError, IoSliceMut, Read, Write};
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+use std::ptr;
+
+/// Adds buffering to a reader, e.g. a [`TcpStream`](super::TcpStream).
+///
+/// It implements [`BufRead`], with [`lines`](BufRead::lines),
+/// [`read_line`](BufRead::read_line) and [`read_until`](BufRead::read_until),
+/// and can be passed to readers like `serde_json::from_reader`.
+///
+/// Unlike the stream it wraps, it can't be sent to another process, because
+/// the buffered data would be lost. Use [`into_inner`](Self::into_inner) to
+/// get the stream back first. It's neither [`Send`] nor [`Sync`].
+pub struct BufReader<R> {
+    inner: io::BufReader<R>,
+    phantom: PhantomData<*const ()>,
+}
+
+impl<R: Read> BufReader<R> {
+    pub fn new(inner: R) -> Self {
+        BufReader {
+            inner: io::BufReader::new(inner),
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn with_capacity(capacity: usize, inner: R) -> Self {
+        BufReader {
+            inner: io::BufReader::with_capacity(capacity, inner),
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        self.inner.get_ref()
+    }
+
+    /// Returns the wrapped reader.
+    ///
+    /// Reading from it directly skips the data that is already buffered.
+    pub fn get_mut(&mut self) -> &mut R {
+        self.inner.get_mut()
+    }
+
+    /// Returns the data that was read from the reader, but not consumed yet.
+    pub fn buffer(&self) -> &[u8] {
+        self.inner.buffer()
+    }
+
+    /// Returns the wrapped reader, dropping the buffered data.
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+}
+
+impl<R: Read> Read for BufReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        self.inner.read_vectored(bufs)
+    }
+}
+
+impl<R: Read> BufRead for BufReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
+    }
+}
+
+/// Adds buffering to a writer, e.g. a [`TcpStream`](super::TcpStream).
+///
+/// Data is written to the writer once the buffer is full, or on
+/// [`flush`](Write::flush). Dropping it flushes the buffer and the writer,
+/// ignoring errors, and drops the writer, closing a stream once no other
+/// handles to it are left. Call `flush` before to handle the errors.
+///
+/// Unlike the stream it wraps, it can't be sent to another process, because
+/// the buffered data would be lost. Use [`into_inner`](Self::into_inner) to
+/// get the stream back first. It's neither [`Send`] nor [`Sync`].
+pub struct BufWriter<W: Write> {
+    inner: io::BufWriter<W>,
+    phantom: PhantomData<*const ()>,
+}
+
+impl<W: Write> BufWriter<W> {
+    pub fn new(inner: W) -> Self {
+        BufWriter {
+            inner: io::BufWriter::new(inner),
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn with_capacity(capacity: usize, inner: W) -> Self {
+        BufWriter {
+            inner: io::BufWriter::with_capacity(capacity, inner),
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.inner.get_ref()
+    }
+
+    /// Returns the wrapped writer.
+    ///
+    /// Writing to it directly puts the data in front of the buffered data.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.get_mut()
+    }
+
+    /// Returns the data that wasn't written to the writer yet.
+    pub fn buffer(&self) -> &[u8] {
+        self.inner.buffer()
+    }
+
+    /// Writes the buffered data and returns the wrapped writer.
+    ///
+    /// If writing fails, the error contains the buffered writer.
+    pub fn into_inner(self) -> Result<W, IntoInnerError<io::BufWriter<W>>> {
+        let this = ManuallyDrop::new(self);
+        // Safety: `this` is never used or dropped again, so `inner` is only
+        // owned by the copy.
+        let inner = unsafe { ptr::read(&this.inner) };
+        inner.into_inner()
+    }
+}
+
+impl<W: Write> Write for BufWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        self.inner.write_vectored(bufs)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.inner.write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for BufWriter<W> {
+    fn drop(&mut self) {
+        // `io::BufWriter` only writes the buffer on drop, without flushing
+        // the writer.
+        let _ = self.inner.flush();
+    }
+}
@@ -1,5 +1,6 @@
 //! Networking related functions.
 
+mod buffered;
 mod resolver;
 mod tcp_listener;
 mod tcp_stream;
@@ -13,12 +14,13 @@ use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV
 use std::option::IntoIter;
 use std::slice::Iter;
 
+pub use buffered::{BufReader, BufWriter};
 pub use resolver::{resolve, resolve_timeout, SocketAddrIterator};
 pub use tcp_listener::TcpListener;
 pub use tcp_stream::TcpStream;
 pub use tls_listener::TlsListener;
 pub use tls_stream::TlsStream;
-pub use udp::UdpSocket;
+pub use udp::{Datagram, UdpSocket};
 
 /// A trait for objects which can be converted or resolved to one or more
 /// [`SocketAddr`] values.
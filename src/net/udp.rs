@@ -2,9 +2,24 @@ use std::cell::UnsafeCell;
 use std::io::{Error, ErrorKind, Result};
 use std::net::SocketAddr;
 
+use serde::{Deserialize, Serialize};
+
 use super::SocketAddrIterator;
 use crate::error::LunaticError;
-use crate::host;
+use crate::serializer::CanSerialize;
+use crate::{host, Process, Tag};
+
+/// The largest payload a UDP datagram can carry.
+const MAX_DATAGRAM_SIZE: usize = 65_507;
+
+/// A datagram received by [`UdpSocket::recv_into_mailbox`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Datagram {
+    /// The payload of the datagram.
+    pub data: Vec<u8>,
+    /// The address the datagram was sent from.
+    pub peer: SocketAddr,
+}
 
 /// A UDP socket.
 ///
@@ -539,7 +554,53 @@ impl UdpSocket {
         })
     }
 
-    /// Dummy fn - This is just to make porting from std easier?
+    /// Receives datagrams on the socket and forwards each of them to
+    /// `process` as a [`Datagram`] message tagged with `tag`.
+    ///
+    /// Receiving only blocks the current process, but a process waiting for
+    /// datagrams can't handle any other messages. Sockets can't be sent to
+    /// other processes, so a process that needs to handle datagrams next to
+    /// other messages should spawn a process binding the socket and calling
+    /// this function, and receive the datagrams from its own mailbox with
+    /// [`Mailbox::tag_receive`](crate::Mailbox::tag_receive).
+    ///
+    /// Only returns if receiving fails, with the error.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use lunatic::net::{Datagram, UdpSocket};
+    /// use lunatic::{Mailbox, Process, Tag};
+    ///
+    /// #[lunatic::main]
+    /// fn main(mailbox: Mailbox<Datagram>) {
+    ///     let tag = Tag::new();
+    ///     let this = mailbox.this();
+    ///     Process::spawn_link((this, tag), |(parent, tag), _: Mailbox<()>| {
+    ///         let socket = UdpSocket::bind("127.0.0.1:34254").unwrap();
+    ///         socket.recv_into_mailbox(parent, tag).unwrap();
+    ///     });
+    ///     let datagram = mailbox.tag_receive(&[tag]);
+    ///     println!("{} bytes from {}", datagram.data.len(), datagram.peer);
+    /// }
+    /// ```
+    pub fn recv_into_mailbox<S>(&self, process: Process<Datagram, S>, tag: Tag) -> Result<()>
+    where
+        S: CanSerialize<Datagram>,
+    {
+        let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+        loop {
+            let (len, peer) = self.recv_from(&mut buf)?;
+            let data = buf[..len].to_vec();
+            process.tag_send(tag, Datagram { data, peer });
+        }
+    }
+
+    /// Has no effect, receiving on a socket only blocks the current process.
+    ///
+    /// Exists to make porting code from `std` easier. To receive datagrams
+    /// without blocking a process that also handles other messages, see
+    /// [`UdpSocket::recv_into_mailbox`].
     pub fn set_nonblocking(&self, _: bool) -> Result<()> {
         Ok(())
     }
@@ -0,0 +1,48 @@
+//! Information about the currently running process.
+
+use std::any::type_name;
+use std::cell::Cell;
+
+pub use crate::ap::park::{park, unpark};
+use crate::ap::{AbstractProcess, ProcessRef};
+pub use crate::ap::{ExitReason, TrapInfo};
+use crate::host;
+
+crate::process_local! {
+    // Type name of the `AbstractProcess` running in this process, set before
+    // `init` is called.
+    static RUNNING: Cell<Option<&'static str>> = Cell::new(None);
+}
+
+/// Returns a reference to the [`AbstractProcess`] of type `T` running in the
+/// current process.
+///
+/// Unlike [`Config::self_ref`](crate::ap::Config::self_ref) and
+/// [`State::self_ref`](crate::ap::State::self_ref) it can be called from
+/// anywhere inside of the process, e.g. from a function called by a handler.
+///
+/// # Panics
+///
+/// Panics if the current process isn't running an `AbstractProcess` of type
+/// `T`.
+#[track_caller]
+pub fn this_ref<T: AbstractProcess>() -> ProcessRef<T> {
+    match RUNNING.with(Cell::get) {
+        Some(running) if running == type_name::<T>() => unsafe {
+            ProcessRef::new(host::node_id(), host::process_id())
+        },
+        Some(running) => panic!(
+            "this_ref::<{}>() called inside of a `{running}` process",
+            type_name::<T>()
+        ),
+        None => panic!(
+            "this_ref::<{}>() called outside of an abstract process",
+            type_name::<T>()
+        ),
+    }
+}
+
+/// Marks the current process as running an `AbstractProcess` of type `T`.
+pub(crate) fn enter<T: AbstractProcess>() {
+    RUNNING.with(|running| running.set(Some(type_name::<T>())));
+}
@@ -346,6 +346,10 @@ where
     where
         S: CanSerialize<ProtocolCapture<C>>,
     {
+        assert!(
+            config.and_then(ProcessConfig::wasm_module).is_none(),
+            "Protocol processes can't be spawned from a WASM module"
+        );
         let entry = entry as usize as i32;
         let node_id = node.unwrap_or_else(host::node_id);
 
@@ -0,0 +1,152 @@
+//! Publish-subscribe messaging between processes.
+//!
+//! Each [`Topic`] is identified by a string key and the type of messages
+//! published on it. Messages are broadcast by a broker process that is started
+//! the first time a topic is used. The broker monitors all subscribers and
+//! removes them as soon as they die.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use lunatic::pubsub::Topic;
+//! use lunatic::Mailbox;
+//!
+//! #[lunatic::main]
+//! fn main(mailbox: Mailbox<String>) {
+//!     let topic = Topic::<String>::new("chat");
+//!     let subscription = topic.subscribe(&mailbox);
+//!     topic.publish("hello".to_owned());
+//!     assert_eq!(mailbox.receive(), "hello");
+//!     subscription.unsubscribe();
+//! }
+//! ```
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::serializer::{Bincode, CanSerialize};
+use crate::{host, LunaticError, Mailbox, MessageSignal, Process, ProcessDiedSignal, Tag};
+
+/// A named channel that broadcasts messages of type `M` to all subscribers.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Topic<M> {
+    broker: Process<BrokerMessage<M>>,
+}
+
+impl<M> Topic<M>
+where
+    M: Serialize + DeserializeOwned + 'static,
+{
+    /// Returns the topic under `key`, starting its broker if it's not running
+    /// yet.
+    ///
+    /// Topics with the same key but a different message type are independent
+    /// of each other.
+    pub fn new(key: &str) -> Self {
+        let name = format!("lunatic::pubsub::{key}");
+        let broker = match Process::name_spawn(&name, (), broker::<M>) {
+            Ok(broker) => broker,
+            Err(LunaticError::NameAlreadyRegistered(node_id, id)) => unsafe {
+                Process::new(node_id, id)
+            },
+            Err(err) => panic!("Failed to start broker of topic `{key}`: {err}"),
+        };
+        Topic { broker }
+    }
+
+    /// Subscribes the current process to the topic.
+    ///
+    /// Published messages are delivered to `mailbox`. The subscription is
+    /// removed when [`Subscription::unsubscribe`] is called or the process
+    /// dies. Subscribing multiple times has the same effect as subscribing
+    /// once.
+    pub fn subscribe(&self, mailbox: &Mailbox<M>) -> Subscription<M> {
+        let subscriber = mailbox.this();
+        self.broker.send(BrokerMessage::Subscribe(subscriber));
+        Subscription {
+            broker: self.broker,
+            subscriber,
+        }
+    }
+
+    /// Sends `message` to all current subscribers.
+    pub fn publish(&self, message: M) {
+        self.broker.send(BrokerMessage::Publish(message));
+    }
+}
+
+impl<M> Clone for Topic<M> {
+    fn clone(&self) -> Self {
+        Topic {
+            broker: self.broker,
+        }
+    }
+}
+
+/// A subscription to a [`Topic`].
+///
+/// Dropping the subscription doesn't unsubscribe the process, use
+/// [`unsubscribe`](Subscription::unsubscribe) for that.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Subscription<M> {
+    broker: Process<BrokerMessage<M>>,
+    subscriber: Process<M>,
+}
+
+impl<M> Subscription<M>
+where
+    M: Serialize + DeserializeOwned + 'static,
+{
+    /// Stops receiving messages published on the topic.
+    pub fn unsubscribe(self) {
+        self.broker
+            .send(BrokerMessage::Unsubscribe(self.subscriber));
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "M: Serialize + DeserializeOwned")]
+enum BrokerMessage<M> {
+    Subscribe(Process<M>),
+    Unsubscribe(Process<M>),
+    Publish(M),
+}
+
+/// Entry point of the broker process.
+fn broker<M>(_: (), mailbox: Mailbox<BrokerMessage<M>, Bincode>)
+where
+    M: Serialize + DeserializeOwned + 'static,
+{
+    let mailbox = mailbox.monitorable();
+    let mut subscribers: Vec<Process<M>> = Vec::new();
+    loop {
+        match mailbox.receive() {
+            MessageSignal::Message(BrokerMessage::Subscribe(subscriber)) => {
+                if !subscribers.contains(&subscriber) {
+                    mailbox.monitor(subscriber);
+                    subscribers.push(subscriber);
+                }
+            }
+            MessageSignal::Message(BrokerMessage::Unsubscribe(subscriber)) => {
+                if subscribers.contains(&subscriber) {
+                    mailbox.stop_monitoring(subscriber);
+                    subscribers.retain(|s| s != &subscriber);
+                }
+            }
+            MessageSignal::Message(BrokerMessage::Publish(message)) => {
+                for subscriber in &subscribers {
+                    // Encode the message for each subscriber, without requiring it to be
+                    // `Clone`.
+                    unsafe { host::api::message::create_data(Tag::none().id(), 0) };
+                    <Bincode as CanSerialize<M>>::encode(&message).unwrap();
+                    host::send(subscriber.node_id(), subscriber.id());
+                }
+            }
+            MessageSignal::Signal(ProcessDiedSignal(id)) => {
+                subscribers.retain(|subscriber| subscriber.id() != id);
+            }
+        }
+    }
+}
@@ -26,6 +26,10 @@ impl SqliteClient {
         })
     }
 
+    pub(super) fn from_id(conn: u64) -> Self {
+        SqliteClient { conn }
+    }
+
     pub(crate) fn id(&self) -> u64 {
         self.conn
     }
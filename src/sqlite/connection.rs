@@ -0,0 +1,296 @@
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+use lunatic_sqlite_api::guest_api::sqlite_guest_bindings as bindings;
+
+use super::client::SqliteClient;
+use super::error::{SqliteCode, SqliteError};
+use super::query::{Query, Statement};
+use super::value::Value;
+
+/// Connection to a Sqlite database, owned by the current process.
+///
+/// The database file is opened by the host on behalf of the process, and the
+/// connection only holds the id of that resource. It's neither `Send` nor
+/// `Sync`, each process needs to open its own connection.
+///
+/// # Example
+///
+/// ```no_run
+/// use lunatic::sqlite::Connection;
+///
+/// let conn = Connection::open(":memory:")?;
+/// conn.execute("CREATE TABLE users (id INTEGER, name TEXT)", ())?;
+/// conn.execute("INSERT INTO users VALUES (?, ?)", (1, "Ana"))?;
+/// let names = conn.query_map("SELECT name FROM users", (), |row| row.get::<String>(0))?;
+/// # Ok::<(), lunatic::sqlite::SqliteError>(())
+/// ```
+#[derive(Debug)]
+pub struct Connection {
+    client: SqliteClient,
+    phantom: PhantomData<*const ()>,
+}
+
+impl Connection {
+    /// Opens the Sqlite database at `path`, creating it if it doesn't exist.
+    ///
+    /// Use `":memory:"` for an in-memory database.
+    pub fn open(path: &str) -> Result<Self, SqliteError> {
+        let mut connection_id = 0;
+        let result = unsafe { bindings::open(path.as_ptr(), path.len(), &mut connection_id) };
+        if result != 0 {
+            return Err(SqliteError::from_code(result as u32).unwrap_or_default());
+        }
+        Ok(Connection {
+            client: SqliteClient::from_id(connection_id as u64),
+            phantom: PhantomData,
+        })
+    }
+
+    /// Returns the underlying client.
+    pub fn client(&self) -> SqliteClient {
+        self.client
+    }
+
+    /// Executes `sql` with `params` bound to it, and returns the number of
+    /// changed rows.
+    pub fn execute<P: Params>(&self, sql: &str, params: P) -> Result<usize, SqliteError> {
+        let mut rows = self.prepare(sql, params).execute_iter();
+        while rows.try_next().map_err(|code| self.error(code))?.is_some() {}
+        Ok(unsafe { bindings::sqlite3_changes(self.client.id()) } as usize)
+    }
+
+    /// Executes the query `sql` with `params` bound to it, and maps each
+    /// returned row with `f`.
+    ///
+    /// Stops at the first row that `f` fails to map.
+    pub fn query_map<P, T, F>(&self, sql: &str, params: P, mut f: F) -> Result<Vec<T>, SqliteError>
+    where
+        P: Params,
+        F: FnMut(&Row) -> Result<T, SqliteError>,
+    {
+        let mut rows = self.prepare(sql, params).execute_iter();
+        let mut mapped = Vec::new();
+        while let Some(values) = rows.try_next().map_err(|code| self.error(code))? {
+            mapped.push(f(&Row { values })?);
+        }
+        Ok(mapped)
+    }
+
+    /// Runs `f` inside of a transaction.
+    ///
+    /// The transaction is committed if `f` returns `Ok`, and rolled back if it
+    /// returns `Err` or panics.
+    pub fn transaction<R, E, F>(&mut self, f: F) -> Result<R, E>
+    where
+        E: From<SqliteError>,
+        F: FnOnce(&Transaction) -> Result<R, E>,
+    {
+        self.execute("BEGIN", ())?;
+        let tx = Transaction {
+            conn: self,
+            finished: false,
+        };
+        match f(&tx) {
+            Ok(result) => {
+                tx.finish("COMMIT")?;
+                Ok(result)
+            }
+            Err(err) => {
+                tx.finish("ROLLBACK")?;
+                Err(err)
+            }
+        }
+    }
+
+    fn prepare<P: Params>(&self, sql: &str, params: P) -> Statement {
+        params.bind(self.client.prepare_query(sql))
+    }
+
+    /// Returns the error of the last failed step, with the message reported
+    /// by Sqlite if there is one.
+    fn error(&self, code: SqliteCode) -> SqliteError {
+        SqliteError::last_(self.client.id()).unwrap_or(SqliteError {
+            code,
+            message: None,
+        })
+    }
+}
+
+/// A transaction started with [`Connection::transaction`].
+///
+/// It dereferences to the [`Connection`], all statements executed through it
+/// are part of the transaction.
+pub struct Transaction<'conn> {
+    conn: &'conn Connection,
+    finished: bool,
+}
+
+impl Transaction<'_> {
+    fn finish(mut self, sql: &str) -> Result<(), SqliteError> {
+        self.finished = true;
+        self.conn.execute(sql, ()).map(|_| ())
+    }
+}
+
+impl Deref for Transaction<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        // `f` panicked, don't leave the transaction open.
+        if !self.finished {
+            let _ = self.conn.execute("ROLLBACK", ());
+        }
+    }
+}
+
+/// A row returned by [`Connection::query_map`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    values: Vec<Value>,
+}
+
+impl Row {
+    /// Returns the value of the column at index `col`, converted to `T`.
+    ///
+    /// Fails with [`SqliteCode::Range`] if the column doesn't exist, and with
+    /// [`SqliteCode::Mismatch`] if the value can't be converted.
+    pub fn get<T: FromSql>(&self, col: usize) -> Result<T, SqliteError> {
+        let value = self.values.get(col).ok_or_else(|| SqliteError {
+            code: SqliteCode::Range,
+            message: Some(format!("column index {col} out of range")),
+        })?;
+        T::from_sql(value).ok_or_else(|| SqliteError {
+            code: SqliteCode::Mismatch,
+            message: Some(format!(
+                "can't convert {value:?} in column {col} to {}",
+                std::any::type_name::<T>()
+            )),
+        })
+    }
+
+    /// Returns the number of columns.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns `true` if the row has no columns.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// Types that can be read from a column of a [`Row`].
+pub trait FromSql: Sized {
+    /// Converts `value`, returning `None` if it has an incompatible type.
+    fn from_sql(value: &Value) -> Option<Self>;
+}
+
+impl FromSql for Value {
+    fn from_sql(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+impl FromSql for i64 {
+    fn from_sql(value: &Value) -> Option<Self> {
+        value.as_int_any()
+    }
+}
+
+impl FromSql for i32 {
+    fn from_sql(value: &Value) -> Option<Self> {
+        value.as_int_any()?.try_into().ok()
+    }
+}
+
+impl FromSql for u32 {
+    fn from_sql(value: &Value) -> Option<Self> {
+        value.as_int_any()?.try_into().ok()
+    }
+}
+
+impl FromSql for bool {
+    fn from_sql(value: &Value) -> Option<Self> {
+        value.as_int_any().map(|value| value != 0)
+    }
+}
+
+impl FromSql for f64 {
+    fn from_sql(value: &Value) -> Option<Self> {
+        match value {
+            Value::Double(value) => Some(*value),
+            _ => value.as_int_any().map(|value| value as f64),
+        }
+    }
+}
+
+impl FromSql for String {
+    fn from_sql(value: &Value) -> Option<Self> {
+        value.as_text().cloned()
+    }
+}
+
+impl FromSql for Vec<u8> {
+    fn from_sql(value: &Value) -> Option<Self> {
+        value.as_blob().cloned()
+    }
+}
+
+impl<T: FromSql> FromSql for Option<T> {
+    fn from_sql(value: &Value) -> Option<Self> {
+        match value {
+            Value::Null => Some(None),
+            value => T::from_sql(value).map(Some),
+        }
+    }
+}
+
+/// Parameters bound to a statement, by position.
+///
+/// Implemented for `()`, slices and vectors of [`Value`], and tuples of up to
+/// 8 values convertible into [`Value`].
+pub trait Params {
+    /// Binds the parameters to `statement`.
+    fn bind(self, statement: Statement) -> Statement;
+}
+
+impl Params for &[Value] {
+    fn bind(self, statement: Statement) -> Statement {
+        self.iter()
+            .fold(statement, |statement, value| statement.bind(value.clone()))
+    }
+}
+
+impl Params for Vec<Value> {
+    fn bind(self, statement: Statement) -> Statement {
+        self.into_iter().fold(statement, Statement::bind)
+    }
+}
+
+macro_rules! impl_params {
+    ($($t:ident $i:tt),*) => {
+        impl<$($t: Into<Value>),*> Params for ($($t,)*) {
+            #[allow(unused_variables)]
+            fn bind(self, statement: Statement) -> Statement {
+                statement $(.bind(self.$i))*
+            }
+        }
+    };
+}
+
+impl_params!();
+impl_params!(T0 0);
+impl_params!(T0 0, T1 1);
+impl_params!(T0 0, T1 1, T2 2);
+impl_params!(T0 0, T1 1, T2 2, T3 3);
+impl_params!(T0 0, T1 1, T2 2, T3 3, T4 4);
+impl_params!(T0 0, T1 1, T2 2, T3 3, T4 4, T5 5);
+impl_params!(T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6);
+impl_params!(T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6, T7 7);
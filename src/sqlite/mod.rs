@@ -13,13 +13,27 @@
 //!     // ...
 //! }
 //! ```
+//!
+//! [`Connection`] adds typed parameters, rows and transactions on top of the
+//! client:
+//!
+//! ```
+//! use lunatic::sqlite::Connection;
+//!
+//! let mut conn = Connection::open(":memory:")?;
+//! conn.execute("create table users (name text)", ())?;
+//! conn.transaction(|tx| tx.execute("insert into users values (?)", ("Ana",)))?;
+//! let names = conn.query_map("select name from users", (), |row| row.get::<String>(0))?;
+//! ```
 
 mod client;
+mod connection;
 mod error;
 mod query;
 mod value;
 
 pub use client::*;
+pub use connection::*;
 pub use error::*;
 pub use query::*;
 pub use value::*;
@@ -104,18 +104,18 @@ pub struct QueryIter {
     statement: Statement,
 }
 
-impl Iterator for QueryIter {
-    type Item = Vec<Value>;
-
-    fn next(&mut self) -> Option<Self::Item> {
+impl QueryIter {
+    /// Advances to the next row, returning the code of the failed step
+    /// instead of panicking.
+    pub(super) fn try_next(&mut self) -> Result<Option<Vec<Value>>, SqliteCode> {
         match SqliteCode::from_code(unsafe { bindings::sqlite3_step(self.statement.id) }) {
-            Some(SqliteCode::Done) => return None,
+            Some(SqliteCode::Done) => return Ok(None),
             Some(SqliteCode::Row) => {}
-            Some(code) => panic!("unexpected code {code:?} from lunatic::sqlite::sqlite3_step. Expected SQLITE_DONE or SQLITE_ROW"),
-            None => panic!("unexpected code from lunatic::sqlite::sqlite3_step. Expected SQLITE_DONE or SQLITE_ROW"),
+            Some(code) => return Err(code),
+            None => return Err(SqliteCode::Error),
         }
 
-        Some(
+        Ok(Some(
             call_host_alloc::<SqliteRow>(|len_ptr| unsafe {
                 bindings::read_row(self.statement.id, len_ptr)
             })
@@ -124,6 +124,17 @@ impl Iterator for QueryIter {
             .into_iter()
             .map(|value| value.into())
             .collect(),
-        )
+        ))
+    }
+}
+
+impl Iterator for QueryIter {
+    type Item = Vec<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.try_next() {
+            Ok(row) => row,
+            Err(code) => panic!("unexpected code {code:?} from lunatic::sqlite::sqlite3_step. Expected SQLITE_DONE or SQLITE_ROW"),
+        }
     }
 }
@@ -1,13 +1,44 @@
+mod factory;
+mod handoff;
+mod health;
+mod hooks;
+mod siblings;
+mod tree;
+
+use std::any::{Any, TypeId};
+use std::collections::VecDeque;
+use std::fmt;
 use std::marker::PhantomData;
+use std::mem;
+use std::time::{Duration, Instant, SystemTime};
 
-use crate::ap::handlers::{DeferredRequest, Request};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::ap::handlers::{DeferredRequest, Message, Request};
 use crate::ap::{
-    AbstractProcess, Config, DeferredRequestHandler, DeferredResponse, ProcessRef, RequestHandler,
-    State,
+    AbstractProcess, Config, DeferredRequestHandler, DeferredResponse, ExitNotice, ExitReason,
+    MessageHandler, ProcessRef, RequestHandler, StartupError, State, TrapInfo,
 };
 use crate::function::process::{process_name, ProcessType};
+use crate::mailbox::{LinkDiedSignal, MessageSignal};
 use crate::serializer::Bincode;
-use crate::{host, Tag};
+use crate::{distributed, host, Mailbox, Process, ProcessConfig, Tag};
+
+pub use self::factory::{
+    CountInstances, FactoryRef, FactoryState, FactorySupervisor, SpawnInstance, TerminateInstance,
+    WhichChildren,
+};
+pub use self::handoff::{Handoff, RestartArg, SaveState, StateHandoff};
+use self::health::{child_health, HealthFn};
+pub use self::health::{GetHealth, Health, HealthReport};
+pub use self::hooks::ChildHooks;
+use self::hooks::RawHooks;
+pub use self::siblings::{SiblingRefs, Siblings};
+pub use self::tree::{
+    GetTreeChild, SupervisorTree, TreeRef, TreeStartError, TreeState, TreeSupervisor,
+};
 
 /// A `Supervisor` can detect failures (panics) inside
 /// [`AbstractProcesses`](AbstractProcess) and restart them.
@@ -68,10 +99,27 @@ where
     type Arg = T::Arg;
     type State = SupervisorConfig<T>;
     type Serializer = Bincode;
-    type Handlers = (Request<GetChildren>, DeferredRequest<ShutdownSubscribe>);
-    type StartupError = ();
-
-    fn init(config: Config<Self>, arg: T::Arg) -> Result<Self::State, ()> {
+    type Handlers = (
+        Request<GetChildren>,
+        DeferredRequest<ShutdownSubscribe>,
+        Request<StartChild>,
+        Request<TerminateChild>,
+        Request<GetChild>,
+        Request<GetChildInfo>,
+        Request<LookupChild>,
+        Request<SubscribeEvents>,
+        Request<StopChild>,
+        Request<RestartChild>,
+        Request<ChangeChildSpec>,
+        Message<DelayedRestart>,
+        Message<ChildExit>,
+        Message<CheckNodes>,
+        Message<SaveState>,
+        Request<GetHealth>,
+    );
+    type StartupError = SupervisorStartError;
+
+    fn init(config: Config<Self>, arg: T::Arg) -> Result<Self::State, SupervisorStartError> {
         // Supervisor shouldn't die if the children die
         config.die_if_link_dies(false);
 
@@ -87,7 +135,29 @@ where
             );
         }
 
-        sup_config.start_link();
+        let ids = T::Children::child_ids(&sup_config);
+        for (index, id) in ids.iter().enumerate() {
+            if ids[..index].contains(id) {
+                panic!(
+                    "Supervisor {} has more than one child with the id `{id}`",
+                    std::any::type_name::<T>()
+                );
+            }
+        }
+
+        sup_config.start_link()?;
+        if sup_config.children_nodes.is_some() {
+            let this = unsafe { ProcessRef::<T>::new(host::node_id(), host::process_id()) };
+            this.delayed_send(CheckNodes, sup_config.node_check);
+        }
+        for index in 0..sup_config.restart_state.len() {
+            sup_config.notify_child(ChildRef::Static(index), |child| {
+                SupervisorEvent::ChildStarted {
+                    child,
+                    time: SystemTime::now(),
+                }
+            });
+        }
 
         Ok(sup_config)
     }
@@ -96,8 +166,8 @@ where
         config.terminate();
     }
 
-    fn handle_link_death(mut sup_config: State<Self>, tag: Tag) {
-        T::Children::handle_failure(&mut sup_config, tag);
+    fn handle_link_death(mut sup_config: State<Self>, info: TrapInfo) {
+        sup_config.handle_exit(info.tag, ExitReason::Unknown);
     }
 }
 
@@ -161,14 +231,1265 @@ where
     pub fn children(&self) -> <<T as Supervisor>::Children as Supervisable<T>>::Processes {
         self.request(GetChildren)
     }
+
+    /// Returns the static child at position `I`, typed after the children of
+    /// the supervisor.
+    ///
+    /// The supervisor is asked for the current process of the child, also
+    /// after a restart. Positions outside of the children fail to compile.
+    pub fn child<const I: usize>(&self) -> ProcessRef<ChildType<T, I>>
+    where
+        <<T as Supervisor>::Children as Supervisable<T>>::Processes: ChildAt<I>,
+    {
+        self.children().child()
+    }
+
+    /// Returns the static child of type `C`.
+    ///
+    /// The position `P` is inferred, the call is written as
+    /// `sup.child_of::<C, _>()`. It fails to compile if no child or more than
+    /// one child has the type `C`, use [`child`](Self::child) for those.
+    pub fn child_of<C, P>(&self) -> ProcessRef<C>
+    where
+        C: AbstractProcess,
+        <<T as Supervisor>::Children as Supervisable<T>>::Processes: ChildOf<C, P>,
+    {
+        self.children().child_of()
+    }
+
+    /// Returns the current process of the child with the `id`, static or
+    /// dynamic.
+    ///
+    /// Returns `None` if there is no such child, or if it isn't of type `C`.
+    pub fn child_by_id<C: AbstractProcess>(&self, id: ChildId) -> Option<ProcessRef<C>> {
+        let (node_id, process_id) = self.request(GetChild {
+            id,
+            type_name: std::any::type_name::<C>().to_owned(),
+        })?;
+        Some(unsafe { ProcessRef::new(node_id, process_id) })
+    }
+
+    /// Starts a child of type `C` with `arg` and supervises it next to the
+    /// static children.
+    ///
+    /// Returns an id for the child that stays the same when it's restarted,
+    /// together with a reference to the process. Dynamic children are
+    /// restarted one by one after a failure, independent of the strategy, and
+    /// count towards the maximum restart intensity. The supervisor blocks
+    /// until the `init` function of the child finishes.
+    ///
+    /// The argument is sent to the supervisor encoded with `Bincode`. Like
+    /// spawned processes, the supervisor needs to run the same module.
+    pub fn start_child<C>(&self, arg: C::Arg) -> Result<(ChildId, ProcessRef<C>), StartupError<C>>
+    where
+        C: AbstractProcess,
+        C::Arg: Serialize + DeserializeOwned,
+        C::StartupError: Serialize + DeserializeOwned + 'static,
+    {
+        let request = StartChild {
+            start: start_dynamic::<C> as StartChildFn as usize,
+            shutdown: shutdown_dynamic::<C> as fn(u64, u64) as usize,
+            health: child_health::<C> as HealthFn as usize,
+            type_name: std::any::type_name::<C>().to_owned(),
+            arg: bincode::serialize(&arg).unwrap(),
+        };
+        match self.request(request) {
+            Ok((id, node_id, process_id)) => {
+                let child = unsafe { ProcessRef::new(node_id, process_id) };
+                Ok((ChildId::Dynamic(id), child))
+            }
+            Err(err) => Err(bincode::deserialize(&err).unwrap()),
+        }
+    }
+
+    /// Shuts down the dynamic child with the `id` and stops supervising it.
+    ///
+    /// Returns `false` if there is no such child. Static children can't be
+    /// terminated, use [`stop_child`](Self::stop_child) and
+    /// [`delete_child`](Self::delete_child) for them.
+    pub fn terminate_child(&self, id: ChildId) -> bool {
+        match id {
+            ChildId::Dynamic(id) => self.request(TerminateChild::Id(id)),
+            ChildId::Static(_) | ChildId::Named(_) => false,
+        }
+    }
+
+    /// Shuts down the dynamic child running as `child` and stops supervising
+    /// it.
+    ///
+    /// Returns `false` if `child` isn't a dynamic child of the supervisor, e.g.
+    /// because it was restarted since and runs as a different process now.
+    pub fn terminate_child_ref<C: AbstractProcess>(&self, child: &ProcessRef<C>) -> bool {
+        self.request(TerminateChild::Process(child.id()))
+    }
+
+    /// Returns the current process of the dynamic child with the `id`.
+    ///
+    /// Returns `None` if there is no such child, or if it isn't of type `C`.
+    pub fn dynamic_child<C: AbstractProcess>(&self, id: ChildId) -> Option<ProcessRef<C>> {
+        match id {
+            ChildId::Dynamic(_) => self.child_by_id(id),
+            ChildId::Static(_) | ChildId::Named(_) => None,
+        }
+    }
+
+    /// Returns information about all children, static children in start
+    /// order followed by dynamic children.
+    pub fn which_children(&self) -> Vec<ChildInfo> {
+        self.request(GetChildInfo)
+    }
+
+    /// Counts the children of the supervisor.
+    pub fn count_children(&self) -> ChildCounts {
+        let children = self.which_children();
+        ChildCounts {
+            specs: children.len(),
+            active: children
+                .iter()
+                .filter(|child| child.status == ChildStatus::Running)
+                .count(),
+            dynamic: children
+                .iter()
+                .filter(|child| matches!(child.child, ChildId::Dynamic(_)))
+                .count(),
+        }
+    }
+
+    /// Sends a [`SupervisorEvent`] to `listener` for each start, exit and
+    /// restart of a child.
+    ///
+    /// Events are sent as messages, a slow listener doesn't hold up the
+    /// supervisor. Listeners on the local node are dropped once they exit.
+    pub fn subscribe_events(&self, listener: Process<SupervisorEvent>) {
+        self.request(SubscribeEvents::Subscribe(listener));
+    }
+
+    /// Stops sending events to `listener`.
+    pub fn unsubscribe_events(&self, listener: Process<SupervisorEvent>) {
+        self.request(SubscribeEvents::Unsubscribe(listener));
+    }
+
+    /// Returns the running child of type `C` named `name`.
+    ///
+    /// Unlike [`ProcessRef::lookup`], the lookup is answered by the
+    /// supervisor after it finished handling earlier exits, so it never
+    /// returns a child that died or is still starting. Returns `None` if
+    /// there is no such child or it waits for its restart.
+    pub fn lookup_child<C: AbstractProcess>(&self, name: &str) -> Option<ProcessRef<C>> {
+        let (node_id, process_id) = self.request(LookupChild {
+            name: name.to_owned(),
+            type_name: std::any::type_name::<C>().to_owned(),
+        })?;
+        Some(unsafe { ProcessRef::new(node_id, process_id) })
+    }
+
+    /// Stops the child `child`, but keeps supervising it.
+    ///
+    /// Static children are stopped with their [`ChildShutdown`] policy. The
+    /// child isn't restarted until [`restart_child`](Self::restart_child) is
+    /// called, or until the strategy restarts it together with a failed
+    /// sibling. Unlike [`terminate_child`](Self::terminate_child), a dynamic
+    /// child keeps its id and can be restarted later.
+    pub fn stop_child(&self, child: ChildId) -> Result<(), SupervisorError> {
+        self.request(StopChild(child))
+    }
+
+    /// Stops the child `child` and starts it again with its original
+    /// argument, e.g. to make it read its configuration again.
+    ///
+    /// It also starts a child that was stopped with
+    /// [`stop_child`](Self::stop_child). The restart is handled by the
+    /// supervisor like a failure, so a child that crashes at the same time is
+    /// only started once. It doesn't count towards the maximum restart
+    /// intensity.
+    pub fn restart_child<C: AbstractProcess>(
+        &self,
+        child: ChildId,
+    ) -> Result<ProcessRef<C>, SupervisorError> {
+        let (node_id, process_id) = self.request(RestartChild {
+            child,
+            type_name: std::any::type_name::<C>().to_owned(),
+        })?;
+        Ok(unsafe { ProcessRef::new(node_id, process_id) })
+    }
+
+    /// Changes the argument, restart policy or hooks of the child `child`,
+    /// which is of type `C`.
+    ///
+    /// The changes are used by all following restarts, after a failure or
+    /// by [`restart_child`](Self::restart_child). With
+    /// [`ChildSpec::restart_now`] the child is restarted right away.
+    pub fn replace_child_spec<C>(
+        &self,
+        child: ChildId,
+        spec: ChildSpec<C>,
+    ) -> Result<(), SupervisorError>
+    where
+        C: AbstractProcess,
+        C::Arg: Serialize + DeserializeOwned,
+    {
+        self.request(ChangeChildSpec::Replace {
+            child,
+            type_name: std::any::type_name::<C>().to_owned(),
+            arg: spec.arg.map(|arg| bincode::serialize(&arg).unwrap()),
+            set_arg: set_arg::<C> as SetArgFn as usize,
+            restart: spec.restart,
+            hooks: spec.hooks.into(),
+            restart_now: spec.restart_now,
+        })
+    }
+
+    /// Removes the child `child` from the supervisor.
+    ///
+    /// The child needs to be stopped first, e.g. with
+    /// [`stop_child`](Self::stop_child), otherwise
+    /// [`SupervisorError::ChildRunning`] is returned. A deleted static child
+    /// keeps its position, but isn't started again, also not by the strategy,
+    /// and no longer shows up in [`which_children`](Self::which_children).
+    pub fn delete_child(&self, child: ChildId) -> Result<(), SupervisorError> {
+        self.request(ChangeChildSpec::Delete(child))
+    }
+}
+
+/// Error returned by [`ProcessRef::stop_child`],
+/// [`ProcessRef::restart_child`] and the other requests changing a single
+/// child.
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SupervisorError {
+    #[error("the supervisor has no child `{0}`")]
+    ChildNotFound(ChildId),
+    #[error("the child is of type `{0}`")]
+    WrongType(String),
+    #[error("the child failed to start: {0}")]
+    StartFailed(String),
+    #[error("the child is still running")]
+    ChildRunning,
+    #[error("the argument of the child can't be decoded: {0}")]
+    InvalidArg(String),
+}
+
+/// Changes to the spec of a child of type `C`, see
+/// [`ProcessRef::replace_child_spec`].
+pub struct ChildSpec<C: AbstractProcess> {
+    arg: Option<C::Arg>,
+    restart: Option<ChildRestart>,
+    hooks: ChildHooks,
+    restart_now: bool,
+}
+
+impl<C: AbstractProcess> ChildSpec<C> {
+    /// Creates a spec that doesn't change anything.
+    pub fn new() -> Self {
+        ChildSpec {
+            arg: None,
+            restart: None,
+            hooks: ChildHooks::new(),
+            restart_now: false,
+        }
+    }
+
+    /// Starts the child with `arg` from now on.
+    pub fn arg(mut self, arg: C::Arg) -> Self {
+        self.arg = Some(arg);
+        self
+    }
+
+    /// Replaces the restart policy of the child.
+    pub fn restart_policy(mut self, restart: ChildRestart) -> Self {
+        self.restart = Some(restart);
+        self
+    }
+
+    /// Replaces the `on_restart` hook of the child, see
+    /// [`ChildHooks::on_restart`].
+    pub fn on_restart(mut self, hook: fn(ChildId, u32)) -> Self {
+        self.hooks = self.hooks.on_restart(hook);
+        self
+    }
+
+    /// Replaces the `on_give_up` hook of the child, see
+    /// [`ChildHooks::on_give_up`].
+    pub fn on_give_up(mut self, hook: fn(ChildId)) -> Self {
+        self.hooks = self.hooks.on_give_up(hook);
+        self
+    }
+
+    /// Restarts the child right away, instead of using the changes on its
+    /// next restart.
+    pub fn restart_now(mut self) -> Self {
+        self.restart_now = true;
+        self
+    }
+}
+
+impl<C: AbstractProcess> Default for ChildSpec<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Replaces the argument behind the pointer with the encoded argument.
+type SetArgFn = fn(&[u8], *mut ()) -> Result<(), String>;
+
+fn set_arg<C>(arg: &[u8], target: *mut ()) -> Result<(), String>
+where
+    C: AbstractProcess,
+    C::Arg: DeserializeOwned,
+{
+    let arg: C::Arg = bincode::deserialize(arg).map_err(|err| err.to_string())?;
+    // Safety: The supervisor only passes a pointer to the argument of a child
+    // with the same type name as `C`.
+    unsafe { *(target as *mut C::Arg) = arg };
+    Ok(())
+}
+
+/// Error returned when a supervisor fails to start one of its children.
+///
+/// The children that started before the failed one are shut down again, in
+/// reverse order, before the supervisor returns the error. If the failed
+/// child is a nested supervisor, the error describes the child that failed
+/// inside of it.
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[error("supervisor child `{path}` failed to start: {cause}")]
+pub struct SupervisorStartError {
+    /// Position of the child of this supervisor that failed to start, or
+    /// contains the failed child.
+    pub index: usize,
+    /// Id of the child at `index`.
+    pub child: ChildId,
+    /// The names of the nested supervisors leading to the failed child and
+    /// its own name, separated by `/`. Children are named by their id if
+    /// they have one, by their registered name otherwise, or by their
+    /// position.
+    pub path: String,
+    /// Type name of the failed child.
+    pub type_name: String,
+    pub cause: ChildStartCause,
+    /// Positions of the children that started before the failure.
+    pub started: Vec<usize>,
+}
+
+impl SupervisorStartError {
+    /// Prepends the child at `index`, at which the error passed by.
+    fn within(
+        mut self,
+        index: usize,
+        child: ChildId,
+        name: Option<&String>,
+        started: Vec<usize>,
+    ) -> Self {
+        let name = match &child {
+            ChildId::Named(id) => id.clone(),
+            ChildId::Static(_) | ChildId::Dynamic(_) => {
+                name.cloned().unwrap_or_else(|| index.to_string())
+            }
+        };
+        self.path = if self.path.is_empty() {
+            name
+        } else {
+            format!("{name}/{}", self.path)
+        };
+        self.index = index;
+        self.child = child;
+        self.started = started;
+        self
+    }
+}
+
+/// Why a child failed to start, part of a [`SupervisorStartError`].
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChildStartCause {
+    #[error("`init` panicked")]
+    InitPanicked,
+    /// The `init` function didn't finish within the start timeout, see
+    /// [`SupervisorConfig::set_start_timeout`].
+    #[error("`init` timed out")]
+    TimedOut,
+    /// None of the nodes of the child is connected.
+    #[error("none of the nodes {0:?} is connected")]
+    NoNode(Vec<u64>),
+    /// The startup error of the child, formatted with `Debug`.
+    #[error("{0}")]
+    Failed(String),
+}
+
+/// Returns `true` if `C` is a supervisor, which reports the failures of its
+/// own children.
+fn is_supervisor<C>() -> bool
+where
+    C: AbstractProcess,
+    C::StartupError: 'static,
+{
+    TypeId::of::<C::StartupError>() == TypeId::of::<SupervisorStartError>()
+}
+
+/// Converts the startup error of the child `C` into the error of its
+/// supervisor, with an empty path.
+fn start_error<C>(err: StartupError<C>) -> SupervisorStartError
+where
+    C: AbstractProcess,
+    C::StartupError: 'static,
+{
+    let cause = match err {
+        StartupError::InitPanicked => ChildStartCause::InitPanicked,
+        StartupError::TimedOut => ChildStartCause::TimedOut,
+        StartupError::Custom(err) => {
+            if let Some(err) = (&err as &dyn Any).downcast_ref::<SupervisorStartError>() {
+                return err.clone();
+            }
+            ChildStartCause::Failed(format!("{err:?}"))
+        }
+        err => ChildStartCause::Failed(format!("{err:?}")),
+    };
+    SupervisorStartError {
+        index: 0,
+        child: ChildId::Static(0),
+        path: String::new(),
+        type_name: std::any::type_name::<C>().to_owned(),
+        cause,
+        started: Vec::new(),
+    }
+}
+
+/// A child of the supervisor, found by its [`ChildId`].
+#[derive(Clone, Copy)]
+enum ChildRef {
+    /// Position of a static child.
+    Static(usize),
+    Dynamic(DynamicId),
+}
+
+/// Number of a child started with [`ProcessRef::start_child`], part of its
+/// [`ChildId`].
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DynamicId(u64);
+
+/// A child of a supervisor, returned by [`ProcessRef::which_children`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChildInfo {
+    /// Id of the child, which stays the same across restarts.
+    pub child: ChildId,
+    /// Name the child is registered under.
+    pub name: Option<String>,
+    /// Type name of the child.
+    pub type_name: String,
+    /// Node of the process the child currently runs as.
+    pub node_id: u64,
+    /// Id of the process the child currently runs as.
+    pub process_id: u64,
+    pub status: ChildStatus,
+    /// How often the child was restarted, by its own exit or by the strategy.
+    pub restarts: u32,
+}
+
+impl ChildInfo {
+    /// Returns a reference to the process of the child.
+    ///
+    /// Returns `None` if the child isn't of type `C`.
+    pub fn process<C: AbstractProcess>(&self) -> Option<ProcessRef<C>> {
+        if self.type_name != std::any::type_name::<C>() {
+            return None;
+        }
+        Some(unsafe { ProcessRef::new(self.node_id, self.process_id) })
+    }
+}
+
+/// Identifies a child of a supervisor.
+///
+/// Unlike the id of its process, it stays the same when the child is
+/// restarted, so that events and errors of the same child can be matched up.
+/// [`ChildInfo`], [`SupervisorEvent`] and [`SupervisorStartError`] carry the
+/// id of a static child as [`Named`](Self::Named) if it was given one with
+/// [`SupervisorConfig::set_ids`], and as [`Static`](Self::Static) otherwise.
+/// Requests accept both forms for a named child.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChildId {
+    /// Position of a static child in [`Supervisor::Children`].
+    Static(usize),
+    /// Id of a static child set with [`SupervisorConfig::set_ids`].
+    Named(String),
+    /// A child started with [`ProcessRef::start_child`].
+    Dynamic(DynamicId),
+}
+
+impl ChildId {
+    /// Creates the id of a static child set with
+    /// [`SupervisorConfig::set_ids`].
+    pub fn named(id: impl Into<String>) -> Self {
+        ChildId::Named(id.into())
+    }
+}
+
+impl fmt::Display for ChildId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChildId::Static(index) => write!(f, "{index}"),
+            ChildId::Named(id) => write!(f, "{id}"),
+            ChildId::Dynamic(DynamicId(id)) => write!(f, "dynamic:{id}"),
+        }
+    }
+}
+
+/// Status of a child in a [`ChildInfo`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChildStatus {
+    Running,
+    /// The child exited and waits for its restart, see
+    /// [`SupervisorConfig::set_backoffs`].
+    Restarting,
+    /// The child exited and its restart policy didn't ask for a restart.
+    Exited,
+    /// The child was stopped with [`ProcessRef::stop_child`].
+    Stopped,
+}
+
+/// Number of children of a supervisor, returned by
+/// [`ProcessRef::count_children`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChildCounts {
+    /// All supervised children, static and dynamic.
+    pub specs: usize,
+    /// Children that are currently running.
+    pub active: usize,
+    /// Children started with [`ProcessRef::start_child`].
+    pub dynamic: usize,
+}
+
+/// Starts a dynamic child linked with the tag from its encoded argument,
+/// reporting a normal exit with the notice.
+///
+/// Returns the node and process id of the child, or the encoded startup
+/// error.
+type StartChildFn = fn(&[u8], Tag, ExitNotice) -> Result<(u64, u64), Vec<u8>>;
+
+fn start_dynamic<C>(arg: &[u8], tag: Tag, notice: ExitNotice) -> Result<(u64, u64), Vec<u8>>
+where
+    C: AbstractProcess,
+    C::Arg: DeserializeOwned,
+    C::StartupError: Serialize,
+{
+    let arg = bincode::deserialize(arg).unwrap();
+    match C::link_with(tag).notify_exit(notice).start(arg) {
+        Ok(child) => Ok((child.node_id(), child.id())),
+        Err(err) => Err(bincode::serialize(&err).unwrap()),
+    }
+}
+
+fn shutdown_dynamic<C: AbstractProcess>(node_id: u64, process_id: u64) {
+    let child = unsafe { ProcessRef::<C>::new(node_id, process_id) };
+    child.shutdown();
+}
+
+/// A child started with [`ProcessRef::start_child`].
+struct DynamicChild {
+    id: DynamicId,
+    type_name: String,
+    start: StartChildFn,
+    shutdown: fn(u64, u64),
+    health: HealthFn,
+    /// Argument of the child, encoded with `Bincode`.
+    arg: Vec<u8>,
+    restart: ChildRestart,
+    tag: Tag,
+    node_id: u64,
+    process_id: u64,
+    restarts: u32,
+    /// The child was stopped with [`ProcessRef::stop_child`].
+    stopped: bool,
+    /// The exit of the current process was handled.
+    exited: bool,
+    hooks: ChildHooks,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StartChild {
+    /// Pointer to [`start_dynamic`], instantiated for the type of the child.
+    start: usize,
+    /// Pointer to [`shutdown_dynamic`], instantiated for the type of the child.
+    shutdown: usize,
+    /// Pointer to [`child_health`], instantiated for the type of the child.
+    health: usize,
+    type_name: String,
+    arg: Vec<u8>,
+}
+impl<T> RequestHandler<StartChild> for T
+where
+    T: Supervisor,
+    T: AbstractProcess<State = SupervisorConfig<T>, Serializer = Bincode>,
+{
+    /// Id, node and process id of the child.
+    type Response = Result<(DynamicId, u64, u64), Vec<u8>>;
+
+    fn handle(mut state: State<Self>, request: StartChild) -> Self::Response {
+        state.start_dynamic_child(request)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum TerminateChild {
+    Id(DynamicId),
+    Process(u64),
+}
+impl<T> RequestHandler<TerminateChild> for T
+where
+    T: Supervisor,
+    T: AbstractProcess<State = SupervisorConfig<T>, Serializer = Bincode>,
+{
+    type Response = bool;
+
+    fn handle(mut state: State<Self>, request: TerminateChild) -> bool {
+        let index = state
+            .dynamic_children
+            .iter()
+            .position(|child| match request {
+                TerminateChild::Id(id) => child.id == id,
+                TerminateChild::Process(process_id) => child.process_id == process_id,
+            });
+        match index {
+            Some(index) => {
+                let child = state.dynamic_children.remove(index);
+                (child.shutdown)(child.node_id, child.process_id);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetChild {
+    id: ChildId,
+    type_name: String,
+}
+impl<T> RequestHandler<GetChild> for T
+where
+    T: Supervisor,
+    T: AbstractProcess<State = SupervisorConfig<T>, Serializer = Bincode>,
+{
+    /// Node and process id of the child.
+    type Response = Option<(u64, u64)>;
+
+    fn handle(state: State<Self>, request: GetChild) -> Option<(u64, u64)> {
+        let child = state.resolve(&request.id).ok()?;
+        state
+            .info(child)
+            .ok()
+            .filter(|info| info.type_name == request.type_name)
+            .map(|info| (info.node_id, info.process_id))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetChildInfo;
+impl<T> RequestHandler<GetChildInfo> for T
+where
+    T: Supervisor,
+    T: AbstractProcess<State = SupervisorConfig<T>, Serializer = Bincode>,
+{
+    type Response = Vec<ChildInfo>;
+
+    fn handle(state: State<Self>, _: GetChildInfo) -> Vec<ChildInfo> {
+        state.child_info()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LookupChild {
+    name: String,
+    type_name: String,
+}
+impl<T> RequestHandler<LookupChild> for T
+where
+    T: Supervisor,
+    T: AbstractProcess<State = SupervisorConfig<T>, Serializer = Bincode>,
+{
+    /// Node and process id of the child.
+    type Response = Option<(u64, u64)>;
+
+    fn handle(state: State<Self>, request: LookupChild) -> Option<(u64, u64)> {
+        state
+            .child_info()
+            .into_iter()
+            .find(|child| {
+                child.name.as_deref() == Some(request.name.as_str())
+                    && child.type_name == request.type_name
+                    && child.status == ChildStatus::Running
+            })
+            .map(|child| (child.node_id, child.process_id))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum SubscribeEvents {
+    Subscribe(Process<SupervisorEvent>),
+    Unsubscribe(Process<SupervisorEvent>),
+}
+impl<T> RequestHandler<SubscribeEvents> for T
+where
+    T: Supervisor,
+    T: AbstractProcess<State = SupervisorConfig<T>, Serializer = Bincode>,
+{
+    type Response = ();
+
+    fn handle(mut state: State<Self>, request: SubscribeEvents) {
+        match request {
+            SubscribeEvents::Subscribe(listener) => state.subscribe_events(listener),
+            SubscribeEvents::Unsubscribe(listener) => {
+                state.event_listeners.retain(|other| *other != listener)
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StopChild(ChildId);
+impl<T> RequestHandler<StopChild> for T
+where
+    T: Supervisor,
+    T: AbstractProcess<State = SupervisorConfig<T>, Serializer = Bincode>,
+{
+    type Response = Result<(), SupervisorError>;
+
+    fn handle(mut state: State<Self>, StopChild(child): StopChild) -> Self::Response {
+        let child = state.resolve(&child)?;
+        state.stop_child(child)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RestartChild {
+    child: ChildId,
+    type_name: String,
+}
+impl<T> RequestHandler<RestartChild> for T
+where
+    T: Supervisor,
+    T: AbstractProcess<State = SupervisorConfig<T>, Serializer = Bincode>,
+{
+    /// Node and process id of the new process.
+    type Response = Result<(u64, u64), SupervisorError>;
+
+    fn handle(mut state: State<Self>, request: RestartChild) -> Self::Response {
+        let child = state.resolve(&request.child)?;
+        state.restart_child(child, &request.type_name)
+    }
 }
 
+#[derive(Serialize, Deserialize)]
+pub enum ChangeChildSpec {
+    Replace {
+        child: ChildId,
+        type_name: String,
+        /// The argument, encoded with `Bincode`.
+        arg: Option<Vec<u8>>,
+        /// Pointer to [`set_arg`], instantiated for the type of the child.
+        set_arg: usize,
+        restart: Option<ChildRestart>,
+        hooks: RawHooks,
+        restart_now: bool,
+    },
+    Delete(ChildId),
+}
+impl<T> RequestHandler<ChangeChildSpec> for T
+where
+    T: Supervisor,
+    T: AbstractProcess<State = SupervisorConfig<T>, Serializer = Bincode>,
+{
+    type Response = Result<(), SupervisorError>;
+
+    fn handle(mut state: State<Self>, request: ChangeChildSpec) -> Self::Response {
+        match request {
+            ChangeChildSpec::Replace {
+                child,
+                type_name,
+                arg,
+                set_arg,
+                restart,
+                hooks,
+                restart_now,
+            } => {
+                // Safety: The pointer was created from the same function type
+                // in `ProcessRef::replace_child_spec`.
+                let set_arg: SetArgFn = unsafe { mem::transmute(set_arg) };
+                let child = state.resolve(&child)?;
+                state.replace_child_spec(child, &type_name, arg, set_arg, restart, hooks.into())?;
+                if restart_now {
+                    state.restart_child(child, &type_name)?;
+                }
+                Ok(())
+            }
+            ChangeChildSpec::Delete(child) => {
+                let child = state.resolve(&child)?;
+                state.delete_child(child)
+            }
+        }
+    }
+}
+
+/// Event sent by a supervisor to the listeners registered with
+/// [`ProcessRef::subscribe_events`].
+///
+/// Child events carry the [`ChildInfo`] at the time of the event, including
+/// its restart count. The time is taken by the supervisor when it handles
+/// the event, the exit of a child is noticed with a short delay.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SupervisorEvent {
+    /// A child was started, when the supervisor starts or with
+    /// [`ProcessRef::start_child`].
+    ChildStarted { child: ChildInfo, time: SystemTime },
+    /// A child failed.
+    ChildCrashed {
+        child: ChildInfo,
+        /// [`ExitReason::Panicked`] if a handler or `terminate` of the child
+        /// panicked, otherwise [`ExitReason::Unknown`], e.g. if the child was
+        /// killed.
+        reason: ExitReason,
+        time: SystemTime,
+    },
+    /// A child finished normally.
+    ChildExited { child: ChildInfo, time: SystemTime },
+    /// A child was restarted, after its own exit or by the strategy.
+    ChildRestarted { child: ChildInfo, time: SystemTime },
+    /// Children exited more often than allowed by
+    /// [`SupervisorConfig::set_max_restarts`], the supervisor shuts down the
+    /// remaining children and exits.
+    IntensityExceeded {
+        /// The child whose exit exceeded the limit.
+        child: ChildId,
+        time: SystemTime,
+    },
+    /// The supervisor shuts down.
+    ShuttingDown { time: SystemTime },
+}
+
+/// Restarts a child after its backoff, sent by the supervisor to itself.
+#[derive(Serialize, Deserialize)]
+pub struct DelayedRestart(Tag);
+impl<T> MessageHandler<DelayedRestart> for T
+where
+    T: Supervisor,
+    T: AbstractProcess<State = SupervisorConfig<T>, Serializer = Bincode>,
+{
+    fn handle(mut state: State<Self>, DelayedRestart(tag): DelayedRestart) {
+        // The strategy could have restarted the child already, after a
+        // sibling failed.
+        let pending = T::Children::child_index(&state, tag)
+            .is_some_and(|index| state.restart_state[index].pending);
+        if pending {
+            state.restart(tag);
+        }
+    }
+}
+
+/// Reports the exit of a child, see [`exit_notice`].
+///
+/// A local child sends it itself when it exits normally, a child on another
+/// node reports it to its watcher, which forwards it together with failures.
+#[derive(Serialize, Deserialize)]
+pub struct ChildExit {
+    tag: Tag,
+    reason: ExitReason,
+}
+impl<T> MessageHandler<ChildExit> for T
+where
+    T: Supervisor,
+    T: AbstractProcess<State = SupervisorConfig<T>, Serializer = Bincode>,
+{
+    fn handle(mut state: State<Self>, ChildExit { tag, reason }: ChildExit) {
+        state.handle_exit(tag, reason);
+    }
+}
+
+/// Checks that the nodes of remote children are still connected, sent by the
+/// supervisor to itself.
+#[derive(Serialize, Deserialize)]
+pub struct CheckNodes;
+impl<T> MessageHandler<CheckNodes> for T
+where
+    T: Supervisor,
+    T: AbstractProcess<State = SupervisorConfig<T>, Serializer = Bincode>,
+{
+    fn handle(mut state: State<Self>, _: CheckNodes) {
+        let local = host::node_id();
+        let nodes = distributed::nodes();
+        let lost: Vec<usize> = T::Children::child_info(&state)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, info)| info.node_id != local && !nodes.contains(&info.node_id))
+            .map(|(index, _)| index)
+            .collect();
+        // The watchers of these children went down with their node.
+        for index in lost {
+            if let Some(tag) = T::Children::child_tag_at(&state, index) {
+                state.handle_exit(tag, ExitReason::Unknown);
+            }
+        }
+        let interval = state.node_check;
+        state.self_ref().delayed_send(CheckNodes, interval);
+    }
+}
+
+/// Decides which children the supervisor restarts when a child fails.
 pub enum SupervisorStrategy {
+    /// Only the failed child is restarted.
     OneForOne,
+    /// All children are restarted. The other children are first stopped in
+    /// reverse start order with their [`ChildShutdown`] policy, so they can
+    /// run [`terminate`](AbstractProcess::terminate), then all children are
+    /// started again in order.
     OneForAll,
+    /// The failed child and the children started after it are restarted,
+    /// stopping them like [`OneForAll`](Self::OneForAll).
     RestForOne,
 }
 
+/// Decides if the supervisor restarts a child after it exits, see
+/// [`SupervisorConfig::set_restarts`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChildRestart {
+    /// The child is always restarted.
+    #[default]
+    Permanent,
+    /// The child is only restarted if it fails, not if it finishes normally.
+    Transient,
+    /// The child is never restarted and its exit doesn't count towards the
+    /// maximum restart intensity.
+    Temporary,
+}
+
+/// Decides if the normal exit of significant children shuts the supervisor
+/// down, see [`SupervisorConfig::set_auto_shutdown`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AutoShutdown {
+    /// The supervisor keeps running.
+    #[default]
+    Never,
+    /// The supervisor shuts down once any significant child exits normally.
+    AnySignificant,
+    /// The supervisor shuts down once all significant children exited, the
+    /// last of them normally.
+    AllSignificant,
+}
+
+/// Decides how the supervisor stops a child, see
+/// [`SupervisorConfig::set_shutdowns`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChildShutdown {
+    /// The child is killed if it doesn't finish
+    /// [`terminate`](AbstractProcess::terminate) within the timeout.
+    Timeout(Duration),
+    /// The child is killed right away, without running `terminate`.
+    BrutalKill,
+    /// The supervisor waits until `terminate` finishes.
+    #[default]
+    Infinity,
+}
+
+/// The nodes a child can be started on, see [`SupervisorConfig::set_nodes`].
+///
+/// The child is started on the first node that is connected, when the
+/// supervisor starts and on each restart. A child whose node disconnected is
+/// restarted on the next connected node, and moves back on a later restart
+/// once its first node is connected again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChildNode {
+    nodes: Vec<u64>,
+}
+
+impl ChildNode {
+    /// Starts the child on `node`.
+    pub fn on(node: u64) -> Self {
+        ChildNode { nodes: vec![node] }
+    }
+
+    /// Falls back to `node` if none of the previous nodes is connected.
+    pub fn or(mut self, node: u64) -> Self {
+        self.nodes.push(node);
+        self
+    }
+
+    /// Returns the first connected node.
+    fn select(&self) -> Option<u64> {
+        let local = host::node_id();
+        let connected = distributed::nodes();
+        self.nodes
+            .iter()
+            .copied()
+            .find(|&node| node == local || connected.contains(&node))
+    }
+}
+
+/// A started child, its link tag and its watcher.
+type Spawned<C> = (ProcessRef<C>, Tag, Option<Process<WatcherCommand>>);
+
+/// Returns the notice a child linked with `tag` reports a normal exit to the
+/// supervisor `K` with.
+///
+/// Links only report failures. Normal exits are reported by the child itself
+/// instead of a monitor, so that the supervisor doesn't need to tell them apart
+/// by the order in which the signals arrive.
+fn exit_notice<K: Supervisor>(tag: Tag) -> ExitNotice {
+    ExitNotice::new(report_exit::<K>, host::node_id(), host::process_id(), tag)
+}
+
+fn report_exit<K: Supervisor>(node_id: u64, process_id: u64, tag: Tag, reason: ExitReason) {
+    let supervisor = unsafe { ProcessRef::<K>::new(node_id, process_id) };
+    supervisor.send(ChildExit { tag, reason });
+}
+
+/// Starts a static child of the supervisor `K`, linked with a new tag.
+///
+/// Links don't work across nodes. A child started on another node is
+/// watched by a process on its node instead, which reports the exit with a
+/// [`ChildExit`] message and can kill the child.
+#[allow(clippy::result_large_err)]
+fn spawn_child<K, C>(
+    arg: C::Arg,
+    name: Option<&String>,
+    config: Option<&ProcessConfig>,
+    node: Option<&ChildNode>,
+    timeout: Option<Duration>,
+) -> Result<Spawned<C>, SupervisorStartError>
+where
+    K: Supervisor,
+    C: AbstractProcess,
+    C::StartupError: 'static,
+{
+    let tag = Tag::new();
+    let node = match node {
+        Some(nodes) => match nodes.select() {
+            Some(node) => node,
+            None => {
+                let mut err = start_error::<C>(StartupError::TimedOut);
+                err.cause = ChildStartCause::NoNode(nodes.nodes.clone());
+                return Err(err);
+            }
+        },
+        None => host::node_id(),
+    };
+    // Nested supervisors time out their own children, so that the error
+    // names the child that stalled.
+    let timeout = timeout.filter(|_| !is_supervisor::<C>());
+    let (child, watcher) = if node == host::node_id() {
+        let builder = C::link_with(tag).notify_exit(exit_notice::<K>(tag));
+        let builder = match config {
+            Some(config) => builder.configure(config),
+            None => builder,
+        };
+        let child = match timeout {
+            Some(timeout) => builder.start_timeout(arg, timeout),
+            None => builder.start(arg),
+        };
+        let child = child.map_err(start_error)?;
+        (child, None)
+    } else {
+        // The child reports a normal exit to the watcher, which needs to run
+        // before the child starts.
+        let supervisor = unsafe { ProcessRef::<K>::new(host::node_id(), host::process_id()) };
+        let watcher = Process::spawn_node(node, (supervisor, tag), watch_child::<K>);
+        let notice = ExitNotice::new(report_to_watcher, node, watcher.id(), tag);
+        let builder = C::on_node(node).notify_exit(notice);
+        let builder = match config {
+            Some(config) => builder.configure(config),
+            None => builder,
+        };
+        let child = match timeout {
+            Some(timeout) => builder.start_timeout(arg, timeout),
+            None => builder.start(arg),
+        };
+        let child = child.map_err(|err| {
+            watcher.send(WatcherCommand::Kill);
+            start_error(err)
+        })?;
+        watcher.send(WatcherCommand::Watch(child.id()));
+        (child, Some(watcher))
+    };
+    // Names are registered once `init` finished, lookups never return a
+    // process that is still starting.
+    if let Some(name) = name {
+        child.register(name);
+    }
+    Ok((child, tag, watcher))
+}
+
+/// Command for the watcher of a child on another node.
+#[derive(Serialize, Deserialize)]
+enum WatcherCommand {
+    /// Starts watching the child running as the process.
+    Watch(u64),
+    /// The child exited, sent by the child itself.
+    Exited(ExitReason),
+    /// Kills the child, or stops the watcher if it doesn't watch a child
+    /// yet.
+    Kill,
+}
+
+fn report_to_watcher(node_id: u64, process_id: u64, _: Tag, reason: ExitReason) {
+    let watcher = unsafe { Process::<WatcherCommand>::new(node_id, process_id) };
+    watcher.send(WatcherCommand::Exited(reason));
+}
+
+/// Watches a child on the node of the watcher until it exits, for a
+/// supervisor on another node.
+///
+/// The child reports a normal exit to the watcher, failures arrive as the
+/// link death. Whichever arrives first is forwarded to the supervisor.
+fn watch_child<K: Supervisor>(
+    (supervisor, tag): (ProcessRef<K>, Tag),
+    mailbox: Mailbox<WatcherCommand>,
+) {
+    let mailbox = mailbox.catch_link_failure();
+    let mut child = None;
+    let reason = loop {
+        match mailbox.receive() {
+            // Linking a child that already exited reports its link death right
+            // away. If it exited normally, its report was sent before and is
+            // received first.
+            MessageSignal::Message(WatcherCommand::Watch(process_id)) => {
+                unsafe { host::api::process::link(tag.id(), process_id) };
+                child = Some(process_id);
+            }
+            MessageSignal::Message(WatcherCommand::Exited(reason)) => break reason,
+            MessageSignal::Message(WatcherCommand::Kill) => match child {
+                Some(child) => unsafe { host::api::process::kill(child) },
+                None => return,
+            },
+            MessageSignal::Signal(LinkDiedSignal(_)) => break ExitReason::Unknown,
+        }
+    };
+    supervisor.send(ChildExit { tag, reason });
+}
+
+/// Stops `child` as configured by `shutdown`.
+///
+/// A child on another node is killed by its `watcher`.
+fn shutdown_child<C: AbstractProcess>(
+    child: &ProcessRef<C>,
+    shutdown: ChildShutdown,
+    watcher: Option<Process<WatcherCommand>>,
+) {
+    let kill = || match watcher {
+        Some(watcher) => watcher.send(WatcherCommand::Kill),
+        None => child.kill(),
+    };
+    match shutdown {
+        ChildShutdown::Timeout(timeout) => {
+            if child.shutdown_timeout(Some(timeout)).is_err() {
+                kill();
+            }
+        }
+        ChildShutdown::BrutalKill => kill(),
+        ChildShutdown::Infinity => child.shutdown(),
+    }
+}
+
+/// Removes the registration of `child` under `name`, unless the name was
+/// registered by another process since.
+fn unregister_child<C: AbstractProcess>(child: &ProcessRef<C>, name: &str) {
+    if ProcessRef::<C>::lookup(name) == Some(*child) {
+        let name = process_name::<C, C::Serializer>(ProcessType::ProcessRef, name);
+        unsafe { host::api::registry::remove(name.as_ptr(), name.len()) };
+    }
+}
+
+/// Delays the restarts of a child that keeps failing, see
+/// [`SupervisorConfig::set_backoffs`].
+///
+/// The first restart waits for `initial`, each following one `multiplier`
+/// times longer than the previous, up to `max`. Once the child runs for the
+/// healthy period without exiting, the delay starts again at `initial`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    multiplier: f64,
+    healthy: Duration,
+}
+
+impl Backoff {
+    /// Creates a backoff with a healthy period of `max`.
+    pub fn new(initial: Duration, max: Duration, multiplier: f64) -> Self {
+        Backoff {
+            initial,
+            max,
+            multiplier,
+            healthy: max,
+        }
+    }
+
+    /// Sets how long a child needs to run before the delay is reset.
+    pub fn healthy_after(mut self, period: Duration) -> Self {
+        self.healthy = period;
+        self
+    }
+
+    /// Returns the delay before restart number `attempt`, counting from 0.
+    fn delay(&self, attempt: u32) -> Duration {
+        let secs = self.initial.as_secs_f64() * self.multiplier.powi(attempt as i32);
+        Duration::try_from_secs_f64(secs).map_or(self.max, |delay| delay.min(self.max))
+    }
+}
+
+/// Restarts of a static child.
+#[derive(Clone)]
+struct RestartState {
+    count: u32,
+    /// Restarts since the child last ran for its healthy period.
+    consecutive: u32,
+    started: Instant,
+    /// A delayed restart is scheduled.
+    pending: bool,
+    /// The child was stopped with [`ProcessRef::stop_child`].
+    stopped: bool,
+    /// Watcher of a child on another node, see [`spawn_child`].
+    watcher: Option<Process<WatcherCommand>>,
+    /// State last saved by the child, see [`SupervisorConfig::set_handoffs`].
+    saved_state: Option<Vec<u8>>,
+    /// Restart policy set with [`ProcessRef::replace_child_spec`].
+    restart_policy: Option<ChildRestart>,
+    /// The child was deleted with [`ProcessRef::delete_child`].
+    deleted: bool,
+    /// The exit of the current process was handled.
+    exited: bool,
+    hooks: ChildHooks,
+}
+
+impl RestartState {
+    fn new(watcher: Option<Process<WatcherCommand>>, hooks: ChildHooks) -> Self {
+        RestartState {
+            count: 0,
+            consecutive: 0,
+            started: Instant::now(),
+            pending: false,
+            stopped: false,
+            watcher,
+            saved_state: None,
+            restart_policy: None,
+            deleted: false,
+            exited: false,
+            hooks,
+        }
+    }
+
+    fn restarted(&mut self) {
+        self.count += 1;
+        self.started = Instant::now();
+        self.pending = false;
+        self.stopped = false;
+        self.exited = false;
+    }
+
+    /// Returns the delay before the next restart.
+    fn next_delay(&mut self, backoff: &Backoff) -> Duration {
+        if self.started.elapsed() >= backoff.healthy {
+            self.consecutive = 0;
+        }
+        let delay = backoff.delay(self.consecutive);
+        self.consecutive += 1;
+        delay
+    }
+}
+
 pub struct SupervisorConfig<T>
 where
     T: Supervisor,
@@ -177,55 +1498,811 @@ where
     children: Option<<<T as Supervisor>::Children as Supervisable<T>>::Processes>,
     children_args: Option<<<T as Supervisor>::Children as Supervisable<T>>::Args>,
     children_names: Option<<<T as Supervisor>::Children as Supervisable<T>>::Names>,
+    children_ids: Option<<<T as Supervisor>::Children as Supervisable<T>>::Ids>,
     children_configs: Option<<<T as Supervisor>::Children as Supervisable<T>>::Configs>,
+    children_restarts: Option<<<T as Supervisor>::Children as Supervisable<T>>::Restarts>,
+    children_backoffs: Option<<<T as Supervisor>::Children as Supervisable<T>>::Backoffs>,
+    children_shutdowns: Option<<<T as Supervisor>::Children as Supervisable<T>>::Shutdowns>,
+    children_nodes: Option<<<T as Supervisor>::Children as Supervisable<T>>::Nodes>,
+    children_hooks: Option<<<T as Supervisor>::Children as Supervisable<T>>::Hooks>,
+    // Interval at which the nodes of remote children are checked.
+    node_check: Duration,
+    start_timeout: Option<Duration>,
+    children_handoffs: Option<<<T as Supervisor>::Children as Supervisable<T>>::Handoffs>,
+    children_siblings: Option<<<T as Supervisor>::Children as Supervisable<T>>::Siblings>,
     children_tags: Option<<<T as Supervisor>::Children as Supervisable<T>>::Tags>,
+    // Restarts of each static child, in start order.
+    restart_state: Vec<RestartState>,
     terminate_subscribers: Vec<DeferredResponse<(), T>>,
+    max_restarts: Option<(u32, Duration)>,
+    children_significant: Option<<<T as Supervisor>::Children as Supervisable<T>>::Significant>,
+    auto_shutdown: AutoShutdown,
+    // Auto shutdown was triggered, the supervisor exits after the current
+    // message.
+    exiting: bool,
+    // Times of the restarts inside of the `max_restarts` window.
+    restarts: VecDeque<Instant>,
+    // Children started with `start_child`, in start order.
+    dynamic_children: Vec<DynamicChild>,
+    next_child_id: u64,
+    // Processes receiving a `SupervisorEvent` for each start, exit and restart.
+    event_listeners: Vec<Process<SupervisorEvent>>,
+    // Timeout of pinging a child in a health check.
+    health_ping: Duration,
+    // Time a health report is reused for.
+    health_ttl: Duration,
+    health_cache: Option<(Instant, HealthReport)>,
     phantom: PhantomData<T>,
 }
 
-impl<T> SupervisorConfig<T>
-where
-    T: Supervisor,
-{
-    pub fn set_strategy(&mut self, strategy: SupervisorStrategy) {
-        self.strategy = strategy;
+impl<T> SupervisorConfig<T>
+where
+    T: Supervisor,
+{
+    pub fn set_strategy(&mut self, strategy: SupervisorStrategy) {
+        self.strategy = strategy;
+    }
+
+    pub fn set_args(&mut self, args: <<T as Supervisor>::Children as Supervisable<T>>::Args) {
+        self.children_args = Some(args);
+    }
+
+    /// Sets the name each child is registered under.
+    ///
+    /// The supervisor registers a child after its `init` function finished
+    /// and removes the registration when the child exits or is stopped, so
+    /// that [`ProcessRef::lookup`] doesn't return a dead process. Use
+    /// [`ProcessRef::lookup_child`] on the supervisor to also wait for
+    /// pending restarts.
+    pub fn set_names(&mut self, names: <<T as Supervisor>::Children as Supervisable<T>>::Names) {
+        self.children_names = Some(names);
+    }
+
+    /// Sets the id of each child, by default children are identified by
+    /// their position.
+    ///
+    /// The id is reported as [`ChildId::Named`] in [`ChildInfo`], in
+    /// [`SupervisorEvent`]s and in errors, and can be passed to requests like
+    /// [`ProcessRef::restart_child`]. Unlike a name, it isn't registered
+    /// anywhere, so it only needs to be unique among the children of the
+    /// supervisor. The supervisor panics in `init` if two children have the
+    /// same id.
+    pub fn set_ids(&mut self, ids: <<T as Supervisor>::Children as Supervisable<T>>::Ids) {
+        self.children_ids = Some(ids);
+    }
+
+    /// Limits the restarts of children to `count` within any period of length
+    /// `within`.
+    ///
+    /// If a child fails once more, the supervisor gives up. It shuts the
+    /// remaining children down in reverse start order and exits with a panic,
+    /// so that its own supervisor can restart it. By default children are
+    /// restarted without limit.
+    pub fn set_max_restarts(&mut self, count: u32, within: Duration) {
+        self.max_restarts = Some((count, within));
+    }
+
+    /// Marks children as significant, by default no child is.
+    ///
+    /// The normal exit of a significant child can shut the supervisor down,
+    /// see [`set_auto_shutdown`](Self::set_auto_shutdown). Significant
+    /// children should be [`Transient`](ChildRestart::Transient) or
+    /// [`Temporary`](ChildRestart::Temporary), a
+    /// [`Permanent`](ChildRestart::Permanent) child is restarted after a
+    /// normal exit and never triggers the shutdown.
+    pub fn set_significant(
+        &mut self,
+        significant: <<T as Supervisor>::Children as Supervisable<T>>::Significant,
+    ) {
+        self.children_significant = Some(significant);
+    }
+
+    /// Sets when the normal exit of significant children shuts the supervisor
+    /// down, [`AutoShutdown::Never`] by default.
+    ///
+    /// The supervisor then stops the remaining children like on a regular
+    /// shutdown and exits normally, so that a significant supervisor in its
+    /// own parent can propagate the shutdown further up the tree. Failures of
+    /// significant children are handled by the strategy as usual.
+    pub fn set_auto_shutdown(&mut self, auto_shutdown: AutoShutdown) {
+        self.auto_shutdown = auto_shutdown;
+    }
+
+    /// Sets the [`ProcessConfig`] each child is spawned with, by default
+    /// children inherit the configuration of the supervisor.
+    ///
+    /// The configurations stay in the state of the supervisor, they aren't
+    /// sent anywhere. Each start and restart of a child spawns it with the
+    /// same configuration, so its memory and fuel limits and its permissions
+    /// are preserved across restarts. The supervisor needs the permission to
+    /// create configurations, [`ProcessConfig::new`] fails otherwise.
+    pub fn set_configs(
+        &mut self,
+        configs: <<T as Supervisor>::Children as Supervisable<T>>::Configs,
+    ) {
+        self.children_configs = Some(configs);
+    }
+
+    /// Sets the restart policy of each child, by default all children are
+    /// [`Permanent`](ChildRestart::Permanent).
+    ///
+    /// The policy decides if the exit of a child invokes the strategy. A
+    /// child that isn't restarted after its exit is still restarted by the
+    /// strategy if a sibling fails.
+    pub fn set_restarts(
+        &mut self,
+        restarts: <<T as Supervisor>::Children as Supervisable<T>>::Restarts,
+    ) {
+        self.children_restarts = Some(restarts);
+    }
+
+    /// Sets the delay before each child is restarted after it exits, by
+    /// default children are restarted right away.
+    ///
+    /// The supervisor keeps handling requests and the exits of other
+    /// children while a restart is delayed. Children that are restarted by
+    /// the strategy after a sibling exits aren't delayed.
+    pub fn set_backoffs(
+        &mut self,
+        backoffs: <<T as Supervisor>::Children as Supervisable<T>>::Backoffs,
+    ) {
+        self.children_backoffs = Some(backoffs);
+    }
+
+    /// Sets how each child is stopped, by default the supervisor waits until
+    /// the child finishes [`terminate`](AbstractProcess::terminate).
+    ///
+    /// Children are stopped one after another in reverse start order, when
+    /// the supervisor shuts down and when the strategy restarts siblings of
+    /// a failed child. A supervisor that is a child of another supervisor
+    /// stops its own children the same way, within its own shutdown policy.
+    pub fn set_shutdowns(
+        &mut self,
+        shutdowns: <<T as Supervisor>::Children as Supervisable<T>>::Shutdowns,
+    ) {
+        self.children_shutdowns = Some(shutdowns);
+    }
+
+    /// Sets the nodes each child is started on, by default children are
+    /// started on the node of the supervisor.
+    ///
+    /// Children on other nodes can't be linked to the supervisor. Their exit
+    /// is reported by a watcher process that runs next to each of them, and
+    /// the supervisor regularly checks that their nodes are still connected,
+    /// see [`set_node_check`](Self::set_node_check). A child whose node
+    /// disconnected counts as failed and is restarted on another node of its
+    /// [`ChildNode`].
+    pub fn set_nodes(&mut self, nodes: <<T as Supervisor>::Children as Supervisable<T>>::Nodes) {
+        self.children_nodes = Some(nodes);
+    }
+
+    /// Sets the hooks the supervisor calls when it restarts a child or gives
+    /// up on it, by default children don't have any.
+    ///
+    /// The hooks run inside of the supervisor, see [`ChildHooks`]. They can
+    /// be replaced later with [`ProcessRef::replace_child_spec`].
+    pub fn set_hooks(&mut self, hooks: <<T as Supervisor>::Children as Supervisable<T>>::Hooks) {
+        self.children_hooks = Some(hooks);
+    }
+
+    /// Sets how often the supervisor checks that the nodes of its children
+    /// are connected, once per second by default.
+    pub fn set_node_check(&mut self, interval: Duration) {
+        self.node_check = interval;
+    }
+
+    /// Limits how long the `init` function of each child can take, when the
+    /// supervisor starts and when it restarts a child.
+    ///
+    /// A child that doesn't finish in time is killed and fails to start with
+    /// [`ChildStartCause::TimedOut`]. Nested supervisors aren't limited, so
+    /// that their own start timeout can name the child that stalled inside
+    /// of them.
+    pub fn set_start_timeout(&mut self, timeout: Duration) {
+        self.start_timeout = Some(timeout);
+    }
+
+    /// Sets how long a health check waits for each child to answer a ping,
+    /// and for how long the supervisor reuses its last health report, see
+    /// [`ProcessRef::health`].
+    ///
+    /// By default children get 100 milliseconds and reports are reused for
+    /// one second, so that frequent probes don't flood the children with
+    /// pings.
+    pub fn set_health_check(&mut self, ping_timeout: Duration, ttl: Duration) {
+        self.health_ping = ping_timeout;
+        self.health_ttl = ttl;
+    }
+
+    /// Enables the state handoff of children, by default no child hands off
+    /// its state.
+    ///
+    /// A child with a [`Handoff`] is started with a [`RestartArg`], wrapping
+    /// its argument. It can save its state in the supervisor, and after a
+    /// restart the new process receives the state last saved by the previous
+    /// one. This works for all restarts, also when the child crashed, because
+    /// the supervisor doesn't need to ask the old process for its state.
+    pub fn set_handoffs(
+        &mut self,
+        handoffs: <<T as Supervisor>::Children as Supervisable<T>>::Handoffs,
+    ) {
+        self.children_handoffs = Some(handoffs);
+    }
+
+    /// Starts the children in two phases, for children that need references
+    /// to each other, by default children are started one after another.
+    ///
+    /// In the first phase all children are started and run their `init`
+    /// function, without references to their siblings. In the second phase
+    /// each child with a [`Siblings`] receives a [`SiblingRefs`] message, with
+    /// the references of all static children by [`ChildId`]. The names of
+    /// these children are only registered after the delivery, and the
+    /// supervisor only answers requests like [`ProcessRef::children`] after
+    /// it, so a process that looks them up can't send them a message before
+    /// their `SiblingRefs`. Siblings can message each other as soon as they
+    /// handled their own `SiblingRefs`, which could be before the other one
+    /// received its own.
+    ///
+    /// After a restart, the restarted children receive their `SiblingRefs`
+    /// the same way and the other children with a `Siblings` receive updated
+    /// ones.
+    pub fn set_siblings(
+        &mut self,
+        siblings: <<T as Supervisor>::Children as Supervisable<T>>::Siblings,
+    ) {
+        self.children_siblings = Some(siblings);
+    }
+
+    pub(crate) fn get_children(
+        &self,
+    ) -> <<T as Supervisor>::Children as Supervisable<T>>::Processes {
+        self.children.as_ref().unwrap().clone()
+    }
+
+    /// Sends a [`SupervisorEvent`] to `listener` for each start, exit and
+    /// restart of a child, also for the children started by `init`.
+    pub fn subscribe_events(&mut self, listener: Process<SupervisorEvent>) {
+        self.event_listeners.push(listener);
+    }
+
+    /// Sends `event` to all listeners.
+    fn notify(&mut self, event: SupervisorEvent) {
+        let local = host::node_id();
+        self.event_listeners
+            .retain(|listener| listener.node_id() != local || listener.is_alive());
+        for listener in &self.event_listeners {
+            listener.send(event.clone());
+        }
+    }
+
+    /// Sends the event created by `event` for the current info of `child`
+    /// to all listeners.
+    fn notify_child(&mut self, child: ChildRef, event: impl FnOnce(ChildInfo) -> SupervisorEvent) {
+        if self.event_listeners.is_empty() {
+            return;
+        }
+        if let Ok(info) = self.info(child) {
+            self.notify(event(info));
+        }
     }
 
-    pub fn set_args(&mut self, args: <<T as Supervisor>::Children as Supervisable<T>>::Args) {
-        self.children_args = Some(args);
+    /// Restarts the static child linked with `tag` with the strategy.
+    fn restart(&mut self, tag: Tag) {
+        let before: Vec<u32> = self.restart_state.iter().map(|state| state.count).collect();
+        T::Children::handle_failure(self, tag);
+        let restarted: Vec<usize> = (0..before.len())
+            .filter(|&index| self.restart_state[index].count > before[index])
+            .collect();
+        T::Children::deliver_siblings(self, &restarted);
+        for index in restarted {
+            self.notify_child(ChildRef::Static(index), |child| {
+                SupervisorEvent::ChildRestarted {
+                    child,
+                    time: SystemTime::now(),
+                }
+            });
+            let state = &self.restart_state[index];
+            state
+                .hooks
+                .restarted(self.id_of(ChildRef::Static(index)), state.count);
+        }
     }
 
-    pub fn set_names(&mut self, names: <<T as Supervisor>::Children as Supervisable<T>>::Names) {
-        self.children_names = Some(names);
+    fn child_info(&self) -> Vec<ChildInfo> {
+        let mut children: Vec<ChildInfo> = T::Children::child_info(self)
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| !self.restart_state[*index].deleted)
+            .map(|(_, info)| info)
+            .collect();
+        children.extend(self.dynamic_children.iter().map(|child| {
+            let alive = unsafe { host::api::process::exists(child.process_id) != 0 };
+            ChildInfo {
+                child: ChildId::Dynamic(child.id),
+                name: None,
+                type_name: child.type_name.clone(),
+                node_id: child.node_id,
+                process_id: child.process_id,
+                status: if child.stopped {
+                    ChildStatus::Stopped
+                } else if alive {
+                    ChildStatus::Running
+                } else {
+                    ChildStatus::Exited
+                },
+                restarts: child.restarts,
+            }
+        }));
+        children
     }
 
-    pub fn set_configs(
-        &mut self,
-        configs: <<T as Supervisor>::Children as Supervisable<T>>::Configs,
-    ) {
-        self.children_configs = Some(configs);
+    /// Returns the current info of `child`.
+    ///
+    /// Fails if the child was deleted.
+    fn info(&self, child: ChildRef) -> Result<ChildInfo, SupervisorError> {
+        let id = self.id_of(child);
+        match self.child_info().into_iter().find(|info| info.child == id) {
+            Some(info) => Ok(info),
+            None => Err(SupervisorError::ChildNotFound(id)),
+        }
     }
 
-    pub(crate) fn get_children(
-        &self,
-    ) -> <<T as Supervisor>::Children as Supervisable<T>>::Processes {
-        self.children.as_ref().unwrap().clone()
+    /// Returns the id `child` is reported under.
+    fn id_of(&self, child: ChildRef) -> ChildId {
+        match child {
+            ChildRef::Static(index) => T::Children::child_ids(self).swap_remove(index),
+            ChildRef::Dynamic(id) => ChildId::Dynamic(id),
+        }
+    }
+
+    /// Finds the child with the id `child`.
+    fn resolve(&self, child: &ChildId) -> Result<ChildRef, SupervisorError> {
+        let found = match child {
+            ChildId::Static(index) => {
+                (*index < self.restart_state.len()).then_some(ChildRef::Static(*index))
+            }
+            ChildId::Named(_) => T::Children::child_ids(self)
+                .iter()
+                .position(|id| id == child)
+                .map(ChildRef::Static),
+            ChildId::Dynamic(id) => self
+                .dynamic_children
+                .iter()
+                .any(|other| other.id == *id)
+                .then_some(ChildRef::Dynamic(*id)),
+        };
+        found.ok_or_else(|| SupervisorError::ChildNotFound(child.clone()))
     }
 
-    pub fn start_link(&mut self) {
-        T::Children::start_links(self);
+    #[allow(clippy::result_large_err)]
+    pub fn start_link(&mut self) -> Result<(), SupervisorStartError> {
+        T::Children::start_links(self)
     }
 
     fn terminate(mut self) {
+        self.notify(SupervisorEvent::ShuttingDown {
+            time: SystemTime::now(),
+        });
         self.terminate_subscribers
             .drain(..)
             .for_each(|sub| sub.send_response(()));
+        // Dynamic children were started last, shut them down first.
+        for child in self.dynamic_children.drain(..).rev() {
+            (child.shutdown)(child.node_id, child.process_id);
+        }
         T::Children::terminate(self);
     }
 
     pub(crate) fn subscribe_shutdown(&mut self, subscriber: DeferredResponse<(), T>) {
         self.terminate_subscribers.push(subscriber);
     }
+
+    fn start_dynamic_child(
+        &mut self,
+        request: StartChild,
+    ) -> Result<(DynamicId, u64, u64), Vec<u8>> {
+        // Safety: The pointers were created from the same functions in
+        // `ProcessRef::start_child`.
+        let start: StartChildFn = unsafe { mem::transmute(request.start) };
+        let shutdown: fn(u64, u64) = unsafe { mem::transmute(request.shutdown) };
+        let health: HealthFn = unsafe { mem::transmute(request.health) };
+        let tag = Tag::new();
+        let (node_id, process_id) = start(&request.arg, tag, exit_notice::<T>(tag))?;
+        let id = DynamicId(self.next_child_id);
+        self.next_child_id += 1;
+        self.dynamic_children.push(DynamicChild {
+            id,
+            type_name: request.type_name,
+            start,
+            shutdown,
+            health,
+            arg: request.arg,
+            restart: ChildRestart::Permanent,
+            tag,
+            node_id,
+            process_id,
+            restarts: 0,
+            stopped: false,
+            exited: false,
+            hooks: ChildHooks::new(),
+        });
+        self.notify_child(ChildRef::Dynamic(id), |child| {
+            SupervisorEvent::ChildStarted {
+                child,
+                time: SystemTime::now(),
+            }
+        });
+        Ok((id, node_id, process_id))
+    }
+
+    /// Restarts the child linked with `tag` after it exited, if its restart
+    /// policy asks for it.
+    fn handle_exit(&mut self, tag: Tag, reason: ExitReason) {
+        // The remaining children are stopped on the way out.
+        if self.exiting {
+            return;
+        }
+        let failed = reason.is_failure();
+        // Dynamic children are restarted one by one after a failure,
+        // independent of the strategy.
+        if let Some(child) = self
+            .dynamic_children
+            .iter_mut()
+            .find(|child| child.tag == tag)
+        {
+            // Only the first report of an exit counts. A child killed right
+            // after it reported its normal exit still finished normally.
+            if child.exited || child.stopped {
+                return;
+            }
+            child.exited = true;
+            let id = child.id;
+            let key = ChildRef::Dynamic(id);
+            let restart = match child.restart {
+                ChildRestart::Permanent => true,
+                ChildRestart::Transient => failed,
+                ChildRestart::Temporary => false,
+            };
+            if failed {
+                self.notify_child(key, |child| SupervisorEvent::ChildCrashed {
+                    child,
+                    reason,
+                    time: SystemTime::now(),
+                });
+            } else {
+                self.notify_child(key, |child| SupervisorEvent::ChildExited {
+                    child,
+                    time: SystemTime::now(),
+                });
+            }
+            if !restart {
+                return;
+            }
+            if !self.record_restart() {
+                self.escalate(tag);
+            }
+            self.restart_dynamic_child(tag);
+            self.notify_child(key, |child| SupervisorEvent::ChildRestarted {
+                child,
+                time: SystemTime::now(),
+            });
+            // The restarted child is linked with a new tag.
+            if let Some(child) = self.dynamic_children.iter().find(|child| child.id == id) {
+                child.hooks.restarted(ChildId::Dynamic(id), child.restarts);
+            }
+            return;
+        }
+        // Reports about children that were replaced since are ignored, like a
+        // second report about a child whose exit was already handled.
+        let Some(index) = T::Children::child_index(self, tag) else {
+            return;
+        };
+        let state = &mut self.restart_state[index];
+        if state.exited || state.pending || state.stopped || state.deleted {
+            return;
+        }
+        state.exited = true;
+        // Lookups shouldn't return the exited process, the name is registered
+        // again after the restart.
+        T::Children::unregister(self, tag);
+        let key = ChildRef::Static(index);
+        if failed {
+            self.notify_child(key, |child| SupervisorEvent::ChildCrashed {
+                child,
+                reason,
+                time: SystemTime::now(),
+            });
+        } else {
+            self.notify_child(key, |child| SupervisorEvent::ChildExited {
+                child,
+                time: SystemTime::now(),
+            });
+        }
+        let restart = match T::Children::child_restart(self, tag) {
+            Some(ChildRestart::Permanent) => true,
+            Some(ChildRestart::Transient) => failed,
+            Some(ChildRestart::Temporary) | None => false,
+        };
+        if !restart {
+            if !failed && self.auto_shutdown(index) {
+                self.exiting = true;
+                let this = unsafe { ProcessRef::<T>::new(host::node_id(), host::process_id()) };
+                this.send_shutdown();
+            }
+            return;
+        }
+        if !self.record_restart() {
+            self.escalate(tag);
+        }
+        match T::Children::child_backoff(self, tag) {
+            Some(backoff) => {
+                let delay = self.restart_state[index].next_delay(&backoff);
+                self.restart_state[index].pending = true;
+                let this = unsafe { ProcessRef::<T>::new(host::node_id(), host::process_id()) };
+                this.delayed_send(DelayedRestart(tag), delay);
+            }
+            None => self.restart(tag),
+        }
+    }
+
+    /// Stops `child` without removing it, see [`ProcessRef::stop_child`].
+    fn stop_child(&mut self, child: ChildRef) -> Result<(), SupervisorError> {
+        match child {
+            ChildRef::Static(index) => {
+                if self.restart_state[index].deleted {
+                    return Err(SupervisorError::ChildNotFound(self.id_of(child)));
+                }
+                T::Children::stop_child(self, index);
+                self.restart_state[index].pending = false;
+                self.restart_state[index].stopped = true;
+            }
+            ChildRef::Dynamic(id) => {
+                let Some(dynamic) = self
+                    .dynamic_children
+                    .iter_mut()
+                    .find(|other| other.id == id)
+                else {
+                    return Err(SupervisorError::ChildNotFound(ChildId::Dynamic(id)));
+                };
+                if unsafe { host::api::process::exists(dynamic.process_id) != 0 } {
+                    (dynamic.shutdown)(dynamic.node_id, dynamic.process_id);
+                }
+                dynamic.stopped = true;
+            }
+        }
+        self.notify_child(child, |child| SupervisorEvent::ChildExited {
+            child,
+            time: SystemTime::now(),
+        });
+        Ok(())
+    }
+
+    /// Stops `child` and starts it again, see [`ProcessRef::restart_child`].
+    ///
+    /// Returns the node and process id of the new process.
+    fn restart_child(
+        &mut self,
+        child: ChildRef,
+        type_name: &str,
+    ) -> Result<(u64, u64), SupervisorError> {
+        let info = self.info(child)?;
+        if info.type_name != type_name {
+            return Err(SupervisorError::WrongType(info.type_name));
+        }
+        self.stop_child(child)?;
+        let process = match child {
+            ChildRef::Static(index) => {
+                T::Children::start_child(self, index).map_err(SupervisorError::StartFailed)?;
+                let state = &mut self.restart_state[index];
+                state.stopped = false;
+                state.exited = false;
+                state.started = Instant::now();
+                T::Children::deliver_siblings(self, &[index]);
+                let info = &T::Children::child_info(self)[index];
+                (info.node_id, info.process_id)
+            }
+            ChildRef::Dynamic(id) => {
+                let dynamic = self
+                    .dynamic_children
+                    .iter_mut()
+                    .find(|other| other.id == id)
+                    .expect("checked above");
+                let tag = Tag::new();
+                let notice = exit_notice::<T>(tag);
+                let (node_id, process_id) =
+                    (dynamic.start)(&dynamic.arg, tag, notice).map_err(|_| {
+                        SupervisorError::StartFailed(format!(
+                            "`init` of `{}` failed",
+                            dynamic.type_name
+                        ))
+                    })?;
+                dynamic.tag = tag;
+                dynamic.node_id = node_id;
+                dynamic.process_id = process_id;
+                dynamic.stopped = false;
+                dynamic.exited = false;
+                (node_id, process_id)
+            }
+        };
+        self.notify_child(child, |child| SupervisorEvent::ChildStarted {
+            child,
+            time: SystemTime::now(),
+        });
+        Ok(process)
+    }
+
+    /// Changes the spec of `child`, see [`ProcessRef::replace_child_spec`].
+    fn replace_child_spec(
+        &mut self,
+        child: ChildRef,
+        type_name: &str,
+        arg: Option<Vec<u8>>,
+        set_arg: SetArgFn,
+        restart: Option<ChildRestart>,
+        hooks: ChildHooks,
+    ) -> Result<(), SupervisorError> {
+        let info = self.info(child)?;
+        if info.type_name != type_name {
+            return Err(SupervisorError::WrongType(info.type_name));
+        }
+        match child {
+            ChildRef::Static(index) => {
+                if let Some(arg) = arg {
+                    T::Children::replace_arg(self, index, &arg, set_arg)
+                        .map_err(SupervisorError::InvalidArg)?;
+                }
+                if restart.is_some() {
+                    self.restart_state[index].restart_policy = restart;
+                }
+                self.restart_state[index].hooks.merge(hooks);
+            }
+            ChildRef::Dynamic(id) => {
+                let dynamic = self
+                    .dynamic_children
+                    .iter_mut()
+                    .find(|other| other.id == id)
+                    .expect("checked above");
+                if let Some(arg) = arg {
+                    dynamic.arg = arg;
+                }
+                if let Some(restart) = restart {
+                    dynamic.restart = restart;
+                }
+                dynamic.hooks.merge(hooks);
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the stopped `child`, see [`ProcessRef::delete_child`].
+    fn delete_child(&mut self, child: ChildRef) -> Result<(), SupervisorError> {
+        let info = self.info(child)?;
+        if matches!(info.status, ChildStatus::Running | ChildStatus::Restarting) {
+            return Err(SupervisorError::ChildRunning);
+        }
+        match child {
+            ChildRef::Static(index) => {
+                let state = &mut self.restart_state[index];
+                state.deleted = true;
+                state.pending = false;
+            }
+            ChildRef::Dynamic(id) => self.dynamic_children.retain(|other| other.id != id),
+        }
+        Ok(())
+    }
+
+    /// Restarts the dynamic child linked with `tag`.
+    ///
+    /// Returns `false` if `tag` doesn't belong to a dynamic child.
+    fn restart_dynamic_child(&mut self, tag: Tag) -> bool {
+        let Some(child) = self
+            .dynamic_children
+            .iter_mut()
+            .find(|child| child.tag == tag)
+        else {
+            return false;
+        };
+        child.tag = Tag::new();
+        child.restarts += 1;
+        match (child.start)(&child.arg, child.tag, exit_notice::<T>(child.tag)) {
+            Ok((node_id, process_id)) => {
+                child.node_id = node_id;
+                child.process_id = process_id;
+                child.exited = false;
+            }
+            Err(_) => {
+                child.hooks.gave_up(ChildId::Dynamic(child.id));
+                panic!(
+                    "Supervisor failed to restart child `{}` of type `{}`",
+                    ChildId::Dynamic(child.id),
+                    child.type_name
+                )
+            }
+        }
+        true
+    }
+
+    /// Gives up on the static child at `index` after its restart failed with
+    /// `err`, and exits the supervisor.
+    fn restart_failed(&self, index: usize, err: impl fmt::Display) -> ! {
+        let child = self.id_of(ChildRef::Static(index));
+        self.restart_state[index].hooks.gave_up(child.clone());
+        panic!("Supervisor failed to restart child `{child}`: {err}");
+    }
+
+    /// Returns `true` if the normal exit of the static child at `index` shuts
+    /// the supervisor down.
+    fn auto_shutdown(&self, index: usize) -> bool {
+        let significant = T::Children::significant(self);
+        if !significant.contains(&index) {
+            return false;
+        }
+        match self.auto_shutdown {
+            AutoShutdown::Never => false,
+            AutoShutdown::AnySignificant => true,
+            AutoShutdown::AllSignificant => {
+                let children = T::Children::child_info(self);
+                significant
+                    .iter()
+                    .all(|&other| other == index || children[other].status == ChildStatus::Exited)
+            }
+        }
+    }
+
+    /// Records a restart and returns `false` if it exceeds the maximum restart
+    /// intensity.
+    fn record_restart(&mut self) -> bool {
+        let Some((count, within)) = self.max_restarts else {
+            return true;
+        };
+        let now = Instant::now();
+        while self
+            .restarts
+            .front()
+            .is_some_and(|&restart| now.duration_since(restart) > within)
+        {
+            self.restarts.pop_front();
+        }
+        self.restarts.push_back(now);
+        self.restarts.len() <= count as usize
+    }
+
+    /// Shuts down all children except the `failed` one and exits the
+    /// supervisor.
+    fn escalate(&mut self, failed: Tag) -> ! {
+        let (child, hooks) = match self
+            .dynamic_children
+            .iter()
+            .find(|child| child.tag == failed)
+        {
+            Some(dynamic) => (ChildId::Dynamic(dynamic.id), dynamic.hooks),
+            None => {
+                let index = T::Children::child_index(self, failed).expect("tag of a child");
+                (
+                    self.id_of(ChildRef::Static(index)),
+                    self.restart_state[index].hooks,
+                )
+            }
+        };
+        self.notify(SupervisorEvent::IntensityExceeded {
+            child: child.clone(),
+            time: SystemTime::now(),
+        });
+        hooks.gave_up(child.clone());
+        for child in self.dynamic_children.drain(..).rev() {
+            if child.tag != failed {
+                (child.shutdown)(child.node_id, child.process_id);
+            }
+        }
+        T::Children::shutdown_except(self, failed);
+        self.terminate_subscribers
+            .drain(..)
+            .for_each(|sub| sub.send_response(()));
+        panic!(
+            "Supervisor {} exceeded the maximum restart intensity, last failed child `{child}`",
+            std::any::type_name::<T>()
+        );
+    }
 }
 
 impl<T> Default for SupervisorConfig<T>
@@ -238,9 +2315,31 @@ where
             children: None,
             children_args: None,
             children_names: None,
+            children_ids: None,
             children_configs: None,
+            children_restarts: None,
+            children_backoffs: None,
+            children_shutdowns: None,
+            children_nodes: None,
+            children_hooks: None,
+            node_check: Duration::from_secs(1),
+            start_timeout: None,
+            children_handoffs: None,
+            children_siblings: None,
             children_tags: None,
+            restart_state: Vec::new(),
             terminate_subscribers: vec![],
+            max_restarts: None,
+            children_significant: None,
+            auto_shutdown: AutoShutdown::Never,
+            exiting: false,
+            restarts: VecDeque::new(),
+            dynamic_children: Vec::new(),
+            next_child_id: 0,
+            event_listeners: Vec::new(),
+            health_ping: Duration::from_millis(100),
+            health_ttl: Duration::from_secs(1),
+            health_cache: None,
             strategy: SupervisorStrategy::OneForOne,
         }
     }
@@ -253,14 +2352,90 @@ where
     type Processes: serde::Serialize + serde::de::DeserializeOwned + Clone;
     type Args: Clone;
     type Names;
+    type Ids;
     type Configs;
+    type Restarts;
+    type Backoffs;
+    type Shutdowns;
+    type Nodes;
+    type Hooks;
+    type Significant;
+    type Handoffs;
+    type Siblings;
     type Tags;
 
-    fn start_links(config: &mut SupervisorConfig<T>);
+    // The error is only returned once, when the supervisor fails to start.
+    #[allow(clippy::result_large_err)]
+    fn start_links(config: &mut SupervisorConfig<T>) -> Result<(), SupervisorStartError>;
     fn terminate(config: SupervisorConfig<T>);
+    fn shutdown_except(config: &mut SupervisorConfig<T>, tag: Tag);
     fn handle_failure(config: &mut SupervisorConfig<T>, tag: Tag);
+    /// Returns the restart policy of the child linked with `tag`.
+    fn child_restart(config: &SupervisorConfig<T>, tag: Tag) -> Option<ChildRestart>;
+    /// Returns the position of the child linked with `tag`.
+    fn child_index(config: &SupervisorConfig<T>, tag: Tag) -> Option<usize>;
+    /// Returns the link tag of the child at `index`.
+    fn child_tag_at(config: &SupervisorConfig<T>, index: usize) -> Option<Tag>;
+    /// Removes the name of the child linked with `tag` from the registry.
+    fn unregister(config: &SupervisorConfig<T>, tag: Tag);
+    /// Returns the backoff of the child linked with `tag`.
+    fn child_backoff(config: &SupervisorConfig<T>, tag: Tag) -> Option<Backoff>;
+    /// Returns the positions of the significant children.
+    fn significant(config: &SupervisorConfig<T>) -> Vec<usize>;
+    /// Returns the ids of the static children, in start order.
+    fn child_ids(config: &SupervisorConfig<T>) -> Vec<ChildId>;
+    /// Returns information about the static children, in start order.
+    fn child_info(config: &SupervisorConfig<T>) -> Vec<ChildInfo>;
+    /// Returns the health of the static children, in start order.
+    fn child_health(
+        config: &SupervisorConfig<T>,
+        ping: Duration,
+        deadline: Instant,
+    ) -> Vec<HealthReport>;
+    /// Shuts down the static child at `index` with its shutdown policy.
+    fn stop_child(config: &mut SupervisorConfig<T>, index: usize);
+    /// Starts the static child at `index` again, without applying the
+    /// strategy.
+    ///
+    /// Returns the startup error formatted with `Debug` if it fails.
+    fn start_child(config: &mut SupervisorConfig<T>, index: usize) -> Result<(), String>;
+    /// Delivers the [`SiblingRefs`] after the static children at `started`
+    /// were started, and registers the names that were deferred.
+    fn deliver_siblings(config: &mut SupervisorConfig<T>, started: &[usize]);
+    /// Replaces the argument of the static child at `index` with the encoded
+    /// `arg`, decoded by `set_arg`.
+    fn replace_arg(
+        config: &mut SupervisorConfig<T>,
+        index: usize,
+        arg: &[u8],
+        set_arg: SetArgFn,
+    ) -> Result<(), String>;
+}
+
+/// The type of the child at position `I` of the supervisor `T`.
+pub type ChildType<T, const I: usize> =
+    <<<T as Supervisor>::Children as Supervisable<T>>::Processes as ChildAt<I>>::Child;
+
+/// Children processes with a child at position `I`, see
+/// [`ProcessRef::child`].
+pub trait ChildAt<const I: usize> {
+    type Child: AbstractProcess;
+
+    fn child(&self) -> ProcessRef<Self::Child>;
+}
+
+/// Children processes with a child of type `C` at position `P`, see
+/// [`ProcessRef::child_of`].
+///
+/// A type that appears more than once implements this trait for more than
+/// one position, and the position can't be inferred.
+pub trait ChildOf<C: AbstractProcess, P> {
+    fn child_of(&self) -> ProcessRef<C>;
 }
 
+/// Position of a child, used by [`ChildOf`].
+pub struct Position<const I: usize>;
+
 // Implement Supervisable for tuples with up to 12 children.
 macros::impl_supervisable!();
 macros::impl_supervisable!(T0 0);
@@ -277,6 +2452,20 @@ macros::impl_supervisable!(T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6, T7 7, T8 8,
 macros::impl_supervisable!(T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6, T7 7, T8 8, T9 9, T10 10, T11 11);
 macros::impl_supervisable!(T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6, T7 7, T8 8, T9 9, T10 10, T11 11, T12 12);
 
+macros::impl_child_at!([T0] T0 0);
+macros::impl_child_at!([T0, T1] T0 0, T1 1);
+macros::impl_child_at!([T0, T1, T2] T0 0, T1 1, T2 2);
+macros::impl_child_at!([T0, T1, T2, T3] T0 0, T1 1, T2 2, T3 3);
+macros::impl_child_at!([T0, T1, T2, T3, T4] T0 0, T1 1, T2 2, T3 3, T4 4);
+macros::impl_child_at!([T0, T1, T2, T3, T4, T5] T0 0, T1 1, T2 2, T3 3, T4 4, T5 5);
+macros::impl_child_at!([T0, T1, T2, T3, T4, T5, T6] T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6);
+macros::impl_child_at!([T0, T1, T2, T3, T4, T5, T6, T7] T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6, T7 7);
+macros::impl_child_at!([T0, T1, T2, T3, T4, T5, T6, T7, T8] T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6, T7 7, T8 8);
+macros::impl_child_at!([T0, T1, T2, T3, T4, T5, T6, T7, T8, T9] T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6, T7 7, T8 8, T9 9);
+macros::impl_child_at!([T0, T1, T2, T3, T4, T5, T6, T7, T8, T9, T10] T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6, T7 7, T8 8, T9 9, T10 10);
+macros::impl_child_at!([T0, T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11] T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6, T7 7, T8 8, T9 9, T10 10, T11 11);
+macros::impl_child_at!([T0, T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12] T0 0, T1 1, T2 2, T3 3, T4 4, T5 5, T6 6, T7 7, T8 8, T9 9, T10 10, T11 11, T12 12);
+
 mod macros {
     // Replace any identifier with `Tag`
     macro_rules! tag {
@@ -291,9 +2480,111 @@ mod macros {
         };
     }
 
-    macro_rules! ignore_expr {
-        ($_:ident, $ret:expr) => {
-            $ret
+    // Id of the child at index `i`
+    macro_rules! child_id {
+        ($config:ident, $i:tt) => {
+            $config
+                .children_ids
+                .as_ref()
+                .and_then(|ids| ids.$i.clone())
+                .map_or(ChildId::Static($i), ChildId::Named)
+        };
+    }
+
+    // Shutdown policy of the child at index `i`
+    macro_rules! child_shutdown {
+        ($config:ident, $i:tt) => {
+            $config
+                .children_shutdowns
+                .as_ref()
+                .map_or(ChildShutdown::default(), |shutdowns| shutdowns.$i)
+        };
+    }
+
+    // Stops the child at index `i` with its shutdown policy
+    macro_rules! shutdown {
+        ($config:ident, $i:tt) => {
+            shutdown_child(
+                &$config.children.as_ref().unwrap().$i,
+                macros::child_shutdown!($config, $i),
+                $config.restart_state[$i].watcher,
+            )
+        };
+    }
+
+    // Name the child at index `i` is registered under right after its
+    // `init`. Children receiving `SiblingRefs` are registered after the
+    // delivery instead.
+    macro_rules! spawn_name {
+        ($config:ident, $i:tt) => {
+            $config
+                .children_names
+                .as_ref()
+                .and_then(|names| names.$i.as_ref())
+                .filter(|_| !macros::has_siblings!($config, $i))
+        };
+    }
+
+    // The child at index `i` receives `SiblingRefs`
+    macro_rules! has_siblings {
+        ($config:ident, $i:tt) => {
+            matches!(
+                $config.children_siblings.as_ref().map(|s| &s.$i),
+                Some(Some(_))
+            )
+        };
+    }
+
+    // Starts the child of type `t` at index `i`, returns why it failed if it
+    // fails
+    macro_rules! start {
+        ($config:ident, $t:ident, $i:tt) => {{
+            let args = macros::handoff!(
+                $config,
+                $i,
+                $config.children_args.as_ref().unwrap().$i.clone()
+            );
+            let name = macros::spawn_name!($config, $i);
+            let proc_config = $config
+                .children_configs
+                .as_ref()
+                .and_then(|configs| configs.$i.as_ref());
+            let node = $config
+                .children_nodes
+                .as_ref()
+                .and_then(|nodes| nodes.$i.as_ref());
+            spawn_child::<K, $t>(args, name, proc_config, node, $config.start_timeout)
+                .map(|(proc, tag, watcher)| {
+                    $config.children.as_mut().unwrap().$i = proc;
+                    $config.children_tags.as_mut().unwrap().$i = tag;
+                    $config.restart_state[$i].watcher = watcher;
+                })
+                .map_err(|err| err.cause.to_string())
+        }};
+    }
+
+    // Adds the saved state of the child at index `i` to its argument `arg`,
+    // if the child hands off its state
+    macro_rules! handoff {
+        ($config:ident, $i:tt, $arg:expr) => {{
+            let mut arg = $arg;
+            if let Some(Some(handoff)) = $config.children_handoffs.as_ref().map(|h| &h.$i) {
+                let saved = $config
+                    .restart_state
+                    .get($i)
+                    .and_then(|state| state.saved_state.clone());
+                handoff.set_arg::<K>(&mut arg, saved, $i);
+            }
+            arg
+        }};
+    }
+
+    // Removes the name of the child at index `i` from the registry
+    macro_rules! unregister {
+        ($config:ident, $i:tt) => {
+            if let Some(Some(name)) = $config.children_names.as_ref().map(|names| &names.$i) {
+                unregister_child(&$config.children.as_ref().unwrap().$i, name);
+            }
         };
     }
 
@@ -302,14 +2593,21 @@ mod macros {
         ($config:ident, []) => {}; // base case
         ($config:ident, [$head_i:tt $($rest_i:tt)*]) => { // recursive case
             macros::reverse_shutdown!($config, [$($rest_i)*]);
-            $config.children.as_ref().unwrap().$head_i.shutdown();
+            // Children that exited and weren't restarted can't be shut down.
+            let child = &$config.children.as_ref().unwrap().$head_i;
+            if child.remote_inspect_alive() {
+                macros::unregister!($config, $head_i);
+                macros::shutdown!($config, $head_i);
+            }
         };
         // reverse_shutdown!(config, skip tag, [...]) shuts down all children with unmatched tags
         ($config:ident, skip $tag:ident, []) => {}; // base case
         ($config:ident, skip $tag:ident, [$head_i:tt $($rest_i:tt)*]) => { // recursive case
             macros::reverse_shutdown!($config, skip $tag, [$($rest_i)*]);
-            if $tag != $config.children_tags.as_ref().unwrap().$head_i {
-                $config.children.as_ref().unwrap().$head_i.shutdown();
+            let child = &$config.children.as_ref().unwrap().$head_i;
+            if $tag != $config.children_tags.as_ref().unwrap().$head_i && child.remote_inspect_alive() {
+                macros::unregister!($config, $head_i);
+                macros::shutdown!($config, $head_i);
             }
         };
         // reverse_shutdown!(config, after tag, [...]) shuts down the children after the tag
@@ -323,6 +2621,29 @@ mod macros {
         };
     }
 
+    // Implements `ChildAt` and `ChildOf` for each position of a tuple of
+    // process references
+    macro_rules! impl_child_at {
+        ($all:tt $($t:ident $i:tt),*) => {
+            $( macros::impl_child_at!(@position $all $t $i); )*
+        };
+        (@position [$($all:ident),*] $t:ident $i:tt) => {
+            impl<$($all: AbstractProcess),*> ChildAt<$i> for ($(ProcessRef<$all>,)*) {
+                type Child = $t;
+
+                fn child(&self) -> ProcessRef<$t> {
+                    self.$i
+                }
+            }
+
+            impl<$($all: AbstractProcess),*> ChildOf<$t, Position<$i>> for ($(ProcessRef<$all>,)*) {
+                fn child_of(&self) -> ProcessRef<$t> {
+                    self.$i
+                }
+            }
+        };
+    }
+
     macro_rules! impl_supervisable {
         ($($t:ident $i:tt),*) => {
             paste::paste! {
@@ -332,50 +2653,269 @@ mod macros {
                     $(
                         $t : AbstractProcess,
                         $t ::Arg : Clone,
+                        $t ::StartupError : 'static,
                     )*
                 {
                     type Processes = ($(ProcessRef<$t>,)*);
                     type Args = ($($t ::Arg,)*);
                     type Names = ($(macros::ignore_type!($t, Option<String>),)*);
+                    type Ids = ($(macros::ignore_type!($t, Option<String>),)*);
                     type Configs = ($(macros::ignore_type!($t, Option<crate::ProcessConfig>),)*);
+                    type Restarts = ($(macros::ignore_type!($t, ChildRestart),)*);
+                    type Backoffs = ($(macros::ignore_type!($t, Option<Backoff>),)*);
+                    type Shutdowns = ($(macros::ignore_type!($t, ChildShutdown),)*);
+                    type Nodes = ($(macros::ignore_type!($t, Option<ChildNode>),)*);
+                    type Hooks = ($(macros::ignore_type!($t, ChildHooks),)*);
+                    type Significant = ($(macros::ignore_type!($t, bool),)*);
+                    type Handoffs = ($(Option<Handoff<$t>>,)*);
+                    type Siblings = ($(Option<Siblings<$t>>,)*);
                     type Tags = ($(macros::tag!($t),)*);
 
-                    #[allow(unused_variables)]
-                    fn start_links(config: &mut SupervisorConfig<K>) {
+                    #[allow(unused_variables, unused_mut)]
+                    fn start_links(config: &mut SupervisorConfig<K>) -> Result<(), SupervisorStartError> {
                         let args = config.children_args.clone().unwrap();
-                        let names = match &config.children_names {
-                            Some(names) => names,
-                            None => { &( $(macros::ignore_expr!($t, None),)* ) }
+                        // Shut down the children started so far, in reverse
+                        // order, if a later one fails.
+                        let mut started: Vec<Box<dyn FnOnce()>> = Vec::new();
+                        $(
+                            let name = macros::spawn_name!(config, $i);
+                            let proc_config = config.children_configs.as_ref().and_then(|configs| configs.$i.as_ref());
+                            let node = config.children_nodes.as_ref().and_then(|nodes| nodes.$i.as_ref());
+                            let ([<proc$i>], [<tag$i>], [<watcher$i>]) =
+                                match spawn_child::<K, $t>(macros::handoff!(config, $i, args.$i), name, proc_config, node, config.start_timeout) {
+                                    Ok(child) => child,
+                                    Err(err) => {
+                                        started.into_iter().rev().for_each(|shutdown| shutdown());
+                                        return Err(err.within($i, macros::child_id!(config, $i), name, (0..$i).collect()));
+                                    }
+                                };
+                            let shutdown = macros::child_shutdown!(config, $i);
+                            let registered = name.cloned();
+                            started.push(Box::new(move || {
+                                if let Some(name) = &registered {
+                                    unregister_child(&[<proc$i>], name);
+                                }
+                                shutdown_child(&[<proc$i>], shutdown, [<watcher$i>]);
+                            }));
+                        )*
+                        config.children = Some(($([<proc$i>],)*));
+                        config.children_tags = Some(($([<tag$i>],)*));
+                        config.restart_state = vec![$(
+                            RestartState::new(
+                                [<watcher$i>],
+                                config.children_hooks.as_ref().map(|hooks| hooks.$i).unwrap_or_default(),
+                            ),
+                        )*];
+                        Self::deliver_siblings(config, &[$($i,)*]);
+                        Ok(())
+                    }
+
+                    #[allow(unused_variables)]
+                    fn terminate(config: SupervisorConfig<K>) {
+                        macros::reverse_shutdown!(config, [ $($i)* ]);
+                    }
+
+                    #[allow(unused_variables)]
+                    fn shutdown_except(config: &mut SupervisorConfig<K>, tag: Tag) {
+                        macros::reverse_shutdown!(config, skip tag, [ $($i)* ]);
+                    }
+
+                    #[allow(unused_variables)]
+                    fn child_restart(config: &SupervisorConfig<K>, tag: Tag) -> Option<ChildRestart> {
+                        let tags = config.children_tags.as_ref()?;
+                        $(
+                            if tag == tags.$i {
+                                if let Some(restart) = config.restart_state[$i].restart_policy {
+                                    return Some(restart);
+                                }
+                                let restart = config.children_restarts.as_ref().map(|restarts| restarts.$i);
+                                return Some(restart.unwrap_or_default());
+                            }
+                        )*
+                        None
+                    }
+
+                    #[allow(unused_variables)]
+                    fn child_tag_at(config: &SupervisorConfig<K>, index: usize) -> Option<Tag> {
+                        let tags = config.children_tags.as_ref()?;
+                        $(
+                            if index == $i {
+                                return Some(tags.$i);
+                            }
+                        )*
+                        None
+                    }
+
+                    #[allow(unused_variables)]
+                    fn child_index(config: &SupervisorConfig<K>, tag: Tag) -> Option<usize> {
+                        let tags = config.children_tags.as_ref()?;
+                        $(
+                            if tag == tags.$i {
+                                return Some($i);
+                            }
+                        )*
+                        None
+                    }
+
+                    #[allow(unused_variables)]
+                    fn unregister(config: &SupervisorConfig<K>, tag: Tag) {
+                        let Some(tags) = config.children_tags.as_ref() else {
+                            return;
+                        };
+                        $(
+                            if tag == tags.$i {
+                                macros::unregister!(config, $i);
+                            }
+                        )*
+                    }
+
+                    #[allow(unused_variables)]
+                    fn child_backoff(config: &SupervisorConfig<K>, tag: Tag) -> Option<Backoff> {
+                        let tags = config.children_tags.as_ref()?;
+                        let backoffs = config.children_backoffs.as_ref()?;
+                        $(
+                            if tag == tags.$i {
+                                return backoffs.$i;
+                            }
+                        )*
+                        None
+                    }
+
+                    #[allow(unused_variables, unused_mut)]
+                    fn significant(config: &SupervisorConfig<K>) -> Vec<usize> {
+                        let mut indices = Vec::new();
+                        let Some(significant) = config.children_significant.as_ref() else {
+                            return indices;
                         };
-                        let configs = match &config.children_configs {
-                            Some(configs) => configs,
-                            None => { &( $(macros::ignore_expr!($t, None),)* ) }
+                        $(
+                            if significant.$i {
+                                indices.push($i);
+                            }
+                        )*
+                        indices
+                    }
+
+                    #[allow(unused_variables)]
+                    fn child_ids(config: &SupervisorConfig<K>) -> Vec<ChildId> {
+                        vec![$(macros::child_id!(config, $i),)*]
+                    }
+
+                    #[allow(unused_variables, unused_mut)]
+                    fn child_info(config: &SupervisorConfig<K>) -> Vec<ChildInfo> {
+                        let mut info = Vec::new();
+                        let Some(children) = config.children.as_ref() else {
+                            return info;
                         };
+                        $(
+                            let name = config.children_names.as_ref().and_then(|names| names.$i.clone());
+                            info.push(ChildInfo {
+                                child: macros::child_id!(config, $i),
+                                name,
+                                type_name: std::any::type_name::<$t>().to_owned(),
+                                node_id: children.$i.node_id(),
+                                process_id: children.$i.id(),
+                                status: if config.restart_state[$i].stopped {
+                                    ChildStatus::Stopped
+                                } else if config.restart_state[$i].pending {
+                                    ChildStatus::Restarting
+                                } else if children.$i.remote_inspect_alive() {
+                                    ChildStatus::Running
+                                } else {
+                                    ChildStatus::Exited
+                                },
+                                restarts: config.restart_state[$i].count,
+                            });
+                        )*
+                        info
+                    }
 
+                    #[allow(unused_variables, unused_mut)]
+                    fn child_health(config: &SupervisorConfig<K>, ping: Duration, deadline: Instant) -> Vec<HealthReport> {
+                        let mut reports = Vec::new();
+                        let Some(children) = config.children.as_ref() else {
+                            return reports;
+                        };
                         $(
-                            let [<tag$i>] = Tag::new();
-                            let proc_builder = $t::link_with([<tag$i>]);
-                            let proc_builder = if let Some(config) = &configs.$i {
-                                proc_builder.configure(&config)
+                            let child = &children.$i;
+                            let state = &config.restart_state[$i];
+                            // The process of a stopped child, or one waiting for its restart, has exited.
+                            let mut report = if state.stopped || state.pending {
+                                HealthReport::down(std::any::type_name::<$t>(), child.node_id(), child.id())
                             } else {
-                                proc_builder
-                            };
-                            let result = match &names.$i {
-                                Some(name) => proc_builder.start_as(name, args.$i),
-                                None => proc_builder.start(args.$i),
-                            };
-                            let [<proc$i>] = match result {
-                                Ok(proc) => proc,
-                                Err(err) => panic!("Supervisor failed to start child `{:?}`", err),
+                                child_health::<$t>(child.node_id(), child.id(), ping, deadline)
                             };
+                            report.name = config.children_names.as_ref().and_then(|names| names.$i.clone());
+                            // Deleted children aren't reported.
+                            if !state.deleted {
+                                reports.push(report);
+                            }
                         )*
-                        config.children = Some(($([<proc$i>],)*));
-                        config.children_tags = Some(($([<tag$i>],)*));
+                        reports
                     }
 
                     #[allow(unused_variables)]
-                    fn terminate(config: SupervisorConfig<K>) {
-                        macros::reverse_shutdown!(config, [ $($i)* ]);
+                    fn stop_child(config: &mut SupervisorConfig<K>, index: usize) {
+                        $(
+                            if index == $i {
+                                let child = &config.children.as_ref().unwrap().$i;
+                                if child.remote_inspect_alive() {
+                                    macros::unregister!(config, $i);
+                                    macros::shutdown!(config, $i);
+                                }
+                            }
+                        )*
+                    }
+
+                    #[allow(unused_variables)]
+                    fn start_child(config: &mut SupervisorConfig<K>, index: usize) -> Result<(), String> {
+                        $(
+                            if index == $i {
+                                macros::start!(config, $t, $i)?;
+                            }
+                        )*
+                        Ok(())
+                    }
+
+                    #[allow(unused_variables)]
+                    fn deliver_siblings(config: &mut SupervisorConfig<K>, started: &[usize]) {
+                        let Some(siblings) = config.children_siblings.as_ref() else {
+                            return;
+                        };
+                        if started.is_empty() {
+                            return;
+                        }
+                        let children: Vec<ChildInfo> = Self::child_info(config)
+                            .into_iter()
+                            .enumerate()
+                            .filter(|(index, _)| !config.restart_state[*index].deleted)
+                            .map(|(_, info)| info)
+                            .collect();
+                        let refs = SiblingRefs::new(children);
+                        let processes = config.children.as_ref().unwrap();
+                        $(
+                            let state = &config.restart_state[$i];
+                            if let Some(deliver) = &siblings.$i {
+                                if !(state.stopped || state.pending || state.deleted) {
+                                    deliver.deliver(&processes.$i, refs.clone());
+                                    // The name is only registered after the delivery.
+                                    let name = config.children_names.as_ref().and_then(|names| names.$i.as_ref());
+                                    if let (true, Some(name)) = (started.contains(&$i), name) {
+                                        processes.$i.register(name);
+                                    }
+                                }
+                            }
+                        )*
+                    }
+
+                    #[allow(unused_variables)]
+                    fn replace_arg(config: &mut SupervisorConfig<K>, index: usize, arg: &[u8], set_arg: SetArgFn) -> Result<(), String> {
+                        $(
+                            if index == $i {
+                                let target = &mut config.children_args.as_mut().unwrap().$i;
+                                return set_arg(arg, target as *mut $t::Arg as *mut ());
+                            }
+                        )*
+                        Ok(())
                     }
 
                     #[allow(unused_variables)]
@@ -386,38 +2926,10 @@ mod macros {
                                 $(
 
                                     if tag == config.children_tags.unwrap().$i {
-                                        let args = config.children_args.as_ref().unwrap().$i.clone();
-                                        let name = match &config.children_names {
-                                            Some(names) => &names.$i,
-                                            None => &None
-                                        };
-                                        let proc_config = match &config.children_configs {
-                                            Some(configs) => &configs.$i,
-                                            None => &None
-                                        };
-
-                                        let link_tag = Tag::new();
-                                        let proc_builder = $t::link_with(link_tag);
-                                        let proc_builder = if let Some(config) = proc_config {
-                                            proc_builder.configure(&config)
-                                        } else {
-                                            proc_builder
-                                        };
-                                        let result = match &name {
-                                            Some(name) => {
-                                                // Remove first the previous registration
-                                                let remove = process_name::<$t, $t::Serializer>(ProcessType::ProcessRef, &name);
-                                                unsafe { host::api::registry::remove(remove.as_ptr(), remove.len()) };
-                                                proc_builder.start_as(name, args)
-                                            },
-                                            None => proc_builder.start(args),
-                                        };
-                                        let proc = match result {
-                                            Ok(proc) => proc,
-                                            Err(err) => panic!("Supervisor failed to start child `{:?}`", err),
-                                        };
-                                        config.children.as_mut().unwrap().$i = proc;
-                                        config.children_tags.as_mut().unwrap().$i = link_tag;
+                                        if let Err(err) = macros::start!(config, $t, $i) {
+                                            config.restart_failed($i, err);
+                                        }
+                                        config.restart_state[$i].restarted();
                                     } else
 
                                 )*
@@ -445,42 +2957,14 @@ mod macros {
                                 // shutdown children in reversed start order
                                 macros::reverse_shutdown!(config, skip tag, [ $($i)* ]);
 
-                                // restart all
+                                // restart all, except deleted children
                                 $(
-
-                                    let args = config.children_args.as_ref().unwrap().$i.clone();
-                                    let name = match &config.children_names {
-                                        Some(names) => &names.$i,
-                                        None => &None
-                                    };
-                                    let proc_config = match &config.children_configs {
-                                        Some(configs) => &configs.$i,
-                                        None => &None
-                                    };
-
-                                    let link_tag = Tag::new();
-                                    let proc_builder = $t::link_with(link_tag);
-                                    let proc_builder = if let Some(config) = proc_config {
-                                        proc_builder.configure(&config)
-                                    } else {
-                                        proc_builder
-                                    };
-                                    let result = match name {
-                                        Some(name) => {
-                                            // Remove first the previous registration
-                                            let remove = process_name::<$t, $t::Serializer>(ProcessType::ProcessRef, &name);
-                                            unsafe { host::api::registry::remove(remove.as_ptr(), remove.len()) };
-                                            proc_builder.start_as(name, args)
-                                        },
-                                        None => proc_builder.start(args),
-                                    };
-                                    let proc = match result {
-                                        Ok(proc) => proc,
-                                        Err(err) => panic!("Supervisor failed to start child `{:?}`", err),
-                                    };
-                                    config.children.as_mut().unwrap().$i = proc;
-                                    config.children_tags.as_mut().unwrap().$i = link_tag;
-
+                                    if !config.restart_state[$i].deleted {
+                                        if let Err(err) = macros::start!(config, $t, $i) {
+                                            config.restart_failed($i, err);
+                                        }
+                                        config.restart_state[$i].restarted();
+                                    }
                                 )*
                             }
                             // If a child process terminates, the rest of the child processes (that is,
@@ -508,42 +2992,14 @@ mod macros {
                                     let mut seen_tag = false;
                                     $(
 
-                                        if seen_tag == true || tag == config.children_tags.unwrap().$i {
+                                        if tag == config.children_tags.unwrap().$i {
                                             seen_tag = true;
-
-                                            let args = config.children_args.as_ref().unwrap().$i.clone();
-                                            let name = match &config.children_names {
-                                                Some(names) => &names.$i,
-                                                None => &None
-                                            };
-                                            let proc_config = match &config.children_configs {
-                                                Some(configs) => &configs.$i,
-                                                None => &None
-                                            };
-
-                                            let link_tag = Tag::new();
-                                            let proc_builder = $t::link_with(link_tag);
-                                            let proc_builder = if let Some(config) = proc_config {
-                                                proc_builder.configure(config)
-                                            } else {
-                                                proc_builder
-                                            };
-                                            let result = match name {
-                                                Some(name) => {
-                                                    // Remove first the previous registration
-                                                    let remove = process_name::<$t, $t::Serializer>(ProcessType::ProcessRef, &name);
-                                                    unsafe { host::api::registry::remove(remove.as_ptr(), remove.len()) };
-                                                    proc_builder.start_as(name, args)
-                                                },
-                                                None => proc_builder.start(args),
-                                            };
-                                            let proc = match result {
-                                                Ok(proc) => proc,
-                                                Err(err) => panic!("Supervisor failed to start child `{:?}`", err),
-                                            };
-                                            config.children.as_mut().unwrap().$i = proc;
-                                            config.children_tags.as_mut().unwrap().$i = link_tag;
-
+                                        }
+                                        if seen_tag && !config.restart_state[$i].deleted {
+                                            if let Err(err) = macros::start!(config, $t, $i) {
+                                                config.restart_failed($i, err);
+                                            }
+                                            config.restart_state[$i].restarted();
                                         }
 
                                     )*
@@ -556,7 +3012,10 @@ mod macros {
         };
     }
 
-    pub(crate) use {ignore_expr, ignore_type, impl_supervisable, reverse_shutdown, tag};
+    pub(crate) use {
+        child_id, child_shutdown, handoff, has_siblings, ignore_type, impl_child_at,
+        impl_supervisable, reverse_shutdown, shutdown, spawn_name, start, tag, unregister,
+    };
 }
 
 #[cfg(test)]
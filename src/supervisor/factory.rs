@@ -0,0 +1,205 @@
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::ap::handlers::Request;
+use crate::ap::{
+    self, AbstractProcess, Config, ProcessRef, RequestHandler, StartupError, TrapInfo,
+};
+use crate::serializer::Bincode;
+use crate::Tag;
+
+/// A supervisor starting any number of children of type `T` on demand, each
+/// with its own argument.
+///
+/// Each child is restarted on its own when it fails, with a copy of the
+/// argument it was spawned with. A child that fails to restart is removed.
+/// This fits workers started per connection or per job, whose number isn't
+/// known up front. On shutdown the children are shut down in reverse spawn
+/// order.
+pub struct FactorySupervisor<T> {
+    phantom: PhantomData<T>,
+}
+
+/// Reference to a [`FactorySupervisor`].
+pub type FactoryRef<T> = ProcessRef<FactorySupervisor<T>>;
+
+/// State of a [`FactorySupervisor`].
+pub struct FactoryState<T: AbstractProcess> {
+    /// Children in spawn order.
+    children: Vec<Instance<T>>,
+}
+
+struct Instance<T: AbstractProcess> {
+    process: ProcessRef<T>,
+    /// Tag of the link to the child.
+    tag: Tag,
+    /// Argument to restart the child with.
+    arg: T::Arg,
+}
+
+/// Starts a child linked to the supervisor.
+fn start_instance<T: AbstractProcess>(arg: T::Arg) -> Result<Instance<T>, StartupError<T>>
+where
+    T::Arg: Clone,
+{
+    let tag = Tag::new();
+    let process = T::link_with(tag).start(arg.clone())?;
+    Ok(Instance { process, tag, arg })
+}
+
+impl<T> AbstractProcess for FactorySupervisor<T>
+where
+    T: AbstractProcess + 'static,
+    T::Arg: Serialize + DeserializeOwned + Clone,
+    T::StartupError: Serialize + DeserializeOwned,
+{
+    type State = FactoryState<T>;
+    type Serializer = Bincode;
+    type Arg = ();
+    type Handlers = (
+        Request<SpawnInstance<T>>,
+        Request<CountInstances>,
+        Request<WhichChildren>,
+        Request<TerminateInstance>,
+    );
+    type StartupError = ();
+
+    fn init(config: Config<Self>, _: ()) -> Result<FactoryState<T>, ()> {
+        // Supervisor shouldn't die if the children die
+        config.die_if_link_dies(false);
+        Ok(FactoryState {
+            children: Vec::new(),
+        })
+    }
+
+    fn terminate(state: FactoryState<T>) {
+        for child in state.children.iter().rev() {
+            child.process.shutdown();
+        }
+    }
+
+    fn handle_link_death(mut state: ap::State<Self>, info: TrapInfo) {
+        let Some(index) = state
+            .children
+            .iter()
+            .position(|child| child.tag == info.tag)
+        else {
+            return;
+        };
+        match start_instance(state.children[index].arg.clone()) {
+            Ok(child) => state.children[index] = child,
+            Err(_) => {
+                state.children.remove(index);
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "T::Arg: Serialize + DeserializeOwned")]
+pub struct SpawnInstance<T: AbstractProcess>(T::Arg);
+impl<T> RequestHandler<SpawnInstance<T>> for FactorySupervisor<T>
+where
+    T: AbstractProcess + 'static,
+    T::Arg: Serialize + DeserializeOwned + Clone,
+    T::StartupError: Serialize + DeserializeOwned,
+{
+    type Response = Result<ProcessRef<T>, StartupError<T>>;
+
+    fn handle(mut state: ap::State<Self>, SpawnInstance(arg): SpawnInstance<T>) -> Self::Response {
+        let child = start_instance(arg)?;
+        let process = child.process;
+        state.children.push(child);
+        Ok(process)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CountInstances;
+impl<T> RequestHandler<CountInstances> for FactorySupervisor<T>
+where
+    T: AbstractProcess + 'static,
+    T::Arg: Serialize + DeserializeOwned + Clone,
+    T::StartupError: Serialize + DeserializeOwned,
+{
+    type Response = usize;
+
+    fn handle(state: ap::State<Self>, _: CountInstances) -> usize {
+        state.children.len()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WhichChildren;
+impl<T> RequestHandler<WhichChildren> for FactorySupervisor<T>
+where
+    T: AbstractProcess + 'static,
+    T::Arg: Serialize + DeserializeOwned + Clone,
+    T::StartupError: Serialize + DeserializeOwned,
+{
+    type Response = Vec<ProcessRef<T>>;
+
+    fn handle(state: ap::State<Self>, _: WhichChildren) -> Self::Response {
+        state.children.iter().map(|child| child.process).collect()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TerminateInstance(u64);
+impl<T> RequestHandler<TerminateInstance> for FactorySupervisor<T>
+where
+    T: AbstractProcess + 'static,
+    T::Arg: Serialize + DeserializeOwned + Clone,
+    T::StartupError: Serialize + DeserializeOwned,
+{
+    type Response = bool;
+
+    fn handle(mut state: ap::State<Self>, TerminateInstance(id): TerminateInstance) -> bool {
+        let Some(index) = state
+            .children
+            .iter()
+            .position(|child| child.process.id() == id)
+        else {
+            return false;
+        };
+        let child = state.children.remove(index);
+        child.process.shutdown();
+        true
+    }
+}
+
+impl<T> ProcessRef<FactorySupervisor<T>>
+where
+    T: AbstractProcess + 'static,
+    T::Arg: Serialize + DeserializeOwned + Clone,
+    T::StartupError: Serialize + DeserializeOwned,
+{
+    /// Starts a new child with `arg`.
+    ///
+    /// The supervisor restarts the child with a copy of `arg` when it fails.
+    pub fn spawn(&self, arg: T::Arg) -> Result<ProcessRef<T>, StartupError<T>> {
+        self.request(SpawnInstance(arg))
+    }
+
+    /// Returns the number of running children.
+    pub fn count(&self) -> usize {
+        self.request(CountInstances)
+    }
+
+    /// Returns the running children in spawn order.
+    ///
+    /// A restarted child keeps its position.
+    pub fn which_children(&self) -> Vec<ProcessRef<T>> {
+        self.request(WhichChildren)
+    }
+
+    /// Shuts `child` down and stops supervising it.
+    ///
+    /// Returns `false` if `child` isn't a child of the supervisor, e.g.
+    /// because it was restarted since and runs as a different process now.
+    pub fn terminate_child(&self, child: &ProcessRef<T>) -> bool {
+        self.request(TerminateInstance(child.id()))
+    }
+}
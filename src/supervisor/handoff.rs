@@ -0,0 +1,180 @@
+use std::marker::PhantomData;
+use std::mem;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::{Supervisor, SupervisorConfig};
+use crate::ap::{AbstractProcess, MessageHandler, ProcessRef, State};
+use crate::host;
+use crate::serializer::Bincode;
+
+/// Argument of a child that receives the state of its previous process when
+/// it's restarted, see [`SupervisorConfig::set_handoffs`].
+///
+/// The child saves its state with the [`StateHandoff`] returned by
+/// [`handoff`](Self::handoff), e.g. after each change. The supervisor keeps
+/// the last saved state and passes it to the next process of the child:
+///
+/// ```ignore
+/// fn init(_: Config<Self>, arg: RestartArg<u32>) -> Result<Counter, ()> {
+///     let count = arg.previous().unwrap_or(arg.original);
+///     Ok(Counter { count, handoff: arg.handoff() })
+/// }
+/// ```
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RestartArg<A> {
+    /// The argument set with [`SupervisorConfig::set_args`].
+    pub original: A,
+    /// The state last saved by the previous process of the child, encoded
+    /// with `Bincode`. `None` on the first start, or if the previous process
+    /// didn't save a state.
+    pub previous_state: Option<Vec<u8>>,
+    handoff: Option<StateHandoff>,
+}
+
+impl<A> RestartArg<A> {
+    pub fn new(original: A) -> Self {
+        RestartArg {
+            original,
+            previous_state: None,
+            handoff: None,
+        }
+    }
+
+    /// Returns the decoded state of the previous process.
+    ///
+    /// Returns `None` if there is no previous state, or if it can't be
+    /// decoded as `S`.
+    pub fn previous<S: DeserializeOwned>(&self) -> Option<S> {
+        bincode::deserialize(self.previous_state.as_ref()?).ok()
+    }
+
+    /// Returns the handle to save the state for the next process of the child.
+    ///
+    /// Returns `None` if the supervisor doesn't hand off the state of the
+    /// child.
+    pub fn handoff(&self) -> Option<StateHandoff> {
+        self.handoff
+    }
+}
+
+/// Saves the state of a child in its supervisor, see [`RestartArg`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct StateHandoff {
+    node_id: u64,
+    process_id: u64,
+    /// Position of the child.
+    child: usize,
+    /// Pointer to [`send_state`], instantiated for the supervisor.
+    send: usize,
+}
+
+impl StateHandoff {
+    /// Creates the handle of the child at `child` for the current process,
+    /// which is a supervisor of type `K`.
+    pub(super) fn new<K: Supervisor>(child: usize) -> Self {
+        StateHandoff {
+            node_id: host::node_id(),
+            process_id: host::process_id(),
+            child,
+            send: send_state::<K> as fn(u64, u64, SaveState) as usize,
+        }
+    }
+
+    /// Saves `state`, encoded with `Bincode`, replacing the previously saved
+    /// one.
+    ///
+    /// The state is sent to the supervisor without waiting for it. A child
+    /// that crashes right after saving could be restarted before the state
+    /// arrives, with the state saved before.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `state` can't be serialized.
+    pub fn save<S: Serialize>(&self, state: &S) {
+        // Safety: The pointer was created from the same function type in
+        // `StateHandoff::new`.
+        let send: fn(u64, u64, SaveState) = unsafe { mem::transmute(self.send) };
+        let message = SaveState {
+            child: self.child,
+            state: bincode::serialize(state).unwrap(),
+        };
+        send(self.node_id, self.process_id, message);
+    }
+}
+
+fn send_state<K: Supervisor>(node_id: u64, process_id: u64, message: SaveState) {
+    let supervisor = unsafe { ProcessRef::<K>::new(node_id, process_id) };
+    supervisor.send(message);
+}
+
+/// Enables the state handoff of a child of type `C`, see
+/// [`SupervisorConfig::set_handoffs`].
+///
+/// Only children started with a [`RestartArg`] can hand off their state.
+pub struct Handoff<C: AbstractProcess> {
+    set_arg: fn(&mut C::Arg, Option<Vec<u8>>, StateHandoff),
+    phantom: PhantomData<C>,
+}
+
+impl<C, A> Handoff<C>
+where
+    C: AbstractProcess<Arg = RestartArg<A>>,
+{
+    pub fn new() -> Self {
+        Handoff {
+            set_arg: |arg, previous_state, handoff| {
+                arg.previous_state = previous_state;
+                arg.handoff = Some(handoff);
+            },
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<C, A> Default for Handoff<C>
+where
+    C: AbstractProcess<Arg = RestartArg<A>>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: AbstractProcess> Handoff<C> {
+    /// Adds the saved state and the handle of the child at `child` to
+    /// `arg`.
+    pub(super) fn set_arg<K: Supervisor>(
+        &self,
+        arg: &mut C::Arg,
+        previous_state: Option<Vec<u8>>,
+        child: usize,
+    ) {
+        (self.set_arg)(arg, previous_state, StateHandoff::new::<K>(child));
+    }
+}
+
+/// Saves the state of a child, sent by its [`StateHandoff`].
+#[derive(Serialize, Deserialize)]
+pub struct SaveState {
+    child: usize,
+    state: Vec<u8>,
+}
+impl<T> MessageHandler<SaveState> for T
+where
+    T: Supervisor,
+    T: AbstractProcess<State = SupervisorConfig<T>, Serializer = Bincode>,
+{
+    fn handle(
+        mut state: State<Self>,
+        SaveState {
+            child,
+            state: saved,
+        }: SaveState,
+    ) {
+        if let Some(restart_state) = state.restart_state.get_mut(child) {
+            restart_state.saved_state = Some(saved);
+        }
+    }
+}
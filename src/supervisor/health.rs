@@ -0,0 +1,203 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use super::{is_supervisor, Supervisable, Supervisor, SupervisorConfig};
+use crate::ap::{AbstractProcess, ProcessRef, RequestHandler, State};
+use crate::host;
+use crate::serializer::Bincode;
+
+/// Time a health check takes at most with [`ProcessRef::health`].
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Health of a process in a [`HealthReport`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Health {
+    /// The process answered, and so did all of its children.
+    Healthy,
+    /// The supervisor answered, but only some of its children are healthy.
+    Degraded,
+    /// The process didn't answer in time, or it doesn't run. A supervisor
+    /// whose children are all down is also down.
+    Down,
+}
+
+impl Health {
+    /// Returns the health of a supervisor with `children`.
+    fn of(children: &[HealthReport]) -> Health {
+        if children.iter().all(|child| child.health == Health::Healthy) {
+            Health::Healthy
+        } else if children.iter().all(|child| child.health == Health::Down) {
+            Health::Down
+        } else {
+            Health::Degraded
+        }
+    }
+}
+
+/// Health of a supervisor and its children, returned by
+/// [`ProcessRef::health`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthReport {
+    /// Name the child is registered under.
+    pub name: Option<String>,
+    /// Type name of the process.
+    pub type_name: String,
+    pub node_id: u64,
+    pub process_id: u64,
+    pub health: Health,
+    /// Reports of the children, in the same order as
+    /// [`ProcessRef::which_children`]. Empty for processes that aren't
+    /// supervisors.
+    pub children: Vec<HealthReport>,
+}
+
+impl HealthReport {
+    pub fn is_healthy(&self) -> bool {
+        self.health == Health::Healthy
+    }
+
+    pub(super) fn down(type_name: &str, node_id: u64, process_id: u64) -> Self {
+        HealthReport {
+            name: None,
+            type_name: type_name.to_owned(),
+            node_id,
+            process_id,
+            health: Health::Down,
+            children: Vec::new(),
+        }
+    }
+}
+
+/// Pointer to [`child_health`], instantiated for the type of a child.
+pub(super) type HealthFn = fn(u64, u64, Duration, Instant) -> HealthReport;
+
+/// Returns the health of the child `C` running as process `process_id`.
+///
+/// A nested supervisor is asked for the health of its own children, other
+/// children are pinged by replacing their state with itself, waiting up to
+/// `ping`. Nothing waits past `deadline`.
+pub(super) fn child_health<C>(
+    node_id: u64,
+    process_id: u64,
+    ping: Duration,
+    deadline: Instant,
+) -> HealthReport
+where
+    C: AbstractProcess,
+    C::StartupError: 'static,
+{
+    let mut report = HealthReport::down(std::any::type_name::<C>(), node_id, process_id);
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    let child = unsafe { ProcessRef::<C>::new(node_id, process_id) };
+    if remaining.is_zero() || !child.remote_inspect_alive() {
+        return report;
+    }
+    if is_supervisor::<C>() {
+        // All supervisors have the same handlers, the request is understood
+        // independent of the children.
+        let nested = unsafe { ProcessRef::<AnySupervisor>::new(node_id, process_id) };
+        // Leave the nested supervisor some time to send its report back.
+        let request = GetHealth {
+            timeout: remaining - remaining / 10,
+        };
+        if let Ok(nested) = nested.request_timeout(request, Some(remaining)) {
+            report.health = nested.health;
+            report.children = nested.children;
+        }
+    } else if child
+        .replace_state_timeout((), |(), state| state, Some(ping.min(remaining)))
+        .is_ok()
+    {
+        report.health = Health::Healthy;
+    }
+    report
+}
+
+/// Stands in for the type of nested supervisors when asking for their health.
+struct AnySupervisor;
+
+impl Supervisor for AnySupervisor {
+    type Arg = ();
+    type Children = ();
+
+    fn init(_: &mut SupervisorConfig<Self>, _: ()) {}
+}
+
+impl<T> SupervisorConfig<T>
+where
+    T: Supervisor,
+{
+    /// Returns the health of the supervisor and its children, checking at
+    /// most for `timeout`.
+    ///
+    /// The report is reused for the time to live set with
+    /// [`set_health_check`](Self::set_health_check).
+    pub(super) fn health(&mut self, timeout: Duration) -> HealthReport {
+        if let Some((checked, report)) = &self.health_cache {
+            if checked.elapsed() < self.health_ttl {
+                return report.clone();
+            }
+        }
+        let deadline = Instant::now() + timeout;
+        let ping = self.health_ping;
+        let mut children = T::Children::child_health(self, ping, deadline);
+        children.extend(self.dynamic_children.iter().map(|child| {
+            if child.stopped {
+                HealthReport::down(&child.type_name, child.node_id, child.process_id)
+            } else {
+                (child.health)(child.node_id, child.process_id, ping, deadline)
+            }
+        }));
+        let report = HealthReport {
+            name: None,
+            type_name: std::any::type_name::<T>().to_owned(),
+            node_id: host::node_id(),
+            process_id: host::process_id(),
+            health: Health::of(&children),
+            children,
+        };
+        self.health_cache = Some((Instant::now(), report.clone()));
+        report
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetHealth {
+    timeout: Duration,
+}
+impl<T> RequestHandler<GetHealth> for T
+where
+    T: Supervisor,
+    T: AbstractProcess<State = SupervisorConfig<T>, Serializer = Bincode>,
+{
+    type Response = HealthReport;
+
+    fn handle(mut state: State<Self>, GetHealth { timeout }: GetHealth) -> HealthReport {
+        state.health(timeout)
+    }
+}
+
+impl<T> ProcessRef<T>
+where
+    T: Supervisor,
+    T: AbstractProcess<State = SupervisorConfig<T>, Serializer = Bincode>,
+{
+    /// Returns the health of the supervisor and all of its children,
+    /// including the children of nested supervisors.
+    ///
+    /// Children that aren't supervisors are pinged, waiting up to the ping
+    /// timeout set with [`SupervisorConfig::set_health_check`]. The whole
+    /// check takes at most `timeout`, children not checked by then are
+    /// reported as [`Down`](Health::Down).
+    pub fn health_timeout(&self, timeout: Duration) -> HealthReport {
+        self.request(GetHealth { timeout })
+    }
+
+    /// Returns the health of the supervisor and all of its children, see
+    /// [`health_timeout`](Self::health_timeout). The check takes at most 5
+    /// seconds.
+    pub fn health(&self) -> HealthReport {
+        self.health_timeout(HEALTH_TIMEOUT)
+    }
+}
@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+
+use super::ChildId;
+use crate::panic::catch_panic;
+
+/// Functions the supervisor calls when it restarts a child or gives up on
+/// it, see [`SupervisorConfig::set_hooks`](super::SupervisorConfig::set_hooks)
+/// and [`ChildSpec`](super::ChildSpec).
+///
+/// Hooks are meant for side effects outside of the child, like removing it
+/// from a load balancer or notifying an operator. They run inside of the
+/// supervisor, which doesn't handle exits or requests until they return, so
+/// they should only do a little work and not block, e.g. send a message to
+/// another process doing the rest. A panicking hook is logged and doesn't
+/// crash the supervisor.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChildHooks {
+    on_restart: Option<fn(ChildId, u32)>,
+    on_give_up: Option<fn(ChildId)>,
+}
+
+impl ChildHooks {
+    /// Creates hooks that don't do anything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calls `hook` after each restart of the child, with its id and restart
+    /// count, like [`SupervisorEvent::ChildRestarted`](super::SupervisorEvent).
+    pub fn on_restart(mut self, hook: fn(ChildId, u32)) -> Self {
+        self.on_restart = Some(hook);
+        self
+    }
+
+    /// Calls `hook` when the supervisor stops restarting the child, because
+    /// its exit exceeded the maximum restart intensity or its restart failed.
+    /// The supervisor exits afterwards.
+    pub fn on_give_up(mut self, hook: fn(ChildId)) -> Self {
+        self.on_give_up = Some(hook);
+        self
+    }
+
+    /// Replaces the hooks that are set in `other`.
+    pub(super) fn merge(&mut self, other: ChildHooks) {
+        self.on_restart = other.on_restart.or(self.on_restart);
+        self.on_give_up = other.on_give_up.or(self.on_give_up);
+    }
+
+    pub(super) fn restarted(&self, child: ChildId, count: u32) {
+        if let Some(hook) = self.on_restart {
+            let id = child.clone();
+            run_hook("on_restart", &child, move || hook(id, count));
+        }
+    }
+
+    pub(super) fn gave_up(&self, child: ChildId) {
+        if let Some(hook) = self.on_give_up {
+            let id = child.clone();
+            run_hook("on_give_up", &child, move || hook(id));
+        }
+    }
+}
+
+/// Runs the hook `name` of `child`, logging a panic instead of crashing the
+/// supervisor.
+fn run_hook(name: &str, child: &ChildId, hook: impl FnOnce()) {
+    if catch_panic(hook).is_err() {
+        crate::warning::emit(format!(
+            "The `{name}` hook of supervisor child `{child}` panicked"
+        ));
+    }
+}
+
+/// [`ChildHooks`] sent with [`ChangeChildSpec`](super::ChangeChildSpec).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RawHooks {
+    /// Pointer to the `on_restart` hook.
+    on_restart: Option<usize>,
+    /// Pointer to the `on_give_up` hook.
+    on_give_up: Option<usize>,
+}
+
+impl From<ChildHooks> for RawHooks {
+    fn from(hooks: ChildHooks) -> Self {
+        RawHooks {
+            on_restart: hooks.on_restart.map(|hook| hook as usize),
+            on_give_up: hooks.on_give_up.map(|hook| hook as usize),
+        }
+    }
+}
+
+impl From<RawHooks> for ChildHooks {
+    fn from(hooks: RawHooks) -> Self {
+        // Safety: The pointers were created from the same function types in
+        // `From<ChildHooks>`, by a process running the same module.
+        unsafe {
+            ChildHooks {
+                on_restart: hooks
+                    .on_restart
+                    .map(|hook| std::mem::transmute::<usize, fn(ChildId, u32)>(hook)),
+                on_give_up: hooks
+                    .on_give_up
+                    .map(|hook| std::mem::transmute::<usize, fn(ChildId)>(hook)),
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::marker::PhantomData;
+
+use serde::{Deserialize, Serialize};
+
+use super::{ChildId, ChildInfo};
+use crate::ap::{AbstractProcess, MessageHandler, ProcessRef};
+use crate::serializer::CanSerialize;
+
+/// The references of all static children of a supervisor, delivered to the
+/// children with [`Siblings`], see [`SupervisorConfig::set_siblings`].
+///
+/// A child handles it like any other message, e.g. by keeping the
+/// references it needs in its state:
+///
+/// ```ignore
+/// impl MessageHandler<SiblingRefs> for Ping {
+///     fn handle(mut state: State<Self>, siblings: SiblingRefs) {
+///         state.pong = siblings.get(&ChildId::named("pong"));
+///     }
+/// }
+/// ```
+///
+/// [`SupervisorConfig::set_siblings`]: super::SupervisorConfig::set_siblings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SiblingRefs {
+    children: Vec<ChildInfo>,
+}
+
+impl SiblingRefs {
+    pub(super) fn new(children: Vec<ChildInfo>) -> Self {
+        SiblingRefs { children }
+    }
+
+    /// Returns a reference to the child with the id `child`, as reported in
+    /// its [`ChildInfo`].
+    ///
+    /// Returns `None` if there is no such child, or if it isn't of type `C`.
+    pub fn get<C: AbstractProcess>(&self, child: &ChildId) -> Option<ProcessRef<C>> {
+        self.children
+            .iter()
+            .find(|info| info.child == *child)?
+            .process()
+    }
+
+    /// Returns the info of all children, in start order.
+    pub fn children(&self) -> &[ChildInfo] {
+        &self.children
+    }
+}
+
+/// Delivers [`SiblingRefs`] to a child of type `C`, see
+/// [`SupervisorConfig::set_siblings`](super::SupervisorConfig::set_siblings).
+pub struct Siblings<C: AbstractProcess> {
+    deliver: fn(u64, u64, SiblingRefs),
+    phantom: PhantomData<C>,
+}
+
+impl<C> Siblings<C>
+where
+    C: AbstractProcess + MessageHandler<SiblingRefs>,
+    C::Serializer: CanSerialize<SiblingRefs>,
+{
+    pub fn new() -> Self {
+        Siblings {
+            deliver: |node_id, process_id, siblings| {
+                let child = unsafe { ProcessRef::<C>::new(node_id, process_id) };
+                child.send(siblings);
+            },
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<C> Default for Siblings<C>
+where
+    C: AbstractProcess + MessageHandler<SiblingRefs>,
+    C::Serializer: CanSerialize<SiblingRefs>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: AbstractProcess> Siblings<C> {
+    /// Sends `siblings` to `child`.
+    pub(super) fn deliver(&self, child: &ProcessRef<C>, siblings: SiblingRefs) {
+        (self.deliver)(child.node_id(), child.id(), siblings);
+    }
+}
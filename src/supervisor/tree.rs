@@ -0,0 +1,470 @@
+use std::any::type_name;
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::mem;
+use std::time::{Duration, Instant};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{is_supervisor, shutdown_dynamic, start_error, SupervisorStrategy};
+use crate::ap::handlers::Request;
+use crate::ap::{
+    self, AbstractProcess, Config, ProcessRef, RequestHandler, StartupError, TrapInfo,
+};
+use crate::serializer::Bincode;
+use crate::{host, Tag};
+
+/// A declarative description of a tree of supervisors.
+///
+/// Each node of the tree is a [`TreeSupervisor`] with its own strategy,
+/// supervising workers and nested supervisors by name:
+///
+/// ```ignore
+/// let tree = SupervisorTree::new()
+///     .strategy(SupervisorStrategy::OneForOne)
+///     .child::<Db>("db", db_arg)
+///     .supervisor("workers", |workers| {
+///         workers
+///             .strategy(SupervisorStrategy::OneForAll)
+///             .child::<Worker>("first", 1)
+///             .child::<Worker>("second", 2)
+///     })
+///     .start()?;
+/// let db = tree.child::<Db>("db").unwrap();
+/// let worker = tree.child::<Worker>("workers/second").unwrap();
+/// ```
+///
+/// Children are started top-down in the order they were added, and shut down
+/// in reverse order. Only failed children are restarted, a child exiting
+/// normally stays down. Names are local to their supervisor and aren't
+/// registered, the same tree can be started several times.
+#[derive(Serialize, Deserialize)]
+pub struct SupervisorTree {
+    strategy: TreeStrategy,
+    max_restarts: Option<(u32, Duration)>,
+    start_timeout: Option<Duration>,
+    children: Vec<TreeChildSpec>,
+}
+
+/// Serializable copy of a [`SupervisorStrategy`].
+#[derive(Serialize, Deserialize)]
+enum TreeStrategy {
+    OneForOne,
+    OneForAll,
+    RestForOne,
+}
+
+/// A child of a node in the tree.
+#[derive(Serialize, Deserialize)]
+struct TreeChildSpec {
+    name: String,
+    type_name: String,
+    /// Pointer to [`start_child`] or [`start_subtree`].
+    start: usize,
+    /// Pointer to [`shutdown_dynamic`], instantiated for the type of the child.
+    shutdown: usize,
+    /// Argument of the child, encoded with `Bincode`.
+    arg: Vec<u8>,
+}
+
+/// Starts a child linked with the tag from its encoded argument, limiting
+/// its `init` to the start timeout of the node.
+///
+/// Returns the id of the process, or why it failed with a path relative to
+/// the child.
+type StartTreeChildFn = fn(&[u8], Tag, Option<Duration>) -> Result<u64, TreeStartError>;
+
+fn start_child<C>(arg: &[u8], tag: Tag, timeout: Option<Duration>) -> Result<u64, TreeStartError>
+where
+    C: AbstractProcess,
+    C::Arg: DeserializeOwned,
+    C::StartupError: Debug + 'static,
+{
+    let arg = bincode::deserialize(arg).unwrap();
+    let builder = C::link_with(tag);
+    // Nested supervisors time out their own children.
+    let child = match timeout.filter(|_| !is_supervisor::<C>()) {
+        Some(timeout) => builder.start_timeout(arg, timeout),
+        None => builder.start(arg),
+    };
+    match child {
+        Ok(child) => Ok(child.id()),
+        Err(err) => {
+            let err = start_error(err);
+            Err(TreeStartError {
+                path: err.path,
+                type_name: err.type_name,
+                reason: err.cause.to_string(),
+            })
+        }
+    }
+}
+
+/// Starts a nested node, which inherits the start timeout unless it has its
+/// own.
+fn start_subtree(arg: &[u8], tag: Tag, timeout: Option<Duration>) -> Result<u64, TreeStartError> {
+    let mut tree: SupervisorTree = bincode::deserialize(arg).unwrap();
+    tree.start_timeout = tree.start_timeout.or(timeout);
+    match TreeSupervisor::link_with(tag).start(tree) {
+        Ok(child) => Ok(child.id()),
+        Err(err) => Err(TreeStartError::from(err)),
+    }
+}
+
+impl SupervisorTree {
+    /// Creates a node without children, restarting them one by one.
+    pub fn new() -> Self {
+        SupervisorTree {
+            strategy: TreeStrategy::OneForOne,
+            max_restarts: None,
+            start_timeout: None,
+            children: Vec::new(),
+        }
+    }
+
+    /// Sets how the children of this node are restarted after one of them
+    /// fails.
+    pub fn strategy(mut self, strategy: SupervisorStrategy) -> Self {
+        self.strategy = match strategy {
+            SupervisorStrategy::OneForOne => TreeStrategy::OneForOne,
+            SupervisorStrategy::OneForAll => TreeStrategy::OneForAll,
+            SupervisorStrategy::RestForOne => TreeStrategy::RestForOne,
+        };
+        self
+    }
+
+    /// Limits the number of restarts of this node, like
+    /// [`SupervisorConfig::set_max_restarts`](super::SupervisorConfig::set_max_restarts).
+    ///
+    /// If the node exceeds the limit, it shuts down its children and fails,
+    /// so that the failure is handled by the node above it.
+    pub fn max_restarts(mut self, count: u32, within: Duration) -> Self {
+        self.max_restarts = Some((count, within));
+        self
+    }
+
+    /// Limits how long the `init` function of each child of this node can
+    /// take, also when it's restarted.
+    ///
+    /// A child that doesn't finish in time is killed, and the tree fails to
+    /// start with the path of the child. Nested nodes use the same limit for
+    /// their children, unless they set their own.
+    pub fn start_timeout(mut self, timeout: Duration) -> Self {
+        self.start_timeout = Some(timeout);
+        self
+    }
+
+    /// Adds a child of type `C`, started with `arg`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node already has a child named `name`, or if `name`
+    /// contains a `/`.
+    #[track_caller]
+    pub fn child<C>(mut self, name: &str, arg: C::Arg) -> Self
+    where
+        C: AbstractProcess,
+        C::Arg: Serialize + DeserializeOwned,
+        C::StartupError: Debug + 'static,
+    {
+        self.push(TreeChildSpec {
+            name: name.to_owned(),
+            type_name: type_name::<C>().to_owned(),
+            start: start_child::<C> as StartTreeChildFn as usize,
+            shutdown: shutdown_dynamic::<C> as fn(u64, u64) as usize,
+            arg: bincode::serialize(&arg).unwrap(),
+        });
+        self
+    }
+
+    /// Adds a nested supervisor, described by `build`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node already has a child named `name`, or if `name`
+    /// contains a `/`.
+    #[track_caller]
+    pub fn supervisor<F>(mut self, name: &str, build: F) -> Self
+    where
+        F: FnOnce(SupervisorTree) -> SupervisorTree,
+    {
+        let tree = build(SupervisorTree::new());
+        self.push(TreeChildSpec {
+            name: name.to_owned(),
+            type_name: type_name::<TreeSupervisor>().to_owned(),
+            start: start_subtree as StartTreeChildFn as usize,
+            shutdown: shutdown_dynamic::<TreeSupervisor> as fn(u64, u64) as usize,
+            arg: bincode::serialize(&tree).unwrap(),
+        });
+        self
+    }
+
+    #[track_caller]
+    fn push(&mut self, child: TreeChildSpec) {
+        assert!(
+            !child.name.contains('/'),
+            "name `{}` of a supervision tree child can't contain a `/`",
+            child.name
+        );
+        assert!(
+            self.children.iter().all(|other| other.name != child.name),
+            "supervision tree node already has a child named `{}`",
+            child.name
+        );
+        self.children.push(child);
+    }
+
+    /// Starts the whole tree, top-down, and returns its root.
+    ///
+    /// If any child fails to start, the already started part of the tree is
+    /// shut down again and the error names the path of the failed child.
+    pub fn start(self) -> Result<TreeRef, TreeStartError> {
+        TreeSupervisor::start(self).map_err(TreeStartError::from)
+    }
+}
+
+impl Default for SupervisorTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Error returned when a [`SupervisorTree`] fails to start.
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[error("supervision tree child `{path}` failed to start: {reason}")]
+pub struct TreeStartError {
+    /// Names of the nodes leading to the failed child, separated by `/`.
+    pub path: String,
+    /// Type name of the failed child.
+    pub type_name: String,
+    /// Why the child failed to start.
+    pub reason: String,
+}
+
+impl TreeStartError {
+    /// Prepends the name of the child at which the error passed by.
+    fn within(mut self, name: &str) -> Self {
+        self.path = if self.path.is_empty() {
+            name.to_owned()
+        } else {
+            format!("{name}/{}", self.path)
+        };
+        self
+    }
+}
+
+impl From<StartupError<TreeSupervisor>> for TreeStartError {
+    fn from(err: StartupError<TreeSupervisor>) -> Self {
+        match err {
+            StartupError::Custom(err) => err,
+            err => TreeStartError {
+                path: String::new(),
+                type_name: type_name::<TreeSupervisor>().to_owned(),
+                reason: format!("{err:?}"),
+            },
+        }
+    }
+}
+
+/// A node of a [`SupervisorTree`].
+pub struct TreeSupervisor;
+
+/// Reference to the root, or another node, of a [`SupervisorTree`].
+pub type TreeRef = ProcessRef<TreeSupervisor>;
+
+/// State of a [`TreeSupervisor`].
+pub struct TreeState {
+    strategy: TreeStrategy,
+    max_restarts: Option<(u32, Duration)>,
+    start_timeout: Option<Duration>,
+    /// Time of each restart within the restart window.
+    restarts: VecDeque<Instant>,
+    /// Children in start order.
+    children: Vec<TreeChild>,
+}
+
+struct TreeChild {
+    name: String,
+    type_name: String,
+    start: StartTreeChildFn,
+    shutdown: fn(u64, u64),
+    arg: Vec<u8>,
+    /// Tag of the link to the child.
+    tag: Tag,
+    process_id: u64,
+}
+
+impl TreeChild {
+    fn start(spec: TreeChildSpec, timeout: Option<Duration>) -> Result<Self, TreeStartError> {
+        // Safety: The pointers were created from the same functions in
+        // `SupervisorTree::child` and `SupervisorTree::supervisor`.
+        let start: StartTreeChildFn = unsafe { mem::transmute(spec.start) };
+        let shutdown: fn(u64, u64) = unsafe { mem::transmute(spec.shutdown) };
+        let tag = Tag::new();
+        let process_id = start(&spec.arg, tag, timeout).map_err(|err| err.within(&spec.name))?;
+        Ok(TreeChild {
+            name: spec.name,
+            type_name: spec.type_name,
+            start,
+            shutdown,
+            arg: spec.arg,
+            tag,
+            process_id,
+        })
+    }
+
+    fn restart(&mut self, timeout: Option<Duration>) {
+        self.tag = Tag::new();
+        match (self.start)(&self.arg, self.tag, timeout) {
+            Ok(process_id) => self.process_id = process_id,
+            Err(err) => panic!(
+                "Supervisor failed to restart tree child: {}",
+                err.within(&self.name)
+            ),
+        }
+    }
+}
+
+impl TreeState {
+    /// Shuts down the children in reverse start order, skipping the one
+    /// linked with `except`.
+    fn shutdown_children(children: &[TreeChild], except: Option<Tag>) {
+        for child in children.iter().rev() {
+            if Some(child.tag) != except {
+                (child.shutdown)(host::node_id(), child.process_id);
+            }
+        }
+    }
+
+    /// Records a restart and returns `false` if it exceeds the maximum restart
+    /// intensity.
+    fn record_restart(&mut self) -> bool {
+        let Some((count, within)) = self.max_restarts else {
+            return true;
+        };
+        let now = Instant::now();
+        while self
+            .restarts
+            .front()
+            .is_some_and(|&restart| now.duration_since(restart) > within)
+        {
+            self.restarts.pop_front();
+        }
+        self.restarts.push_back(now);
+        self.restarts.len() <= count as usize
+    }
+}
+
+impl AbstractProcess for TreeSupervisor {
+    type State = TreeState;
+    type Serializer = Bincode;
+    type Arg = SupervisorTree;
+    type Handlers = (Request<GetTreeChild>,);
+    type StartupError = TreeStartError;
+
+    fn init(config: Config<Self>, tree: SupervisorTree) -> Result<TreeState, TreeStartError> {
+        // Supervisor shouldn't die if the children die
+        config.die_if_link_dies(false);
+        let mut children = Vec::with_capacity(tree.children.len());
+        for spec in tree.children {
+            match TreeChild::start(spec, tree.start_timeout) {
+                Ok(child) => children.push(child),
+                Err(err) => {
+                    TreeState::shutdown_children(&children, None);
+                    return Err(err);
+                }
+            }
+        }
+        Ok(TreeState {
+            strategy: tree.strategy,
+            max_restarts: tree.max_restarts,
+            start_timeout: tree.start_timeout,
+            restarts: VecDeque::new(),
+            children,
+        })
+    }
+
+    fn terminate(state: TreeState) {
+        TreeState::shutdown_children(&state.children, None);
+    }
+
+    fn handle_link_death(mut state: ap::State<Self>, info: TrapInfo) {
+        let Some(index) = state
+            .children
+            .iter()
+            .position(|child| child.tag == info.tag)
+        else {
+            return;
+        };
+        if !state.record_restart() {
+            TreeState::shutdown_children(&state.children, Some(info.tag));
+            panic!(
+                "Supervisor {} exceeded the maximum restart intensity",
+                type_name::<Self>()
+            );
+        }
+        let restarted = match state.strategy {
+            TreeStrategy::OneForOne => index..index + 1,
+            TreeStrategy::OneForAll => 0..state.children.len(),
+            TreeStrategy::RestForOne => index..state.children.len(),
+        };
+        TreeState::shutdown_children(&state.children[restarted.clone()], Some(info.tag));
+        let timeout = state.start_timeout;
+        for child in &mut state.children[restarted] {
+            child.restart(timeout);
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetTreeChild {
+    name: String,
+    type_name: String,
+}
+impl RequestHandler<GetTreeChild> for TreeSupervisor {
+    type Response = Option<u64>;
+
+    fn handle(state: ap::State<Self>, request: GetTreeChild) -> Option<u64> {
+        state
+            .children
+            .iter()
+            .find(|child| child.name == request.name && child.type_name == request.type_name)
+            .map(|child| child.process_id)
+    }
+}
+
+impl ProcessRef<TreeSupervisor> {
+    /// Returns the child of type `C` at `path`, the names of the nested
+    /// supervisors leading to it and its own name separated by `/`.
+    ///
+    /// Returns `None` if there is no such child, or if it isn't of type `C`.
+    /// The reference is to the current process of the child, it becomes stale
+    /// when the child is restarted.
+    pub fn child<C: AbstractProcess>(&self, path: &str) -> Option<ProcessRef<C>> {
+        let (parents, name) = match path.rsplit_once('/') {
+            Some((parents, name)) => (Some(parents), name),
+            None => (None, path),
+        };
+        let node = match parents {
+            Some(parents) => self.subtree(parents)?,
+            None => *self,
+        };
+        let process_id = node.request(GetTreeChild {
+            name: name.to_owned(),
+            type_name: type_name::<C>().to_owned(),
+        })?;
+        Some(unsafe { ProcessRef::new(host::node_id(), process_id) })
+    }
+
+    /// Returns the nested supervisor at `path`, the names of the supervisors
+    /// leading to it separated by `/`.
+    pub fn subtree(&self, path: &str) -> Option<TreeRef> {
+        let mut node = *self;
+        for name in path.split('/') {
+            node = node.child::<TreeSupervisor>(name)?;
+        }
+        Some(node)
+    }
+}
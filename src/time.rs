@@ -3,7 +3,10 @@
 use std::time::Duration;
 
 use crate::ap::messages::{RequestMessage, ShutdownMessage};
-use crate::ap::{AbstractProcess, DeferredRequestHandler, ProcessRef, RequestHandler};
+use crate::ap::{
+    AbstractProcess, DeferredRequestHandler, ProcessRef, RequestHandler, ResponderRequestHandler,
+    ResponseIter, StreamItem, StreamRequestHandler,
+};
 use crate::host;
 use crate::serializer::CanSerialize;
 
@@ -36,6 +39,11 @@ impl<T: AbstractProcess> WithTimeout<ProcessRef<T>> {
         Self { timeout, item }
     }
 
+    /// Returns the process reference without the timeout.
+    pub fn process_ref(&self) -> ProcessRef<T> {
+        self.item
+    }
+
     /// Shuts the [`AbstractProcess`] down.
     ///
     /// The function will only wait for the duration of the specified timeout on
@@ -54,7 +62,10 @@ impl<T: AbstractProcess> WithTimeout<ProcessRef<T>> {
     /// Make a request to the process.
     ///
     /// The function will only wait for the duration of the specified timeout on
-    /// the response, before returning `Err(Timeout)`.
+    /// the response, before returning `Err(Timeout)`. A request that is dropped
+    /// because its deadline passed or its handler was removed, or whose handler
+    /// panicked, is also reported as `Err(Timeout)`, use
+    /// [`ProcessRef::request_timeout`] to tell them apart.
     #[track_caller]
     pub fn request<R: 'static>(&self, request: R) -> Result<T::Response, Timeout>
     where
@@ -63,7 +74,27 @@ impl<T: AbstractProcess> WithTimeout<ProcessRef<T>> {
         T::Serializer: CanSerialize<T::Response>,
         T::Serializer: CanSerialize<RequestMessage<R, T::Response, T::Serializer>>,
     {
-        self.item.request_timeout(request, Some(self.timeout))
+        self.item
+            .request_timeout(request, Some(self.timeout))
+            .map_err(|_| Timeout)
+    }
+
+    /// Make a request to a handler returning a [`ResponseStream`].
+    ///
+    /// The iterator will only wait for the duration of the specified timeout
+    /// on each item, before returning `Err(StreamError::TimedOut)`.
+    ///
+    /// [`ResponseStream`]: crate::ap::ResponseStream
+    #[track_caller]
+    pub fn request_stream<R: 'static>(&self, request: R) -> ResponseIter<T::Item, T::Serializer>
+    where
+        T: StreamRequestHandler<R>,
+        T::Serializer: CanSerialize<R>,
+        T::Serializer: CanSerialize<StreamItem<T::Item>>,
+        T::Serializer: CanSerialize<RequestMessage<R, StreamItem<T::Item>, T::Serializer>>,
+    {
+        self.item
+            .request_stream_timeout(request, Some(self.timeout))
     }
 
     /// Make a deferred request to the process.
@@ -80,6 +111,24 @@ impl<T: AbstractProcess> WithTimeout<ProcessRef<T>> {
     {
         self.item
             .deferred_request_timeout(request, Some(self.timeout))
+            .map_err(|_| Timeout)
+    }
+
+    /// Make a request to a [`ResponderRequestHandler`].
+    ///
+    /// The function will only wait for the duration of the specified timeout on
+    /// the response, before returning `Err(Timeout)`.
+    #[track_caller]
+    pub fn responder_request<R: 'static>(&self, request: R) -> Result<T::Response, Timeout>
+    where
+        T: ResponderRequestHandler<R>,
+        T::Serializer: CanSerialize<R>,
+        T::Serializer: CanSerialize<T::Response>,
+        T::Serializer: CanSerialize<RequestMessage<R, T::Response, T::Serializer>>,
+    {
+        self.item
+            .responder_request_timeout(request, Some(self.timeout))
+            .map_err(|_| Timeout)
     }
 }
 
@@ -112,4 +161,21 @@ impl<T: AbstractProcess> WithDelay<ProcessRef<T>> {
     {
         self.item.delayed_send(message, self.duration)
     }
+
+    /// Send message to the process after the specified duration has passed, if
+    /// the process is running now.
+    ///
+    /// Returns `false` without sending the message if the process doesn't
+    /// exist anymore, see [`ProcessRef::try_send`].
+    #[track_caller]
+    pub fn try_send<M: 'static>(&self, message: M) -> bool
+    where
+        T::Serializer: CanSerialize<M>,
+    {
+        if !self.item.may_be_alive() {
+            return false;
+        }
+        self.item.delayed_send(message, self.duration);
+        true
+    }
 }
@@ -0,0 +1,12 @@
+//! Warnings emitted by the library itself.
+
+use crate::host;
+
+/// Prints the warning `message` to stderr, together with the id of the current
+/// process.
+///
+/// All warnings of the library go through here, so that they look the same and
+/// can be routed somewhere else in one place.
+pub(crate) fn emit(message: String) {
+    eprintln!("warning: process {}: {message}", host::process_id());
+}
@@ -1,13 +1,16 @@
+use std::collections::HashSet;
 use std::time::Duration;
 
-use lunatic::ap::handlers::{DeferredRequest, Message, Request};
+use lunatic::ap::handlers::{DeferredRequest, Message, Request, ResponderRequest};
 use lunatic::ap::{
-    AbstractProcess, Config, DeferredRequestHandler, DeferredResponse, MessageHandler, ProcessRef,
-    RequestHandler, StartupError, State,
+    resolve_any, AbstractProcess, Config, Context, CrashReport, DeferredRequestHandler,
+    DeferredResponse, MessageHandler, Pipeline, ProcessRef, RequestError, RequestHandler,
+    Responder, ResponderRequestHandler, StartupError, State, TrapInfo,
 };
+use lunatic::process::{park, this_ref, unpark};
 use lunatic::serializer::Bincode;
 use lunatic::time::Timeout;
-use lunatic::{sleep, spawn_link, test};
+use lunatic::{sleep, spawn_link, test, Mailbox, Process, Tag};
 
 /// This `AbstractProcess` always panics on `init`.
 struct InitPanicksAP;
@@ -159,6 +162,26 @@ fn float_message_and_request_handling() {
     assert_eq!(ap.request(Sum), 2.6);
 }
 
+#[test]
+fn replace_state() {
+    let ap = FloatsServerAP::link().start(vec![0.5, 1.5]).unwrap();
+    ap.replace_state(2.0, |by, FloatsServerAP(floats)| {
+        FloatsServerAP(floats.into_iter().map(|float| float * by).collect())
+    })
+    .unwrap();
+    assert_eq!(ap.request(Sum), 4.0);
+    ap.send(Add(1.0));
+    assert_eq!(
+        ap.replace_state_timeout(
+            (),
+            |_, _| FloatsServerAP(vec![]),
+            Some(Duration::from_secs(1))
+        ),
+        Ok(())
+    );
+    assert_eq!(ap.request(Sum), 0.0);
+}
+
 /// `AbstractProcess` that self-references itself during `init` and in handlers.
 struct SelfRefAP(u32);
 
@@ -166,7 +189,7 @@ impl AbstractProcess for SelfRefAP {
     type State = Self;
     type Serializer = Bincode;
     type Arg = u32;
-    type Handlers = (Message<Inc>, Request<Count>);
+    type Handlers = (Message<Inc>, Request<Count>, Request<ThisRef>);
     type StartupError = ();
 
     fn init(config: Config<Self>, start: Self::Arg) -> Result<Self, ()> {
@@ -207,6 +230,33 @@ fn self_ref() {
     assert_eq!(ap.request(Count), 10);
 }
 
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ThisRef;
+impl RequestHandler<ThisRef> for SelfRefAP {
+    type Response = ProcessRef<SelfRefAP>;
+
+    fn handle(_: State<Self>, _: ThisRef) -> Self::Response {
+        current_process()
+    }
+}
+
+/// Looks up the running process without access to its state.
+fn current_process() -> ProcessRef<SelfRefAP> {
+    this_ref::<SelfRefAP>()
+}
+
+#[test]
+fn this_ref_in_handler() {
+    let ap = SelfRefAP::link().start(0).unwrap();
+    assert_eq!(ap.request(ThisRef), ap);
+}
+
+#[test]
+#[should_panic]
+fn this_ref_outside_of_abstract_process() {
+    this_ref::<SelfRefAP>();
+}
+
 /// `AbstractProcess` that is registered under a well-known name.
 struct RegisteredAP;
 
@@ -236,6 +286,70 @@ fn lookup() {
     assert!(doesnt_exist.is_ok());
 }
 
+#[test]
+fn process_ref_hash() {
+    let first = InitOkAP::link().start(()).unwrap();
+    let second = InitOkAP::link().start(()).unwrap();
+    let subscribers: HashSet<_> = [first, second, first].into_iter().collect();
+    assert_eq!(subscribers.len(), 2);
+    assert!(subscribers.contains(&first));
+    assert_eq!(first.global_id(), (first.node_id(), first.id()));
+    assert_ne!(first.global_id(), second.global_id());
+}
+
+#[test]
+#[allow(clippy::clone_on_copy)]
+fn process_ref_clone() {
+    let ap = FloatsServerAP::link().start(vec![1.0]).unwrap();
+    let clone = ap.clone();
+    assert_eq!(clone, ap);
+    clone.send(Add(2.0));
+    assert_eq!(ap.request(Sum), 3.0);
+}
+
+#[test]
+fn process_ref_in_message(mailbox: Mailbox<f64>) {
+    let ap = FloatsServerAP::link().start(vec![1.0]).unwrap();
+    // The child only learns about the process through a message.
+    let child = spawn_link!(
+        |mailbox: Mailbox<(ProcessRef<FloatsServerAP>, Process<f64>)>| {
+            let (ap, reply) = mailbox.receive();
+            ap.send(Add(2.0));
+            reply.send(ap.request(Sum));
+        }
+    );
+    child.send((ap, mailbox.this()));
+    assert_eq!(mailbox.receive(), 3.0);
+}
+
+#[test]
+fn broadcast_to_local_node() {
+    use lunatic::distributed::{broadcast_node, node_id, register_broadcast, BroadcastError};
+
+    let ap = FloatsServerAP::link().start(vec![1.0]).unwrap();
+    register_broadcast(&ap);
+    let missing = node_id() + 1;
+    let results = broadcast_node::<FloatsServerAP, _>(&[node_id(), missing], Add(2.0));
+    assert_eq!(
+        results,
+        vec![Ok(()), Err(BroadcastError::NotRegistered(missing))]
+    );
+    assert_eq!(ap.request(Sum), 3.0);
+}
+
+#[test]
+fn start_on_each_node_includes_local_node() {
+    use lunatic::distributed::node_id;
+
+    let results = FloatsServerAP::start_on_each_node(|node| vec![node as f64]);
+    let local = results
+        .into_iter()
+        .map(Result::unwrap)
+        .find(|ap| ap.node_id() == node_id())
+        .unwrap();
+    assert_eq!(local.request(Sum), node_id() as f64);
+}
+
 /// `AbstractProcess` that can panic on message.
 struct PanicOnMessageAP;
 
@@ -295,8 +409,8 @@ impl AbstractProcess for HandleLinkPanicAP {
         Ok(Self { panicked: false })
     }
 
-    fn handle_link_death(mut state: State<Self>, tag: lunatic::Tag) {
-        println!("Link trapped: {:?}", tag);
+    fn handle_link_death(mut state: State<Self>, info: TrapInfo) {
+        println!("Link trapped: {:?}", info.tag);
         state.panicked = true;
     }
 }
@@ -446,6 +560,55 @@ fn deferred_handle_request() {
     assert_eq!(response, "Hello world");
 }
 
+/// `AbstractProcess` that answers deferred requests with the results of two
+/// `FloatsServerAP`s.
+struct PipelineAP {
+    first: ProcessRef<FloatsServerAP>,
+    second: ProcessRef<FloatsServerAP>,
+}
+
+impl AbstractProcess for PipelineAP {
+    type State = Self;
+    type Serializer = Bincode;
+    type Arg = (ProcessRef<FloatsServerAP>, ProcessRef<FloatsServerAP>);
+    type Handlers = (DeferredRequest<Sum>,);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, (first, second): Self::Arg) -> Result<Self, ()> {
+        Ok(Self { first, second })
+    }
+}
+
+impl DeferredRequestHandler<Sum> for PipelineAP {
+    type Response = f64;
+
+    fn handle(state: State<Self>, _: Sum, deferred_response: DeferredResponse<f64, Self>) {
+        let second = state.second;
+        Pipeline::request(state.first, Sum)
+            .then(move |first| {
+                if first > 0.0 {
+                    Pipeline::request(second, Sum).respond_with(move |second| first + second)
+                } else {
+                    Pipeline::ready(first)
+                }
+            })
+            .respond_to(deferred_response);
+    }
+}
+
+#[test]
+fn pipeline() {
+    let first = FloatsServerAP::link().start(vec![1.0, 2.0]).unwrap();
+    let second = FloatsServerAP::link().start(vec![4.0]).unwrap();
+    let ap = PipelineAP::link().start((first, second)).unwrap();
+    assert_eq!(ap.deferred_request(Sum), 7.0);
+    assert_eq!(ap.deferred_request(Sum), 7.0);
+
+    let empty = FloatsServerAP::link().start(vec![]).unwrap();
+    let ap = PipelineAP::link().start((empty, second)).unwrap();
+    assert_eq!(ap.deferred_request(Sum), 0.0);
+}
+
 /// `AbstractProcess` that times out on a deferred request/response
 struct DeferredRequestTimeoutAP;
 
@@ -477,3 +640,413 @@ fn deferred_request_timeout() {
         .deferred_request("Hello".to_owned());
     assert_eq!(response, Err(Timeout));
 }
+
+/// `AbstractProcess` that reports the deadline of incoming requests
+struct DeadlineAP {
+    handled: u32,
+}
+
+impl AbstractProcess for DeadlineAP {
+    type State = Self;
+    type Serializer = Bincode;
+    type Arg = ();
+    type Handlers = (Message<u64>, Request<()>, Request<u32>);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, _: Self::Arg) -> Result<Self, ()> {
+        Ok(Self { handled: 0 })
+    }
+}
+
+impl MessageHandler<u64> for DeadlineAP {
+    fn handle(_: State<Self>, millis: u64) {
+        sleep(Duration::from_millis(millis));
+    }
+}
+
+impl RequestHandler<()> for DeadlineAP {
+    type Response = Option<Duration>;
+
+    fn handle(mut state: State<Self>, _: ()) -> Self::Response {
+        state.handled += 1;
+        Context::remaining_time()
+    }
+}
+
+impl RequestHandler<u32> for DeadlineAP {
+    type Response = u32;
+
+    fn handle(state: State<Self>, _: u32) -> Self::Response {
+        state.handled
+    }
+}
+
+#[test]
+fn request_deadline() {
+    let ap = DeadlineAP::link().start(()).unwrap();
+    assert_eq!(ap.request(()), None);
+    let remaining = ap
+        .request_timeout((), Some(Duration::from_secs(10)))
+        .unwrap()
+        .unwrap();
+    assert!(remaining > Duration::ZERO && remaining <= Duration::from_secs(10));
+}
+
+#[test]
+fn request_deadline_exceeded() {
+    let ap = DeadlineAP::link().start(()).unwrap();
+    // Keep the process busy until the deadline passes.
+    ap.send(50u64);
+    let response = ap.request_timeout((), Some(Duration::from_millis(10)));
+    assert_eq!(response, Err(RequestError::TimedOut));
+    // The expired request was dropped without running the handler.
+    assert_eq!(ap.request(0u32), 0);
+}
+
+#[test]
+fn try_request() {
+    let ap = DeadlineAP::link().start(()).unwrap();
+    let response = ap.try_request(0u32, Duration::from_millis(100));
+    assert_eq!(response, Ok(Some(0)));
+    // A busy process is skipped.
+    ap.send(50u64);
+    let response = ap.try_request(0u32, Duration::from_millis(10));
+    assert_eq!(response, Ok(None));
+
+    ap.unlink();
+    ap.kill();
+    sleep(Duration::from_millis(10));
+    let response = ap.try_request(0u32, Duration::from_millis(10));
+    assert_eq!(response, Err(RequestError::ProcessDied));
+}
+
+/// `AbstractProcess` that registers cleanup actions, each reporting its id to
+/// the parent.
+struct CleanupAP;
+
+impl AbstractProcess for CleanupAP {
+    type State = bool;
+    type Serializer = Bincode;
+    type Arg = (Process<u32>, bool);
+    type Handlers = (Message<(Process<u32>, u32)>,);
+    type StartupError = ();
+
+    fn init(config: Config<Self>, (parent, panic): Self::Arg) -> Result<bool, ()> {
+        config.defer_on_terminate(move || parent.send(1));
+        config.defer_on_terminate(|| panic!("cleanup failed"));
+        Ok(panic)
+    }
+
+    fn terminate(panic: Self::State) {
+        if panic {
+            panic!("terminate failed");
+        }
+    }
+}
+
+impl MessageHandler<(Process<u32>, u32)> for CleanupAP {
+    fn handle(state: State<Self>, (parent, id): (Process<u32>, u32)) {
+        state.defer_on_terminate(move || parent.send(id));
+    }
+}
+
+#[test]
+fn defer_on_terminate(mailbox: Mailbox<u32>) {
+    let ap = CleanupAP::link().start((mailbox.this(), false)).unwrap();
+    ap.send((mailbox.this(), 2));
+    ap.shutdown();
+    // Executed in reverse order, the panicking action doesn't stop the rest.
+    assert_eq!(mailbox.receive(), 2);
+    assert_eq!(mailbox.receive(), 1);
+}
+
+#[test]
+fn defer_on_terminate_panic(mailbox: Mailbox<u32>) {
+    let ap = CleanupAP::link().start((mailbox.this(), true)).unwrap();
+    ap.unlink();
+    ap.send((mailbox.this(), 2));
+    // The process dies after running the cleanup actions.
+    assert!(ap
+        .with_timeout(Duration::from_millis(100))
+        .shutdown()
+        .is_err());
+    assert_eq!(mailbox.receive(), 2);
+    assert_eq!(mailbox.receive(), 1);
+}
+
+/// `AbstractProcess` that replies to requests early and finishes the work
+/// afterwards.
+struct ResponderAP {
+    saved: u32,
+}
+
+impl AbstractProcess for ResponderAP {
+    type State = Self;
+    type Serializer = Bincode;
+    type Arg = ();
+    type Handlers = (ResponderRequest<u32>, Request<()>);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, _: Self::Arg) -> Result<Self, ()> {
+        Ok(Self { saved: 0 })
+    }
+}
+
+impl ResponderRequestHandler<u32> for ResponderAP {
+    type Response = bool;
+
+    fn handle(
+        mut state: State<Self>,
+        millis: u32,
+        responder: &mut Responder<bool, Self>,
+    ) -> Option<bool> {
+        if millis == 0 {
+            // Let the return value be sent as the response.
+            return Some(false);
+        }
+        responder.respond(true);
+        sleep(Duration::from_millis(millis as u64));
+        state.saved += 1;
+        None
+    }
+}
+
+impl RequestHandler<()> for ResponderAP {
+    type Response = u32;
+
+    fn handle(state: State<Self>, _: ()) -> Self::Response {
+        state.saved
+    }
+}
+
+#[test]
+fn responder_request() {
+    let ap = ResponderAP::link().start(()).unwrap();
+    // The response arrives before the handler finishes sleeping.
+    assert_eq!(
+        ap.with_timeout(Duration::from_millis(50))
+            .responder_request(200),
+        Ok(true)
+    );
+    // The next request waits on the rest of the handler.
+    assert_eq!(ap.request(()), 1);
+    assert!(!ap.responder_request(0));
+}
+
+/// `AbstractProcess` that reports crashes to a named process.
+struct CrashReportAP;
+
+impl AbstractProcess for CrashReportAP {
+    type State = ();
+    type Serializer = Bincode;
+    type Arg = String;
+    type Handlers = (Message<String>,);
+    type StartupError = ();
+
+    fn init(config: Config<Self>, destination: Self::Arg) -> Result<(), ()> {
+        config.report_crashes_to(&destination);
+        Ok(())
+    }
+}
+
+impl MessageHandler<String> for CrashReportAP {
+    fn handle(_: State<Self>, message: String) {
+        panic!("{}", message);
+    }
+}
+
+#[test]
+fn crash_report(mailbox: Mailbox<CrashReport>) {
+    mailbox.this().register(&"crash_report_receiver");
+    let ap = CrashReportAP::link()
+        .start("crash_report_receiver".to_owned())
+        .unwrap();
+    ap.unlink();
+    ap.send("boom".to_owned());
+    let report = mailbox.receive_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(report.process_id, ap.id());
+    assert!(report
+        .handler_name
+        .contains("Message<alloc::string::String>"));
+    assert_eq!(report.panic_message, "boom");
+    sleep(Duration::from_millis(10));
+    assert!(!ap.is_alive());
+}
+
+#[test]
+fn remote_inspect_alive() {
+    let ap = SelfRefAP::link().start(0).unwrap();
+    assert!(ap.remote_inspect_alive());
+    ap.shutdown();
+    sleep(Duration::from_millis(10));
+    assert!(!ap.remote_inspect_alive());
+}
+
+/// `AbstractProcess` that makes requests to itself.
+struct SelfRequestAP;
+
+impl AbstractProcess for SelfRequestAP {
+    type State = ();
+    type Serializer = Bincode;
+    type Arg = ();
+    type Handlers = (Request<bool>, Request<()>);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, _: Self::Arg) -> Result<(), ()> {
+        Ok(())
+    }
+}
+
+impl RequestHandler<bool> for SelfRequestAP {
+    type Response = ();
+
+    fn handle(state: State<Self>, aliased: bool) -> Self::Response {
+        let this = if aliased {
+            ProcessRef::<SelfRequestAP>::lookup(&"self_request").unwrap()
+        } else {
+            state.self_ref()
+        };
+        this.request(())
+    }
+}
+
+impl RequestHandler<()> for SelfRequestAP {
+    type Response = ();
+
+    fn handle(_: State<Self>, _: ()) -> Self::Response {}
+}
+
+#[test]
+#[should_panic]
+fn request_to_self() {
+    let ap = SelfRequestAP::link().start(()).unwrap();
+    ap.request(false);
+}
+
+#[test]
+#[should_panic]
+fn request_to_self_aliased() {
+    let ap = SelfRequestAP::link().start_as(&"self_request", ()).unwrap();
+    ap.request(true);
+}
+
+/// `AbstractProcess` that sleeps for the requested number of milliseconds.
+struct SleepAP;
+
+impl AbstractProcess for SleepAP {
+    type State = ();
+    type Serializer = Bincode;
+    type Arg = ();
+    type Handlers = (Request<u64>,);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, _: Self::Arg) -> Result<(), ()> {
+        Ok(())
+    }
+}
+
+impl RequestHandler<u64> for SleepAP {
+    type Response = u64;
+
+    fn handle(_: State<Self>, millis: u64) -> Self::Response {
+        sleep(Duration::from_millis(millis));
+        millis
+    }
+}
+
+#[test]
+fn batch_request() {
+    let ap = SleepAP::link().start(()).unwrap();
+    assert_eq!(
+        ap.batch_request(vec![30, 10, 20]),
+        vec![Ok(30), Ok(10), Ok(20)]
+    );
+    assert_eq!(ap.batch_request(Vec::new()), vec![]);
+    // The timeout is shared by all requests.
+    assert_eq!(
+        ap.batch_request_timeout(vec![0, 30, 30], Some(Duration::from_millis(45))),
+        vec![Ok(0), Ok(30), Err(RequestError::TimedOut)]
+    );
+}
+
+#[test]
+fn call_with_context() {
+    let slow = SleepAP::link().start(()).unwrap();
+    let fast = SleepAP::link().start(()).unwrap();
+
+    // Responses are matched to their context, in the order they arrive.
+    let mut calls = vec![
+        slow.call_with_context("slow", 50),
+        fast.call_with_context("fast", 10),
+    ];
+    assert_eq!(resolve_any(&mut calls, None), Some(("fast", 10)));
+    assert_eq!(resolve_any(&mut calls, None), Some(("slow", 50)));
+    assert_eq!(resolve_any(&mut calls, None), None);
+
+    let call = slow.call_with_context(1, 30);
+    assert_eq!(call.resolve(None), Ok((1, 30)));
+    let call = slow.call_with_context(2, 30);
+    assert_eq!(
+        call.resolve(Some(Duration::from_millis(10))),
+        Err(RequestError::TimedOut)
+    );
+
+    let mut calls = vec![fast.call_with_context((), 30)];
+    assert_eq!(
+        resolve_any(&mut calls, Some(Duration::from_millis(10))),
+        None
+    );
+    assert_eq!(calls.len(), 1);
+}
+
+/// `AbstractProcess` that parks handlers until a value arrives.
+struct ParkAP;
+
+impl AbstractProcess for ParkAP {
+    type State = Vec<u32>;
+    type Serializer = Bincode;
+    type Arg = ();
+    type Handlers = (Request<Park>, Request<Values>);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, _: ()) -> Result<Vec<u32>, ()> {
+        Ok(Vec::new())
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Park(u32);
+impl RequestHandler<Park> for ParkAP {
+    type Response = Tag;
+
+    fn handle(_: State<Self>, Park(offset): Park) -> Self::Response {
+        let tag = Tag::new();
+        park::<Self, u32, _>(tag, move |mut state, value| state.push(value + offset));
+        tag
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Values;
+impl RequestHandler<Values> for ParkAP {
+    type Response = Vec<u32>;
+
+    fn handle(state: State<Self>, _: Values) -> Self::Response {
+        state.clone()
+    }
+}
+
+#[test]
+fn park_and_unpark() {
+    let ap = ParkAP::link().start(()).unwrap();
+    let first = ap.request(Park(10));
+    let second = ap.request(Park(20));
+    // Parked handlers don't block other messages.
+    assert!(ap.request(Values).is_empty());
+    unpark(ap, second, 2);
+    unpark(ap, first, 1);
+    assert_eq!(ap.request(Values), vec![22, 11]);
+    // The continuation only runs once.
+    unpark(ap, first, 1);
+    assert_eq!(ap.request(Values), vec![22, 11]);
+}
@@ -1,8 +1,8 @@
 use std::f32::consts::PI;
 use std::time::Duration;
 
-use lunatic::ap::{AbstractProcess, Config};
-use lunatic::{abstract_process, host, sleep, spawn_link, test, Tag};
+use lunatic::ap::{AbstractProcess, Config, Resume, Step};
+use lunatic::{abstract_process, host, sleep, spawn_link, test, Mailbox, Process, Tag};
 
 #[test]
 fn init() {
@@ -45,6 +45,87 @@ fn shutdown() {
     a.shutdown();
 }
 
+#[test]
+fn warm_up() {
+    struct A {
+        buffer: Vec<u8>,
+        start: u32,
+    }
+
+    #[abstract_process]
+    impl A {
+        #[warm_up]
+        fn warm_up() -> Vec<u8> {
+            Vec::with_capacity(1024)
+        }
+
+        #[init]
+        fn init(_: Config<Self>, start: u32, buffer: Vec<u8>) -> Result<A, ()> {
+            Ok(A { buffer, start })
+        }
+
+        #[handle_request]
+        fn capacity(&self) -> (u32, usize) {
+            (self.start, self.buffer.capacity())
+        }
+    }
+
+    let a = A::link().start(7).unwrap();
+    let (start, capacity) = a.capacity();
+    assert_eq!(start, 7);
+    assert!(capacity >= 1024);
+}
+
+#[test]
+fn terminate_timeout() {
+    struct A;
+
+    #[abstract_process]
+    impl A {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<A, ()> {
+            Ok(A)
+        }
+
+        #[terminate(timeout = "100ms")]
+        fn terminate(self) {
+            sleep(Duration::from_secs(60));
+        }
+    }
+
+    // Not linked, the process gets killed.
+    let a = A::start(()).unwrap();
+    a.shutdown();
+    assert!(!a.is_alive());
+}
+
+#[test]
+fn terminate_finishing_at_timeout(mailbox: Mailbox<()>) {
+    struct A;
+
+    #[abstract_process]
+    impl A {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<A, ()> {
+            Ok(A)
+        }
+
+        #[terminate(timeout = "20ms")]
+        fn terminate(self) {
+            sleep(Duration::from_millis(20));
+        }
+    }
+
+    // Either `terminate` or the watchdog wins, the call is answered once.
+    for _ in 0..10 {
+        let a = A::start(()).unwrap();
+        a.shutdown();
+        sleep(Duration::from_millis(10));
+        assert!(!a.is_alive());
+    }
+    assert!(mailbox.receive_timeout(Duration::from_millis(50)).is_err());
+}
+
 #[test]
 fn handle_link_trapped() {
     struct A {
@@ -79,6 +160,39 @@ fn handle_link_trapped() {
     assert!(a.is_link_trapped());
 }
 
+#[test]
+fn handle_link_trapped_info() {
+    use lunatic::ap::TrapInfo;
+
+    struct A {
+        trapped: Option<TrapInfo>,
+    }
+
+    #[abstract_process]
+    impl A {
+        #[init]
+        fn init(_config: Config<Self>, _arg: ()) -> Result<Self, ()> {
+            unsafe { host::api::process::die_when_link_dies(0) };
+            spawn_link!(|| panic!());
+            Ok(Self { trapped: None })
+        }
+
+        #[handle_link_death]
+        fn handle_link_trapped(&mut self, info: TrapInfo) {
+            self.trapped = Some(info);
+        }
+
+        #[handle_request]
+        fn trapped(&self) -> Option<TrapInfo> {
+            self.trapped.clone()
+        }
+    }
+
+    let a = A::start(()).unwrap();
+    sleep(Duration::from_millis(10));
+    assert!(a.trapped().is_some());
+}
+
 #[test]
 fn handle_zero_argument() {
     struct Counter {
@@ -562,3 +676,1457 @@ fn generics() {
         .unwrap();
     assert_eq!(PI * 2f32, s);
 }
+
+#[test]
+fn continue_with() {
+    use std::collections::HashMap;
+
+    struct Cache {
+        backend: Process<(u32, Resume<u32>)>,
+        values: HashMap<u32, u32>,
+    }
+
+    #[abstract_process]
+    impl Cache {
+        #[init]
+        fn init(_: Config<Self>, backend: Process<(u32, Resume<u32>)>) -> Result<Self, ()> {
+            Ok(Self {
+                backend,
+                values: HashMap::new(),
+            })
+        }
+
+        #[handle_request]
+        #[continue_with(finish_get)]
+        fn get(&mut self, key: u32, resume: Resume<u32>) -> Step<u32, u32> {
+            match self.values.get(&key) {
+                Some(value) => Step::Done(*value),
+                None => {
+                    self.backend.send((key, resume));
+                    Step::Pending(key)
+                }
+            }
+        }
+
+        fn finish_get(&mut self, key: u32, value: u32) -> Step<u32, u32> {
+            self.values.insert(key, value);
+            Step::Done(value)
+        }
+
+        #[handle_request]
+        fn cached(&self) -> usize {
+            self.values.len()
+        }
+    }
+
+    // Slow backend, answering with the doubled key.
+    let backend = spawn_link!(|mailbox: Mailbox<(u32, Resume<u32>)>| loop {
+        let (key, resume) = mailbox.receive();
+        sleep(Duration::from_millis(50));
+        resume.send(key * 2);
+    });
+    let cache = Cache::link().start(backend).unwrap();
+    let child = spawn_link!(@task |cache| cache.get(21));
+    sleep(Duration::from_millis(10));
+    // The cache keeps handling requests while `get` waits on the backend.
+    assert_eq!(cache.cached(), 0);
+    assert_eq!(child.result(), 42);
+    assert_eq!(cache.cached(), 1);
+    assert_eq!(cache.get(21), 42);
+    assert_eq!(cache.with_timeout(Duration::from_millis(100)).get(1), Ok(2));
+}
+
+#[test]
+fn output_pipe() {
+    use lunatic::ap::handlers::{Message, Request};
+    use lunatic::ap::{MessageHandler, RequestHandler, State};
+    use lunatic::serializer::Bincode;
+
+    struct Doubler;
+
+    #[abstract_process]
+    impl Doubler {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Self)
+        }
+
+        #[handle_message]
+        #[output]
+        fn double(&mut self, value: u32) -> u32 {
+            value * 2
+        }
+    }
+
+    struct Collector;
+
+    impl AbstractProcess for Collector {
+        type State = Vec<u32>;
+        type Serializer = Bincode;
+        type Arg = ();
+        type Handlers = (Message<u32>, Request<()>);
+        type StartupError = ();
+
+        fn init(_: Config<Self>, _: ()) -> Result<Vec<u32>, ()> {
+            Ok(Vec::new())
+        }
+    }
+
+    impl MessageHandler<u32> for Collector {
+        fn handle(mut state: State<Self>, value: u32) {
+            state.push(value);
+        }
+    }
+
+    impl RequestHandler<()> for Collector {
+        type Response = Vec<u32>;
+
+        fn handle(state: State<Self>, _: ()) -> Vec<u32> {
+            state.clone()
+        }
+    }
+
+    let doubler = Doubler::link().start(()).unwrap();
+    let collector = Collector::link().start(()).unwrap();
+    let pipe = doubler.pipe_to::<_, u32>(collector);
+    doubler.double(1);
+    doubler.double(2);
+    sleep(Duration::from_millis(10));
+    assert_eq!(collector.request(()), vec![2, 4]);
+    // Outputs are not forwarded after the pipe is dropped.
+    drop(pipe);
+    doubler.double(3);
+    sleep(Duration::from_millis(10));
+    assert_eq!(collector.request(()), vec![2, 4]);
+}
+
+#[test]
+fn generic_store() {
+    use std::collections::HashMap;
+
+    use lunatic::ap::{DeferredResponse, ProcessRef};
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+
+    struct KvStore<T> {
+        values: HashMap<String, T>,
+    }
+
+    #[abstract_process]
+    impl<T: Serialize + DeserializeOwned + Clone + 'static> KvStore<T> {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Self {
+                values: HashMap::new(),
+            })
+        }
+
+        #[handle_message]
+        fn insert(&mut self, key: String, value: T) {
+            self.values.insert(key, value);
+        }
+
+        #[handle_request]
+        fn get(&self, key: String) -> Option<T> {
+            self.values.get(&key).cloned()
+        }
+
+        #[handle_deferred_request]
+        fn take(&mut self, key: String, response: DeferredResponse<Option<T>, Self>) {
+            response.send_response(self.values.remove(&key))
+        }
+    }
+
+    KvStore::<String>::start_as(&"generic_store", ()).unwrap();
+    let numbers = KvStore::<u64>::link().start(()).unwrap();
+    let strings = ProcessRef::<KvStore<String>>::lookup(&"generic_store").unwrap();
+    strings.insert("key".to_owned(), "value".to_owned());
+    numbers.insert("key".to_owned(), 42);
+    assert_eq!(strings.get("key".to_owned()), Some("value".to_owned()));
+    assert_eq!(numbers.take("key".to_owned()), Some(42));
+    assert_eq!(numbers.get("key".to_owned()), None);
+}
+
+#[test]
+fn custom_serializer() {
+    use lunatic::ap::ProcessRef;
+    use lunatic::serializer::{Json, MessagePack};
+
+    struct JsonCounter(u32);
+
+    #[abstract_process(serializer = Json)]
+    impl JsonCounter {
+        #[init]
+        fn init(_: Config<Self>, start: u32) -> Result<Self, ()> {
+            Ok(Self(start))
+        }
+
+        #[handle_message]
+        fn add(&mut self, value: u32) {
+            self.0 += value;
+        }
+
+        #[handle_request]
+        fn count(&self) -> u32 {
+            self.0
+        }
+    }
+
+    struct MsgPackCounter<T>(T);
+
+    #[abstract_process(serializer = MessagePack)]
+    impl<T: Copy + serde::Serialize + serde::de::DeserializeOwned + 'static> MsgPackCounter<T> {
+        #[init]
+        fn init(_: Config<Self>, start: T) -> Result<Self, ()> {
+            Ok(Self(start))
+        }
+
+        #[handle_request]
+        fn value(&self) -> T {
+            self.0
+        }
+    }
+
+    let json = JsonCounter::link()
+        .start_as(&"custom_serializer", 1)
+        .unwrap();
+    let msgpack = MsgPackCounter::<u32>::link().start(2).unwrap();
+    json.add(2);
+    assert_eq!(json.count(), 3);
+    assert_eq!(msgpack.value(), 2);
+
+    let lookup = ProcessRef::<JsonCounter>::lookup(&"custom_serializer").unwrap();
+    assert_eq!(lookup.count(), 3);
+    assert!(ProcessRef::<MsgPackCounter<u32>>::lookup(&"custom_serializer").is_none());
+}
+
+#[test]
+fn owned_self_handler() {
+    enum Light {
+        Off,
+        On { switched: u32 },
+    }
+
+    #[abstract_process]
+    impl Light {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Light::Off)
+        }
+
+        #[handle_message]
+        fn toggle(self) -> Self {
+            match self {
+                Light::Off => Light::On { switched: 1 },
+                Light::On { .. } => Light::Off,
+            }
+        }
+
+        #[handle_message]
+        fn switch_on(self, times: u32) -> Self {
+            match self {
+                Light::Off => Light::On { switched: times },
+                Light::On { switched } => Light::On {
+                    switched: switched + times,
+                },
+            }
+        }
+
+        #[handle_request]
+        fn switched(&self) -> Option<u32> {
+            match self {
+                Light::Off => None,
+                Light::On { switched } => Some(*switched),
+            }
+        }
+    }
+
+    let light = Light::link().start(()).unwrap();
+    assert_eq!(light.switched(), None);
+    light.toggle();
+    assert_eq!(light.switched(), Some(1));
+    light.switch_on(2);
+    assert_eq!(light.switched(), Some(3));
+    light.toggle();
+    assert_eq!(light.switched(), None);
+}
+
+#[test]
+fn custom_names() {
+    use lunatic::ap::ProcessRef;
+
+    struct Users;
+
+    #[abstract_process(trait_name = "UserStore")]
+    impl Users {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Self)
+        }
+
+        #[handle_request(name = "FetchUser")]
+        fn get(&self, id: u32) -> String {
+            format!("user {id}")
+        }
+    }
+
+    struct Groups;
+
+    #[abstract_process]
+    impl Groups {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Self)
+        }
+
+        #[handle_request(name = "FetchGroup")]
+        fn get(&self, id: u32) -> String {
+            format!("group {id}")
+        }
+    }
+
+    let users = Users::link().start(()).unwrap();
+    let groups = Groups::link().start(()).unwrap();
+    assert_eq!(
+        <ProcessRef<Users> as UserStoreRequests>::get(&users, 1),
+        "user 1"
+    );
+    assert_eq!(
+        <ProcessRef<Groups> as GroupsRequests>::get(&groups, 2),
+        "group 2"
+    );
+    // The generated message types can be used directly.
+    assert_eq!(users.request(FetchUser(3)), "user 3");
+}
+
+#[test]
+fn default_arguments() {
+    use lunatic::ap::DeferredResponse;
+
+    struct Log(Vec<String>);
+
+    #[abstract_process]
+    impl Log {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Self(Vec::new()))
+        }
+
+        #[handle_message(default(level = "info".to_owned()))]
+        fn write(&mut self, message: String, level: String) {
+            self.0.push(format!("[{level}] {message}"));
+        }
+
+        #[handle_request(default(skip = 0, limit = 10))]
+        fn lines(&self, skip: usize, limit: usize) -> Vec<String> {
+            self.0.iter().skip(skip).take(limit).cloned().collect()
+        }
+
+        #[handle_deferred_request(default(limit = 1))]
+        fn last(&self, limit: usize, response: DeferredResponse<Vec<String>, Self>) {
+            response.send_response(self.0.iter().rev().take(limit).cloned().collect())
+        }
+    }
+
+    let log = Log::link().start(()).unwrap();
+    log.write("started".to_owned());
+    log.write_with("careful".to_owned(), "warn".to_owned());
+    assert_eq!(log.lines(), vec!["[info] started", "[warn] careful"]);
+    assert_eq!(log.lines_with(1, 1), vec!["[warn] careful"]);
+    assert_eq!(log.last(), vec!["[warn] careful"]);
+    assert_eq!(log.last_with(2).len(), 2);
+    assert_eq!(
+        log.with_timeout(Duration::from_millis(100)).lines(),
+        Ok(vec![
+            "[info] started".to_owned(),
+            "[warn] careful".to_owned()
+        ])
+    );
+    log.with_delay(Duration::from_millis(10))
+        .write("later".to_owned());
+    sleep(Duration::from_millis(20));
+    assert_eq!(log.lines().len(), 3);
+}
+
+#[test]
+fn fifo_mailbox_strategy() {
+    struct Log(Vec<u32>);
+
+    #[abstract_process(mailbox_strategy = "fifo")]
+    impl Log {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Self(Vec::new()))
+        }
+
+        #[handle_message]
+        fn push(&mut self, value: u32) {
+            self.0.push(value);
+        }
+
+        #[handle_request]
+        fn values(&self) -> Vec<u32> {
+            self.0.clone()
+        }
+    }
+
+    let log = Log::link().start(()).unwrap();
+    for value in 0..100 {
+        log.push(value);
+    }
+    assert_eq!(log.values(), (0..100).collect::<Vec<_>>());
+}
+
+#[test]
+fn priority_mailbox_strategy() {
+    struct Log(Vec<String>);
+
+    #[abstract_process(mailbox_strategy = "priority")]
+    impl Log {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Self(Vec::new()))
+        }
+
+        #[handle_message]
+        fn block(&self, duration: Duration) {
+            sleep(duration);
+        }
+
+        #[handle_message]
+        fn info(&mut self, line: String) {
+            self.0.push(line);
+        }
+
+        #[handle_message(priority = 1)]
+        fn warn(&mut self, line: String) {
+            self.0.push(line);
+        }
+
+        #[handle_message(priority = 2)]
+        fn error(&mut self, line: String) {
+            self.0.push(line);
+        }
+
+        #[handle_request]
+        fn lines(&self) -> Vec<String> {
+            self.0.clone()
+        }
+    }
+
+    let log = Log::link().start(()).unwrap();
+    // Queue up messages while the process is busy.
+    log.block(Duration::from_millis(50));
+    log.info("info 1".to_owned());
+    log.warn("warn 1".to_owned());
+    log.error("error".to_owned());
+    log.info("info 2".to_owned());
+    log.warn("warn 2".to_owned());
+    sleep(Duration::from_millis(100));
+    assert_eq!(
+        log.lines(),
+        vec!["error", "warn 1", "warn 2", "info 1", "info 2"]
+    );
+}
+
+#[test]
+fn snapshot() {
+    use lunatic::distributed::{migrate, MigrationError};
+
+    struct Counter(u32);
+
+    #[abstract_process]
+    impl Counter {
+        #[init]
+        fn init(_: Config<Self>, start: u32) -> Result<Self, ()> {
+            Ok(Self(start))
+        }
+
+        #[snapshot]
+        fn snapshot(&self) -> u32 {
+            self.0
+        }
+
+        #[handle_message]
+        fn increment(&mut self) {
+            self.0 += 1;
+        }
+    }
+
+    struct Fixed;
+
+    #[abstract_process]
+    impl Fixed {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Self)
+        }
+
+        #[handle_request]
+        fn ping(&self) -> bool {
+            true
+        }
+    }
+
+    let mut counter = Counter(0);
+    counter.increment();
+    assert_eq!(<Counter as AbstractProcess>::snapshot(&counter), Some(1));
+
+    // Processes without a snapshot keep running on the original node.
+    let fixed = Fixed::link().start(()).unwrap();
+    assert!(matches!(
+        migrate(fixed, lunatic::distributed::node_id()),
+        Err(MigrationError::SnapshotUnsupported)
+    ));
+    assert!(fixed.ping());
+}
+
+#[test]
+fn same_argument_shape() {
+    struct Balance(i64);
+
+    #[abstract_process]
+    impl Balance {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Self(0))
+        }
+
+        #[handle_message(name = "Deposit")]
+        fn deposit(&mut self, amount: u32) {
+            self.0 += i64::from(amount);
+        }
+
+        #[handle_message(name = "Withdraw")]
+        fn withdraw(&mut self, amount: u32) {
+            self.0 -= i64::from(amount);
+        }
+
+        #[handle_request]
+        fn balance(&self) -> i64 {
+            self.0
+        }
+    }
+
+    let balance = Balance::link().start(()).unwrap();
+    balance.deposit(10);
+    balance.withdraw(3);
+    assert_eq!(balance.balance(), 7);
+
+    // A message can't be decoded by another handler with the same arguments.
+    let deposit = bincode::serialize(&Deposit(10)).unwrap();
+    assert!(bincode::deserialize::<Deposit>(&deposit).is_ok());
+    assert!(bincode::deserialize::<Withdraw>(&deposit).is_err());
+}
+
+#[test]
+fn forwarded_attributes() {
+    struct Flag(bool);
+
+    #[abstract_process]
+    impl Flag {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Self(false))
+        }
+
+        /// Sets the flag.
+        #[handle_message]
+        fn set(&mut self) {
+            self.0 = true;
+        }
+
+        // Compiled out, nothing referring to the missing type is generated.
+        #[cfg(any())]
+        #[handle_message]
+        fn clear(&mut self, _: MissingType) {
+            self.0 = false;
+        }
+
+        #[cfg(test)]
+        #[handle_request]
+        fn get(&self) -> bool {
+            self.0
+        }
+
+        #[deprecated(note = "use `get`")]
+        #[handle_request]
+        fn is_set(&self) -> bool {
+            self.0
+        }
+    }
+
+    let flag = Flag::link().start(()).unwrap();
+    flag.set();
+    assert!(flag.get());
+    #[allow(deprecated)]
+    let is_set = flag.is_set();
+    assert!(is_set);
+}
+
+#[test]
+fn mock() {
+    struct Counter(u32);
+
+    #[abstract_process(mock = true)]
+    impl Counter {
+        #[init]
+        fn init(_: Config<Self>, start: u32) -> Result<Self, ()> {
+            Ok(Self(start))
+        }
+
+        #[handle_message]
+        fn increment(&mut self) {
+            self.0 += 1;
+        }
+
+        #[handle_request]
+        fn count(&self) -> u32 {
+            self.0
+        }
+    }
+
+    // Works with both the real process and the mock.
+    fn report<C>(counter: &C) -> String
+    where
+        C: CounterMessages + CounterRequests<ReturnTy_count = u32>,
+    {
+        counter.increment();
+        format!("count: {}", counter.count())
+    }
+
+    let mut mock = MockCounterRef::new();
+    mock.expect_count(|| 42);
+    assert_eq!(report(&mock), "count: 42");
+    assert_eq!(mock.calls(), ["increment", "count"]);
+    // The handler traits are object safe.
+    let counter: &dyn CounterRequests<ReturnTy_count = u32> = &mock;
+    assert_eq!(counter.count(), 42);
+
+    let counter = Counter::link().start(0).unwrap();
+    assert_eq!(report(&counter), "count: 1");
+}
+
+#[test]
+fn const_generics() {
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+
+    struct Window<T, const N: usize> {
+        items: Vec<T>,
+    }
+
+    #[abstract_process]
+    impl<T, const N: usize> Window<T, N>
+    where
+        T: Serialize + DeserializeOwned + Clone + 'static,
+    {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Self { items: Vec::new() })
+        }
+
+        #[handle_message]
+        fn push(&mut self, item: T) {
+            if self.items.len() == N {
+                self.items.remove(0);
+            }
+            self.items.push(item);
+        }
+
+        #[handle_request]
+        fn items(&self) -> Vec<T> {
+            self.items.clone()
+        }
+    }
+
+    let short = Window::<u32, 1>::link().start(()).unwrap();
+    let long = Window::<u32, 3>::link().start(()).unwrap();
+    for i in 0..3 {
+        short.push(i);
+        long.push(i);
+    }
+    assert_eq!(short.items(), vec![2]);
+    assert_eq!(long.items(), vec![0, 1, 2]);
+}
+
+#[test]
+fn borrowed_arguments() {
+    use std::collections::HashMap;
+
+    #[derive(Clone, serde::Serialize, serde::Deserialize)]
+    struct Meta {
+        owner: String,
+    }
+
+    #[derive(Default)]
+    struct Blobs {
+        blobs: HashMap<String, (Vec<u8>, Option<String>)>,
+    }
+
+    #[abstract_process]
+    impl Blobs {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Self::default())
+        }
+
+        #[handle_message]
+        fn put(&mut self, key: &str, value: &[u8]) {
+            self.blobs.insert(key.to_owned(), (value.to_vec(), None));
+        }
+
+        #[handle_message]
+        fn annotate(&mut self, key: &str, meta: &Meta) {
+            if let Some(blob) = self.blobs.get_mut(key) {
+                blob.1 = Some(meta.owner.clone());
+            }
+        }
+
+        #[handle_request]
+        fn get(&self, key: &str) -> Option<(Vec<u8>, Option<String>)> {
+            self.blobs.get(key).cloned()
+        }
+    }
+
+    let blobs = Blobs::link().start(()).unwrap();
+    blobs.put("a", &[1, 2, 3]);
+    blobs.annotate(
+        "a",
+        &Meta {
+            owner: "ana".to_owned(),
+        },
+    );
+    assert_eq!(
+        blobs.get("a"),
+        Some((vec![1, 2, 3], Some("ana".to_owned())))
+    );
+    assert_eq!(blobs.get("b"), None);
+}
+
+#[test]
+fn enum_state() {
+    enum Light {
+        Off,
+        On { brightness: u8 },
+        Blinking(u32, u8),
+    }
+
+    #[abstract_process]
+    impl Light {
+        #[init]
+        fn init(_: Config<Self>, brightness: Option<u8>) -> Result<Self, ()> {
+            Ok(match brightness {
+                Some(brightness) => Light::On { brightness },
+                None => Light::Off,
+            })
+        }
+
+        #[terminate]
+        fn terminate(self) {
+            assert!(matches!(self, Light::Off));
+        }
+
+        #[handle_message]
+        fn dim(&mut self, by: u8) {
+            if let Light::On { brightness } | Light::Blinking(_, brightness) = self {
+                *brightness = brightness.saturating_sub(by);
+            }
+        }
+
+        #[handle_message]
+        fn blink(self, interval: u32) -> Self {
+            match self {
+                Light::On { brightness } => Light::Blinking(interval, brightness),
+                light => light,
+            }
+        }
+
+        #[handle_message]
+        fn turn_off(self) -> Self {
+            Light::Off
+        }
+
+        #[handle_request]
+        fn state(&self) -> (Option<u32>, u8) {
+            match self {
+                Light::Off => (None, 0),
+                Light::On { brightness } => (None, *brightness),
+                Light::Blinking(interval, brightness) => (Some(*interval), *brightness),
+            }
+        }
+    }
+
+    let light = Light::link().start(Some(100)).unwrap();
+    light.dim(30);
+    assert_eq!(light.state(), (None, 70));
+    light.blink(500);
+    light.dim(20);
+    assert_eq!(light.state(), (Some(500), 50));
+    light.turn_off();
+    assert_eq!(light.state(), (None, 0));
+    light.shutdown();
+
+    let light = Light::link().start(None).unwrap();
+    light.blink(500);
+    assert_eq!(light.state(), (None, 0));
+}
+
+#[test]
+fn timeout_and_try_variants() {
+    use lunatic::ap::{DeferredResponse, RequestError};
+
+    struct Cache(Vec<u32>);
+
+    #[abstract_process(mock = true)]
+    impl Cache {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Self(Vec::new()))
+        }
+
+        #[handle_message]
+        fn push(&mut self, value: u32) {
+            self.0.push(value);
+        }
+
+        #[handle_request]
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        #[handle_request]
+        fn slow_len(&self, delay: u64) -> usize {
+            sleep(Duration::from_millis(delay));
+            self.0.len()
+        }
+
+        #[handle_deferred_request]
+        fn deferred_len(&self, dr: DeferredResponse<usize, Self>) {
+            dr.send_response(self.0.len())
+        }
+    }
+
+    let cache = Cache::link().start(()).unwrap();
+    assert!(cache.try_push(1));
+    assert!(cache.with_delay(Duration::from_millis(5)).try_push(2));
+    assert_eq!(cache.len_timeout(Duration::from_millis(100)), Ok(1));
+    assert_eq!(
+        cache.slow_len_timeout(20, Duration::from_millis(5)),
+        Err(RequestError::TimedOut)
+    );
+    // The explicit timeout takes precedence over `with_timeout`.
+    assert_eq!(
+        cache
+            .with_timeout(Duration::from_millis(1))
+            .deferred_len_timeout(Duration::from_millis(100)),
+        Ok(2)
+    );
+    cache.shutdown();
+    sleep(Duration::from_millis(10));
+    assert!(!cache.try_push(3));
+
+    let mut mock = MockCacheRef::new();
+    mock.expect_len(|| 7);
+    assert!(mock.try_push(1));
+    assert_eq!(mock.len_timeout(Duration::from_millis(1)), Ok(7));
+    assert_eq!(mock.calls(), ["push", "len"]);
+
+    struct Manual;
+
+    #[abstract_process(timeouts = false, try_cast = false)]
+    impl Manual {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Self)
+        }
+
+        #[handle_request]
+        fn get(&self) -> u32 {
+            1
+        }
+
+        // Doesn't collide with a generated method, they are disabled.
+        fn get_timeout(&self) -> u32 {
+            2
+        }
+    }
+
+    let manual = Manual::link().start(()).unwrap();
+    assert_eq!(manual.get(), 1);
+    assert_eq!(Manual.get_timeout(), 2);
+}
+
+#[test]
+fn restart_on_panic() {
+    struct Fragile(u32);
+
+    #[abstract_process(on_panic = "restart(max = 2, delay = '5ms')")]
+    impl Fragile {
+        #[init]
+        fn init(_: Config<Self>, start: u32) -> Result<Self, ()> {
+            Ok(Self(start))
+        }
+
+        #[handle_message]
+        fn increment(&mut self) {
+            self.0 += 1;
+        }
+
+        #[handle_message]
+        fn crash(&self) {
+            panic!("crash");
+        }
+
+        #[handle_request]
+        fn count(&self) -> u32 {
+            self.0
+        }
+    }
+
+    let fragile = Fragile::link().start(10).unwrap();
+    fragile.increment();
+    fragile.crash();
+    // The state is initialized again with the original argument.
+    assert_eq!(fragile.count(), 10);
+    fragile.increment();
+    fragile.crash();
+    assert_eq!(fragile.count(), 10);
+    // The third panic exceeds the maximum number of restarts.
+    fragile.unlink();
+    fragile.crash();
+    sleep(Duration::from_millis(20));
+    assert!(!fragile.is_alive());
+}
+
+#[test]
+fn restart_on_panicking_request() {
+    use std::time::Instant;
+
+    use lunatic::spawn;
+    use lunatic::time::Timeout;
+
+    struct Fragile(u32);
+
+    #[abstract_process(on_panic = "restart(max = 3)")]
+    impl Fragile {
+        #[init]
+        fn init(_: Config<Self>, start: u32) -> Result<Self, ()> {
+            Ok(Self(start))
+        }
+
+        #[handle_message]
+        fn increment(&mut self) {
+            self.0 += 1;
+        }
+
+        #[handle_request]
+        fn crash(&self) -> u32 {
+            panic!("crash");
+        }
+
+        #[handle_request]
+        fn count(&self) -> u32 {
+            self.0
+        }
+    }
+
+    let fragile = Fragile::link().start(10).unwrap();
+    fragile.increment();
+    // Requests with a timeout get an error back right away.
+    let started = Instant::now();
+    assert_eq!(
+        fragile.with_timeout(Duration::from_secs(10)).crash(),
+        Err(Timeout)
+    );
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert_eq!(fragile.count(), 10);
+    // Callers without a timeout are killed instead of waiting forever.
+    let caller = spawn!(|fragile| {
+        fragile.crash();
+    });
+    sleep(Duration::from_millis(20));
+    assert!(!caller.is_alive());
+    assert_eq!(fragile.count(), 10);
+}
+
+#[test]
+fn retry_requests() {
+    use lunatic::ap::{DeferredResponse, RequestError};
+
+    struct Flaky(u32);
+
+    #[abstract_process]
+    impl Flaky {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Self(0))
+        }
+
+        #[handle_deferred_request(retry(times = 2, backoff = "5ms"))]
+        fn answer(&mut self, question: String, response: DeferredResponse<String, Self>) {
+            self.0 += 1;
+            // The first message is ignored.
+            if self.0 > 1 {
+                response.send_response(format!("{question} {}", self.0));
+            }
+        }
+
+        #[handle_request(retry(times = 1, backoff = "15ms"))]
+        fn attempt(&mut self) -> u32 {
+            self.0 += 1;
+            // The first response arrives after the timeout.
+            if self.0 == 1 {
+                sleep(Duration::from_millis(20));
+            }
+            self.0
+        }
+
+        #[handle_deferred_request(retry(times = 2))]
+        fn never(&mut self, _response: DeferredResponse<(), Self>) {
+            self.0 += 1;
+        }
+
+        #[handle_request]
+        fn attempts(&self) -> u32 {
+            self.0
+        }
+    }
+
+    let flaky = Flaky::link().start(()).unwrap();
+    let answer = flaky
+        .with_timeout(Duration::from_millis(10))
+        .answer("attempt".to_owned());
+    assert_eq!(answer, Ok("attempt 2".to_owned()));
+
+    let flaky = Flaky::link().start(()).unwrap();
+    // The late response to the first attempt isn't mistaken for the second.
+    assert_eq!(flaky.attempt_timeout(Duration::from_millis(10)), Ok(2));
+
+    let flaky = Flaky::link().start(()).unwrap();
+    assert_eq!(
+        flaky.never_timeout(Duration::from_millis(5)),
+        Err(RequestError::TimedOut)
+    );
+    assert_eq!(flaky.attempts(), 3);
+}
+
+#[test]
+fn map_reply() {
+    struct Directory {
+        owner: String,
+        entries: Vec<(String, u64)>,
+    }
+
+    #[abstract_process]
+    impl Directory {
+        #[init]
+        fn init(_: Config<Self>, owner: String) -> Result<Self, ()> {
+            Ok(Self {
+                owner,
+                entries: vec![("a".to_owned(), 10), ("b".to_owned(), 200)],
+            })
+        }
+
+        #[handle_request(map_reply = Vec<String>)]
+        fn larger_than(&self, size: u64) -> impl Iterator<Item = String> + '_ {
+            self.entries
+                .iter()
+                .filter(move |(_, entry_size)| *entry_size > size)
+                .map(|(name, _)| name.clone())
+        }
+
+        #[handle_request(map_reply = String)]
+        fn owner(&self) -> &str {
+            &self.owner
+        }
+    }
+
+    let directory = Directory::link().start("ana".to_owned()).unwrap();
+    assert_eq!(directory.larger_than(0), vec!["a", "b"]);
+    assert_eq!(directory.larger_than(100), vec!["b"]);
+    assert_eq!(directory.owner(), "ana");
+}
+
+#[test]
+fn extend_impl_blocks() {
+    mod store {
+        use std::collections::HashMap;
+
+        use lunatic::abstract_process;
+        use lunatic::ap::Config;
+
+        pub struct Store {
+            entries: HashMap<String, u32>,
+        }
+
+        #[abstract_process(visibility = pub, extensions = [writes::StoreWrites])]
+        impl Store {
+            #[init]
+            fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+                Ok(Self {
+                    entries: HashMap::new(),
+                })
+            }
+
+            #[handle_request]
+            fn get(&self, key: String) -> Option<u32> {
+                self.entries.get(&key).copied()
+            }
+        }
+
+        pub mod writes {
+            use lunatic::abstract_process;
+
+            use super::Store;
+
+            #[abstract_process(visibility = pub, extend = "StoreWrites", trait_name = "StoreWrites")]
+            impl Store {
+                #[handle_message]
+                fn put(&mut self, key: String, value: u32) {
+                    self.entries.insert(key, value);
+                }
+
+                #[handle_request]
+                fn remove(&mut self, key: String) -> Option<u32> {
+                    self.entries.remove(&key)
+                }
+            }
+        }
+    }
+
+    use store::writes::{StoreWritesMessages, StoreWritesRequests};
+    use store::{Store, StoreRequests};
+
+    let store = Store::link().start(()).unwrap();
+    store.put("a".to_owned(), 1);
+    assert_eq!(store.get("a".to_owned()), Some(1));
+    assert_eq!(store.remove("a".to_owned()), Some(1));
+    assert_eq!(store.get("a".to_owned()), None);
+}
+
+#[test]
+fn export_schema() {
+    use lunatic::ap::DeferredResponse;
+
+    struct Names(Vec<String>);
+
+    #[abstract_process(export_schema)]
+    impl Names {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Self(Vec::new()))
+        }
+
+        #[handle_message]
+        fn add(&mut self, name: &str) {
+            self.0.push(name.to_owned());
+        }
+
+        #[handle_request(id = 4)]
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        #[handle_deferred_request]
+        fn get(&self, index: u32, response: DeferredResponse<Option<String>, Self>) {
+            response.send_response(self.0.get(index as usize).cloned());
+        }
+    }
+
+    let schema = Names::SCHEMA;
+    assert!(schema.starts_with(
+        r#"{"process":"Names","serializer":"lunatic::serializer::Bincode","handlers":["#
+    ));
+    assert!(schema.contains(r#"{"name":"add","id":1,"direction":"cast","method_id":"#));
+    assert!(schema.contains(r#""args":[{"name":"name","type":"String"}],"reply":null}"#));
+    assert!(schema.contains(r#"{"name":"get","id":2,"direction":"request","method_id":"#));
+    assert!(schema.contains(r#""args":[{"name":"index","type":"u32"}],"reply":"Option<String>"}"#));
+    assert!(schema.contains(r#"{"name":"len","id":4,"direction":"request","method_id":"#));
+    assert!(schema.contains(r#""args":[],"reply":"usize"}"#));
+
+    // Handlers are dispatched by their assigned ids.
+    let names = Names::link().start(()).unwrap();
+    names.add("a");
+    assert_eq!(names.len(), 1);
+    assert_eq!(names.get(0), Some("a".to_owned()));
+}
+
+#[test]
+fn instrument() {
+    struct Measured(u32);
+
+    #[abstract_process(instrument)]
+    impl Measured {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Self(0))
+        }
+
+        #[handle_message]
+        fn increment(&mut self) {
+            self.0 += 1;
+        }
+
+        #[handle_request]
+        fn count(&self) -> u32 {
+            self.0
+        }
+    }
+
+    assert!(Measured::instrument());
+    let measured = Measured::link().start(()).unwrap();
+    measured.increment();
+    assert_eq!(measured.count(), 1);
+}
+
+#[test]
+fn removed_handler() {
+    use lunatic::ap::{ProcessRef, RequestError};
+
+    // Version of the process the old client was built with, before `reset`
+    // and `get` were removed.
+    struct OldCounter(u32);
+
+    #[abstract_process]
+    impl OldCounter {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Self(0))
+        }
+
+        #[handle_message]
+        fn reset(&mut self) {
+            self.0 = 0;
+        }
+
+        #[handle_request]
+        fn get(&self, by: u32) -> u32 {
+            self.0 * by
+        }
+
+        #[handle_message]
+        fn increment(&mut self) {
+            self.0 += 1;
+        }
+
+        #[handle_request]
+        fn count(&self) -> u32 {
+            self.0
+        }
+    }
+
+    mod v2 {
+        use lunatic::abstract_process;
+        use lunatic::ap::Config;
+
+        pub struct Counter(pub u32);
+
+        #[abstract_process(visibility = pub)]
+        #[removed_handler(id = 1, name = "reset")]
+        #[removed_handler(id = 2, name = "get", request(u32))]
+        impl Counter {
+            #[init]
+            fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+                Ok(Self(0))
+            }
+
+            #[handle_message]
+            fn increment(&mut self) {
+                self.0 += 1;
+            }
+
+            #[handle_request]
+            fn count(&self) -> u32 {
+                self.0
+            }
+        }
+    }
+
+    use v2::{CounterMessages, CounterRequests};
+
+    let counter = v2::Counter::link().start(()).unwrap();
+    // Safety: Only handlers that still exist are called through the old
+    // reference, or ids kept by `#[removed_handler]`.
+    let old_client = unsafe { ProcessRef::<OldCounter>::new(counter.node_id(), counter.id()) };
+    counter.increment();
+    old_client.increment();
+    // Dropped instead of crashing the process.
+    old_client.reset();
+    assert_eq!(
+        old_client.request_timeout(__MsgWrapGet(2), Some(Duration::from_secs(1))),
+        Err(RequestError::HandlerRemoved)
+    );
+    assert_eq!(counter.count(), 2);
+    assert_eq!(old_client.count(), 2);
+}
+
+#[test]
+fn item_visibility() {
+    mod counter {
+        use lunatic::abstract_process;
+        use lunatic::ap::Config;
+
+        pub struct Counter(pub u32);
+
+        #[abstract_process(
+            visibility(msgs = pub(crate), traits = pub, mock = pub),
+            client_mod = "counter_client",
+            mock = true
+        )]
+        #[removed_handler(id = 1, name = "reset")]
+        impl Counter {
+            #[init]
+            fn init(_: Config<Self>, start: u32) -> Result<Self, ()> {
+                Ok(Self(start))
+            }
+
+            #[handle_message]
+            fn increment(&mut self) {
+                self.0 += 1;
+            }
+
+            #[cfg(test)]
+            #[handle_request]
+            fn count(&self) -> u32 {
+                self.0
+            }
+        }
+    }
+
+    use counter::{Counter, CounterMessages, CounterRequests, MockCounterRef};
+
+    let mut mock = MockCounterRef::new();
+    mock.expect_count(|| 42);
+    assert_eq!(mock.count(), 42);
+
+    let counter = Counter::link().start(1).unwrap();
+    counter.increment();
+    assert_eq!(counter.count(), 2);
+    // Reachable inside of the crate with `msgs = pub(crate)`.
+    assert_eq!(counter.request(counter::__MsgWrapCount()), 2);
+}
+
+#[test]
+fn response_stream() {
+    use lunatic::ap::ResponseStream;
+
+    struct Log(Vec<String>);
+
+    #[abstract_process(mock = true)]
+    impl Log {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Self(Vec::new()))
+        }
+
+        #[handle_message]
+        fn append(&mut self, line: String) {
+            self.0.push(line);
+        }
+
+        #[handle_request]
+        fn tail(&self, lines: usize) -> ResponseStream<String> {
+            let start = self.0.len().saturating_sub(lines);
+            ResponseStream::new(self.0[start..].to_vec())
+        }
+
+        #[handle_request]
+        fn count(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    let log = Log::link().start(()).unwrap();
+    for line in ["a", "b", "c"] {
+        log.append(line.to_owned());
+    }
+    let tail: Vec<String> = log.tail(2).map(Result::unwrap).collect();
+    assert_eq!(tail, ["b", "c"]);
+    // Other requests work after the end of the stream.
+    assert_eq!(log.count(), 3);
+
+    // The timeout applies to each item.
+    let items = log.tail_timeout(5, Duration::from_secs(1)).unwrap();
+    assert_eq!(items.count(), 3);
+    let mut items = log.with_timeout(Duration::from_secs(1)).tail(0);
+    assert!(items.next().is_none());
+
+    let mut mock = MockLogRef::new();
+    mock.expect_tail(|lines| (0..lines).map(|i| i.to_string()).collect());
+    let tail: Vec<String> = mock.tail(2).map(Result::unwrap).collect();
+    assert_eq!(tail, ["0", "1"]);
+}
+
+#[test]
+fn responder_request() {
+    use lunatic::ap::Responder;
+
+    struct Saver(u32);
+
+    #[abstract_process(mock = true)]
+    impl Saver {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Self(0))
+        }
+
+        #[handle_responder_request]
+        fn save(&mut self, millis: u64, responder: &mut Responder<bool, Self>) -> Option<bool> {
+            if millis == 0 {
+                // Let the return value be sent as the response.
+                return Some(false);
+            }
+            responder.respond(true);
+            sleep(Duration::from_millis(millis));
+            self.0 += 1;
+            None
+        }
+
+        #[handle_request]
+        fn saved(&self) -> u32 {
+            self.0
+        }
+    }
+
+    let saver = Saver::link().start(()).unwrap();
+    // The early response arrives before the handler finishes sleeping.
+    assert_eq!(saver.save_timeout(200, Duration::from_millis(50)), Ok(true));
+    // The next request waits on the rest of the handler.
+    assert_eq!(saver.saved(), 1);
+    // The late response is the returned value.
+    assert!(!saver.save(0));
+    assert_eq!(saver.saved(), 1);
+
+    let mut mock = MockSaverRef::new();
+    mock.expect_save(|millis| millis > 0);
+    assert!(mock.save(1));
+}
+
+#[test]
+#[should_panic]
+fn responder_request_responds_twice() {
+    use lunatic::ap::Responder;
+
+    struct Twice;
+
+    #[abstract_process]
+    impl Twice {
+        #[init]
+        fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+            Ok(Self)
+        }
+
+        #[handle_responder_request]
+        fn answer(&self, responder: &mut Responder<u32, Self>) -> Option<u32> {
+            responder.respond(1);
+            responder.respond(2);
+            None
+        }
+    }
+
+    let twice = Twice::link().start(()).unwrap();
+    assert_eq!(twice.answer(), 1);
+    // The second `respond` panics and the link kills this process.
+    sleep(Duration::from_millis(100));
+}
@@ -0,0 +1,590 @@
+use std::time::{Duration, Instant};
+
+use lunatic::actor::{
+    Aggregator, Cache, CacheConfig, CacheStats, Circuit, CircuitError, CircuitState, CommandError,
+    Debounce, EventSourced, EventSourcing, EventSourcingConfig, EventStore, Loader, OnError,
+    Pipeline, Rate, Router, Saga, SagaFailed, SagaStep, Stage, State, StateMachine, StepError,
+    Throttle, Transition,
+};
+use lunatic::ap::{Config, MessageHandler, ProcessRef, RequestError, RequestHandler};
+use lunatic::{sleep, AbstractProcess, Mailbox, Process};
+use lunatic_test::test;
+use serde::{Deserialize, Serialize};
+
+/// A door that reports entered and exited states to the `door_observer`
+/// process.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+enum Door {
+    Open,
+    Closed,
+    Locked,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Action {
+    Open,
+    Close,
+    Lock,
+    Unlock,
+}
+
+impl Door {
+    fn report(&self, hook: &str) {
+        if let Some(observer) = Process::<String>::lookup(&"door_observer") {
+            observer.send(format!("{hook} {self:?}"));
+        }
+    }
+}
+
+impl State<Action> for Door {
+    fn transition(&mut self, action: Action) -> Transition<Self> {
+        match (&self, action) {
+            (Door::Closed, Action::Open) => Transition::Next(Door::Open),
+            (Door::Open, Action::Close) => Transition::Next(Door::Closed),
+            (Door::Closed, Action::Lock) => Transition::Next(Door::Locked),
+            (Door::Locked, Action::Unlock) => Transition::Next(Door::Closed),
+            _ => Transition::Stay,
+        }
+    }
+
+    fn on_enter(&mut self) {
+        self.report("enter");
+    }
+
+    fn on_exit(&mut self) {
+        self.report("exit");
+    }
+}
+
+#[test]
+fn transitions() {
+    let door = StateMachine::<Door, Action>::link()
+        .start(Door::Closed)
+        .unwrap();
+    assert_eq!(door.current_state(), Door::Closed);
+    door.trigger(Action::Open);
+    assert_eq!(door.current_state(), Door::Open);
+    // Invalid transitions keep the current state.
+    door.trigger(Action::Lock);
+    assert_eq!(door.current_state(), Door::Open);
+    door.trigger(Action::Close);
+    door.trigger(Action::Lock);
+    assert_eq!(door.current_state(), Door::Locked);
+}
+
+#[test]
+fn hooks(mailbox: Mailbox<String>) {
+    mailbox.this().register(&"door_observer");
+    let door = StateMachine::<Door, Action>::start_as(&"door", Door::Closed).unwrap();
+    assert_eq!(mailbox.receive(), "enter Closed");
+    let door_ref = ProcessRef::<StateMachine<Door, Action>>::lookup(&"door").unwrap();
+    door_ref.trigger(Action::Lock);
+    assert_eq!(mailbox.receive(), "exit Closed");
+    assert_eq!(mailbox.receive(), "enter Locked");
+    // Staying in the same state doesn't call any hooks.
+    door.trigger(Action::Open);
+    door.trigger(Action::Unlock);
+    assert_eq!(mailbox.receive(), "exit Locked");
+    assert_eq!(mailbox.receive(), "enter Closed");
+    door.shutdown();
+    assert_eq!(mailbox.receive(), "exit Closed");
+}
+
+/// Worker reporting each message together with its id.
+struct Shard;
+
+impl AbstractProcess for Shard {
+    type State = (u32, Process<(u32, String)>);
+    type Serializer = lunatic::serializer::Bincode;
+    type Arg = (u32, Process<(u32, String)>);
+    type Handlers = (lunatic::ap::handlers::Message<String>,);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, arg: Self::Arg) -> Result<Self::State, ()> {
+        Ok(arg)
+    }
+}
+
+impl MessageHandler<String> for Shard {
+    fn handle(state: lunatic::ap::State<Self>, message: String) {
+        state.1.send((state.0, message));
+    }
+}
+
+#[test]
+fn router_routes_by_key(mailbox: Mailbox<(u32, String)>) {
+    let router = Router::<Shard, String>::new(16);
+    let first = router.spawn_worker("first", (1, mailbox.this())).unwrap();
+    let second = router.spawn_worker("second", (2, mailbox.this())).unwrap();
+
+    // Messages with the same routing key reach the same worker.
+    let worker = router.worker_for("user-42").unwrap();
+    assert!(worker == first || worker == second);
+    router.route("user-42", "a".to_owned());
+    router.route("user-42", "b".to_owned());
+    let (id_a, a) = mailbox.receive();
+    let (id_b, b) = mailbox.receive();
+    assert_eq!((a.as_str(), b.as_str()), ("a", "b"));
+    assert_eq!(id_a, id_b);
+
+    router.remove_worker("first");
+    assert_eq!(router.worker_for("user-42"), Some(second));
+    router.route("user-42", "c".to_owned());
+    assert_eq!(mailbox.receive(), (2, "c".to_owned()));
+}
+
+#[test]
+fn router_restarts_workers(mailbox: Mailbox<(u32, String)>) {
+    let router = Router::<Shard, String>::new(4);
+    let worker = router.spawn_worker("only", (1, mailbox.this())).unwrap();
+    worker.kill();
+    sleep(Duration::from_millis(50));
+    let restarted = router.worker_for("key").unwrap();
+    assert_ne!(restarted, worker);
+    router.route("key", "after restart".to_owned());
+    assert_eq!(mailbox.receive(), (1, "after restart".to_owned()));
+}
+
+/// Downstream of an aggregator, forwarding each value to a process.
+struct Sink;
+
+impl AbstractProcess for Sink {
+    type State = Process<u32>;
+    type Serializer = lunatic::serializer::Bincode;
+    type Arg = Process<u32>;
+    type Handlers = (lunatic::ap::handlers::Message<u32>,);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, arg: Self::Arg) -> Result<Self::State, ()> {
+        Ok(arg)
+    }
+}
+
+impl MessageHandler<u32> for Sink {
+    fn handle(state: lunatic::ap::State<Self>, value: u32) {
+        state.send(value);
+    }
+}
+
+#[test]
+fn aggregator_windows(mailbox: Mailbox<u32>) {
+    let sink = Sink::link().start(mailbox.this()).unwrap();
+    let aggregator =
+        Aggregator::<u32, u32>::new(Duration::from_millis(100), |sum, n| sum + n, 0, sink);
+    aggregator.push(1);
+    aggregator.push(2);
+    aggregator.push(3);
+    assert_eq!(mailbox.receive(), 6);
+    // Each window starts with the initial value again.
+    assert_eq!(mailbox.receive(), 0);
+    aggregator.push(4);
+    assert_eq!(mailbox.receive(), 4);
+}
+
+#[test]
+fn aggregator_sliding_windows(mailbox: Mailbox<u32>) {
+    let sink = Sink::link().start(mailbox.this()).unwrap();
+    let aggregator = Aggregator::<u32, u32>::sliding(
+        Duration::from_millis(200),
+        Duration::from_millis(100),
+        |sum, n| sum + n,
+        0,
+        sink,
+    );
+    aggregator.push(1);
+    sleep(Duration::from_millis(150));
+    // Folded into both open windows.
+    aggregator.push(2);
+    assert_eq!(mailbox.receive(), 3);
+    assert_eq!(mailbox.receive(), 2);
+}
+
+#[test]
+fn throttle_limits_rate(mailbox: Mailbox<u32>) {
+    let sink = Sink::link().start(mailbox.this()).unwrap();
+    let throttle = Throttle::new(sink, Rate::per_second(10));
+    let started = Instant::now();
+    for n in 0..3 {
+        throttle.send(n);
+    }
+    assert_eq!(mailbox.receive(), 0);
+    assert_eq!(mailbox.receive(), 1);
+    assert_eq!(mailbox.receive(), 2);
+    // The first message is released right away, the others 100ms apart.
+    assert!(started.elapsed() >= Duration::from_millis(200));
+}
+
+#[test]
+fn debounce_forwards_last_of_burst(mailbox: Mailbox<u32>) {
+    let sink = Sink::link().start(mailbox.this()).unwrap();
+    let debounce = Debounce::new(sink, Duration::from_millis(50), |n: &u32| n % 2);
+    for n in [1, 3, 2, 5] {
+        debounce.send(n);
+        sleep(Duration::from_millis(10));
+    }
+    let mut received = [mailbox.receive(), mailbox.receive()];
+    received.sort();
+    assert_eq!(received, [2, 5]);
+    assert!(mailbox.receive_timeout(Duration::from_millis(100)).is_err());
+
+    // A message arriving after the delay starts a new burst.
+    debounce.send(7);
+    assert_eq!(mailbox.receive(), 7);
+}
+
+#[test]
+fn throttle_drops_oldest(mailbox: Mailbox<u32>) {
+    let sink = Sink::link().start(mailbox.this()).unwrap();
+    let throttle = Throttle::with_capacity(sink, Rate::new(1, 1), 1);
+    for n in 0..3 {
+        throttle.send(n);
+    }
+    assert_eq!(mailbox.receive(), 0);
+    // The buffered `1` was dropped for `2`.
+    assert_eq!(mailbox.receive(), 2);
+    assert!(mailbox
+        .receive_timeout(Duration::from_millis(1500))
+        .is_err());
+}
+
+/// Answers a request after sleeping for the requested number of
+/// milliseconds.
+struct Slow;
+
+impl AbstractProcess for Slow {
+    type State = Self;
+    type Serializer = lunatic::serializer::Bincode;
+    type Arg = ();
+    type Handlers = (lunatic::ap::handlers::Request<u64>,);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+        Ok(Slow)
+    }
+}
+
+impl RequestHandler<u64> for Slow {
+    type Response = u64;
+
+    fn handle(_: lunatic::ap::State<Self>, millis: u64) -> u64 {
+        sleep(Duration::from_millis(millis));
+        millis
+    }
+}
+
+#[test]
+fn circuit_opens_and_recovers() {
+    let slow = Slow::link().start(()).unwrap();
+    let circuit = Circuit::wrap(slow, 2, Duration::from_millis(200));
+    let timeout = Some(Duration::from_millis(20));
+    assert_eq!(circuit.request_timeout(0, timeout), Ok(0));
+    for _ in 0..2 {
+        assert_eq!(
+            circuit.request_timeout(100, timeout),
+            Err(CircuitError::Request(RequestError::TimedOut))
+        );
+    }
+    assert_eq!(circuit.state(), CircuitState::Open);
+    assert_eq!(circuit.request(0), Err(CircuitError::Open));
+
+    // The failed probe keeps the circuit open.
+    sleep(Duration::from_millis(250));
+    assert!(circuit.request_timeout(100, timeout).is_err());
+    assert_eq!(circuit.state(), CircuitState::Open);
+    assert_eq!(circuit.request(0), Err(CircuitError::Open));
+
+    // The successful probe closes it.
+    sleep(Duration::from_millis(250));
+    assert_eq!(circuit.request_timeout(0, timeout), Ok(0));
+    assert_eq!(circuit.state(), CircuitState::Closed);
+    circuit.shutdown();
+}
+
+/// Parses numbers, failing on anything else.
+struct Parse;
+
+impl Stage for Parse {
+    type Arg = ();
+    type Input = String;
+    type Output = u32;
+    type Error = std::num::ParseIntError;
+
+    fn init(_: ()) -> Self {
+        Parse
+    }
+
+    fn process(&mut self, input: String) -> Result<u32, Self::Error> {
+        input.parse()
+    }
+}
+
+/// Multiplies numbers by the factor it was started with.
+struct Multiply(u32);
+
+impl Stage for Multiply {
+    type Arg = u32;
+    type Input = u32;
+    type Output = u32;
+    type Error = ();
+
+    fn init(factor: u32) -> Self {
+        Multiply(factor)
+    }
+
+    fn process(&mut self, input: u32) -> Result<u32, ()> {
+        Ok(input * self.0)
+    }
+}
+
+/// Sends numbers to the process it was started with.
+struct Collect(Process<u32>);
+
+impl Stage for Collect {
+    type Arg = Process<u32>;
+    type Input = u32;
+    type Output = ();
+    type Error = ();
+
+    fn init(process: Process<u32>) -> Self {
+        Collect(process)
+    }
+
+    fn process(&mut self, input: u32) -> Result<(), ()> {
+        self.0.send(input);
+        Ok(())
+    }
+}
+
+#[test]
+fn pipeline_stops_on_error(mailbox: Mailbox<u32>) {
+    let pipeline = Pipeline::start::<Parse>(())
+        .then::<Multiply>(2)
+        .then::<Collect>(mailbox.this())
+        .build();
+    pipeline.send("1".to_owned());
+    pipeline.send("x".to_owned());
+    pipeline.send("3".to_owned());
+    assert_eq!(mailbox.receive(), 2);
+    assert!(mailbox.receive_timeout(Duration::from_millis(100)).is_err());
+    assert_eq!(
+        pipeline.error().as_deref(),
+        Some("ParseIntError { kind: InvalidDigit }")
+    );
+    assert_eq!(pipeline.failures(), 1);
+    pipeline.shutdown();
+}
+
+#[test]
+fn pipeline_continues_on_error(mailbox: Mailbox<u32>) {
+    let pipeline = Pipeline::start::<Parse>(())
+        .then::<Multiply>(2)
+        .then::<Multiply>(5)
+        .then::<Collect>(mailbox.this())
+        .on_error(OnError::Continue)
+        .build();
+    pipeline.send("1".to_owned());
+    pipeline.send("x".to_owned());
+    pipeline.send("3".to_owned());
+    assert_eq!(mailbox.receive(), 10);
+    assert_eq!(mailbox.receive(), 30);
+    assert_eq!(pipeline.error(), None);
+    assert_eq!(pipeline.failures(), 1);
+    pipeline.shutdown();
+}
+
+#[test]
+fn cache_evicts_least_recently_used() {
+    let cache = Cache::<String, u32>::new(2);
+    cache.put("a".to_owned(), 1);
+    cache.put("b".to_owned(), 2);
+    // `b` is now the least recently used.
+    assert_eq!(cache.get("a".to_owned()), Some(1));
+    cache.put("c".to_owned(), 3);
+    assert_eq!(cache.get("b".to_owned()), None);
+    assert_eq!(cache.get("c".to_owned()), Some(3));
+    assert!(cache.invalidate("a".to_owned()));
+    assert!(!cache.invalidate("a".to_owned()));
+    assert_eq!(
+        cache.stats(),
+        CacheStats {
+            hits: 2,
+            misses: 1,
+            evictions: 1,
+            expirations: 0,
+            size: 1,
+            capacity: 2,
+        }
+    );
+}
+
+#[test]
+fn cache_expires_and_loads() {
+    fn square(key: u32) -> Option<u32> {
+        (key < 100).then_some(key * key)
+    }
+
+    let loader = Loader::new(square);
+    let config = CacheConfig::new(8)
+        .ttl(Duration::from_millis(50))
+        .on_miss(loader);
+    let cache = Cache::<u32, u32>::with_config(config);
+    cache.put(3, 0);
+    assert_eq!(cache.get(3), Some(0));
+    sleep(Duration::from_millis(60));
+    // The expired value is loaded again.
+    assert_eq!(cache.get(3), Some(9));
+    assert_eq!(cache.get(3), Some(9));
+    assert_eq!(cache.get(100), None);
+    let stats = cache.stats();
+    assert_eq!((stats.hits, stats.misses, stats.expirations), (2, 2, 1));
+    assert_eq!(stats.size, 1);
+}
+
+/// An order reporting the executed actions to `log`, failing at step
+/// `fail_at`.
+#[derive(Serialize, Deserialize)]
+struct Order {
+    log: Process<String>,
+    fail_at: usize,
+}
+
+impl Order {
+    fn step(&self, step: usize) -> Result<(), String> {
+        if step == self.fail_at {
+            return Err(format!("step {step} failed"));
+        }
+        self.log.send(format!("forward {step}"));
+        Ok(())
+    }
+}
+
+fn order_saga() -> Saga<Order, String> {
+    Saga::new(vec![
+        SagaStep::new(
+            |order| order.step(0),
+            |order| order.log.send("undo 0".into()),
+        ),
+        SagaStep::new(
+            |order| order.step(1),
+            |order| order.log.send("undo 1".into()),
+        ),
+        SagaStep::new(
+            |order| order.step(2),
+            |order| order.log.send("undo 2".into()),
+        ),
+    ])
+}
+
+#[test]
+fn saga_executes_all_steps(mailbox: Mailbox<String>) {
+    let order = Order {
+        log: mailbox.this(),
+        fail_at: 3,
+    };
+    let order = order_saga().execute(order).unwrap();
+    assert_eq!(order.fail_at, 3);
+    for expected in ["forward 0", "forward 1", "forward 2"] {
+        assert_eq!(mailbox.receive(), expected);
+    }
+}
+
+#[test]
+fn saga_compensates_in_reverse(mailbox: Mailbox<String>) {
+    let order = Order {
+        log: mailbox.this(),
+        fail_at: 2,
+    };
+    let failed = order_saga().execute(order).err().unwrap();
+    assert_eq!(
+        failed,
+        SagaFailed {
+            step: 2,
+            error: StepError::Failed("step 2 failed".to_string()),
+            compensated: true,
+        }
+    );
+    for expected in ["forward 0", "forward 1", "undo 1", "undo 0"] {
+        assert_eq!(mailbox.receive(), expected);
+    }
+    assert!(mailbox.receive_timeout(Duration::from_millis(10)).is_err());
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+enum AccountEvent {
+    Deposited(u32),
+    Withdrawn(u32),
+}
+
+#[derive(Serialize, Deserialize)]
+enum AccountCommand {
+    Deposit(u32),
+    Withdraw(u32),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Account {
+    balance: u32,
+}
+
+impl EventSourced<AccountEvent> for Account {
+    type Command = AccountCommand;
+
+    fn command(&self, command: AccountCommand) -> Result<Vec<AccountEvent>, CommandError> {
+        match command {
+            AccountCommand::Deposit(amount) => Ok(vec![AccountEvent::Deposited(amount)]),
+            AccountCommand::Withdraw(amount) if amount > self.balance => {
+                Err(CommandError::new("insufficient funds"))
+            }
+            AccountCommand::Withdraw(amount) => Ok(vec![AccountEvent::Withdrawn(amount)]),
+        }
+    }
+
+    fn apply_event(&mut self, event: AccountEvent) {
+        match event {
+            AccountEvent::Deposited(amount) => self.balance += amount,
+            AccountEvent::Withdrawn(amount) => self.balance -= amount,
+        }
+    }
+}
+
+#[test]
+fn event_sourcing_replays_stream() {
+    let store = EventStore::new();
+    let config = EventSourcingConfig::new(Account { balance: 0 }, store, "account/1");
+    let account = EventSourcing::<AccountEvent, Account>::start(config.clone()).unwrap();
+    assert_eq!(
+        account.execute(AccountCommand::Deposit(10)),
+        Ok(vec![AccountEvent::Deposited(10)])
+    );
+    account.execute(AccountCommand::Withdraw(3)).unwrap();
+    assert_eq!(
+        account.execute(AccountCommand::Withdraw(8)),
+        Err(CommandError::new("insufficient funds"))
+    );
+    assert_eq!(account.current_state(), Account { balance: 7 });
+    account.kill();
+
+    // A new process with the same stream replays the events.
+    let account = EventSourcing::<AccountEvent, Account>::start(config).unwrap();
+    assert_eq!(account.current_state(), Account { balance: 7 });
+    assert_eq!(store.load("account/1").events.len(), 2);
+}
+
+#[test]
+fn event_sourcing_snapshots() {
+    let store = EventStore::new();
+    let config =
+        EventSourcingConfig::new(Account { balance: 0 }, store, "account/2").snapshot_every(2);
+    let account = EventSourcing::<AccountEvent, Account>::start(config.clone()).unwrap();
+    for amount in 1..=3 {
+        account.execute(AccountCommand::Deposit(amount)).unwrap();
+    }
+    account.kill();
+
+    // Only the event after the snapshot is stored.
+    let stored = store.load("account/2");
+    assert!(stored.snapshot.is_some());
+    assert_eq!(stored.events.len(), 1);
+    let account = EventSourcing::<AccountEvent, Account>::start(config).unwrap();
+    assert_eq!(account.current_state(), Account { balance: 6 });
+}
@@ -0,0 +1,23 @@
+use std::time::Duration;
+
+use lunatic::bench::{round_trip_latency, Echo, Stats};
+use lunatic_test::test;
+
+#[test]
+fn stats_from_samples() {
+    let samples: Vec<Duration> = (1..=1000).map(Duration::from_nanos).collect();
+    let stats = Stats::from_samples(&samples);
+    assert_eq!(stats.mean_ns, 500);
+    assert_eq!(stats.p50_ns, 501);
+    assert_eq!(stats.p99_ns, 990);
+    assert_eq!(stats.p999_ns, 999);
+    assert_eq!(Stats::from_samples(&[]).p999_ns, 0);
+}
+
+#[test]
+fn echo_round_trip() {
+    let stats = round_trip_latency::<Echo>(100);
+    assert!(stats.mean_ns > 0);
+    assert!(stats.p50_ns <= stats.p99_ns);
+    assert!(stats.p99_ns <= stats.p999_ns);
+}
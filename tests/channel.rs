@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+use lunatic::channel::{oneshot, RecvError};
+use lunatic::{spawn_link, test, Mailbox};
+
+#[test]
+fn oneshot_reply() {
+    let (sender, receiver) = oneshot::<String>();
+    spawn_link!(|sender| sender.send("hello".to_owned()));
+    let reply = receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(reply, "hello");
+}
+
+#[test]
+fn oneshot_skips_other_messages(mailbox: Mailbox<u32>) {
+    let (sender, receiver) = oneshot::<u32>();
+    mailbox.this().send(1);
+    sender.send(2);
+    assert_eq!(receiver.recv().unwrap(), 2);
+    assert_eq!(mailbox.receive(), 1);
+}
+
+#[test]
+fn oneshot_timeout() {
+    let (_sender, receiver) = oneshot::<u32>();
+    assert!(matches!(
+        receiver.recv_timeout(Duration::from_millis(10)),
+        Err(RecvError::TimedOut)
+    ));
+}
@@ -0,0 +1,17 @@
+//! Client of an abstract process generated with `client_mod`. The process is
+//! implemented in the `client-mod-server` crate, which only exports the
+//! generated client module.
+
+use lunatic::ap::ProcessRef;
+use lunatic::test;
+
+#[test]
+fn client_uses_only_api() {
+    use client_mod_server::api::{Counter, CounterMessages, CounterRequests};
+
+    client_mod_server::start("client_mod_counter");
+    let counter = ProcessRef::<Counter>::lookup("client_mod_counter").unwrap();
+    counter.increment(2);
+    counter.increment(3);
+    assert_eq!(counter.count(), 5);
+}
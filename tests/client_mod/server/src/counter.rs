@@ -0,0 +1,29 @@
+//! The process implementation, only reachable through the re-exported
+//! `counter_client` module.
+
+use lunatic::abstract_process;
+use lunatic::ap::{AbstractProcess, Config};
+
+pub struct Counter(u32);
+
+#[abstract_process(visibility = pub, client_mod = "counter_client")]
+impl Counter {
+    #[init]
+    fn init(_: Config<Self>, start: u32) -> Result<Self, ()> {
+        Ok(Self(start))
+    }
+
+    #[handle_message]
+    fn increment(&mut self, by: u32) {
+        self.0 += by;
+    }
+
+    #[handle_request]
+    fn count(&self) -> u32 {
+        self.0
+    }
+}
+
+pub fn start(name: &str) {
+    Counter::start_as(&name, 0).unwrap();
+}
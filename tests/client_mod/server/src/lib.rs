@@ -0,0 +1,11 @@
+//! Server crate of the `client_mod` test. The process implementation stays
+//! private, clients only see the generated `counter_client` module.
+
+mod counter;
+
+pub use counter::counter_client as api;
+
+/// Starts the counter under `name`.
+pub fn start(name: &str) {
+    counter::start(name);
+}
@@ -0,0 +1,71 @@
+use std::io::{ErrorKind, Write};
+
+use lunatic::codec::{JsonCodec, LengthDelimitedCodec};
+use lunatic::{net, Mailbox, Process};
+use lunatic_test::test;
+use serde::{Deserialize, Serialize};
+
+#[test]
+fn length_delimited_frames() {
+    let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    Process::spawn(addr, |addr, _: Mailbox<()>| {
+        let stream = net::TcpStream::connect(addr).unwrap();
+        let mut framed = LengthDelimitedCodec::new(stream);
+        framed.write_frame(b"hello").unwrap();
+        framed.write_frame(b"").unwrap();
+        framed.write_frame(&[7; 10_000]).unwrap();
+    });
+
+    let (stream, _) = listener.accept().unwrap();
+    let mut framed = LengthDelimitedCodec::new(stream);
+    assert_eq!(framed.read_frame().unwrap(), b"hello");
+    assert_eq!(framed.read_frame().unwrap(), b"");
+    assert_eq!(framed.read_frame().unwrap(), vec![7; 10_000]);
+    let err = framed.read_frame().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn frame_too_long() {
+    let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    Process::spawn(addr, |addr, _: Mailbox<()>| {
+        let mut stream = net::TcpStream::connect(addr).unwrap();
+        stream.write_all(&100u32.to_be_bytes()).unwrap();
+    });
+
+    let (stream, _) = listener.accept().unwrap();
+    let mut framed = LengthDelimitedCodec::new(stream);
+    framed.set_max_frame_length(10);
+    let err = framed.read_frame().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    let err = framed.write_frame(&[0; 11]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Command {
+    Set(String, u32),
+    Get(String),
+}
+
+#[test]
+fn json_codec() {
+    let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    Process::spawn(addr, |addr, _: Mailbox<()>| {
+        let stream = net::TcpStream::connect(addr).unwrap();
+        let mut codec = JsonCodec::<Command>::new(stream);
+        codec.write(&Command::Set("a".to_owned(), 1)).unwrap();
+        codec.write(&Command::Get("a".to_owned())).unwrap();
+        codec.into_framed().write_frame(b"not json").unwrap();
+    });
+
+    let (stream, _) = listener.accept().unwrap();
+    let mut codec = JsonCodec::<Command>::new(stream);
+    assert_eq!(codec.read().unwrap(), Command::Set("a".to_owned(), 1));
+    assert_eq!(codec.read().unwrap(), Command::Get("a".to_owned()));
+    let err = codec.read().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
@@ -101,6 +101,25 @@ fn config_env_variable() {
     assert!(std::env::var("foo").is_err());
 }
 
+#[test]
+fn lunatic_env_var() {
+    use lunatic::env::{self, EnvError};
+
+    let mut config = ProcessConfig::new().unwrap();
+    config.add_environment_variable("hello", "world");
+
+    let task = spawn_link!(@task &config, || {
+        assert_eq!(env::var("hello"), Ok("world".to_owned()));
+        assert_eq!(env::var("hell"), Err(EnvError::NotPresent));
+        env::set_var("hello", "there");
+        assert_eq!(env::var("hello"), Ok("there".to_owned()));
+        std::env::var("hello").unwrap()
+    });
+    assert_eq!(task.result(), "there");
+
+    assert_eq!(env::var("hello"), Err(EnvError::NotPresent));
+}
+
 #[test]
 fn config_cli_args() {
     let mut config = ProcessConfig::new().unwrap();
@@ -127,3 +146,10 @@ fn config_file_operations() {
     });
     let _ = task.result();
 }
+
+#[test]
+#[should_panic]
+fn invalid_wasm_module_is_rejected() {
+    let mut config = ProcessConfig::new().unwrap();
+    config.set_wasm_module(b"not a wasm module");
+}
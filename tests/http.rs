@@ -0,0 +1,116 @@
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use lunatic::http::client::{self, RequestBuilder, Response};
+use lunatic::http::{server, HttpError, Method};
+use lunatic::{net, Mailbox, Process};
+use lunatic_test::test;
+
+/// Accepts one connection, replies with `response` and returns the raw
+/// request.
+fn serve_once(listener: &net::TcpListener, response: &str) -> String {
+    let (mut stream, _) = listener.accept().unwrap();
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.ends_with(b"\r\n\r\n") && !request.ends_with(b"ping") {
+        let n = stream.read(&mut buf).unwrap();
+        request.extend_from_slice(&buf[..n]);
+    }
+    stream.write_all(response.as_bytes()).unwrap();
+    String::from_utf8(request).unwrap()
+}
+
+#[test]
+fn get_content_length(mailbox: Mailbox<Result<Response, HttpError>>) {
+    let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let url = format!("http://127.0.0.1:{port}/hello?x=1");
+    Process::spawn((url, mailbox.this()), |(url, parent), _: Mailbox<()>| {
+        parent.send(client::get(&url))
+    });
+
+    let request = serve_once(
+        &listener,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\r\nhello",
+    );
+    assert!(request.starts_with("GET /hello?x=1 HTTP/1.1\r\n"));
+    assert!(request.contains(&format!("Host: 127.0.0.1:{port}\r\n")));
+
+    let response = mailbox.receive().unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(response.header("Content-Type"), Some("text/plain"));
+    assert_eq!(response.body, b"hello");
+}
+
+#[test]
+fn post_chunked_response(mailbox: Mailbox<Result<Response, HttpError>>) {
+    let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let request = RequestBuilder::new(Method::Post, &format!("http://127.0.0.1:{port}"))
+        .header("x-test", "yes")
+        .body("ping")
+        .timeout(Duration::from_secs(5));
+    Process::spawn(
+        (request, mailbox.this()),
+        |(request, parent), _: Mailbox<()>| parent.send(request.send()),
+    );
+
+    let request = serve_once(
+        &listener,
+        "HTTP/1.1 201 Created\r\nTransfer-Encoding: chunked\r\n\r\n3\r\npon\r\n1\r\ng\r\n0\r\n\r\n",
+    );
+    assert!(request.starts_with("POST / HTTP/1.1\r\n"));
+    assert!(request.contains("x-test: yes\r\n"));
+    assert!(request.contains("Content-Length: 4\r\n"));
+    assert!(request.ends_with("\r\n\r\nping"));
+
+    let response = mailbox.receive().unwrap();
+    assert_eq!(response.status, 201);
+    assert_eq!(response.text(), "pong");
+}
+
+#[test]
+fn invalid_urls() {
+    assert_eq!(
+        client::get("ftp://example.com"),
+        Err(HttpError::UnsupportedScheme("ftp".to_owned()))
+    );
+    assert_eq!(
+        client::get("example.com"),
+        Err(HttpError::InvalidUrl("example.com".to_owned()))
+    );
+}
+
+#[test]
+fn server_round_trip() {
+    let server = server::listen("127.0.0.1:0", |request: server::Request| {
+        server::Response::new(200)
+            .header("x-method", request.method.as_str())
+            .body([request.path.as_bytes(), b" ", &request.body].concat())
+    })
+    .unwrap();
+    let url = format!("http://{}/echo", server.local_addr());
+
+    let response = client::post(&url, "ping").unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(response.header("x-method"), Some("POST"));
+    assert_eq!(response.text(), "/echo ping");
+
+    server.stop();
+}
+
+#[test]
+fn server_handler_panics() {
+    let server = server::listen("127.0.0.1:0", |_: server::Request| -> server::Response {
+        panic!("handler failed")
+    })
+    .unwrap();
+    let url = format!("http://{}/", server.local_addr());
+
+    let response = client::get(&url).unwrap();
+    assert_eq!(response.status, 500);
+    // The server keeps accepting requests.
+    assert_eq!(client::get(&url).unwrap().status, 500);
+
+    server.stop();
+}
@@ -0,0 +1,18 @@
+use lunatic::io::{stdin_lines, StdinEvent};
+use lunatic::{test, Mailbox};
+
+#[test]
+fn stdin_event_interrupted_by_message(mailbox: Mailbox<u32>) {
+    let mut lines = stdin_lines();
+    mailbox.this().send(42);
+    // Stdin may have ended or produced lines before the message arrives.
+    loop {
+        match lines.next_event(&mailbox).unwrap() {
+            StdinEvent::Message(message) => {
+                assert_eq!(message, 42);
+                break;
+            }
+            StdinEvent::Line(_) | StdinEvent::Eof => continue,
+        }
+    }
+}
@@ -0,0 +1,18 @@
+//! Client of an abstract process generated with
+//! `visibility(msgs = pub(crate), traits = pub)` in the
+//! `item-visibility-server` crate. Only the client traits are reachable from
+//! here, `tests/ui/crate_private_messages.rs` checks that the message types
+//! aren't.
+
+use lunatic::ap::ProcessRef;
+use lunatic::test;
+
+#[test]
+fn client_uses_public_traits() {
+    use item_visibility_server::store::{Store, StoreMessages, StoreRequests};
+
+    item_visibility_server::start("item_visibility_store");
+    let store = ProcessRef::<Store>::lookup("item_visibility_store").unwrap();
+    store.insert("second".to_owned());
+    assert_eq!(store.count(), 2);
+}
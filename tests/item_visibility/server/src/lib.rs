@@ -0,0 +1,9 @@
+//! Server crate of the `item_visibility` test. The message types generated
+//! for `Store` are `pub(crate)`, other crates only see the client traits.
+
+pub mod store;
+
+/// Starts the store under `name`.
+pub fn start(name: &str) {
+    store::start(name);
+}
@@ -0,0 +1,34 @@
+//! The process with public client traits and crate-private message types.
+
+use lunatic::abstract_process;
+use lunatic::ap::{AbstractProcess, Config};
+
+pub struct Store(Vec<String>);
+
+#[abstract_process(visibility(msgs = pub(crate), traits = pub))]
+impl Store {
+    #[init]
+    fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+        Ok(Self(Vec::new()))
+    }
+
+    #[handle_message]
+    fn insert(&mut self, value: String) {
+        self.0.push(value);
+    }
+
+    #[handle_request]
+    fn count(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// The message types are usable inside of this crate.
+fn insert_message(value: String) -> __MsgWrapInsert {
+    __MsgWrapInsert(value)
+}
+
+pub(crate) fn start(name: &str) {
+    let store = Store::start_as(&name, ()).unwrap();
+    store.send(insert_message("first".to_owned()));
+}
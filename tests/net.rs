@@ -0,0 +1,37 @@
+use std::io::{BufRead, Write};
+
+use lunatic::net::{self, BufReader, BufWriter};
+use lunatic::{Mailbox, Process};
+use lunatic_test::test;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Greeting {
+    from: String,
+}
+
+#[test]
+fn buffered_tcp_stream() {
+    let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    Process::spawn(addr, |addr, _: Mailbox<()>| {
+        let stream = net::TcpStream::connect(addr).unwrap();
+        let mut writer = BufWriter::new(stream);
+        writer.write_all(b"hello\nworld\n").unwrap();
+        let greeting = Greeting {
+            from: "client".to_owned(),
+        };
+        serde_json::to_writer(&mut writer, &greeting).unwrap();
+        // Dropping the writer flushes it and closes the stream.
+    });
+
+    let (stream, _) = listener.accept().unwrap();
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    reader.read_until(b'\n', &mut line).unwrap();
+    assert_eq!(line, b"hello\n");
+    let mut lines = (&mut reader).lines();
+    assert_eq!(lines.next().unwrap().unwrap(), "world");
+    let greeting: Greeting = serde_json::from_reader(reader).unwrap();
+    assert_eq!(greeting.from, "client");
+}
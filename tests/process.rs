@@ -1,8 +1,9 @@
 use std::time::Duration;
 
+use lunatic::distributed::{node_id, RemoteCall, RemoteCallError, RemoteCallable};
 use lunatic::host::api::message::receive;
 use lunatic::host::api::process::die_when_link_dies;
-use lunatic::{spawn_link, Mailbox, Process, ProcessConfig};
+use lunatic::{spawn_link, Mailbox, Process, ProcessConfig, Tag};
 use lunatic_test::test;
 
 #[test]
@@ -117,6 +118,17 @@ fn kill_process() {
     lunatic::sleep(Duration::from_millis(100));
 }
 
+#[test]
+fn kill_process_gracefully(m: Mailbox<bool>) {
+    let child = Process::spawn(m.this(), |parent, mailbox: Mailbox<()>| {
+        // The signal also interrupts waiting for other tags.
+        let result = mailbox.tag_receive_timeout(&[Tag::new()], Duration::from_secs(10));
+        parent.send(matches!(result, Err(err) if err.is_process_killed()));
+    });
+    child.kill_gracefully();
+    assert!(m.receive());
+}
+
 #[test]
 fn unlink_shouldnt_fail_on_dead_process() {
     let child = Process::spawn_link((), |_, _: Mailbox<()>| {});
@@ -149,3 +161,43 @@ fn is_alive() {
     lunatic::sleep(Duration::from_millis(150));
     assert_eq!(child.is_alive(), false);
 }
+
+#[derive(serde::Serialize, serde::Deserialize)]
+enum Call {
+    Add(u32, u32),
+    Panic,
+    Sleep(Duration),
+}
+
+impl RemoteCallable for Call {
+    type Output = u32;
+
+    fn execute(self) -> u32 {
+        match self {
+            Call::Add(a, b) => a + b,
+            Call::Panic => panic!("remote call failed"),
+            Call::Sleep(duration) => {
+                lunatic::sleep(duration);
+                0
+            }
+        }
+    }
+}
+
+#[test]
+fn remote_call_on_local_node() {
+    let node = node_id();
+    assert_eq!(RemoteCall::call(node, Call::Add(1, 2)), Ok(3));
+    assert_eq!(
+        RemoteCall::call(node, Call::Panic),
+        Err(RemoteCallError::Panicked)
+    );
+    assert_eq!(
+        RemoteCall::call_timeout(
+            node,
+            Call::Sleep(Duration::from_secs(1)),
+            Some(Duration::from_millis(50))
+        ),
+        Err(RemoteCallError::TimedOut)
+    );
+}
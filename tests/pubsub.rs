@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use lunatic::pubsub::Topic;
+use lunatic::{sleep, spawn_link, Mailbox, MailboxError};
+use lunatic_test::test;
+
+#[test]
+fn publish_to_subscribers(mailbox: Mailbox<String>) {
+    let topic = Topic::<String>::new("publish_to_subscribers");
+    let parent = mailbox.this();
+    spawn_link!(|parent, mailbox: Mailbox<String>| {
+        let topic = Topic::<String>::new("publish_to_subscribers");
+        topic.subscribe(&mailbox);
+        parent.send("subscribed".to_owned());
+        let message = mailbox.receive();
+        parent.send(format!("child: {message}"));
+    });
+    assert_eq!(mailbox.receive(), "subscribed");
+
+    let subscription = topic.subscribe(&mailbox);
+    topic.publish("hello".to_owned());
+    let mut received = vec![mailbox.receive(), mailbox.receive()];
+    received.sort();
+    assert_eq!(received, vec!["child: hello", "hello"]);
+
+    subscription.unsubscribe();
+    topic.publish("bye".to_owned());
+    assert!(matches!(
+        mailbox.receive_timeout(Duration::from_millis(50)),
+        Err(MailboxError::TimedOut)
+    ));
+}
+
+#[test]
+fn topics_are_namespaced(mailbox: Mailbox<String>) {
+    let first = Topic::<String>::new("namespaced_first");
+    let second = Topic::<String>::new("namespaced_second");
+    first.subscribe(&mailbox);
+    second.publish("second".to_owned());
+    first.publish("first".to_owned());
+    assert_eq!(mailbox.receive(), "first");
+}
+
+#[test]
+fn dead_subscribers_are_removed(mailbox: Mailbox<String>) {
+    let topic = Topic::<String>::new("dead_subscribers_are_removed");
+    let parent = mailbox.this();
+    let child = spawn_link!(|parent, mailbox: Mailbox<String>| {
+        Topic::<String>::new("dead_subscribers_are_removed").subscribe(&mailbox);
+        parent.send("subscribed".to_owned());
+        mailbox.receive();
+    });
+    assert_eq!(mailbox.receive(), "subscribed");
+    child.unlink();
+    child.kill();
+    sleep(Duration::from_millis(10));
+    // Publishing to a topic without live subscribers must not fail.
+    topic.publish("hello".to_owned());
+    topic.subscribe(&mailbox);
+    topic.publish("again".to_owned());
+    assert_eq!(mailbox.receive(), "again");
+}
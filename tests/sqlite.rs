@@ -1,4 +1,4 @@
-use lunatic::sqlite::{Query, SqliteClient, Value};
+use lunatic::sqlite::{Connection, Query, SqliteClient, SqliteCode, SqliteError, Value};
 use lunatic_test::test;
 
 #[test]
@@ -25,3 +25,63 @@ fn execute() {
 
     client.execute("select \"Hello\"").unwrap();
 }
+
+#[test]
+fn connection_execute_and_query_map() {
+    let conn = Connection::open(":memory:").unwrap();
+    conn.execute("create table users (id integer, name text, email text)", ())
+        .unwrap();
+    let changed = conn
+        .execute(
+            "insert into users values (?, ?, ?), (?, ?, ?)",
+            (1, "Ana", "ana@example.com", 2, "Bo", None::<String>),
+        )
+        .unwrap();
+    assert_eq!(changed, 2);
+
+    let users = conn
+        .query_map("select id, name, email from users order by id", (), |row| {
+            Ok((
+                row.get::<i64>(0)?,
+                row.get::<String>(1)?,
+                row.get::<Option<String>>(2)?,
+            ))
+        })
+        .unwrap();
+    assert_eq!(
+        users,
+        vec![
+            (1, "Ana".to_owned(), Some("ana@example.com".to_owned())),
+            (2, "Bo".to_owned(), None)
+        ]
+    );
+
+    let err = conn
+        .query_map("select name from users", (), |row| row.get::<i64>(0))
+        .unwrap_err();
+    assert_eq!(err.code, SqliteCode::Mismatch);
+    let err = conn
+        .query_map("select name from users", (), |row| row.get::<String>(1))
+        .unwrap_err();
+    assert_eq!(err.code, SqliteCode::Range);
+}
+
+#[test]
+fn connection_transaction() {
+    let mut conn = Connection::open(":memory:").unwrap();
+    conn.execute("create table counter (value integer)", ())
+        .unwrap();
+
+    conn.transaction(|tx| tx.execute("insert into counter values (?)", (1,)))
+        .unwrap();
+    let rolled_back: Result<(), SqliteError> = conn.transaction(|tx| {
+        tx.execute("insert into counter values (?)", (2,))?;
+        Err(SqliteError::default())
+    });
+    assert!(rolled_back.is_err());
+
+    let values = conn
+        .query_map("select value from counter", (), |row| row.get::<i64>(0))
+        .unwrap();
+    assert_eq!(values, vec![1]);
+}
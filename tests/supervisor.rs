@@ -1,10 +1,17 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use lunatic::ap::handlers::{Message, Request};
-use lunatic::ap::{AbstractProcess, Config, MessageHandler, ProcessRef, RequestHandler, State};
-use lunatic::serializer::{Json, MessagePack};
-use lunatic::supervisor::{Supervisor, SupervisorConfig, SupervisorStrategy};
-use lunatic::{sleep, spawn, test, ProcessConfig};
+use lunatic::ap::{
+    AbstractProcess, Config, MessageHandler, ProcessRef, RequestHandler, StartupError, State,
+};
+use lunatic::serializer::{Bincode, Json, MessagePack};
+use lunatic::supervisor::{
+    AutoShutdown, Backoff, ChildCounts, ChildHooks, ChildId, ChildNode, ChildRestart,
+    ChildShutdown, ChildSpec, ChildStartCause, ChildStatus, FactorySupervisor, Handoff, Health,
+    RestartArg, SiblingRefs, Siblings, StateHandoff, Supervisor, SupervisorConfig, SupervisorError,
+    SupervisorEvent, SupervisorStrategy, SupervisorTree,
+};
+use lunatic::{distributed, sleep, spawn, test, Mailbox, Process, ProcessConfig};
 
 const LOGGER_NAME: &'static str = "logger/assert_order";
 
@@ -350,6 +357,49 @@ fn two_failing_process_one_for_all() {
         b.send(Inc);
     }
 }
+
+#[test]
+fn one_for_all_terminates_siblings_in_reverse() {
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = (A, A, A);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_strategy(SupervisorStrategy::OneForAll);
+            config.set_args(((0, 'a'), (0, 'b'), (0, 'c')));
+            config.set_shutdowns((
+                ChildShutdown::Infinity,
+                ChildShutdown::Timeout(Duration::from_secs(1)),
+                ChildShutdown::Timeout(Duration::from_secs(1)),
+            ));
+        }
+    }
+
+    let logger = Logger::link().start_as(&LOGGER_NAME, ()).unwrap();
+    let sup = Sup::link().start(()).unwrap();
+
+    let (a, _, _) = sup.children();
+    a.send(Panic);
+    sleep(Duration::from_millis(20));
+
+    // The healthy siblings run `terminate` before anything is restarted.
+    let log = logger.request(TakeLogs);
+    assert_eq!(
+        log,
+        vec![
+            LogEvent::Init('a'),
+            LogEvent::Init('b'),
+            LogEvent::Init('c'),
+            LogEvent::Panic('a'),
+            LogEvent::Shutdown('c'),
+            LogEvent::Shutdown('b'),
+            LogEvent::Init('a'),
+            LogEvent::Init('b'),
+            LogEvent::Init('c'),
+        ]
+    );
+}
 #[test]
 fn four_failing_process_rest_for_all() {
     struct Sup;
@@ -621,3 +671,1369 @@ fn env_var_config() {
     );
     assert_eq!(named.request(GetEnvVar("no".to_string())), None);
 }
+
+// Child allocating memory on request.
+struct Hog;
+
+impl AbstractProcess for Hog {
+    type Arg = ();
+    type State = Vec<Vec<u8>>;
+    type Serializer = Bincode;
+    type Handlers = (Request<Alloc>,);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, _: ()) -> Result<Vec<Vec<u8>>, ()> {
+        Ok(Vec::new())
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Alloc(usize);
+impl RequestHandler<Alloc> for Hog {
+    type Response = ();
+
+    fn handle(mut state: State<Self>, Alloc(bytes): Alloc) {
+        state.push(vec![1; bytes]);
+    }
+}
+
+#[test]
+fn memory_limit_config() {
+    const MB: usize = 1024 * 1024;
+
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = (Hog,);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((),));
+            let mut process_config = ProcessConfig::new().unwrap();
+            process_config.set_max_memory(32 * MB as u64);
+            config.set_configs((Some(process_config),));
+        }
+    }
+
+    let sup = Sup::link().start(()).unwrap();
+    for restarts in 1..=2 {
+        let hog = sup.children().0;
+        hog.request(Alloc(MB));
+        // The limit applies to the restarted child too.
+        let _ = hog.request_timeout(Alloc(64 * MB), Some(Duration::from_millis(500)));
+        sleep(Duration::from_millis(50));
+        assert_ne!(sup.children().0, hog);
+        assert_eq!(sup.which_children()[0].restarts, restarts);
+    }
+    sup.shutdown();
+}
+
+// Child looking up an earlier child by name during `init`.
+struct Dependent(u32);
+
+impl AbstractProcess for Dependent {
+    type Arg = String;
+    type State = Dependent;
+    type Serializer = MessagePack;
+    type Handlers = (Request<Count>,);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, name: String) -> Result<Dependent, ()> {
+        let dependency = ProcessRef::<A>::lookup(&name).unwrap();
+        Ok(Dependent(dependency.request(Count)))
+    }
+}
+
+impl RequestHandler<Count> for Dependent {
+    type Response = u32;
+
+    fn handle(state: State<Self>, _: Count) -> u32 {
+        state.0
+    }
+}
+
+#[test]
+fn rest_for_one_lookup_during_init() {
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = (A, A, Dependent);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_strategy(SupervisorStrategy::RestForOne);
+            config.set_args(((1, 'a'), (2, 'b'), "rest_for_one/b".to_owned()));
+            config.set_names((
+                Some("rest_for_one/a".to_owned()),
+                Some("rest_for_one/b".to_owned()),
+                Some("rest_for_one/dependent".to_owned()),
+            ));
+        }
+    }
+
+    let sup = Sup::link().start(()).unwrap();
+    let (a, b, dependent) = sup.children();
+    assert_eq!(dependent.request(Count), 2);
+
+    b.send(Panic);
+    sleep(Duration::from_millis(100));
+
+    // `b` and `dependent` are restarted in order, `a` is left untouched.
+    let (new_a, new_b, new_dependent) = sup.children();
+    assert_eq!(new_a, a);
+    assert_ne!(new_b, b);
+    assert_ne!(new_dependent, dependent);
+    // The dependent child found the restarted `b` during `init`.
+    assert_eq!(new_dependent.request(Count), 2);
+    let lookup = ProcessRef::<A>::lookup(&"rest_for_one/b").unwrap();
+    assert_eq!(lookup, new_b);
+    let lookup = ProcessRef::<Dependent>::lookup(&"rest_for_one/dependent").unwrap();
+    assert_eq!(lookup, new_dependent);
+}
+
+#[test]
+fn exit_reason_restarts() {
+    use lunatic::process::ExitReason;
+
+    // Supervisors restart children on every reason except `Normal`.
+    assert!(!ExitReason::Normal.is_failure());
+    let panicked = ExitReason::Panicked {
+        message: "boom".to_owned(),
+    };
+    assert!(panicked.is_failure());
+    assert!(ExitReason::Unknown.is_failure());
+}
+
+#[test]
+fn max_restarts_window() {
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = (A,);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((0, 'a'),));
+            config.set_max_restarts(2, Duration::from_millis(300));
+        }
+    }
+
+    // Not linked, the supervisor exits once it gives up.
+    let sup = Sup::start(()).unwrap();
+    let panic_child = || {
+        let (a,) = sup.children();
+        a.send(Panic);
+        sleep(Duration::from_millis(50));
+    };
+
+    panic_child();
+    panic_child();
+    // The earlier restarts leave the window and restarts resume.
+    sleep(Duration::from_millis(400));
+    panic_child();
+    panic_child();
+    assert!(sup.is_alive());
+    assert_eq!(sup.children().0.request(Count), 0);
+
+    // Third failure within the window.
+    panic_child();
+    assert!(!sup.is_alive());
+}
+
+#[test]
+fn max_restarts_escalation() {
+    struct Inner;
+    impl Supervisor for Inner {
+        type Arg = ();
+        type Children = (A, A);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((0, 'a'), (0, 'b')));
+            config.set_max_restarts(1, Duration::from_secs(10));
+        }
+    }
+
+    struct Outer;
+    impl Supervisor for Outer {
+        type Arg = ();
+        type Children = (Inner,);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((),));
+        }
+    }
+
+    let outer = Outer::link().start(()).unwrap();
+    let (inner,) = outer.children();
+    let (_, b) = inner.children();
+
+    // The first failure is restarted by `inner`.
+    inner.children().0.send(Panic);
+    sleep(Duration::from_millis(50));
+    assert!(inner.is_alive());
+    assert!(b.is_alive());
+
+    // The second one exceeds the limit, `inner` shuts `b` down and exits, and
+    // `outer` restarts it together with new children.
+    inner.children().0.send(Panic);
+    sleep(Duration::from_millis(100));
+    assert!(!inner.is_alive());
+    assert!(!b.is_alive());
+    let (new_inner,) = outer.children();
+    assert_ne!(new_inner, inner);
+    let (new_a, new_b) = new_inner.children();
+    assert_eq!(new_a.request(Count), 0);
+    assert_eq!(new_b.request(Count), 0);
+}
+
+#[test]
+fn dynamic_children() {
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = ();
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(());
+        }
+    }
+
+    let sup = Sup::link().start(()).unwrap();
+    let children: Vec<_> = (0..100)
+        .map(|count| sup.start_child::<A>((count, 'x')).unwrap())
+        .collect();
+
+    let crashed = [3, 42, 99];
+    for &index in &crashed {
+        let (_, child) = &children[index];
+        child.send(Inc);
+        child.send(Panic);
+    }
+    sleep(Duration::from_millis(100));
+
+    // Only the crashed children run as new processes, with their original
+    // argument.
+    for (index, (id, child)) in children.iter().enumerate() {
+        let current = sup.dynamic_child::<A>(id.clone()).unwrap();
+        assert_eq!(current == *child, !crashed.contains(&index));
+        assert_eq!(current.request(Count), index as u32);
+    }
+
+    let (id, child) = &children[0];
+    assert!(sup.terminate_child(id.clone()));
+    assert!(!child.is_alive());
+    assert!(!sup.terminate_child(id.clone()));
+    assert!(sup.dynamic_child::<A>(id.clone()).is_none());
+
+    let (id, child) = &children[1];
+    assert!(sup.terminate_child_ref(child));
+    assert!(sup.dynamic_child::<A>(id.clone()).is_none());
+    // The restarted child runs as a different process.
+    assert!(!sup.terminate_child_ref(&children[3].1));
+}
+
+#[test]
+fn dynamic_child_events(mailbox: Mailbox<SupervisorEvent>) {
+    use lunatic::process::ExitReason;
+
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = ();
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(());
+        }
+    }
+
+    let sup = Sup::link().start(()).unwrap();
+    let (id, child) = sup.start_child::<A>((0, 'e')).unwrap();
+    sup.subscribe_events(mailbox.this());
+
+    // A normal exit isn't reported as a crash.
+    child.shutdown();
+    match mailbox.receive() {
+        SupervisorEvent::ChildExited { child, .. } => assert_eq!(child.child, id),
+        event => panic!("unexpected event {event:?}"),
+    }
+    assert!(matches!(
+        mailbox.receive(),
+        SupervisorEvent::ChildRestarted { .. }
+    ));
+
+    sup.dynamic_child::<A>(id.clone()).unwrap().send(Panic);
+    match mailbox.receive() {
+        SupervisorEvent::ChildCrashed { child, reason, .. } => {
+            assert_eq!(child.child, id);
+            let message = "explicit panic".to_owned();
+            assert_eq!(reason, ExitReason::Panicked { message });
+        }
+        event => panic!("unexpected event {event:?}"),
+    }
+}
+
+#[test]
+fn factory_supervisor() {
+    let factory = FactorySupervisor::<A>::link().start(()).unwrap();
+    let children: Vec<_> = (0..5)
+        .map(|count| factory.spawn((count, 'f')).unwrap())
+        .collect();
+    assert_eq!(factory.count(), 5);
+    assert_eq!(factory.which_children(), children);
+
+    // Only the failed child is restarted, with its original argument.
+    children[2].send(Inc);
+    children[2].send(Panic);
+    sleep(Duration::from_millis(50));
+    let current = factory.which_children();
+    assert_eq!(current.len(), 5);
+    for (index, (child, old)) in current.iter().zip(&children).enumerate() {
+        assert_eq!(child == old, index != 2);
+        assert_eq!(child.request(Count), index as u32);
+    }
+
+    assert!(factory.terminate_child(&children[0]));
+    assert!(!children[0].is_alive());
+    assert!(!factory.terminate_child(&children[2]));
+    assert_eq!(factory.count(), 4);
+}
+
+#[test]
+fn transient_child() {
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = (A, A);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((0, 'a'), (0, 'b')));
+            config.set_restarts((ChildRestart::Transient, ChildRestart::Transient));
+        }
+    }
+
+    let sup = Sup::link().start(()).unwrap();
+    let (a, b) = sup.children();
+
+    // A failing transient child is restarted.
+    a.send(Panic);
+    sleep(Duration::from_millis(50));
+    let (new_a, _) = sup.children();
+    assert_ne!(new_a, a);
+    assert!(new_a.is_alive());
+
+    // A transient child finishing normally isn't.
+    b.shutdown();
+    sleep(Duration::from_millis(50));
+    let (_, new_b) = sup.children();
+    assert_eq!(new_b, b);
+    assert!(!new_b.is_alive());
+
+    sup.shutdown();
+}
+
+#[test]
+fn permanent_and_temporary_children() {
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = (A, A);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((0, 'a'), (0, 'b')));
+            config.set_restarts((ChildRestart::Permanent, ChildRestart::Temporary));
+            // Exits of temporary children don't count towards the intensity.
+            config.set_max_restarts(1, Duration::from_secs(10));
+        }
+    }
+
+    let sup = Sup::link().start(()).unwrap();
+    let (a, b) = sup.children();
+
+    // A permanent child is restarted even if it finishes normally.
+    a.shutdown();
+    sleep(Duration::from_millis(50));
+    let (new_a, _) = sup.children();
+    assert_ne!(new_a, a);
+    assert!(new_a.is_alive());
+
+    // A temporary child is never restarted.
+    b.send(Panic);
+    sleep(Duration::from_millis(50));
+    let (_, new_b) = sup.children();
+    assert_eq!(new_b, b);
+    assert!(!new_b.is_alive());
+    assert!(sup.is_alive());
+
+    sup.shutdown();
+}
+
+#[test]
+fn which_and_count_children() {
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = (A, A);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((0, 'a'), (0, 'b')));
+            config.set_names((Some("which_children_a".to_owned()), None));
+            config.set_restarts((ChildRestart::Permanent, ChildRestart::Temporary));
+        }
+    }
+
+    let sup = Sup::link().start(()).unwrap();
+    let (a, b) = sup.children();
+    let (id, c) = sup.start_child::<A>((0, 'c')).unwrap();
+
+    a.send(Panic);
+    b.shutdown();
+    c.send(Panic);
+    sleep(Duration::from_millis(50));
+
+    let children = sup.which_children();
+    assert_eq!(children.len(), 3);
+    assert_eq!(children[0].child, ChildId::Static(0));
+    assert_eq!(children[0].name.as_deref(), Some("which_children_a"));
+    assert_eq!(children[0].status, ChildStatus::Running);
+    assert_eq!(children[0].restarts, 1);
+    assert_eq!(children[0].process::<A>(), Some(sup.children().0));
+    assert_eq!(children[1].status, ChildStatus::Exited);
+    assert_eq!(children[1].restarts, 0);
+    assert_eq!(children[2].child, id);
+    assert_eq!(children[2].restarts, 1);
+    assert_eq!(children[2].process::<A>(), sup.dynamic_child::<A>(id));
+
+    assert_eq!(
+        sup.count_children(),
+        ChildCounts {
+            specs: 3,
+            active: 2,
+            dynamic: 1,
+        }
+    );
+
+    sup.shutdown();
+}
+
+#[test]
+fn stop_and_restart_child() {
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = (A, A);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((0, 'a'), (10, 'b')));
+        }
+    }
+
+    let sup = Sup::link().start(()).unwrap();
+    let (a, b) = sup.children();
+    a.send(Inc);
+    b.send(Inc);
+
+    // Only the restarted child starts over.
+    let restarted = sup.restart_child::<A>(ChildId::Static(0)).unwrap();
+    assert_ne!(restarted, a);
+    assert_eq!(sup.children().0, restarted);
+    assert_eq!(restarted.request(Count), 0);
+    assert_eq!(b.request(Count), 11);
+
+    sup.stop_child(ChildId::Static(1)).unwrap();
+    sleep(Duration::from_millis(10));
+    assert_eq!(sup.which_children()[1].status, ChildStatus::Stopped);
+    assert_eq!(sup.count_children().active, 1);
+    let b = sup.restart_child::<A>(ChildId::Static(1)).unwrap();
+    assert_eq!(b.request(Count), 10);
+    assert_eq!(sup.which_children()[1].status, ChildStatus::Running);
+
+    let (id, c) = sup.start_child::<A>((20, 'c')).unwrap();
+    sup.stop_child(id.clone()).unwrap();
+    assert!(!c.is_alive());
+    assert_eq!(sup.which_children()[2].status, ChildStatus::Stopped);
+    let c = sup.restart_child::<A>(id.clone()).unwrap();
+    assert_eq!(sup.dynamic_child::<A>(id), Some(c));
+    assert_eq!(c.request(Count), 20);
+
+    assert_eq!(
+        sup.restart_child::<A>(ChildId::Static(2)),
+        Err(SupervisorError::ChildNotFound(ChildId::Static(2)))
+    );
+    assert!(matches!(
+        sup.restart_child::<Logger>(ChildId::Static(0)),
+        Err(SupervisorError::WrongType(_))
+    ));
+
+    sup.shutdown();
+}
+
+#[test]
+fn restart_backoff() {
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = (A, A);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((0, 'a'), (0, 'b')));
+            let backoff = Backoff::new(Duration::from_millis(200), Duration::from_millis(400), 2.0)
+                .healthy_after(Duration::from_secs(10));
+            config.set_backoffs((Some(backoff), None));
+        }
+    }
+
+    let sup = Sup::link().start(()).unwrap();
+    let (a, b) = sup.children();
+
+    // The supervisor keeps answering while the restart is delayed.
+    a.send(Panic);
+    sleep(Duration::from_millis(50));
+    assert_eq!(sup.which_children()[0].status, ChildStatus::Restarting);
+    assert_eq!(sup.children().0, a);
+
+    // Children without a backoff are still restarted right away.
+    b.send(Panic);
+    sleep(Duration::from_millis(50));
+    assert_ne!(sup.children().1, b);
+
+    sleep(Duration::from_millis(200));
+    let (a2, _) = sup.children();
+    assert_ne!(a2, a);
+    assert!(a2.is_alive());
+
+    // The next failure within the healthy period waits twice as long.
+    a2.send(Panic);
+    sleep(Duration::from_millis(300));
+    assert_eq!(sup.children().0, a2);
+    sleep(Duration::from_millis(200));
+    assert_ne!(sup.children().0, a2);
+    assert_eq!(sup.which_children()[0].restarts, 2);
+
+    sup.shutdown();
+}
+
+struct SlowTerminate;
+
+impl AbstractProcess for SlowTerminate {
+    type Arg = ();
+    type State = Self;
+    type Serializer = Bincode;
+    type Handlers = ();
+    type StartupError = ();
+
+    fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+        Ok(SlowTerminate)
+    }
+
+    fn terminate(_: Self) {
+        sleep(Duration::from_secs(10));
+    }
+}
+
+#[test]
+fn shutdown_timeout_kills_slow_child() {
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = (SlowTerminate, SlowTerminate);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((), ()));
+            config.set_shutdowns((
+                ChildShutdown::Timeout(Duration::from_millis(500)),
+                ChildShutdown::BrutalKill,
+            ));
+        }
+    }
+
+    let sup = Sup::link().start(()).unwrap();
+    let (a, b) = sup.children();
+    let started = Instant::now();
+    sup.shutdown();
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(500));
+    assert!(elapsed < Duration::from_secs(2));
+    assert!(!a.is_alive());
+    assert!(!b.is_alive());
+}
+
+#[test]
+fn named_child_reregistered_on_restart() {
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = (A,);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((0, 'a'),));
+            config.set_names((Some("reregistered".to_owned()),));
+            let backoff = Backoff::new(Duration::from_millis(200), Duration::from_millis(200), 1.0);
+            config.set_backoffs((Some(backoff),));
+        }
+    }
+
+    let sup = Sup::link().start(()).unwrap();
+    let (a,) = sup.children();
+    assert_eq!(sup.lookup_child::<A>("reregistered"), Some(a));
+    assert_eq!(sup.lookup_child::<Logger>("reregistered"), None);
+
+    // While the restart is pending, the dead child can't be looked up.
+    a.send(Panic);
+    sleep(Duration::from_millis(50));
+    assert_eq!(ProcessRef::<A>::lookup(&"reregistered"), None);
+    assert_eq!(sup.lookup_child::<A>("reregistered"), None);
+
+    sleep(Duration::from_millis(250));
+    let (new_a,) = sup.children();
+    assert_ne!(new_a, a);
+    assert_eq!(ProcessRef::<A>::lookup(&"reregistered"), Some(new_a));
+    assert_eq!(sup.lookup_child::<A>("reregistered"), Some(new_a));
+
+    // Names are removed when the supervisor shuts down.
+    sup.shutdown();
+    assert_eq!(ProcessRef::<A>::lookup(&"reregistered"), None);
+}
+
+#[test]
+fn supervisor_events(mailbox: Mailbox<SupervisorEvent>) {
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = (A, A);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((0, 'a'), (0, 'b')));
+            config.set_max_restarts(1, Duration::from_secs(10));
+        }
+    }
+
+    let sup = Sup::start(()).unwrap();
+    sup.subscribe_events(mailbox.this());
+    let (a, b) = sup.children();
+
+    a.send(Panic);
+    match mailbox.receive() {
+        SupervisorEvent::ChildCrashed { child, .. } => {
+            assert_eq!(child.child, ChildId::Static(0));
+            assert_eq!(child.process_id, a.id());
+            assert_eq!(child.restarts, 0);
+        }
+        event => panic!("unexpected event {event:?}"),
+    }
+    match mailbox.receive() {
+        SupervisorEvent::ChildRestarted { child, .. } => {
+            assert_eq!(child.child, ChildId::Static(0));
+            assert_ne!(child.process_id, a.id());
+            assert_eq!(child.restarts, 1);
+        }
+        event => panic!("unexpected event {event:?}"),
+    }
+
+    // The second failure exceeds the intensity.
+    b.send(Panic);
+    assert!(matches!(
+        mailbox.receive(),
+        SupervisorEvent::ChildCrashed { .. }
+    ));
+    assert!(matches!(
+        mailbox.receive(),
+        SupervisorEvent::IntensityExceeded { .. }
+    ));
+}
+
+#[test]
+fn supervisor_tree() {
+    let tree = SupervisorTree::new()
+        .child::<A>("counter", (10, 'c'))
+        .supervisor("workers", |workers| {
+            workers
+                .strategy(SupervisorStrategy::OneForAll)
+                .child::<A>("first", (1, '1'))
+                .child::<A>("second", (2, '2'))
+        })
+        .start()
+        .unwrap();
+
+    let counter = tree.child::<A>("counter").unwrap();
+    let first = tree.child::<A>("workers/first").unwrap();
+    let second = tree.child::<A>("workers/second").unwrap();
+    assert_eq!(counter.request(Count), 10);
+    assert_eq!(first.request(Count), 1);
+    assert_eq!(second.request(Count), 2);
+    assert!(tree.child::<A>("workers").is_none());
+    assert!(tree.child::<A>("workers/third").is_none());
+    assert!(tree.child::<Logger>("counter").is_none());
+    let workers = tree.subtree("workers").unwrap();
+    assert_eq!(workers.child::<A>("first"), Some(first));
+
+    // Only the nested supervisor restarts all of its children.
+    counter.send(Inc);
+    first.send(Inc);
+    second.send(Panic);
+    sleep(Duration::from_millis(10));
+    assert_eq!(tree.child::<A>("counter"), Some(counter));
+    assert_eq!(counter.request(Count), 11);
+    let restarted = tree.child::<A>("workers/first").unwrap();
+    assert_ne!(restarted, first);
+    assert_eq!(restarted.request(Count), 1);
+    assert_ne!(tree.child::<A>("workers/second").unwrap(), second);
+
+    tree.shutdown();
+}
+
+/// `AbstractProcess` that always fails to start.
+struct Broken;
+
+impl AbstractProcess for Broken {
+    type Arg = ();
+    type State = ();
+    type Serializer = Bincode;
+    type Handlers = ();
+    type StartupError = String;
+
+    fn init(_: Config<Self>, _: ()) -> Result<(), String> {
+        Err("unavailable".to_owned())
+    }
+}
+
+#[test]
+fn supervisor_tree_startup_failure() {
+    let logger = Logger::link().start_as(&LOGGER_NAME, ()).unwrap();
+    let result = SupervisorTree::new()
+        .child::<A>("a", (0, 'a'))
+        .supervisor("workers", |workers| {
+            workers
+                .child::<A>("b", (0, 'b'))
+                .child::<Broken>("broken", ())
+                .child::<A>("c", (0, 'c'))
+        })
+        .start();
+
+    let err = result.unwrap_err();
+    assert_eq!(err.path, "workers/broken");
+    assert!(err.reason.contains("unavailable"));
+    // The started part of the tree is shut down again, bottom-up.
+    assert_eq!(
+        logger.request(TakeLogs),
+        vec![
+            LogEvent::Init('a'),
+            LogEvent::Init('b'),
+            LogEvent::Shutdown('b'),
+            LogEvent::Shutdown('a'),
+        ]
+    );
+}
+
+#[test]
+fn child_node_fallback() {
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = (A, A);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((0, 'a'), (10, 'b')));
+            // No node with this id is connected.
+            let node = ChildNode::on(u64::MAX).or(distributed::node_id());
+            config.set_nodes((Some(node), None));
+            config.set_node_check(Duration::from_millis(10));
+        }
+    }
+
+    let sup = Sup::link().start(()).unwrap();
+    let children = sup.which_children();
+    assert!(children
+        .iter()
+        .all(|child| child.node_id == distributed::node_id()));
+
+    // Children on the fallback node are linked like other local children.
+    let (a, _) = sup.children();
+    a.send(Panic);
+    sleep(Duration::from_millis(50));
+    let restarted = sup.children().0;
+    assert_ne!(restarted, a);
+    assert_eq!(restarted.request(Count), 0);
+    assert_eq!(sup.which_children()[0].restarts, 1);
+
+    sup.shutdown();
+}
+
+#[test]
+fn lookup_and_restart_remote_child() {
+    // Only runs if the test node is connected to another node.
+    let local = distributed::node_id();
+    let Some(remote) = distributed::nodes().into_iter().find(|&node| node != local) else {
+        return;
+    };
+
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = u64;
+        type Children = (A,);
+
+        fn init(config: &mut SupervisorConfig<Self>, remote: u64) {
+            config.set_args(((0, 'a'),));
+            config.set_names((Some("remote_child".to_owned()),));
+            config.set_nodes((Some(ChildNode::on(remote)),));
+        }
+    }
+
+    let sup = Sup::link().start(remote).unwrap();
+    let child = sup.lookup_child::<A>("remote_child").unwrap();
+    assert_eq!(child.node_id(), remote);
+    assert_eq!(child, sup.children().0);
+
+    let restarted = sup.restart_child::<A>(ChildId::Static(0)).unwrap();
+    assert_eq!(restarted.node_id(), remote);
+    assert_ne!(restarted, child);
+    assert_eq!(restarted, sup.children().0);
+    assert_eq!(restarted.request(Count), 0);
+
+    sup.shutdown();
+}
+
+#[test]
+fn auto_shutdown_any_significant() {
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = (A, A);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((0, 'a'), (0, 'b')));
+            config.set_restarts((ChildRestart::Transient, ChildRestart::Permanent));
+            config.set_significant((true, false));
+            config.set_auto_shutdown(AutoShutdown::AnySignificant);
+        }
+    }
+
+    let sup = Sup::link().start(()).unwrap();
+    // Failures of significant children are handled by the strategy.
+    sup.children().0.send(Panic);
+    sleep(Duration::from_millis(50));
+    assert!(sup.is_alive());
+
+    let (a, b) = sup.children();
+    a.shutdown();
+    sleep(Duration::from_millis(50));
+    assert!(!b.is_alive());
+    assert!(!sup.is_alive());
+}
+
+#[test]
+fn auto_shutdown_all_significant() {
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = (A, A, A);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((0, 'a'), (0, 'b'), (0, 'c')));
+            config.set_restarts((
+                ChildRestart::Temporary,
+                ChildRestart::Temporary,
+                ChildRestart::Permanent,
+            ));
+            config.set_significant((true, true, false));
+            config.set_auto_shutdown(AutoShutdown::AllSignificant);
+        }
+    }
+
+    let sup = Sup::link().start(()).unwrap();
+    let (a, b, c) = sup.children();
+    a.shutdown();
+    sleep(Duration::from_millis(50));
+    assert!(sup.is_alive());
+    b.shutdown();
+    sleep(Duration::from_millis(50));
+    assert!(!c.is_alive());
+    assert!(!sup.is_alive());
+}
+
+#[test]
+fn typed_child_refs(mailbox: Mailbox<ProcessRef<A>>) {
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = (A, Hog, A);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((0, 'a'), (), (10, 'c')));
+        }
+    }
+
+    let sup = Sup::link().start(()).unwrap();
+    let (a, hog, c) = sup.children();
+    assert_eq!(sup.child::<0>(), a);
+    assert_eq!(sup.child::<2>(), c);
+    assert_eq!(sup.child_of::<Hog, _>(), hog);
+
+    // The references follow restarts of the children.
+    sup.child::<0>().send(Panic);
+    sleep(Duration::from_millis(50));
+    let restarted = sup.child::<0>();
+    assert_ne!(restarted, a);
+    restarted.send(Inc);
+    assert_eq!(restarted.request(Count), 1);
+
+    // And are resolved the same way from other processes.
+    let this = mailbox.this();
+    spawn!(|sup, this| this.send(sup.child::<2>()));
+    assert_eq!(mailbox.receive(), c);
+    sup.shutdown();
+}
+
+/// `AbstractProcess` whose `init` doesn't finish.
+struct Stall;
+
+impl AbstractProcess for Stall {
+    type Arg = ();
+    type State = ();
+    type Serializer = Bincode;
+    type Handlers = ();
+    type StartupError = ();
+
+    fn init(_: Config<Self>, _: ()) -> Result<(), ()> {
+        sleep(Duration::from_secs(60));
+        Ok(())
+    }
+}
+
+#[test]
+fn start_timeout_names_stalled_child() {
+    struct Inner;
+    impl Supervisor for Inner {
+        type Arg = ();
+        type Children = (A, Stall);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((0, 'b'), ()));
+            config.set_names((None, Some("stall".to_owned())));
+            config.set_start_timeout(Duration::from_millis(50));
+        }
+    }
+
+    struct Outer;
+    impl Supervisor for Outer {
+        type Arg = ();
+        type Children = (A, Inner);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((0, 'a'), ()));
+            config.set_start_timeout(Duration::from_millis(50));
+        }
+    }
+
+    let logger = Logger::link().start_as(&LOGGER_NAME, ()).unwrap();
+    let err = match Outer::link().start(()) {
+        Err(StartupError::Custom(err)) => err,
+        other => panic!("unexpected start result: {other:?}"),
+    };
+    assert_eq!(err.path, "1/stall");
+    assert_eq!(err.index, 1);
+    assert!(err.type_name.ends_with("Stall"));
+    assert_eq!(err.cause, ChildStartCause::TimedOut);
+    assert_eq!(err.started, vec![0]);
+    // The started children are shut down again, bottom-up.
+    assert_eq!(
+        logger.request(TakeLogs),
+        vec![
+            LogEvent::Init('a'),
+            LogEvent::Init('b'),
+            LogEvent::Shutdown('b'),
+            LogEvent::Shutdown('a'),
+        ]
+    );
+}
+
+#[test]
+fn supervisor_tree_start_timeout() {
+    let err = SupervisorTree::new()
+        .start_timeout(Duration::from_millis(50))
+        .child::<A>("a", (0, 'a'))
+        .supervisor("workers", |workers| workers.child::<Stall>("stall", ()))
+        .start()
+        .unwrap_err();
+    assert_eq!(err.path, "workers/stall");
+    assert!(err.type_name.ends_with("Stall"));
+    assert!(err.reason.contains("timed out"));
+}
+
+// Counter handing off its count to the next process.
+struct Resumable {
+    count: u32,
+    handoff: Option<StateHandoff>,
+}
+
+impl AbstractProcess for Resumable {
+    type Arg = RestartArg<u32>;
+    type State = Resumable;
+    type Serializer = Bincode;
+    type Handlers = (Message<Inc>, Request<Count>, Message<Panic>);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, arg: RestartArg<u32>) -> Result<Resumable, ()> {
+        Ok(Resumable {
+            count: arg.previous().unwrap_or(arg.original),
+            handoff: arg.handoff(),
+        })
+    }
+}
+
+impl MessageHandler<Inc> for Resumable {
+    fn handle(mut state: State<Self>, _: Inc) {
+        state.count += 1;
+        if let Some(handoff) = state.handoff {
+            handoff.save(&state.count);
+        }
+    }
+}
+
+impl RequestHandler<Count> for Resumable {
+    type Response = u32;
+
+    fn handle(state: State<Self>, _: Count) -> u32 {
+        state.count
+    }
+}
+
+impl MessageHandler<Panic> for Resumable {
+    fn handle(_: State<Self>, _: Panic) {
+        panic!();
+    }
+}
+
+#[test]
+fn restart_state_handoff() {
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = (Resumable, Resumable);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args((RestartArg::new(10), RestartArg::new(10)));
+            config.set_handoffs((Some(Handoff::new()), None));
+        }
+    }
+
+    let sup = Sup::link().start(()).unwrap();
+    let (resumed, reset) = sup.children();
+    for _ in 0..3 {
+        resumed.send(Inc);
+        reset.send(Inc);
+    }
+    assert_eq!(resumed.request(Count), 13);
+    assert_eq!(reset.request(Count), 13);
+
+    resumed.send(Panic);
+    reset.send(Panic);
+    sleep(Duration::from_millis(50));
+    // Only the child with a handoff resumes from its last saved state.
+    let (resumed, reset) = sup.children();
+    assert_eq!(resumed.request(Count), 13);
+    assert_eq!(reset.request(Count), 10);
+    sup.shutdown();
+}
+
+#[test]
+fn health_of_nested_supervisors() {
+    struct Inner;
+    impl Supervisor for Inner {
+        type Arg = ();
+        type Children = (A, A);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((0, 'b'), (0, 'c')));
+            config.set_health_check(Duration::from_millis(100), Duration::ZERO);
+        }
+    }
+
+    struct Outer;
+    impl Supervisor for Outer {
+        type Arg = ();
+        type Children = (A, Inner);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((0, 'a'), ()));
+            config.set_names((Some("health/a".to_owned()), None));
+            config.set_health_check(Duration::from_millis(100), Duration::from_millis(50));
+        }
+    }
+
+    let sup = Outer::link().start(()).unwrap();
+    let report = sup.health();
+    assert_eq!(report.health, Health::Healthy);
+    assert_eq!(report.children.len(), 2);
+    assert_eq!(report.children[0].name.as_deref(), Some("health/a"));
+    assert!(report.children[0].children.is_empty());
+    assert_eq!(report.children[1].children.len(), 2);
+
+    // A stopped child of the nested supervisor degrades the whole tree, once
+    // the cached report expired.
+    let (_, inner) = sup.children();
+    inner.stop_child(ChildId::Static(1)).unwrap();
+    assert!(sup.health().is_healthy());
+    sleep(Duration::from_millis(60));
+    let report = sup.health();
+    assert_eq!(report.health, Health::Degraded);
+    assert_eq!(report.children[0].health, Health::Healthy);
+    assert_eq!(report.children[1].health, Health::Degraded);
+    let health: Vec<Health> = report.children[1]
+        .children
+        .iter()
+        .map(|child| child.health)
+        .collect();
+    assert_eq!(health, vec![Health::Healthy, Health::Down]);
+}
+
+#[test]
+fn replace_and_delete_child_specs() {
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = (A, A);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((0, 'a'), (10, 'b')));
+        }
+    }
+
+    let sup = Sup::link().start(()).unwrap();
+
+    // The new argument is used by later restarts, also after a crash.
+    let spec = ChildSpec::<A>::new().arg((100, 'a')).restart_now();
+    sup.replace_child_spec(ChildId::Static(0), spec).unwrap();
+    assert_eq!(sup.children().0.request(Count), 100);
+    sup.children().0.send(Panic);
+    sleep(Duration::from_millis(10));
+    assert_eq!(sup.children().0.request(Count), 100);
+
+    // A temporary child isn't restarted anymore.
+    let spec = ChildSpec::<A>::new().restart_policy(ChildRestart::Temporary);
+    sup.replace_child_spec(ChildId::Static(1), spec).unwrap();
+    sup.children().1.send(Panic);
+    sleep(Duration::from_millis(10));
+    assert_eq!(sup.which_children()[1].status, ChildStatus::Exited);
+
+    assert_eq!(
+        sup.delete_child(ChildId::Static(0)),
+        Err(SupervisorError::ChildRunning)
+    );
+    sup.delete_child(ChildId::Static(1)).unwrap();
+    assert_eq!(sup.which_children().len(), 1);
+    assert_eq!(
+        sup.restart_child::<A>(ChildId::Static(1)),
+        Err(SupervisorError::ChildNotFound(ChildId::Static(1)))
+    );
+    assert!(matches!(
+        sup.replace_child_spec(ChildId::Static(0), ChildSpec::<Logger>::new()),
+        Err(SupervisorError::WrongType(_))
+    ));
+
+    let (id, _) = sup.start_child::<A>((20, 'c')).unwrap();
+    let spec = ChildSpec::<A>::new().arg((30, 'c')).restart_now();
+    sup.replace_child_spec(id.clone(), spec).unwrap();
+    assert_eq!(
+        sup.dynamic_child::<A>(id.clone()).unwrap().request(Count),
+        30
+    );
+    sup.stop_child(id.clone()).unwrap();
+    sup.delete_child(id).unwrap();
+    assert_eq!(sup.count_children().dynamic, 0);
+
+    sup.shutdown();
+}
+
+#[test]
+fn child_ids_are_stable(mailbox: Mailbox<SupervisorEvent>) {
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = (A, A);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((0, 'a'), (0, 'b')));
+            config.set_ids((Some("cache".to_owned()), None));
+            config.set_max_restarts(1, Duration::from_secs(10));
+        }
+    }
+
+    let sup = Sup::start(()).unwrap();
+    let children = sup.which_children();
+    assert_eq!(children[0].child, ChildId::named("cache"));
+    assert_eq!(children[1].child, ChildId::Static(1));
+    // Named children can also be found by their position.
+    let (a, b) = sup.children();
+    assert_eq!(sup.child_by_id::<A>(ChildId::named("cache")), Some(a));
+    assert_eq!(sup.child_by_id::<A>(ChildId::Static(0)), Some(a));
+    assert_eq!(sup.child_by_id::<Logger>(ChildId::named("cache")), None);
+    assert_eq!(
+        sup.restart_child::<A>(ChildId::named("missing")),
+        Err(SupervisorError::ChildNotFound(ChildId::named("missing")))
+    );
+
+    sup.subscribe_events(mailbox.this());
+    a.send(Panic);
+    for _ in 0..2 {
+        match mailbox.receive() {
+            SupervisorEvent::ChildCrashed { child, .. }
+            | SupervisorEvent::ChildRestarted { child, .. } => {
+                assert_eq!(child.child, ChildId::named("cache"));
+            }
+            event => panic!("unexpected event {event:?}"),
+        }
+    }
+
+    // The second failure exceeds the intensity, the error names the child.
+    b.send(Panic);
+    assert!(matches!(
+        mailbox.receive(),
+        SupervisorEvent::ChildCrashed { .. }
+    ));
+    match mailbox.receive() {
+        SupervisorEvent::IntensityExceeded { child, .. } => {
+            assert_eq!(child, ChildId::Static(1));
+        }
+        event => panic!("unexpected event {event:?}"),
+    }
+
+    struct Failing;
+    impl Supervisor for Failing {
+        type Arg = ();
+        type Children = (A, Stall);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((0, 'a'), ()));
+            config.set_ids((None, Some("stall".to_owned())));
+            config.set_start_timeout(Duration::from_millis(50));
+        }
+    }
+
+    let err = match Failing::link().start(()) {
+        Err(StartupError::Custom(err)) => err,
+        other => panic!("unexpected start result: {other:?}"),
+    };
+    assert_eq!(err.child, ChildId::named("stall"));
+    assert_eq!(err.path, "stall");
+}
+
+fn report_restart(child: ChildId, count: u32) {
+    let listener = Process::<String>::lookup(&"supervisor_hooks").unwrap();
+    listener.send(format!("restart {child} {count}"));
+}
+
+fn report_give_up(child: ChildId) {
+    let listener = Process::<String>::lookup(&"supervisor_hooks").unwrap();
+    listener.send(format!("give up {child}"));
+}
+
+fn panicking_hook(_: ChildId, _: u32) {
+    panic!("hook failed");
+}
+
+#[test]
+fn restart_hooks(mailbox: Mailbox<String>) {
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = (A, A);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(((0, 'a'), (0, 'b')));
+            config.set_ids((Some("cache".to_owned()), None));
+            config.set_hooks((
+                ChildHooks::new()
+                    .on_restart(report_restart)
+                    .on_give_up(report_give_up),
+                ChildHooks::new().on_restart(panicking_hook),
+            ));
+            config.set_max_restarts(3, Duration::from_secs(10));
+        }
+    }
+
+    mailbox.this().register(&"supervisor_hooks");
+    let sup = Sup::start(()).unwrap();
+    let (a, b) = sup.children();
+    a.send(Panic);
+    assert_eq!(mailbox.receive(), "restart cache 1");
+
+    // A panicking hook doesn't take the supervisor down.
+    b.send(Panic);
+    sleep(Duration::from_millis(50));
+    assert_eq!(sup.which_children()[1].restarts, 1);
+
+    // Hooks of dynamic children are set with their spec.
+    let (id, c) = sup.start_child::<A>((0, 'c')).unwrap();
+    let spec = ChildSpec::new().on_restart(report_restart);
+    sup.replace_child_spec::<A>(id.clone(), spec).unwrap();
+    c.send(Panic);
+    assert_eq!(mailbox.receive(), format!("restart {id} 1"));
+
+    // The fourth failure exceeds the intensity.
+    sup.children().0.send(Panic);
+    assert_eq!(mailbox.receive(), "give up cache");
+}
+
+/// A child that keeps the reference to its peer, received with its
+/// `SiblingRefs`.
+struct Peer {
+    peer: Option<ProcessRef<Peer>>,
+    peer_id: ChildId,
+}
+
+impl AbstractProcess for Peer {
+    type Arg = String;
+    type State = Self;
+    type Serializer = Bincode;
+    type Handlers = (Message<SiblingRefs>, Request<GetPeer>);
+    type StartupError = ();
+
+    fn init(_: Config<Self>, peer_id: String) -> Result<Self, ()> {
+        Ok(Peer {
+            peer: None,
+            peer_id: ChildId::named(peer_id),
+        })
+    }
+}
+
+impl MessageHandler<SiblingRefs> for Peer {
+    fn handle(mut state: State<Self>, siblings: SiblingRefs) {
+        state.peer = siblings.get(&state.peer_id);
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct GetPeer;
+
+impl RequestHandler<GetPeer> for Peer {
+    type Response = Option<ProcessRef<Peer>>;
+
+    fn handle(state: State<Self>, _: GetPeer) -> Self::Response {
+        state.peer
+    }
+}
+
+#[test]
+fn sibling_refs() {
+    struct Sup;
+    impl Supervisor for Sup {
+        type Arg = ();
+        type Children = (Peer, Peer);
+
+        fn init(config: &mut SupervisorConfig<Self>, _: ()) {
+            config.set_args(("pong".to_owned(), "ping".to_owned()));
+            config.set_ids((Some("ping".to_owned()), Some("pong".to_owned())));
+            config.set_names((Some("siblings/ping".to_owned()), None));
+            config.set_siblings((Some(Siblings::new()), Some(Siblings::new())));
+        }
+    }
+
+    let sup = Sup::start(()).unwrap();
+    let (ping, pong) = sup.children();
+    // Both references are delivered before the supervisor answers.
+    assert_eq!(ping.request(GetPeer), Some(pong));
+    assert_eq!(pong.request(GetPeer), Some(ping));
+    assert_eq!(ProcessRef::lookup(&"siblings/ping"), Some(ping));
+
+    // A restarted child gets the references again, its sibling updated ones.
+    let pong = sup.restart_child::<Peer>(ChildId::named("pong")).unwrap();
+    assert_eq!(pong.request(GetPeer), Some(ping));
+    assert_eq!(ping.request(GetPeer), Some(pong));
+}
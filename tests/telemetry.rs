@@ -0,0 +1,72 @@
+use std::str::FromStr;
+
+use lunatic::abstract_process;
+use lunatic::ap::{AbstractProcess, Config, TraceHeaders};
+use lunatic_test::test;
+use opentelemetry::trace::{SpanContext, TraceContextExt, TraceState};
+use opentelemetry::{Context, SpanId, TraceFlags, TraceId};
+
+struct Traced;
+
+#[abstract_process(telemetry = "opentelemetry")]
+impl Traced {
+    #[init]
+    fn init(_: Config<Self>, _: ()) -> Result<Self, ()> {
+        Ok(Traced)
+    }
+
+    #[handle_message]
+    fn ping(&self) {}
+
+    #[handle_request]
+    fn headers(&self) -> TraceHeaders {
+        TraceHeaders::current()
+    }
+}
+
+fn remote_span_context() -> SpanContext {
+    SpanContext::new(
+        TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+        SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+        TraceFlags::SAMPLED,
+        true,
+        TraceState::from_str("vendor=value").unwrap(),
+    )
+}
+
+#[test]
+fn trace_context_is_propagated() {
+    let _guard = Context::new()
+        .with_remote_span_context(remote_span_context())
+        .attach();
+    let traced = Traced::link().start(()).unwrap();
+    traced.ping();
+    // Without a tracer provider the handler span continues the caller's span.
+    let headers = traced.headers();
+    assert_eq!(
+        headers.traceparent.as_deref(),
+        Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+    );
+    assert_eq!(headers.tracestate.as_deref(), Some("vendor=value"));
+}
+
+#[test]
+fn messages_without_a_span_start_a_new_trace() {
+    let traced = Traced::link().start(()).unwrap();
+    assert_eq!(traced.headers(), TraceHeaders::default());
+}
+
+#[test]
+fn malformed_headers_are_ignored() {
+    let headers = TraceHeaders {
+        traceparent: Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7".to_owned()),
+        tracestate: None,
+    };
+    assert_eq!(headers.span_context(), None);
+
+    let headers = TraceHeaders {
+        traceparent: Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".to_owned()),
+        tracestate: Some("vendor=value".to_owned()),
+    };
+    assert_eq!(headers.span_context(), Some(remote_span_context()));
+}
@@ -1,7 +1,7 @@
 use std::io::ErrorKind;
 use std::net::IpAddr;
 
-use lunatic::net;
+use lunatic::{net, Mailbox, Process, Tag};
 use lunatic_test::test;
 
 #[test]
@@ -127,3 +127,28 @@ fn udp_broadcast_setter_getter_false() {
 
     assert_eq!(cur_broadcast, false);
 }
+
+#[test]
+fn udp_recv_into_mailbox(mailbox: Mailbox<net::Datagram>) {
+    let (addr_tag, tag) = (Tag::new(), Tag::new());
+    let this = mailbox.this();
+    Process::spawn_link(
+        (this, addr_tag, tag),
+        |(parent, addr_tag, tag), _: Mailbox<()>| {
+            let receiver = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+            // Report the bound address back as an empty datagram.
+            let peer = receiver.local_addr().unwrap();
+            parent.tag_send(addr_tag, net::Datagram { data: vec![], peer });
+            receiver.recv_into_mailbox(parent, tag).unwrap();
+        },
+    );
+    let receiver_addr = mailbox.tag_receive(&[addr_tag]).peer;
+
+    let sender = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let sender_addr = sender.local_addr().unwrap();
+    sender.send_to("P1NG".as_bytes(), receiver_addr).unwrap();
+
+    let datagram = mailbox.tag_receive(&[tag]);
+    assert_eq!(datagram.data, "P1NG".as_bytes());
+    assert_eq!(datagram.peer, sender_addr);
+}
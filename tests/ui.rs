@@ -0,0 +1,12 @@
+//! Compile errors of the macros for common mistakes.
+//!
+//! Needs to run on the host, e.g. with `cargo test --target
+//! x86_64-unknown-linux-gnu --test ui`. Set `TRYBUILD=overwrite` to update the
+//! expected messages in `tests/ui/*.stderr`.
+
+#[cfg(not(target_arch = "wasm32"))]
+#[test]
+fn ui() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
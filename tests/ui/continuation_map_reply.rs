@@ -0,0 +1,24 @@
+use lunatic::abstract_process;
+
+struct Counter(u32);
+
+#[abstract_process]
+impl Counter {
+    #[init]
+    fn init(_: lunatic::ap::Config<Self>, start: u32) -> Result<Self, ()> {
+        Ok(Self(start))
+    }
+
+    #[handle_request(map_reply = String)]
+    #[continue_with(finish)]
+    fn count(&self, resume: lunatic::ap::Resume<u32>) -> lunatic::ap::Step<u32, u32> {
+        let _ = resume;
+        lunatic::ap::Step::Pending(self.0)
+    }
+
+    fn finish(&mut self, pending: u32, reply: u32) -> lunatic::ap::Step<u32, u32> {
+        lunatic::ap::Step::Done(pending + reply)
+    }
+}
+
+fn main() {}
@@ -0,0 +1,25 @@
+use lunatic::abstract_process;
+use lunatic::ap::{Resume, Step};
+
+struct Counter(u32);
+
+#[abstract_process]
+impl Counter {
+    #[init]
+    fn init(_: lunatic::ap::Config<Self>, start: u32) -> Result<Self, ()> {
+        Ok(Self(start))
+    }
+
+    #[handle_request]
+    #[continue_with(finish)]
+    fn count(&self, resume: Resume<u32>) -> Step<u32, String> {
+        let _ = resume;
+        Step::Pending("pending".to_owned())
+    }
+
+    fn finish(&mut self, pending: u64, reply: u32) -> Step<u32, String> {
+        Step::Done(pending as u32 + reply)
+    }
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+// The message types of `Store` are `pub(crate)` in the `item-visibility-server`
+// crate, they can't be named from another crate.
+use item_visibility_server::store::__MsgWrapInsert;
+
+fn main() {
+    let _ = __MsgWrapInsert("value".to_owned());
+}
@@ -0,0 +1,19 @@
+use lunatic::abstract_process;
+
+struct Counter(u32);
+
+#[abstract_process]
+impl Counter {
+    #[init]
+    fn init(_: lunatic::ap::Config<Self>, start: u32) -> Result<Self, ()> {
+        Ok(Self(start))
+    }
+
+    #[terminate]
+    fn terminate(self) {}
+
+    #[terminate]
+    fn cleanup(self) {}
+}
+
+fn main() {}
@@ -0,0 +1,18 @@
+use lunatic::abstract_process;
+
+struct Counter(u32);
+
+#[abstract_process]
+impl Counter {
+    #[init]
+    fn init(_: lunatic::ap::Config<Self>, start: u32) -> Result<Self, ()> {
+        Ok(Self(start))
+    }
+
+    #[handle_request]
+    fn take(self) -> u32 {
+        self.0
+    }
+}
+
+fn main() {}
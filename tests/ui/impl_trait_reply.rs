@@ -0,0 +1,18 @@
+use lunatic::abstract_process;
+
+struct Names(Vec<String>);
+
+#[abstract_process]
+impl Names {
+    #[init]
+    fn init(_: lunatic::ap::Config<Self>, _: ()) -> Result<Self, ()> {
+        Ok(Self(Vec::new()))
+    }
+
+    #[handle_request]
+    fn names(&self) -> impl Iterator<Item = String> + '_ {
+        self.0.iter().cloned()
+    }
+}
+
+fn main() {}
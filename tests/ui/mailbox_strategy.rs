@@ -0,0 +1,18 @@
+use lunatic::abstract_process;
+
+struct Stack(Vec<u32>);
+
+#[abstract_process(mailbox_strategy = "lifo")]
+impl Stack {
+    #[init]
+    fn init(_: lunatic::ap::Config<Self>, _: ()) -> Result<Self, ()> {
+        Ok(Self(Vec::new()))
+    }
+
+    #[handle_message]
+    fn push(&mut self, value: u32) {
+        self.0.push(value);
+    }
+}
+
+fn main() {}
@@ -0,0 +1,18 @@
+use lunatic::abstract_process;
+
+struct Queue(Vec<u32>);
+
+#[abstract_process]
+impl Queue {
+    #[init]
+    fn init(_: lunatic::ap::Config<Self>, _: ()) -> Result<Self, ()> {
+        Ok(Self(Vec::new()))
+    }
+
+    #[handle_message(priority = 2)]
+    fn push(&mut self, value: u32) {
+        self.0.push(value);
+    }
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use lunatic::abstract_process;
+
+struct Counter(u32);
+
+#[abstract_process]
+impl Counter {
+    #[handle_message]
+    fn increment(&mut self) {
+        self.0 += 1;
+    }
+}
+
+fn main() {}
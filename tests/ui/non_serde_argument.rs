@@ -0,0 +1,20 @@
+use lunatic::abstract_process;
+
+struct Counter(u32);
+
+struct Step(u32);
+
+#[abstract_process]
+impl Counter {
+    #[init]
+    fn init(_: lunatic::ap::Config<Self>, start: u32) -> Result<Self, ()> {
+        Ok(Self(start))
+    }
+
+    #[handle_message]
+    fn increment(&mut self, step: Step) {
+        self.0 += step.0;
+    }
+}
+
+fn main() {}
@@ -0,0 +1,30 @@
+mod counter {
+    use lunatic::abstract_process;
+
+    pub struct Counter(u32);
+
+    #[abstract_process(visibility(msgs = pub(self), traits = pub))]
+    impl Counter {
+        #[init]
+        fn init(_: lunatic::ap::Config<Self>, start: u32) -> Result<Self, ()> {
+            Ok(Self(start))
+        }
+
+        #[handle_request]
+        fn count(&self) -> u32 {
+            self.0
+        }
+    }
+}
+
+use counter::CounterRequests;
+use lunatic::ap::ProcessRef;
+
+// The client trait is reachable, the message types aren't.
+fn count(counter: ProcessRef<counter::Counter>) -> u32 {
+    counter.count()
+}
+
+fn main() {
+    let _ = counter::__MsgWrapCount;
+}
@@ -0,0 +1,18 @@
+use lunatic::abstract_process;
+
+struct Counter(u32);
+
+#[abstract_process(telemetry = "zipkin")]
+impl Counter {
+    #[init]
+    fn init(_: lunatic::ap::Config<Self>, _: ()) -> Result<Self, ()> {
+        Ok(Self(0))
+    }
+
+    #[handle_message]
+    fn increment(&mut self) {
+        self.0 += 1;
+    }
+}
+
+fn main() {}
@@ -0,0 +1,16 @@
+use lunatic::abstract_process;
+
+struct Counter(u32);
+
+#[abstract_process]
+impl Counter {
+    #[init]
+    fn init(_: lunatic::ap::Config<Self>, start: u32) -> Result<Self, ()> {
+        Ok(Self(start))
+    }
+
+    #[terminate(timeout = "5 seconds")]
+    fn terminate(self) {}
+}
+
+fn main() {}
@@ -0,0 +1,16 @@
+use lunatic::abstract_process;
+
+struct Counter(u32);
+
+#[abstract_process]
+impl Counter {
+    #[warm_up]
+    fn warm_up(offset: u32) {}
+
+    #[init]
+    fn init(_: lunatic::ap::Config<Self>, start: u32, _: ()) -> Result<Self, ()> {
+        Ok(Self(start))
+    }
+}
+
+fn main() {}